@@ -1,5 +1,8 @@
 use std::alloc::{GlobalAlloc, Layout};
-use tasksapp_net::{NewTaskRequest, NewTaskResult, DeleteByIdResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tasksapp_net::{into_response, wire, FatPtr, MergeReport, Priority, TaskError, TaskResult};
+use tasksapp_net::{ImportReport, ImportTasksRequest};
+use tasksapp_net::{LOCAL_SESSION, NewTaskRequest, NewTaskResult, DeleteByIdResult, DeleteTaskResult, QueryByTagResult, TaskEvent};
 
 // ============================================================================
 //  THE SYSTEM ALLOCATOR (The Magic Fix)
@@ -23,6 +26,10 @@ unsafe extern "C" {
 
     fn host_print(ptr: i32, len: i32);
     fn send_to_server(message_ptr: i32, message_len: i32);
+
+    // User-visible files (see host_calls/files.rs)
+    fn host_file_read(name_ptr: i32, name_len: i32) -> i64;
+    fn host_file_write(name_ptr: i32, name_len: i32, data_ptr: i32, data_len: i32) -> i32;
     fn fire_and_forget(
         instance_id_ptr: i32,
         instance_id_len: i32,
@@ -62,11 +69,18 @@ fn print(s: &str) {
     }
 }
 
-fn pack_i64(ptr: i32, len: i32) -> i64 {
-    (len as i64) << 32 | (ptr as i64 & 0xFFFFFFFF)
+// Core's response buffers are ours once copied out of; this gives them back
+fn free_core_response(ptr: i32, len: i32) {
+    call_core_args("free_response", ptr, len);
 }
 
 fn call_core(func_name: &str, payload: &[u8]) -> (i32, i32) {
+    call_core_args(func_name, payload.as_ptr() as i32, payload.len() as i32)
+}
+
+// `call` forwards its two payload ints untouched, so exports taking plain
+// arguments (like `delete_task(task_id, _)`) can be called with them directly
+fn call_core_args(func_name: &str, arg0: i32, arg1: i32) -> (i32, i32) {
     let instance_id = b"tasksapp_core".to_vec();
     let func_name_bytes = func_name.as_bytes();
 
@@ -78,18 +92,21 @@ fn call_core(func_name: &str, payload: &[u8]) -> (i32, i32) {
             instance_id.len() as i32,
             func_name_bytes.as_ptr() as i32,
             func_name_bytes.len() as i32,
-            payload.as_ptr() as i32,
-            payload.len() as i32,
+            arg0,
+            arg1,
         )
     };
 
     print(&format!("call_core returned i64: {}", packed_result));
 
-    let ptr: i32 = (packed_result & 0xFFFFFFFF) as i32;
-    let len: i32 = (packed_result >> 32) as i32;
+    let FatPtr { ptr, len } = FatPtr::unpack(packed_result);
     (ptr, len)
 }
 
+tasksapp_net::export_abi_version!();
+
+tasksapp_net::export_free_response!();
+
 #[unsafe(no_mangle)]
 pub fn create_task(title_ptr: i32, title_len: i32, priority: i32) -> i64 {
     print(&"Hello from client create_task".to_string());
@@ -103,18 +120,27 @@ pub fn create_task(title_ptr: i32, title_len: i32, priority: i32) -> i64 {
 
     print(&format!("title: {}", title));
 
+    let Ok(priority) = Priority::try_from(priority) else {
+        let result: NewTaskResult = Err(TaskError::InvalidPriority);
+        return into_response(wire::serialize(&result).unwrap());
+    };
+
     // 2. Create request
     let request = NewTaskRequest {
         title,
         priority,
         completed: false,
+        due_at: None,
+        tags: Vec::new(),
+        session: LOCAL_SESSION,
+        parent_id: None,
     };
 
     print(&format!("sending request: {:#?}", request));
 
     // 3. Call core
-    let payload = bincode::serialize(&request).unwrap();
-    print(&"bincode serialize works");
+    let payload = wire::serialize(&request).unwrap();
+    print(&"wire serialize works");
 
     let (result_ptr, result_len) = call_core("new_task", &payload);
     print(&"call_core works");
@@ -123,25 +149,23 @@ pub fn create_task(title_ptr: i32, title_len: i32, priority: i32) -> i64 {
     let result_bytes =
         unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
 
-    let result: NewTaskResult = bincode::deserialize(result_bytes).expect("error deserializing");
+    let result: NewTaskResult = wire::deserialize(result_bytes).expect("error deserializing");
+    free_core_response(result_ptr, result_len);
 
     let debug: String = format!("{:?}", result);
     print(&debug);
 
     // 6. Return response
-    let response = bincode::serialize(&result).unwrap();
-    let ptr = response.as_ptr() as i32;
-    let len = response.len() as i32;
-    std::mem::forget(response); // Leak it to the host
-
-    pack_i64(ptr, len)
+    into_response(wire::serialize(&result).unwrap())
 }
 
 // Implement other exports (list_pending_tasks) similarly if needed...
+// Passes core's buffer straight through. Both of us allocate through host_alloc,
+// so the host can free it with our free_response.
 #[unsafe(no_mangle)]
 pub fn list_pending_tasks() -> i64 {
     let (result_ptr, result_len) = call_core("show_pending_tasks", &[]);
-    pack_i64(result_ptr, result_len)
+    FatPtr::new(result_ptr, result_len).pack()
 }
 
 #[unsafe(no_mangle)]
@@ -155,17 +179,162 @@ pub fn call_delete_task_by_id(id: i32) -> i64 {
     let result_bytes =
         unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
 
-    let result: DeleteByIdResult = bincode::deserialize(result_bytes).expect("error deserializing");
+    let result: DeleteByIdResult = wire::deserialize(result_bytes).expect("error deserializing");
+    free_core_response(result_ptr, result_len);
 
     let debug: String = format!("{:?}", result);
     print(&debug);
 
     // 6. Return response
-    let response = bincode::serialize(&result).unwrap();
-    let ptr = response.as_ptr() as i32;
-    let len = response.len() as i32;
-    std::mem::forget(response); // Leak it to the host
-                                // 
+    into_response(wire::serialize(&result).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub fn call_delete_task(id: i32) -> i64 {
+    print(&format!("Deleting task with id: {}", id));
+
+    let (result_ptr, result_len) = call_core_args("delete_task", id, 0);
+
+    let result_bytes =
+        unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
+
+    let result: DeleteTaskResult = wire::deserialize(result_bytes).expect("error deserializing");
+    free_core_response(result_ptr, result_len);
+    print(&format!("{:?}", result));
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub fn call_query_by_tag(tag_ptr: i32, tag_len: i32) -> i64 {
+    // The tag is already in shared memory, pass it through as-is.
+    // Like list_pending_tasks, the response is core's buffer handed on to our caller.
+    let (result_ptr, result_len) = call_core_args("query_by_tag", tag_ptr, tag_len);
+
+    let result_bytes =
+        unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
+
+    let result: QueryByTagResult = wire::deserialize(result_bytes).expect("error deserializing");
+    print(&format!("{:?}", result));
+
+    FatPtr::new(result_ptr, result_len).pack()
+}
+
+// Asks core to push TaskEvents to our on_task_event instead of us re-fetching lists
+#[unsafe(no_mangle)]
+pub fn subscribe_to_core() -> i64 {
+    let name = b"tasksapp_client";
+    let (status, _) = call_core_args("subscribe", name.as_ptr() as i32, name.len() as i32);
+    status as i64
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn on_task_event(event_ptr: i32, event_len: i32) {
+    let event_bytes = unsafe { std::slice::from_raw_parts(event_ptr as *const u8, event_len as usize) };
+    match wire::deserialize::<TaskEvent>(event_bytes) {
+        Ok(event) => print(&format!("Task event: {:?}", event)),
+        Err(_) => print(&"error deserializing task event"),
+    }
+}
+
+// Uploads everything changed locally since the last sync. The reply arrives later
+// through receive_from_server.
+#[unsafe(no_mangle)]
+pub fn sync_to_server() -> i32 {
+    upload_changes("pending_changes")
+}
+
+// Same, but as FlatBuffers (tasksapp_net's schema/sync.fbs) for servers that aren't Rust.
+// Core reads either format back, so the server may answer in the one it was sent.
+#[unsafe(no_mangle)]
+pub fn sync_to_server_flatbuffers() -> i32 {
+    upload_changes("pending_changes_flatbuffers")
+}
+
+// And as Protocol Buffers (tasksapp_net's proto/sync.proto). There's no telling protobuf from
+// wire messages by its bytes, so the answer is expected in protobuf until the next upload in
+// another format.
+#[unsafe(no_mangle)]
+pub fn sync_to_server_protobuf() -> i32 {
+    upload_changes("pending_changes_protobuf")
+}
+
+// Which merge export the server's next answer goes to, after the format we last uploaded in
+static PROTOBUF_REPLIES: AtomicBool = AtomicBool::new(false);
+
+fn upload_changes(export: &str) -> i32 {
+    PROTOBUF_REPLIES.store(export == "pending_changes_protobuf", Ordering::Relaxed);
+    // Core already serialised a SyncPush, forward it untouched
+    let (changes_ptr, changes_len) = call_core(export, &[]);
+    unsafe { send_to_server(changes_ptr, changes_len) };
+    // send_to_server copies the message
+    free_core_response(changes_ptr, changes_len);
+    0
+}
+
+// The host frees the message once we return
+#[unsafe(no_mangle)]
+pub extern "C" fn receive_from_server(message_ptr: i32, message_len: i32) {
+    // A SyncSnapshot, core merges it into the local tasks
+    let merge = if PROTOBUF_REPLIES.load(Ordering::Relaxed) {
+        "merge_server_state_protobuf"
+    } else {
+        "merge_server_state"
+    };
+    let (result_ptr, result_len) = call_core_args(merge, message_ptr, message_len);
+
+    let result_bytes =
+        unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
+    match wire::deserialize::<TaskResult<MergeReport>>(result_bytes) {
+        Ok(Ok(report)) => print(&format!("Synced: {:?}", report)),
+        Ok(Err(e)) => print(&format!("core rejected the server state: {:?}", e)),
+        Err(_) => print(&"error deserializing merge report"),
+    }
+    free_core_response(result_ptr, result_len);
+}
+
+// Writes every task to the named file in FORMAT_JSON or FORMAT_CSV. Returns 0, or -1 on failure.
+#[unsafe(no_mangle)]
+pub fn export_tasks_to_file(format: i32, name_ptr: i32, name_len: i32) -> i32 {
+    let (result_ptr, result_len) = call_core_args("export_tasks", format, 0);
+    let result_bytes =
+        unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
+    let result: TaskResult<Vec<u8>> = wire::deserialize(result_bytes).expect("error deserializing");
+    free_core_response(result_ptr, result_len);
+
+    match result {
+        Ok(data) => unsafe { host_file_write(name_ptr, name_len, data.as_ptr() as i32, data.len() as i32) },
+        Err(e) => {
+            print(&format!("Export failed: {:?}", e));
+            -1
+        }
+    }
+}
+
+// Adds the tasks from the named file; answers with TaskResult<ImportReport>
+#[unsafe(no_mangle)]
+pub fn import_tasks_from_file(format: i32, name_ptr: i32, name_len: i32) -> i64 {
+    let packed = unsafe { host_file_read(name_ptr, name_len) };
+    // -1 = no such file, 0 = unreadable
+    if packed == -1 || packed == 0 {
+        let result: TaskResult<ImportReport> = Err(TaskError::NotFound);
+        return into_response(wire::serialize(&result).unwrap());
+    }
+
+    let FatPtr { ptr, len } = FatPtr::unpack(packed);
+    let data = unsafe {
+        let data = std::slice::from_raw_parts(ptr as *const u8, len as usize).to_vec();
+        host_dealloc(ptr, len);
+        data
+    };
+
+    let payload = wire::serialize(&ImportTasksRequest { format, data }).unwrap();
+    let (result_ptr, result_len) = call_core("import_tasks", &payload);
+    let result_bytes =
+        unsafe { std::slice::from_raw_parts(result_ptr as *const u8, result_len as usize) };
+    let result: TaskResult<ImportReport> = wire::deserialize(result_bytes).expect("error deserializing");
+    free_core_response(result_ptr, result_len);
+    print(&format!("Import: {:?}", result));
 
-    pack_i64(ptr, len)
+    into_response(wire::serialize(&result).unwrap())
 }
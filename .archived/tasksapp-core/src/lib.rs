@@ -2,13 +2,30 @@ use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
 use std::alloc::{GlobalAlloc, Layout};
-use tasksapp_net::{NewTaskError, NewTaskRequest, NewTaskResult, QueryByIdResult, Task, DeleteByIdResult};
+use serde::{Deserialize, Serialize};
+use tasksapp_net::{TaskError, TaskResult, NewTaskRequest, NewTaskResult, QueryByIdResult, Task, DeleteByIdResult, DeleteTaskResult, OverdueTasksRequest, QueryByTagResult};
+use tasksapp_net::{ListTasksRequest, TaskEvent, TaskFilter, TaskPage, TaskPatch, UpdateTaskResult};
+use tasksapp_net::{OpenSessionResult, LOCAL_OWNER, LOCAL_SESSION};
+use tasksapp_net::{into_response, wire, FatPtr};
+use tasksapp_net::archive::into_archived_response;
+use tasksapp_net::{Priority, ReparentRequest, TaskNode, INVALID_PRIORITY};
+use tasksapp_net::{MergeReport, SyncChange, SyncPush, SyncSnapshot};
+use tasksapp_net::{sync_fb, sync_pb};
+use tasksapp_net::{ImportReport, ImportTasksRequest, FORMAT_CSV, FORMAT_JSON};
+use tasksapp_net::{SORT_BY_DUE, SORT_BY_ID, SORT_BY_PRIORITY, SORT_BY_TITLE};
 
 // --- ALLOCATOR ---
 unsafe extern "C" {
     fn host_alloc(size: i32) -> i32;
     fn host_dealloc(ptr: i32, size: i32);
     fn host_print(ptr: i32, len: i32);
+
+    // Host key-value storage (see host_calls/storage.rs)
+    fn host_storage_get(key_ptr: i32, key_len: i32) -> i64;
+    fn host_storage_set(key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32) -> i32;
+
+    // Dynamic linking: puts another plugin's export in our table, returns its index
+    fn host_link_call(provider_mod_ptr: i32, provider_mod_len: i32, provider_fn_ptr: i32, provider_fn_len: i32) -> i32;
 }
 
 struct HostAllocator;
@@ -33,16 +50,388 @@ fn print(s: &str) {
     unsafe { host_print(s.as_ptr() as i32, s.len() as i32); }
 }
 
-// FIX 1: Standardize Packing Helper (High=Len, Low=Ptr)
-fn pack(ptr: i32, len: i32) -> i64 {
-    (len as i64) << 32 | (ptr as i64 & 0xFFFFFFFF)
-}
-
 static DB: Lazy<Mutex<HashMap<i32, Task>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 static mut CURRENT_ID: i32 = 0;
 
+// Bad payloads are InvalidInput, unless all that's wrong is an out-of-range priority
+fn payload_error(e: wire::Error) -> TaskError {
+    match e {
+        wire::Error::Malformed(msg) if msg.starts_with(INVALID_PRIORITY) => TaskError::InvalidPriority,
+        _ => TaskError::InvalidInput,
+    }
+}
+
+// --- SESSIONS ---
+// Session id -> owner. Sessions live only as long as the instance; tasks keep their owner.
+
+static SESSIONS: Lazy<Mutex<HashMap<i32, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static mut NEXT_SESSION: i32 = LOCAL_SESSION + 1;
+
+fn owner_of(session: i32) -> Option<String> {
+    SESSIONS.lock().unwrap().get(&session).cloned()
+}
+
+// list_tasks page sizes
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+// --- NOTIFICATIONS ---
+// Subscribers' `on_task_event` exports, linked into our table. A table index is a function pointer in wasm.
+
+const EVENT_CALLBACK: &str = "on_task_event";
+
+static SUBSCRIBERS: Lazy<Mutex<Vec<(String, i32)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// Callers must not hold the DB lock: subscribers are free to call back into core
+fn notify(event: TaskEvent) {
+    let callbacks: Vec<i32> = SUBSCRIBERS.lock().unwrap().iter().map(|(_, idx)| *idx).collect();
+    if callbacks.is_empty() {
+        return;
+    }
+
+    let serialized = wire::serialize(&event).unwrap();
+    for idx in callbacks {
+        let callback: extern "C" fn(i32, i32) = unsafe { std::mem::transmute(idx as usize) };
+        callback(serialized.as_ptr() as i32, serialized.len() as i32);
+    }
+}
+
+// --- SYNC ---
+// Local changes the server hasn't confirmed yet, by task id. Always lock DB before OUTBOX.
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Pending {
+    // The task as the server last had it; None if it was created here. Base of three-way merges.
+    base: Option<Task>,
+    version: u64,
+    deleted: bool,
+}
+
+static OUTBOX: Lazy<Mutex<HashMap<i32, Pending>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Call with DB locked, before save_db, so the outbox is saved together with the change
+fn record_change(id: i32, before: Option<&Task>, version: u64, deleted: bool) {
+    let mut outbox = OUTBOX.lock().unwrap();
+    let pending = outbox.entry(id).or_insert_with(|| Pending {
+        base: before.cloned(),
+        version,
+        deleted,
+    });
+    pending.version = version;
+    pending.deleted = deleted;
+}
+
+// Fields changed locally since `base` keep the local value, the rest take the server's
+fn merge_fields(base: Option<&Task>, local: &Task, server: &Task) -> Task {
+    let Some(base) = base else {
+        return local.clone();
+    };
+    Task {
+        id: local.id,
+        title: if local.title != base.title { local.title.clone() } else { server.title.clone() },
+        priority: if local.priority != base.priority { local.priority } else { server.priority },
+        completed: if local.completed != base.completed { local.completed } else { server.completed },
+        due_at: if local.due_at != base.due_at { local.due_at } else { server.due_at },
+        tags: if local.tags != base.tags { local.tags.clone() } else { server.tags.clone() },
+        owner: if local.owner != base.owner { local.owner.clone() } else { server.owner.clone() },
+        version: local.version.max(server.version) + 1,
+        parent_id: if local.parent_id != base.parent_id { local.parent_id } else { server.parent_id },
+    }
+}
+
+// --- SUBTASKS ---
+// All of these expect DB locked and return the other tasks they changed, for notify.
+
+// Re-derives completion of `parent` and its ancestors from their children
+fn roll_up(db: &mut HashMap<i32, Task>, mut parent: Option<i32>) -> Vec<Task> {
+    let mut changed = Vec::new();
+    while let Some(id) = parent {
+        let (children, done) = db
+            .values()
+            .filter(|t| t.parent_id == Some(id))
+            .fold((0, 0), |(n, done), t| (n + 1, done + t.completed as u32));
+        // A parent whose last child went away keeps whatever status it had
+        if children == 0 {
+            break;
+        }
+        let Some(task) = db.get_mut(&id) else {
+            break;
+        };
+        if task.completed == (done == children) {
+            break;
+        }
+
+        let before = task.clone();
+        task.completed = done == children;
+        task.version += 1;
+        record_change(id, Some(&before), task.version, false);
+        changed.push(task.clone());
+        parent = task.parent_id;
+    }
+    changed
+}
+
+fn set_parent(db: &mut HashMap<i32, Task>, id: i32, parent_id: Option<i32>) -> Option<Task> {
+    let task = db.get_mut(&id)?;
+    let before = task.clone();
+    task.parent_id = parent_id;
+    task.version += 1;
+    record_change(id, Some(&before), task.version, false);
+    Some(task.clone())
+}
+
+// Its subtasks move up to its own parent rather than disappearing with it
+fn remove_task(db: &mut HashMap<i32, Task>, id: i32) -> Option<(Task, Vec<Task>)> {
+    let task = db.remove(&id)?;
+    record_change(task.id, Some(&task), task.version + 1, true);
+
+    let children: Vec<i32> = db.values().filter(|t| t.parent_id == Some(id)).map(|t| t.id).collect();
+    let mut changed: Vec<Task> = children
+        .into_iter()
+        .filter_map(|child| set_parent(db, child, task.parent_id))
+        .collect();
+    changed.extend(roll_up(db, task.parent_id));
+    Some((task, changed))
+}
+
+// Would `id` end up below itself?
+fn is_descendant(db: &HashMap<i32, Task>, id: i32, mut of: Option<i32>) -> bool {
+    while let Some(current) = of {
+        if current == id {
+            return true;
+        }
+        of = db.get(&current).and_then(|t| t.parent_id);
+    }
+    false
+}
+
+// --- IMPORT / EXPORT ---
+// One flat record per task. Versions and sync state stay behind: imports are new local tasks.
+
+#[derive(Serialize, Deserialize)]
+struct TaskRecord {
+    id: i32,
+    title: String,
+    priority: Priority,
+    completed: bool,
+    due_at: Option<i64>,
+    tags: Vec<String>,
+    owner: String,
+    parent_id: Option<i32>,
+}
+
+// CSV has no lists, so tags share one column
+#[derive(Serialize, Deserialize)]
+struct CsvRecord {
+    id: i32,
+    title: String,
+    priority: Priority,
+    completed: bool,
+    due_at: Option<i64>,
+    tags: String,
+    owner: String,
+    parent_id: Option<i32>,
+}
+
+fn encode_tasks(format: i32, records: Vec<TaskRecord>) -> Option<Vec<u8>> {
+    match format {
+        FORMAT_JSON => serde_json::to_vec_pretty(&records).ok(),
+        FORMAT_CSV => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for r in records {
+                let row = CsvRecord {
+                    id: r.id,
+                    title: r.title,
+                    priority: r.priority,
+                    completed: r.completed,
+                    due_at: r.due_at,
+                    tags: r.tags.join(";"),
+                    owner: r.owner,
+                    parent_id: r.parent_id,
+                };
+                writer.serialize(row).ok()?;
+            }
+            writer.into_inner().ok()
+        }
+        _ => None,
+    }
+}
+
+fn decode_tasks(format: i32, data: &[u8]) -> Option<Vec<TaskRecord>> {
+    match format {
+        FORMAT_JSON => serde_json::from_slice(data).ok(),
+        FORMAT_CSV => csv::Reader::from_reader(data)
+            .deserialize::<CsvRecord>()
+            .map(|row| {
+                row.ok().map(|r| TaskRecord {
+                    id: r.id,
+                    title: r.title,
+                    priority: r.priority,
+                    completed: r.completed,
+                    due_at: r.due_at,
+                    tags: r.tags.split(';').filter(|t| !t.is_empty()).map(str::to_string).collect(),
+                    owner: r.owner,
+                    parent_id: r.parent_id,
+                })
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+// --- PERSISTENCE ---
+// The whole DB is one storage value, rewritten after every mutation
+
+const DB_KEY: &str = "tasksapp_core.db";
+
+#[derive(Serialize, Deserialize)]
+struct StoredDb {
+    next_id: i32,
+    tasks: Vec<Task>,
+    // Edits made offline survive a restart
+    outbox: Vec<(i32, Pending)>,
+}
+
+fn load_db() -> Option<StoredDb> {
+    let packed = unsafe { host_storage_get(DB_KEY.as_ptr() as i32, DB_KEY.len() as i32) };
+    // -1 = nothing saved yet, 0 = storage error
+    if packed == -1 || packed == 0 {
+        return None;
+    }
+
+    let FatPtr { ptr, len } = FatPtr::unpack(packed);
+    let stored = unsafe {
+        let slice = std::slice::from_raw_parts(ptr as *const u8, len as usize);
+        // Saves from before the wire format are bincode
+        let stored = wire::deserialize(slice).ok().or_else(|| bincode::deserialize(slice).ok());
+        host_dealloc(ptr, len);
+        stored
+    };
+    if stored.is_none() {
+        print(&format!("Saved DB is corrupt, starting empty"));
+    }
+    stored
+}
+
+fn save_db(db: &HashMap<i32, Task>) -> bool {
+    let stored = StoredDb {
+        next_id: unsafe { CURRENT_ID },
+        tasks: db.values().cloned().collect(),
+        outbox: OUTBOX.lock().unwrap().iter().map(|(id, p)| (*id, p.clone())).collect(),
+    };
+    let serialized = wire::serialize(&stored).unwrap();
+    let status = unsafe {
+        host_storage_set(
+            DB_KEY.as_ptr() as i32,
+            DB_KEY.len() as i32,
+            serialized.as_ptr() as i32,
+            serialized.len() as i32,
+        )
+    };
+    if status != 0 {
+        print(&format!("Failed to save DB"));
+    }
+    status == 0
+}
+
 // --- EXPORTS ---
 
+tasksapp_net::export_abi_version!();
+
+// Packed responses below come from into_response; callers give them back through this
+tasksapp_net::export_free_response!();
+
+// Called by the host right after loading
+#[unsafe(no_mangle)]
+pub fn init() {
+    SESSIONS.lock().unwrap().insert(LOCAL_SESSION, LOCAL_OWNER.to_string());
+
+    if let Some(stored) = load_db() {
+        unsafe { CURRENT_ID = stored.next_id };
+        let mut db = DB.lock().unwrap();
+        db.extend(stored.tasks.into_iter().map(|task| (task.id, task)));
+        OUTBOX.lock().unwrap().extend(stored.outbox);
+        print(&format!("Loaded {} tasks", db.len()));
+    }
+}
+
+// Mutations already save, this is for hosts that want a checkpoint (e.g. before shutdown).
+// Returns 0 on success, -1 on failure.
+#[unsafe(no_mangle)]
+pub fn flush() -> i32 {
+    let db = DB.lock().unwrap();
+    if save_db(&db) { 0 } else { -1 }
+}
+
+// Starts a session for another user (a remote client, a second plugin, ...)
+#[unsafe(no_mangle)]
+pub fn open_session(owner_ptr: i32, owner_len: i32) -> i64 {
+    let owner = unsafe {
+        let slice = std::slice::from_raw_parts(owner_ptr as *const u8, owner_len as usize);
+        std::str::from_utf8(slice).ok().map(|o| o.trim().to_string())
+    };
+
+    let result: OpenSessionResult = match owner.filter(|o| !o.is_empty()) {
+        Some(owner) => {
+            let session = unsafe {
+                let session = NEXT_SESSION;
+                NEXT_SESSION += 1;
+                session
+            };
+            SESSIONS.lock().unwrap().insert(session, owner);
+            Ok(session)
+        }
+        None => Err(TaskError::InvalidInput),
+    };
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// The local session can't be closed. Returns 0, or -1 for unknown sessions.
+#[unsafe(no_mangle)]
+pub fn close_session(session: i32, _unused: i32) -> i64 {
+    if session == LOCAL_SESSION {
+        return -1;
+    }
+    match SESSIONS.lock().unwrap().remove(&session) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+// Registers the named plugin for change events; it must export `on_task_event(ptr, len)`.
+// Subscribing twice is a no-op. Returns 0 on success, -1 if the name isn't UTF-8 or linking failed.
+#[unsafe(no_mangle)]
+pub fn subscribe(module_ptr: i32, module_len: i32) -> i64 {
+    let module = unsafe {
+        let slice = std::slice::from_raw_parts(module_ptr as *const u8, module_len as usize);
+        match std::str::from_utf8(slice) {
+            Ok(name) => name.to_string(),
+            Err(_) => return -1,
+        }
+    };
+
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.iter().any(|(name, _)| *name == module) {
+        return 0;
+    }
+
+    let idx = unsafe {
+        host_link_call(
+            module.as_ptr() as i32,
+            module.len() as i32,
+            EVENT_CALLBACK.as_ptr() as i32,
+            EVENT_CALLBACK.len() as i32,
+        )
+    };
+    if idx < 0 {
+        return -1;
+    }
+    print(&format!("{} subscribed to task events", module));
+    subscribers.push((module, idx));
+    0
+}
+
 #[unsafe(no_mangle)]
 pub fn new_task(payload_ptr: i32, payload_len: i32) -> i64 {
     print(&format!("Hello from Core!"));
@@ -52,19 +441,26 @@ pub fn new_task(payload_ptr: i32, payload_len: i32) -> i64 {
         slice.to_vec()
     };
 
-    let request: NewTaskRequest = match bincode::deserialize(&payload) {
+    let request: NewTaskRequest = match wire::deserialize(&payload) {
         Ok(req) => req,
-        Err(_) => {
+        Err(e) => {
             print(&format!("Error deserializing request"));
-            let result = NewTaskResult::Error(NewTaskError::TaskAlreadyExists);
-            let serialized = bincode::serialize(&result).unwrap();
-            let ptr = serialized.as_ptr() as i32;
-            let len = serialized.len() as i32;
-            std::mem::forget(serialized);
-            return pack(ptr, len); // FIX: Use helper
+            let result: NewTaskResult = Err(payload_error(e));
+            return into_response(wire::serialize(&result).unwrap());
         }
     };
 
+    let Some(owner) = owner_of(request.session) else {
+        let result: NewTaskResult = Err(TaskError::UnknownSession);
+        return into_response(wire::serialize(&result).unwrap());
+    };
+
+    let mut db = DB.lock().unwrap();
+    if request.parent_id.is_some_and(|parent| !db.contains_key(&parent)) {
+        let result: NewTaskResult = Err(TaskError::NotFound);
+        return into_response(wire::serialize(&result).unwrap());
+    }
+
     let task_id = unsafe {
         CURRENT_ID += 1;
         CURRENT_ID
@@ -75,19 +471,26 @@ pub fn new_task(payload_ptr: i32, payload_len: i32) -> i64 {
         title: request.title,
         priority: request.priority,
         completed: request.completed,
+        due_at: request.due_at,
+        tags: request.tags,
+        owner,
+        version: 1,
+        parent_id: request.parent_id,
     };
 
-    let mut db = DB.lock().unwrap();
     db.insert(task_id, task.clone());
+    record_change(task_id, None, task.version, false);
+    let changed = roll_up(&mut db, task.parent_id);
+    save_db(&db);
+    drop(db);
+    notify(TaskEvent::Created(task.clone()));
+    for parent in changed {
+        notify(TaskEvent::Changed(parent));
+    }
 
-    let result = NewTaskResult::Success(task);
+    let result: NewTaskResult = Ok(task);
     
-    let serialized = bincode::serialize(&result).unwrap();
-    let ptr = serialized.as_ptr() as i32;
-    let len = serialized.len() as i32;
-    std::mem::forget(serialized);
-
-    pack(ptr, len) // FIX: Use helper
+    into_response(wire::serialize(&result).unwrap())
 }
 
 // FIX 2: Update Signature to accept arguments (even if unused)
@@ -101,12 +504,7 @@ pub fn show_pending_tasks(_ptr: i32, _len: i32) -> i64 {
         print(&format!("{:?}", task));
     }
 
-    let serialized = bincode::serialize(&pending).unwrap();
-    let ptr = serialized.as_ptr() as i32;
-    let len = serialized.len() as i32;
-    std::mem::forget(serialized);
-
-    pack(ptr, len) // FIX: Use helper
+    into_response(wire::serialize(&pending).unwrap())
 }
 
 // FIX 2: Update Signature
@@ -115,42 +513,223 @@ pub fn show_completed_tasks(_ptr: i32, _len: i32) -> i64 {
     let db = DB.lock().unwrap();
     let completed: Vec<Task> = db.values().filter(|t| t.completed).cloned().collect();
 
-    let serialized = bincode::serialize(&completed).unwrap();
-    let ptr = serialized.as_ptr() as i32;
-    let len = serialized.len() as i32;
-    std::mem::forget(serialized);
+    into_response(wire::serialize(&completed).unwrap())
+}
 
-    pack(ptr, len)
+// Paginated listing, so big DBs don't turn into one giant allocation per call
+#[unsafe(no_mangle)]
+pub fn list_tasks(payload_ptr: i32, payload_len: i32) -> i64 {
+    let page = list_page(payload_ptr, payload_len);
+    into_response(wire::serialize(&page).unwrap())
 }
 
-// These don't return i64, so they don't use the pack helper, but they work fine via direct call
+// Same request as list_tasks, but the page comes back archived for tasksapp_net::archive::view
 #[unsafe(no_mangle)]
-pub fn mark_as_completed(task_id: i32) {
-    let mut db = DB.lock().unwrap();
-    if let Some(task) = db.get_mut(&task_id) {
-        task.completed = true;
+pub fn list_tasks_archived(payload_ptr: i32, payload_len: i32) -> i64 {
+    let page = list_page(payload_ptr, payload_len);
+    into_archived_response(&page)
+}
+
+fn list_page(payload_ptr: i32, payload_len: i32) -> TaskPage {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    let request = wire::deserialize::<ListTasksRequest>(payload).unwrap_or_else(|_| {
+        print(&format!("Error deserializing list request, listing from the start"));
+        ListTasksRequest {
+            cursor: None,
+            limit: 0,
+            filter: TaskFilter::All,
+            session: None,
+        }
+    });
+    // An unknown session owns nothing, rather than seeing everything
+    let owner = request
+        .session
+        .map(|session| owner_of(session).unwrap_or_default());
+    let limit = match request.limit {
+        0 => DEFAULT_PAGE_SIZE,
+        n => n.min(MAX_PAGE_SIZE),
+    } as usize;
+
+    let db = DB.lock().unwrap();
+    let mut ids: Vec<i32> = db
+        .values()
+        .filter(|t| request.cursor.is_none_or(|cursor| t.id > cursor))
+        .filter(|t| owner.as_ref().is_none_or(|owner| t.owner == *owner))
+        .filter(|t| match &request.filter {
+            TaskFilter::All => true,
+            TaskFilter::Pending => !t.completed,
+            TaskFilter::Completed => t.completed,
+            TaskFilter::Tag(tag) => t.tags.contains(tag),
+        })
+        .map(|t| t.id)
+        .collect();
+    ids.sort_unstable();
+
+    let more = ids.len() > limit;
+    ids.truncate(limit);
+    TaskPage {
+        next_cursor: if more { ids.last().copied() } else { None },
+        tasks: ids.iter().map(|id| db[id].clone()).collect(),
     }
 }
 
+// Every task carrying the tag (exact match), by id
+#[unsafe(no_mangle)]
+pub fn query_by_tag(tag_ptr: i32, tag_len: i32) -> i64 {
+    let tag = unsafe {
+        let slice = std::slice::from_raw_parts(tag_ptr as *const u8, tag_len as usize);
+        std::str::from_utf8(slice).ok().map(|t| t.trim().to_string())
+    };
+
+    let result: QueryByTagResult = match tag.filter(|t| !t.is_empty()) {
+        Some(tag) => {
+            let db = DB.lock().unwrap();
+            let mut tasks: Vec<Task> = db.values().filter(|t| t.tags.contains(&tag)).cloned().collect();
+            tasks.sort_by_key(|t| t.id);
+            Ok(tasks)
+        }
+        None => Err(TaskError::InvalidInput),
+    };
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// Pending tasks due before `now`, soonest first
 #[unsafe(no_mangle)]
-pub fn change_priority(task_id: i32, new_priority: i32) {
+pub fn show_overdue_tasks(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    let now = match wire::deserialize::<OverdueTasksRequest>(payload) {
+        Ok(request) => request.now,
+        Err(_) => {
+            print(&format!("Error deserializing overdue request"));
+            i64::MIN // Nothing is overdue
+        }
+    };
+
+    let db = DB.lock().unwrap();
+    let mut overdue: Vec<Task> = db
+        .values()
+        .filter(|t| !t.completed && t.due_at.is_some_and(|due| due < now))
+        .cloned()
+        .collect();
+    overdue.sort_by_key(|t| (t.due_at, t.id));
+
+    into_response(wire::serialize(&overdue).unwrap())
+}
+
+// Every task, ordered by one of the SORT_BY_* keys (unknown keys fall back to id). Ties go by id.
+#[unsafe(no_mangle)]
+pub fn show_tasks_sorted(by: i32, _unused: i32) -> i64 {
+    let db = DB.lock().unwrap();
+    let mut tasks: Vec<Task> = db.values().cloned().collect();
+    tasks.sort_by_key(|t| t.id);
+    match by {
+        SORT_BY_DUE => tasks.sort_by_key(|t| (t.due_at.is_none(), t.due_at)),
+        SORT_BY_PRIORITY => tasks.sort_by_key(|t| std::cmp::Reverse(t.priority)),
+        SORT_BY_TITLE => tasks.sort_by(|a, b| a.title.cmp(&b.title)),
+        SORT_BY_ID => {}
+        _ => print(&format!("Unknown sort key {}, sorting by id", by)),
+    }
+
+    into_response(wire::serialize(&tasks).unwrap())
+}
+
+// Applies the fields set in `patch` and saves
+fn apply_patch(patch: TaskPatch) -> TaskResult<Task> {
+    if patch.title.as_ref().is_some_and(|title| title.trim().is_empty()) {
+        return Err(TaskError::InvalidInput);
+    }
+
     let mut db = DB.lock().unwrap();
-    if let Some(task) = db.get_mut(&task_id) {
-        task.priority = new_priority;
+    let task = db.get_mut(&patch.id).ok_or(TaskError::NotFound)?;
+    let before = task.clone();
+
+    if let Some(title) = patch.title {
+        task.title = title;
+    }
+    if let Some(priority) = patch.priority {
+        task.priority = priority;
+    }
+    if let Some(completed) = patch.completed {
+        task.completed = completed;
     }
+    if let Some(due_at) = patch.due_at {
+        task.due_at = due_at;
+    }
+    task.version += 1;
+
+    let task = task.clone();
+    record_change(task.id, Some(&before), task.version, false);
+    let changed = if task.completed != before.completed {
+        roll_up(&mut db, task.parent_id)
+    } else {
+        Vec::new()
+    };
+    save_db(&db);
+    drop(db);
+    notify(TaskEvent::Changed(task.clone()));
+    for parent in changed {
+        notify(TaskEvent::Changed(parent));
+    }
+    Ok(task)
 }
 
 #[unsafe(no_mangle)]
-pub fn change_title(task_id: i32, title_ptr: i32, title_len: i32) {
+pub fn update_task(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+
+    let result: UpdateTaskResult = match wire::deserialize::<TaskPatch>(payload) {
+        Ok(patch) => apply_patch(patch),
+        Err(e) => Err(payload_error(e)),
+    };
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// Single-field shorthands for update_task, kept for existing callers.
+// They answer with the same UpdateTaskResult.
+#[unsafe(no_mangle)]
+pub fn mark_as_completed(task_id: i32) -> i64 {
+    let result = apply_patch(TaskPatch {
+        id: task_id,
+        completed: Some(true),
+        ..Default::default()
+    });
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub fn change_priority(task_id: i32, new_priority: i32) -> i64 {
+    let result = match Priority::try_from(new_priority) {
+        Ok(priority) => apply_patch(TaskPatch {
+            id: task_id,
+            priority: Some(priority),
+            ..Default::default()
+        }),
+        Err(_) => Err(TaskError::InvalidPriority),
+    };
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub fn change_title(task_id: i32, title_ptr: i32, title_len: i32) -> i64 {
     let title = unsafe {
         let slice = std::slice::from_raw_parts(title_ptr as *const u8, title_len as usize);
-        String::from_utf8_lossy(slice).to_string()
+        std::str::from_utf8(slice).ok().map(str::to_string)
     };
 
-    let mut db = DB.lock().unwrap();
-    if let Some(task) = db.get_mut(&task_id) {
-        task.title = title;
-    }
+    let result = match title {
+        Some(title) => apply_patch(TaskPatch {
+            id: task_id,
+            title: Some(title),
+            ..Default::default()
+        }),
+        None => Err(TaskError::InvalidInput),
+    };
+
+    into_response(wire::serialize(&result).unwrap())
 }
 
 // FIX 2: Update Signature (just in case you call it generically later)
@@ -158,17 +737,12 @@ pub fn change_title(task_id: i32, title_ptr: i32, title_len: i32) {
 pub fn query_by_id(task_id: i32, _unused: i32) -> i64 {
     let db = DB.lock().unwrap();
 
-    let result = match db.get(&task_id) {
-        Some(task) => QueryByIdResult::Success((*task).clone()),
-        None => QueryByIdResult::NotFoundError,
+    let result: QueryByIdResult = match db.get(&task_id) {
+        Some(task) => Ok((*task).clone()),
+        None => Err(TaskError::NotFound),
     };
 
-    let serialized = bincode::serialize(&result).unwrap();
-    let ptr = serialized.as_ptr() as i32;
-    let len = serialized.len() as i32;
-    std::mem::forget(serialized);
-
-    pack(ptr, len)
+    into_response(wire::serialize(&result).unwrap())
 }
 
 #[unsafe(no_mangle)]
@@ -181,19 +755,369 @@ pub fn delete_task_by_id(task_id_ptr: i32, _unused: i32) -> i64 {
     };
         
 
-    let result = match db.remove(&task_id) {
-        Some(task) => DeleteByIdResult::Success(task.id),
-        None => DeleteByIdResult::NotFoundError,
+    let (result, changed): (DeleteByIdResult, _) = match remove_task(&mut db, task_id) {
+        Some((task, changed)) => {
+            save_db(&db);
+            (Ok(task.id), changed)
+        }
+        None => (Err(TaskError::NotFound), Vec::new()),
     };
+    drop(db);
+    if let Ok(id) = result {
+        notify(TaskEvent::Deleted(id));
+    }
+    for task in changed {
+        notify(TaskEvent::Changed(task));
+    }
 
-    let serialized = bincode::serialize(&result).unwrap();
-    let ptr = serialized.as_ptr() as i32;
-    let len = serialized.len() as i32;
-    std::mem::forget(serialized);
+    into_response(wire::serialize(&result).unwrap())
+}
 
-    pack(ptr, len)
+// Takes the id directly (second argument unused, like query_by_id) and hands back the removed task
+#[unsafe(no_mangle)]
+pub fn delete_task(task_id: i32, _unused: i32) -> i64 {
+    let mut db = DB.lock().unwrap();
+
+    let (result, changed): (DeleteTaskResult, _) = match remove_task(&mut db, task_id) {
+        Some((task, changed)) => {
+            save_db(&db);
+            (Ok(task), changed)
+        }
+        None => (Err(TaskError::NotFound), Vec::new()),
+    };
+    drop(db);
+    if let Ok(task) = &result {
+        notify(TaskEvent::Deleted(task.id));
+    }
+    for task in changed {
+        notify(TaskEvent::Changed(task));
+    }
+
+    into_response(wire::serialize(&result).unwrap())
 }
 
+// Direct subtasks of `task_id` by id, or the top-level tasks for 0
+#[unsafe(no_mangle)]
+pub fn list_children(task_id: i32, _unused: i32) -> i64 {
+    let db = DB.lock().unwrap();
+    let parent = (task_id != 0).then_some(task_id);
+
+    let result: TaskResult<Vec<TaskNode>> = if parent.is_some_and(|id| !db.contains_key(&id)) {
+        Err(TaskError::NotFound)
+    } else {
+        let mut nodes: Vec<TaskNode> = db
+            .values()
+            .filter(|t| t.parent_id == parent)
+            .map(|t| {
+                let (children, completed_children) = db
+                    .values()
+                    .filter(|c| c.parent_id == Some(t.id))
+                    .fold((0, 0), |(n, done), c| (n + 1, done + c.completed as u32));
+                TaskNode {
+                    task: t.clone(),
+                    children,
+                    completed_children,
+                }
+            })
+            .collect();
+        nodes.sort_by_key(|node| node.task.id);
+        Ok(nodes)
+    };
 
+    into_response(wire::serialize(&result).unwrap())
+}
 
+// Moves a task (with its subtasks) under another task, or to the top level
+#[unsafe(no_mangle)]
+pub fn reparent_task(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    let Ok(request) = wire::deserialize::<ReparentRequest>(payload) else {
+        let result: UpdateTaskResult = Err(TaskError::InvalidInput);
+        return into_response(wire::serialize(&result).unwrap());
+    };
 
+    let mut db = DB.lock().unwrap();
+    let mut changed = Vec::new();
+    let result: UpdateTaskResult = match db.get(&request.id).map(|t| t.parent_id) {
+        None => Err(TaskError::NotFound),
+        Some(_) if request.parent_id.is_some_and(|parent| !db.contains_key(&parent)) => Err(TaskError::NotFound),
+        Some(_) if is_descendant(&db, request.id, request.parent_id) => Err(TaskError::InvalidInput),
+        Some(old_parent) if old_parent == request.parent_id => Ok(db[&request.id].clone()),
+        Some(old_parent) => {
+            let task = set_parent(&mut db, request.id, request.parent_id).unwrap();
+            changed.extend(roll_up(&mut db, old_parent));
+            changed.extend(roll_up(&mut db, request.parent_id));
+            save_db(&db);
+            Ok(task)
+        }
+    };
+    drop(db);
+    if let Ok(task) = &result {
+        notify(TaskEvent::Changed(task.clone()));
+    }
+    for task in changed {
+        notify(TaskEvent::Changed(task));
+    }
+
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// What to upload: a SyncPush of every unconfirmed local change
+#[unsafe(no_mangle)]
+pub fn pending_changes(_ptr: i32, _len: i32) -> i64 {
+    into_response(wire::serialize(&collect_changes()).unwrap())
+}
+
+// Same SyncPush as FlatBuffers, for servers that can't read wire messages
+#[unsafe(no_mangle)]
+pub fn pending_changes_flatbuffers(_ptr: i32, _len: i32) -> i64 {
+    into_response(sync_fb::encode_push(&collect_changes()))
+}
+
+// And as Protocol Buffers (proto/sync.proto in tasksapp_net)
+#[unsafe(no_mangle)]
+pub fn pending_changes_protobuf(_ptr: i32, _len: i32) -> i64 {
+    into_response(sync_pb::encode_push(&collect_changes()))
+}
+
+fn collect_changes() -> SyncPush {
+    let db = DB.lock().unwrap();
+    let outbox = OUTBOX.lock().unwrap();
+
+    let mut changes: Vec<SyncChange> = outbox
+        .iter()
+        .filter_map(|(id, pending)| match (pending.deleted, db.get(id)) {
+            (true, _) => Some(SyncChange::Delete {
+                id: *id,
+                version: pending.version,
+            }),
+            (false, Some(task)) => Some(SyncChange::Upsert(task.clone())),
+            (false, None) => None,
+        })
+        .collect();
+    changes.sort_by_key(|change| match change {
+        SyncChange::Upsert(task) => task.id,
+        SyncChange::Delete { id, .. } => *id,
+    });
+    SyncPush { changes }
+}
+
+// Folds the server's state (a SyncSnapshot, wire or FlatBuffers) into ours and answers with
+// a TaskResult<MergeReport>.
+// - no local change: the server's copy wins if it's at least as new
+// - local change the server already has: confirmed, leaves the outbox
+// - local change, server unchanged since: ours stays queued
+// - both changed: merged field by field against the last synced copy; an edit beats a delete
+#[unsafe(no_mangle)]
+pub fn merge_server_state(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    let snapshot = if sync_fb::is_flatbuffer(payload) {
+        sync_fb::decode_snapshot(payload)
+    } else {
+        wire::deserialize::<SyncSnapshot>(payload).ok()
+    };
+    merge_snapshot(snapshot)
+}
+
+// merge_server_state for a protobuf SyncSnapshot, which has no header to tell it from wire
+#[unsafe(no_mangle)]
+pub fn merge_server_state_protobuf(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    merge_snapshot(sync_pb::decode_snapshot(payload))
+}
+
+fn merge_snapshot(snapshot: Option<SyncSnapshot>) -> i64 {
+    let Some(snapshot) = snapshot else {
+        let result: TaskResult<MergeReport> = Err(TaskError::InvalidInput);
+        return into_response(wire::serialize(&result).unwrap());
+    };
+
+    let mut report = MergeReport {
+        applied: 0,
+        conflicts: 0,
+        pending: 0,
+    };
+    let mut events = Vec::new();
+
+    let mut db = DB.lock().unwrap();
+    let mut outbox = OUTBOX.lock().unwrap();
+
+    for server in snapshot.tasks {
+        let id = server.id;
+        let local = db.get(&id);
+        let Some(pending) = outbox.get_mut(&id) else {
+            if local.is_none_or(|local| server.version >= local.version && *local != server) {
+                events.push(match local {
+                    Some(_) => TaskEvent::Changed(server.clone()),
+                    None => TaskEvent::Created(server.clone()),
+                });
+                db.insert(id, server);
+                report.applied += 1;
+            }
+            continue;
+        };
+
+        let base_version = pending.base.as_ref().map_or(0, |base| base.version);
+        if !pending.deleted && local == Some(&server) {
+            outbox.remove(&id);
+        } else if server.version <= base_version {
+            // The server hasn't moved since our last sync, our change just needs uploading
+        } else if pending.deleted {
+            report.conflicts += 1;
+            outbox.remove(&id);
+            events.push(TaskEvent::Created(server.clone()));
+            db.insert(id, server);
+        } else if let Some(local) = local {
+            report.conflicts += 1;
+            let merged = merge_fields(pending.base.as_ref(), local, &server);
+            pending.version = merged.version;
+            pending.base = Some(server);
+            events.push(TaskEvent::Changed(merged.clone()));
+            db.insert(id, merged);
+        }
+    }
+
+    for id in snapshot.deleted {
+        match outbox.get(&id) {
+            Some(pending) if pending.deleted => {
+                outbox.remove(&id);
+            }
+            // Edited here, so it goes back up instead
+            Some(_) => {}
+            None => {
+                if db.remove(&id).is_some() {
+                    events.push(TaskEvent::Deleted(id));
+                    report.applied += 1;
+                }
+            }
+        }
+    }
+
+    // Ids handed out by the server must not be reused for tasks created here
+    if let Some(max_id) = db.keys().max() {
+        unsafe { CURRENT_ID = CURRENT_ID.max(*max_id) };
+    }
+
+    report.pending = outbox.len() as u32;
+    drop(outbox);
+    save_db(&db);
+    drop(db);
+    for event in events {
+        notify(event);
+    }
+
+    let result: TaskResult<MergeReport> = Ok(report);
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// Every task as a FORMAT_JSON / FORMAT_CSV file, by id
+#[unsafe(no_mangle)]
+pub fn export_tasks(format: i32, _unused: i32) -> i64 {
+    let db = DB.lock().unwrap();
+    let mut records: Vec<TaskRecord> = db
+        .values()
+        .map(|t| TaskRecord {
+            id: t.id,
+            title: t.title.clone(),
+            priority: t.priority,
+            completed: t.completed,
+            due_at: t.due_at,
+            tags: t.tags.clone(),
+            owner: t.owner.clone(),
+            parent_id: t.parent_id,
+        })
+        .collect();
+    drop(db);
+    records.sort_by_key(|r| r.id);
+
+    let result: TaskResult<Vec<u8>> = encode_tasks(format, records).ok_or(TaskError::InvalidInput);
+    into_response(wire::serialize(&result).unwrap())
+}
+
+// Adds the tasks of an exported file. Nothing is imported if any record is malformed.
+#[unsafe(no_mangle)]
+pub fn import_tasks(payload_ptr: i32, payload_len: i32) -> i64 {
+    let payload = unsafe { std::slice::from_raw_parts(payload_ptr as *const u8, payload_len as usize) };
+    let records = wire::deserialize::<ImportTasksRequest>(payload)
+        .ok()
+        .and_then(|request| decode_tasks(request.format, &request.data))
+        // Ids only link subtasks to parents inside the file, so they must be unique there
+        .filter(|records| {
+            let mut ids: Vec<i32> = records.iter().map(|r| r.id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids.len() == records.len()
+        });
+    let Some(records) = records else {
+        let result: TaskResult<ImportReport> = Err(TaskError::InvalidInput);
+        return into_response(wire::serialize(&result).unwrap());
+    };
+
+    let mut db = DB.lock().unwrap();
+    // File ids -> our ids, handed out up front so parents can come after their subtasks
+    let new_ids: HashMap<i32, i32> = records
+        .iter()
+        .map(|r| {
+            let id = unsafe {
+                CURRENT_ID += 1;
+                CURRENT_ID
+            };
+            (r.id, id)
+        })
+        .collect();
+
+    // Links to the record itself, or closing a loop through earlier ones, are dropped: the
+    // parent walks (roll_up, is_descendant) would never end
+    let mut parents: HashMap<i32, i32> = HashMap::new();
+    for r in &records {
+        let Some(parent) = r.parent_id.filter(|parent| new_ids.contains_key(parent)) else {
+            continue;
+        };
+        let mut walk = Some(parent);
+        while let Some(current) = walk.filter(|current| *current != r.id) {
+            walk = parents.get(&current).copied();
+        }
+        if walk.is_none() {
+            parents.insert(r.id, parent);
+        }
+    }
+
+    let mut created = Vec::new();
+    for r in records {
+        let task = Task {
+            id: new_ids[&r.id],
+            title: r.title,
+            priority: r.priority,
+            completed: r.completed,
+            due_at: r.due_at,
+            tags: r.tags,
+            owner: if r.owner.is_empty() { LOCAL_OWNER.to_string() } else { r.owner },
+            version: 1,
+            // Parents missing from the file make top-level tasks
+            parent_id: parents.get(&r.id).map(|parent| new_ids[parent]),
+        };
+        record_change(task.id, None, task.version, false);
+        db.insert(task.id, task.clone());
+        created.push(task.id);
+    }
+    // Parents are done when all their subtasks are, whatever the file said
+    let mut imported_parents: Vec<i32> = parents.values().map(|parent| new_ids[parent]).collect();
+    imported_parents.sort_unstable();
+    imported_parents.dedup();
+    for parent in imported_parents {
+        roll_up(&mut db, Some(parent));
+    }
+    save_db(&db);
+    let created: Vec<Task> = created.iter().map(|id| db[id].clone()).collect();
+    drop(db);
+
+    let report = ImportReport {
+        imported: created.len() as u32,
+    };
+    for task in created {
+        notify(TaskEvent::Created(task));
+    }
+
+    let result: TaskResult<ImportReport> = Ok(report);
+    into_response(wire::serialize(&result).unwrap())
+}
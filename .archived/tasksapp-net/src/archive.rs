@@ -0,0 +1,35 @@
+// Zero-copy responses. Bincode answers have to be deserialized into fresh allocations before
+// anything can be read; archived ones are validated once and then read straight out of the
+// buffer the callee handed over, which pays off for whole task lists.
+//
+// The buffer is still a normal response (a packed FatPtr given back through free_response),
+// so `view`'s result can't outlive that call. Task, Priority and TaskPage are archivable.
+
+use crate::into_response;
+use rkyv::api::high::{HighSerializer, HighValidator};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::Error;
+use rkyv::ser::allocator::ArenaHandle;
+use rkyv::util::AlignedVec;
+use rkyv::{Archive, Portable};
+
+/// Like `into_response`, but archived with rkyv instead of a `wire` message.
+pub fn into_archived_response<T>(value: &T) -> i64
+where
+    T: for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, Error>>,
+{
+    let bytes = rkyv::to_bytes::<Error>(value).expect("archiving a response can't fail");
+    // Host allocations are 8-aligned, which covers every archived type here (nothing wider
+    // than a u64), so the copy stays readable in place
+    into_response(bytes.to_vec())
+}
+
+/// Checks `bytes` and reads the archived `T` in place. `None` if they're malformed or
+/// misaligned.
+pub fn view<T>(bytes: &[u8]) -> Option<&T::Archived>
+where
+    T: Archive,
+    T::Archived: Portable + for<'a> CheckBytes<HighValidator<'a, Error>>,
+{
+    rkyv::access::<T::Archived, Error>(bytes).ok()
+}
@@ -1,39 +1,315 @@
+pub use fat_ptr::{FatPtr, export_abi_version};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+pub mod wire;
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
+
+#[cfg(feature = "flatbuffers")]
+pub mod sync_fb;
+
+#[cfg(feature = "protobuf")]
+pub mod sync_pb;
+
+// --- RESPONSE MEMORY ---
+// Exports answer with a packed FatPtr to a buffer they allocated. Once the call returns the
+// caller owns it: copy what it needs, then give it back through the callee's `free_response`.
+
+/// Hands `bytes` out as an export response, to be released by `free_response`.
+pub fn into_response(bytes: Vec<u8>) -> i64 {
+    FatPtr::into_leaked(bytes).pack()
+}
+
+/// Releases a buffer from `into_response`.
+///
+/// # Safety
+/// `ptr`/`len` must come from `into_response` in this module, and be freed only once.
+pub unsafe fn free_response(ptr: i32, len: i32) {
+    // Empty responses never allocated, reclaim skips them
+    drop(unsafe { FatPtr::new(ptr, len).reclaim() });
+}
+
+/// Defines the `free_response(ptr, len)` export. Every module that returns responses needs one.
+#[macro_export]
+macro_rules! export_free_response {
+    () => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn free_response(ptr: i32, len: i32) {
+            unsafe { $crate::free_response(ptr, len) }
+        }
+    };
+}
+
+// Sent and stored as its number (0, 1, 2), same as the plain i32 it replaces
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(try_from = "i32", into = "i32")]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Regular = 1,
+    Urgent = 2,
+}
+
+// Doubles as the deserialization error, so core can tell it apart from other bad payloads
+pub const INVALID_PRIORITY: &str = "invalid priority";
+
+#[derive(Debug)]
+pub struct InvalidPriority(pub i32);
+
+impl std::fmt::Display for InvalidPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", INVALID_PRIORITY, self.0)
+    }
+}
+
+impl TryFrom<i32> for Priority {
+    type Error = InvalidPriority;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Priority::Low),
+            1 => Ok(Priority::Regular),
+            2 => Ok(Priority::Urgent),
+            other => Err(InvalidPriority(other)),
+        }
+    }
+}
+
+impl From<Priority> for i32 {
+    fn from(priority: Priority) -> Self {
+        priority as i32
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct Task {
     pub id: i32,
     pub title: String,
-    pub priority: i32,
+    pub priority: Priority,
     pub completed: bool,
+    // Unix seconds
+    #[serde(default)]
+    pub due_at: Option<i64>,
+    // Project/context labels, e.g. "work", "home"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Who the task belongs to, taken from the session that created it
+    #[serde(default)]
+    pub owner: String,
+    // Bumped on every change; sync compares versions to tell whose copy is newer
+    #[serde(default)]
+    pub version: u64,
+    // Subtask of this task; `None` for top-level tasks
+    #[serde(default)]
+    pub parent_id: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum NewTaskError {
-    TaskAlreadyExists,
-}
+// Always open: the user sitting at this host
+pub const LOCAL_SESSION: i32 = 0;
+pub const LOCAL_OWNER: &str = "local";
 
-#[derive(Serialize, Deserialize, Debug)]
-pub enum NewTaskResult {
-    Success(Task),
-    Error(NewTaskError),
+// Why a call failed. Every endpoint answers with a `TaskResult` built on this.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TaskError {
+    // No task with that id
+    NotFound,
+    // Payload didn't deserialize, or text wasn't UTF-8 / was empty
+    InvalidInput,
+    // Session id was never opened, or is closed
+    UnknownSession,
+    // Not one of the Priority values
+    InvalidPriority,
 }
 
+pub type TaskResult<T> = Result<T, TaskError>;
+
+pub type NewTaskResult = TaskResult<Task>;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NewTaskRequest {
     pub title: String,
-    pub priority: i32,
+    pub priority: Priority,
     pub completed: bool,
+    #[serde(default)]
+    pub due_at: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // From open_session, or LOCAL_SESSION
+    #[serde(default)]
+    pub session: i32,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
 }
 
+// Payload of show_overdue_tasks. Plugins have no clock, so the caller says what time it is.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OverdueTasksRequest {
+    pub now: i64,
+}
+
+// `by` argument of show_tasks_sorted
+pub const SORT_BY_ID: i32 = 0;
+pub const SORT_BY_DUE: i32 = 1; // Soonest first, undated last
+pub const SORT_BY_PRIORITY: i32 = 2; // Highest first
+pub const SORT_BY_TITLE: i32 = 3;
+
+pub type QueryByIdResult = TaskResult<Task>;
+
+// The deleted id
+pub type DeleteByIdResult = TaskResult<i32>;
+
+// The deleted task
+pub type DeleteTaskResult = TaskResult<Task>;
+
+// query_by_tag takes the tag as raw UTF-8 (ptr, len); InvalidInput if it's empty or not UTF-8
+pub type QueryByTagResult = TaskResult<Vec<Task>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TaskFilter {
+    All,
+    Pending,
+    Completed,
+    Tag(String),
+}
+
+// Payload of list_tasks. Pages are in id order; pass the previous page's
+// `next_cursor` to continue, `None` to start from the beginning.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListTasksRequest {
+    pub cursor: Option<i32>,
+    // 0 picks the default page size; larger values are capped
+    pub limit: u32,
+    pub filter: TaskFilter,
+    // Only tasks owned by this session's user; `None` lists everyone's
+    #[serde(default)]
+    pub session: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    // `None` once the last page was returned
+    pub next_cursor: Option<i32>,
+}
+
+// Payload of update_task. Only the fields that are `Some` change;
+// `due_at: Some(None)` clears the due date.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct TaskPatch {
+    pub id: i32,
+    pub title: Option<String>,
+    pub priority: Option<Priority>,
+    pub completed: Option<bool>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "wire::double_option::deserialize"
+    )]
+    pub due_at: Option<Option<i64>>,
+}
+
+// Also what mark_as_completed, change_priority and change_title answer with
+pub type UpdateTaskResult = TaskResult<Task>;
+
+// --- SUBTASKS ---
+// A task with children counts as completed exactly when all of them are; core keeps that up to date.
+
+// list_children(task_id, _) answers with TaskResult<Vec<TaskNode>>; task_id 0 lists top-level tasks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskNode {
+    pub task: Task,
+    // Direct subtasks, enough to draw a collapse toggle and "2/5"
+    pub children: u32,
+    pub completed_children: u32,
+}
+
+// Payload of reparent_task, answered with an UpdateTaskResult.
+// InvalidInput if the new parent is the task itself or one of its subtasks.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReparentRequest {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+}
+
+// What core sends to subscribers' `on_task_event(event_ptr, event_len)` export after each mutation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum TaskEvent {
+    Created(Task),
+    Changed(Task),
+    Deleted(i32),
+}
+
+// open_session(owner_ptr, owner_len) answers with the new session id;
+// InvalidInput if the owner is empty or not UTF-8
+pub type OpenSessionResult = TaskResult<i32>;
+
+// --- SYNC ---
+// The client uploads a SyncPush with send_to_server; the server answers through
+// receive_from_server with a SyncSnapshot, which core's merge_server_state folds in.
+// Both are `wire` messages, or FlatBuffers (schema/sync.fbs) / Protocol Buffers (proto/sync.proto)
+// for servers that aren't Rust.
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum SyncChange {
+    Upsert(Task),
+    Delete { id: i32, version: u64 },
+}
+
+// Everything changed locally since the server last confirmed it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncPush {
+    pub changes: Vec<SyncChange>,
+}
+
+// The server's current copy of the tasks it knows about
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncSnapshot {
+    pub tasks: Vec<Task>,
+    // Ids deleted on the server
+    pub deleted: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MergeReport {
+    // Server changes taken over locally
+    pub applied: u32,
+    // Tasks changed on both sides since the last sync, merged field by field
+    pub conflicts: u32,
+    // Local changes still waiting for the server
+    pub pending: u32,
+}
+
+// --- IMPORT / EXPORT ---
+// export_tasks(format, _) answers with TaskResult<Vec<u8>>, the file contents.
+// import_tasks takes an ImportTasksRequest and answers with TaskResult<ImportReport>.
+// Both use the same columns: id, title, priority, completed, due_at, tags, owner, parent_id
+// (CSV joins tags with ';').
+pub const FORMAT_JSON: i32 = 0;
+pub const FORMAT_CSV: i32 = 1;
+
 #[derive(Serialize, Deserialize, Debug)]
-pub enum QueryByIdResult {
-    Success(Task),
-    NotFoundError,
+pub struct ImportTasksRequest {
+    pub format: i32,
+    pub data: Vec<u8>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub enum DeleteByIdResult {
-    Success(i32),
-    NotFoundError,
+pub struct ImportReport {
+    // Imported tasks get fresh ids; subtasks keep pointing at their (renumbered) parents
+    pub imported: u32,
 }
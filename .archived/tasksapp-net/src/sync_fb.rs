@@ -0,0 +1,154 @@
+// Server sync as FlatBuffers (schema/sync.fbs) instead of `wire`, so the other end of
+// send_to_server can be written in anything flatc supports. Same SyncPush / SyncSnapshot
+// content either way; these convert between them and the wire bytes.
+
+mod sync_generated;
+
+pub use sync_generated::SYNC_SNAPSHOT_IDENTIFIER as IDENTIFIER;
+
+use crate::{Priority, SyncChange, SyncPush, SyncSnapshot, Task};
+use flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector, WIPOffset};
+use sync_generated as fb;
+
+/// True if `bytes` carry the sync identifier, i.e. are FlatBuffers rather than `wire`.
+/// (A wire SyncSnapshot's bytes 4..8 are the tail of its first field name, never "TSYN".)
+pub fn is_flatbuffer(bytes: &[u8]) -> bool {
+    fb::sync_snapshot_buffer_has_identifier(bytes)
+}
+
+pub fn encode_push(push: &SyncPush) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+    let changes: Vec<_> = push
+        .changes
+        .iter()
+        .map(|change| match change {
+            SyncChange::Upsert(task) => {
+                let task = build_task(&mut fbb, task);
+                fb::SyncChange::create(
+                    &mut fbb,
+                    &fb::SyncChangeArgs {
+                        task: Some(task),
+                        ..Default::default()
+                    },
+                )
+            }
+            SyncChange::Delete { id, version } => fb::SyncChange::create(
+                &mut fbb,
+                &fb::SyncChangeArgs {
+                    task: None,
+                    id: *id,
+                    version: *version,
+                },
+            ),
+        })
+        .collect();
+    let changes = fbb.create_vector(&changes);
+    let root = fb::SyncPush::create(
+        &mut fbb,
+        &fb::SyncPushArgs {
+            changes: Some(changes),
+        },
+    );
+    fbb.finish(root, Some(IDENTIFIER));
+    fbb.finished_data().to_vec()
+}
+
+pub fn encode_snapshot(snapshot: &SyncSnapshot) -> Vec<u8> {
+    let mut fbb = FlatBufferBuilder::new();
+    let tasks: Vec<_> = snapshot
+        .tasks
+        .iter()
+        .map(|task| build_task(&mut fbb, task))
+        .collect();
+    let tasks = fbb.create_vector(&tasks);
+    let deleted = fbb.create_vector(&snapshot.deleted);
+    let root = fb::SyncSnapshot::create(
+        &mut fbb,
+        &fb::SyncSnapshotArgs {
+            tasks: Some(tasks),
+            deleted: Some(deleted),
+        },
+    );
+    fbb.finish(root, Some(IDENTIFIER));
+    fbb.finished_data().to_vec()
+}
+
+/// `None` if `bytes` aren't a valid sync FlatBuffer, or hold an invalid priority.
+pub fn decode_push(bytes: &[u8]) -> Option<SyncPush> {
+    if !is_flatbuffer(bytes) {
+        return None;
+    }
+    let push = flatbuffers::root::<fb::SyncPush>(bytes).ok()?;
+    let changes = push
+        .changes()
+        .into_iter()
+        .flatten()
+        .map(|change| match change.task() {
+            Some(task) => read_task(task).map(SyncChange::Upsert),
+            None => Some(SyncChange::Delete {
+                id: change.id(),
+                version: change.version(),
+            }),
+        })
+        .collect::<Option<_>>()?;
+    Some(SyncPush { changes })
+}
+
+/// `None` if `bytes` aren't a valid sync FlatBuffer, or hold an invalid priority.
+pub fn decode_snapshot(bytes: &[u8]) -> Option<SyncSnapshot> {
+    if !is_flatbuffer(bytes) {
+        return None;
+    }
+    let snapshot = flatbuffers::root::<fb::SyncSnapshot>(bytes).ok()?;
+    let tasks = snapshot
+        .tasks()
+        .into_iter()
+        .flatten()
+        .map(read_task)
+        .collect::<Option<_>>()?;
+    let deleted = snapshot
+        .deleted()
+        .map(|ids| ids.iter().collect())
+        .unwrap_or_default();
+    Some(SyncSnapshot { tasks, deleted })
+}
+
+fn build_task<'a>(fbb: &mut FlatBufferBuilder<'a>, task: &Task) -> WIPOffset<fb::Task<'a>> {
+    let title = fbb.create_string(&task.title);
+    let tags: Vec<_> = task.tags.iter().map(|tag| fbb.create_string(tag)).collect();
+    let tags: WIPOffset<Vector<ForwardsUOffset<&str>>> = fbb.create_vector(&tags);
+    let owner = fbb.create_string(&task.owner);
+    fb::Task::create(
+        fbb,
+        &fb::TaskArgs {
+            id: task.id,
+            title: Some(title),
+            priority: fb::Priority(task.priority as i8),
+            completed: task.completed,
+            due_at: task.due_at,
+            tags: Some(tags),
+            owner: Some(owner),
+            version: task.version,
+            parent_id: task.parent_id,
+        },
+    )
+}
+
+fn read_task(task: fb::Task) -> Option<Task> {
+    Some(Task {
+        id: task.id(),
+        title: task.title().unwrap_or_default().to_string(),
+        priority: Priority::try_from(task.priority().0 as i32).ok()?,
+        completed: task.completed(),
+        due_at: task.due_at(),
+        tags: task
+            .tags()
+            .into_iter()
+            .flatten()
+            .map(str::to_string)
+            .collect(),
+        owner: task.owner().unwrap_or_default().to_string(),
+        version: task.version(),
+        parent_id: task.parent_id(),
+    })
+}
@@ -0,0 +1,595 @@
+// Accessors for schema/sync.fbs, in the shape `flatc --rust` generates. Don't edit by hand:
+// change the schema and regenerate.
+#![allow(dead_code, non_upper_case_globals, clippy::all)]
+
+use flatbuffers::{
+    Allocator, EndianScalar, FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Push,
+    SimpleToVerifyInSlice, Table, TableUnfinishedWIPOffset, VOffsetT, Vector, Verifiable, Verifier,
+    WIPOffset,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[repr(transparent)]
+pub struct Priority(pub i8);
+
+impl Priority {
+    pub const Low: Self = Self(0);
+    pub const Regular: Self = Self(1);
+    pub const Urgent: Self = Self(2);
+}
+
+impl<'a> Follow<'a> for Priority {
+    type Inner = Self;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self(unsafe { flatbuffers::read_scalar_at::<i8>(buf, loc) })
+    }
+}
+
+impl Push for Priority {
+    type Output = Priority;
+    #[inline]
+    unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+        unsafe { flatbuffers::emplace_scalar::<i8>(dst, self.0) };
+    }
+}
+
+impl EndianScalar for Priority {
+    type Scalar = i8;
+    #[inline]
+    fn to_little_endian(self) -> i8 {
+        self.0.to_le()
+    }
+    #[inline]
+    fn from_little_endian(v: i8) -> Self {
+        Self(i8::from_le(v))
+    }
+}
+
+impl Verifiable for Priority {
+    #[inline]
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        i8::run_verifier(v, pos)
+    }
+}
+
+impl SimpleToVerifyInSlice for Priority {}
+
+// --- Task ---
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Task<'a> {
+    pub _tab: Table<'a>,
+}
+
+impl<'a> Follow<'a> for Task<'a> {
+    type Inner = Task<'a>;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: unsafe { Table::new(buf, loc) },
+        }
+    }
+}
+
+impl<'a> Task<'a> {
+    pub const VT_ID: VOffsetT = 4;
+    pub const VT_TITLE: VOffsetT = 6;
+    pub const VT_PRIORITY: VOffsetT = 8;
+    pub const VT_COMPLETED: VOffsetT = 10;
+    pub const VT_DUE_AT: VOffsetT = 12;
+    pub const VT_TAGS: VOffsetT = 14;
+    pub const VT_OWNER: VOffsetT = 16;
+    pub const VT_VERSION: VOffsetT = 18;
+    pub const VT_PARENT_ID: VOffsetT = 20;
+
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: Allocator + 'bldr>(
+        fbb: &'mut_bldr mut FlatBufferBuilder<'bldr, A>,
+        args: &'args TaskArgs<'args>,
+    ) -> WIPOffset<Task<'bldr>> {
+        let mut builder = TaskBuilder::new(fbb);
+        builder.add_version(args.version);
+        if let Some(x) = args.due_at {
+            builder.add_due_at(x);
+        }
+        if let Some(x) = args.parent_id {
+            builder.add_parent_id(x);
+        }
+        if let Some(x) = args.owner {
+            builder.add_owner(x);
+        }
+        if let Some(x) = args.tags {
+            builder.add_tags(x);
+        }
+        if let Some(x) = args.title {
+            builder.add_title(x);
+        }
+        builder.add_id(args.id);
+        builder.add_completed(args.completed);
+        builder.add_priority(args.priority);
+        builder.finish()
+    }
+
+    #[inline]
+    pub fn id(&self) -> i32 {
+        unsafe { self._tab.get::<i32>(Task::VT_ID, Some(0)).unwrap() }
+    }
+    #[inline]
+    pub fn title(&self) -> Option<&'a str> {
+        unsafe { self._tab.get::<ForwardsUOffset<&str>>(Task::VT_TITLE, None) }
+    }
+    #[inline]
+    pub fn priority(&self) -> Priority {
+        unsafe {
+            self._tab
+                .get::<Priority>(Task::VT_PRIORITY, Some(Priority::Regular))
+                .unwrap()
+        }
+    }
+    #[inline]
+    pub fn completed(&self) -> bool {
+        unsafe {
+            self._tab
+                .get::<bool>(Task::VT_COMPLETED, Some(false))
+                .unwrap()
+        }
+    }
+    #[inline]
+    pub fn due_at(&self) -> Option<i64> {
+        unsafe { self._tab.get::<i64>(Task::VT_DUE_AT, None) }
+    }
+    #[inline]
+    pub fn tags(&self) -> Option<Vector<'a, ForwardsUOffset<&'a str>>> {
+        unsafe {
+            self._tab
+                .get::<ForwardsUOffset<Vector<'a, ForwardsUOffset<&'a str>>>>(Task::VT_TAGS, None)
+        }
+    }
+    #[inline]
+    pub fn owner(&self) -> Option<&'a str> {
+        unsafe { self._tab.get::<ForwardsUOffset<&str>>(Task::VT_OWNER, None) }
+    }
+    #[inline]
+    pub fn version(&self) -> u64 {
+        unsafe { self._tab.get::<u64>(Task::VT_VERSION, Some(0)).unwrap() }
+    }
+    #[inline]
+    pub fn parent_id(&self) -> Option<i32> {
+        unsafe { self._tab.get::<i32>(Task::VT_PARENT_ID, None) }
+    }
+}
+
+impl Verifiable for Task<'_> {
+    #[inline]
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<i32>("id", Self::VT_ID, false)?
+            .visit_field::<ForwardsUOffset<&str>>("title", Self::VT_TITLE, false)?
+            .visit_field::<Priority>("priority", Self::VT_PRIORITY, false)?
+            .visit_field::<bool>("completed", Self::VT_COMPLETED, false)?
+            .visit_field::<i64>("due_at", Self::VT_DUE_AT, false)?
+            .visit_field::<ForwardsUOffset<Vector<'_, ForwardsUOffset<&'_ str>>>>(
+                "tags",
+                Self::VT_TAGS,
+                false,
+            )?
+            .visit_field::<ForwardsUOffset<&str>>("owner", Self::VT_OWNER, false)?
+            .visit_field::<u64>("version", Self::VT_VERSION, false)?
+            .visit_field::<i32>("parent_id", Self::VT_PARENT_ID, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+pub struct TaskArgs<'a> {
+    pub id: i32,
+    pub title: Option<WIPOffset<&'a str>>,
+    pub priority: Priority,
+    pub completed: bool,
+    pub due_at: Option<i64>,
+    pub tags: Option<WIPOffset<Vector<'a, ForwardsUOffset<&'a str>>>>,
+    pub owner: Option<WIPOffset<&'a str>>,
+    pub version: u64,
+    pub parent_id: Option<i32>,
+}
+
+impl Default for TaskArgs<'_> {
+    #[inline]
+    fn default() -> Self {
+        TaskArgs {
+            id: 0,
+            title: None,
+            priority: Priority::Regular,
+            completed: false,
+            due_at: None,
+            tags: None,
+            owner: None,
+            version: 0,
+            parent_id: None,
+        }
+    }
+}
+
+pub struct TaskBuilder<'a: 'b, 'b, A: Allocator + 'a> {
+    fbb_: &'b mut FlatBufferBuilder<'a, A>,
+    start_: WIPOffset<TableUnfinishedWIPOffset>,
+}
+
+impl<'a: 'b, 'b, A: Allocator + 'a> TaskBuilder<'a, 'b, A> {
+    #[inline]
+    pub fn add_id(&mut self, id: i32) {
+        self.fbb_.push_slot::<i32>(Task::VT_ID, id, 0);
+    }
+    #[inline]
+    pub fn add_title(&mut self, title: WIPOffset<&'b str>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(Task::VT_TITLE, title);
+    }
+    #[inline]
+    pub fn add_priority(&mut self, priority: Priority) {
+        self.fbb_
+            .push_slot::<Priority>(Task::VT_PRIORITY, priority, Priority::Regular);
+    }
+    #[inline]
+    pub fn add_completed(&mut self, completed: bool) {
+        self.fbb_
+            .push_slot::<bool>(Task::VT_COMPLETED, completed, false);
+    }
+    #[inline]
+    pub fn add_due_at(&mut self, due_at: i64) {
+        self.fbb_.push_slot_always::<i64>(Task::VT_DUE_AT, due_at);
+    }
+    #[inline]
+    pub fn add_tags(&mut self, tags: WIPOffset<Vector<'b, ForwardsUOffset<&'b str>>>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(Task::VT_TAGS, tags);
+    }
+    #[inline]
+    pub fn add_owner(&mut self, owner: WIPOffset<&'b str>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(Task::VT_OWNER, owner);
+    }
+    #[inline]
+    pub fn add_version(&mut self, version: u64) {
+        self.fbb_.push_slot::<u64>(Task::VT_VERSION, version, 0);
+    }
+    #[inline]
+    pub fn add_parent_id(&mut self, parent_id: i32) {
+        self.fbb_
+            .push_slot_always::<i32>(Task::VT_PARENT_ID, parent_id);
+    }
+    #[inline]
+    pub fn new(fbb: &'b mut FlatBufferBuilder<'a, A>) -> TaskBuilder<'a, 'b, A> {
+        let start = fbb.start_table();
+        TaskBuilder {
+            fbb_: fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> WIPOffset<Task<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        WIPOffset::new(o.value())
+    }
+}
+
+// --- SyncChange ---
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct SyncChange<'a> {
+    pub _tab: Table<'a>,
+}
+
+impl<'a> Follow<'a> for SyncChange<'a> {
+    type Inner = SyncChange<'a>;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: unsafe { Table::new(buf, loc) },
+        }
+    }
+}
+
+impl<'a> SyncChange<'a> {
+    pub const VT_TASK: VOffsetT = 4;
+    pub const VT_ID: VOffsetT = 6;
+    pub const VT_VERSION: VOffsetT = 8;
+
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: Allocator + 'bldr>(
+        fbb: &'mut_bldr mut FlatBufferBuilder<'bldr, A>,
+        args: &'args SyncChangeArgs<'args>,
+    ) -> WIPOffset<SyncChange<'bldr>> {
+        let mut builder = SyncChangeBuilder::new(fbb);
+        builder.add_version(args.version);
+        builder.add_id(args.id);
+        if let Some(x) = args.task {
+            builder.add_task(x);
+        }
+        builder.finish()
+    }
+
+    #[inline]
+    pub fn task(&self) -> Option<Task<'a>> {
+        unsafe {
+            self._tab
+                .get::<ForwardsUOffset<Task>>(SyncChange::VT_TASK, None)
+        }
+    }
+    #[inline]
+    pub fn id(&self) -> i32 {
+        unsafe { self._tab.get::<i32>(SyncChange::VT_ID, Some(0)).unwrap() }
+    }
+    #[inline]
+    pub fn version(&self) -> u64 {
+        unsafe {
+            self._tab
+                .get::<u64>(SyncChange::VT_VERSION, Some(0))
+                .unwrap()
+        }
+    }
+}
+
+impl Verifiable for SyncChange<'_> {
+    #[inline]
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Task>>("task", Self::VT_TASK, false)?
+            .visit_field::<i32>("id", Self::VT_ID, false)?
+            .visit_field::<u64>("version", Self::VT_VERSION, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+pub struct SyncChangeArgs<'a> {
+    pub task: Option<WIPOffset<Task<'a>>>,
+    pub id: i32,
+    pub version: u64,
+}
+
+impl Default for SyncChangeArgs<'_> {
+    #[inline]
+    fn default() -> Self {
+        SyncChangeArgs {
+            task: None,
+            id: 0,
+            version: 0,
+        }
+    }
+}
+
+pub struct SyncChangeBuilder<'a: 'b, 'b, A: Allocator + 'a> {
+    fbb_: &'b mut FlatBufferBuilder<'a, A>,
+    start_: WIPOffset<TableUnfinishedWIPOffset>,
+}
+
+impl<'a: 'b, 'b, A: Allocator + 'a> SyncChangeBuilder<'a, 'b, A> {
+    #[inline]
+    pub fn add_task(&mut self, task: WIPOffset<Task<'b>>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<Task>>(SyncChange::VT_TASK, task);
+    }
+    #[inline]
+    pub fn add_id(&mut self, id: i32) {
+        self.fbb_.push_slot::<i32>(SyncChange::VT_ID, id, 0);
+    }
+    #[inline]
+    pub fn add_version(&mut self, version: u64) {
+        self.fbb_
+            .push_slot::<u64>(SyncChange::VT_VERSION, version, 0);
+    }
+    #[inline]
+    pub fn new(fbb: &'b mut FlatBufferBuilder<'a, A>) -> SyncChangeBuilder<'a, 'b, A> {
+        let start = fbb.start_table();
+        SyncChangeBuilder {
+            fbb_: fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> WIPOffset<SyncChange<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        WIPOffset::new(o.value())
+    }
+}
+
+// --- SyncPush ---
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct SyncPush<'a> {
+    pub _tab: Table<'a>,
+}
+
+impl<'a> Follow<'a> for SyncPush<'a> {
+    type Inner = SyncPush<'a>;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: unsafe { Table::new(buf, loc) },
+        }
+    }
+}
+
+impl<'a> SyncPush<'a> {
+    pub const VT_CHANGES: VOffsetT = 4;
+
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: Allocator + 'bldr>(
+        fbb: &'mut_bldr mut FlatBufferBuilder<'bldr, A>,
+        args: &'args SyncPushArgs<'args>,
+    ) -> WIPOffset<SyncPush<'bldr>> {
+        let mut builder = SyncPushBuilder::new(fbb);
+        if let Some(x) = args.changes {
+            builder.add_changes(x);
+        }
+        builder.finish()
+    }
+
+    #[inline]
+    pub fn changes(&self) -> Option<Vector<'a, ForwardsUOffset<SyncChange<'a>>>> {
+        unsafe {
+            self._tab
+                .get::<ForwardsUOffset<Vector<'a, ForwardsUOffset<SyncChange>>>>(
+                    SyncPush::VT_CHANGES,
+                    None,
+                )
+        }
+    }
+}
+
+impl Verifiable for SyncPush<'_> {
+    #[inline]
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Vector<'_, ForwardsUOffset<SyncChange>>>>(
+                "changes",
+                Self::VT_CHANGES,
+                false,
+            )?
+            .finish();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct SyncPushArgs<'a> {
+    pub changes: Option<WIPOffset<Vector<'a, ForwardsUOffset<SyncChange<'a>>>>>,
+}
+
+pub struct SyncPushBuilder<'a: 'b, 'b, A: Allocator + 'a> {
+    fbb_: &'b mut FlatBufferBuilder<'a, A>,
+    start_: WIPOffset<TableUnfinishedWIPOffset>,
+}
+
+impl<'a: 'b, 'b, A: Allocator + 'a> SyncPushBuilder<'a, 'b, A> {
+    #[inline]
+    pub fn add_changes(&mut self, changes: WIPOffset<Vector<'b, ForwardsUOffset<SyncChange<'b>>>>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(SyncPush::VT_CHANGES, changes);
+    }
+    #[inline]
+    pub fn new(fbb: &'b mut FlatBufferBuilder<'a, A>) -> SyncPushBuilder<'a, 'b, A> {
+        let start = fbb.start_table();
+        SyncPushBuilder {
+            fbb_: fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> WIPOffset<SyncPush<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        WIPOffset::new(o.value())
+    }
+}
+
+// --- SyncSnapshot ---
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct SyncSnapshot<'a> {
+    pub _tab: Table<'a>,
+}
+
+impl<'a> Follow<'a> for SyncSnapshot<'a> {
+    type Inner = SyncSnapshot<'a>;
+    #[inline]
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            _tab: unsafe { Table::new(buf, loc) },
+        }
+    }
+}
+
+impl<'a> SyncSnapshot<'a> {
+    pub const VT_TASKS: VOffsetT = 4;
+    pub const VT_DELETED: VOffsetT = 6;
+
+    pub fn create<'bldr: 'args, 'args: 'mut_bldr, 'mut_bldr, A: Allocator + 'bldr>(
+        fbb: &'mut_bldr mut FlatBufferBuilder<'bldr, A>,
+        args: &'args SyncSnapshotArgs<'args>,
+    ) -> WIPOffset<SyncSnapshot<'bldr>> {
+        let mut builder = SyncSnapshotBuilder::new(fbb);
+        if let Some(x) = args.deleted {
+            builder.add_deleted(x);
+        }
+        if let Some(x) = args.tasks {
+            builder.add_tasks(x);
+        }
+        builder.finish()
+    }
+
+    #[inline]
+    pub fn tasks(&self) -> Option<Vector<'a, ForwardsUOffset<Task<'a>>>> {
+        unsafe {
+            self._tab
+                .get::<ForwardsUOffset<Vector<'a, ForwardsUOffset<Task>>>>(
+                    SyncSnapshot::VT_TASKS,
+                    None,
+                )
+        }
+    }
+    #[inline]
+    pub fn deleted(&self) -> Option<Vector<'a, i32>> {
+        unsafe {
+            self._tab
+                .get::<ForwardsUOffset<Vector<'a, i32>>>(SyncSnapshot::VT_DELETED, None)
+        }
+    }
+}
+
+impl Verifiable for SyncSnapshot<'_> {
+    #[inline]
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Vector<'_, ForwardsUOffset<Task>>>>(
+                "tasks",
+                Self::VT_TASKS,
+                false,
+            )?
+            .visit_field::<ForwardsUOffset<Vector<'_, i32>>>("deleted", Self::VT_DELETED, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct SyncSnapshotArgs<'a> {
+    pub tasks: Option<WIPOffset<Vector<'a, ForwardsUOffset<Task<'a>>>>>,
+    pub deleted: Option<WIPOffset<Vector<'a, i32>>>,
+}
+
+pub struct SyncSnapshotBuilder<'a: 'b, 'b, A: Allocator + 'a> {
+    fbb_: &'b mut FlatBufferBuilder<'a, A>,
+    start_: WIPOffset<TableUnfinishedWIPOffset>,
+}
+
+impl<'a: 'b, 'b, A: Allocator + 'a> SyncSnapshotBuilder<'a, 'b, A> {
+    #[inline]
+    pub fn add_tasks(&mut self, tasks: WIPOffset<Vector<'b, ForwardsUOffset<Task<'b>>>>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(SyncSnapshot::VT_TASKS, tasks);
+    }
+    #[inline]
+    pub fn add_deleted(&mut self, deleted: WIPOffset<Vector<'b, i32>>) {
+        self.fbb_
+            .push_slot_always::<WIPOffset<_>>(SyncSnapshot::VT_DELETED, deleted);
+    }
+    #[inline]
+    pub fn new(fbb: &'b mut FlatBufferBuilder<'a, A>) -> SyncSnapshotBuilder<'a, 'b, A> {
+        let start = fbb.start_table();
+        SyncSnapshotBuilder {
+            fbb_: fbb,
+            start_: start,
+        }
+    }
+    #[inline]
+    pub fn finish(self) -> WIPOffset<SyncSnapshot<'a>> {
+        let o = self.fbb_.end_table(self.start_);
+        WIPOffset::new(o.value())
+    }
+}
+
+pub const SYNC_SNAPSHOT_IDENTIFIER: &str = "TSYN";
+
+#[inline]
+pub fn sync_snapshot_buffer_has_identifier(buf: &[u8]) -> bool {
+    flatbuffers::buffer_has_identifier(buf, SYNC_SNAPSHOT_IDENTIFIER, false)
+}
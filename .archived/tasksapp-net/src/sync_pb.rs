@@ -0,0 +1,163 @@
+// Server sync as Protocol Buffers (proto/sync.proto) instead of `wire`, for backends and
+// browsers that have a protobuf library but no CBOR. Unlike FlatBuffers there's no
+// identifier to sniff, so each side has to know which format it was sent.
+
+use crate::{Priority, SyncChange, SyncPush, SyncSnapshot, Task};
+use prost::Message;
+
+// Mirrors of the .proto messages, as prost-build would generate them
+pub mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Priority {
+        Low = 0,
+        Regular = 1,
+        Urgent = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Task {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+        #[prost(string, tag = "2")]
+        pub title: String,
+        #[prost(enumeration = "Priority", tag = "3")]
+        pub priority: i32,
+        #[prost(bool, tag = "4")]
+        pub completed: bool,
+        #[prost(int64, optional, tag = "5")]
+        pub due_at: Option<i64>,
+        #[prost(string, repeated, tag = "6")]
+        pub tags: Vec<String>,
+        #[prost(string, tag = "7")]
+        pub owner: String,
+        #[prost(uint64, tag = "8")]
+        pub version: u64,
+        #[prost(int32, optional, tag = "9")]
+        pub parent_id: Option<i32>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Delete {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+        #[prost(uint64, tag = "2")]
+        pub version: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SyncChange {
+        #[prost(oneof = "sync_change::Change", tags = "1, 2")]
+        pub change: Option<sync_change::Change>,
+    }
+
+    pub mod sync_change {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        pub enum Change {
+            #[prost(message, tag = "1")]
+            Upsert(super::Task),
+            #[prost(message, tag = "2")]
+            Delete(super::Delete),
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SyncPush {
+        #[prost(message, repeated, tag = "1")]
+        pub changes: Vec<SyncChange>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SyncSnapshot {
+        #[prost(message, repeated, tag = "1")]
+        pub tasks: Vec<Task>,
+        #[prost(int32, repeated, tag = "2")]
+        pub deleted: Vec<i32>,
+    }
+}
+
+use proto::sync_change::Change;
+
+pub fn encode_push(push: &SyncPush) -> Vec<u8> {
+    let changes = push
+        .changes
+        .iter()
+        .map(|change| proto::SyncChange {
+            change: Some(match change {
+                SyncChange::Upsert(task) => Change::Upsert(to_proto(task)),
+                SyncChange::Delete { id, version } => Change::Delete(proto::Delete {
+                    id: *id,
+                    version: *version,
+                }),
+            }),
+        })
+        .collect();
+    proto::SyncPush { changes }.encode_to_vec()
+}
+
+pub fn encode_snapshot(snapshot: &SyncSnapshot) -> Vec<u8> {
+    proto::SyncSnapshot {
+        tasks: snapshot.tasks.iter().map(to_proto).collect(),
+        deleted: snapshot.deleted.clone(),
+    }
+    .encode_to_vec()
+}
+
+/// `None` if `bytes` aren't a SyncPush, or hold an invalid priority or an empty change.
+pub fn decode_push(bytes: &[u8]) -> Option<SyncPush> {
+    let push = proto::SyncPush::decode(bytes).ok()?;
+    let changes = push
+        .changes
+        .into_iter()
+        .map(|change| match change.change? {
+            Change::Upsert(task) => from_proto(task).map(SyncChange::Upsert),
+            Change::Delete(delete) => Some(SyncChange::Delete {
+                id: delete.id,
+                version: delete.version,
+            }),
+        })
+        .collect::<Option<_>>()?;
+    Some(SyncPush { changes })
+}
+
+/// `None` if `bytes` aren't a SyncSnapshot, or hold an invalid priority.
+pub fn decode_snapshot(bytes: &[u8]) -> Option<SyncSnapshot> {
+    let snapshot = proto::SyncSnapshot::decode(bytes).ok()?;
+    let tasks = snapshot
+        .tasks
+        .into_iter()
+        .map(from_proto)
+        .collect::<Option<_>>()?;
+    Some(SyncSnapshot {
+        tasks,
+        deleted: snapshot.deleted,
+    })
+}
+
+fn to_proto(task: &Task) -> proto::Task {
+    proto::Task {
+        id: task.id,
+        title: task.title.clone(),
+        priority: task.priority as i32,
+        completed: task.completed,
+        due_at: task.due_at,
+        tags: task.tags.clone(),
+        owner: task.owner.clone(),
+        version: task.version,
+        parent_id: task.parent_id,
+    }
+}
+
+fn from_proto(task: proto::Task) -> Option<Task> {
+    Some(Task {
+        id: task.id,
+        title: task.title,
+        priority: Priority::try_from(task.priority).ok()?,
+        completed: task.completed,
+        due_at: task.due_at,
+        tags: task.tags,
+        owner: task.owner,
+        version: task.version,
+        parent_id: task.parent_id,
+    })
+}
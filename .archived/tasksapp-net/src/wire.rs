@@ -0,0 +1,70 @@
+// How every message in this crate travels between plugins and is stored: a version byte, then
+// the message as CBOR with named fields. Unlike bincode the fields carry their names, so a
+// message keeps decoding when the other side was built against an older or newer tasksapp-net.
+//
+// Evolution rules:
+// - New fields get `#[serde(default)]`, so messages from older senders still decode. Decoders
+//   skip fields they don't know, so newer senders are fine too.
+// - Never rename a field or change its type; add a new one and leave the old one defaulted.
+// - Enum variants can be added, but only peers that know them can decode a message using one.
+// - Anything else bumps WIRE_VERSION; decoders turn away versions newer than their own.
+//
+// `serialize` / `deserialize` mirror bincode's, so IDL stubs use them through `codec`.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub const WIRE_VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Empty,
+    // Sent by a newer tasksapp-net, or not a wire message at all
+    UnknownVersion(u8),
+    Malformed(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty message"),
+            Error::UnknownVersion(version) => write!(f, "unknown wire version {}", version),
+            Error::Malformed(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn serialize<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![WIRE_VERSION];
+    ciborium::into_writer(value, &mut bytes).map_err(|e| Error::Malformed(e.to_string()))?;
+    Ok(bytes)
+}
+
+pub fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let (&version, body) = bytes.split_first().ok_or(Error::Empty)?;
+    if version == 0 || version > WIRE_VERSION {
+        return Err(Error::UnknownVersion(version));
+    }
+    ciborium::from_reader(body).map_err(|e| match e {
+        // What a Deserialize impl reported, e.g. INVALID_PRIORITY
+        ciborium::de::Error::Semantic(_, msg) => Error::Malformed(msg),
+        e => Error::Malformed(e.to_string()),
+    })
+}
+
+// For `Option<Option<T>>` fields, where `Some(None)` means something. CBOR writes both `None`s
+// as null, so the outer one is left out instead (with `skip_serializing_if`) and null always
+// decodes as `Some(None)`.
+pub(crate) mod double_option {
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Some)
+    }
+}
@@ -1,11 +1,13 @@
-use ecs_protocol::{CAPABILITY_TUI, StandardIds};
+use ecs_protocol::kernel::{EcsKernel, SystemFn};
+use fat_ptr::FatPtr;
 use once_cell::sync::Lazy;
 use rustc_hash::{FxHashMap, FxHashSet};
-use std::alloc::{GlobalAlloc, Layout};
+use std::alloc::Layout;
 use std::panic;
 use std::sync::{Arc, Mutex};
 
 // --- HOST IMPORTS ---
+#[cfg(not(any(test, feature = "mock-host")))]
 #[link(wasm_import_module = "env")]
 extern "C" {
     fn host_alloc(size: i32) -> i32;
@@ -14,30 +16,65 @@ extern "C" {
     fn host_link_call(m_ptr: *const u8, m_len: usize, f_ptr: *const u8, f_len: usize) -> i32;
 }
 
-// --- ALLOCATOR ---
-struct HostAllocator;
-unsafe impl GlobalAlloc for HostAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let size = layout.size();
-        if size == 0 {
-            return std::ptr::null_mut();
-        }
-        let ptr = host_alloc(size as i32);
-        ptr as *mut u8
+// Natively there's no host: allocations are the system's and prints go to stdout. Nothing can
+// be linked, so systems are registered straight into the world there.
+#[cfg(any(test, feature = "mock-host"))]
+mod mock {
+    pub fn print(s: &str) {
+        println!("{}", s);
     }
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        host_dealloc(ptr as i32, layout.size() as i32);
+
+    pub unsafe fn host_link_call(_: *const u8, _: usize, _: *const u8, _: usize) -> i32 {
+        panic!("no host to link systems through")
     }
 }
+#[cfg(any(test, feature = "mock-host"))]
+use mock::*;
 
-#[global_allocator]
-static ALLOCATOR: HostAllocator = HostAllocator;
+// --- ALLOCATOR ---
+// host_alloc hands out 8-byte aligned blocks. Bigger alignments (component columns) take a
+// larger block and keep its address in the 4 bytes before the aligned pointer.
+#[cfg(not(any(test, feature = "mock-host")))]
+mod allocator {
+    use super::{host_alloc, host_dealloc};
+    use std::alloc::{GlobalAlloc, Layout};
+
+    const HOST_ALIGN: usize = 8;
 
-static mut POS_ID: i32 = -1;
-static mut TILE_ID: i32 = -1; // Was VEL_ID
-static mut CURSOR_ID: i32 = -1; // Was SPRITE_ID
+    struct HostAllocator;
+    unsafe impl GlobalAlloc for HostAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let size = layout.size();
+            if size == 0 {
+                return std::ptr::null_mut();
+            }
+            if layout.align() <= HOST_ALIGN {
+                return host_alloc(size as i32) as *mut u8;
+            }
+            let block = host_alloc((size + layout.align() + 4) as i32);
+            if block == 0 {
+                return std::ptr::null_mut();
+            }
+            let aligned = (block as usize + 4 + layout.align() - 1) & !(layout.align() - 1);
+            *((aligned - 4) as *mut u32) = block as u32;
+            aligned as *mut u8
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            if layout.align() <= HOST_ALIGN {
+                host_dealloc(ptr as i32, layout.size() as i32);
+                return;
+            }
+            let block = *((ptr as usize - 4) as *const u32);
+            host_dealloc(block as i32, (layout.size() + layout.align() + 4) as i32);
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: HostAllocator = HostAllocator;
+}
 
 // --- UTILS ---
+#[cfg(not(any(test, feature = "mock-host")))]
 fn print(s: &str) {
     unsafe {
         host_print(s.as_ptr() as i32, s.len() as i32);
@@ -54,8 +91,6 @@ pub extern "C" fn setup_panic_hook() {
 
 // --- ECS STRUCTURES (Your Archetype Logic + FxHash) ---
 
-type SystemFn = extern "C" fn(i32);
-
 #[derive(Clone)]
 struct SystemMeta {
     name: String,
@@ -65,53 +100,147 @@ struct SystemMeta {
     dependencies: Vec<String>,
 }
 
+// Systems in one stage touch no component another one writes, so they could run in any order
+// (or at once); stages run one after another, each after those of its systems' dependencies.
+impl SystemMeta {
+    // A component one of them writes and the other uses
+    fn conflict(&self, other: &SystemMeta) -> Option<i32> {
+        let writes = |a: &SystemMeta, b: &SystemMeta| {
+            a.writes
+                .iter()
+                .copied()
+                .find(|id| b.reads.contains(id) || b.writes.contains(id))
+        };
+        writes(self, other).or_else(|| writes(other, self))
+    }
+}
+
 struct Stage {
+    names: Vec<String>,
     systems: Vec<SystemFn>,
+    reads: FxHashSet<i32>,
+    writes: FxHashSet<i32>,
 }
 
+impl Stage {
+    fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            systems: Vec::new(),
+            reads: FxHashSet::default(),
+            writes: FxHashSet::default(),
+        }
+    }
+
+    // Whether `meta` writes what the stage touches, or touches what it writes
+    fn conflicts(&self, meta: &SystemMeta) -> bool {
+        meta.writes
+            .iter()
+            .any(|id| self.reads.contains(id) || self.writes.contains(id))
+            || meta.reads.iter().any(|id| self.writes.contains(id))
+    }
+
+    fn push(&mut self, meta: &SystemMeta) {
+        self.names.push(meta.name.clone());
+        self.systems.push(meta.func);
+        self.reads.extend(meta.reads.iter().copied());
+        self.writes.extend(meta.writes.iter().copied());
+    }
+}
+
+// One component's values for every row of a table, `stride` bytes apart in a buffer allocated
+// with the component's alignment, so the pointers guests get back are aligned for it.
 struct Column {
-    data: Vec<u8>,
+    ptr: *mut u8,
+    // Rows in use and rows allocated
+    len: usize,
+    cap: usize,
     stride: usize,
+    align: usize,
 }
 
+// The world lives behind a Mutex in a static; plugins are single-threaded anyway
+unsafe impl Send for Column {}
+
 impl Column {
-    fn new(stride: usize, capacity: usize) -> Self {
-        Self {
-            data: Vec::with_capacity(capacity * stride),
-            stride,
+    fn new(layout: Layout, capacity: usize) -> Self {
+        let mut column = Self {
+            // Aligned but dangling until something is allocated
+            ptr: layout.align() as *mut u8,
+            len: 0,
+            cap: 0,
+            stride: layout.pad_to_align().size(),
+            align: layout.align(),
+        };
+        column.reserve(capacity);
+        column
+    }
+
+    fn layout(&self, rows: usize) -> Layout {
+        Layout::from_size_align(rows * self.stride, self.align).expect("column too large")
+    }
+
+    // Room for at least `rows` rows in all
+    fn reserve(&mut self, rows: usize) {
+        if rows <= self.cap {
+            return;
+        }
+        if self.stride == 0 {
+            self.cap = usize::MAX;
+            return;
         }
+        let new_cap = rows.max(self.cap * 2);
+        let new_layout = self.layout(new_cap);
+        let new_ptr = unsafe {
+            if self.cap == 0 {
+                std::alloc::alloc(new_layout)
+            } else {
+                std::alloc::realloc(self.ptr, self.layout(self.cap), new_layout.size())
+            }
+        };
+        if new_ptr.is_null() {
+            std::alloc::handle_alloc_error(new_layout);
+        }
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+    }
+
+    fn byte_len(&self) -> usize {
+        self.len * self.stride
     }
 
     unsafe fn push(&mut self, src_ptr: *const u8) {
-        let current_len = self.data.len();
-        let new_len = current_len + self.stride;
-        if new_len > self.data.capacity() {
-            self.data.reserve(self.data.capacity().max(new_len));
-        }
-        self.data.set_len(new_len);
-        let dest_ptr = self.data.as_mut_ptr().add(current_len);
+        self.reserve(self.len + 1);
+        let dest_ptr = self.ptr.add(self.byte_len());
         std::ptr::copy_nonoverlapping(src_ptr, dest_ptr, self.stride);
+        self.len += 1;
     }
 
     unsafe fn swap_remove(&mut self, row: usize) -> Vec<u8> {
-        let count = self.data.len() / self.stride;
-        let last_index = count - 1;
+        let last_index = self.len - 1;
         let mut removed_bytes = vec![0u8; self.stride];
 
         // Copy removed data
-        let src_ptr = self.data.as_ptr().add(row * self.stride);
+        let src_ptr = self.ptr.add(row * self.stride);
         std::ptr::copy_nonoverlapping(src_ptr, removed_bytes.as_mut_ptr(), self.stride);
 
         if row != last_index {
-            let last_ptr = self.data.as_ptr().add(last_index * self.stride);
-            let hole_ptr = self.data.as_mut_ptr().add(row * self.stride);
-            std::ptr::copy_nonoverlapping(last_ptr, hole_ptr, self.stride);
+            let last_ptr = self.ptr.add(last_index * self.stride);
+            std::ptr::copy_nonoverlapping(last_ptr, src_ptr, self.stride);
         }
-        self.data.set_len(last_index * self.stride);
+        self.len = last_index;
         removed_bytes
     }
 }
 
+impl Drop for Column {
+    fn drop(&mut self) {
+        if self.cap > 0 && self.stride > 0 {
+            unsafe { std::alloc::dealloc(self.ptr, self.layout(self.cap)) };
+        }
+    }
+}
+
 struct Table {
     columns: FxHashMap<i32, Column>,
     entities: Vec<i32>,
@@ -124,8 +253,8 @@ impl Table {
             entities: Vec::new(),
         }
     }
-    fn add_column(&mut self, comp_id: i32, stride: usize) {
-        self.columns.insert(comp_id, Column::new(stride, 10));
+    fn add_column(&mut self, comp_id: i32, layout: Layout) {
+        self.columns.insert(comp_id, Column::new(layout, 10));
     }
     fn swap_remove_entity(&mut self, row: usize) -> Option<i32> {
         let last_val = self.entities.pop()?;
@@ -137,52 +266,51 @@ impl Table {
     }
 }
 
+#[derive(Default)]
 pub struct EcsWorld {
     tables: Vec<Table>,
     archetype_map: FxHashMap<Vec<i32>, usize>,
     entity_index: FxHashMap<i32, (usize, usize)>,
     next_entity_id: i32,
-    component_strides: FxHashMap<i32, usize>,
+    component_layouts: FxHashMap<i32, Layout>,
     systems: FxHashMap<String, SystemMeta>,
     schedule: Vec<Stage>,
+    resources: FxHashMap<i32, Box<[u8]>>,
+    // What the last sys_query_tables answered
+    query_buffer: Vec<i32>,
 }
 
 // Global Singleton
-pub static WORLD: Lazy<Arc<Mutex<EcsWorld>>> = Lazy::new(|| {
-    Arc::new(Mutex::new(EcsWorld {
-        tables: Vec::new(),
-        archetype_map: FxHashMap::default(),
-        entity_index: FxHashMap::default(),
-        next_entity_id: 0,
-        component_strides: FxHashMap::default(),
-        systems: FxHashMap::default(),
-        schedule: Vec::new(),
-    }))
-});
+pub static WORLD: Lazy<Arc<Mutex<EcsWorld>>> = Lazy::new(Default::default);
+
+// The kernel syscalls (ecs_protocol::kernel), so plugins written for ecs-core run on this
+// kernel too. Components registered through them get ids after the ones picked by plugins.
+ecs_protocol::export_kernel!(WORLD.lock().unwrap());
 
 // --- API EXPORTS ---
 
 #[no_mangle]
-pub extern "C" fn register_component(id: i32, size: i32, _align: i32) {
+pub extern "C" fn register_component(id: i32, size: i32, align: i32) {
     let mut world = WORLD.lock().unwrap();
-    world.component_strides.insert(id, size as usize);
+    world.define_component(id, size.max(0) as usize, align.max(1) as usize);
 }
 
+/// # Safety
+/// Both names are `len` bytes of UTF-8.
 #[no_mangle]
-pub extern "C" fn register_system(
+pub unsafe extern "C" fn register_system(
     mod_ptr: *const u8,
     mod_len: usize,
     fn_ptr: *const u8,
     fn_len: usize,
 ) {
-    let fn_idx = unsafe { host_link_call(mod_ptr, mod_len, fn_ptr, fn_len) };
+    let fn_idx = host_link_call(mod_ptr, mod_len, fn_ptr, fn_len);
 
     // 2. Read the System Name for our Hash Map
-    let name =
-        unsafe { String::from_utf8_lossy(std::slice::from_raw_parts(fn_ptr, fn_len)).to_string() };
+    let name = read_name(fn_ptr, fn_len);
 
     // We trust the Host put the correct function at this index in OUR table.
-    let func: SystemFn = unsafe { std::mem::transmute(fn_idx as usize) };
+    let func: SystemFn = std::mem::transmute(fn_idx as usize);
 
     print(&format!(
         "[Core] Linked system '{}' (Table Index: {})",
@@ -202,6 +330,90 @@ pub extern "C" fn register_system(
     );
 }
 
+unsafe fn read_name(ptr: *const u8, len: usize) -> String {
+    String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len)).to_string()
+}
+
+unsafe fn read_ids(ptr: *const i32, count: usize) -> FxHashSet<i32> {
+    if count == 0 {
+        return FxHashSet::default();
+    }
+    std::slice::from_raw_parts(ptr, count)
+        .iter()
+        .copied()
+        .collect()
+}
+
+// The component ids a registered system reads and writes (i32 arrays of `reads_len` and
+// `writes_len`), replacing what it declared before. Writing one counts as reading it too.
+/// # Safety
+/// The name is `name_len` bytes and each array as long as its length.
+#[no_mangle]
+pub unsafe extern "C" fn declare_system_access(
+    name_ptr: *const u8,
+    name_len: usize,
+    reads_ptr: *const i32,
+    reads_len: usize,
+    writes_ptr: *const i32,
+    writes_len: usize,
+) {
+    let name = read_name(name_ptr, name_len);
+    let reads = read_ids(reads_ptr, reads_len);
+    let writes = read_ids(writes_ptr, writes_len);
+    let mut world = WORLD.lock().unwrap();
+    let unknown: Vec<String> = reads
+        .union(&writes)
+        .filter(|id| !world.component_layouts.contains_key(id))
+        .map(|id| id.to_string())
+        .collect();
+    let Some(meta) = world.systems.get_mut(&name) else {
+        print(&format!(
+            "[Core] declare_system_access: no system '{}'",
+            name
+        ));
+        return;
+    };
+    meta.reads = reads;
+    meta.writes = writes;
+    if !unknown.is_empty() {
+        let text = format!(
+            "[Core] '{}' uses unregistered components {}",
+            name,
+            unknown.join(", ")
+        );
+        print(&text);
+    }
+}
+
+// `name` runs in a later stage than `before_name`
+/// # Safety
+/// Both names are `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn declare_system_dependency(
+    name_ptr: *const u8,
+    name_len: usize,
+    before_ptr: *const u8,
+    before_len: usize,
+) {
+    let name = read_name(name_ptr, name_len);
+    let before = read_name(before_ptr, before_len);
+    if name == before {
+        print(&format!("[Core] '{}' can't depend on itself", name));
+        return;
+    }
+    let mut world = WORLD.lock().unwrap();
+    let Some(meta) = world.systems.get_mut(&name) else {
+        print(&format!(
+            "[Core] declare_system_dependency: no system '{}'",
+            name
+        ));
+        return;
+    };
+    if !meta.dependencies.contains(&before) {
+        meta.dependencies.push(before);
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn spawn_entity() -> i32 {
     let mut world = WORLD.lock().unwrap();
@@ -210,82 +422,150 @@ pub extern "C" fn spawn_entity() -> i32 {
     id
 }
 
-#[no_mangle]
-pub extern "C" fn add_component(entity_id: i32, comp_id: i32, data_ptr: i32) {
-    let mut world = WORLD.lock().unwrap();
+impl EcsWorld {
+    // The table for exactly `types` (sorted), created if no entity had them before
+    fn table_for(&mut self, types: &[i32]) -> usize {
+        if let Some(&idx) = self.archetype_map.get(types) {
+            return idx;
+        }
+        let mut new_table = Table::new();
+        for &cid in types {
+            new_table.add_column(cid, self.layout_of(cid));
+        }
+        let idx = self.tables.len();
+        self.tables.push(new_table);
+        self.archetype_map.insert(types.to_vec(), idx);
+        idx
+    }
 
-    // 1. Current Location
-    let current_loc = world.entity_index.get(&entity_id).cloned();
+    // Takes the entity out of its table, answering its components' bytes. The table's last
+    // row fills its place, so the entity that was there is re-indexed.
+    fn take_row(&mut self, entity_id: i32) -> FxHashMap<i32, Vec<u8>> {
+        let mut data = FxHashMap::default();
+        let Some((idx, row)) = self.entity_index.remove(&entity_id) else {
+            return data;
+        };
+        let table = &mut self.tables[idx];
+        for (&cid, col) in &mut table.columns {
+            let bytes = unsafe { col.swap_remove(row) };
+            data.insert(cid, bytes);
+        }
+        if let Some(swapped) = table.swap_remove_entity(row) {
+            self.entity_index.insert(swapped, (idx, row));
+        }
+        data
+    }
 
-    // 2. Identify Target Archetype
-    let mut new_types = Vec::new();
-    if let Some((table_idx, _)) = current_loc {
-        new_types.extend(world.tables[table_idx].columns.keys().cloned());
+    // Appends the entity to a table, with the bytes of the components that table has
+    fn put_row(&mut self, entity_id: i32, idx: usize, data: FxHashMap<i32, Vec<u8>>) {
+        let table = &mut self.tables[idx];
+        let row = table.entities.len();
+        table.entities.push(entity_id);
+        for (cid, bytes) in data {
+            if let Some(col) = table.columns.get_mut(&cid) {
+                unsafe {
+                    col.push(bytes.as_ptr());
+                }
+            }
+        }
+        self.entity_index.insert(entity_id, (idx, row));
     }
-    if !new_types.contains(&comp_id) {
-        new_types.push(comp_id);
+
+    // Unregistered components take no room
+    fn layout_of(&self, comp_id: i32) -> Layout {
+        self.component_layouts
+            .get(&comp_id)
+            .copied()
+            .unwrap_or(Layout::new::<()>())
     }
-    new_types.sort();
 
-    // 3. Find/Create Target Table
-    let target_table_idx = if let Some(&idx) = world.archetype_map.get(&new_types) {
-        idx
-    } else {
-        let mut new_table = Table::new();
-        for &cid in &new_types {
-            let stride = *world.component_strides.get(&cid).unwrap_or(&0);
-            new_table.add_column(cid, stride);
+    fn define_component(&mut self, id: i32, size: usize, align: usize) -> bool {
+        let Ok(layout) = Layout::from_size_align(size, align) else {
+            print(&format!(
+                "[Core] Component {} can't be {} bytes aligned to {}",
+                id, size, align
+            ));
+            return false;
+        };
+        self.component_layouts.insert(id, layout);
+        true
+    }
+
+    // Copies the component's bytes from `data` into the entity, moving it to the table with
+    // that component too
+    unsafe fn add_component(&mut self, entity_id: i32, comp_id: i32, data: *const u8) {
+        // 1. Identify Target Archetype
+        let mut new_types = self.types_of(entity_id);
+        if !new_types.contains(&comp_id) {
+            new_types.push(comp_id);
         }
-        let idx = world.tables.len();
-        world.tables.push(new_table);
-        world.archetype_map.insert(new_types.clone(), idx);
-        idx
-    };
+        new_types.sort();
 
-    // 4. Migrate Data
-    let mut migrated_data: FxHashMap<i32, Vec<u8>> = FxHashMap::default();
-    let mut swapped_entity_update = None;
+        // 2. Find/Create Target Table
+        let target_table_idx = self.table_for(&new_types);
 
-    if let Some((old_idx, old_row)) = current_loc {
-        let old_table = &mut world.tables[old_idx];
+        // 3. Migrate Data
+        let mut migrated_data = self.take_row(entity_id);
 
-        // Remove from old table
-        for (&cid, col) in &mut old_table.columns {
-            let bytes = unsafe { col.swap_remove(old_row) };
-            migrated_data.insert(cid, bytes);
-        }
+        // 4. Add New Component Data
+        // The guest's value is `size` bytes; columns copy whole strides, so pad it out to one
+        let layout = self.layout_of(comp_id);
+        let mut new_bytes = std::slice::from_raw_parts(data, layout.size()).to_vec();
+        new_bytes.resize(layout.pad_to_align().size(), 0);
+        migrated_data.insert(comp_id, new_bytes);
+
+        // 5. Push to New Table
+        self.put_row(entity_id, target_table_idx, migrated_data);
+    }
 
-        // Handle swap-remove entity fixup
-        if let Some(swapped_entity) = old_table.swap_remove_entity(old_row) {
-            swapped_entity_update = Some((swapped_entity, old_idx, old_row));
+    // Moves the entity to the table without `comp_id`, dropping that component's bytes. Removing
+    // its last component leaves it in no table, like a freshly spawned entity.
+    fn remove_component(&mut self, entity_id: i32, comp_id: i32) {
+        let mut new_types = self.types_of(entity_id);
+        let Some(pos) = new_types.iter().position(|&cid| cid == comp_id) else {
+            return;
+        };
+        new_types.remove(pos);
+
+        let mut migrated_data = self.take_row(entity_id);
+        migrated_data.remove(&comp_id);
+        if new_types.is_empty() {
+            return;
         }
+        let target_table_idx = self.table_for(&new_types);
+        self.put_row(entity_id, target_table_idx, migrated_data);
     }
 
-    if let Some((swapped, idx, row)) = swapped_entity_update {
-        world.entity_index.insert(swapped, (idx, row));
+    // Drops the entity and all its components. Its id isn't handed out again.
+    fn despawn(&mut self, entity_id: i32) {
+        self.take_row(entity_id);
     }
 
-    // 5. Add New Component Data
-    let stride = *world.component_strides.get(&comp_id).unwrap_or(&0);
-    let new_bytes = unsafe { std::slice::from_raw_parts(data_ptr as *const u8, stride).to_vec() };
-    migrated_data.insert(comp_id, new_bytes);
+    // The component ids of the entity's table, sorted; none if it has no components
+    fn types_of(&self, entity_id: i32) -> Vec<i32> {
+        let mut types: Vec<i32> = match self.entity_index.get(&entity_id) {
+            Some(&(idx, _)) => self.tables[idx].columns.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+        types.sort();
+        types
+    }
+}
 
-    // 6. Push to New Table
-    let target_table = &mut world.tables[target_table_idx];
-    let new_row = target_table.entities.len();
-    target_table.entities.push(entity_id);
+#[no_mangle]
+pub extern "C" fn add_component(entity_id: i32, comp_id: i32, data_ptr: i32) {
+    let mut world = WORLD.lock().unwrap();
+    unsafe { world.add_component(entity_id, comp_id, data_ptr as *const u8) };
+}
 
-    for (cid, bytes) in migrated_data {
-        if let Some(col) = target_table.columns.get_mut(&cid) {
-            unsafe {
-                col.push(bytes.as_ptr());
-            }
-        }
-    }
+#[no_mangle]
+pub extern "C" fn remove_component(entity_id: i32, comp_id: i32) {
+    WORLD.lock().unwrap().remove_component(entity_id, comp_id);
+}
 
-    world
-        .entity_index
-        .insert(entity_id, (target_table_idx, new_row));
+#[no_mangle]
+pub extern "C" fn despawn_entity(entity_id: i32) {
+    WORLD.lock().unwrap().despawn(entity_id);
 }
 
 #[no_mangle]
@@ -317,80 +597,432 @@ pub extern "C" fn get_table_column(table_idx: i32, comp_id: i32) -> i64 {
     let mut world = WORLD.lock().unwrap();
     if let Some(table) = world.tables.get_mut(table_idx as usize) {
         if let Some(col) = table.columns.get_mut(&comp_id) {
-            let ptr = col.data.as_mut_ptr() as i32;
-            let len = col.data.len() as i32;
-            return ((len as i64) << 32) | (ptr as i64 & 0xFFFFFFFF);
+            return FatPtr::new(col.ptr as i32, col.byte_len() as i32).pack();
         }
     }
     0
 }
 
-#[no_mangle]
-pub extern "C" fn get_standard_ids() -> i64 {
-    unsafe {
-        let ids = StandardIds {
-            position_id: POS_ID,
-            tile_id: TILE_ID,
-            cursor_id: CURSOR_ID,
-        };
-
-        // Allocate memory in WASM for the struct so Host can read it
-        let ptr = host_alloc(std::mem::size_of::<StandardIds>() as i32);
-        let slice = std::slice::from_raw_parts_mut(ptr as *mut StandardIds, 1);
-        slice[0] = ids;
-
-        ptr as i64
+// Systems in dependency order (Kahn's algorithm), ties broken by name so the schedule is the
+// same every run. Dependencies on systems that aren't registered are dropped; systems caught in
+// a cycle come last, by name, each in a stage of its own.
+fn dependency_order(
+    systems: &FxHashMap<String, SystemMeta>,
+) -> (Vec<&SystemMeta>, Vec<&SystemMeta>) {
+    let mut waiting_on: FxHashMap<&str, usize> = FxHashMap::default();
+    let mut dependents: FxHashMap<&str, Vec<&str>> = FxHashMap::default();
+    for meta in systems.values() {
+        let mut count = 0;
+        for dep in &meta.dependencies {
+            if !systems.contains_key(dep) {
+                let text = format!("[Core] '{}' depends on unregistered '{}'", meta.name, dep);
+                print(&text);
+                continue;
+            }
+            dependents.entry(dep.as_str()).or_default().push(&meta.name);
+            count += 1;
+        }
+        waiting_on.insert(&meta.name, count);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn set_standard_id(kind: i32, id: i32) {
-    unsafe {
-        match kind {
-            1 => POS_ID = id,    // Position
-            2 => TILE_ID = id,   // Tile
-            3 => CURSOR_ID = id, // Cursor
-            _ => {}
+    let mut ready: Vec<&str> = waiting_on
+        .iter()
+        .filter(|(_, &n)| n == 0)
+        .map(|(&name, _)| name)
+        .collect();
+    let mut order = Vec::new();
+    while !ready.is_empty() {
+        // Smallest name first
+        ready.sort_unstable_by(|a, b| b.cmp(a));
+        let name = ready.pop().unwrap();
+        order.push(&systems[name]);
+        for &dependent in dependents.get(name).into_iter().flatten() {
+            let count = waiting_on.get_mut(dependent).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(dependent);
+            }
         }
     }
+
+    let mut cyclic: Vec<&SystemMeta> = waiting_on
+        .iter()
+        .filter(|(_, &n)| n > 0)
+        .map(|(&name, _)| &systems[name])
+        .collect();
+    cyclic.sort_by(|a, b| a.name.cmp(&b.name));
+    (order, cyclic)
 }
 
 #[no_mangle]
 pub extern "C" fn rebuild_schedule() {
-    let mut world = WORLD.lock().unwrap();
-    let mut stage = Stage {
-        systems: Vec::new(),
-    };
-    for meta in world.systems.values() {
-        stage.systems.push(meta.func);
+    WORLD.lock().unwrap().rebuild_schedule();
+}
+
+impl EcsWorld {
+    // Each system goes in the first stage after all of its dependencies' that has nothing it
+    // conflicts with, so independent systems share stages and only real orderings split them.
+    fn rebuild_schedule(&mut self) {
+        let (order, cyclic) = dependency_order(&self.systems);
+
+        let mut schedule: Vec<Stage> = Vec::new();
+        let mut stage_of: FxHashMap<&str, usize> = FxHashMap::default();
+        for meta in order {
+            let earliest = meta
+                .dependencies
+                .iter()
+                .filter_map(|dep| stage_of.get(dep.as_str()))
+                .map(|&stage| stage + 1)
+                .max()
+                .unwrap_or(0);
+            let stage = match (earliest..schedule.len()).find(|&i| !schedule[i].conflicts(meta)) {
+                Some(stage) => stage,
+                None => {
+                    schedule.push(Stage::new());
+                    schedule.len() - 1
+                }
+            };
+            // Nothing orders it after the systems it was kept apart from, so that's up to names
+            if stage > earliest {
+                let (other, id) = schedule[earliest]
+                    .names
+                    .iter()
+                    .find_map(|other| meta.conflict(&self.systems[other]).map(|id| (other, id)))
+                    .expect("a conflict kept it out of the stage");
+                print(&format!(
+                    "[Core] '{}' and '{}' both use component {} and neither depends on the other; \
+                     '{}' runs first",
+                    other, meta.name, id, other
+                ));
+            }
+            schedule[stage].push(meta);
+            stage_of.insert(&meta.name, stage);
+        }
+        if !cyclic.is_empty() {
+            let names: Vec<&str> = cyclic.iter().map(|meta| meta.name.as_str()).collect();
+            print(&format!(
+                "[Core] Dependency cycle between {}, running them last in that order",
+                names.join(", ")
+            ));
+        }
+        for meta in cyclic {
+            let mut stage = Stage::new();
+            stage.push(meta);
+            schedule.push(stage);
+        }
+
+        for (i, stage) in schedule.iter().enumerate() {
+            print(&format!("[Core] Stage {}: {}", i, stage.names.join(", ")));
+        }
+        self.schedule = schedule;
     }
-    world.schedule = vec![stage];
 }
 
-#[no_mangle]
-pub extern "C" fn run_schedule() {
-    // 1. Snapshot functions to run (Unlock Mutex immediately)
-    let systems_to_run = {
-        let world = WORLD.lock().unwrap();
-        let mut funcs = Vec::new();
-        for stage in &world.schedule {
-            for sys in &stage.systems {
-                funcs.push(*sys);
+impl EcsKernel for EcsWorld {
+    fn register_component(&mut self, size: usize, align: usize) -> i32 {
+        let id = self.component_layouts.keys().max().map_or(0, |id| id + 1);
+        if self.define_component(id, size, align) {
+            id
+        } else {
+            -1
+        }
+    }
+
+    unsafe fn spawn(&mut self, components: &[(i32, *const u8)]) -> i32 {
+        let id = self.next_entity_id;
+        self.next_entity_id += 1;
+        for &(comp_id, data) in components {
+            self.add_component(id, comp_id, data);
+        }
+        id
+    }
+
+    fn query_tables(&mut self, required: &[i32]) -> &[i32] {
+        self.query_buffer.clear();
+        for (idx, table) in self.tables.iter().enumerate() {
+            if required.iter().all(|id| table.columns.contains_key(id)) {
+                self.query_buffer.push(idx as i32);
             }
         }
-        funcs
-    };
+        &self.query_buffer
+    }
+
+    fn table_len(&self, table: i32) -> usize {
+        self.tables
+            .get(table as usize)
+            .map_or(0, |table| table.entities.len())
+    }
+
+    fn column_ptr(&mut self, table: i32, component: i32) -> *mut u8 {
+        match self
+            .tables
+            .get(table as usize)
+            .and_then(|table| table.columns.get(&component))
+        {
+            Some(col) => col.ptr,
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    fn resource(&mut self, id: i32, size: usize) -> *mut u8 {
+        if size == 0 {
+            return self
+                .resources
+                .get_mut(&id)
+                .map_or(std::ptr::null_mut(), |blob| blob.as_mut_ptr());
+        }
+        self.resources
+            .entry(id)
+            .or_insert_with(|| vec![0u8; size].into_boxed_slice())
+            .as_mut_ptr()
+    }
 
-    // 2. Execute (re-entrant safe)
-    for sys in systems_to_run {
-        sys(0);
+    fn schedule(&mut self) -> Vec<Vec<SystemFn>> {
+        self.schedule
+            .iter()
+            .map(|stage| stage.systems.clone())
+            .collect()
     }
 }
 
+// The schedule's old name, for plugins written against custom_ecs alone
 #[no_mangle]
-pub extern "C" fn tick(delta: f32) {
-    // In a real scenario, we might put 'delta' into a Singleton Resource here
+pub extern "C" fn run_schedule() {
+    sys_run_schedule();
+}
 
-    // Run the schedule (Logic Systems)
+#[no_mangle]
+pub extern "C" fn tick(_delta: f32) {
     run_schedule();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn noop(_: i32) {}
+
+    fn system(world: &mut EcsWorld, name: &str, reads: &[i32], writes: &[i32], after: &[&str]) {
+        let meta = SystemMeta {
+            name: name.to_string(),
+            func: noop,
+            reads: reads.iter().copied().collect(),
+            writes: writes.iter().copied().collect(),
+            dependencies: after.iter().map(|dep| dep.to_string()).collect(),
+        };
+        world.systems.insert(name.to_string(), meta);
+    }
+
+    fn stages(world: &EcsWorld) -> Vec<Vec<&str>> {
+        world
+            .schedule
+            .iter()
+            .map(|stage| stage.names.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn dependencies_order_the_stages() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "render", &[], &[], &["physics"]);
+        system(&mut world, "physics", &[], &[], &["input"]);
+        system(&mut world, "input", &[], &[], &[]);
+        system(&mut world, "audio", &[], &[], &[]);
+        world.rebuild_schedule();
+        assert_eq!(
+            stages(&world),
+            [vec!["audio", "input"], vec!["physics"], vec!["render"]]
+        );
+    }
+
+    #[test]
+    fn an_unregistered_dependency_is_dropped() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "physics", &[], &[], &["input"]);
+        world.rebuild_schedule();
+        assert_eq!(stages(&world), [vec!["physics"]]);
+    }
+
+    #[test]
+    fn a_cycle_runs_last_one_system_a_stage() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "b", &[], &[], &["a"]);
+        system(&mut world, "a", &[], &[], &["b"]);
+        system(&mut world, "free", &[], &[], &[]);
+        world.rebuild_schedule();
+        assert_eq!(stages(&world), [vec!["free"], vec!["a"], vec!["b"]]);
+    }
+
+    #[test]
+    fn conflicting_systems_are_split_by_name() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "mover", &[1], &[1], &[]);
+        system(&mut world, "drawer", &[1], &[], &[]);
+        system(&mut world, "scorer", &[2], &[2], &[]);
+        world.rebuild_schedule();
+        assert_eq!(stages(&world), [vec!["drawer", "scorer"], vec!["mover"]]);
+    }
+
+    #[test]
+    fn readers_share_a_stage() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "a", &[1], &[], &[]);
+        system(&mut world, "b", &[1], &[], &[]);
+        world.rebuild_schedule();
+        assert_eq!(stages(&world), [vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn a_conflict_after_a_dependency_moves_to_a_later_stage() {
+        let mut world = EcsWorld::default();
+        system(&mut world, "input", &[], &[1], &[]);
+        system(&mut world, "ai", &[], &[2], &["input"]);
+        system(&mut world, "physics", &[2], &[], &["input"]);
+        world.rebuild_schedule();
+        assert_eq!(stages(&world), [vec!["input"], vec!["ai"], vec!["physics"]]);
+    }
+
+    // A world with two i32 components, answering their ids
+    fn world() -> (EcsWorld, i32, i32) {
+        let mut world = EcsWorld::default();
+        let pos = world.register_component(4, 4);
+        let tile = world.register_component(4, 4);
+        (world, pos, tile)
+    }
+
+    fn spawn(world: &mut EcsWorld, components: &[(i32, i32)]) -> i32 {
+        let ptrs: Vec<(i32, *const u8)> = components
+            .iter()
+            .map(|(id, value)| (*id, value as *const i32 as *const u8))
+            .collect();
+        unsafe { world.spawn(&ptrs) }
+    }
+
+    // Every row's value of `component`, for the table with exactly `types`
+    fn column(world: &mut EcsWorld, types: &[i32], component: i32) -> Vec<i32> {
+        let table = world.archetype_map[types] as i32;
+        let len = world.table_len(table);
+        let ptr = world.column_ptr(table, component) as *const i32;
+        unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+    }
+
+    // The entity's table's components and its row there
+    fn row_of(world: &EcsWorld, entity: i32) -> Option<(Vec<i32>, usize)> {
+        let &(_, row) = world.entity_index.get(&entity)?;
+        Some((world.types_of(entity), row))
+    }
+
+    #[test]
+    fn despawning_the_last_row_leaves_the_rest() {
+        let (mut world, pos, _) = world();
+        let a = spawn(&mut world, &[(pos, 10)]);
+        let b = spawn(&mut world, &[(pos, 20)]);
+        world.despawn(b);
+        assert_eq!(column(&mut world, &[pos], pos), [10]);
+        assert_eq!(row_of(&world, a), Some((vec![pos], 0)));
+        assert_eq!(row_of(&world, b), None);
+    }
+
+    #[test]
+    fn despawning_a_middle_row_moves_the_last_into_it() {
+        let (mut world, pos, tile) = world();
+        let a = spawn(&mut world, &[(pos, 10), (tile, 1)]);
+        let b = spawn(&mut world, &[(pos, 20), (tile, 2)]);
+        let c = spawn(&mut world, &[(pos, 30), (tile, 3)]);
+        world.despawn(b);
+        assert_eq!(column(&mut world, &[pos, tile], pos), [10, 30]);
+        assert_eq!(column(&mut world, &[pos, tile], tile), [1, 3]);
+        assert_eq!(row_of(&world, a), Some((vec![pos, tile], 0)));
+        assert_eq!(row_of(&world, c), Some((vec![pos, tile], 1)));
+    }
+
+    #[test]
+    fn despawning_an_archetypes_only_entity_empties_its_table() {
+        let (mut world, pos, tile) = world();
+        let a = spawn(&mut world, &[(pos, 10)]);
+        let b = spawn(&mut world, &[(pos, 20), (tile, 2)]);
+        world.despawn(b);
+        assert_eq!(column(&mut world, &[pos, tile], pos), [] as [i32; 0]);
+        assert_eq!(world.query_tables(&[tile]).len(), 1);
+        assert_eq!(column(&mut world, &[pos], pos), [10]);
+        assert_eq!(row_of(&world, a), Some((vec![pos], 0)));
+
+        // The empty table takes entities again
+        let c = spawn(&mut world, &[(pos, 30), (tile, 3)]);
+        assert_eq!(column(&mut world, &[pos, tile], tile), [3]);
+        assert_eq!(row_of(&world, c), Some((vec![pos, tile], 0)));
+    }
+
+    #[test]
+    fn removing_from_a_middle_row_moves_it_and_the_last_row() {
+        let (mut world, pos, tile) = world();
+        let a = spawn(&mut world, &[(pos, 10), (tile, 1)]);
+        let b = spawn(&mut world, &[(pos, 20), (tile, 2)]);
+        let c = spawn(&mut world, &[(pos, 30), (tile, 3)]);
+        world.remove_component(b, tile);
+        assert_eq!(column(&mut world, &[pos, tile], pos), [10, 30]);
+        assert_eq!(column(&mut world, &[pos, tile], tile), [1, 3]);
+        assert_eq!(column(&mut world, &[pos], pos), [20]);
+        assert_eq!(row_of(&world, a), Some((vec![pos, tile], 0)));
+        assert_eq!(row_of(&world, c), Some((vec![pos, tile], 1)));
+        assert_eq!(world.types_of(b), [pos]);
+    }
+
+    #[test]
+    fn removing_from_the_last_row_empties_the_archetype() {
+        let (mut world, pos, tile) = world();
+        let a = spawn(&mut world, &[(pos, 10), (tile, 1)]);
+        world.remove_component(a, tile);
+        assert_eq!(column(&mut world, &[pos, tile], pos), [] as [i32; 0]);
+        assert_eq!(column(&mut world, &[pos], pos), [10]);
+        assert_eq!(world.types_of(a), [pos]);
+    }
+
+    #[test]
+    fn removing_the_last_component_leaves_no_row() {
+        let (mut world, pos, tile) = world();
+        let a = spawn(&mut world, &[(pos, 10)]);
+        world.remove_component(a, tile);
+        assert_eq!(world.types_of(a), [pos]);
+        world.remove_component(a, pos);
+        assert_eq!(row_of(&world, a), None);
+        assert_eq!(column(&mut world, &[pos], pos), [] as [i32; 0]);
+    }
+
+    // Rows of a component aligned wider than it is long are a whole alignment apart
+    fn strided(world: &mut EcsWorld, types: &[i32], component: i32, stride: usize) -> Vec<i32> {
+        let table = world.archetype_map[types] as i32;
+        let ptr = world.column_ptr(table, component);
+        assert_eq!(ptr as usize % stride, 0);
+        (0..world.table_len(table))
+            .map(|row| unsafe { *(ptr.add(row * stride) as *const i32) })
+            .collect()
+    }
+
+    #[test]
+    fn a_component_aligned_past_its_size_keeps_its_rows_aligned() {
+        let (mut world, pos, _) = world();
+        let wide = world.register_component(4, 16);
+        let a = spawn(&mut world, &[(pos, 10), (wide, 1)]);
+        let b = spawn(&mut world, &[(pos, 20), (wide, 2)]);
+        let c = spawn(&mut world, &[(pos, 30)]);
+        unsafe { world.add_component(c, wide, &3 as *const i32 as *const u8) };
+        assert_eq!(strided(&mut world, &[pos, wide], wide, 16), [1, 2, 3]);
+
+        world.remove_component(a, pos);
+        assert_eq!(strided(&mut world, &[pos, wide], wide, 16), [3, 2]);
+        assert_eq!(column(&mut world, &[pos, wide], pos), [30, 20]);
+        assert_eq!(strided(&mut world, &[wide], wide, 16), [1]);
+
+        world.remove_component(b, wide);
+        assert_eq!(strided(&mut world, &[pos, wide], wide, 16), [3]);
+        assert_eq!(column(&mut world, &[pos], pos), [20]);
+
+        let tables = world.query_tables(&[wide]).to_vec();
+        let rows: usize = tables.iter().map(|&table| world.table_len(table)).sum();
+        assert_eq!(rows, 2);
+        for table in tables {
+            assert_eq!(world.column_ptr(table, wide) as usize % 16, 0);
+        }
+    }
+}
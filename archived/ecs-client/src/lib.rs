@@ -1,4 +1,5 @@
 // crates/ecs-client/src/lib.rs
+use fat_ptr::FatPtr;
 use std::alloc::{GlobalAlloc, Layout};
 use std::marker::PhantomData;
 
@@ -15,8 +16,23 @@ mod ffi {
         pub fn get_table_column(table_idx: i32, comp_id: i32) -> i64;
         pub fn spawn_entity() -> i32;
         pub fn add_component(entity_id: i32, comp_id: i32, data_ptr: i32);
+        pub fn remove_component(entity_id: i32, comp_id: i32);
+        pub fn despawn_entity(entity_id: i32);
         pub fn register_system(mod_ptr: i32, mod_len: i32, fn_ptr: i32, fn_len: i32);
-        pub fn set_standard_id(kind: i32, id: i32);
+        pub fn declare_system_access(
+            name_ptr: i32,
+            name_len: i32,
+            reads_ptr: i32,
+            reads_len: i32,
+            writes_ptr: i32,
+            writes_len: i32,
+        );
+        pub fn declare_system_dependency(
+            name_ptr: i32,
+            name_len: i32,
+            before_ptr: i32,
+            before_len: i32,
+        );
     }
 }
 
@@ -59,8 +75,16 @@ pub fn add_component<T: Component>(entity: i32, component: &T) {
     }
 }
 
-pub trait StandardComponent: Component {
-    const KIND: i32;
+pub fn remove_component<T: Component>(entity: i32) {
+    unsafe {
+        ffi::remove_component(entity, T::ID);
+    }
+}
+
+pub fn despawn_entity(entity: i32) {
+    unsafe {
+        ffi::despawn_entity(entity);
+    }
 }
 
 pub fn register_component<T: Component>() {
@@ -73,13 +97,6 @@ pub fn register_component<T: Component>() {
     }
 }
 
-pub fn register_std_component<T: StandardComponent>() {
-    register_component::<T>();
-    unsafe {
-        ffi::set_standard_id(T::KIND, T::ID);
-    }
-}
-
 pub fn register_system<Q: WorldQuery>(
     module_name: &str,
     system_name: &str,
@@ -94,6 +111,36 @@ pub fn register_system<Q: WorldQuery>(
             system_name.len() as i32,
         );
     }
+    // What Q touches, so Core can put systems that don't conflict in the same stage
+    declare_system_access(system_name, &Q::get_ids(), &Q::get_writes());
+}
+
+/// Replaces what Core knows `system_name` reads and writes, for systems that touch more than
+/// their query.
+pub fn declare_system_access(system_name: &str, reads: &[i32], writes: &[i32]) {
+    unsafe {
+        ffi::declare_system_access(
+            system_name.as_ptr() as i32,
+            system_name.len() as i32,
+            reads.as_ptr() as i32,
+            reads.len() as i32,
+            writes.as_ptr() as i32,
+            writes.len() as i32,
+        );
+    }
+}
+
+/// Runs `system_name` in a later stage than `before`. Call once both are registered, before
+/// Core rebuilds its schedule.
+pub fn declare_system_dependency(system_name: &str, before: &str) {
+    unsafe {
+        ffi::declare_system_dependency(
+            system_name.as_ptr() as i32,
+            system_name.len() as i32,
+            before.as_ptr() as i32,
+            before.len() as i32,
+        );
+    }
 }
 
 // --- QUERY SYSTEM ---
@@ -107,6 +154,8 @@ pub trait WorldQuery {
     type Item<'a>;
     type Columns;
     fn get_ids() -> Vec<i32>;
+    // The ids it hands out as &mut
+    fn get_writes() -> Vec<i32>;
     unsafe fn init_columns(table_idx: i32) -> Self::Columns;
     unsafe fn fetch<'a>(columns: &Self::Columns, row: usize) -> Self::Item<'a>;
 }
@@ -139,7 +188,7 @@ impl<Q: WorldQuery> Query<Q> {
                 let columns = Q::init_columns(table_idx);
                 // We use ID[0] to determine row count
                 let packed = ffi::get_table_column(table_idx, ids[0]);
-                let len_bytes = (packed >> 32) as usize;
+                let len_bytes = FatPtr::unpack(packed).len as usize;
                 // Note: You need a way to know the size of component ID[0] here to calc rows perfectly.
                 // For now assuming 8 bytes (f32, f32). In prod, `get_table_column` should return `rows` directly.
                 let row_count = len_bytes / 8;
@@ -160,9 +209,12 @@ impl<T: Component> WorldQuery for &mut T {
     fn get_ids() -> Vec<i32> {
         vec![T::ID]
     }
+    fn get_writes() -> Vec<i32> {
+        vec![T::ID]
+    }
     unsafe fn init_columns(idx: i32) -> Self::Columns {
         let packed = ffi::get_table_column(idx, T::ID);
-        let ptr = (packed & 0xFFFFFFFF) as *mut T;
+        let ptr = FatPtr::unpack(packed).ptr as *mut T;
         ColumnView { ptr, _len: 0 }
     }
     unsafe fn fetch<'a>(col: &Self::Columns, row: usize) -> Self::Item<'a> {
@@ -176,9 +228,12 @@ impl<T: Component> WorldQuery for &T {
     fn get_ids() -> Vec<i32> {
         vec![T::ID]
     }
+    fn get_writes() -> Vec<i32> {
+        Vec::new()
+    }
     unsafe fn init_columns(idx: i32) -> Self::Columns {
         let packed = ffi::get_table_column(idx, T::ID);
-        let ptr = (packed & 0xFFFFFFFF) as *mut T;
+        let ptr = FatPtr::unpack(packed).ptr as *mut T;
         ColumnView { ptr, _len: 0 }
     }
     unsafe fn fetch<'a>(col: &Self::Columns, row: usize) -> Self::Item<'a> {
@@ -194,6 +249,11 @@ impl<A: WorldQuery, B: WorldQuery> WorldQuery for (A, B) {
         ids.extend(B::get_ids());
         ids
     }
+    fn get_writes() -> Vec<i32> {
+        let mut ids = A::get_writes();
+        ids.extend(B::get_writes());
+        ids
+    }
     unsafe fn init_columns(idx: i32) -> Self::Columns {
         (A::init_columns(idx), B::init_columns(idx))
     }
@@ -1,16 +1,11 @@
 use anyhow::Result;
+use fat_ptr::unpack;
 use std::cell::UnsafeCell;
 use wasmtime::*;
 
 mod lib;
 use lib::*;
 
-fn unpack_i64_result(packed_i64: i64) -> (i32, i32) {
-    let ptr: i32 = (packed_i64 & 0xFFFFFFFF) as i32;
-    let len: i32 = (packed_i64 >> 32) as i32;
-    (ptr, len)
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 1. Setup Runtime (allocates memory, instantiates plugins, sets up heap)
     let (mut store, _instance_core, instance_client) = setup_runtime()?;
@@ -54,7 +49,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )?;
 
     // 5. Unpack Result
-    let (result_ptr, result_len) = unpack_i64_result(result_i64);
+    let (result_ptr, result_len) = unpack(result_i64);
     println!(
         "✅ Task Created! Result stored at: Ptr={}, Len={}",
         result_ptr, result_len
@@ -66,7 +61,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         instance_client.get_typed_func::<(), i64>(&mut store, "list_pending_tasks")?;
 
     let tasks_i64 = list_pending.call(&mut store, ())?;
-    let (tasks_ptr, tasks_len) = unpack_i64_result(tasks_i64);
+    let (tasks_ptr, tasks_len) = unpack(tasks_i64);
 
     println!(
         "✅ Pending Tasks Listed! Data at: Ptr={}, Len={}",
@@ -1,5 +1,6 @@
 use std::alloc::{GlobalAlloc, Layout};
 
+#[cfg(not(feature = "mock-host"))]
 extern "C" {
     fn host_alloc(size: i32) -> i32;
     fn host_dealloc(ptr: i32, size: i32);
@@ -7,6 +8,7 @@ extern "C" {
 
 pub struct HostAllocator;
 
+#[cfg(not(feature = "mock-host"))]
 unsafe impl GlobalAlloc for HostAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let size = layout.size();
@@ -23,6 +25,18 @@ unsafe impl GlobalAlloc for HostAllocator {
     }
 }
 
+// Native pointers don't fit host_alloc's i32 offsets, so mock hosts hand out the system's
+#[cfg(feature = "mock-host")]
+unsafe impl GlobalAlloc for HostAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        std::alloc::System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        std::alloc::System.dealloc(ptr, layout)
+    }
+}
+
 // 3. Set as the Global Allocator for any crate that uses this
 // #[global_allocator]
-// static ALLOCATOR: HostAllocator = HostAllocator;
\ No newline at end of file
+// static ALLOCATOR: HostAllocator = HostAllocator;
@@ -0,0 +1,51 @@
+// The host's chat: what players type into the host's chat overlay and what plugins post, on
+// every player's screen in split play. Plugins don't have to do anything for it; to post a
+// line of their own (a kill feed, "waiting for players"), they send `CHAT` a message whose
+// payload is the text, UTF-8, and the host names them as its author.
+//
+// A line on the network, little-endian: from (u16 len + UTF-8), then the text.
+//
+// Texts are at most MAX_TEXT bytes, on one line; the host cuts longer ones short and turns
+// control characters into spaces.
+
+pub const CHAT: &str = "chat";
+
+pub const MAX_TEXT: usize = 512;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChatLine {
+    pub from: String,
+    pub text: String,
+}
+
+impl ChatLine {
+    /// A line of `text` by `from`, cut and cleaned up as the header says.
+    pub fn new(from: &str, text: &str) -> Self {
+        let mut clean = String::new();
+        for c in text.chars().map(|c| if c.is_control() { ' ' } else { c }) {
+            if clean.len() + c.len_utf8() > MAX_TEXT {
+                break;
+            }
+            clean.push(c);
+        }
+        Self {
+            from: from.to_string(),
+            text: clean,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let from = &self.from.as_bytes()[..self.from.len().min(u16::MAX as usize)];
+        let mut bytes = (from.len() as u16).to_le_bytes().to_vec();
+        bytes.extend_from_slice(from);
+        bytes.extend_from_slice(self.text.as_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let len = u16::from_le_bytes(bytes.get(..2)?.try_into().ok()?) as usize;
+        let from = std::str::from_utf8(bytes.get(2..2 + len)?).ok()?;
+        let text = std::str::from_utf8(&bytes[2 + len..]).ok()?;
+        Some(Self::new(from, text))
+    }
+}
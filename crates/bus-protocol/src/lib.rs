@@ -0,0 +1,175 @@
+// Asynchronous messages between plugins, routed by the host. Unlike `call`, sending never waits:
+// requests carry a correlation id, and the answer shows up later as a reply with the same id.
+//
+//   bus_send(to_ptr, to_len, message_ptr, message_len) -> i32      host call
+//       queues an encoded Envelope for the plugin loaded as `to`; the host fills in `sender`.
+//       0 queued, -1 no such plugin, -2 malformed envelope
+//   on_message(message_ptr, message_len)                           plugin export, optional
+//       called between ticks with each queued Envelope; the buffer is freed afterwards
+//
+// Wire format, little-endian: correlation_id u64, kind u8, sender (u16 len + UTF-8),
+// reply_to (u16 len + UTF-8, empty = the sender), then the payload.
+
+pub mod chat;
+pub mod lobby;
+pub mod quotas;
+
+pub const KIND_REQUEST: u8 = 0;
+pub const KIND_REPLY: u8 = 1;
+pub const KIND_EVENT: u8 = 2;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Envelope {
+    pub correlation_id: u64,
+    pub kind: u8,
+    pub sender: String,
+    // Where replies go, if not back to the sender
+    pub reply_to: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn request(correlation_id: u64, payload: Vec<u8>) -> Self {
+        Self {
+            correlation_id,
+            kind: KIND_REQUEST,
+            payload,
+            ..Default::default()
+        }
+    }
+
+    pub fn event(payload: Vec<u8>) -> Self {
+        Self {
+            kind: KIND_EVENT,
+            payload,
+            ..Default::default()
+        }
+    }
+
+    /// The answer to this request, and who it should go to.
+    pub fn reply(&self, payload: Vec<u8>) -> (String, Envelope) {
+        let to = self.reply_to.clone().unwrap_or_else(|| self.sender.clone());
+        let reply = Self {
+            correlation_id: self.correlation_id,
+            kind: KIND_REPLY,
+            payload,
+            ..Default::default()
+        };
+        (to, reply)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let reply_to = self.reply_to.as_deref().unwrap_or("");
+        let mut bytes =
+            Vec::with_capacity(13 + self.sender.len() + reply_to.len() + self.payload.len());
+        bytes.extend_from_slice(&self.correlation_id.to_le_bytes());
+        bytes.push(self.kind);
+        for s in [self.sender.as_str(), reply_to] {
+            bytes.extend_from_slice(&(s.len().min(u16::MAX as usize) as u16).to_le_bytes());
+            bytes.extend_from_slice(&s.as_bytes()[..s.len().min(u16::MAX as usize)]);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let correlation_id = u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?);
+        let kind = *bytes.get(8)?;
+        let mut rest = &bytes[9..];
+        let mut text = || -> Option<String> {
+            let len = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+            let s = std::str::from_utf8(rest.get(2..2 + len)?).ok()?.to_string();
+            rest = &rest[2 + len..];
+            Some(s)
+        };
+        let sender = text()?;
+        let reply_to = Some(text()?).filter(|s| !s.is_empty());
+        Some(Self {
+            correlation_id,
+            kind,
+            sender,
+            reply_to,
+            payload: rest.to_vec(),
+        })
+    }
+
+    /// Decodes the buffer `on_message` was called with.
+    ///
+    /// # Safety
+    /// `ptr`/`len` must be the arguments the host passed to `on_message`.
+    pub unsafe fn from_raw(ptr: i32, len: i32) -> Option<Self> {
+        if ptr == 0 || len <= 0 {
+            return None;
+        }
+        Self::decode(std::slice::from_raw_parts(
+            ptr as usize as *const u8,
+            len as usize,
+        ))
+    }
+}
+
+// --- GUEST SIDE ---
+
+extern "C" {
+    #[link_name = "bus_send"]
+    fn __bus_send(to_ptr: i32, to_len: i32, message_ptr: i32, message_len: i32) -> i32;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendError {
+    NoSuchPlugin,
+    Malformed,
+}
+
+/// Queues `envelope` for the plugin loaded as `to` and returns right away.
+pub fn send(to: &str, envelope: &Envelope) -> Result<(), SendError> {
+    let bytes = envelope.encode();
+    let status = unsafe {
+        __bus_send(
+            to.as_ptr() as i32,
+            to.len() as i32,
+            bytes.as_ptr() as i32,
+            bytes.len() as i32,
+        )
+    };
+    match status {
+        0 => Ok(()),
+        -1 => Err(SendError::NoSuchPlugin),
+        _ => Err(SendError::Malformed),
+    }
+}
+
+/// Hands out correlation ids and remembers which requests are still waiting, so replies can
+/// be matched (and unexpected ones ignored). Ids only need to be unique per sender.
+#[derive(Default)]
+pub struct Requests {
+    next_id: u64,
+    pending: Vec<u64>,
+}
+
+impl Requests {
+    /// Sends `payload` as a request to `to`; the reply will carry the returned id.
+    pub fn send(&mut self, to: &str, payload: Vec<u8>) -> Result<u64, SendError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        send(to, &Envelope::request(id, payload))?;
+        self.pending.push(id);
+        Ok(id)
+    }
+
+    /// If `envelope` answers one of our requests, forgets that request and returns its id.
+    pub fn take_reply(&mut self, envelope: &Envelope) -> Option<u64> {
+        if envelope.kind != KIND_REPLY {
+            return None;
+        }
+        let idx = self
+            .pending
+            .iter()
+            .position(|id| *id == envelope.correlation_id)?;
+        Some(self.pending.swap_remove(idx))
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.pending.len()
+    }
+}
@@ -0,0 +1,232 @@
+// The host's lobby: multiplayer sessions, their player slots and who's ready, kept by the host
+// so games don't each do their own bookkeeping. Plugins reach it over the bus as `LOBBY`, with
+// requests that get a reply; the other members of a session get an event when it changes.
+// In split play the server's lobby answers, so players on different machines meet there.
+//
+// Payloads, little-endian, strings as u16 len + UTF-8:
+//
+//   request   op u8, then
+//               CREATE   slots u8, name     a session with that many slots; the sender takes slot 0
+//               JOIN     session u32        the first free slot (the sender's, if it has one)
+//               LEAVE    session u32        the creator leaving closes the session
+//               READY    session u32, ready u8
+//               LIST                        every session
+//   reply     status u8, count u16, then that many sessions: the one acted on, or all for LIST
+//   event     event u8 (CHANGED, READY: every slot taken and ready, CLOSED), then the session
+//
+//   session   id u32, name, slots u8, then per slot: member (empty = free), ready u8
+//
+// Members are named by the plugin that joined: its name, or `name@client` for a split client.
+
+pub const LOBBY: &str = "lobby";
+
+pub const OP_CREATE: u8 = 0;
+pub const OP_JOIN: u8 = 1;
+pub const OP_LEAVE: u8 = 2;
+pub const OP_READY: u8 = 3;
+pub const OP_LIST: u8 = 4;
+
+pub const STATUS_OK: u8 = 0;
+pub const STATUS_NO_SESSION: u8 = 1;
+pub const STATUS_FULL: u8 = 2;
+pub const STATUS_NOT_MEMBER: u8 = 3;
+pub const STATUS_MALFORMED: u8 = 4;
+
+pub const EVENT_CHANGED: u8 = 0;
+pub const EVENT_READY: u8 = 1;
+pub const EVENT_CLOSED: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    Create { slots: u8, name: String },
+    Join(u32),
+    Leave(u32),
+    Ready(u32, bool),
+    List,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Slot {
+    pub member: Option<String>,
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Session {
+    pub id: u32,
+    pub name: String,
+    pub slots: Vec<Slot>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reply {
+    pub status: u8,
+    pub sessions: Vec<Session>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub event: u8,
+    pub session: Session,
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Request::Create { slots, name } => {
+                bytes.extend_from_slice(&[OP_CREATE, *slots]);
+                put_text(&mut bytes, name);
+            }
+            Request::Join(session) => put_op(&mut bytes, OP_JOIN, *session),
+            Request::Leave(session) => put_op(&mut bytes, OP_LEAVE, *session),
+            Request::Ready(session, ready) => {
+                put_op(&mut bytes, OP_READY, *session);
+                bytes.push(*ready as u8);
+            }
+            Request::List => bytes.push(OP_LIST),
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        let request = match reader.u8()? {
+            OP_CREATE => Request::Create {
+                slots: reader.u8()?,
+                name: reader.text()?,
+            },
+            OP_JOIN => Request::Join(reader.u32()?),
+            OP_LEAVE => Request::Leave(reader.u32()?),
+            OP_READY => Request::Ready(reader.u32()?, reader.u8()? != 0),
+            OP_LIST => Request::List,
+            _ => return None,
+        };
+        reader.0.is_empty().then_some(request)
+    }
+}
+
+impl Session {
+    /// The slot `member` holds, if any.
+    pub fn slot_of(&self, member: &str) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.member.as_deref() == Some(member))
+    }
+
+    /// Every slot taken, and everyone in them ready.
+    pub fn is_ready(&self) -> bool {
+        self.slots
+            .iter()
+            .all(|slot| slot.member.is_some() && slot.ready)
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        self.slots.iter().filter_map(|slot| slot.member.as_deref())
+    }
+
+    fn put(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.id.to_le_bytes());
+        put_text(bytes, &self.name);
+        bytes.push(self.slots.len() as u8);
+        for slot in &self.slots {
+            put_text(bytes, slot.member.as_deref().unwrap_or(""));
+            bytes.push(slot.ready as u8);
+        }
+    }
+
+    fn take(reader: &mut Reader) -> Option<Self> {
+        let id = reader.u32()?;
+        let name = reader.text()?;
+        let slots = (0..reader.u8()?)
+            .map(|_| {
+                Some(Slot {
+                    member: Some(reader.text()?).filter(|member| !member.is_empty()),
+                    ready: reader.u8()? != 0,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { id, name, slots })
+    }
+}
+
+impl Reply {
+    pub fn status(status: u8) -> Self {
+        Self {
+            status,
+            sessions: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.status];
+        bytes.extend_from_slice(&(self.sessions.len() as u16).to_le_bytes());
+        for session in &self.sessions {
+            session.put(&mut bytes);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        let status = reader.u8()?;
+        let sessions = (0..reader.u16()?)
+            .map(|_| Session::take(&mut reader))
+            .collect::<Option<_>>()?;
+        Some(Self { status, sessions })
+    }
+}
+
+impl Event {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.event];
+        self.session.put(&mut bytes);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        Some(Self {
+            event: reader.u8()?,
+            session: Session::take(&mut reader)?,
+        })
+    }
+}
+
+fn put_op(bytes: &mut Vec<u8>, op: u8, session: u32) {
+    bytes.push(op);
+    bytes.extend_from_slice(&session.to_le_bytes());
+}
+
+fn put_text(bytes: &mut Vec<u8>, text: &str) {
+    let len = text.len().min(u16::MAX as usize);
+    bytes.extend_from_slice(&(len as u16).to_le_bytes());
+    bytes.extend_from_slice(&text.as_bytes()[..len]);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let (taken, rest) = (self.0.get(..len)?, self.0.get(len..)?);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn text(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+}
@@ -0,0 +1,62 @@
+// The host's quotas (host/host/quotas.rs): a plugin over one of its budgets is throttled, its
+// ticks skipped for a while, or after too many times in a row suspended until the embedder
+// resumes it. Every other plugin with an `on_message` hears about it, as an event from `QUOTAS`,
+// so a game can pause while a mod it relies on sits out.
+//
+// Payload, little-endian: event u8, resource u8, plugin (u16 len + UTF-8), used u64, budget u64.
+// Time is in microseconds, heap in bytes, fuel in wasmtime fuel units; RESUMED carries zeros.
+
+pub const QUOTAS: &str = "quotas";
+
+pub const EVENT_THROTTLED: u8 = 0;
+pub const EVENT_SUSPENDED: u8 = 1;
+pub const EVENT_RESUMED: u8 = 2;
+
+// Time in its exports per tick
+pub const RESOURCE_CPU: u8 = 0;
+// Live host heap bytes
+pub const RESOURCE_HEAP: u8 = 1;
+// Wasm instructions per call
+pub const RESOURCE_FUEL: u8 = 2;
+// Wall-clock time per call
+pub const RESOURCE_TIME: u8 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaEvent {
+    pub event: u8,
+    pub resource: u8,
+    pub plugin: String,
+    pub used: u64,
+    pub budget: u64,
+}
+
+impl QuotaEvent {
+    pub fn encode(&self) -> Vec<u8> {
+        let plugin = &self.plugin.as_bytes()[..self.plugin.len().min(u16::MAX as usize)];
+        let mut bytes = vec![self.event, self.resource];
+        bytes.extend_from_slice(&(plugin.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(plugin);
+        bytes.extend_from_slice(&self.used.to_le_bytes());
+        bytes.extend_from_slice(&self.budget.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&event, &resource) = (bytes.first()?, bytes.get(1)?);
+        let len = u16::from_le_bytes(bytes.get(2..4)?.try_into().ok()?) as usize;
+        let plugin = std::str::from_utf8(bytes.get(4..4 + len)?)
+            .ok()?
+            .to_string();
+        let rest = &bytes[4 + len..];
+        if rest.len() != 16 {
+            return None;
+        }
+        Some(Self {
+            event,
+            resource,
+            plugin,
+            used: u64::from_le_bytes(rest[..8].try_into().ok()?),
+            budget: u64::from_le_bytes(rest[8..].try_into().ok()?),
+        })
+    }
+}
@@ -1,36 +1,109 @@
-use std::alloc::{GlobalAlloc, Layout};
+#[cfg(not(feature = "mock-host"))]
+use fat_ptr::envelope;
+#[cfg(not(feature = "mock-host"))]
+use fat_ptr::FatPtr;
+pub use fat_ptr::envelope::CallError;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
 
 // ============================================================================
 // 1. HOST & KERNEL BINDS
 // ============================================================================
 
-struct HostAllocator;
+#[cfg(not(feature = "mock-host"))]
 extern "C" {
-    fn host_alloc(size: i32) -> i32;
     fn host_dealloc(ptr: i32, size: i32);
+    fn host_print(ptr: i32, len: i32);
+    fn host_register_id(namespace_ptr: i32, namespace_len: i32, name_ptr: i32, name_len: i32) -> i32;
+    fn host_intern(ptr: i32, len: i32) -> i32;
+    fn host_resolve(id: i32) -> i64;
+    #[link_name = "call"]
+    fn host_call(module_ptr: i32, module_len: i32, func_ptr: i32, func_len: i32, arg0: i32, arg1: i32) -> i64;
+}
 
-    // Kernel Syscalls
+// Kernel Syscalls (universal-test-harness has them natively)
+extern "C" {
     fn sys_register_component(size: i32, align: i32) -> i32;
     fn sys_spawn_entity(count: i32, ids: *const i32, data: *const *const u8) -> i32;
     fn sys_query_tables(ids: *const i32, len: i32, out_len: *mut i32) -> *const i32;
     fn sys_get_table_len(table: i32) -> i32;
     fn sys_get_column_ptr(table: i32, comp: i32) -> *mut u8;
     fn sys_resource(id: i32, size: i32) -> *mut u8;
+    fn sys_run_schedule();
+}
+
+/// A line in the host's log, as the plugin's.
+#[cfg(not(feature = "mock-host"))]
+pub fn print(text: &str) {
+    unsafe { host_print(text.as_ptr() as i32, text.len() as i32) }
 }
 
-unsafe impl GlobalAlloc for HostAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        host_alloc(layout.size() as i32) as *mut u8
+/// The host-wide ID for `name` within `namespace`: the same in every plugin that asks.
+#[cfg(not(feature = "mock-host"))]
+pub fn register_id(namespace: &str, name: &str) -> i32 {
+    unsafe {
+        host_register_id(
+            namespace.as_ptr() as i32,
+            namespace.len() as i32,
+            name.as_ptr() as i32,
+            name.len() as i32,
+        )
     }
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        host_dealloc(ptr as i32, layout.size() as i32);
+}
+
+/// A host-wide handle for `s`, cheap to pass around instead of the string itself.
+/// 0 never names a string.
+#[cfg(not(feature = "mock-host"))]
+pub fn intern(s: &str) -> u32 {
+    unsafe { host_intern(s.as_ptr() as i32, s.len() as i32) as u32 }
+}
+
+/// The string behind a handle from `intern`, by any plugin.
+#[cfg(not(feature = "mock-host"))]
+pub fn resolve(id: u32) -> Option<String> {
+    let packed = unsafe { host_resolve(id as i32) };
+    if packed <= 0 {
+        return None;
+    }
+    let FatPtr { ptr, len } = FatPtr::unpack(packed);
+    let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len as usize) }.to_vec();
+    unsafe { host_dealloc(ptr, len) };
+    // The host only interns valid UTF-8
+    String::from_utf8(bytes).ok()
+}
+
+/// Calls `module::export(payload)` in another plugin and copies out its response.
+#[cfg(not(feature = "mock-host"))]
+pub fn call(module: &str, export: &str, payload: &[u8]) -> Result<Vec<u8>, CallError> {
+    let packed = unsafe {
+        host_call(
+            module.as_ptr() as i32,
+            module.len() as i32,
+            export.as_ptr() as i32,
+            export.len() as i32,
+            payload.as_ptr() as i32,
+            payload.len() as i32,
+        )
+    };
+    let response = envelope::open(packed)?;
+    if response.is_null() {
+        return Ok(Vec::new());
     }
+    let bytes = unsafe { std::slice::from_raw_parts(response.ptr as usize as *const u8, response.len as usize) }.to_vec();
+    // Responses come from the shared host heap, whoever allocated them
+    unsafe { host_dealloc(response.ptr, response.len) };
+    Ok(bytes)
 }
 
+#[cfg(feature = "mock-host")]
+pub mod mock;
+#[cfg(feature = "mock-host")]
+pub use mock::{call, intern, print, register_id, resolve};
+
+// System-backed with mock-host
 #[global_allocator]
-static ALLOCATOR: HostAllocator = HostAllocator;
+static ALLOCATOR: tasksapp_allocator::HostAllocator = tasksapp_allocator::HostAllocator;
 
 // ============================================================================
 // 2. COMPONENTS & COMMANDS
@@ -90,22 +163,33 @@ impl Commands {
     }
 }
 
+/// Runs the kernel's systems, on kernels that schedule them (archived/custom_ecs). Nothing
+/// happens on the others, where plugins run their systems themselves.
+pub fn run_schedule() {
+    unsafe { sys_run_schedule() }
+}
+
 // ============================================================================
 // 3. RESOURCES
 // ============================================================================
 
 pub trait Resource: Sized + 'static {
-    // We change this to a function that CAN be overridden
+    // Resources are looked up by name in the host's registry; the type name by default.
+    // Override it with a shared name (see ecs_protocol) when other plugins need the same one.
+    fn resource_name() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
     fn resource_id() -> i32 {
-        // Default behavior: Generate a random ID (offset by 1000 to avoid conflicts with fixed IDs)
-        static ID: AtomicI32 = AtomicI32::new(-1);
-        let id = ID.load(Ordering::Relaxed);
-        if id == -1 {
-            static CTR: AtomicI32 = AtomicI32::new(1000);
-            let new_id = CTR.fetch_add(1, Ordering::Relaxed);
-            ID.store(new_id, Ordering::Relaxed);
-            return new_id;
+        // Generic statics are shared by every T, so cache per type name
+        static IDS: Mutex<Vec<(&'static str, i32)>> = Mutex::new(Vec::new());
+        let name = Self::resource_name();
+        let mut ids = IDS.lock().unwrap();
+        if let Some(&(_, id)) = ids.iter().find(|(n, _)| *n == name) {
+            return id;
         }
+        let id = register_id("resource", name);
+        ids.push((name, id));
         id
     }
 }
@@ -0,0 +1,102 @@
+use crate::CallError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// What stands in for the host with the `mock-host` feature, in-process: the same functions
+// the crate wraps host calls in, plus a way to set up and look at the host's side in tests.
+//
+//   print               kept for `printed`, and echoed to stdout (captured by cargo test)
+//   register_id         IDs from 1 per namespace, like the host's registry
+//   intern / resolve    handles from 1, like the host's string table
+//   call                runs what `export` registered as module::export, in this thread
+//
+// Allocation is the system allocator's (tasksapp_allocator's mock-host), and the kernel's
+// syscalls come from universal-test-harness. IDs and handles are shared by every test thread
+// since the crate caches them in statics; printed lines and exports are per thread.
+
+type Export = Arc<dyn Fn(&[u8]) -> Result<Vec<u8>, CallError> + Send + Sync>;
+
+#[derive(Default)]
+struct Registry {
+    ids: HashMap<(String, String), i32>,
+    next_ids: HashMap<String, i32>,
+    strings: Vec<String>,
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+thread_local! {
+    static PRINTED: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+    static EXPORTS: std::cell::RefCell<HashMap<(String, String), Export>> = std::cell::RefCell::new(HashMap::new());
+}
+
+fn with_registry<R>(f: impl FnOnce(&mut Registry) -> R) -> R {
+    f(REGISTRY.lock().unwrap().get_or_insert_with(Registry::default))
+}
+
+pub fn print(text: &str) {
+    println!("{}", text);
+    PRINTED.with(|printed| printed.borrow_mut().push(text.to_string()));
+}
+
+/// Every line this thread printed since the last call.
+pub fn printed() -> Vec<String> {
+    PRINTED.with(|printed| std::mem::take(&mut *printed.borrow_mut()))
+}
+
+pub fn register_id(namespace: &str, name: &str) -> i32 {
+    if namespace.is_empty() || name.is_empty() {
+        return -1;
+    }
+    with_registry(|registry| {
+        let key = (namespace.to_string(), name.to_string());
+        if let Some(&id) = registry.ids.get(&key) {
+            return id;
+        }
+        let next = registry.next_ids.entry(namespace.to_string()).or_default();
+        *next += 1;
+        registry.ids.insert(key, *next);
+        *next
+    })
+}
+
+pub fn intern(s: &str) -> u32 {
+    with_registry(|registry| match registry.strings.iter().position(|known| known == s) {
+        Some(idx) => idx as u32 + 1,
+        None => {
+            registry.strings.push(s.to_string());
+            registry.strings.len() as u32
+        }
+    })
+}
+
+pub fn resolve(id: u32) -> Option<String> {
+    let idx = id.checked_sub(1)? as usize;
+    with_registry(|registry| registry.strings.get(idx).cloned())
+}
+
+/// Makes `module::export` callable with `call` on this thread, as if that plugin were loaded.
+pub fn export<F>(module: &str, export: &str, f: F)
+where
+    F: Fn(&[u8]) -> Result<Vec<u8>, CallError> + Send + Sync + 'static,
+{
+    EXPORTS.with(|exports| {
+        exports
+            .borrow_mut()
+            .insert((module.to_string(), export.to_string()), Arc::new(f))
+    });
+}
+
+/// Panics for exports nobody registered, as the host traps the caller for unknown ones.
+pub fn call(module: &str, export: &str, payload: &[u8]) -> Result<Vec<u8>, CallError> {
+    let f = EXPORTS
+        .with(|exports| exports.borrow().get(&(module.to_string(), export.to_string())).cloned())
+        .unwrap_or_else(|| panic!("Provider '{}::{}' not found, mock::export it first", module, export));
+    f(payload)
+}
+
+/// Forgets this thread's exports and printed lines. IDs and handles stay, like the host's.
+pub fn reset() {
+    PRINTED.with(|printed| printed.borrow_mut().clear());
+    EXPORTS.with(|exports| exports.borrow_mut().clear());
+}
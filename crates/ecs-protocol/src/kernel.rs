@@ -0,0 +1,123 @@
+// What an ECS kernel is to the host and to the plugins built on it, whichever ECS is behind it:
+// the syscalls in idl/ecs.idl, exported from a plugin loaded as KERNEL_MODULE. Kernels
+// implement EcsKernel and export_kernel! turns that into the syscalls, so the bevy_ecs one
+// (plugins/ecs-core) and the lightweight archetype one (archived/custom_ecs) are swapped by
+// loading one or the other; host.toml's `[ecs] kernel` picks which.
+//
+//   kernel_init()                                     sets the kernel up (also done on first use)
+//   sys_register_component(size, align) -> id
+//   sys_spawn_entity(count, ids, data) -> entity      data: a pointer to each component's bytes
+//   sys_query_tables(ids, len, out_len) -> tables     the tables having all of `ids`; holds
+//                                                     until the next query
+//   sys_get_table_len(table) -> rows
+//   sys_get_column_ptr(table, id) -> ptr              the component's values, back to back
+//   sys_resource(id, size) -> ptr                     created zeroed when `size` > 0; null if
+//                                                     it doesn't exist and `size` is 0
+//   sys_run_schedule()                                runs the kernel's systems, stage by stage
+
+// The name the kernel is loaded as, whichever it is
+pub const KERNEL_MODULE: &str = "ecs_core";
+
+// A system, called with 0
+pub type SystemFn = extern "C" fn(i32);
+
+pub trait EcsKernel {
+    fn register_component(&mut self, size: usize, align: usize) -> i32;
+    /// Spawns an entity with a copy of each component's bytes.
+    ///
+    /// # Safety
+    /// Each pointer is to a value as large as its component.
+    unsafe fn spawn(&mut self, components: &[(i32, *const u8)]) -> i32;
+    fn query_tables(&mut self, required: &[i32]) -> &[i32];
+    fn table_len(&self, table: i32) -> usize;
+    /// Null if the table doesn't exist or doesn't have the component.
+    fn column_ptr(&mut self, table: i32, component: i32) -> *mut u8;
+    fn resource(&mut self, id: i32, size: usize) -> *mut u8;
+    /// The systems to run, stage by stage. Kernels whose plugins tick themselves have none.
+    fn schedule(&mut self) -> Vec<Vec<SystemFn>> {
+        Vec::new()
+    }
+}
+
+/// Exports the kernel syscalls from `$kernel`, an expression giving something that derefs to
+/// an EcsKernel (a static's lock, say). It's evaluated once per syscall, and not held while
+/// systems run, so they can make syscalls of their own.
+#[macro_export]
+macro_rules! export_kernel {
+    ($kernel:expr) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn kernel_init() {
+            let _ = &mut *$kernel;
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn sys_register_component(size: i32, align: i32) -> i32 {
+            $crate::kernel::EcsKernel::register_component(
+                &mut *$kernel,
+                size.max(0) as usize,
+                align.max(1) as usize,
+            )
+        }
+
+        /// # Safety
+        /// `ids` and `data` hold `count` entries, each pointer to a value of its component.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn sys_spawn_entity(
+            count: i32,
+            ids: *const i32,
+            data: *const *const u8,
+        ) -> i32 {
+            let count = count.max(0) as usize;
+            let components: ::std::vec::Vec<(i32, *const u8)> = if count == 0 {
+                ::std::vec::Vec::new()
+            } else {
+                let ids = unsafe { ::std::slice::from_raw_parts(ids, count) };
+                let data = unsafe { ::std::slice::from_raw_parts(data, count) };
+                ids.iter().copied().zip(data.iter().copied()).collect()
+            };
+            unsafe { $crate::kernel::EcsKernel::spawn(&mut *$kernel, &components) }
+        }
+
+        /// # Safety
+        /// `ids` holds `len` component IDs and `out_len` is writable.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn sys_query_tables(
+            ids: *const i32,
+            len: i32,
+            out_len: *mut i32,
+        ) -> *const i32 {
+            let ids: &[i32] = match len {
+                1.. => unsafe { ::std::slice::from_raw_parts(ids, len as usize) },
+                _ => &[],
+            };
+            #[allow(unused_mut)]
+            let mut kernel = $kernel;
+            let tables = $crate::kernel::EcsKernel::query_tables(&mut *kernel, ids);
+            unsafe { *out_len = tables.len() as i32 };
+            tables.as_ptr()
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn sys_get_table_len(table: i32) -> i32 {
+            $crate::kernel::EcsKernel::table_len(&*$kernel, table) as i32
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn sys_get_column_ptr(table: i32, comp: i32) -> *mut u8 {
+            $crate::kernel::EcsKernel::column_ptr(&mut *$kernel, table, comp)
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn sys_resource(id: i32, size: i32) -> *mut u8 {
+            $crate::kernel::EcsKernel::resource(&mut *$kernel, id, size.max(0) as usize)
+        }
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn sys_run_schedule() {
+            let stages = $crate::kernel::EcsKernel::schedule(&mut *$kernel);
+            for system in stages.into_iter().flatten() {
+                system(0);
+            }
+        }
+    };
+}
@@ -1,22 +1,41 @@
 // crate: ecs-protocol
 use bytemuck::{Pod, Zeroable};
+use layout_fingerprint::{Fingerprint, assert_layout};
 
-// Component IDs must be consistent between Host and WASM
-pub const COMPONENT_POSITION: u32 = 1;
-pub const COMPONENT_TILE: u32 = 2;
-pub const RESOURCE_CONFIG: u32 = 100;
-pub const RESOURCE_STATE: u32 = 101;
+pub mod kernel;
+
+// --- CAPABILITIES ---
+// Bits for `host_capabilities() -> u64` (what the host and its frontend provide) and the
+// `get_capabilities() -> u64` plugin export (what the plugin uses). Plugins should check the
+// host's bits and fall back when something is missing.
+pub const CAPABILITY_TUI: u64 = 1 << 0; // Grid drivers are drawn and receive key input
+pub const CAPABILITY_AUDIO: u64 = 1 << 1;
+pub const CAPABILITY_MOUSE: u64 = 1 << 2; // GridInput carries mouse events
+pub const CAPABILITY_EVENTS: u64 = 1 << 3; // Plugin-to-plugin event callbacks through host_link_call
+pub const CAPABILITY_ECS_KERNEL: u64 = 1 << 4; // A loaded plugin exports the sys_* ECS syscalls
+
+// --- IDS ---
+// Names for host_register_id. The host turns them into IDs every plugin agrees on, so nothing
+// here is a number that could collide.
+pub const ID_NAMESPACE_COMPONENT: &str = "component";
+pub const ID_NAMESPACE_RESOURCE: &str = "resource";
+
+pub const COMPONENT_POSITION: &str = "position";
+pub const COMPONENT_TILE: &str = "tile";
+pub const RESOURCE_CONFIG: &str = "game_config";
+pub const RESOURCE_STATE: &str = "game_state";
+pub const RESOURCE_GRID: &str = "game_grid";
 
 // --- COMPONENTS ---
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Fingerprint)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Fingerprint)]
 pub struct Tile {
     pub is_mine: i32, // boolean as i32 for alignment
     pub adj_count: i32,
@@ -25,7 +44,7 @@ pub struct Tile {
 
 // --- RESOURCES ---
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Default)]
+#[derive(Clone, Copy, Pod, Zeroable, Default, Fingerprint)]
 pub struct GameConfig {
     pub width: i32,
     pub height: i32,
@@ -33,7 +52,7 @@ pub struct GameConfig {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Default)]
+#[derive(Clone, Copy, Pod, Zeroable, Default, Fingerprint)]
 pub struct GameState {
     pub is_game_over: i32,
     pub is_victory: i32,
@@ -41,7 +60,7 @@ pub struct GameState {
 }
 
 #[repr(C)] // Crucial for Host-Wasm interoperability
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Fingerprint)]
 pub struct Cell {
     pub is_mine: bool,
     pub neighbors: u8,
@@ -49,16 +68,23 @@ pub struct Cell {
     pub _padding: u8, // Keep alignment happy
 }
 
+// Kernel and plugins read these straight out of component columns and resource memory
+assert_layout!(Position, size 8, align 4, { x: 0, y: 4 });
+assert_layout!(Tile, size 12, align 4, { is_mine: 0, adj_count: 4, status: 8 });
+assert_layout!(GameConfig, size 12, align 4, { width: 0, height: 4, mine_count: 8 });
+assert_layout!(GameState, size 12, align 4, { is_game_over: 0, is_victory: 4, first_move: 8 });
+assert_layout!(Cell, size 4, align 1, { is_mine: 0, neighbors: 1, status: 2, _padding: 3 });
+
 pub const MAX_WIDTH: usize = 32;
 pub const MAX_HEIGHT: usize = 16;
 pub const MAX_CELLS: usize = MAX_WIDTH * MAX_HEIGHT;
 
 #[repr(C)]
+#[derive(Fingerprint)]
 pub struct GameGrid {
     pub width: i32,
     pub height: i32,
     pub cells: [Cell; MAX_CELLS],
 }
 
-// The "Magic Number" ID for the Grid Resource
-pub const GRID_RESOURCE_ID: i32 = 100;
+assert_layout!(GameGrid, size 8 + 4 * MAX_CELLS, align 4, { width: 0, height: 4, cells: 8 });
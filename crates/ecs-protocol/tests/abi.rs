@@ -0,0 +1,38 @@
+// Components and resources the kernel stores as raw bytes. assert_layout! in the crate already
+// stops the build if one moves; these pin down the bytes themselves.
+#![deny(warnings)]
+
+use ecs_protocol::{Cell, GameConfig, GameGrid, GameState, MAX_CELLS, Position, Tile};
+use std::mem::{align_of, offset_of, size_of};
+
+#[test]
+fn component_sizes() {
+    assert_eq!(size_of::<Position>(), 8);
+    assert_eq!(size_of::<Tile>(), 12);
+    assert_eq!(align_of::<Position>(), 4);
+    assert_eq!(align_of::<Tile>(), 4);
+}
+
+#[test]
+fn position_bytes() {
+    let position = Position { x: 1, y: -1 };
+    assert_eq!(
+        bytemuck::bytes_of(&position),
+        &[1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]
+    );
+}
+
+#[test]
+fn resource_sizes() {
+    assert_eq!(size_of::<GameConfig>(), 12);
+    assert_eq!(size_of::<GameState>(), 12);
+    assert_eq!(offset_of!(GameState, first_move), 8);
+}
+
+#[test]
+fn game_grid_layout() {
+    assert_eq!(size_of::<Cell>(), 4);
+    assert_eq!(align_of::<Cell>(), 1);
+    assert_eq!(offset_of!(GameGrid, cells), 8);
+    assert_eq!(size_of::<GameGrid>(), 8 + MAX_CELLS * size_of::<Cell>());
+}
@@ -0,0 +1,52 @@
+// What every EcsKernel has to do, whichever ECS is behind it: kernel.rs runs it against a mock,
+// custom_ecs.rs and ecs_core.rs against the two kernels. Each of those links one kernel only,
+// since their syscalls share names.
+
+use ecs_protocol::kernel::EcsKernel;
+
+/// Registers, spawns, queries, reads columns and makes a resource through `kernel`, which
+/// starts out empty.
+pub fn check(kernel: &mut impl EcsKernel) {
+    let position = kernel.register_component(8, 4);
+    let tile = kernel.register_component(4, 4);
+    assert_ne!(position, tile);
+    let at: [u8; 8] = [1, 0, 0, 0, 2, 0, 0, 0];
+    let glyph: [u8; 4] = [9, 0, 0, 0];
+    unsafe {
+        kernel.spawn(&[(position, at.as_ptr()), (tile, glyph.as_ptr())]);
+        kernel.spawn(&[(position, at.as_ptr())]);
+    }
+
+    let tiled = kernel.query_tables(&[tile]).to_vec();
+    assert_eq!(tiled.len(), 1, "one table has tiles");
+    let table = tiled[0];
+    assert_eq!(kernel.table_len(table), 1);
+    let column = kernel.column_ptr(table, position);
+    assert_eq!(unsafe { std::slice::from_raw_parts(column, 8) }, at);
+    let column = kernel.column_ptr(table, tile);
+    assert_eq!(unsafe { std::slice::from_raw_parts(column, 4) }, glyph);
+
+    let placed = kernel.query_tables(&[position]).to_vec();
+    assert_eq!(
+        placed
+            .iter()
+            .map(|&table| kernel.table_len(table))
+            .sum::<usize>(),
+        2
+    );
+    let untiled = placed
+        .iter()
+        .copied()
+        .find(|&other| other != table && kernel.table_len(other) == 1);
+    assert!(
+        kernel
+            .column_ptr(untiled.expect("a table without tiles"), tile)
+            .is_null()
+    );
+    assert!(kernel.column_ptr(i32::MAX, tile).is_null());
+
+    assert!(kernel.resource(7, 0).is_null());
+    let resource = kernel.resource(7, 4);
+    assert_eq!(unsafe { std::slice::from_raw_parts(resource, 4) }, [0; 4]);
+    assert_eq!(kernel.resource(7, 0), resource);
+}
@@ -0,0 +1,10 @@
+// The kernel tests (tests/common) against archived/custom_ecs.
+
+mod common;
+
+use custom_ecs::EcsWorld;
+
+#[test]
+fn custom_ecs_is_a_kernel() {
+    common::check(&mut EcsWorld::default());
+}
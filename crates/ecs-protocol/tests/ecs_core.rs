@@ -0,0 +1,10 @@
+// The kernel tests (tests/common) against plugins/ecs-core, on bevy_ecs.
+
+mod common;
+
+use ecs_core::BevyKernel;
+
+#[test]
+fn ecs_core_is_a_kernel() {
+    common::check(&mut BevyKernel::default());
+}
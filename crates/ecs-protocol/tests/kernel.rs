@@ -0,0 +1,140 @@
+// export_kernel! (kernel.rs): syscalls that reach the kernel behind them, and a schedule run
+// without the kernel held. Then the kernel tests (tests/common), on the mock kernel itself.
+
+mod common;
+
+use ecs_protocol::export_kernel;
+use ecs_protocol::kernel::{EcsKernel, SystemFn};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+// One table per set of components, values back to back
+#[derive(Default)]
+struct TinyKernel {
+    layouts: Vec<usize>,
+    tables: Vec<(Vec<i32>, Vec<Vec<u8>>, usize)>,
+    found: Vec<i32>,
+    resources: Vec<(i32, Vec<u8>)>,
+    systems: Vec<SystemFn>,
+}
+
+impl EcsKernel for TinyKernel {
+    fn register_component(&mut self, size: usize, _align: usize) -> i32 {
+        self.layouts.push(size);
+        self.layouts.len() as i32 - 1
+    }
+
+    unsafe fn spawn(&mut self, components: &[(i32, *const u8)]) -> i32 {
+        let ids: Vec<i32> = components.iter().map(|(id, _)| *id).collect();
+        let table = match self.tables.iter().position(|(has, _, _)| *has == ids) {
+            Some(table) => table,
+            None => {
+                self.tables
+                    .push((ids.clone(), vec![Vec::new(); ids.len()], 0));
+                self.tables.len() - 1
+            }
+        };
+        for (column, (id, data)) in components.iter().enumerate() {
+            let bytes = unsafe { std::slice::from_raw_parts(*data, self.layouts[*id as usize]) };
+            self.tables[table].1[column].extend_from_slice(bytes);
+        }
+        self.tables[table].2 += 1;
+        table as i32
+    }
+
+    fn query_tables(&mut self, required: &[i32]) -> &[i32] {
+        self.found = (0..self.tables.len() as i32)
+            .filter(|&table| {
+                required
+                    .iter()
+                    .all(|id| self.tables[table as usize].0.contains(id))
+            })
+            .collect();
+        &self.found
+    }
+
+    fn table_len(&self, table: i32) -> usize {
+        self.tables.get(table as usize).map_or(0, |table| table.2)
+    }
+
+    fn column_ptr(&mut self, table: i32, component: i32) -> *mut u8 {
+        let Some((ids, columns, _)) = self.tables.get_mut(table as usize) else {
+            return std::ptr::null_mut();
+        };
+        match ids.iter().position(|&id| id == component) {
+            Some(column) => columns[column].as_mut_ptr(),
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    fn resource(&mut self, id: i32, size: usize) -> *mut u8 {
+        if !self.resources.iter().any(|(has, _)| *has == id) {
+            if size == 0 {
+                return std::ptr::null_mut();
+            }
+            self.resources.push((id, vec![0; size]));
+        }
+        let (_, blob) = self
+            .resources
+            .iter_mut()
+            .find(|(has, _)| *has == id)
+            .unwrap();
+        blob.as_mut_ptr()
+    }
+
+    fn schedule(&mut self) -> Vec<Vec<SystemFn>> {
+        vec![self.systems.clone()]
+    }
+}
+
+// Set up the first time a syscall needs it
+static KERNEL: LazyLock<Mutex<TinyKernel>> = LazyLock::new(Default::default);
+
+export_kernel!(KERNEL.lock().unwrap());
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+// Makes a syscall of its own, which would deadlock if the kernel were held while it runs
+extern "C" fn counting_system(_: i32) {
+    sys_get_table_len(0);
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn syscalls_reach_the_kernel_and_systems_can_make_their_own() {
+    kernel_init();
+    let position = sys_register_component(8, 4);
+    let tile = sys_register_component(4, 4);
+    let values: [[u8; 8]; 2] = [[1, 0, 0, 0, 2, 0, 0, 0], [9, 0, 0, 0, 0, 0, 0, 0]];
+    let ids = [position, tile];
+    let data = [values[0].as_ptr(), values[1].as_ptr()];
+    let table = unsafe { sys_spawn_entity(2, ids.as_ptr(), data.as_ptr()) };
+    unsafe { sys_spawn_entity(1, ids.as_ptr(), data.as_ptr()) };
+
+    let mut found = 0;
+    let tables = unsafe { sys_query_tables([tile].as_ptr(), 1, &mut found) };
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(tables, found as usize) },
+        [table]
+    );
+    unsafe { sys_query_tables(ids.as_ptr(), 0, &mut found) };
+    assert_eq!(found, 2, "no components is every table");
+    assert_eq!(sys_get_table_len(table), 1);
+    let column = sys_get_column_ptr(table, position);
+    assert_eq!(unsafe { std::slice::from_raw_parts(column, 8) }, values[0]);
+    assert!(sys_get_column_ptr(table + 1, tile).is_null());
+
+    assert!(sys_resource(7, 0).is_null());
+    let resource = sys_resource(7, 4);
+    assert_eq!(unsafe { std::slice::from_raw_parts(resource, 4) }, [0; 4]);
+    assert_eq!(sys_resource(7, 0), resource);
+
+    KERNEL.lock().unwrap().systems = vec![counting_system, counting_system];
+    sys_run_schedule();
+    assert_eq!(RAN.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn the_mock_kernel_is_a_kernel() {
+    common::check(&mut TinyKernel::default());
+}
@@ -0,0 +1,112 @@
+// Answers to cross-plugin calls. An export answers with a packed FatPtr to its response, as
+// always; an answer with ERROR_BIT set is an error envelope instead. Clearing the bit gives a
+// FatPtr to the status (i32, little-endian) followed by a UTF-8 message, in host_alloc'd
+// memory that whoever reads it frees with host_dealloc.
+//
+// Exports answer with `error` when they fail. The host answers STATUS_TRAPPED on its own when
+// a callee linked through host_link_call_checked traps, so the trap stops there instead of
+// unwinding the caller. Callers sort answers with `open`.
+//
+// Older callers see an error envelope as a negative length, i.e. an empty answer.
+
+use crate::FatPtr;
+
+pub const ERROR_BIT: i64 = i64::MIN;
+
+pub const STATUS_TRAPPED: i32 = 1; // The callee trapped (a panic, usually); the host answered for it
+pub const STATUS_FAILED: i32 = 2; // The callee reported a failure of its own
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallError {
+    pub status: i32,
+    pub message: String,
+}
+
+impl CallError {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.status.to_le_bytes().to_vec();
+        bytes.extend_from_slice(self.message.as_bytes());
+        bytes
+    }
+
+    /// Envelopes too short for a status count as STATUS_FAILED.
+    pub fn decode(bytes: &[u8]) -> Self {
+        let Some((status, message)) = bytes.split_first_chunk::<4>() else {
+            return Self {
+                status: STATUS_FAILED,
+                message: String::new(),
+            };
+        };
+        Self {
+            status: i32::from_le_bytes(*status),
+            message: String::from_utf8_lossy(message).into_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            STATUS_TRAPPED => write!(f, "call trapped: {}", self.message),
+            status => write!(f, "call failed ({}): {}", status, self.message),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+pub fn is_error(packed: i64) -> bool {
+    packed & ERROR_BIT != 0
+}
+
+/// Marks the envelope at `envelope` as an error answer.
+pub fn pack_error(envelope: FatPtr) -> i64 {
+    envelope.pack() | ERROR_BIT
+}
+
+/// Where an error answer's envelope is.
+pub fn unpack_error(packed: i64) -> FatPtr {
+    FatPtr::unpack(packed & !ERROR_BIT)
+}
+
+// --- GUEST SIDE ---
+
+extern "C" {
+    #[link_name = "host_alloc"]
+    fn __host_alloc(size: i32) -> i32;
+    #[link_name = "host_dealloc"]
+    fn __host_dealloc(ptr: i32, size: i32);
+}
+
+/// Answers the current call with an error envelope instead of a response.
+pub fn error(status: i32, message: &str) -> i64 {
+    let bytes = CallError {
+        status,
+        message: message.to_string(),
+    }
+    .encode();
+    let ptr = unsafe { __host_alloc(bytes.len() as i32) };
+    if ptr == 0 {
+        // Still an error, just without the details
+        return ERROR_BIT;
+    }
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as usize as *mut u8, bytes.len()) };
+    pack_error(FatPtr::new(ptr, bytes.len() as i32))
+}
+
+/// The response of a successful answer, or the error (its envelope already freed).
+pub fn open(packed: i64) -> Result<FatPtr, CallError> {
+    if !is_error(packed) {
+        return Ok(FatPtr::unpack(packed));
+    }
+    let envelope = unpack_error(packed);
+    if envelope.is_null() {
+        return Err(CallError::decode(&[]));
+    }
+    let bytes = unsafe {
+        std::slice::from_raw_parts(envelope.ptr as usize as *const u8, envelope.len as usize)
+    };
+    let error = CallError::decode(bytes);
+    unsafe { __host_dealloc(envelope.ptr, envelope.len) };
+    Err(error)
+}
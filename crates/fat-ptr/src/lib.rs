@@ -0,0 +1,104 @@
+// A (ptr, len) pair in the plugins' linear memory, and the one way it travels as an i64:
+// length in the high 32 bits, pointer in the low 32 bits.
+//
+// Guests hand buffers out with `into_leaked` and take them back with `reclaim`; hosts
+// copy them out of shared memory with `read` (feature "host"), through `shared`. Calls that can
+// fail answer through `envelope`.
+
+pub mod envelope;
+pub mod shared;
+
+/// Bumped whenever something plugins and the host both bake in changes: GridCell and the other
+/// shared layouts, the host call set, or how FatPtrs are packed. Plugins report the version they
+/// were built against through `__abi_version` (see `export_abi_version!`), and the host refuses
+/// the ones outside its supported range.
+pub const ABI_VERSION: i32 = 1;
+
+/// Defines the `__abi_version() -> i32` export the host checks before running anything else.
+/// Every plugin needs exactly one.
+#[macro_export]
+macro_rules! export_abi_version {
+    () => {
+        #[no_mangle]
+        pub extern "C" fn __abi_version() -> i32 {
+            $crate::ABI_VERSION
+        }
+    };
+}
+
+/// `(len << 32) | ptr`
+pub fn pack(ptr: i32, len: i32) -> i64 {
+    FatPtr { ptr, len }.pack()
+}
+
+/// Inverse of `pack`, as `(ptr, len)`.
+pub fn unpack(packed: i64) -> (i32, i32) {
+    let fat = FatPtr::unpack(packed);
+    (fat.ptr, fat.len)
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FatPtr {
+    pub ptr: i32,
+    pub len: i32,
+}
+
+impl FatPtr {
+    pub const NULL: FatPtr = FatPtr { ptr: 0, len: 0 };
+
+    pub fn new(ptr: i32, len: i32) -> Self {
+        Self { ptr, len }
+    }
+
+    /// Borrows `data`'s location. Only meaningful inside wasm32, where pointers are offsets.
+    pub fn of<T>(data: &[T]) -> Self {
+        Self {
+            ptr: data.as_ptr() as usize as i32,
+            len: data.len() as i32,
+        }
+    }
+
+    pub fn pack(self) -> i64 {
+        ((self.len as i64) << 32) | (self.ptr as i64 & 0xFFFFFFFF)
+    }
+
+    pub fn unpack(packed: i64) -> Self {
+        Self {
+            ptr: (packed & 0xFFFFFFFF) as i32,
+            len: (packed >> 32) as i32,
+        }
+    }
+
+    pub fn is_null(self) -> bool {
+        self.ptr == 0 || self.len <= 0
+    }
+
+    /// Hands `bytes` out for someone else to read. Spare capacity is dropped first, so the
+    /// (ptr, len) pair is all `reclaim` needs to free it.
+    pub fn into_leaked(bytes: Vec<u8>) -> Self {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len() as i32;
+        let ptr = Box::into_raw(bytes) as *mut u8 as usize as i32;
+        Self { ptr, len }
+    }
+
+    /// Takes back a buffer from `into_leaked`. Null/empty pointers give an empty Vec.
+    ///
+    /// # Safety
+    /// `self` must come from `into_leaked` in this module, and be reclaimed only once.
+    pub unsafe fn reclaim(self) -> Vec<u8> {
+        if self.is_null() {
+            return Vec::new();
+        }
+        let slice =
+            std::ptr::slice_from_raw_parts_mut(self.ptr as usize as *mut u8, self.len as usize);
+        Box::from_raw(slice).into_vec()
+    }
+
+    /// Copies the bytes out of the plugins' memory. `None` if they're not all inside it.
+    #[cfg(feature = "host")]
+    pub fn read(self, memory: &wasmtime::SharedMemory) -> Option<Vec<u8>> {
+        shared::SharedSlice::of(memory).read(self.ptr, self.len)
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::UnsafeCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// The host's one way into the plugins' shared memory, which wasmtime hands out as
+// &[UnsafeCell<u8>]: plugins (and, later, other host threads) may write any byte of it at any
+// time, so a plain &[u8] over it is only sound while nothing else runs.
+//
+//   read(ptr, len) / read_into(ptr, out)   copy bytes out
+//   write(ptr, data)                       copy bytes in
+//   bytes()                                every byte, in order
+//   view(ptr, len)                         borrow without copying (unsafe: see below)
+//
+// Copies go byte by byte through relaxed atomics, so two threads racing on the same bytes
+// see each byte either before or after the other's write, never undefined behavior. A copy
+// isn't atomic as a whole: readers that need a consistent value need a lock of their own.
+//
+// `view` is the exception, for zero-copy reads (the embedder's grid): its caller promises that
+// nothing writes the range while the borrow lives, as the single-threaded host can while no
+// plugin is running.
+//
+// Ranges are (ptr, len) as plugins pass them; copies answer None/false for any range that
+// isn't all inside the memory, negative ones included.
+
+#[derive(Clone, Copy)]
+pub struct SharedSlice<'a> {
+    cells: &'a [UnsafeCell<u8>],
+}
+
+// Safety: every access through a SharedSlice is atomic, except `view`, whose caller
+// guarantees it doesn't race
+unsafe impl Send for SharedSlice<'_> {}
+unsafe impl Sync for SharedSlice<'_> {}
+
+impl<'a> SharedSlice<'a> {
+    pub fn new(cells: &'a [UnsafeCell<u8>]) -> Self {
+        Self { cells }
+    }
+
+    /// The plugins' memory, as the host holds it.
+    #[cfg(feature = "host")]
+    pub fn of(memory: &'a wasmtime::SharedMemory) -> Self {
+        Self::new(memory.data())
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    fn range(&self, ptr: i32, len: usize) -> Option<Range<usize>> {
+        let start = usize::try_from(ptr).ok()?;
+        let end = start.checked_add(len)?;
+        (end <= self.cells.len()).then_some(start..end)
+    }
+
+    fn byte(cell: &UnsafeCell<u8>) -> &AtomicU8 {
+        // Safety: the cell is valid and aligned for as long as the slice, and nothing accesses
+        // it non-atomically while it's borrowed (see `view`)
+        unsafe { AtomicU8::from_ptr(cell.get()) }
+    }
+
+    /// Copies `len` bytes out from `ptr`. `None` if they're not all inside the memory.
+    pub fn read(&self, ptr: i32, len: i32) -> Option<Vec<u8>> {
+        let mut out = vec![0; usize::try_from(len).ok()?];
+        self.read_into(ptr, &mut out).then_some(out)
+    }
+
+    /// Fills `out` with the bytes from `ptr`. False, with `out` untouched, if they're not all
+    /// inside the memory.
+    pub fn read_into(&self, ptr: i32, out: &mut [u8]) -> bool {
+        let Some(range) = self.range(ptr, out.len()) else {
+            return false;
+        };
+        for (byte, cell) in out.iter_mut().zip(&self.cells[range]) {
+            *byte = Self::byte(cell).load(Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Copies `data` in at `ptr`. False, with nothing written, if it doesn't all fit.
+    pub fn write(&self, ptr: i32, data: &[u8]) -> bool {
+        let Some(range) = self.range(ptr, data.len()) else {
+            return false;
+        };
+        for (&byte, cell) in data.iter().zip(&self.cells[range]) {
+            Self::byte(cell).store(byte, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Every byte of the memory, read as the iterator gets to it.
+    pub fn bytes(&self) -> impl Iterator<Item = u8> + 'a {
+        self.cells
+            .iter()
+            .map(|cell| Self::byte(cell).load(Ordering::Relaxed))
+    }
+
+    /// Borrows `len` bytes at `ptr` without copying. `None` if they're not all inside the memory.
+    ///
+    /// # Safety
+    /// Nothing may write the range, through this slice or otherwise, while the borrow lives.
+    pub unsafe fn view(&self, ptr: i32, len: i32) -> Option<&'a [u8]> {
+        let range = self.range(ptr, usize::try_from(len).ok()?)?;
+        let cells = &self.cells[range];
+        Some(std::slice::from_raw_parts(
+            cells.as_ptr() as *const u8,
+            cells.len(),
+        ))
+    }
+}
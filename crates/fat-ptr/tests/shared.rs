@@ -0,0 +1,107 @@
+// SharedSlice under threads racing on the same memory, as a multi-threaded host would have them.
+// On their own these check what each copy sees; run under miri to check that the races are
+// defined behavior at all (plain copies instead of the atomics fail there as data races):
+//
+//   cargo +nightly miri test -p fat-ptr --test shared
+
+use fat_ptr::shared::SharedSlice;
+use std::cell::UnsafeCell;
+use std::thread;
+
+// Miri is slow enough that a few rounds show any race
+const ROUNDS: usize = if cfg!(miri) { 4 } else { 1000 };
+
+fn memory(len: usize) -> Vec<UnsafeCell<u8>> {
+    (0..len).map(|_| UnsafeCell::new(0)).collect()
+}
+
+#[test]
+fn copies_outside_the_memory_are_refused_whole() {
+    let cells = memory(64);
+    let memory = SharedSlice::new(&cells);
+
+    assert!(memory.write(60, &[1; 4]));
+    assert!(!memory.write(61, &[2; 4]));
+    assert!(!memory.write(-1, &[2]));
+    assert_eq!(memory.read(60, 4), Some(vec![1; 4]));
+    assert_eq!(memory.read(61, 4), None);
+    assert_eq!(memory.read(0, -1), None);
+    assert_eq!(memory.read(i32::MAX, 1), None);
+
+    let mut out = [9; 8];
+    assert!(!memory.read_into(60, &mut out));
+    assert_eq!(out, [9; 8]);
+    assert_eq!(memory.bytes().filter(|&byte| byte == 1).count(), 4);
+}
+
+#[test]
+fn writers_on_separate_ranges_never_touch_each_other() {
+    const THREADS: usize = 4;
+    const STRIPE: usize = 32;
+    let cells = memory(THREADS * STRIPE);
+    let memory = SharedSlice::new(&cells);
+
+    thread::scope(|s| {
+        for id in 0..THREADS {
+            s.spawn(move || {
+                let ptr = (id * STRIPE) as i32;
+                for round in 0..ROUNDS {
+                    let value = (id * 16 + round % 16) as u8;
+                    assert!(memory.write(ptr, &[value; STRIPE]));
+                    assert_eq!(memory.read(ptr, STRIPE as i32), Some(vec![value; STRIPE]));
+                }
+            });
+        }
+    });
+
+    for id in 0..THREADS {
+        let last = (id * 16 + (ROUNDS - 1) % 16) as u8;
+        assert_eq!(
+            memory.read((id * STRIPE) as i32, STRIPE as i32),
+            Some(vec![last; STRIPE])
+        );
+    }
+}
+
+#[test]
+fn readers_racing_a_writer_see_each_byte_before_or_after() {
+    const LEN: usize = 64;
+    let cells = memory(LEN);
+    let memory = SharedSlice::new(&cells);
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            for round in 0..ROUNDS {
+                let value = if round % 2 == 0 { 0xaa } else { 0x55 };
+                assert!(memory.write(0, &[value; LEN]));
+            }
+        });
+        for _ in 0..2 {
+            s.spawn(move || {
+                for _ in 0..ROUNDS {
+                    // A whole copy may mix rounds, a single byte never
+                    let bytes = memory.read(0, LEN as i32).unwrap();
+                    assert!(
+                        bytes.iter().all(|byte| matches!(byte, 0 | 0xaa | 0x55)),
+                        "{:?}",
+                        bytes
+                    );
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn views_see_what_was_written_before_they_were_taken() {
+    let cells = memory(16);
+    let memory = SharedSlice::new(&cells);
+    thread::scope(|s| {
+        s.spawn(move || assert!(memory.write(4, b"grid")));
+    });
+
+    // Safety: the writer has been joined, so nothing writes while the view lives
+    let view = unsafe { memory.view(4, 4) }.unwrap();
+    assert_eq!(view, b"grid");
+    assert_eq!(unsafe { memory.view(14, 4) }, None);
+}
@@ -0,0 +1,179 @@
+use crate::GridCell;
+
+// Sub-cell "pixels" for drivers. Braille packs 2x4 dots per cell, half-blocks 1x2.
+// Color is per cell in braille mode (the last dot set wins) and per pixel in half-block mode.
+
+const BRAILLE_BASE: u32 = 0x2800;
+const UPPER_HALF_BLOCK: u32 = 0x2580; // '▀'
+
+// Dot bit for (x, y) inside a braille cell, indexed [y][x]
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CanvasMode {
+    Braille,
+    HalfBlock,
+}
+
+pub struct Canvas<'a> {
+    cells: &'a mut [GridCell],
+    width: i32,
+    height: i32,
+    mode: CanvasMode,
+    background: u8,
+}
+
+impl<'a> Canvas<'a> {
+    /// `width`/`height` are in cells; `cells` must hold at least `width * height` of them.
+    pub fn new(cells: &'a mut [GridCell], width: i32, height: i32, mode: CanvasMode) -> Self {
+        Self {
+            cells,
+            width,
+            height,
+            mode,
+            background: 0,
+        }
+    }
+
+    /// Color used for pixels that are off (and for cell backgrounds).
+    pub fn with_background(mut self, color: u8) -> Self {
+        self.background = color;
+        self
+    }
+
+    /// Resolution in pixels.
+    pub fn pixel_size(&self) -> (i32, i32) {
+        match self.mode {
+            CanvasMode::Braille => (self.width * 2, self.height * 4),
+            CanvasMode::HalfBlock => (self.width, self.height * 2),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let count = (self.width * self.height).max(0) as usize;
+        for cell in self.cells.iter_mut().take(count) {
+            cell.character = ' ' as u32;
+            cell.fg_color = self.background;
+            cell.bg_color = self.background;
+        }
+    }
+
+    /// Turns a pixel on. Out of range coordinates are ignored.
+    pub fn set(&mut self, x: i32, y: i32, color: u8) {
+        let (pw, ph) = self.pixel_size();
+        if x < 0 || y < 0 || x >= pw || y >= ph {
+            return;
+        }
+
+        match self.mode {
+            CanvasMode::Braille => {
+                let idx = ((y / 4) * self.width + x / 2) as usize;
+                let background = self.background;
+                let Some(cell) = self.cells.get_mut(idx) else {
+                    return;
+                };
+                if !(BRAILLE_BASE..BRAILLE_BASE + 0x100).contains(&cell.character) {
+                    cell.character = BRAILLE_BASE;
+                    cell.bg_color = background;
+                }
+                cell.character |= BRAILLE_DOTS[(y % 4) as usize][(x % 2) as usize];
+                cell.fg_color = color;
+            }
+            CanvasMode::HalfBlock => {
+                let idx = ((y / 2) * self.width + x) as usize;
+                let background = self.background;
+                let Some(cell) = self.cells.get_mut(idx) else {
+                    return;
+                };
+                // Top pixel is the glyph's fg, bottom pixel shows through as bg
+                if cell.character != UPPER_HALF_BLOCK {
+                    cell.character = UPPER_HALF_BLOCK;
+                    cell.fg_color = background;
+                    cell.bg_color = background;
+                }
+                if y % 2 == 0 {
+                    cell.fg_color = color;
+                } else {
+                    cell.bg_color = color;
+                }
+            }
+        }
+    }
+
+    // Bresenham
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.set(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Outline of a `w` x `h` rectangle with its top-left pixel at (x, y).
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: u8) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        let (x1, y1) = (x + w - 1, y + h - 1);
+        self.line(x, y, x1, y, color);
+        self.line(x, y1, x1, y1, color);
+        self.line(x, y, x, y1, color);
+        self.line(x1, y, x1, y1, color);
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: u8) {
+        for py in y..y + h {
+            for px in x..x + w {
+                self.set(px, py, color);
+            }
+        }
+    }
+
+    // Midpoint circle
+    pub fn circle(&mut self, cx: i32, cy: i32, r: i32, color: u8) {
+        if r < 0 {
+            return;
+        }
+        let (mut x, mut y) = (r, 0);
+        let mut err = 1 - r;
+
+        while x >= y {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.set(cx + px, cy + py, color);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+}
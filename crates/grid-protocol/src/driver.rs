@@ -0,0 +1,299 @@
+use crate::widgets::WidgetNode;
+use crate::{
+    DirtyRect, GridCell, GridInput, GRID_PROTOCOL_VERSION, INPUT_COMMIT, INPUT_KEY, INPUT_PREEDIT,
+};
+pub use ecs_protocol::{
+    CAPABILITY_AUDIO, CAPABILITY_ECS_KERNEL, CAPABILITY_EVENTS, CAPABILITY_MOUSE, CAPABILITY_TUI,
+};
+#[doc(hidden)]
+pub use fat_ptr::{self as __fat_ptr, FatPtr};
+#[doc(hidden)]
+pub use layout_fingerprint as __layout;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+// What a grid driver plugin implements. `export_grid_driver!` turns an implementor into
+// the exports the host binds (tick, set_input, set_tickrate, get_grid_ptr, get_grid_dimensions,
+// get_dirty_rects, get_widgets, get_frame_counter, get_capabilities, grid_negotiate,
+// __abi_version, __layout_fingerprints).
+pub trait GridDriver: Send + 'static {
+    fn new() -> Self;
+
+    /// Grid size in cells, as (width, height).
+    fn dimensions(&self) -> (i32, i32);
+
+    /// The `width * height` cells of the last completed frame, which the host reads after
+    /// every tick. Drivers using `DoubleBuffer` return its front buffer here.
+    fn grid(&mut self) -> &mut [GridCell];
+
+    fn handle_input(&mut self, input: &GridInput);
+
+    /// IME composition events. The default ignores preedit text and feeds committed
+    /// text to `handle_input` one character at a time, so key-only drivers still get it.
+    fn handle_composition(&mut self, composition: Composition<'_>) {
+        if let Composition::Commit(text) = composition {
+            for c in text.chars() {
+                self.handle_input(&GridInput {
+                    input_type: INPUT_KEY,
+                    key_code: c as u32,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    fn tick(&mut self, delta: f32);
+
+    fn set_tickrate(&mut self, _rate: f32) {}
+
+    /// Regions changed by the last `tick`. `None` tells the host to re-read the whole grid.
+    /// Like `grid`, the returned slice must stay put until the next tick.
+    fn dirty_rects(&self) -> Option<&[DirtyRect]> {
+        None
+    }
+
+    /// Number of completed frames, bumped every time the front buffer changes. The host
+    /// reads it around each grid copy to detect torn reads. `None` if the driver doesn't count.
+    fn frame_counter(&self) -> Option<u64> {
+        None
+    }
+
+    /// Widgets the host draws over the grid after each tick (see `crate::widgets`).
+    fn widgets(&self) -> Vec<WidgetNode> {
+        Vec::new()
+    }
+
+    /// `CAPABILITY_*` bits the driver uses, reported to the host as `get_capabilities`.
+    fn capabilities(&self) -> u64 {
+        CAPABILITY_TUI
+    }
+
+    /// `GRID_EXT_*` bits the driver would like. Only those the host offers are granted;
+    /// check `granted_extensions` before using one.
+    fn extensions(&self) -> u64 {
+        0
+    }
+}
+
+static GRANTED_EXTENSIONS: AtomicU64 = AtomicU64::new(0);
+static HOST_GRID_VERSION: AtomicU32 = AtomicU32::new(1);
+
+/// `GRID_EXT_*` bits the host granted. 0 until `grid_negotiate` runs, and on hosts that
+/// predate it.
+pub fn granted_extensions() -> u64 {
+    GRANTED_EXTENSIONS.load(Ordering::Relaxed)
+}
+
+/// The newest grid protocol version the host speaks, 1 on hosts that never negotiate.
+pub fn host_grid_version() -> u32 {
+    HOST_GRID_VERSION.load(Ordering::Relaxed)
+}
+
+// What export_grid_driver!'s grid_negotiate answers
+pub fn negotiate(version: i32, offered: i64, wanted: u64) -> i64 {
+    let granted = wanted & offered as u64;
+    HOST_GRID_VERSION.store(
+        (version as u32).min(GRID_PROTOCOL_VERSION),
+        Ordering::Relaxed,
+    );
+    GRANTED_EXTENSIONS.store(granted, Ordering::Relaxed);
+    granted as i64
+}
+
+extern "C" {
+    #[link_name = "host_capabilities"]
+    fn __host_capabilities() -> i64;
+}
+
+/// `CAPABILITY_*` bits the host provides, so drivers can skip what isn't there.
+pub fn host_capabilities() -> u64 {
+    unsafe { __host_capabilities() as u64 }
+}
+
+// Two grids: drivers draw into `back` and `swap` once the frame is complete, so `front`
+// (what `grid` hands to the host) never shows a half-drawn frame.
+pub struct DoubleBuffer {
+    buffers: [Vec<GridCell>; 2],
+    front: usize,
+    counter: u64,
+}
+
+impl DoubleBuffer {
+    pub fn new(len: usize) -> Self {
+        Self {
+            buffers: [
+                vec![GridCell::default(); len],
+                vec![GridCell::default(); len],
+            ],
+            front: 0,
+            counter: 0,
+        }
+    }
+
+    pub fn front(&mut self) -> &mut [GridCell] {
+        &mut self.buffers[self.front]
+    }
+
+    /// The buffer to draw the next frame into. It holds the frame before last, not the
+    /// current one, so drivers that only touch some cells must redraw from their own state.
+    pub fn back(&mut self) -> &mut [GridCell] {
+        &mut self.buffers[self.front ^ 1]
+    }
+
+    /// Publishes the back buffer as the new front.
+    pub fn swap(&mut self) {
+        self.front ^= 1;
+        self.counter += 1;
+    }
+
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Composition<'a> {
+    /// Text being composed, not yet part of the document. Empty when composition ends.
+    Preedit(&'a str),
+    Commit(&'a str),
+}
+
+/// Routes the input the host wrote at `input` to the matching trait method.
+///
+/// # Safety
+/// `input` must point to a GridInput written by the host (see `crate::input_text`).
+pub unsafe fn dispatch_input<D: GridDriver>(driver: &mut D, input: *const GridInput) {
+    let event = *input;
+    match event.input_type {
+        INPUT_PREEDIT => driver.handle_composition(Composition::Preedit(crate::input_text(input))),
+        INPUT_COMMIT => driver.handle_composition(Composition::Commit(crate::input_text(input))),
+        _ => driver.handle_input(&event),
+    }
+}
+
+// Width in the high half, height in the low half
+pub fn pack_dimensions(width: i32, height: i32) -> i64 {
+    ((width as i64) << 32) | (height as i64 & 0xFFFFFFFF)
+}
+
+// Count and pointer as a FatPtr; -1 when the whole grid is dirty
+pub fn pack_dirty_rects(rects: Option<&[DirtyRect]>) -> i64 {
+    match rects {
+        Some(rects) => FatPtr::of(rects).pack(),
+        None => -1,
+    }
+}
+
+#[cfg(feature = "guest")]
+#[doc(hidden)]
+pub use tasksapp_allocator as __alloc;
+
+#[cfg(feature = "guest")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __grid_driver_allocator {
+    () => {
+        #[cfg(target_arch = "wasm32")]
+        #[global_allocator]
+        static __GRID_DRIVER_ALLOC: $crate::driver::__alloc::HostAllocator =
+            $crate::driver::__alloc::HostAllocator;
+    };
+}
+
+#[cfg(not(feature = "guest"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __grid_driver_allocator {
+    () => {};
+}
+
+#[macro_export]
+macro_rules! export_grid_driver {
+    ($driver:ty) => {
+        $crate::__grid_driver_allocator!();
+        $crate::driver::__fat_ptr::export_abi_version!();
+        $crate::driver::__layout::export_layout_fingerprints!(
+            $crate::GridCell,
+            $crate::GridInput,
+            $crate::DirtyRect
+        );
+
+        static __GRID_DRIVER: ::std::sync::Mutex<Option<$driver>> = ::std::sync::Mutex::new(None);
+
+        fn __with_driver<R>(f: impl FnOnce(&mut $driver) -> R) -> R {
+            let mut guard = __GRID_DRIVER.lock().unwrap();
+            let driver = guard.get_or_insert_with(<$driver as $crate::driver::GridDriver>::new);
+            f(driver)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_grid_dimensions() -> i64 {
+            __with_driver(|d| {
+                let (w, h) = $crate::driver::GridDriver::dimensions(d);
+                $crate::driver::pack_dimensions(w, h)
+            })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_grid_ptr() -> i32 {
+            __with_driver(|d| $crate::driver::GridDriver::grid(d).as_mut_ptr() as i32)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_dirty_rects() -> i64 {
+            __with_driver(|d| {
+                $crate::driver::pack_dirty_rects($crate::driver::GridDriver::dirty_rects(d))
+            })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_frame_counter() -> i64 {
+            __with_driver(|d| match $crate::driver::GridDriver::frame_counter(d) {
+                Some(counter) => (counter & i64::MAX as u64) as i64,
+                None => -1,
+            })
+        }
+
+        // Holds the last encoding so the pointer stays valid while the host reads it
+        static __GRID_WIDGETS: ::std::sync::Mutex<Vec<u8>> = ::std::sync::Mutex::new(Vec::new());
+
+        #[no_mangle]
+        pub extern "C" fn get_widgets() -> i64 {
+            let nodes = __with_driver(|d| $crate::driver::GridDriver::widgets(d));
+            if nodes.is_empty() {
+                return 0;
+            }
+            let mut bytes = __GRID_WIDGETS.lock().unwrap();
+            *bytes = $crate::widgets::encode(&nodes);
+            $crate::driver::FatPtr::of(&bytes).pack()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn get_capabilities() -> i64 {
+            __with_driver(|d| $crate::driver::GridDriver::capabilities(d) as i64)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn grid_negotiate(version: i32, offered: i64) -> i64 {
+            let wanted = __with_driver(|d| $crate::driver::GridDriver::extensions(d));
+            $crate::driver::negotiate(version, offered, wanted)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn set_tickrate(rate: f32) {
+            __with_driver(|d| $crate::driver::GridDriver::set_tickrate(d, rate))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn set_input(ptr: i32) {
+            // Safety: The host guarantees this pointer is valid and points to a GridInput
+            __with_driver(|d| unsafe {
+                $crate::driver::dispatch_input(d, ptr as *const $crate::GridInput)
+            })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn tick(delta: f32) {
+            __with_driver(|d| $crate::driver::GridDriver::tick(d, delta))
+        }
+    };
+}
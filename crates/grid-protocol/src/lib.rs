@@ -1,16 +1,47 @@
 use bytemuck::{Pod, Zeroable};
+use layout_fingerprint::{assert_layout, Fingerprint};
+
+pub mod canvas;
+pub mod driver;
+pub mod widgets;
+
+// --- PROTOCOL VERSION ---
+// Version 1 is the plain protocol: ANSI 256 cells, key input, image cells. Version 2 drivers
+// also export `grid_negotiate(version, offered) -> i64`, which the host calls once after
+// loading with the newest version it speaks and the GRID_EXT_* bits it offers. The driver
+// answers with the bits it will use, a subset of `offered`; anything not granted stays off.
+// Drivers without the export are treated as version 1 and get exactly the v1 features.
+pub const GRID_PROTOCOL_VERSION: u32 = 2;
+
+// Extensions
+pub const GRID_EXT_TRUECOLOR: u64 = 1 << 0; // 24-bit cell colors (no host offers it yet)
+pub const GRID_EXT_STYLES: u64 = 1 << 1; // GridCell::style carries STYLE_* bits
+pub const GRID_EXT_LAYERS: u64 = 1 << 2; // Stacked grids (no host offers it yet)
+pub const GRID_EXT_MOUSE: u64 = 1 << 3; // GridInput carries mouse events
+pub const GRID_EXT_IMAGES: u64 = 1 << 4; // CELL_IMAGE anchors and host_upload_image; implied in v1
+pub const GRID_EXT_PLAYERS: u64 = 1 << 5; // GridInput::player says whose input it is
+
+// What a version 1 driver may use without negotiating
+pub const GRID_V1_EXTENSIONS: u64 = GRID_EXT_IMAGES;
+
+// Cell Styles (GRID_EXT_STYLES)
+pub const STYLE_BOLD: u16 = 1;
+pub const STYLE_DIM: u16 = 2;
+pub const STYLE_ITALIC: u16 = 4;
+pub const STYLE_UNDERLINE: u16 = 8;
+pub const STYLE_REVERSE: u16 = 16;
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Debug, Default)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Default, Fingerprint)]
 pub struct GridCell {
     pub character: u32, // UTF-32 character
     pub fg_color: u8,   // ANSI 256 color index
     pub bg_color: u8,   // ANSI 256 color index
-    pub padding: u16,   // Padding for alignment
+    pub style: u16,     // STYLE_* bits with GRID_EXT_STYLES, otherwise ignored
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable, Debug, Default)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Default, Fingerprint)]
 pub struct GridInput {
     pub input_type: u32, // 0=None, 1=Key
     pub key_code: u32,   // UTF-32 char or Special Key Constant
@@ -18,9 +49,47 @@ pub struct GridInput {
     pub padding: [u8; 3],
 }
 
+// Players (GRID_EXT_PLAYERS)
+// Input from several players goes to the same driver, one input per tick, each tagged with
+// who sent it: 0 is the host's own keyboard, 1 and up remote players in the order they joined;
+// in rollback netplay, each peer's position in the shared address list.
+// The id sits in the first padding byte, so the layout is unchanged; without the extension
+// it's always 0.
+impl GridInput {
+    pub fn player(&self) -> u8 {
+        self.padding[0]
+    }
+
+    pub fn with_player(mut self, player: u8) -> Self {
+        self.padding[0] = player;
+        self
+    }
+}
+
+// Host and drivers both cast raw shared memory to these, so their layouts are frozen
+assert_layout!(GridCell, size 8, align 4, { character: 0, fg_color: 4, bg_color: 5, style: 6 });
+assert_layout!(GridInput, size 12, align 4, { input_type: 0, key_code: 4, modifiers: 8, padding: 9 });
+
 // Input Types
 pub const INPUT_NONE: u32 = 0;
 pub const INPUT_KEY: u32 = 1;
+pub const INPUT_PREEDIT: u32 = 2; // IME composition in progress
+pub const INPUT_COMMIT: u32 = 3; // IME (or pasted) text to insert
+
+// Composition Text
+// For INPUT_PREEDIT / INPUT_COMMIT the UTF-8 text is stored right after the GridInput,
+// and `key_code` holds its length in bytes. An empty preedit ends the composition.
+pub const INPUT_TEXT_CAPACITY: usize = 256;
+
+/// Reads the composition text that follows a PREEDIT/COMMIT input. Invalid UTF-8 yields "".
+///
+/// # Safety
+/// `input` must point to a GridInput the host wrote, followed by its text buffer.
+pub unsafe fn input_text<'a>(input: *const GridInput) -> &'a str {
+    let len = ((*input).key_code as usize).min(INPUT_TEXT_CAPACITY);
+    let text = (input as *const u8).add(std::mem::size_of::<GridInput>());
+    std::str::from_utf8(std::slice::from_raw_parts(text, len)).unwrap_or("")
+}
 
 // Special Key Constants (Starting after max valid Unicode 0x10FFFF)
 pub const KEY_ENTER: u32 = 0x110000;
@@ -37,3 +106,42 @@ pub const KEY_TAB: u32 = 0x110008;
 pub const MOD_SHIFT: u8 = 1;
 pub const MOD_CTRL: u8 = 2;
 pub const MOD_ALT: u8 = 4;
+
+// Image Cells (GRID_EXT_IMAGES)
+// A cell whose `character` has CELL_IMAGE set anchors an uploaded image instead of a
+// glyph. The low bits hold the id returned by `host_upload_image`.
+pub const CELL_IMAGE: u32 = 0x8000_0000;
+pub const CELL_IMAGE_ID_MASK: u32 = 0x00FF_FFFF;
+
+// Image Formats
+pub const IMAGE_FORMAT_RGBA8: u32 = 0;
+
+/// Describes a blob passed to `host_upload_image(desc_ptr, data_ptr, data_len) -> i32`.
+/// The call returns the new image id, or -1 if the upload was rejected.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Default)]
+pub struct ImageDesc {
+    pub width: u32,    // Pixels
+    pub height: u32,   // Pixels
+    pub format: u32,   // IMAGE_FORMAT_*
+    pub fallback: u32, // Glyph drawn when the terminal has no graphics support
+    pub cols: u16,     // Cells covered, starting at the anchor cell
+    pub rows: u16,
+}
+
+assert_layout!(ImageDesc, size 20, align 4, { width: 0, height: 4, format: 8, fallback: 12, cols: 16, rows: 18 });
+
+/// A region of the grid, in cells, that changed during the last tick.
+/// Drivers may export `get_dirty_rects() -> i64` returning the rect count in the high
+/// 32 bits and a pointer to the `DirtyRect` array in the low 32 bits. A count of 0 means
+/// nothing changed; -1 (or no export at all) means the whole grid must be re-read.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, Default, PartialEq, Eq, Fingerprint)]
+pub struct DirtyRect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+assert_layout!(DirtyRect, size 8, align 2, { x: 0, y: 2, width: 4, height: 6 });
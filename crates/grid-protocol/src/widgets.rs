@@ -0,0 +1,210 @@
+// Retained-mode UI on top of the cell grid. A driver describes widgets, the host draws
+// them with its own toolkit over the driver's cells. Drivers export
+// `get_widgets() -> i64` (byte length high, pointer low, 0 for none) pointing at `encode`'s output.
+//
+// Wire format, all integers little endian:
+//   u32 node count, then per node: u8 kind, u16 x/y/width/height, kind fields.
+//   Strings are u32 byte length + UTF-8, string lists are u32 count + strings,
+//   selections are u32 (u32::MAX = none), ratios are f32.
+
+const KIND_TEXT: u8 = 0;
+const KIND_TEXT_INPUT: u8 = 1;
+const KIND_LIST: u8 = 2;
+const KIND_MENU: u8 = 3;
+const KIND_PROGRESS: u8 = 4;
+
+const NO_SELECTION: u32 = u32::MAX;
+
+/// Cell area a widget covers, relative to the driver's grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WidgetRect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Widget {
+    Text {
+        text: String,
+    },
+    TextInput {
+        label: String,
+        value: String,
+        // Char index of the cursor, shown when focused
+        cursor: u32,
+        focused: bool,
+    },
+    List {
+        title: String,
+        items: Vec<String>,
+        selected: Option<u32>,
+    },
+    // A horizontal row of choices
+    Menu {
+        items: Vec<String>,
+        selected: Option<u32>,
+    },
+    Progress {
+        label: String,
+        // 0.0 ..= 1.0
+        ratio: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetNode {
+    pub area: WidgetRect,
+    pub widget: Widget,
+}
+
+pub fn encode(nodes: &[WidgetNode]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_u32(&mut out, nodes.len() as u32);
+    for node in nodes {
+        let kind = match node.widget {
+            Widget::Text { .. } => KIND_TEXT,
+            Widget::TextInput { .. } => KIND_TEXT_INPUT,
+            Widget::List { .. } => KIND_LIST,
+            Widget::Menu { .. } => KIND_MENU,
+            Widget::Progress { .. } => KIND_PROGRESS,
+        };
+        out.push(kind);
+        for v in [node.area.x, node.area.y, node.area.width, node.area.height] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+
+        match &node.widget {
+            Widget::Text { text } => put_str(&mut out, text),
+            Widget::TextInput {
+                label,
+                value,
+                cursor,
+                focused,
+            } => {
+                put_str(&mut out, label);
+                put_str(&mut out, value);
+                put_u32(&mut out, *cursor);
+                out.push(*focused as u8);
+            }
+            Widget::List {
+                title,
+                items,
+                selected,
+            } => {
+                put_str(&mut out, title);
+                put_list(&mut out, items);
+                put_u32(&mut out, selected.unwrap_or(NO_SELECTION));
+            }
+            Widget::Menu { items, selected } => {
+                put_list(&mut out, items);
+                put_u32(&mut out, selected.unwrap_or(NO_SELECTION));
+            }
+            Widget::Progress { label, ratio } => {
+                put_str(&mut out, label);
+                out.extend_from_slice(&ratio.to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+/// Parses `encode`'s output. Returns `None` on truncated or malformed input.
+pub fn decode(bytes: &[u8]) -> Option<Vec<WidgetNode>> {
+    let mut r = Reader { bytes };
+    let count = r.u32()?;
+    let mut nodes = Vec::new();
+    for _ in 0..count {
+        let kind = r.u8()?;
+        let area = WidgetRect {
+            x: r.u16()?,
+            y: r.u16()?,
+            width: r.u16()?,
+            height: r.u16()?,
+        };
+        let widget = match kind {
+            KIND_TEXT => Widget::Text { text: r.str()? },
+            KIND_TEXT_INPUT => Widget::TextInput {
+                label: r.str()?,
+                value: r.str()?,
+                cursor: r.u32()?,
+                focused: r.u8()? != 0,
+            },
+            KIND_LIST => Widget::List {
+                title: r.str()?,
+                items: r.list()?,
+                selected: r.selection()?,
+            },
+            KIND_MENU => Widget::Menu {
+                items: r.list()?,
+                selected: r.selection()?,
+            },
+            KIND_PROGRESS => Widget::Progress {
+                label: r.str()?,
+                ratio: f32::from_le_bytes(r.take(4)?.try_into().ok()?),
+            },
+            _ => return None,
+        };
+        nodes.push(WidgetNode { area, widget });
+    }
+    Some(nodes)
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_list(out: &mut Vec<u8>, items: &[String]) {
+    put_u32(out, items.len() as u32);
+    for item in items {
+        put_str(out, item);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < n {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn list(&mut self) -> Option<Vec<String>> {
+        let count = self.u32()?;
+        (0..count).map(|_| self.str()).collect()
+    }
+
+    fn selection(&mut self) -> Option<Option<u32>> {
+        let v = self.u32()?;
+        Some((v != NO_SELECTION).then_some(v))
+    }
+}
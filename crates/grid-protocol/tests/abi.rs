@@ -0,0 +1,63 @@
+// The layouts host and drivers cast shared memory to. assert_layout! in the crate already
+// stops the build if one moves; these pin down the bytes themselves.
+#![deny(warnings)]
+
+use grid_protocol::{
+    DirtyRect, GridCell, GridInput, ImageDesc, INPUT_KEY, INPUT_TEXT_CAPACITY, MOD_CTRL,
+};
+use std::mem::{align_of, offset_of, size_of};
+
+#[test]
+fn grid_cell_is_eight_bytes() {
+    assert_eq!(size_of::<GridCell>(), 8);
+    assert_eq!(align_of::<GridCell>(), 4);
+}
+
+#[test]
+fn grid_cell_fields_sit_where_the_host_reads_them() {
+    let cell = GridCell {
+        character: 0x0001_F600,
+        fg_color: 15,
+        bg_color: 4,
+        style: 0x0102,
+    };
+    assert_eq!(
+        bytemuck::bytes_of(&cell),
+        &[0x00, 0xF6, 0x01, 0x00, 15, 4, 0x02, 0x01]
+    );
+}
+
+#[test]
+fn grid_input_offsets() {
+    assert_eq!(size_of::<GridInput>(), 12);
+    assert_eq!(offset_of!(GridInput, input_type), 0);
+    assert_eq!(offset_of!(GridInput, key_code), 4);
+    assert_eq!(offset_of!(GridInput, modifiers), 8);
+    assert_eq!(offset_of!(GridInput, padding), 9);
+
+    let input = GridInput {
+        input_type: INPUT_KEY,
+        key_code: 'a' as u32,
+        modifiers: MOD_CTRL,
+        padding: [0; 3],
+    };
+    assert_eq!(
+        bytemuck::bytes_of(&input),
+        &[1, 0, 0, 0, b'a', 0, 0, 0, MOD_CTRL, 0, 0, 0]
+    );
+}
+
+// Composition text starts right after the input, so the buffer the host allocates is this big
+#[test]
+fn input_buffer_size() {
+    assert_eq!(size_of::<GridInput>() + INPUT_TEXT_CAPACITY, 268);
+}
+
+#[test]
+fn dirty_rect_and_image_desc() {
+    assert_eq!(size_of::<DirtyRect>(), 8);
+    assert_eq!(align_of::<DirtyRect>(), 2);
+    assert_eq!(size_of::<ImageDesc>(), 20);
+    assert_eq!(offset_of!(ImageDesc, cols), 16);
+    assert_eq!(offset_of!(ImageDesc, rows), 18);
+}
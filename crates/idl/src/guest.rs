@@ -0,0 +1,339 @@
+use crate::{Function, Interface, Kind, Type, WasmType};
+use anyhow::{anyhow, Result};
+use std::fmt::Write;
+
+// Generated stubs lean on the including crate for the payload codec (`bincode` unless the IDL
+// names another), `serde` and `fat_ptr`, and on whatever is in scope for the payload types
+// (usually a glob import of the protocol crate).
+
+/// Rust source for every interface: a struct per `interface` block, an extern block per
+/// `syscalls` block. Meant to be `include!`d.
+pub fn guest_bindings(interfaces: &[Interface]) -> Result<String> {
+    let mut out = String::from("// Generated by the idl crate. Do not edit.\n\n");
+
+    let archives = interfaces
+        .iter()
+        .flat_map(|i| &i.functions)
+        .any(|f| matches!(f.ret, Some(Type::Archived(_))));
+    if archives {
+        out.push_str(ARCHIVED_RESPONSE);
+    }
+    if interfaces.iter().any(|i| i.kind == Kind::Interface) {
+        out.push_str(
+            "extern \"C\" {\n    fn host_link_call(provider_mod_ptr: i32, provider_mod_len: i32, \
+             provider_fn_ptr: i32, provider_fn_len: i32) -> i32;\n    \
+             fn host_link_call_checked(provider_mod_ptr: i32, provider_mod_len: i32, \
+             provider_fn_ptr: i32, provider_fn_len: i32) -> i32;\n}\n\n",
+        );
+    }
+    for interface in interfaces {
+        match interface.kind {
+            Kind::Interface => linked(&mut out, interface)?,
+            Kind::Syscalls => syscalls(&mut out, interface),
+        }
+    }
+    Ok(out)
+}
+
+const ARCHIVED_RESPONSE: &str = r#"/// An archived answer, checked once and then read in place. The provider gets its buffer back
+/// when this drops.
+pub struct ArchivedResponse<T> {
+    response: ::fat_ptr::FatPtr,
+    free_response: extern "C" fn(i32, i32),
+    _answer: ::std::marker::PhantomData<T>,
+}
+
+#[allow(dead_code)]
+impl<T> ArchivedResponse<T>
+where
+    T: ::rkyv::Archive,
+    T::Archived: ::rkyv::Portable
+        + for<'a> ::rkyv::bytecheck::CheckBytes<::rkyv::api::high::HighValidator<'a, ::rkyv::rancor::Error>>,
+{
+    fn new(packed: i64, free_response: extern "C" fn(i32, i32)) -> Option<Self> {
+        let response = ::fat_ptr::FatPtr::unpack(packed);
+        if response.len <= 0 {
+            return None;
+        }
+        // Built first, so a malformed answer is still freed on the way out
+        let answer = Self {
+            response,
+            free_response,
+            _answer: ::std::marker::PhantomData,
+        };
+        ::rkyv::access::<T::Archived, ::rkyv::rancor::Error>(answer.bytes()).ok()?;
+        Some(answer)
+    }
+
+    pub fn get(&self) -> &T::Archived {
+        // Safety: validated in new, and the buffer stays ours until drop
+        unsafe { ::rkyv::access_unchecked::<T::Archived>(self.bytes()) }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe {
+            ::std::slice::from_raw_parts(self.response.ptr as usize as *const u8, self.response.len as usize)
+        }
+    }
+}
+
+impl<T> Drop for ArchivedResponse<T> {
+    fn drop(&mut self) {
+        (self.free_response)(self.response.ptr, self.response.len);
+    }
+}
+
+"#;
+
+fn linked(out: &mut String, interface: &Interface) -> Result<()> {
+    let answers_payload = interface
+        .functions
+        .iter()
+        .any(|f| matches!(f.ret, Some(Type::Payload(_))));
+    let answers_buffer = interface
+        .functions
+        .iter()
+        .any(|f| matches!(f.ret, Some(Type::Payload(_) | Type::Archived(_))));
+    if answers_buffer {
+        let free = interface
+            .functions
+            .iter()
+            .find(|f| f.name == "free_response");
+        let valid = free.is_some_and(|f| {
+            f.ret.is_none() && f.params.len() == 2 && f.params.iter().all(|p| p.ty == Type::I32)
+        });
+        if !valid {
+            return Err(anyhow!(
+                "'{}' answers with payloads, so it needs 'fn free_response(ptr: i32, len: i32);'",
+                interface.module
+            ));
+        }
+    }
+
+    let name = camel_case(&interface.module);
+    // Stubs cover the whole interface, whether or not the crate calls every export
+    let _ = writeln!(
+        out,
+        "/// Calls into the plugin loaded as `{module}`, linking each export on first use.\n\
+         /// Exports answering buffers are linked checked: when one traps or answers an error,\n\
+         /// its stub returns `None` and `take_error` says why.\n\
+         #[derive(Default)]\n\
+         pub struct {name} {{\n    linked: ::std::collections::HashMap<&'static str, usize>,\n    \
+         last_error: Option<::fat_ptr::envelope::CallError>,\n}}\n\n\
+         #[allow(dead_code)]\n\
+         impl {name} {{\n    pub const MODULE: &'static str = \"{module}\";\n\n    \
+         /// Why the last stub that returned `None` did, if the export answered an error.\n    \
+         pub fn take_error(&mut self) -> Option<::fat_ptr::envelope::CallError> {{\n        \
+         self.last_error.take()\n    }}\n",
+        module = interface.module,
+    );
+    for function in &interface.functions {
+        stub(out, function, &interface.codec);
+    }
+
+    if answers_buffer {
+        out.push_str(
+            "    // Lets successful answers through and keeps the error of failed ones\n    \
+             fn check(&mut self, packed: i64) -> Option<i64> {\n        \
+             match ::fat_ptr::envelope::open(packed) {\n            \
+             Ok(_) => Some(packed),\n            \
+             Err(error) => {\n                \
+             self.last_error = Some(error);\n                \
+             None\n            }\n        }\n    }\n\n",
+        );
+    }
+    if answers_payload {
+        let _ = write!(
+            out,
+            "    // Decodes a packed answer, then hands the buffer back now that it's copied out\n    \
+             fn take_response<T: ::serde::de::DeserializeOwned>(&mut self, packed: i64) -> Option<T> {{\n        \
+             let response = ::fat_ptr::FatPtr::unpack(self.check(packed)?);\n        \
+             if response.len <= 0 {{\n            return None;\n        }}\n        \
+             let bytes = unsafe {{\n            \
+             ::std::slice::from_raw_parts(response.ptr as usize as *const u8, response.len as usize)\n        }};\n        \
+             let decoded = ::{codec}::deserialize(bytes).ok();\n        \
+             self.free_response(response.ptr, response.len);\n        \
+             decoded\n    }}\n\n",
+            codec = interface.codec,
+        );
+    }
+    // A table index is a function pointer in wasm; calling it with the wrong signature traps,
+    // which is why every stub spells its lowered type out
+    out.push_str(
+        "    fn link(&mut self, name: &'static str, checked: bool) -> usize {\n        \
+         *self.linked.entry(name).or_insert_with(|| {\n            \
+         let link = if checked { host_link_call_checked } else { host_link_call };\n            \
+         let idx = unsafe {\n                \
+         link(\n                    \
+         Self::MODULE.as_ptr() as i32,\n                    \
+         Self::MODULE.len() as i32,\n                    \
+         name.as_ptr() as i32,\n                    \
+         name.len() as i32,\n                )\n            };\n            \
+         idx as usize\n        })\n    }\n}\n\n",
+    );
+    Ok(())
+}
+
+fn stub(out: &mut String, function: &Function, codec: &str) {
+    let answers_payload = matches!(function.ret, Some(Type::Payload(_) | Type::Archived(_)));
+    let mut params = vec!["&mut self".to_string()];
+    let mut setup = String::new();
+    let mut args = Vec::new();
+
+    for param in &function.params {
+        let Some(name) = &param.name else {
+            args.extend(param.ty.lower_param().iter().map(|t| zero(*t).to_string()));
+            continue;
+        };
+        match &param.ty {
+            Type::Str => {
+                params.push(format!("{}: &str", name));
+                let _ = writeln!(
+                    setup,
+                    "        let {name} = ::fat_ptr::FatPtr::of({name}.as_bytes());"
+                );
+                args.push(format!("{name}.ptr, {name}.len"));
+            }
+            Type::Payload(ty) => {
+                params.push(format!("{}: &{}", name, ty));
+                let encode = if answers_payload {
+                    ".ok()?".to_string()
+                } else {
+                    format!(".expect(\"'{}' failed to encode\")", name)
+                };
+                let _ = writeln!(
+                    setup,
+                    "        let {name} = ::{codec}::serialize({name}){encode};"
+                );
+                let _ = writeln!(
+                    setup,
+                    "        let {name} = ::fat_ptr::FatPtr::of(&{name});"
+                );
+                args.push(format!("{name}.ptr, {name}.len"));
+            }
+            Type::Pointer(ty) => {
+                params.push(format!("{}: {}", name, ty));
+                args.push(format!("{} as usize as i32", name));
+            }
+            ty => {
+                params.push(format!("{}: {}", name, rust_type(ty)));
+                args.push(name.clone());
+            }
+        }
+    }
+
+    let lowered: Vec<&str> = function.wasm_params().into_iter().map(wasm_name).collect();
+    let fn_type = match function.wasm_results().first() {
+        Some(result) => format!(
+            "extern \"C\" fn({}) -> {}",
+            lowered.join(", "),
+            wasm_name(*result)
+        ),
+        None => format!("extern \"C\" fn({})", lowered.join(", ")),
+    };
+    let call = format!("call({})", args.join(", "));
+    let (ret, body) = match &function.ret {
+        None => (String::new(), call),
+        Some(Type::Payload(ty)) => (
+            format!(" -> Option<{}>", ty),
+            format!("self.take_response({})", call),
+        ),
+        Some(Type::Archived(ty)) => {
+            let _ = writeln!(
+                setup,
+                "        let free: extern \"C\" fn(i32, i32) = unsafe {{ ::std::mem::transmute(self.link(\"free_response\", false)) }};"
+            );
+            (
+                format!(" -> Option<ArchivedResponse<{}>>", ty),
+                format!("ArchivedResponse::new(self.check({})?, free)", call),
+            )
+        }
+        Some(Type::Pointer(ty)) => (
+            format!(" -> {}", ty),
+            format!("{} as usize as {}", call, ty),
+        ),
+        Some(ty) => (format!(" -> {}", rust_type(ty)), call),
+    };
+
+    let _ = writeln!(
+        out,
+        "    pub fn {name}({params}){ret} {{\n{setup}        \
+         let call: {fn_type} = unsafe {{ ::std::mem::transmute(self.link(\"{name}\", {answers_payload})) }};\n        \
+         {body}\n    }}\n",
+        name = function.name,
+        params = params.join(", "),
+    );
+}
+
+fn syscalls(out: &mut String, interface: &Interface) {
+    let _ = writeln!(
+        out,
+        "// Exported by `{}`, imported from env\nextern \"C\" {{",
+        interface.module
+    );
+    for function in &interface.functions {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let name = p.name.clone().unwrap_or_else(|| format!("_unused{}", idx));
+                format!("{}: {}", name, rust_type(&p.ty))
+            })
+            .collect();
+        let ret = function
+            .ret
+            .as_ref()
+            .map(|t| format!(" -> {}", rust_type(t)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "    pub fn {}({}){};",
+            function.name,
+            params.join(", "),
+            ret
+        );
+    }
+    out.push_str("}\n\n");
+}
+
+fn rust_type(ty: &Type) -> String {
+    match ty {
+        Type::I32 => "i32".to_string(),
+        Type::I64 => "i64".to_string(),
+        Type::F32 => "f32".to_string(),
+        Type::F64 => "f64".to_string(),
+        Type::Pointer(t) | Type::Payload(t) | Type::Archived(t) => t.clone(),
+        Type::Str => "&str".to_string(),
+    }
+}
+
+fn wasm_name(ty: WasmType) -> &'static str {
+    match ty {
+        WasmType::I32 => "i32",
+        WasmType::I64 => "i64",
+        WasmType::F32 => "f32",
+        WasmType::F64 => "f64",
+    }
+}
+
+fn zero(ty: WasmType) -> &'static str {
+    match ty {
+        WasmType::I32 | WasmType::I64 => "0",
+        WasmType::F32 | WasmType::F64 => "0.0",
+    }
+}
+
+// tasksapp_core -> TasksappCore
+fn camel_case(module: &str) -> String {
+    module
+        .split(['_', '-'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+use crate::{Interface, WasmType};
+use std::fmt::Write;
+
+/// Rust source for `INTERFACES`, the lowered signature of every export an IDL file promises.
+/// The including module supplies `InterfaceSpec`, `ExportSpec` and `WasmType` with the fields
+/// used here.
+pub fn host_bindings(interfaces: &[Interface]) -> String {
+    let mut out = String::from("// Generated by the idl crate. Do not edit.\n\npub const INTERFACES: &[InterfaceSpec] = &[\n");
+    for interface in interfaces {
+        let _ = writeln!(
+            out,
+            "    InterfaceSpec {{\n        module: \"{}\",\n        exports: &[",
+            interface.module
+        );
+        for function in &interface.functions {
+            let _ = writeln!(
+                out,
+                "            ExportSpec {{ name: \"{}\", params: &[{}], results: &[{}] }},",
+                function.name,
+                list(&function.wasm_params()),
+                list(&function.wasm_results()),
+            );
+        }
+        out.push_str("        ],\n    },\n");
+    }
+    out.push_str("];\n");
+    out
+}
+
+fn list(types: &[WasmType]) -> String {
+    types
+        .iter()
+        .map(|t| format!("WasmType::{:?}", t))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,354 @@
+// A minimal IDL for calls between plugins, and the code generated from it.
+//
+//   interface tasksapp_core {            exports reached through host_link_call
+//       fn new_task(request: NewTaskRequest) -> NewTaskResult;
+//   }
+//   syscalls ecs_core {                  exports guests import straight from `env`
+//       fn sys_get_table_len(table: i32) -> i32;
+//   }
+//
+// Types are i32, i64, f32, f64, raw pointers (`*const T`, `*mut T`), `str` (UTF-8 as ptr, len)
+// or any other Rust type, which travels bincode-encoded as (ptr, len) and comes back as a
+// packed FatPtr answer. `-> archived T` answers with an rkyv archive the caller reads in place
+// (stubs return an ArchivedResponse). A parameter named `_` is ignored by the export and left
+// out of stubs. Exports answering buffers are linked with host_link_call_checked, so a trap
+// or fat_ptr::envelope error in them makes the stub return `None` instead of trapping too.
+// `codec some_crate::module;` before a block encodes its payloads with that module's
+// bincode-style `serialize` / `deserialize` instead (e.g. `tasksapp_net::wire`).
+// Declarations are one per line; `//` starts a comment.
+//
+// Build scripts turn .idl files into guest stubs (`write_guest_bindings`) and into the export
+// signatures the host checks plugins against (`write_host_bindings`).
+
+mod guest;
+mod host;
+
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+pub use guest::guest_bindings;
+pub use host::host_bindings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Linked at runtime with host_link_call and called through the table
+    Interface,
+    /// Imported from `env`, raw types only
+    Syscalls,
+}
+
+#[derive(Clone, Debug)]
+pub struct Interface {
+    pub kind: Kind,
+    pub module: String,
+    pub functions: Vec<Function>,
+    /// Path of the payload codec, `bincode` unless a `codec` line said otherwise
+    pub codec: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub ret: Option<Type>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Param {
+    /// `None` for `_`
+    pub name: Option<String>,
+    pub ty: Type,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Type {
+    I32,
+    I64,
+    F32,
+    F64,
+    /// `*const T` / `*mut T`, spelled as written
+    Pointer(String),
+    Str,
+    /// Anything else, bincode-encoded
+    Payload(String),
+    /// `archived T`, results only
+    Archived(String),
+}
+
+/// Value types as wasm sees them once an IDL signature is lowered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WasmType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(text: &str) -> Self {
+        match text {
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "str" => Type::Str,
+            t if t.starts_with('*') => Type::Pointer(t.to_string()),
+            t if t.starts_with("archived ") => {
+                Type::Archived(t["archived ".len()..].trim().to_string())
+            }
+            t => Type::Payload(t.to_string()),
+        }
+    }
+
+    fn is_raw(&self) -> bool {
+        !matches!(self, Type::Str | Type::Payload(_) | Type::Archived(_))
+    }
+
+    pub fn lower_param(&self) -> &'static [WasmType] {
+        match self {
+            Type::I32 | Type::Pointer(_) => &[WasmType::I32],
+            Type::I64 => &[WasmType::I64],
+            Type::F32 => &[WasmType::F32],
+            Type::F64 => &[WasmType::F64],
+            Type::Str | Type::Payload(_) | Type::Archived(_) => &[WasmType::I32, WasmType::I32],
+        }
+    }
+
+    pub fn lower_result(&self) -> WasmType {
+        match self {
+            Type::I32 | Type::Pointer(_) => WasmType::I32,
+            Type::F32 => WasmType::F32,
+            Type::F64 => WasmType::F64,
+            // Payloads answer with a packed FatPtr
+            Type::I64 | Type::Str | Type::Payload(_) | Type::Archived(_) => WasmType::I64,
+        }
+    }
+}
+
+impl Function {
+    pub fn wasm_params(&self) -> Vec<WasmType> {
+        self.params
+            .iter()
+            .flat_map(|p| p.ty.lower_param().iter().copied())
+            .collect()
+    }
+
+    pub fn wasm_results(&self) -> Vec<WasmType> {
+        self.ret.iter().map(Type::lower_result).collect()
+    }
+}
+
+pub fn parse(source: &str) -> Result<Vec<Interface>> {
+    let mut interfaces = Vec::new();
+    let mut current: Option<Interface> = None;
+    let mut codec = DEFAULT_CODEC.to_string();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        parse_line(line, &mut current, &mut codec, &mut interfaces)
+            .with_context(|| format!("line {}", number + 1))?;
+    }
+
+    if let Some(open) = current {
+        return Err(anyhow!("'{}' is missing its closing brace", open.module));
+    }
+    Ok(interfaces)
+}
+
+const DEFAULT_CODEC: &str = "bincode";
+
+fn parse_line(
+    line: &str,
+    current: &mut Option<Interface>,
+    codec: &mut String,
+    done: &mut Vec<Interface>,
+) -> Result<()> {
+    if line == "}" {
+        let interface = current
+            .take()
+            .ok_or(anyhow!("'}}' without an open block"))?;
+        done.push(interface);
+        return Ok(());
+    }
+
+    if let Some(interface) = current {
+        let function = parse_function(line)?;
+        validate(interface.kind, &function)?;
+        if interface.functions.iter().any(|f| f.name == function.name) {
+            return Err(anyhow!("'{}' is declared twice", function.name));
+        }
+        interface.functions.push(function);
+        return Ok(());
+    }
+
+    // Applies to every block after it
+    if let Some(path) = line.strip_prefix("codec ") {
+        let path = path
+            .strip_suffix(';')
+            .ok_or(anyhow!("expected 'codec <path>;'"))?
+            .trim();
+        if !path.split("::").all(is_ident) {
+            return Err(anyhow!("'{}' is not a valid codec path", path));
+        }
+        *codec = path.to_string();
+        return Ok(());
+    }
+
+    let header = line.strip_suffix('{').ok_or(anyhow!(
+        "expected 'interface <module> {{' or 'syscalls <module> {{'"
+    ))?;
+    let (kind, module) = match header.split_whitespace().collect::<Vec<_>>()[..] {
+        ["interface", module] => (Kind::Interface, module),
+        ["syscalls", module] => (Kind::Syscalls, module),
+        _ => {
+            return Err(anyhow!(
+                "expected 'interface <module> {{' or 'syscalls <module> {{'"
+            ))
+        }
+    };
+    if !is_ident(module) {
+        return Err(anyhow!("'{}' is not a valid module name", module));
+    }
+    *current = Some(Interface {
+        kind,
+        module: module.to_string(),
+        functions: Vec::new(),
+        codec: codec.clone(),
+    });
+    Ok(())
+}
+
+// fn name(a: T, b: U) -> R;
+fn parse_function(line: &str) -> Result<Function> {
+    let rest = line
+        .strip_prefix("fn ")
+        .and_then(|l| l.strip_suffix(';'))
+        .ok_or(anyhow!("expected 'fn name(...) -> Type;'"))?;
+    let (name, rest) = rest.split_once('(').ok_or(anyhow!("missing '('"))?;
+    let name = name.trim();
+    if !is_ident(name) {
+        return Err(anyhow!("'{}' is not a valid function name", name));
+    }
+    let (args, ret) = rest.rsplit_once(')').ok_or(anyhow!("missing ')'"))?;
+
+    let mut params = Vec::new();
+    for arg in split_top_level(args) {
+        let (param, ty) = arg
+            .split_once(':')
+            .ok_or(anyhow!("parameter '{}' needs a type", arg))?;
+        let param = param.trim();
+        if param != "_" && !is_ident(param) {
+            return Err(anyhow!("'{}' is not a valid parameter name", param));
+        }
+        params.push(Param {
+            name: (param != "_").then(|| param.to_string()),
+            ty: Type::parse(ty.trim()),
+        });
+    }
+
+    let ret = match ret.trim() {
+        "" => None,
+        r => Some(Type::parse(
+            r.strip_prefix("->")
+                .ok_or(anyhow!("expected '->' before the result"))?
+                .trim(),
+        )),
+    };
+    Ok(Function {
+        name: name.to_string(),
+        params,
+        ret,
+    })
+}
+
+fn validate(kind: Kind, function: &Function) -> Result<()> {
+    if kind == Kind::Syscalls {
+        let raw =
+            function.params.iter().all(|p| p.ty.is_raw()) && function.ret.iter().all(Type::is_raw);
+        if !raw {
+            return Err(anyhow!(
+                "syscall '{}' can only use raw types",
+                function.name
+            ));
+        }
+    }
+    if function
+        .params
+        .iter()
+        .any(|p| matches!(p.ty, Type::Archived(_)))
+    {
+        return Err(anyhow!(
+            "'{}' can only answer with archived types, not take them",
+            function.name
+        ));
+    }
+    if function.ret == Some(Type::Str) {
+        return Err(anyhow!(
+            "'{}' can't answer with str, use String",
+            function.name
+        ));
+    }
+    Ok(())
+}
+
+// Commas inside generics (`Result<A, B>`) don't split
+fn split_top_level(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in args.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&args[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&args[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn read_all(inputs: &[impl AsRef<Path>]) -> Result<Vec<Interface>> {
+    let mut interfaces = Vec::new();
+    for input in inputs {
+        let path = input.as_ref();
+        // Build scripts are the only callers, so let cargo know what to watch
+        println!("cargo:rerun-if-changed={}", path.display());
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        interfaces.extend(parse(&source).with_context(|| format!("In '{}'", path.display()))?);
+    }
+    Ok(interfaces)
+}
+
+/// Parses `inputs` and writes their guest stubs to `out`; meant for build.rs.
+pub fn write_guest_bindings(inputs: &[impl AsRef<Path>], out: impl AsRef<Path>) -> Result<()> {
+    let code = guest_bindings(&read_all(inputs)?)?;
+    std::fs::write(out.as_ref(), code)
+        .with_context(|| format!("Failed to write '{}'", out.as_ref().display()))
+}
+
+/// Parses `inputs` and writes the host's export tables to `out`; meant for build.rs.
+pub fn write_host_bindings(inputs: &[impl AsRef<Path>], out: impl AsRef<Path>) -> Result<()> {
+    let code = host_bindings(&read_all(inputs)?);
+    std::fs::write(out.as_ref(), code)
+        .with_context(|| format!("Failed to write '{}'", out.as_ref().display()))
+}
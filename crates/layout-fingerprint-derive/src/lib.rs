@@ -0,0 +1,69 @@
+// #[derive(Fingerprint)], see the layout-fingerprint crate
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+#[proc_macro_derive(Fingerprint)]
+pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    // Without a fixed layout there's nothing stable to fingerprint
+    let mut fixed = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                fixed = true;
+            }
+            Ok(())
+        })?;
+    }
+    if !fixed {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Fingerprint needs #[repr(C)]",
+        ));
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Fingerprint only works on structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Fingerprint needs named fields",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let steps = fields.named.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        quote! {
+            .usize(::core::mem::offset_of!(Self, #ident))
+            .usize(::core::mem::size_of::<#ty>())
+            .usize(::core::mem::align_of::<#ty>())
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::layout_fingerprint::Fingerprint for #name #ty_generics #where_clause {
+            const NAME: &'static str = stringify!(#name);
+            const FINGERPRINT: u64 = ::layout_fingerprint::Hasher::new()
+                .str(stringify!(#name))
+                .usize(::core::mem::size_of::<Self>())
+                .usize(::core::mem::align_of::<Self>())
+                #(#steps)*
+                .finish();
+        }
+    })
+}
@@ -0,0 +1,137 @@
+// Layout hashes for the #[repr(C)] structs host and plugins share. `#[derive(Fingerprint)]`
+// hashes a struct's name, size, alignment and each field's offset, size and alignment; a
+// plugin lists the structs it was built with through `export_layout_fingerprints!`, and the
+// host refuses it if any of them differs from its own copy.
+//
+//   __layout_fingerprints() -> i64
+//       FatPtr to a static [LayoutEntry], len = entry count. Optional.
+//
+// Only what both sides know gets compared, so plugins can list structs the host never heard of.
+
+pub use layout_fingerprint_derive::Fingerprint;
+
+#[doc(hidden)]
+pub use fat_ptr as __fat_ptr;
+
+pub trait Fingerprint {
+    const NAME: &'static str;
+    const FINGERPRINT: u64;
+}
+
+/// One struct in `__layout_fingerprints`: its name and its layout, both hashed.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutEntry {
+    pub name: u64,
+    pub fingerprint: u64,
+}
+
+impl LayoutEntry {
+    pub const SIZE: usize = std::mem::size_of::<LayoutEntry>();
+
+    pub const fn of<T: Fingerprint>() -> Self {
+        Self {
+            name: name_hash(T::NAME),
+            fingerprint: T::FINGERPRINT,
+        }
+    }
+
+    /// Decodes the little-endian entries the host copied out of a plugin.
+    pub fn parse_all(bytes: &[u8]) -> Vec<Self> {
+        bytes
+            .chunks_exact(Self::SIZE)
+            .map(|chunk| Self {
+                name: u64::from_le_bytes(chunk[..8].try_into().unwrap()),
+                fingerprint: u64::from_le_bytes(chunk[8..].try_into().unwrap()),
+            })
+            .collect()
+    }
+}
+
+pub const fn name_hash(name: &str) -> u64 {
+    Hasher::new().str(name).finish()
+}
+
+/// FNV-1a, usable in consts.
+pub struct Hasher(u64);
+
+impl Hasher {
+    pub const fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub const fn bytes(mut self, bytes: &[u8]) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            self.0 ^= bytes[i] as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        self
+    }
+
+    pub const fn str(self, s: &str) -> Self {
+        self.bytes(s.as_bytes())
+    }
+
+    // Widened, so 32- and 64-bit builds hash the same numbers the same way
+    pub const fn usize(self, value: usize) -> Self {
+        self.bytes(&(value as u64).to_le_bytes())
+    }
+
+    pub const fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Defines `__layout_fingerprints` for the given `Fingerprint` types.
+#[macro_export]
+macro_rules! export_layout_fingerprints {
+    ($($ty:ty),+ $(,)?) => {
+        static __LAYOUT_FINGERPRINTS: &[$crate::LayoutEntry] = &[$($crate::LayoutEntry::of::<$ty>()),+];
+
+        #[no_mangle]
+        pub extern "C" fn __layout_fingerprints() -> i64 {
+            $crate::__fat_ptr::FatPtr::of(__LAYOUT_FINGERPRINTS).pack()
+        }
+    };
+}
+
+/// Fails the build unless `T` has exactly the size, alignment and field offsets written down.
+/// Fingerprints catch two builds disagreeing at load time; this catches the change itself.
+///
+/// ```
+/// #[repr(C)]
+/// struct Pair {
+///     a: u32,
+///     b: u16,
+/// }
+/// layout_fingerprint::assert_layout!(Pair, size 8, align 4, { a: 0, b: 4 });
+/// ```
+#[macro_export]
+macro_rules! assert_layout {
+    ($ty:ty, size $size:expr, align $align:expr, { $($field:ident: $offset:expr),* $(,)? }) => {
+        const _: () = {
+            assert!(
+                ::core::mem::size_of::<$ty>() == $size,
+                concat!(stringify!($ty), " must be ", stringify!($size), " bytes")
+            );
+            assert!(
+                ::core::mem::align_of::<$ty>() == $align,
+                concat!(stringify!($ty), " must be ", stringify!($align), "-aligned")
+            );
+            $(
+                assert!(
+                    ::core::mem::offset_of!($ty, $field) == $offset,
+                    concat!(stringify!($ty), "::", stringify!($field), " must be at offset ", stringify!($offset))
+                );
+            )*
+        };
+    };
+}
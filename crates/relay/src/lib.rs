@@ -0,0 +1,335 @@
+// A relay for players who can't reach each other directly, behind NATs or firewalls: everyone
+// connects out to it, and it passes their traffic on. It only matches peers up by room name
+// and copies bytes, without looking at them. Run it with `cargo run -p relay -- addr` somewhere
+// everyone can reach, and play with `--relay addr/room`. TCP and UDP share its port.
+//
+// TCP, for split play. Each connection starts with op u8, room (u16 len + UTF-8), then
+//
+//   HOST              the room's split server; stays open, and gets client u32 for each client
+//                     that joins
+//   ACCEPT client u32 the server's connection for that client
+//   JOIN              a client of the room
+//
+// and is answered with status u8 (OK, TAKEN: the room has a server already, NO_ROOM: no
+// server or no such client, MALFORMED). A JOIN is answered once the server has accepted it;
+// from then on the two connections are piped to each other until either closes.
+//
+// UDP, for netplay: room (u8 len + UTF-8), from u8, to u8, then the payload, which goes on as
+// it is to where player `to` of the room last sent from. Players nobody has heard from in
+// UDP_TIMEOUT are forgotten, and what's sent to them is lost like any other datagram.
+//
+// Like the servers it relays for, nothing is authenticated: anyone who knows a room's name can
+// join it.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PORT: u16 = 7400;
+
+pub const OP_HOST: u8 = 0;
+pub const OP_ACCEPT: u8 = 1;
+pub const OP_JOIN: u8 = 2;
+
+pub const STATUS_OK: u8 = 0;
+pub const STATUS_TAKEN: u8 = 1;
+pub const STATUS_NO_ROOM: u8 = 2;
+pub const STATUS_MALFORMED: u8 = 3;
+
+pub const UDP_TIMEOUT: Duration = Duration::from_secs(60);
+
+// How long a client waits for the server to accept it
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+// Room names are a u8 long in datagrams
+const MAX_ROOM: usize = u8::MAX as usize;
+const MAX_DATAGRAM: usize = 64 * 1024;
+
+/// A room on a relay, `addr/room` on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub addr: String,
+    pub name: String,
+}
+
+impl Room {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (addr, name) = spec
+            .rsplit_once('/')
+            .filter(|(addr, name)| !addr.is_empty() && !name.is_empty())
+            .ok_or(anyhow!("Expected a relay as 'addr/room', got '{}'", spec))?;
+        if name.len() > MAX_ROOM {
+            return Err(anyhow!(
+                "Room names are at most {} bytes, '{}' is longer",
+                MAX_ROOM,
+                name
+            ));
+        }
+        Ok(Self {
+            addr: addr.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    /// The relay's address, resolved.
+    pub fn socket_addr(&self) -> Result<SocketAddr> {
+        self.addr
+            .to_socket_addrs()
+            .with_context(|| format!("'{}' is not an address (host:port)", self.addr))?
+            .next()
+            .ok_or(anyhow!("'{}' has no address", self.addr))
+    }
+
+    /// Hosts the room: the connection the relay announces joining clients on (`next_client`).
+    pub fn host(&self) -> Result<TcpStream> {
+        self.request(OP_HOST, None)
+    }
+
+    /// The server's end of `client`'s connection.
+    pub fn accept(&self, client: u32) -> Result<TcpStream> {
+        self.request(OP_ACCEPT, Some(client))
+    }
+
+    /// A connection to the room's server, once it has accepted it.
+    pub fn join(&self) -> Result<TcpStream> {
+        self.request(OP_JOIN, None)
+    }
+
+    /// A datagram for player `to` of the room, from player `from`.
+    pub fn datagram(&self, from: u8, to: u8, payload: &[u8]) -> Vec<u8> {
+        let mut datagram = vec![self.name.len() as u8];
+        datagram.extend_from_slice(self.name.as_bytes());
+        datagram.extend_from_slice(&[from, to]);
+        datagram.extend_from_slice(payload);
+        datagram
+    }
+
+    fn request(&self, op: u8, client: Option<u32>) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.addr)
+            .with_context(|| format!("Failed to connect to the relay at {}", self.addr))?;
+        let mut request = vec![op];
+        request.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        request.extend_from_slice(self.name.as_bytes());
+        if let Some(client) = client {
+            request.extend_from_slice(&client.to_le_bytes());
+        }
+        stream.write_all(&request)?;
+
+        stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let mut status = [0u8];
+        stream
+            .read_exact(&mut status)
+            .with_context(|| format!("The relay at {} didn't answer", self.addr))?;
+        stream.set_read_timeout(None)?;
+        match status[0] {
+            STATUS_OK => Ok(stream),
+            STATUS_TAKEN => Err(anyhow!("Room '{}' already has a server", self.name)),
+            STATUS_NO_ROOM => Err(anyhow!(
+                "Nobody is serving room '{}' on the relay",
+                self.name
+            )),
+            status => Err(anyhow!(
+                "The relay turned the request down (status {})",
+                status
+            )),
+        }
+    }
+}
+
+/// The next client that joined, read off a HOST connection.
+pub fn next_client(host: &mut TcpStream) -> Result<u32> {
+    let mut client = [0u8; 4];
+    host.read_exact(&mut client).context("Lost the relay")?;
+    Ok(u32::from_le_bytes(client))
+}
+
+#[derive(Default)]
+struct Rooms {
+    // Each hosted room's HOST connection, and the token of whoever opened it
+    hosts: HashMap<String, (TcpStream, u64)>,
+    // Clients waiting for the server to accept them
+    joining: HashMap<(String, u32), TcpStream>,
+    next: u64,
+}
+
+pub struct Relay {
+    listener: TcpListener,
+    socket: UdpSocket,
+    rooms: Arc<Mutex<Rooms>>,
+}
+
+impl Relay {
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to start the relay on {}", addr))?;
+        let socket = UdpSocket::bind(listener.local_addr()?)
+            .with_context(|| format!("Failed to bind UDP {}", addr))?;
+        Ok(Self {
+            listener,
+            socket,
+            rooms: Arc::default(),
+        })
+    }
+
+    /// Where the relay listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Relays until the process is stopped.
+    pub fn run(self) -> Result<()> {
+        let socket = self.socket;
+        std::thread::spawn(move || {
+            if let Err(e) = relay_datagrams(&socket) {
+                eprintln!("⚠️ [RELAY] UDP: {:#}", e);
+            }
+        });
+        for stream in self.listener.incoming().flatten() {
+            let rooms = self.rooms.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handle_stream(stream, &rooms) {
+                    eprintln!("⚠️ [RELAY] {:#}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_stream(mut stream: TcpStream, rooms: &Mutex<Rooms>) -> Result<()> {
+    let mut header = [0u8; 3];
+    stream.read_exact(&mut header)?;
+    let mut name = vec![0u8; u16::from_le_bytes([header[1], header[2]]) as usize];
+    stream.read_exact(&mut name)?;
+    let Ok(room) = String::from_utf8(name) else {
+        return answer(&mut stream, STATUS_MALFORMED);
+    };
+
+    match header[0] {
+        OP_HOST => {
+            let token = {
+                let mut rooms = rooms.lock().unwrap();
+                if rooms.hosts.contains_key(&room) {
+                    drop(rooms);
+                    return answer(&mut stream, STATUS_TAKEN);
+                }
+                rooms.next += 1;
+                let token = rooms.next;
+                rooms
+                    .hosts
+                    .insert(room.clone(), (stream.try_clone()?, token));
+                token
+            };
+            answer(&mut stream, STATUS_OK)?;
+            eprintln!("📡 [RELAY] Room '{}' is up", room);
+            // Open until the server goes; nothing comes from it
+            let _ = stream.read(&mut [0u8; 1]);
+            let mut rooms = rooms.lock().unwrap();
+            if rooms
+                .hosts
+                .get(&room)
+                .is_some_and(|(_, host)| *host == token)
+            {
+                rooms.hosts.remove(&room);
+                rooms.joining.retain(|(joining, _), _| *joining != room);
+                eprintln!("📡 [RELAY] Room '{}' is down", room);
+            }
+            Ok(())
+        }
+        OP_JOIN => {
+            let mut rooms = rooms.lock().unwrap();
+            rooms.next += 1;
+            let client = rooms.next as u32;
+            let announced = match rooms.hosts.get_mut(&room) {
+                Some((host, _)) => host.write_all(&client.to_le_bytes()).is_ok(),
+                None => false,
+            };
+            if !announced {
+                drop(rooms);
+                return answer(&mut stream, STATUS_NO_ROOM);
+            }
+            // Answered when the server accepts
+            rooms.joining.insert((room, client), stream);
+            Ok(())
+        }
+        OP_ACCEPT => {
+            let mut client = [0u8; 4];
+            stream.read_exact(&mut client)?;
+            let joining = rooms
+                .lock()
+                .unwrap()
+                .joining
+                .remove(&(room, u32::from_le_bytes(client)));
+            let Some(mut joining) = joining else {
+                return answer(&mut stream, STATUS_NO_ROOM);
+            };
+            answer(&mut joining, STATUS_OK)?;
+            answer(&mut stream, STATUS_OK)?;
+            pipe(stream, joining)
+        }
+        _ => answer(&mut stream, STATUS_MALFORMED),
+    }
+}
+
+fn answer(stream: &mut TcpStream, status: u8) -> Result<()> {
+    Ok(stream.write_all(&[status])?)
+}
+
+// Copies both ways until either end closes, then closes the other
+fn pipe(a: TcpStream, b: TcpStream) -> Result<()> {
+    let (mut a_in, mut b_out) = (a.try_clone()?, b.try_clone()?);
+    let back = std::thread::spawn(move || {
+        let _ = std::io::copy(&mut a_in, &mut b_out);
+        let _ = b_out.shutdown(Shutdown::Both);
+    });
+    let (mut b_in, mut a_out) = (b, a);
+    let _ = std::io::copy(&mut b_in, &mut a_out);
+    let _ = a_out.shutdown(Shutdown::Both);
+    let _ = back.join();
+    Ok(())
+}
+
+fn relay_datagrams(socket: &UdpSocket) -> Result<()> {
+    // Where each room's players last sent from, and when
+    let mut players: HashMap<(Vec<u8>, u8), (SocketAddr, Instant)> = HashMap::new();
+    let mut swept = Instant::now();
+    let mut datagram = vec![0u8; MAX_DATAGRAM];
+    loop {
+        let (len, from_addr) = match socket.recv_from(&mut datagram) {
+            Ok(received) => received,
+            // ICMP about an earlier send: that player is gone, which the timeout takes care of
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::ConnectionReset | ErrorKind::ConnectionRefused
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let datagram = &datagram[..len];
+        let Some(&room_len) = datagram.first() else {
+            continue;
+        };
+        let payload_start = 1 + room_len as usize + 2;
+        if len < payload_start {
+            continue;
+        }
+        let room = datagram[1..1 + room_len as usize].to_vec();
+        let (from, to) = (datagram[payload_start - 2], datagram[payload_start - 1]);
+
+        let now = Instant::now();
+        players.insert((room.clone(), from), (from_addr, now));
+        if let Some((to_addr, _)) = players.get(&(room, to)) {
+            // Lost like any other datagram if it doesn't go
+            let _ = socket.send_to(&datagram[payload_start..], to_addr);
+        }
+        if now.duration_since(swept) > UDP_TIMEOUT {
+            players.retain(|_, (_, seen)| now.duration_since(*seen) < UDP_TIMEOUT);
+            swept = now;
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use relay::{Relay, DEFAULT_PORT};
+
+// `relay [addr]`: relays on addr, or every interface on DEFAULT_PORT
+fn main() -> Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or(format!("0.0.0.0:{}", DEFAULT_PORT));
+    let relay = Relay::bind(&addr)?;
+    eprintln!(
+        "📡 [RELAY] Relaying on {} (TCP and UDP)",
+        relay.local_addr()?
+    );
+    relay.run()
+}
@@ -0,0 +1,101 @@
+// The relay on loopback: a split server's room with a client piped to it, and netplay
+// datagrams passed between two players.
+
+use relay::{next_client, Relay, Room};
+use std::io::{Read, Write};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn relay() -> String {
+    let relay = Relay::bind("127.0.0.1:0").unwrap();
+    let addr = relay.local_addr().unwrap().to_string();
+    std::thread::spawn(move || relay.run());
+    addr
+}
+
+fn room(addr: &str, name: &str) -> Room {
+    Room::parse(&format!("{}/{}", addr, name)).unwrap()
+}
+
+#[test]
+fn rooms_parse_from_addr_slash_name() {
+    let parsed = Room::parse("relay.example.com:7400/friday").unwrap();
+    assert_eq!(
+        (parsed.addr.as_str(), parsed.name.as_str()),
+        ("relay.example.com:7400", "friday")
+    );
+    assert!(Room::parse("relay.example.com:7400").is_err());
+    assert!(Room::parse("relay.example.com:7400/").is_err());
+    assert!(Room::parse(&format!("a:1/{}", "x".repeat(256))).is_err());
+}
+
+#[test]
+fn clients_are_piped_to_the_rooms_server() {
+    let addr = relay();
+    let friday = room(&addr, "friday");
+    let mut announcer = friday.host().unwrap();
+    assert!(friday.host().is_err(), "a room has one server");
+    assert!(
+        room(&addr, "monday").join().is_err(),
+        "nobody serves monday"
+    );
+
+    let joining = {
+        let friday = friday.clone();
+        std::thread::spawn(move || friday.join().unwrap())
+    };
+    let client = next_client(&mut announcer).unwrap();
+    let mut server_end = friday.accept(client).unwrap();
+    let mut client_end = joining.join().unwrap();
+    assert!(friday.accept(client).is_err(), "a client is accepted once");
+
+    client_end.write_all(b"hello").unwrap();
+    let mut got = [0u8; 5];
+    server_end.read_exact(&mut got).unwrap();
+    assert_eq!(&got, b"hello");
+    server_end.write_all(b"back").unwrap();
+    let mut got = [0u8; 4];
+    client_end.read_exact(&mut got).unwrap();
+    assert_eq!(&got, b"back");
+
+    // The server going takes the room with it
+    drop(announcer);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(friday.host().is_ok());
+}
+
+#[test]
+fn datagrams_go_to_the_player_they_are_for() {
+    let addr = relay();
+    let friday = room(&addr, "friday");
+    let (a, b) = (
+        UdpSocket::bind("127.0.0.1:0").unwrap(),
+        UdpSocket::bind("127.0.0.1:0").unwrap(),
+    );
+    for socket in [&a, &b] {
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+    }
+
+    // Nobody has heard from player 1 yet, so this one is lost
+    a.send_to(&friday.datagram(0, 1, b"lost"), &addr).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    b.send_to(&friday.datagram(1, 0, b"to a"), &addr).unwrap();
+    let mut got = [0u8; 16];
+    let (len, from) = a.recv_from(&mut got).unwrap();
+    assert_eq!(
+        (&got[..len], from.to_string()),
+        (&b"to a"[..], addr.clone())
+    );
+
+    a.send_to(&friday.datagram(0, 1, b"to b"), &addr).unwrap();
+    let len = b.recv(&mut got).unwrap();
+    assert_eq!(&got[..len], b"to b");
+    // Other rooms have players of their own
+    a.send_to(&room(&addr, "monday").datagram(0, 1, b"elsewhere"), &addr)
+        .unwrap();
+    b.set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(b.recv(&mut got).is_err());
+}
@@ -0,0 +1,169 @@
+// The ECS kernel's syscalls (plugins/ecs-core), natively: plugins written against ecs-client
+// `cargo test` their systems on the host target, with no wasmtime and no kernel build.
+//
+// The sys_* functions are exported under the kernel's names, so ecs-client's imports resolve
+// to them when a test binary links this crate (`use universal_test_harness as _;` keeps it
+// linked). The same calls are also here as plain Rust, to set up and inspect a world:
+//
+//   register_component(size, align) -> id     sys_register_component
+//   spawn(&[(id, bytes)]) -> entity           sys_spawn_entity
+//   query_tables(&[id]) -> tables             sys_query_tables
+//   table_len(table), column_ptr(table, id)   sys_get_table_len, sys_get_column_ptr
+//   resource(id, size) -> ptr                 sys_resource
+//                                             sys_run_schedule, which has nothing to run
+//   reset(), entity_count()
+//
+// Storage matches the kernel's: one table per set of components, one column per component,
+// values back to back, and table 0 for entities without components. Pointers into a column
+// hold until the next spawn into its table.
+//
+// Only the kernel is here: ecs-client's host imports (host_alloc, host_register_id, ...) have
+// native stand-ins behind its `mock-host` feature.
+//
+// Each test thread has a world of its own, so tests run in parallel without seeing each other's
+// entities. Component IDs are shared by all threads, since ecs-client caches them per type.
+
+mod world;
+
+pub use world::World;
+
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::slice;
+use std::sync::Mutex;
+
+static COMPONENTS: Mutex<Vec<Layout>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static WORLD: RefCell<World> = RefCell::new(World::default());
+}
+
+fn layout_of(component: i32) -> Layout {
+    let components = COMPONENTS.lock().unwrap();
+    *components
+        .get(component as usize)
+        .unwrap_or_else(|| panic!("Component {} was never registered", component))
+}
+
+/// Runs `f` on this thread's world.
+pub fn with_world<R>(f: impl FnOnce(&mut World) -> R) -> R {
+    WORLD.with(|world| f(&mut world.borrow_mut()))
+}
+
+/// Starts this thread over with an empty world. Registered components stay registered.
+pub fn reset() {
+    with_world(|world| *world = World::default());
+}
+
+pub fn entity_count() -> usize {
+    with_world(|world| world.entity_count())
+}
+
+pub fn register_component(size: i32, align: i32) -> i32 {
+    let layout =
+        Layout::from_size_align(size as usize, align as usize).expect("Invalid component layout");
+    let mut components = COMPONENTS.lock().unwrap();
+    components.push(layout);
+    (components.len() - 1) as i32
+}
+
+/// Spawns an entity with a copy of each component's bytes, which must be exactly its size.
+pub fn spawn(components: &[(i32, &[u8])]) -> i32 {
+    let components: Vec<_> = components
+        .iter()
+        .map(|(id, bytes)| {
+            let layout = layout_of(*id);
+            assert_eq!(
+                bytes.len(),
+                layout.size(),
+                "Component {} is {} bytes",
+                id,
+                layout.size()
+            );
+            (*id, layout, bytes.as_ptr())
+        })
+        .collect();
+    // Safety: every value is as long as its layout says, checked above
+    with_world(|world| unsafe { world.spawn(&components) })
+}
+
+pub fn query_tables(components: &[i32]) -> Vec<i32> {
+    with_world(|world| world.query_tables(components).to_vec())
+}
+
+pub fn table_len(table: i32) -> i32 {
+    with_world(|world| world.table_len(table))
+}
+
+pub fn column_ptr(table: i32, component: i32) -> *mut u8 {
+    with_world(|world| world.column_ptr(table, component))
+}
+
+pub fn resource(id: i32, size: i32) -> *mut u8 {
+    with_world(|world| world.resource(id, size))
+}
+
+// --- SYSCALLS ---
+// What ecs-client imports, with the kernel's signatures.
+
+#[no_mangle]
+pub extern "C" fn kernel_init() {}
+
+#[no_mangle]
+pub extern "C" fn sys_register_component(size: i32, align: i32) -> i32 {
+    register_component(size, align)
+}
+
+/// # Safety
+/// `comp_ids_ptr` and `data_ptrs` hold `count` entries, each pointer to a value of its component.
+#[no_mangle]
+pub unsafe extern "C" fn sys_spawn_entity(
+    count: i32,
+    comp_ids_ptr: *const i32,
+    data_ptrs: *const *const u8,
+) -> i32 {
+    let ids = slice::from_raw_parts(comp_ids_ptr, count as usize);
+    let ptrs = slice::from_raw_parts(data_ptrs, count as usize);
+    let components: Vec<_> = ids
+        .iter()
+        .zip(ptrs)
+        .map(|(id, ptr)| (*id, layout_of(*id), *ptr))
+        .collect();
+    with_world(|world| world.spawn(&components))
+}
+
+/// # Safety
+/// `req_ids_ptr` holds `req_len` component IDs and `out_len` is writable. The answer holds
+/// until the next query on this thread.
+#[no_mangle]
+pub unsafe extern "C" fn sys_query_tables(
+    req_ids_ptr: *const i32,
+    req_len: i32,
+    out_len: *mut i32,
+) -> *const i32 {
+    let ids = slice::from_raw_parts(req_ids_ptr, req_len as usize);
+    with_world(|world| {
+        let tables = world.query_tables(ids);
+        *out_len = tables.len() as i32;
+        tables.as_ptr()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn sys_get_table_len(table_id: i32) -> i32 {
+    table_len(table_id)
+}
+
+#[no_mangle]
+pub extern "C" fn sys_get_column_ptr(table_id: i32, comp_index: i32) -> *mut u8 {
+    column_ptr(table_id, comp_index)
+}
+
+#[no_mangle]
+pub extern "C" fn sys_resource(id: i32, size: i32) -> *mut u8 {
+    resource(id, size)
+}
+
+// Systems here are called by the tests themselves
+#[no_mangle]
+pub extern "C" fn sys_run_schedule() {}
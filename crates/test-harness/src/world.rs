@@ -0,0 +1,171 @@
+use std::alloc::Layout;
+use std::collections::HashMap;
+
+// Components may be aligned to at most this; columns are built from chunks this aligned
+const MAX_ALIGN: usize = 16;
+
+#[derive(Clone, Copy)]
+#[repr(C, align(16))]
+struct Chunk([u8; MAX_ALIGN]);
+
+// One component's values for every entity in a table, back to back like the kernel's columns
+struct Column {
+    item: Layout,
+    chunks: Vec<Chunk>,
+}
+
+impl Column {
+    fn new(item: Layout) -> Self {
+        Self {
+            item,
+            chunks: Vec::new(),
+        }
+    }
+
+    // Safety: `value` points to `item.size()` readable bytes
+    unsafe fn push(&mut self, row: usize, value: *const u8) {
+        let size = self.item.size();
+        let needed = ((row + 1) * size).div_ceil(MAX_ALIGN);
+        if self.chunks.len() < needed {
+            self.chunks.resize(needed, Chunk([0; MAX_ALIGN]));
+        }
+        let dst = (self.chunks.as_mut_ptr() as *mut u8).add(row * size);
+        std::ptr::copy_nonoverlapping(value, dst, size);
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        match self.item.size() {
+            // Anything non-null and aligned will do for zero-sized components
+            0 => self.item.align() as *mut u8,
+            _ => self.chunks.as_mut_ptr() as *mut u8,
+        }
+    }
+}
+
+// Every entity with exactly the same components, like an archetype table in the kernel
+struct Table {
+    components: Vec<i32>,
+    columns: HashMap<i32, Column>,
+    len: usize,
+}
+
+/// One thread's kernel state: entities, tables and resources.
+pub struct World {
+    tables: Vec<Table>,
+    resources: Vec<Option<Box<[u8]>>>,
+    next_entity: i32,
+    // What the last query answered, until the next one (sys_query_tables returns a pointer into it)
+    query: Vec<i32>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        // Table 0 holds entities without components, as in the kernel
+        let empty = Table {
+            components: Vec::new(),
+            columns: HashMap::new(),
+            len: 0,
+        };
+        Self {
+            tables: vec![empty],
+            resources: Vec::new(),
+            next_entity: 0,
+            query: Vec::new(),
+        }
+    }
+}
+
+impl World {
+    /// Spawns an entity with a copy of each `(component, layout, value)`. A component given
+    /// twice keeps its last value.
+    ///
+    /// # Safety
+    /// Each value points to `layout.size()` readable bytes.
+    pub unsafe fn spawn(&mut self, components: &[(i32, Layout, *const u8)]) -> i32 {
+        let mut components: Vec<_> = components.to_vec();
+        components.reverse();
+        components.sort_by_key(|(id, _, _)| *id);
+        components.dedup_by_key(|(id, _, _)| *id);
+        let ids: Vec<i32> = components.iter().map(|(id, _, _)| *id).collect();
+
+        let table_id = match self.tables.iter().position(|table| table.components == ids) {
+            Some(table_id) => table_id,
+            None => {
+                let columns = components
+                    .iter()
+                    .map(|(id, layout, _)| {
+                        assert!(
+                            layout.align() <= MAX_ALIGN,
+                            "Components can't be aligned to more than {} bytes",
+                            MAX_ALIGN
+                        );
+                        (*id, Column::new(*layout))
+                    })
+                    .collect();
+                self.tables.push(Table {
+                    components: ids,
+                    columns,
+                    len: 0,
+                });
+                self.tables.len() - 1
+            }
+        };
+
+        let table = &mut self.tables[table_id];
+        for (id, _, value) in &components {
+            table.columns.get_mut(id).unwrap().push(table.len, *value);
+        }
+        table.len += 1;
+
+        let entity = self.next_entity;
+        self.next_entity += 1;
+        entity
+    }
+
+    /// The tables holding all of `components`.
+    pub fn query_tables(&mut self, components: &[i32]) -> &[i32] {
+        self.query.clear();
+        for (table_id, table) in self.tables.iter().enumerate() {
+            if components.iter().all(|id| table.columns.contains_key(id)) {
+                self.query.push(table_id as i32);
+            }
+        }
+        &self.query
+    }
+
+    pub fn table_len(&self, table_id: i32) -> i32 {
+        self.tables
+            .get(table_id as usize)
+            .map_or(0, |table| table.len as i32)
+    }
+
+    /// The start of `component`'s column in `table_id`, null if the table has none.
+    pub fn column_ptr(&mut self, table_id: i32, component: i32) -> *mut u8 {
+        self.tables
+            .get_mut(table_id as usize)
+            .and_then(|table| table.columns.get_mut(&component))
+            .map_or(std::ptr::null_mut(), Column::as_mut_ptr)
+    }
+
+    /// Resource `id`, zeroed on first use. Asking with `size` 0 never creates it.
+    pub fn resource(&mut self, id: i32, size: i32) -> *mut u8 {
+        let idx = id as usize;
+        if self.resources.len() <= idx {
+            if size <= 0 {
+                return std::ptr::null_mut();
+            }
+            self.resources.resize(idx + 1, None);
+        }
+        if self.resources[idx].is_none() {
+            if size <= 0 {
+                return std::ptr::null_mut();
+            }
+            self.resources[idx] = Some(vec![0u8; size as usize].into_boxed_slice());
+        }
+        self.resources[idx].as_mut().unwrap().as_mut_ptr()
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.tables.iter().map(|table| table.len).sum()
+    }
+}
@@ -0,0 +1,72 @@
+// The harness behaves like the kernel from a plugin's side: tables per component set,
+// columns of raw values, resources zeroed on first use.
+#![deny(warnings)]
+
+use std::mem::size_of;
+use universal_test_harness as harness;
+
+fn bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+#[test]
+fn spawn_and_query() {
+    harness::reset();
+    let position = harness::register_component(8, 4);
+    let velocity = harness::register_component(4, 4);
+
+    harness::spawn(&[(position, bytes(&[1i32, 2])), (velocity, bytes(&5i32))]);
+    harness::spawn(&[(position, bytes(&[3i32, 4]))]);
+    harness::spawn(&[(velocity, bytes(&6i32)), (position, bytes(&[5i32, 6]))]);
+    assert_eq!(harness::entity_count(), 3);
+
+    // Both components: one table, whichever order they were spawned in
+    let tables = harness::query_tables(&[position, velocity]);
+    assert_eq!(tables.len(), 1);
+    assert_eq!(harness::table_len(tables[0]), 2);
+    let column = harness::column_ptr(tables[0], velocity) as *const i32;
+    assert_eq!(unsafe { [*column, *column.add(1)] }, [5, 6]);
+
+    let xs: Vec<i32> = harness::query_tables(&[position])
+        .into_iter()
+        .flat_map(|table| {
+            let column = harness::column_ptr(table, position) as *const [i32; 2];
+            let len = harness::table_len(table) as usize;
+            unsafe { std::slice::from_raw_parts(column, len) }
+                .iter()
+                .map(|p| p[0])
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(xs.len(), 3);
+    assert!(xs.contains(&1) && xs.contains(&3) && xs.contains(&5));
+}
+
+#[test]
+fn syscalls_share_the_world() {
+    harness::reset();
+    let health = harness::sys_register_component(4, 4);
+    let value = 42i32;
+    let ids = [health];
+    let ptrs = [&value as *const i32 as *const u8];
+    unsafe { harness::sys_spawn_entity(1, ids.as_ptr(), ptrs.as_ptr()) };
+
+    let mut count = 0;
+    let tables = unsafe { harness::sys_query_tables(ids.as_ptr(), 1, &mut count) };
+    assert_eq!(count, 1);
+    let table = unsafe { *tables };
+    assert_eq!(
+        unsafe { *(harness::sys_get_column_ptr(table, health) as *const i32) },
+        42
+    );
+}
+
+#[test]
+fn resources() {
+    harness::reset();
+    assert!(harness::resource(7, 0).is_null());
+    let score = harness::resource(7, 4) as *mut i32;
+    assert_eq!(unsafe { *score }, 0);
+    unsafe { *score = 10 };
+    assert_eq!(unsafe { *(harness::resource(7, 0) as *const i32) }, 10);
+}
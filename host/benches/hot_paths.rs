@@ -0,0 +1,242 @@
+// Timings for the paths that perf-motivated redesigns (query caching, zero-copy render) would
+// change, so a change can show what it bought:
+//
+//   query/<n>          iterating 10k entities' matches for n of their 4 components
+//   alloc/churn        host_alloc/host_dealloc from a plugin, 64 blocks live at a time, in
+//                      calls of CHURN_STEPS steps (criterion reports steps per second too)
+//   call/round-trip    a plugin calling another through `call` and back
+//   grid/read          a driver's 80x24 grid copied out of shared memory after a tick
+//   grid/render        that grid painted into a ratatui buffer, as the TUI draws it
+//
+//   cargo bench -p host --bench hot_paths [-- <name filter>]
+//
+// criterion runs them, and compares each run with the last (target/criterion).
+//
+// Queries run on the test harness's native world, which stores components as the kernel does,
+// so they time the storage layout rather than wasm codegen.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use host::embedder::driver::{DriverHandle, Frame};
+use host::embedder::images::ImageStore;
+use host::embedder::theme::Theme;
+use host::embedder::widgets;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use std::hint::black_box;
+use universal_test_harness as ecs;
+
+const ENTITIES: usize = 10_000;
+
+fn queries(c: &mut Criterion) {
+    let components: Vec<i32> = (0..4).map(|_| ecs::register_component(8, 4)).collect();
+    for idx in 0..ENTITIES {
+        let value = (idx as u64).to_le_bytes();
+        ecs::spawn(
+            &components
+                .iter()
+                .map(|&id| (id, &value[..]))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let mut group = c.benchmark_group("query");
+    for count in 1..=4 {
+        let wanted = &components[..count];
+        group.bench_function(count.to_string(), |b| {
+            b.iter(|| {
+                let mut sum = 0u64;
+                for table in ecs::query_tables(wanted) {
+                    let len = ecs::table_len(table) as usize;
+                    for &id in wanted {
+                        let column = ecs::column_ptr(table, id) as *const u64;
+                        // Safety: a column holds `len` values of its component, 8 bytes each
+                        let values = unsafe { std::slice::from_raw_parts(column, len) };
+                        sum = values
+                            .iter()
+                            .fold(sum, |sum, value| sum.wrapping_add(*value));
+                    }
+                }
+                black_box(sum);
+            })
+        });
+    }
+    group.finish();
+    ecs::reset();
+}
+
+// Keeps a ring of 64 allocations at `ring`, replacing one per step with a block of another size
+const CHURNER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (import "env" "host_dealloc" (func $dealloc (param i32 i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "churn") (param $ring i32) (param $steps i32) (local $i i32) (local $entry i32) (local $size i32)
+    (loop $next
+      local.get $ring
+      local.get $i i32.const 63 i32.and i32.const 8 i32.mul
+      i32.add
+      local.set $entry
+      local.get $entry i32.load
+      if
+        local.get $entry i32.load
+        local.get $entry i32.load offset=4
+        call $dealloc
+      end
+      local.get $i i32.const 37 i32.mul i32.const 2047 i32.and i32.const 16 i32.add
+      local.set $size
+      local.get $entry
+      local.get $size call $alloc
+      i32.store
+      local.get $entry
+      local.get $size
+      i32.store offset=4
+      local.get $i i32.const 1 i32.add
+      local.tee $i
+      local.get $steps
+      i32.lt_u
+      br_if $next)))
+"#;
+
+const CHURN_STEPS: i32 = 1000;
+
+fn alloc_churn(c: &mut Criterion) {
+    let mut host = host();
+    host.load_plugin("churner", CHURNER.as_bytes()).unwrap();
+    let ring = slot(&host, "churner");
+    host.write_mem(ring, &[0; 64 * 8]).unwrap();
+    let churn = host
+        .get_func("churner", "churn")
+        .unwrap()
+        .typed::<(i32, i32), ()>(&host.store)
+        .unwrap();
+
+    let mut group = c.benchmark_group("alloc");
+    group.throughput(Throughput::Elements(CHURN_STEPS as u64));
+    group.bench_function("churn", |b| {
+        b.iter(|| churn.call(&mut host.store, (ring, CHURN_STEPS)).unwrap())
+    });
+    group.finish();
+}
+
+const CALLER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "call" (func $call (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "round_trip") (param i32 i32 i32 i32) (result i64)
+    local.get 0 local.get 1 local.get 2 local.get 3 i32.const 7 i32.const 1 call $call))
+"#;
+
+const PROVIDER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "echo") (param i32 i32) (result i64)
+    local.get 1 i64.extend_i32_u i64.const 32 i64.shl
+    local.get 0 i64.extend_i32_u
+    i64.or))
+"#;
+
+fn call_round_trip(c: &mut Criterion) {
+    let mut host = host();
+    host.load_plugin("provider", PROVIDER.as_bytes()).unwrap();
+    host.load_plugin("caller", CALLER.as_bytes()).unwrap();
+    let names = slot(&host, "caller");
+    host.write_mem(names, b"providerecho").unwrap();
+    let round_trip = host.get_func("caller", "round_trip").unwrap();
+    let round_trip = round_trip
+        .typed::<(i32, i32, i32, i32), i64>(&host.store)
+        .unwrap();
+
+    c.bench_function("call/round-trip", |b| {
+        b.iter(|| {
+            black_box(
+                round_trip
+                    .call(&mut host.store, (names, 8, names + 8, 4))
+                    .unwrap(),
+            )
+        })
+    });
+}
+
+// An 80x24 driver repainting every cell each tick, in a grid from host_alloc
+const DRIVER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (global $grid (mut i32) (i32.const 0))
+  (global $frame (mut i32) (i32.const 0))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "set_input") (param i32))
+  (func (export "set_tickrate") (param f32))
+  (func (export "get_grid_dimensions") (result i64) i64.const 0x5000000018)
+  (func $grid (result i32)
+    global.get $grid
+    i32.eqz
+    if i32.const 15360 call $alloc global.set $grid end
+    global.get $grid)
+  (func (export "get_grid_ptr") (result i32) call $grid)
+  (func (export "tick") (param f32) (local $i i32) (local $cell i32)
+    (loop $next
+      call $grid
+      local.get $i i32.const 8 i32.mul
+      i32.add
+      local.set $cell
+      local.get $cell
+      local.get $i global.get $frame i32.add i32.const 26 i32.rem_u i32.const 97 i32.add
+      i32.store
+      local.get $cell
+      local.get $i i32.const 7 i32.and
+      i32.store8 offset=4
+      local.get $i i32.const 1 i32.add
+      local.tee $i
+      i32.const 1920
+      i32.lt_u
+      br_if $next)
+    global.get $frame i32.const 1 i32.add global.set $frame))
+"#;
+
+fn grid(c: &mut Criterion) {
+    let mut host = host();
+    host.load_plugin("driver", DRIVER.as_bytes()).unwrap();
+    let driver = DriverHandle::bind(&mut host, "driver").unwrap();
+    let mut frame = Frame::default();
+
+    c.bench_function("grid/read", |b| {
+        b.iter(|| {
+            driver.tick_only(&mut host, 1.0 / 60.0).unwrap();
+            driver.update_frame(&mut host, &mut frame).unwrap();
+        })
+    });
+
+    let (theme, images) = (Theme::default(), ImageStore::default());
+    let area = Rect::new(0, 0, 80, 24);
+    let mut buffer = Buffer::empty(area);
+    c.bench_function("grid/render", |b| {
+        b.iter(|| {
+            widgets::render_grid(&mut buffer, area, &frame.as_ref(), &theme, &images);
+            black_box(&buffer);
+        })
+    });
+}
+
+fn host() -> BlindHost {
+    let host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host
+}
+
+fn slot(host: &BlindHost, plugin: &str) -> i32 {
+    host.store
+        .data()
+        .slots
+        .iter()
+        .find(|(name, _)| name == plugin)
+        .unwrap()
+        .1
+}
+
+criterion_group!(benches, queries, alloc_churn, call_round_trip, grid);
+criterion_main!(benches);
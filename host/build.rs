@@ -0,0 +1,7 @@
+use std::path::PathBuf;
+
+// Export tables for the interfaces in /idl, checked against plugins as they load
+fn main() {
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("interfaces.rs");
+    idl::write_host_bindings(&["../idl/tasksapp.idl", "../idl/ecs.idl"], out).unwrap();
+}
@@ -0,0 +1,58 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use host_fuzz::Fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let mut fuzz = Fuzz::new();
+    let alloc = fuzz.export::<(i32,), i32>("alloc");
+    let dealloc = fuzz.export::<(i32, i32), ()>("dealloc");
+    let heap_start = fuzz.host.store.data().heap_start_address;
+    // What the host handed out and the probe hasn't freed yet
+    let mut live: Vec<(i32, i32)> = Vec::new();
+
+    while !u.is_empty() {
+        let Ok(op) = u.int_in_range(0..=3) else { break };
+        match op {
+            0 => {
+                let Ok(size) = u.int_in_range(-8..=1 << 20) else { break };
+                let ptr = alloc.call(&mut fuzz.host.store, (size,)).unwrap();
+                if ptr == 0 {
+                    continue;
+                }
+                assert!(size >= 0, "host_alloc({}) handed out {}", size, ptr);
+                let end = ptr as usize + size as usize;
+                assert!(ptr >= heap_start && end <= fuzz.memory_len(), "{}..{} outside the heap", ptr, end);
+                for &(other, other_size) in &live {
+                    assert!(
+                        end as i32 <= other || other + other_size <= ptr,
+                        "{}+{} overlaps live {}+{}",
+                        ptr,
+                        size,
+                        other,
+                        other_size
+                    );
+                }
+                live.push((ptr, size));
+            }
+            // Free something live, with its own size or a wrong one
+            1 if !live.is_empty() => {
+                let Ok(idx) = u.choose_index(live.len()) else { break };
+                let (ptr, size) = live.swap_remove(idx);
+                let Ok(exact) = u.arbitrary::<bool>() else { break };
+                let size = if exact { size } else { size / 2 };
+                dealloc.call(&mut fuzz.host.store, (ptr, size)).unwrap();
+            }
+            // Free anything: a double free, a made-up pointer, a pointer into a live block
+            _ => {
+                let (Ok(ptr), Ok(size)) = (fuzz.ptr(&mut u), fuzz.len(&mut u)) else { break };
+                if live.iter().any(|&(live_ptr, _)| live_ptr == ptr) {
+                    continue;
+                }
+                dealloc.call(&mut fuzz.host.store, (ptr, size)).unwrap();
+            }
+        }
+    }
+});
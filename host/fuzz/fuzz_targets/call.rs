@@ -0,0 +1,18 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use host_fuzz::Fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let mut fuzz = Fuzz::new();
+    let call = fuzz.export::<(i32, i32, i32, i32, i32, i32), i64>("call");
+    while !u.is_empty() {
+        let (Ok(module), Ok(export)) = (fuzz.string(&mut u), fuzz.string(&mut u)) else { break };
+        let (Ok(arg0), Ok(arg1)) = (fuzz.ptr(&mut u), fuzz.len(&mut u)) else {
+            break;
+        };
+        let _ = call.call(&mut fuzz.host.store, (module.0, module.1, export.0, export.1, arg0, arg1));
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use host_fuzz::Fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let mut fuzz = Fuzz::new();
+    let print = fuzz.export::<(i32, i32), ()>("print");
+    while !u.is_empty() {
+        let Ok((ptr, len)) = fuzz.string(&mut u) else { break };
+        // Ranges outside the probe's slot and the heap trap
+        let printed = print.call(&mut fuzz.host.store, (ptr, len));
+        if fuzz.in_scratch(ptr, len) {
+            printed.unwrap();
+        }
+    }
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use host_fuzz::Fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let mut fuzz = Fuzz::new();
+    while !u.is_empty() {
+        let (Ok(ptr), Ok(len)) = (fuzz.ptr(&mut u), fuzz.len(&mut u)) else { break };
+        let in_bounds = ptr >= 0 && len >= 0 && ptr as usize + len as usize <= fuzz.memory_len();
+        let read = fuzz.host.read_mem(ptr, len);
+        assert_eq!(read.is_ok(), in_bounds, "read_mem({}, {})", ptr, len);
+
+        let Ok(bytes) = u.bytes(len.clamp(0, 4096) as usize) else {
+            break;
+        };
+        let in_bounds = ptr >= 0 && ptr as usize + bytes.len() <= fuzz.memory_len();
+        let written = fuzz.host.write_mem(ptr, bytes);
+        assert_eq!(written.is_ok(), in_bounds, "write_mem({}, {} bytes)", ptr, bytes.len());
+        if in_bounds {
+            assert_eq!(fuzz.host.read_mem(ptr, bytes.len() as i32).unwrap(), bytes);
+        }
+    }
+});
@@ -0,0 +1,137 @@
+// Shared by the fuzz targets: a live host with a "probe" plugin that passes whatever the
+// fuzzer picks straight to the host calls, and a "provider" plugin for them to reach.
+//
+//   cargo +nightly fuzz run host_print        host_print(ptr, len)
+//   cargo +nightly fuzz run host_link_call    host_link_call(module, export), then the link
+//   cargo +nightly fuzz run call              call(module, export, arg0, arg1)
+//   cargo +nightly fuzz run memory            BlindHost::read_mem / write_mem
+//   cargo +nightly fuzz run alloc             host_alloc / host_dealloc sequences
+//
+// Traps are fine: a bad pointer should end the plugin's call with an error (always:
+// host_calls/bounds.rs). Panics, aborts and sanitizer reports are the bugs.
+
+use arbitrary::{Result, Unstructured};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use wasmtime::TypedFunc;
+
+// Where probes write the strings they pass by pointer: this far into the probe's slot, which
+// has no data of its own, so the bounds check lets them through
+const SCRATCH_OFFSET: i32 = 4096;
+pub const SCRATCH_LEN: usize = 1024;
+
+const PROBE: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__indirect_function_table" (table 0 funcref))
+  (import "env" "host_print" (func $print (param i32 i32)))
+  (import "env" "host_link_call" (func $link (param i32 i32 i32 i32) (result i32)))
+  (import "env" "call" (func $call (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (import "env" "host_dealloc" (func $dealloc (param i32 i32)))
+  (type $linked (func (param i32 i32) (result i64)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "print") (param i32 i32)
+    local.get 0 local.get 1 call $print)
+  (func (export "link") (param i32 i32 i32 i32) (result i32)
+    local.get 0 local.get 1 local.get 2 local.get 3 call $link)
+  (func (export "call_linked") (param i32 i32 i32) (result i64)
+    local.get 1 local.get 2 local.get 0 call_indirect (type $linked))
+  (func (export "call") (param i32 i32 i32 i32 i32 i32) (result i64)
+    local.get 0 local.get 1 local.get 2 local.get 3 local.get 4 local.get 5 call $call)
+  (func (export "alloc") (param i32) (result i32)
+    local.get 0 call $alloc)
+  (func (export "dealloc") (param i32 i32)
+    local.get 0 local.get 1 call $dealloc))
+"#;
+
+// Answers with its payload, or with a trap for an empty one
+const PROVIDER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "echo") (param i32 i32) (result i64)
+    local.get 1
+    i32.eqz
+    if unreachable end
+    local.get 1
+    i64.extend_i32_u
+    i64.const 32
+    i64.shl
+    local.get 0
+    i64.extend_i32_u
+    i64.or)
+  (func (export "free_response") (param i32 i32)))
+"#;
+
+pub struct Fuzz {
+    pub host: BlindHost,
+    pub scratch: i32,
+}
+
+impl Fuzz {
+    pub fn new() -> Self {
+        let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+        host.init_heap();
+        host.load_plugin("provider", PROVIDER.as_bytes()).unwrap();
+        host.load_plugin("probe", PROBE.as_bytes()).unwrap();
+        let slots = &host.store.data().slots;
+        let scratch = slots.iter().find(|(name, _)| name == "probe").unwrap().1 + SCRATCH_OFFSET;
+        Self { host, scratch }
+    }
+
+    pub fn export<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(&mut self, name: &str) -> TypedFunc<P, R> {
+        self.host.get_func("probe", name).unwrap().typed(&self.host.store).unwrap()
+    }
+
+    pub fn memory_len(&self) -> usize {
+        self.host.store.data().shared_memory.data().len()
+    }
+
+    /// A pointer worth trying: anywhere at all, near the ends of memory, or into the scratch.
+    pub fn ptr(&self, u: &mut Unstructured) -> Result<i32> {
+        let len = self.memory_len() as i64;
+        Ok(match u.int_in_range(0..=3)? {
+            0 => u.arbitrary()?,
+            1 => (len + u.int_in_range(-64..=64)?) as i32,
+            2 => u.int_in_range(-64..=64)?,
+            _ => self.scratch + u.int_in_range(0..=SCRATCH_LEN as i32)?,
+        })
+    }
+
+    /// A length to go with `ptr`: anything, or small.
+    pub fn len(&self, u: &mut Unstructured) -> Result<i32> {
+        Ok(match u.arbitrary()? {
+            true => u.arbitrary()?,
+            false => u.int_in_range(-8..=SCRATCH_LEN as i32)?,
+        })
+    }
+
+    /// A (ptr, len) string argument: mostly bytes the fuzzer chose, written to the scratch
+    /// (often a real module or export name), otherwise any range at all.
+    pub fn string(&mut self, u: &mut Unstructured) -> Result<(i32, i32)> {
+        if u.ratio(1, 4)? {
+            return Ok((self.ptr(u)?, self.len(u)?));
+        }
+        let bytes: Vec<u8> = match u.int_in_range(0..=3)? {
+            0 => b"provider".to_vec(),
+            1 => b"echo".to_vec(),
+            2 => b"free_response".to_vec(),
+            _ => u.arbitrary()?,
+        };
+        let bytes = &bytes[..bytes.len().min(SCRATCH_LEN)];
+        let at = self.scratch + u.int_in_range(0..=(SCRATCH_LEN - bytes.len()) as i32)?;
+        self.host.write_mem(at, bytes).unwrap();
+        Ok((at, bytes.len() as i32))
+    }
+
+    /// Whether `ptr..ptr + len` is all in the scratch, where no host call should trap on it.
+    pub fn in_scratch(&self, ptr: i32, len: i32) -> bool {
+        len >= 0 && ptr >= self.scratch && ptr as i64 + len as i64 <= self.scratch as i64 + SCRATCH_LEN as i64
+    }
+}
+
+impl Default for Fuzz {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,6 +5,7 @@ pub struct FreeBlock {
     pub size: u32,
 }
 
+#[derive(Default, Clone)]
 pub struct HostHeap {
     pub free_blocks: Vec<FreeBlock>,
 }
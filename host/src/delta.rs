@@ -0,0 +1,59 @@
+// Deltas between two versions of the same bytes, for replicated state (embedder/split.rs) and
+// save journals (host_calls/saves.rs): the new version XORed with the old, so what didn't
+// change is zeros, with the zero runs left out.
+//
+//   runs of   skip u16, len u16, then len bytes of new XOR old
+//
+// Runs cover the bytes in order; what's past the last one is unchanged. Both versions have to
+// be the same length, like the state a plugin designates.
+
+/// The delta that turns `old` into `new`, or None if they aren't the same length.
+pub fn encode(old: &[u8], new: &[u8]) -> Option<Vec<u8>> {
+    if old.len() != new.len() {
+        return None;
+    }
+    let mut delta = Vec::new();
+    let mut pos = 0;
+    while pos < new.len() {
+        let start = pos;
+        while pos < new.len() && old[pos] == new[pos] && pos - start < u16::MAX as usize {
+            pos += 1;
+        }
+        if pos == new.len() {
+            break;
+        }
+        let skip = pos - start;
+        let changed = pos;
+        // A run ends at the first unchanged byte, or when its length runs out
+        while pos < new.len() && old[pos] != new[pos] && pos - changed < u16::MAX as usize {
+            pos += 1;
+        }
+        delta.extend_from_slice(&(skip as u16).to_le_bytes());
+        delta.extend_from_slice(&((pos - changed) as u16).to_le_bytes());
+        delta.extend(
+            old[changed..pos]
+                .iter()
+                .zip(&new[changed..pos])
+                .map(|(old, new)| old ^ new),
+        );
+    }
+    Some(delta)
+}
+
+/// `old` with `delta` applied, or None if the delta doesn't fit it.
+pub fn decode(old: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut new = old.to_vec();
+    let (mut pos, mut rest) = (0, delta);
+    while !rest.is_empty() {
+        let skip = u16::from_le_bytes(rest.get(..2)?.try_into().ok()?) as usize;
+        let len = u16::from_le_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+        let xor = rest.get(4..4 + len)?;
+        pos += skip;
+        for (byte, xor) in new.get_mut(pos..pos + len)?.iter_mut().zip(xor) {
+            *byte ^= xor;
+        }
+        pos += len;
+        rest = &rest[4 + len..];
+    }
+    Some(new)
+}
@@ -0,0 +1,287 @@
+use super::compositor::Layout;
+use super::export::ExportFormat;
+use anyhow::{anyhow, Result};
+use relay::Room;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// What a failed host_assert does besides being logged (`--on-assert`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssertAction {
+    Log,
+    // Stop ticking until the pause key; headless runs just log
+    Pause,
+    // End the run with an error
+    Fail,
+}
+
+impl AssertAction {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "log" => Ok(Self::Log),
+            "pause" => Ok(Self::Pause),
+            "fail" => Ok(Self::Fail),
+            other => Err(anyhow!(
+                "Unknown assertion action '{}' (expected log, pause or fail)",
+                other
+            )),
+        }
+    }
+}
+
+// Command line flags of the grid embedder binary
+#[derive(Debug)]
+pub struct Args {
+    pub theme: Option<PathBuf>,
+    pub keys: Option<PathBuf>,
+    // host.toml, see embedder/config.rs
+    pub config: Option<PathBuf>,
+    pub export_format: ExportFormat,
+    pub record: Option<PathBuf>,
+    pub headless: bool,
+    pub ticks: u32,
+    pub input_script: Option<PathBuf>,
+    pub every: Option<u32>,
+    pub out_dir: Option<PathBuf>,
+    // `--driver name=path`, repeatable. Empty means the default grid-driver build.
+    pub drivers: Vec<(String, PathBuf)>,
+    // `--plugin name=path`, repeatable: modules drivers link against, loaded before them; `path`
+    // is a .wasm or a directory with a plugin.toml (host/manifest.rs)
+    pub plugins: Vec<(String, PathBuf)>,
+    pub layout: Layout,
+    // Screen reader output: announcements to a file/pipe and/or a TTS command
+    pub narrate: Option<PathBuf>,
+    pub speak: Option<String>,
+    // `send_to_server` endpoint, e.g. http://localhost:8080/sync
+    pub sync_url: Option<String>,
+    // Time calls into the kernel's sys_* exports as well (BlindHostConfig::profile_syscalls)
+    pub profile_syscalls: bool,
+    // Serve puffin flame graphs of the host loop (needs the `puffin` feature)
+    pub profile: bool,
+    // `--inspect addr[:len]`: hex dump of shared memory once loaded (after the run, headless)
+    pub inspect: Option<(i32, i32)>,
+    // `--debug-server addr`, e.g. 127.0.0.1:7878: JSON debug commands over TCP (debug_server.rs)
+    pub debug_server: Option<String>,
+    // `--metrics addr`: Prometheus metrics at http://addr/metrics (host/metrics.rs)
+    pub metrics: Option<String>,
+    // `--spectate addr`: browsers at http://addr/ watch the frames the TUI draws (spectate.rs)
+    pub spectate: Option<String>,
+    // `--players addr`: remote players send input over TCP, tagged with their id (players.rs)
+    pub players: Option<String>,
+    // `--rollback addr,addr,...`: rollback netplay, one address per player (rollback.rs)
+    pub rollback: Option<Vec<String>>,
+    // `--lockstep addr,addr,...`: the same, but waiting for everyone's input (lockstep.rs)
+    pub lockstep: Option<Vec<String>>,
+    // `--player n`: which of those addresses is ours
+    pub player: u32,
+    // `--input-delay frames`: how late local input is played in netplay
+    pub input_delay: Option<u32>,
+    // `--serve addr`: run the `--plugin`s as a headless split server for clients (split.rs)
+    pub serve: Option<String>,
+    // `--connect addr`: run the drivers as a client of the split server at addr
+    pub connect: Option<String>,
+    // `--relay addr/room`: play through a relay (the relay crate). Split servers take clients
+    // there too, clients go there if the server can't be reached, and netplay goes through it
+    // with `--rollback n` / `--lockstep n`, the number of players
+    pub relay: Option<Room>,
+    // Repeatable runs: fixed tick delta, --seed (0 by default), nondeterministic host calls trap
+    pub deterministic: bool,
+    // host_random's seed
+    pub seed: Option<u64>,
+    // `--trace-calls path`: the cross-plugin call graph, written at exit (.dot, else JSON)
+    pub trace_calls: Option<PathBuf>,
+    // `--heap-timeline path`: host heap samples as CSV, written at exit (heap_timeline.rs)
+    pub heap_timeline: Option<PathBuf>,
+    // `--audit-log path`: every link and cross-plugin call, appended as it happens (audit.rs)
+    pub audit_log: Option<PathBuf>,
+    // `--tick-budget ms`: ticks taking longer are logged with the exports that overran
+    pub tick_budget: Option<Duration>,
+    // Don't draw the frame after a tick over budget, to work through queued input first
+    pub skip_slow_frames: bool,
+    // `--record-input file` / `--replay-input file`, see input_record.rs
+    pub record_input: Option<PathBuf>,
+    pub replay_input: Option<PathBuf>,
+    pub on_assert: AssertAction,
+    // `--golden dir`: check headless frames against its snapshots (golden.rs)
+    pub golden: Option<PathBuf>,
+    pub update_golden: bool,
+    // Print the hashes of a deterministic headless run and exit (simulate.rs)
+    pub simulate: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            theme: None,
+            keys: None,
+            config: None,
+            export_format: ExportFormat::Ansi,
+            record: None,
+            headless: false,
+            ticks: 1,
+            input_script: None,
+            every: None,
+            out_dir: None,
+            drivers: Vec::new(),
+            plugins: Vec::new(),
+            layout: Layout::Horizontal,
+            narrate: None,
+            speak: None,
+            sync_url: None,
+            profile_syscalls: false,
+            profile: false,
+            inspect: None,
+            debug_server: None,
+            metrics: None,
+            spectate: None,
+            players: None,
+            rollback: None,
+            lockstep: None,
+            player: 0,
+            input_delay: None,
+            serve: None,
+            connect: None,
+            relay: None,
+            deterministic: false,
+            seed: None,
+            trace_calls: None,
+            heap_timeline: None,
+            audit_log: None,
+            tick_budget: None,
+            skip_slow_frames: false,
+            record_input: None,
+            replay_input: None,
+            on_assert: AssertAction::Log,
+            golden: None,
+            update_golden: false,
+            simulate: false,
+        }
+    }
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--theme" => parsed.theme = Some(value_of(&arg, args.next())?.into()),
+                "--keys" => parsed.keys = Some(value_of(&arg, args.next())?.into()),
+                "--config" => parsed.config = Some(value_of(&arg, args.next())?.into()),
+                "--record" => parsed.record = Some(value_of(&arg, args.next())?.into()),
+                "--headless" => parsed.headless = true,
+                "--ticks" => parsed.ticks = number_of(&arg, args.next())?,
+                "--input" => parsed.input_script = Some(value_of(&arg, args.next())?.into()),
+                "--every" => parsed.every = Some(number_of(&arg, args.next())?),
+                "--out" => parsed.out_dir = Some(value_of(&arg, args.next())?.into()),
+                "--driver" => parsed.drivers.push(module_of(&arg, args.next())?),
+                "--plugin" => parsed.plugins.push(module_of(&arg, args.next())?),
+                "--layout" => parsed.layout = Layout::parse(&value_of(&arg, args.next())?)?,
+                "--narrate" => parsed.narrate = Some(value_of(&arg, args.next())?.into()),
+                "--speak" => parsed.speak = Some(value_of(&arg, args.next())?),
+                "--sync" => parsed.sync_url = Some(value_of(&arg, args.next())?),
+                "--profile-syscalls" => parsed.profile_syscalls = true,
+                "--profile" => parsed.profile = true,
+                "--inspect" => parsed.inspect = Some(range_of(&arg, args.next())?),
+                "--debug-server" => parsed.debug_server = Some(value_of(&arg, args.next())?),
+                "--metrics" => parsed.metrics = Some(value_of(&arg, args.next())?),
+                "--spectate" => parsed.spectate = Some(value_of(&arg, args.next())?),
+                "--players" => parsed.players = Some(value_of(&arg, args.next())?),
+                "--rollback" => parsed.rollback = Some(peers_of(&arg, args.next())?),
+                "--lockstep" => parsed.lockstep = Some(peers_of(&arg, args.next())?),
+                "--player" => parsed.player = number_of(&arg, args.next())?,
+                "--input-delay" => parsed.input_delay = Some(number_of(&arg, args.next())?),
+                "--serve" => parsed.serve = Some(value_of(&arg, args.next())?),
+                "--connect" => parsed.connect = Some(value_of(&arg, args.next())?),
+                "--relay" => parsed.relay = Some(Room::parse(&value_of(&arg, args.next())?)?),
+                "--deterministic" => parsed.deterministic = true,
+                "--seed" => {
+                    let value = value_of(&arg, args.next())?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| anyhow!("Value '{}' for '{}' is not a number", value, arg))?;
+                    parsed.seed = Some(seed);
+                }
+                "--trace-calls" => parsed.trace_calls = Some(value_of(&arg, args.next())?.into()),
+                "--heap-timeline" => {
+                    parsed.heap_timeline = Some(value_of(&arg, args.next())?.into())
+                }
+                "--audit-log" => parsed.audit_log = Some(value_of(&arg, args.next())?.into()),
+                "--tick-budget" => {
+                    let value = value_of(&arg, args.next())?;
+                    let ms: f64 = value
+                        .parse()
+                        .ok()
+                        .filter(|ms: &f64| ms.is_finite() && *ms > 0.0)
+                        .ok_or(anyhow!(
+                            "Value '{}' for '{}' is not a number of milliseconds",
+                            value,
+                            arg
+                        ))?;
+                    parsed.tick_budget = Some(Duration::from_secs_f64(ms / 1000.0));
+                }
+                "--skip-slow-frames" => parsed.skip_slow_frames = true,
+                "--record-input" => parsed.record_input = Some(value_of(&arg, args.next())?.into()),
+                "--replay-input" => parsed.replay_input = Some(value_of(&arg, args.next())?.into()),
+                "--on-assert" => {
+                    parsed.on_assert = AssertAction::parse(&value_of(&arg, args.next())?)?
+                }
+                "--golden" => parsed.golden = Some(value_of(&arg, args.next())?.into()),
+                "--update-golden" => parsed.update_golden = true,
+                "--simulate" => parsed.simulate = true,
+                "--export-format" => {
+                    parsed.export_format = ExportFormat::parse(&value_of(&arg, args.next())?)?
+                }
+                other => return Err(anyhow!("Unknown argument '{}'", other)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn value_of(flag: &str, value: Option<String>) -> Result<String> {
+    value.ok_or(anyhow!("Missing value for '{}'", flag))
+}
+
+// `name=path`
+fn module_of(flag: &str, value: Option<String>) -> Result<(String, PathBuf)> {
+    let spec = value_of(flag, value)?;
+    let (name, path) =
+        spec.split_once('=')
+            .ok_or(anyhow!("Expected '{} name=path', got '{}'", flag, spec))?;
+    Ok((name.to_string(), path.into()))
+}
+
+// `addr,addr,...`, one per player
+fn peers_of(flag: &str, value: Option<String>) -> Result<Vec<String>> {
+    Ok(value_of(flag, value)?
+        .split(',')
+        .map(|peer| peer.trim().to_string())
+        .collect())
+}
+
+// Shared memory range to dump, `addr[:len]` in decimal or 0x hex; 256 bytes if no len
+fn range_of(flag: &str, value: Option<String>) -> Result<(i32, i32)> {
+    let spec = value_of(flag, value)?;
+    let (addr, len) = spec.split_once(':').unwrap_or((&spec, "256"));
+    let parse = |n: &str| match n.strip_prefix("0x") {
+        Some(hex) => i32::from_str_radix(hex, 16),
+        None => n.parse(),
+    };
+    match (parse(addr), parse(len)) {
+        (Ok(addr), Ok(len)) => Ok((addr, len)),
+        _ => Err(anyhow!("Expected '{} addr[:len]', got '{}'", flag, spec)),
+    }
+}
+
+fn number_of(flag: &str, value: Option<String>) -> Result<u32> {
+    let value = value_of(flag, value)?;
+    value
+        .parse()
+        .map_err(|_| anyhow!("Value '{}' for '{}' is not a number", value, flag))
+}
@@ -0,0 +1,118 @@
+use crate::host::host_object::BlindHost;
+use crate::host::logger::Level;
+use crate::host_calls::saves::save_version;
+use anyhow::anyhow;
+use std::time::{Duration, Instant};
+use wasmtime::Val;
+
+// Autosaves, so a crash late in a long session loses minutes rather than the session: every
+// host.toml `[saves] autosave` seconds the host loop asks each plugin exporting
+//
+//   autosave() -> i64
+//       its state as (len << 32 | ptr), freed through free_response if it exports one;
+//       0 or -1 when there's nothing to save this time
+//
+// and writes that to the plugin's AUTOSAVE_SLOT save slot (host_calls/saves.rs), where it
+// host_loads it from like any other slot. Nothing happens while saves are off. With `[saves]
+// journal`, autosaves go through the slot's journal, so they cost what changed since the last
+// one; `autosave = 0` then journals on every pass of the host loop.
+
+pub const AUTOSAVE_SLOT: &str = "autosave";
+
+pub struct Autosave {
+    every: Duration,
+    last: Instant,
+    journal: bool,
+}
+
+impl Autosave {
+    pub fn new(every: Duration) -> Self {
+        Self {
+            every,
+            last: Instant::now(),
+            journal: false,
+        }
+    }
+
+    /// Autosaves through the slots' journals (SaveStore::append) instead of in full.
+    pub fn journaled(mut self, journal: bool) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    pub fn due(&self) -> bool {
+        self.last.elapsed() >= self.every
+    }
+
+    /// Autosaves if it's time to, answering how many plugins saved.
+    pub fn tick(&mut self, host: &mut BlindHost) -> usize {
+        if !self.due() {
+            return 0;
+        }
+        self.last = Instant::now();
+        autosave(host, self.journal)
+    }
+}
+
+/// Saves every plugin that has an `autosave` export and something to save, answering how many
+/// did, through their journals if `journal`. A plugin trapping or a save failing is logged and
+/// doesn't stop the others.
+pub fn autosave(host: &mut BlindHost, journal: bool) -> usize {
+    let Some(saves) = host.store.data().saves.clone() else {
+        return 0;
+    };
+    let plugins: Vec<String> = host
+        .store
+        .data()
+        .slots
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut saved = 0;
+    for plugin in plugins {
+        let Ok(export) = host.get_func(&plugin, "autosave") else {
+            continue;
+        };
+        let mut result = [Val::I64(0)];
+        let state = host
+            .profiled(&plugin, "autosave", |store| {
+                export.call(store, &[], &mut result)
+            })
+            .and_then(|()| match result[0].i64() {
+                Some(0 | -1) => Ok(None),
+                Some(packed) => host.take_response(&plugin, packed).map(Some),
+                None => Err(anyhow!("'autosave' doesn't answer an i64")),
+            })
+            .and_then(|state| match state {
+                Some(state) => {
+                    let saved_with = save_version(host.store.data(), &plugin);
+                    let saved = if journal {
+                        saves
+                            .append(&plugin, AUTOSAVE_SLOT, saved_with, &state)
+                            .map(|_| ())
+                    } else {
+                        saves
+                            .save(&plugin, AUTOSAVE_SLOT, saved_with, &state)
+                            .map(|_| ())
+                    };
+                    saved.map(Some)
+                }
+                None => Ok(None),
+            });
+        match state {
+            Ok(Some(_)) => saved += 1,
+            Ok(None) => {}
+            Err(e) => {
+                // The TUI owns stderr, so this goes to the log pane like the host's other notices
+                let text = format!("Autosave of '{}' failed: {:#}", plugin, e);
+                host.store
+                    .data()
+                    .logger
+                    .lock()
+                    .unwrap()
+                    .log("host", Level::Warn, &text);
+            }
+        }
+    }
+    saved
+}
@@ -0,0 +1,59 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::time::Duration;
+
+// The chat overlay's input line (the chat key, HostAction::Chat). While it's open the keyboard
+// types into it instead of driving the focused pane: Enter posts the line to the host's chat
+// (host_calls/chat.rs), which split play sends to everyone, and Esc closes it unsent. Drivers
+// don't take part; the host draws the overlay over the panes (widgets::render_chat).
+
+// How long a line stays on screen with the input closed
+pub const CHAT_FADE: Duration = Duration::from_secs(8);
+// Lines the overlay shows at most
+pub const CHAT_LINES: usize = 6;
+
+#[derive(Debug, Default)]
+pub struct ChatInput {
+    text: String,
+}
+
+// What a key did to the input
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChatKey {
+    Typing,
+    // Enter on a line with something in it; the input is done
+    Send(String),
+    Close,
+}
+
+impl ChatInput {
+    pub fn key(&mut self, key: &KeyEvent) -> ChatKey {
+        match key.code {
+            KeyCode::Enter if self.text.trim().is_empty() => ChatKey::Close,
+            KeyCode::Enter => ChatKey::Send(std::mem::take(&mut self.text)),
+            KeyCode::Esc => ChatKey::Close,
+            KeyCode::Backspace => {
+                self.text.pop();
+                ChatKey::Typing
+            }
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.text.push(c);
+                ChatKey::Typing
+            }
+            _ => ChatKey::Typing,
+        }
+    }
+
+    /// Pasted text, on one line.
+    pub fn paste(&mut self, text: &str) {
+        self.text
+            .extend(text.chars().map(|c| if c.is_control() { ' ' } else { c }));
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
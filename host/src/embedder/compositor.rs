@@ -0,0 +1,238 @@
+use super::driver::{DriverHandle, Frame};
+use crate::host::host_object::BlindHost;
+use anyhow::{anyhow, Result};
+use grid_protocol::widgets::WidgetNode;
+use grid_protocol::{GridCell, CELL_IMAGE, GRID_EXT_IMAGES, GRID_EXT_STYLES};
+use ratatui::layout::{Constraint, Layout as Split, Rect};
+
+// Colors of the pane bar, as ANSI 256 indices so the theme remaps them like grid cells
+const BAR_FOCUSED: (u8, u8) = (0, 15);
+const BAR_IDLE: (u8, u8) = (15, 8);
+const BLANK: GridCell = GridCell {
+    character: ' ' as u32,
+    fg_color: 15,
+    bg_color: 0,
+    style: 0,
+};
+
+// Extensions that change how cells are drawn
+const RENDERED: u64 = GRID_EXT_STYLES | GRID_EXT_IMAGES;
+
+// Drivers that weren't granted an extension may still leave its bits in cells (v1 drivers
+// never cleared the old padding field), so those bits are dropped rather than drawn
+fn without_ungranted(mut cell: GridCell, extensions: u64) -> GridCell {
+    if extensions & GRID_EXT_STYLES == 0 {
+        cell.style = 0;
+    }
+    if extensions & GRID_EXT_IMAGES == 0 && cell.character & CELL_IMAGE != 0 {
+        cell.character = char::REPLACEMENT_CHARACTER as u32;
+    }
+    cell
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // Panes side by side
+    Horizontal,
+    // Panes stacked top to bottom
+    Vertical,
+    // Only the focused pane is shown
+    Tabs,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "horizontal" => Ok(Self::Horizontal),
+            "vertical" => Ok(Self::Vertical),
+            "tabs" => Ok(Self::Tabs),
+            other => Err(anyhow!(
+                "Unknown layout '{}' (expected horizontal, vertical or tabs)",
+                other
+            )),
+        }
+    }
+}
+
+// One loaded driver and the host's copy of its grid and widgets
+pub struct Pane {
+    pub driver: DriverHandle,
+    pub frame: Frame,
+    pub widgets: Vec<WidgetNode>,
+}
+
+impl Pane {
+    /// Pulls the driver's latest grid and widgets. Returns whether either changed.
+    pub fn refresh(&mut self, host: &mut BlindHost) -> Result<bool> {
+        // Only the regions the driver reports dirty are copied out of shared memory
+        let mut changed = self.driver.update_frame(host, &mut self.frame)?;
+        let widgets = self.driver.read_widgets(host)?;
+        if widgets != self.widgets {
+            self.widgets = widgets;
+            changed = true;
+        }
+        Ok(changed)
+    }
+}
+
+// Arranges several drivers on one screen. Input goes to the focused pane only.
+pub struct Compositor {
+    panes: Vec<Pane>,
+    layout: Layout,
+    focus: usize,
+}
+
+impl Compositor {
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            panes: Vec::new(),
+            layout,
+            focus: 0,
+        }
+    }
+
+    pub fn add(&mut self, driver: DriverHandle) {
+        self.panes.push(Pane {
+            driver,
+            frame: Frame::default(),
+            widgets: Vec::new(),
+        });
+    }
+
+    pub fn panes(&self) -> &[Pane] {
+        &self.panes
+    }
+
+    pub fn panes_mut(&mut self) -> &mut [Pane] {
+        &mut self.panes
+    }
+
+    pub fn focus_index(&self) -> usize {
+        self.focus
+    }
+
+    pub fn focused(&self) -> &Pane {
+        &self.panes[self.focus]
+    }
+
+    pub fn focus_next(&mut self) {
+        if !self.panes.is_empty() {
+            self.focus = (self.focus + 1) % self.panes.len();
+        }
+    }
+
+    /// Ticks every pane once with no input, so each has something to show.
+    pub fn prime(&mut self, host: &mut BlindHost, tick_rate: f32) -> Result<()> {
+        for pane in &mut self.panes {
+            pane.driver.set_tickrate(host, tick_rate)?;
+            pane.driver.tick_only(host, 0.0)?;
+            pane.refresh(host)?;
+        }
+        Ok(())
+    }
+
+    /// Swaps a reloaded driver's exports into its pane and primes it like `prime` does.
+    /// Returns whether `name` is one of the panes' drivers.
+    pub fn rebind(&mut self, host: &mut BlindHost, name: &str, tick_rate: f32) -> Result<bool> {
+        let Some(pane) = self.panes.iter_mut().find(|pane| pane.driver.name == name) else {
+            return Ok(false);
+        };
+        pane.driver = DriverHandle::bind(host, name)?;
+        pane.frame = Frame::default();
+        pane.widgets.clear();
+        pane.driver.set_tickrate(host, tick_rate)?;
+        pane.driver.tick_only(host, 0.0)?;
+        pane.refresh(host)?;
+        Ok(true)
+    }
+
+    /// Where each visible pane goes on a `width` x `height` screen.
+    pub fn areas(&self, width: u16, height: u16) -> Vec<(usize, Rect)> {
+        let mut area = Rect::new(0, 0, width, height);
+        // A single pane gets the whole screen, otherwise the top row shows pane names
+        if self.panes.len() > 1 {
+            area.y += 1;
+            area.height = area.height.saturating_sub(1);
+        }
+
+        let count = self.panes.len() as u32;
+        let ratios = vec![Constraint::Ratio(1, count.max(1)); self.panes.len()];
+        match self.layout {
+            Layout::Tabs => vec![(self.focus, area)],
+            Layout::Horizontal => Split::horizontal(ratios)
+                .split(area)
+                .iter()
+                .copied()
+                .enumerate()
+                .collect(),
+            Layout::Vertical => Split::vertical(ratios)
+                .split(area)
+                .iter()
+                .copied()
+                .enumerate()
+                .collect(),
+        }
+    }
+
+    /// Flattens the visible panes (and the pane bar) into one screen-sized frame.
+    pub fn compose(&self, width: u16, height: u16) -> Frame {
+        let mut frame = Frame {
+            width: width as i32,
+            height: height as i32,
+            cells: vec![BLANK; width as usize * height as usize],
+            counter: None,
+        };
+
+        if self.panes.len() > 1 {
+            self.draw_bar(&mut frame);
+        }
+
+        for (idx, area) in self.areas(width, height) {
+            let extensions = self.panes[idx].driver.extensions;
+            let pane = &self.panes[idx].frame;
+            let cols = (pane.width.max(0) as u16).min(area.width);
+            let rows = (pane.height.max(0) as u16).min(area.height);
+            for y in 0..rows {
+                let src = y as usize * pane.width as usize;
+                let dst = (area.y + y) as usize * width as usize + area.x as usize;
+                let (from, to) = (
+                    &pane.cells[src..src + cols as usize],
+                    &mut frame.cells[dst..dst + cols as usize],
+                );
+                if extensions & RENDERED == RENDERED {
+                    to.copy_from_slice(from);
+                } else {
+                    for (to, from) in to.iter_mut().zip(from) {
+                        *to = without_ungranted(*from, extensions);
+                    }
+                }
+            }
+        }
+        frame
+    }
+
+    fn draw_bar(&self, frame: &mut Frame) {
+        let width = frame.width.max(0) as usize;
+        let mut x = 0;
+        for (idx, pane) in self.panes.iter().enumerate() {
+            let (fg, bg) = if idx == self.focus {
+                BAR_FOCUSED
+            } else {
+                BAR_IDLE
+            };
+            for c in format!(" {} ", pane.driver.name).chars() {
+                if x >= width {
+                    return;
+                }
+                frame.cells[x] = GridCell {
+                    character: c as u32,
+                    fg_color: fg,
+                    bg_color: bg,
+                    style: 0,
+                };
+                x += 1;
+            }
+            x += 1;
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use super::autosave::Autosave;
+use super::transport::TransportKind;
+use crate::host::logger::LogConfig;
+use crate::host::module_cache::DEFAULT_MODULE_CACHE_DIR;
+use crate::host::quotas::QuotaConfig;
+use crate::host_calls::save_backend::{HttpBackend, LocalDir, Mirrored, SaveBackend};
+use crate::host_calls::saves::DEFAULT_SAVES_DIR;
+use crate::host_calls::wasi::DEFAULT_WASI_DIR;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Looked up in the working directory when no `--config` is given
+pub const DEFAULT_CONFIG_PATH: &str = "host.toml";
+
+// Host settings that aren't about looks or keys (theme.toml, keys.toml).
+//
+// host.toml:
+//   [log]                    # plugin logs, see host/logger.rs
+//   level = "info"
+//   sinks = ["pane"]
+//   [net]                    # split play, see embedder/transport.rs
+//   transport = "tcp"
+//   name = "ada"             # who your chat lines are from; $USER if unset
+//   [saves]                  # save slots, see host_calls/saves.rs
+//   autosave = 300           # seconds between autosaves (embedder/autosave.rs); off if unset
+//   journal = true           # autosave what changed, compacted now and then (saves.rs)
+//   backend = "local"        # or "http", mirrored to `url` (host_calls/save_backend.rs)
+//   dir = "saves"            # where they're kept here, DEFAULT_SAVES_DIR if unset
+//   url = "http://saves.example/ada"
+//   [quotas]                 # per-plugin budgets, see host/quotas.rs; each off if unset
+//   cpu_ms = 8               # time in its exports per tick
+//   heap_mb = 64             # host heap it holds
+//   fuel = 1000000000        # wasm instructions per call
+//   timeout_ms = 500         # time per call
+//   throttle = 30            # ticks skipped for going over
+//   suspend_after = 3        # times over in a row before it's suspended
+//   [tick_deadlines]         # ms a plugin's tick may run before it traps as a runaway
+//   "*" = 250                # (BlindHostConfig::tick_deadlines); none if unset
+//   game = 50                # this plugin's, instead of "*"'s
+//   [ecs]                    # the ECS kernel the game's plugins run on (ecs_protocol::kernel)
+//   kernel = "bevy"          # plugins/ecs-core; or "custom", archived/custom_ecs, which is
+//                            # lighter, for constrained targets. None is loaded if unset
+//   path = "kernels/ecs.wasm"  # its build, the release one in target/ if unset
+//   [wasi]                   # WASI Preview 1 for plugins, see host_calls/wasi.rs
+//   enabled = true           # off if unset
+//   dir = "wasi"             # what they see as `/`, one subdirectory each; DEFAULT_WASI_DIR if unset
+//   [module_cache]           # compiled plugins, kept between runs, see host/module_cache.rs
+//   enabled = false          # on if unset
+//   dir = "cache"            # DEFAULT_MODULE_CACHE_DIR if unset
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HostConfig {
+    pub log: LogConfig,
+    pub net: NetConfig,
+    pub saves: SavesConfig,
+    pub quotas: QuotasConfig,
+    pub tick_deadlines: HashMap<String, u64>,
+    pub ecs: EcsConfig,
+    pub wasi: WasiConfig,
+    pub module_cache: ModuleCacheConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NetConfig {
+    pub transport: TransportKind,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SavesConfig {
+    pub autosave: Option<u64>,
+    pub journal: bool,
+    pub backend: SaveBackendKind,
+    pub dir: Option<PathBuf>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct QuotasConfig {
+    pub cpu_ms: Option<f64>,
+    pub heap_mb: Option<u64>,
+    pub fuel: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    pub throttle: Option<u32>,
+    pub suspend_after: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EcsConfig {
+    pub kernel: Option<KernelKind>,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WasiConfig {
+    pub enabled: bool,
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ModuleCacheConfig {
+    pub enabled: Option<bool>,
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KernelKind {
+    Bevy,
+    Custom,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveBackendKind {
+    #[default]
+    Local,
+    Http,
+}
+
+impl SavesConfig {
+    pub fn backend(&self) -> Result<Arc<dyn SaveBackend>> {
+        let local = LocalDir::new(self.dir.clone().unwrap_or(DEFAULT_SAVES_DIR.into()));
+        match self.backend {
+            SaveBackendKind::Local => Ok(Arc::new(local)),
+            SaveBackendKind::Http => {
+                let url = self
+                    .url
+                    .as_deref()
+                    .ok_or(anyhow!("[saves] backend = \"http\" needs a url"))?;
+                Ok(Arc::new(Mirrored::new(
+                    local,
+                    Arc::new(HttpBackend::new(url)?),
+                )))
+            }
+        }
+    }
+
+    // Journaled autosaves are cheap enough to take every pass of the host loop (autosave = 0)
+    pub fn autosave_every(&self) -> Option<Duration> {
+        self.autosave
+            .filter(|&secs| secs > 0 || self.journal)
+            .map(Duration::from_secs)
+    }
+
+    pub fn autosave(&self) -> Option<Autosave> {
+        self.autosave_every()
+            .map(|every| Autosave::new(every).journaled(self.journal))
+    }
+}
+
+impl QuotasConfig {
+    pub fn quotas(&self) -> QuotaConfig {
+        let defaults = QuotaConfig::default();
+        QuotaConfig {
+            cpu: self.cpu_ms.map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+            heap: self.heap_mb.map(|mb| mb * 1024 * 1024),
+            fuel: self.fuel,
+            timeout: self.timeout_ms.map(Duration::from_millis),
+            throttle: self.throttle.unwrap_or(defaults.throttle),
+            suspend_after: self.suspend_after.unwrap_or(defaults.suspend_after),
+        }
+    }
+}
+
+impl EcsConfig {
+    /// Where the configured kernel's .wasm is, if one is configured.
+    pub fn kernel_path(&self) -> Option<PathBuf> {
+        let kernel = self.kernel?;
+        Some(self.path.clone().unwrap_or_else(|| {
+            let build = match kernel {
+                KernelKind::Bevy => "ecs_core.wasm",
+                KernelKind::Custom => "custom_ecs.wasm",
+            };
+            Path::new("target/wasm32-unknown-unknown/release").join(build)
+        }))
+    }
+}
+
+impl WasiConfig {
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or(DEFAULT_WASI_DIR.into())
+    }
+}
+
+impl ModuleCacheConfig {
+    /// Where compiled plugins are kept, unless the cache is off.
+    pub fn dir(&self) -> Option<PathBuf> {
+        match self.enabled {
+            Some(false) => None,
+            _ => Some(self.dir.clone().unwrap_or(DEFAULT_MODULE_CACHE_DIR.into())),
+        }
+    }
+}
+
+impl NetConfig {
+    /// The name chat lines typed here go out under.
+    pub fn player_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .filter(|name| !name.is_empty())
+            .unwrap_or("player".to_string())
+    }
+}
+
+impl HostConfig {
+    pub fn tick_deadlines(&self) -> HashMap<String, Duration> {
+        self.tick_deadlines
+            .iter()
+            .map(|(plugin, ms)| (plugin.clone(), Duration::from_millis(*ms)))
+            .collect()
+    }
+
+    /// Uses `path` if given, otherwise `host.toml` if it exists, otherwise the defaults.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::load(Path::new(DEFAULT_CONFIG_PATH))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config '{}'", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config '{}'", path.display()))
+    }
+}
@@ -0,0 +1,138 @@
+use super::headless::{self, ScriptInput};
+use crate::host::backtrace::Trapped;
+use crate::host::host_object::BlindHost;
+use crate::host::inspect::{self, RegionKind};
+use anyhow::{Context, Error, Result};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use wasmtime::Trap;
+
+// Bundles are written under here, one `crash-<unix time>` directory each
+pub const DEFAULT_CRASH_DIR: &str = "crashes";
+
+// How many ticks of input a bundle keeps
+const INPUT_HISTORY: usize = 1000;
+
+// What the host writes when a plugin trap ends the run, for the plugin's author to reproduce
+// it offline:
+//
+//   trap.txt              the error, with the symbolicated backtrace (backtrace.rs)
+//   slot-<plugin>.bin     the trapping plugin's whole slot (data, then stack), from `slot_base`
+//   memory.txt            the region map and heap usage
+//   input.txt             the last ticks' input as a headless input script (`--input`)
+//   config.txt            the command line and host.toml the run used
+
+/// The input of the latest ticks, numbered like headless ticks.
+#[derive(Default)]
+pub struct InputLog {
+    events: VecDeque<(u32, ScriptInput)>,
+    tick: u32,
+}
+
+impl InputLog {
+    /// Notes the input the focused driver was ticked with.
+    pub fn push(&mut self, input: ScriptInput) {
+        self.tick += 1;
+        if self.events.len() == INPUT_HISTORY {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.tick, input));
+    }
+
+    fn to_script(&self) -> String {
+        let mut script = String::new();
+        if self.tick as usize > self.events.len() {
+            let _ = writeln!(
+                script,
+                "# Only the last {} of {} ticks",
+                self.events.len(),
+                self.tick
+            );
+        }
+        for (tick, input) in &self.events {
+            if let Some(key) = headless::format_input(input) {
+                let _ = writeln!(script, "{} {}", tick, key);
+            }
+        }
+        script
+    }
+}
+
+/// Whether `error` is a plugin trap, the only errors a bundle is written for.
+pub fn is_trap(error: &Error) -> bool {
+    error.downcast_ref::<Trap>().is_some()
+}
+
+/// Writes a bundle for `error` under `dir` and returns its path. `config` is whatever
+/// describes the run, e.g. the parsed command line.
+pub fn write_bundle(
+    dir: &Path,
+    error: &Error,
+    host: &BlindHost,
+    inputs: &InputLog,
+    config: &str,
+) -> Result<PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let bundle = dir.join(format!("crash-{}", timestamp));
+    std::fs::create_dir_all(&bundle)
+        .with_context(|| format!("Failed to create '{}'", bundle.display()))?;
+    let write = |name: &str, contents: &[u8]| {
+        let path = bundle.join(name);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write '{}'", path.display()))
+    };
+
+    write("trap.txt", format!("{:?}\n", error).as_bytes())?;
+
+    let state = host.store.data();
+    let plugin = error
+        .downcast_ref::<Trapped>()
+        .map(|trapped| trapped.plugin.as_str());
+    // The latest slot, should the plugin have been reloaded
+    let slot = state
+        .slots
+        .iter()
+        .rev()
+        .find(|(name, _)| Some(name.as_str()) == plugin);
+    if let Some((name, base)) = slot {
+        write(
+            &format!("slot-{}.bin", name),
+            host.view_mem(*base, state.slot_size)?,
+        )?;
+    }
+
+    let mut memory = String::new();
+    if let Some((name, base)) = slot {
+        let _ = writeln!(memory, "slot_base of '{}': {:#010x}", name, base);
+    }
+    let regions = inspect::region_map(host);
+    let (mut in_use, mut free, mut largest_free) = (0, 0, 0);
+    for region in &regions {
+        let size = region.end - region.start;
+        match region.kind {
+            RegionKind::HeapInUse => in_use += size,
+            RegionKind::HeapFree => {
+                free += size;
+                largest_free = largest_free.max(size);
+            }
+            _ => {}
+        }
+    }
+    let _ = writeln!(
+        memory,
+        "host heap: {} bytes in use, {} free (largest free block {})\n",
+        in_use, free, largest_free
+    );
+    for region in &regions {
+        let _ = writeln!(memory, "{}", region);
+    }
+    write("memory.txt", memory.as_bytes())?;
+
+    write("input.txt", inputs.to_script().as_bytes())?;
+    write("config.txt", config.as_bytes())?;
+    Ok(bundle)
+}
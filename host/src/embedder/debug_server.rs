@@ -0,0 +1,326 @@
+use super::compositor::Compositor;
+use crate::host::host_object::BlindHost;
+use crate::host::inspect::{self, RegionKind};
+use crate::host::interfaces;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use wasmtime::{Val, ValType};
+
+// Most bytes one `read` answers with
+const MAX_READ: i32 = 1 << 20;
+
+// Debug commands for external tools (editor plugins, dashboards), served with `--debug-server
+// addr`. One JSON object per line each way; every request gets `{"ok": ...}` or `{"error": "..."}`.
+//
+//   {"cmd": "plugins"}                                   name, slot, capabilities, exports
+//   {"cmd": "stats"}                                     memory, bus, panes, profile
+//   {"cmd": "read", "addr": 4096, "len": 64}             hex bytes and the regions they're in
+//   {"cmd": "call", "plugin": "p", "export": "f", "args": [1, 2.5]}
+//   {"cmd": "reload", "plugin": "p"}                     from the path it was loaded from
+//   {"cmd": "call_graph", "format": "dot"}               "json" (the default) or Graphviz text
+//
+// Connections are read on their own threads, but commands run on the main thread between
+// ticks (`serve`), so they never race a plugin call. Nothing is authenticated: anyone who can
+// connect can call into plugins, so keep the address on localhost.
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Plugins,
+    Stats,
+    Read {
+        addr: i32,
+        #[serde(default = "default_read_len")]
+        len: i32,
+    },
+    Call {
+        plugin: String,
+        export: String,
+        #[serde(default)]
+        args: Vec<Value>,
+    },
+    Reload {
+        plugin: String,
+    },
+    CallGraph {
+        #[serde(default)]
+        format: Option<String>,
+    },
+}
+
+fn default_read_len() -> i32 {
+    256
+}
+
+pub struct DebugServer {
+    requests: Receiver<(Command, Sender<Value>)>,
+}
+
+impl DebugServer {
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to start the debug server on {}", addr))?;
+        let (sender, requests) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, sender) {
+                        eprintln!("⚠️ [DEBUG] {:#}", e);
+                    }
+                });
+            }
+        });
+        eprintln!("🐞 [HOST] Debug server listening on {}", addr);
+
+        Ok(Self { requests })
+    }
+
+    /// Runs the commands that came in since the last call. `modules` are the paths plugins
+    /// and drivers were loaded from, for `reload`. Returns how many ran.
+    pub fn serve(
+        &self,
+        host: &mut BlindHost,
+        compositor: &mut Compositor,
+        modules: &[(String, PathBuf)],
+        tick_rate: f32,
+    ) -> usize {
+        let mut served = 0;
+        for (command, reply) in self.requests.try_iter() {
+            let answer = match run(command, host, compositor, modules, tick_rate) {
+                Ok(value) => json!({ "ok": value }),
+                Err(e) => json!({ "error": format!("{:#}", e) }),
+            };
+            let _ = reply.send(answer);
+            served += 1;
+        }
+        served
+    }
+}
+
+fn handle_connection(stream: TcpStream, requests: Sender<(Command, Sender<Value>)>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (reply, answer) = mpsc::channel();
+                // The host went away
+                if requests.send((command, reply)).is_err() {
+                    break;
+                }
+                answer.recv()?
+            }
+            Err(e) => json!({ "error": format!("Invalid command: {}", e) }),
+        };
+        writeln!(writer, "{}", answer)?;
+    }
+    Ok(())
+}
+
+fn run(
+    command: Command,
+    host: &mut BlindHost,
+    compositor: &mut Compositor,
+    modules: &[(String, PathBuf)],
+    tick_rate: f32,
+) -> Result<Value> {
+    match command {
+        Command::Plugins => plugins(host),
+        Command::Stats => Ok(stats(host, compositor)),
+        Command::Read { addr, len } => read(host, addr, len),
+        Command::Call {
+            plugin,
+            export,
+            args,
+        } => call(host, &plugin, &export, &args),
+        Command::Reload { plugin } => {
+            let (_, path) = modules
+                .iter()
+                .find(|(name, _)| *name == plugin)
+                .ok_or(anyhow!("'{}' wasn't loaded from a file", plugin))?;
+            // A fresh slot: the old instance isn't unloaded (BlindHost::unload_plugin), so
+            // plugins that already linked against it keep calling it
+            host.load_plugin_file(&plugin, path)?;
+            interfaces::check_exports(host, &plugin)?;
+            let driver = compositor.rebind(host, &plugin, tick_rate)?;
+            Ok(json!({ "plugin": plugin, "driver": driver }))
+        }
+        Command::CallGraph { format } => match format.as_deref() {
+            None | Some("json") => Ok(host.call_graph_json()),
+            Some("dot") => Ok(Value::String(host.call_graph_dot())),
+            Some(other) => Err(anyhow!(
+                "Unknown call graph format '{}' (json or dot)",
+                other
+            )),
+        },
+    }
+}
+
+fn plugins(host: &mut BlindHost) -> Result<Value> {
+    let slots = host.store.data().slots.clone();
+    let mut plugins = Vec::new();
+    for (idx, (name, slot)) in slots.iter().enumerate() {
+        // Reloaded plugins hold several slots, only the latest is live
+        if slots[idx + 1..].iter().any(|(later, _)| later == name) {
+            continue;
+        }
+        let instance = host.store.data().instances[name];
+        let mut exports = Vec::new();
+        let funcs: Vec<_> = instance
+            .exports(&mut host.store)
+            .filter_map(|e| Some((e.name().to_string(), e.into_func()?)))
+            .collect();
+        for (export, func) in funcs {
+            let ty = func.ty(&host.store);
+            exports.push(json!({
+                "name": export,
+                "params": ty.params().map(|t| t.to_string()).collect::<Vec<_>>(),
+                "results": ty.results().map(|t| t.to_string()).collect::<Vec<_>>(),
+            }));
+        }
+        plugins.push(json!({
+            "name": name,
+            "slot": slot,
+            "capabilities": host.plugin_capabilities(name),
+            "exports": exports,
+        }));
+    }
+    Ok(Value::Array(plugins))
+}
+
+fn stats(host: &BlindHost, compositor: &Compositor) -> Value {
+    let state = host.store.data();
+    let (mut heap_in_use, mut heap_free) = (0, 0);
+    for region in inspect::region_map(host) {
+        match region.kind {
+            RegionKind::HeapInUse => heap_in_use += region.end - region.start,
+            RegionKind::HeapFree => heap_free += region.end - region.start,
+            _ => {}
+        }
+    }
+    let panes: Vec<Value> = compositor
+        .panes()
+        .iter()
+        .map(|pane| {
+            json!({
+                "driver": pane.driver.name,
+                "version": pane.driver.version,
+                "extensions": pane.driver.extensions,
+                "width": pane.frame.width,
+                "height": pane.frame.height,
+                "widgets": pane.widgets.len(),
+            })
+        })
+        .collect();
+    let profile: Vec<Value> = host
+        .profile_report()
+        .entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "plugin": entry.plugin,
+                "export": entry.export,
+                "calls": entry.calls,
+                "avg_us": entry.avg.as_micros() as u64,
+                "p99_us": entry.p99.as_micros() as u64,
+                "max_us": entry.max.as_micros() as u64,
+            })
+        })
+        .collect();
+
+    json!({
+        "memory": {
+            "bytes": state.shared_memory.data().len(),
+            "heap_start": state.heap_start_address,
+            "heap_in_use": heap_in_use,
+            "heap_free": heap_free,
+            "slots_used": state.slots.len(),
+            "slots_free": (state.heap_start_address - state.next_memory_offset) / state.slot_size
+                + state.free_slots.len() as i32,
+        },
+        "capabilities": host.capabilities(),
+        "bus_queued": state.bus.lock().unwrap().len(),
+        "panes": panes,
+        "profile": profile,
+    })
+}
+
+fn read(host: &BlindHost, addr: i32, len: i32) -> Result<Value> {
+    if len > MAX_READ {
+        return Err(anyhow!("At most {} bytes per read", MAX_READ));
+    }
+    let bytes = host.view_mem(addr, len)?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    let (start, end) = (addr as u32, addr as u32 + len as u32);
+    let regions: Vec<Value> = inspect::region_map(host)
+        .into_iter()
+        .filter(|r| r.start < end && r.end > start)
+        .map(|r| json!({ "start": r.start, "end": r.end, "kind": r.kind.to_string() }))
+        .collect();
+    Ok(json!({ "addr": addr, "hex": hex, "regions": regions }))
+}
+
+fn call(host: &mut BlindHost, plugin: &str, export: &str, args: &[Value]) -> Result<Value> {
+    let func = host
+        .get_func(plugin, export)
+        .with_context(|| format!("'{}::{}'", plugin, export))?;
+    let ty = func.ty(&host.store);
+    if ty.params().len() != args.len() {
+        return Err(anyhow!(
+            "'{}::{}' takes {} arguments, got {}",
+            plugin,
+            export,
+            ty.params().len(),
+            args.len()
+        ));
+    }
+    let params = ty
+        .params()
+        .zip(args)
+        .map(|(ty, arg)| to_val(&ty, arg))
+        .collect::<Result<Vec<_>>>()?;
+    let mut results = vec![Val::I32(0); ty.results().len()];
+    host.profiled(plugin, export, |store| {
+        func.call(store, &params, &mut results)
+    })?;
+    results
+        .iter()
+        .map(from_val)
+        .collect::<Result<Vec<_>>>()
+        .map(Value::Array)
+}
+
+fn to_val(ty: &ValType, arg: &Value) -> Result<Val> {
+    let bad = || anyhow!("Expected an {} argument, got {}", ty, arg);
+    Ok(match ty {
+        ValType::I32 => Val::I32(
+            arg.as_i64()
+                .and_then(|n| i32::try_from(n).ok())
+                .ok_or_else(bad)?,
+        ),
+        ValType::I64 => Val::I64(arg.as_i64().ok_or_else(bad)?),
+        ValType::F32 => Val::F32((arg.as_f64().ok_or_else(bad)? as f32).to_bits()),
+        ValType::F64 => Val::F64(arg.as_f64().ok_or_else(bad)?.to_bits()),
+        _ => return Err(anyhow!("{} arguments can't be passed as JSON", ty)),
+    })
+}
+
+fn from_val(val: &Val) -> Result<Value> {
+    Ok(match val {
+        Val::I32(n) => json!(n),
+        Val::I64(n) => json!(n),
+        Val::F32(bits) => json!(f32::from_bits(*bits)),
+        Val::F64(bits) => json!(f64::from_bits(*bits)),
+        _ => return Err(anyhow!("A result can't be expressed as JSON")),
+    })
+}
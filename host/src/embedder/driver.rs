@@ -0,0 +1,352 @@
+use super::export::FrameRef;
+use crate::host::host_object::BlindHost;
+use crate::host_calls::allocator::alloc_owned;
+use anyhow::{anyhow, Result};
+use fat_ptr::FatPtr;
+use grid_protocol::widgets::{self, WidgetNode};
+use grid_protocol::{
+    DirtyRect, GridCell, GridInput, GRID_EXT_IMAGES, GRID_EXT_PLAYERS, GRID_EXT_STYLES,
+    GRID_PROTOCOL_VERSION, GRID_V1_EXTENSIONS, INPUT_TEXT_CAPACITY,
+};
+use wasmtime::TypedFunc;
+
+// How often a grid copy is retried when the driver swaps buffers underneath it
+const MAX_READ_ATTEMPTS: usize = 3;
+
+// Where the grid-driver build lands, driven when no --driver is given
+pub const DEFAULT_DRIVER: (&str, &str) = (
+    "grid-driver",
+    "target/wasm32-unknown-unknown/release/grid_driver.wasm",
+);
+
+// GRID_EXT_* bits this host renders, offered to every version 2 driver
+pub const OFFERED_EXTENSIONS: u64 = GRID_EXT_STYLES | GRID_EXT_IMAGES | GRID_EXT_PLAYERS;
+
+// The exports every grid driver plugin provides, bound once after loading
+pub struct DriverHandle {
+    pub name: String,
+    tick_fn: TypedFunc<(f32,), ()>,
+    set_input_fn: TypedFunc<(i32,), ()>,
+    set_tickrate_fn: TypedFunc<(f32,), ()>,
+    get_dims_fn: TypedFunc<(), i64>,
+    get_ptr_fn: TypedFunc<(), i32>,
+    // Optional: drivers without it are re-read in full every frame
+    get_dirty_fn: Option<TypedFunc<(), i64>>,
+    // Optional: drivers that draw everything into cells don't export it
+    get_widgets_fn: Option<TypedFunc<(), i64>>,
+    // Optional: only double-buffered drivers count frames
+    get_counter_fn: Option<TypedFunc<(), i64>>,
+    // The driver reads its input from this pointer. We write to it.
+    // Composition text goes in the INPUT_TEXT_CAPACITY bytes right after it.
+    input_ptr: i32,
+    // Grid protocol version the driver speaks and the GRID_EXT_* bits it was granted
+    pub version: u32,
+    pub extensions: u64,
+}
+
+// An owned copy of the driver's grid
+#[derive(Default)]
+pub struct Frame {
+    pub width: i32,
+    pub height: i32,
+    pub cells: Vec<GridCell>,
+    // The driver's frame counter when this copy was taken, if it has one
+    pub counter: Option<u64>,
+}
+
+impl Frame {
+    pub fn as_ref(&self) -> FrameRef<'_> {
+        FrameRef {
+            cells: &self.cells,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl DriverHandle {
+    pub fn bind(host: &mut BlindHost, name: &str) -> Result<Self> {
+        // Typed functions for performance and type safety
+        let tick_fn = host.get_func(name, "tick")?.typed(&host.store)?;
+        let set_input_fn = host.get_func(name, "set_input")?.typed(&host.store)?;
+        let set_tickrate_fn = host.get_func(name, "set_tickrate")?.typed(&host.store)?;
+        let get_dims_fn = host
+            .get_func(name, "get_grid_dimensions")?
+            .typed(&host.store)?;
+        let get_ptr_fn = host.get_func(name, "get_grid_ptr")?.typed(&host.store)?;
+        let get_dirty_fn = match host.get_func(name, "get_dirty_rects") {
+            Ok(func) => Some(func.typed(&host.store)?),
+            Err(_) => None,
+        };
+        let get_widgets_fn = match host.get_func(name, "get_widgets") {
+            Ok(func) => Some(func.typed(&host.store)?),
+            Err(_) => None,
+        };
+        let get_counter_fn = match host.get_func(name, "get_frame_counter") {
+            Ok(func) => Some(func.typed(&host.store)?),
+            Err(_) => None,
+        };
+
+        // Version 1 drivers don't negotiate and keep what v1 always had
+        let (version, extensions) = match host.get_func(name, "grid_negotiate") {
+            Ok(func) => {
+                let negotiate = func.typed::<(i32, i64), i64>(&host.store)?;
+                let offer = (GRID_PROTOCOL_VERSION as i32, OFFERED_EXTENSIONS as i64);
+                let granted =
+                    host.profiled(name, "grid_negotiate", |store| negotiate.call(store, offer))?;
+                (GRID_PROTOCOL_VERSION, granted as u64 & OFFERED_EXTENSIONS)
+            }
+            Err(_) => (1, GRID_V1_EXTENSIONS),
+        };
+
+        // Allocate Input Buffer (plus composition text) in Shared Memory, charged to the driver so
+        // unloading it (a restart, say) takes the buffer with it
+        let input_size = std::mem::size_of::<GridInput>() + INPUT_TEXT_CAPACITY;
+        let input_ptr = match alloc_owned(host.store.data(), name, input_size as i32) {
+            0 => return Err(anyhow!("Failed to allocate input buffer in SharedMemory")),
+            ptr => ptr,
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            tick_fn,
+            set_input_fn,
+            set_tickrate_fn,
+            get_dims_fn,
+            get_ptr_fn,
+            get_dirty_fn,
+            get_widgets_fn,
+            get_counter_fn,
+            input_ptr,
+            version,
+            extensions,
+        })
+    }
+
+    pub fn set_tickrate(&self, host: &mut BlindHost, rate: f32) -> Result<()> {
+        host.profiled(&self.name, "set_tickrate", |store| {
+            self.set_tickrate_fn.call(store, (rate,))
+        })
+    }
+
+    /// Hands `input` to the driver and runs one tick. Its player is only kept for drivers
+    /// granted GRID_EXT_PLAYERS. Drivers their quotas hold back (BlindHost::may_tick) skip it.
+    pub fn tick(&self, host: &mut BlindHost, input: &GridInput, delta: f32) -> Result<()> {
+        crate::scope!("driver_tick", &self.name);
+        if !host.may_tick(&self.name) {
+            return Ok(());
+        }
+        let input = match self.extensions & GRID_EXT_PLAYERS {
+            0 => input.with_player(0),
+            _ => *input,
+        };
+        host.write_mem(self.input_ptr, bytemuck::bytes_of(&input))?;
+        host.profiled(&self.name, "set_input", |store| {
+            self.set_input_fn.call(store, (self.input_ptr,))
+        })?;
+        host.profiled(&self.name, "tick", |store| {
+            self.tick_fn.call(store, (delta,))
+        })
+    }
+
+    /// Hands a composition event (`INPUT_PREEDIT` / `INPUT_COMMIT`) to the driver and runs one tick.
+    /// Text beyond INPUT_TEXT_CAPACITY bytes is cut at the last whole character.
+    pub fn tick_text(
+        &self,
+        host: &mut BlindHost,
+        player: u8,
+        input_type: u32,
+        text: &str,
+        delta: f32,
+    ) -> Result<()> {
+        let mut len = text.len().min(INPUT_TEXT_CAPACITY);
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        let input = GridInput {
+            input_type,
+            key_code: len as u32,
+            ..Default::default()
+        }
+        .with_player(player);
+        let text_ptr = self.input_ptr + std::mem::size_of::<GridInput>() as i32;
+        host.write_mem(text_ptr, &text.as_bytes()[..len])?;
+        self.tick(host, &input, delta)
+    }
+
+    /// Ticks without touching the input buffer (used for the very first frame).
+    pub fn tick_only(&self, host: &mut BlindHost, delta: f32) -> Result<()> {
+        crate::scope!("driver_tick", &self.name);
+        if !host.may_tick(&self.name) {
+            return Ok(());
+        }
+        host.profiled(&self.name, "tick", |store| {
+            self.tick_fn.call(store, (delta,))
+        })
+    }
+
+    pub fn dimensions(&self, host: &mut BlindHost) -> Result<(i32, i32)> {
+        let dims = host.profiled(&self.name, "get_grid_dimensions", |store| {
+            self.get_dims_fn.call(store, ())
+        })?;
+        Ok(((dims >> 32) as i32, (dims & 0xFFFFFFFF) as i32))
+    }
+
+    /// Completed frames so far, or `None` for drivers that don't double-buffer.
+    pub fn frame_counter(&self, host: &mut BlindHost) -> Result<Option<u64>> {
+        let Some(get_counter_fn) = &self.get_counter_fn else {
+            return Ok(None);
+        };
+        let counter = host.profiled(&self.name, "get_frame_counter", |store| {
+            get_counter_fn.call(store, ())
+        })?;
+        Ok((counter >= 0).then_some(counter as u64))
+    }
+
+    /// Copies the driver's last completed frame. If the frame counter moves while we copy,
+    /// the copy may mix two frames, so it is thrown away and taken again.
+    pub fn read_frame(&self, host: &mut BlindHost) -> Result<Frame> {
+        for _ in 0..MAX_READ_ATTEMPTS {
+            let before = self.frame_counter(host)?;
+            let (width, height) = self.dimensions(host)?;
+            let grid_ptr = host.profiled(&self.name, "get_grid_ptr", |store| {
+                self.get_ptr_fn.call(store, ())
+            })?;
+
+            let cells = host
+                .view_slice::<GridCell>(grid_ptr, cell_count(width, height))?
+                .to_vec();
+
+            if self.frame_counter(host)? == before {
+                return Ok(Frame {
+                    width,
+                    height,
+                    cells,
+                    counter: before,
+                });
+            }
+        }
+        Err(anyhow!(
+            "Driver '{}' swapped buffers during every read attempt",
+            self.name
+        ))
+    }
+
+    /// Borrows the driver's current grid straight out of shared memory, without copying.
+    /// No guest code can run while the view is alive, so it can't be torn.
+    pub fn grid_view<'a>(&self, host: &'a mut BlindHost) -> Result<FrameRef<'a>> {
+        let (width, height) = self.dimensions(host)?;
+        let grid_ptr = host.profiled(&self.name, "get_grid_ptr", |store| {
+            self.get_ptr_fn.call(store, ())
+        })?;
+        let cells = host.view_slice(grid_ptr, cell_count(width, height))?;
+        Ok(FrameRef {
+            cells,
+            width,
+            height,
+        })
+    }
+
+    /// Regions changed since the last tick, or `None` if the whole grid must be re-read.
+    pub fn dirty_rects(&self, host: &mut BlindHost) -> Result<Option<Vec<DirtyRect>>> {
+        let Some(get_dirty_fn) = &self.get_dirty_fn else {
+            return Ok(None);
+        };
+        let packed = host.profiled(&self.name, "get_dirty_rects", |store| {
+            get_dirty_fn.call(store, ())
+        })?;
+        if packed == -1 {
+            return Ok(None);
+        }
+
+        let FatPtr { ptr, len: count } = FatPtr::unpack(packed);
+        if count == 0 {
+            return Ok(Some(Vec::new()));
+        }
+        let data = host.read_mem(ptr, count * std::mem::size_of::<DirtyRect>() as i32)?;
+        Ok(Some(
+            data.chunks_exact(std::mem::size_of::<DirtyRect>())
+                .map(bytemuck::pod_read_unaligned)
+                .collect(),
+        ))
+    }
+
+    /// Brings `frame` up to date, copying only the dirty regions when the driver reports them.
+    /// Returns whether anything changed.
+    pub fn update_frame(&self, host: &mut BlindHost, frame: &mut Frame) -> Result<bool> {
+        let counter = self.frame_counter(host)?;
+        if counter.is_some() && counter == frame.counter {
+            return Ok(false);
+        }
+
+        let rects = self.dirty_rects(host)?;
+        let (width, height) = self.dimensions(host)?;
+        // Dirty rects only describe the latest frame; if we skipped one, they aren't enough
+        let next_frame = match (frame.counter, counter) {
+            (Some(ours), Some(theirs)) => theirs == ours + 1,
+            _ => true,
+        };
+
+        let rects = match rects {
+            Some(rects) if width == frame.width && height == frame.height && next_frame => rects,
+            _ => {
+                *frame = self.read_frame(host)?;
+                return Ok(true);
+            }
+        };
+        if rects.is_empty() {
+            frame.counter = counter;
+            return Ok(false);
+        }
+
+        let grid_ptr = host.profiled(&self.name, "get_grid_ptr", |store| {
+            self.get_ptr_fn.call(store, ())
+        })?;
+        let grid = host.view_slice::<GridCell>(grid_ptr, cell_count(width, height))?;
+        for rect in rects {
+            // Clamp to the grid so a sloppy driver can't make us read past it
+            let x0 = (rect.x as i32).min(width);
+            let x1 = (rect.x as i32 + rect.width as i32).min(width);
+            let y0 = (rect.y as i32).min(height);
+            let y1 = (rect.y as i32 + rect.height as i32).min(height);
+            if x0 >= x1 {
+                continue;
+            }
+
+            for y in y0..y1 {
+                let start = (y * width + x0) as usize;
+                let end = (y * width + x1) as usize;
+                frame.cells[start..end].copy_from_slice(&grid[start..end]);
+            }
+        }
+
+        // A swap mid-copy leaves a mix of frames behind, start over from a full read
+        if self.frame_counter(host)? != counter {
+            *frame = self.read_frame(host)?;
+            return Ok(true);
+        }
+        frame.counter = counter;
+        Ok(true)
+    }
+
+    /// The widget tree the driver wants drawn over its grid, empty if it has none.
+    pub fn read_widgets(&self, host: &mut BlindHost) -> Result<Vec<WidgetNode>> {
+        let Some(get_widgets_fn) = &self.get_widgets_fn else {
+            return Ok(Vec::new());
+        };
+        let packed = host.profiled(&self.name, "get_widgets", |store| {
+            get_widgets_fn.call(store, ())
+        })?;
+        let FatPtr { ptr, len } = FatPtr::unpack(packed);
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let data = host.read_mem(ptr, len)?;
+        widgets::decode(&data).ok_or(anyhow!("Driver '{}' sent malformed widgets", self.name))
+    }
+}
+
+// In usize, so dimensions too large for memory fail the view rather than overflow
+fn cell_count(width: i32, height: i32) -> usize {
+    (width.max(0) as usize).saturating_mul(height.max(0) as usize)
+}
@@ -0,0 +1,183 @@
+use super::images::ImageStore;
+use super::theme::Theme;
+use anyhow::{anyhow, Context, Result};
+use grid_protocol::GridCell;
+use ratatui::style::Color;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ansi,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "ansi" => Ok(Self::Ansi),
+            "html" => Ok(Self::Html),
+            other => Err(anyhow!(
+                "Unknown export format '{}' (expected ansi or html)",
+                other
+            )),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ansi => "ans",
+            Self::Html => "html",
+        }
+    }
+}
+
+// A borrowed view of one rendered frame, as the host read it from the driver
+pub struct FrameRef<'a> {
+    pub cells: &'a [GridCell],
+    pub width: i32,
+    pub height: i32,
+}
+
+impl FrameRef<'_> {
+    fn rows(&self) -> impl Iterator<Item = &[GridCell]> {
+        let width = self.width.max(1) as usize;
+        self.cells.chunks(width).take(self.height.max(0) as usize)
+    }
+}
+
+/// Writes `frame` next to the working directory as `frame-<unix secs>.<ext>`.
+pub fn save_frame(
+    frame: &FrameRef,
+    format: ExportFormat,
+    theme: &Theme,
+    images: &ImageStore,
+) -> Result<PathBuf> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = PathBuf::from(format!("frame-{}.{}", stamp, format.extension()));
+    write_frame(&path, frame, format, theme, images)?;
+    Ok(path)
+}
+
+pub fn write_frame(
+    path: &Path,
+    frame: &FrameRef,
+    format: ExportFormat,
+    theme: &Theme,
+    images: &ImageStore,
+) -> Result<()> {
+    let text = match format {
+        ExportFormat::Ansi => to_ansi(frame, theme, images),
+        ExportFormat::Html => to_html(frame, theme, images),
+    };
+    std::fs::write(path, text).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Glyphs only, one line per row. Handy for diffing frames.
+pub fn to_text(frame: &FrameRef, images: &ImageStore) -> String {
+    let mut out = String::new();
+    for row in frame.rows() {
+        out.extend(row.iter().map(|cell| images.glyph(cell).unwrap_or(' ')));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_ansi(frame: &FrameRef, theme: &Theme, images: &ImageStore) -> String {
+    ansi_rows(frame, theme, images, "\n")
+}
+
+/// Full-screen redraw of `frame` (cursor home + raw-mode line endings), as a terminal would receive it.
+pub fn to_ansi_screen(frame: &FrameRef, theme: &Theme, images: &ImageStore) -> String {
+    let mut out = String::from("\x1b[H");
+    out.push_str(&ansi_rows(frame, theme, images, "\r\n"));
+    out
+}
+
+fn ansi_rows(frame: &FrameRef, theme: &Theme, images: &ImageStore, line_end: &str) -> String {
+    let mut out = String::new();
+    for row in frame.rows() {
+        let mut current: Option<(u8, u8)> = None;
+        for cell in row {
+            let colors = (cell.fg_color, cell.bg_color);
+            if current != Some(colors) {
+                out.push_str("\x1b[");
+                push_sgr_color(&mut out, 38, theme.color(cell.fg_color));
+                out.push(';');
+                push_sgr_color(&mut out, 48, theme.color(cell.bg_color));
+                out.push('m');
+                current = Some(colors);
+            }
+            out.push(images.glyph(cell).unwrap_or(' '));
+        }
+        out.push_str("\x1b[0m");
+        out.push_str(line_end);
+    }
+    out
+}
+
+fn push_sgr_color(out: &mut String, base: u8, color: Color) {
+    let _ = match color {
+        Color::Rgb(r, g, b) => write!(out, "{};2;{};{};{}", base, r, g, b),
+        Color::Indexed(i) => write!(out, "{};5;{}", base, i),
+        _ => write!(out, "{}", base + 1), // 39/49: terminal default
+    };
+}
+
+pub fn to_html(frame: &FrameRef, theme: &Theme, images: &ImageStore) -> String {
+    let (page_r, page_g, page_b) = theme
+        .background
+        .map(|c| match c {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i) => theme.rgb(i),
+            _ => (0, 0, 0),
+        })
+        .unwrap_or((0, 0, 0));
+
+    let mut out = String::new();
+    out.push_str(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Grid Frame</title>\n",
+    );
+    let _ = writeln!(
+        out,
+        "<style>body {{ background: #{:02x}{:02x}{:02x}; }} pre {{ font-family: monospace; line-height: 1.2; }}</style>",
+        page_r, page_g, page_b
+    );
+    out.push_str("</head>\n<body>\n<pre>");
+
+    for row in frame.rows() {
+        let mut current: Option<(u8, u8)> = None;
+        for cell in row {
+            let colors = (cell.fg_color, cell.bg_color);
+            if current != Some(colors) {
+                if current.is_some() {
+                    out.push_str("</span>");
+                }
+                let (fr, fg, fb) = theme.rgb(cell.fg_color);
+                let (br, bg, bb) = theme.rgb(cell.bg_color);
+                let _ = write!(
+                    out,
+                    "<span style=\"color:#{:02x}{:02x}{:02x};background:#{:02x}{:02x}{:02x}\">",
+                    fr, fg, fb, br, bg, bb
+                );
+                current = Some(colors);
+            }
+            match images.glyph(cell).unwrap_or(' ') {
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '&' => out.push_str("&amp;"),
+                c => out.push(c),
+            }
+        }
+        if current.is_some() {
+            out.push_str("</span>");
+        }
+        out.push('\n');
+    }
+
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,181 @@
+use super::export::FrameRef;
+use super::images::ImageStore;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+// Golden frames: headless frames checked against snapshots stored next to a driver's tests,
+// `frame-<tick>.txt` in a directory per scenario (`--golden dir`, or GoldenFrames in a test).
+// A snapshot is the frame's glyphs, then one letter per cell naming its colors and style,
+// then what the letters mean, so a color or style regression fails as loudly as a glyph:
+//
+//   Hi
+//   --- attributes ---
+//   ab
+//   --- legend ---
+//   a fg 7 bg 0 style 0
+//   b fg 1 bg 0 style 1
+//
+// Missing snapshots are written, with a note: review and commit them. Mismatches fail with a
+// line diff, unless updating (`--update-golden`, or UPDATE_GOLDEN set for tests).
+
+// Set to update a test's snapshots instead of failing on mismatches
+pub const UPDATE_ENV: &str = "UPDATE_GOLDEN";
+
+// Letters for attribute combinations, in order of first appearance; any further share '*'
+const ATTRIBUTE_LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const OTHER_ATTRIBUTES: char = '*';
+
+/// `frame` in the snapshot format above.
+pub fn snapshot(frame: &FrameRef, images: &ImageStore) -> String {
+    let width = frame.width.max(1) as usize;
+    let mut text = String::new();
+    let mut attributes = String::from("--- attributes ---\n");
+    let mut legend: Vec<(u8, u8, u16)> = Vec::new();
+    let mut letters = HashMap::new();
+
+    for row in frame.cells.chunks(width) {
+        for cell in row {
+            text.push(images.glyph(cell).unwrap_or(' '));
+            let key = (cell.fg_color, cell.bg_color, cell.style);
+            let letter = *letters.entry(key).or_insert_with(|| {
+                legend.push(key);
+                ATTRIBUTE_LETTERS
+                    .chars()
+                    .nth(legend.len() - 1)
+                    .unwrap_or(OTHER_ATTRIBUTES)
+            });
+            attributes.push(letter);
+        }
+        text.push('\n');
+        attributes.push('\n');
+    }
+
+    text.push_str(&attributes);
+    text.push_str("--- legend ---\n");
+    for (idx, (fg, bg, style)) in legend.iter().enumerate() {
+        match ATTRIBUTE_LETTERS.chars().nth(idx) {
+            Some(letter) => {
+                writeln!(text, "{} fg {} bg {} style {}", letter, fg, bg, style).unwrap()
+            }
+            None => {
+                writeln!(text, "{} any other", OTHER_ATTRIBUTES).unwrap();
+                break;
+            }
+        }
+    }
+    text
+}
+
+/// The lines that differ between two snapshots, `-` expected and `+` actual with a `^` under
+/// each changed column. `None` if they're the same.
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut out = String::new();
+    for line in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(line), actual.get(line));
+        if old == new {
+            continue;
+        }
+        writeln!(out, "line {}:", line + 1).unwrap();
+        if let Some(old) = old {
+            writeln!(out, "  - |{}|", old).unwrap();
+        }
+        if let Some(new) = new {
+            writeln!(out, "  + |{}|", new).unwrap();
+        }
+        if let (Some(old), Some(new)) = (old, new) {
+            let (old, new): (Vec<char>, Vec<char>) = (old.chars().collect(), new.chars().collect());
+            let carets: String = (0..old.len().max(new.len()))
+                .map(|col| {
+                    if old.get(col) == new.get(col) {
+                        ' '
+                    } else {
+                        '^'
+                    }
+                })
+                .collect();
+            writeln!(out, "     {}", carets.trim_end()).unwrap();
+        }
+    }
+    Some(out)
+}
+
+/// One scenario's snapshots: check every frame, then `finish` for the verdict.
+pub struct GoldenFrames {
+    dir: PathBuf,
+    update: bool,
+    written: Vec<PathBuf>,
+    mismatches: Vec<String>,
+}
+
+impl GoldenFrames {
+    pub fn new(dir: &Path, update: bool) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            update,
+            written: Vec::new(),
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// For tests: updating when UPDATE_GOLDEN is set.
+    pub fn from_env(dir: &Path) -> Self {
+        Self::new(dir, std::env::var_os(UPDATE_ENV).is_some())
+    }
+
+    /// Compares the snapshot of the frame at `tick` with the stored one.
+    pub fn check(&mut self, tick: u32, actual: &str) -> Result<()> {
+        let path = self.dir.join(format!("frame-{}.txt", tick));
+        let expected = match std::fs::read_to_string(&path) {
+            Ok(expected) => Some(expected),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+
+        let mismatch = expected
+            .as_deref()
+            .and_then(|expected| diff(expected, actual));
+        if expected.is_some() && mismatch.is_none() {
+            return Ok(());
+        }
+        match mismatch {
+            Some(diff) if !self.update => {
+                self.mismatches
+                    .push(format!("{} differs:\n{}", path.display(), diff));
+            }
+            _ => {
+                std::fs::create_dir_all(&self.dir)
+                    .with_context(|| format!("Failed to create '{}'", self.dir.display()))?;
+                std::fs::write(&path, actual)
+                    .with_context(|| format!("Failed to write '{}'", path.display()))?;
+                self.written.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails with every mismatch's diff. Notes the snapshots it wrote on stderr.
+    pub fn finish(self) -> Result<()> {
+        for path in &self.written {
+            eprintln!("📸 [HOST] Wrote golden frame '{}'", path.display());
+        }
+        if self.mismatches.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} golden frame(s) differ (rerun with --update-golden or {}=1 if that's intended)\n\n{}",
+            self.mismatches.len(),
+            UPDATE_ENV,
+            self.mismatches.join("\n")
+        ))
+    }
+}
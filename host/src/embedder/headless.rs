@@ -0,0 +1,305 @@
+use super::driver::DriverHandle;
+use super::export::{self, FrameRef};
+use super::golden::{self, GoldenFrames};
+use super::images::ImageStore;
+use crate::host::host_object::BlindHost;
+use crate::host_calls::bus;
+use anyhow::{anyhow, Context, Result};
+use grid_protocol::{
+    GridInput, INPUT_COMMIT, INPUT_KEY, INPUT_PREEDIT, KEY_BACKSPACE, KEY_DELETE, KEY_DOWN,
+    KEY_ENTER, KEY_ESC, KEY_LEFT, KEY_RIGHT, KEY_TAB, KEY_UP, MOD_ALT, MOD_CTRL, MOD_SHIFT,
+};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Every headless tick pretends this much time passed, so runs don't depend on the machine
+pub const HEADLESS_DELTA: f32 = 1.0 / 60.0;
+
+#[derive(Default)]
+pub struct HeadlessOptions {
+    pub ticks: u32,
+    pub input_script: Option<PathBuf>,
+    // Also dump the grid every N ticks (the final state is always dumped)
+    pub every: Option<u32>,
+    // Write `frame-<tick>.txt` files here instead of printing to stdout
+    pub out_dir: Option<PathBuf>,
+    // End the run with an error at the first failed host_assert
+    pub fail_on_assert: bool,
+    // Check the dumped frames against the snapshots here instead (golden.rs)
+    pub golden: Option<PathBuf>,
+    // Write the snapshots that differ instead of failing
+    pub update_golden: bool,
+}
+
+// Scripted input: one `<tick> <key>` pair per line, `#` starts a comment.
+// Keys are single characters, `Space`, or one of Enter/Esc/Backspace/Tab/Up/Down/Left/Right/Delete,
+// optionally prefixed with `Ctrl+`, `Shift+` and/or `Alt+`. `Preedit:<text>` and
+// `Commit:<text>` send IME composition events instead. Ticks are 1-based, and may carry
+// the time they were recorded at (`<tick>@<ms>`, see input_record.rs), which headless runs ignore.
+//
+//   1 Right
+//   2 Right
+//   3 Preedit:ni
+//   4 Commit:你
+//   5@1830 Ctrl+c
+#[derive(Default)]
+pub struct InputScript {
+    events: BTreeMap<u32, ScriptInput>,
+    // Milliseconds since the recording started, for ticks that have them
+    times: BTreeMap<u32, u64>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ScriptInput {
+    Key(GridInput),
+    // INPUT_PREEDIT / INPUT_COMMIT with its text
+    Text(u32, String),
+}
+
+impl InputScript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read input script '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid input script '{}'", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut events = BTreeMap::new();
+        let mut times = BTreeMap::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (tick, key) = line
+                .split_once(char::is_whitespace)
+                .ok_or(anyhow!("Line {}: expected '<tick> <key>'", line_no + 1))?;
+            let (tick, ms) = match tick.split_once('@') {
+                Some((tick, ms)) => (tick, Some(ms)),
+                None => (tick, None),
+            };
+            let tick: u32 = tick
+                .parse()
+                .map_err(|_| anyhow!("Line {}: '{}' is not a tick number", line_no + 1, tick))?;
+            if let Some(ms) = ms {
+                let ms: u64 = ms.parse().map_err(|_| {
+                    anyhow!(
+                        "Line {}: '{}' is not a time in milliseconds",
+                        line_no + 1,
+                        ms
+                    )
+                })?;
+                times.insert(tick, ms);
+            }
+            let key = key.trim();
+            let input =
+                parse_input(key).ok_or(anyhow!("Line {}: unknown key '{}'", line_no + 1, key))?;
+            events.insert(tick, input);
+        }
+        Ok(Self { events, times })
+    }
+
+    pub fn input_at(&self, tick: u32) -> ScriptInput {
+        self.events
+            .get(&tick)
+            .cloned()
+            .unwrap_or(ScriptInput::Key(GridInput::default()))
+    }
+
+    /// When `tick` was recorded, if the script says.
+    pub fn time_at(&self, tick: u32) -> Option<u64> {
+        self.times.get(&tick).copied()
+    }
+
+    /// The first tick after `tick` with input.
+    pub fn next_event(&self, tick: u32) -> Option<u32> {
+        self.events.range(tick + 1..).next().map(|(tick, _)| *tick)
+    }
+
+    pub fn last_tick(&self) -> u32 {
+        self.events.keys().next_back().copied().unwrap_or(0)
+    }
+}
+
+/// An input script line's key, or its `Preedit:` / `Commit:` text (the inverse of `format_input`).
+pub fn parse_input(spec: &str) -> Option<ScriptInput> {
+    if let Some(text) = spec.strip_prefix("Preedit:") {
+        Some(ScriptInput::Text(INPUT_PREEDIT, text.to_string()))
+    } else if let Some(text) = spec.strip_prefix("Commit:") {
+        Some(ScriptInput::Text(INPUT_COMMIT, text.to_string()))
+    } else {
+        parse_key(spec).map(ScriptInput::Key)
+    }
+}
+
+pub fn parse_key(spec: &str) -> Option<GridInput> {
+    let mut input = GridInput {
+        input_type: INPUT_KEY,
+        ..Default::default()
+    };
+
+    let mut rest = spec;
+    loop {
+        if let Some(r) = rest.strip_prefix("Ctrl+") {
+            input.modifiers |= MOD_CTRL;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("Shift+") {
+            input.modifiers |= MOD_SHIFT;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("Alt+") {
+            input.modifiers |= MOD_ALT;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+
+    input.key_code = match rest {
+        "Enter" => KEY_ENTER,
+        "Esc" => KEY_ESC,
+        "Backspace" => KEY_BACKSPACE,
+        "Tab" => KEY_TAB,
+        "Up" => KEY_UP,
+        "Down" => KEY_DOWN,
+        "Left" => KEY_LEFT,
+        "Right" => KEY_RIGHT,
+        "Delete" => KEY_DELETE,
+        "Space" => ' ' as u32,
+        single => {
+            let mut chars = single.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c as u32,
+                _ => return None,
+            }
+        }
+    };
+    Some(input)
+}
+
+/// `input` as an input script line's key, `None` for no input (the inverse of `parse_key` and
+/// the `Preedit:` / `Commit:` prefixes).
+pub fn format_input(input: &ScriptInput) -> Option<String> {
+    let input = match input {
+        ScriptInput::Text(INPUT_PREEDIT, text) => return Some(format!("Preedit:{}", text)),
+        ScriptInput::Text(_, text) => return Some(format!("Commit:{}", text)),
+        ScriptInput::Key(input) if input.input_type != INPUT_KEY => return None,
+        ScriptInput::Key(input) => input,
+    };
+
+    let mut spec = String::new();
+    for (bit, prefix) in [
+        (MOD_CTRL, "Ctrl+"),
+        (MOD_SHIFT, "Shift+"),
+        (MOD_ALT, "Alt+"),
+    ] {
+        if input.modifiers & bit != 0 {
+            spec.push_str(prefix);
+        }
+    }
+    match input.key_code {
+        KEY_ENTER => spec.push_str("Enter"),
+        KEY_ESC => spec.push_str("Esc"),
+        KEY_BACKSPACE => spec.push_str("Backspace"),
+        KEY_TAB => spec.push_str("Tab"),
+        KEY_UP => spec.push_str("Up"),
+        KEY_DOWN => spec.push_str("Down"),
+        KEY_LEFT => spec.push_str("Left"),
+        KEY_RIGHT => spec.push_str("Right"),
+        KEY_DELETE => spec.push_str("Delete"),
+        code if code == ' ' as u32 => spec.push_str("Space"),
+        code => spec.push(char::from_u32(code)?),
+    }
+    Some(spec)
+}
+
+/// Runs the driver for `options.ticks` ticks without a terminal.
+pub fn run(
+    host: &mut BlindHost,
+    driver: &DriverHandle,
+    images: &ImageStore,
+    options: &HeadlessOptions,
+) -> Result<()> {
+    let script = match &options.input_script {
+        Some(path) => InputScript::load(path)?,
+        None => InputScript::default(),
+    };
+
+    if let Some(dir) = &options.out_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    }
+
+    let mut golden = options
+        .golden
+        .as_deref()
+        .map(|dir| GoldenFrames::new(dir, options.update_golden));
+    play(
+        host,
+        driver,
+        &script,
+        options,
+        |tick, frame| match &mut golden {
+            Some(golden) => golden.check(tick, &golden::snapshot(&frame, images)),
+            None => emit(options, tick, &export::to_text(&frame, images)),
+        },
+    )?;
+    golden.map_or(Ok(()), GoldenFrames::finish)
+}
+
+/// Ticks the driver through `script` as `run` does, handing `on_frame` each frame it dumps:
+/// every `options.every` ticks and the last.
+pub fn play(
+    host: &mut BlindHost,
+    driver: &DriverHandle,
+    script: &InputScript,
+    options: &HeadlessOptions,
+    mut on_frame: impl FnMut(u32, FrameRef) -> Result<()>,
+) -> Result<()> {
+    driver.set_tickrate(host, 0.0)?;
+    driver.tick_only(host, 0.0)?;
+
+    for tick in 1..=options.ticks {
+        let tick_start = std::time::Instant::now();
+        bus::deliver(host)?;
+        match script.input_at(tick) {
+            ScriptInput::Key(input) => driver.tick(host, &input, HEADLESS_DELTA)?,
+            ScriptInput::Text(kind, text) => {
+                driver.tick_text(host, 0, kind, &text, HEADLESS_DELTA)?
+            }
+        }
+        host.end_tick(tick_start.elapsed());
+        if let Some(failure) = host.take_assert_failures().into_iter().next() {
+            if options.fail_on_assert {
+                return Err(anyhow!(
+                    "'{}' failed an assertion at tick {}: {}",
+                    failure.plugin,
+                    failure.tick,
+                    failure.message
+                ));
+            }
+        }
+
+        let periodic = options.every.is_some_and(|n| n > 0 && tick % n == 0);
+        if periodic || tick == options.ticks {
+            on_frame(tick, driver.grid_view(host)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn emit(options: &HeadlessOptions, tick: u32, text: &str) -> Result<()> {
+    match &options.out_dir {
+        Some(dir) => {
+            let path = dir.join(format!("frame-{}.txt", tick));
+            std::fs::write(&path, text)
+                .with_context(|| format!("Failed to write '{}'", path.display()))
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "--- tick {} ---", tick)?;
+            stdout.write_all(text.as_bytes())?;
+            Ok(())
+        }
+    }
+}
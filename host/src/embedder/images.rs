@@ -0,0 +1,326 @@
+use crate::host::caller_state::HostState;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::Result;
+use base64::Engine as _;
+use crossterm::{cursor::MoveTo, queue};
+use grid_protocol::{GridCell, ImageDesc, CELL_IMAGE, CELL_IMAGE_ID_MASK, IMAGE_FORMAT_RGBA8};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use wasmtime::{Caller, Linker};
+
+// Kitty wants the base64 payload split into chunks of at most 4096 bytes
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Sixel,
+    None,
+}
+
+impl GraphicsProtocol {
+    /// Guesses what the terminal supports from the environment.
+    /// `GRID_GRAPHICS=kitty|sixel|none` overrides the guess.
+    pub fn detect() -> Self {
+        if let Ok(forced) = std::env::var("GRID_GRAPHICS") {
+            return match forced.as_str() {
+                "kitty" => Self::Kitty,
+                "sixel" => Self::Sixel,
+                _ => Self::None,
+            };
+        }
+
+        let term = std::env::var("TERM").unwrap_or_default();
+        let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term_program == "WezTerm"
+            || term_program == "ghostty"
+        {
+            Self::Kitty
+        } else if term.contains("sixel") || term == "foot" || term_program == "mlterm" {
+            Self::Sixel
+        } else {
+            Self::None
+        }
+    }
+}
+
+pub struct Image {
+    pub desc: ImageDesc,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub x: u16,
+    pub y: u16,
+    pub id: u32,
+}
+
+#[derive(Default)]
+pub struct ImageStore {
+    images: HashMap<u32, Image>,
+    next_id: u32,
+    // Kitty keeps image data terminal-side, so each id only has to be sent once
+    transmitted: HashSet<u32>,
+    last_placements: Vec<Placement>,
+}
+
+pub type SharedImageStore = Arc<Mutex<ImageStore>>;
+
+impl ImageStore {
+    pub fn insert(&mut self, desc: ImageDesc, rgba: Vec<u8>) -> Option<u32> {
+        let expected_len = desc.width as usize * desc.height as usize * 4;
+        if desc.format != IMAGE_FORMAT_RGBA8
+            || expected_len == 0
+            || rgba.len() != expected_len
+            || desc.cols == 0
+            || desc.rows == 0
+        {
+            return None;
+        }
+
+        let id = self.next_id + 1;
+        if id > CELL_IMAGE_ID_MASK {
+            return None;
+        }
+        self.next_id = id;
+        self.images.insert(id, Image { desc, rgba });
+        Some(id)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Image> {
+        self.images.get(&id)
+    }
+
+    /// Glyph to put in the text buffer for an image anchor cell.
+    pub fn fallback_glyph(&self, id: u32) -> char {
+        self.get(id)
+            .and_then(|img| char::from_u32(img.desc.fallback))
+            .unwrap_or('?')
+    }
+
+    /// Text representation of any cell: its character, or the fallback for image anchors.
+    pub fn glyph(&self, cell: &GridCell) -> Option<char> {
+        match image_id(cell) {
+            Some(id) => Some(self.fallback_glyph(id)),
+            None => char::from_u32(cell.character),
+        }
+    }
+
+    /// Draws the image placements on top of the frame ratatui just flushed.
+    /// Does nothing if the placements didn't change since the last call.
+    pub fn render<W: Write>(
+        &mut self,
+        out: &mut W,
+        protocol: GraphicsProtocol,
+        placements: Vec<Placement>,
+    ) -> std::io::Result<()> {
+        if protocol == GraphicsProtocol::None || placements == self.last_placements {
+            return Ok(());
+        }
+
+        if protocol == GraphicsProtocol::Kitty {
+            // Drop the previous placements but keep the image data around
+            write!(out, "\x1b_Ga=d,d=a,q=2\x1b\\")?;
+        }
+
+        for p in &placements {
+            let Some(img) = self.images.get(&p.id) else {
+                continue;
+            };
+
+            match protocol {
+                GraphicsProtocol::Kitty => {
+                    if self.transmitted.insert(p.id) {
+                        out.write_all(kitty_transmit(p.id, img).as_bytes())?;
+                    }
+                    queue!(out, MoveTo(p.x, p.y))?;
+                    write!(
+                        out,
+                        "\x1b_Ga=p,i={},c={},r={},C=1,q=2\x1b\\",
+                        p.id, img.desc.cols, img.desc.rows
+                    )?;
+                }
+                GraphicsProtocol::Sixel => {
+                    queue!(out, MoveTo(p.x, p.y))?;
+                    out.write_all(sixel_encode(img).as_bytes())?;
+                }
+                GraphicsProtocol::None => {}
+            }
+        }
+
+        self.last_placements = placements;
+        out.flush()
+    }
+}
+
+pub fn image_id(cell: &GridCell) -> Option<u32> {
+    if cell.character & CELL_IMAGE != 0 {
+        Some(cell.character & CELL_IMAGE_ID_MASK)
+    } else {
+        None
+    }
+}
+
+/// Finds every image anchor in a `width`-wide grid that lands inside the visible area.
+pub fn collect_placements(
+    cells: &[GridCell],
+    width: i32,
+    area_w: u16,
+    area_h: u16,
+) -> Vec<Placement> {
+    if width <= 0 {
+        return Vec::new();
+    }
+    cells
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, cell)| {
+            let id = image_id(cell)?;
+            let x = (idx as i32 % width) as u16;
+            let y = (idx as i32 / width) as u16;
+            (x < area_w && y < area_h).then_some(Placement { x, y, id })
+        })
+        .collect()
+}
+
+// --- PROTOCOL ENCODERS ---
+
+fn kitty_transmit(id: u32, img: &Image) -> String {
+    let payload = base64::engine::general_purpose::STANDARD.encode(&img.rgba);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        // base64 output is always ASCII
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Ga=t,f=32,s={},v={},i={},q=2,m={};{}\x1b\\",
+                img.desc.width, img.desc.height, id, more, chunk
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    out
+}
+
+// Quantizes to the 6x6x6 color cube so the palette fits in one fixed header
+fn sixel_encode(img: &Image) -> String {
+    let w = img.desc.width as usize;
+    let h = img.desc.height as usize;
+
+    let color_at = |x: usize, y: usize| -> Option<usize> {
+        let px = &img.rgba[(y * w + x) * 4..(y * w + x) * 4 + 4];
+        if px[3] < 128 {
+            return None;
+        }
+        let q = |v: u8| (v as usize * 5 + 127) / 255;
+        Some(q(px[0]) * 36 + q(px[1]) * 6 + q(px[2]))
+    };
+
+    let mut out = String::from("\x1bPq");
+    let _ = write!(out, "\"1;1;{};{}", w, h);
+    for i in 0..216 {
+        let pct = |v: usize| v * 100 / 5;
+        let _ = write!(
+            out,
+            "#{};2;{};{};{}",
+            i,
+            pct(i / 36),
+            pct(i / 6 % 6),
+            pct(i % 6)
+        );
+    }
+
+    for band in (0..h).step_by(6) {
+        let band_h = (h - band).min(6);
+        let mut colors: Vec<usize> = (0..w)
+            .flat_map(|x| (0..band_h).filter_map(move |dy| color_at(x, band + dy)))
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for color in colors {
+            let _ = write!(out, "#{}", color);
+            let mut run_char = '\0';
+            let mut run_len = 0;
+            for x in 0..w {
+                let mut bits = 0u8;
+                for dy in 0..band_h {
+                    if color_at(x, band + dy) == Some(color) {
+                        bits |= 1 << dy;
+                    }
+                }
+                let c = (63 + bits) as char;
+                if c == run_char {
+                    run_len += 1;
+                } else {
+                    push_sixel_run(&mut out, run_char, run_len);
+                    run_char = c;
+                    run_len = 1;
+                }
+            }
+            push_sixel_run(&mut out, run_char, run_len);
+            out.push('$');
+        }
+        out.push('-');
+    }
+
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_sixel_run(out: &mut String, c: char, len: usize) {
+    match len {
+        0 => {}
+        1..=3 => (0..len).for_each(|_| out.push(c)),
+        _ => {
+            let _ = write!(out, "!{}{}", len, c);
+        }
+    }
+}
+
+// --- HOST CALLS ---
+
+pub fn register_host_calls(
+    linker: &mut Linker<HostState>,
+    plugin: String,
+    images: SharedImageStore,
+) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "host_upload_image",
+        move |caller: Caller<'_, HostState>,
+              desc_ptr: i32,
+              data_ptr: i32,
+              data_len: i32|
+              -> Result<i32> {
+            let desc_len = std::mem::size_of::<ImageDesc>() as i32;
+            validate_guest_range(&caller, &plugin, "host_upload_image", desc_ptr, desc_len)?;
+            validate_guest_range(&caller, &plugin, "host_upload_image", data_ptr, data_len)?;
+            let Some(desc_bytes) = read_guest(&caller, desc_ptr, desc_len) else {
+                return Ok(-1);
+            };
+            let Some(data) = read_guest(&caller, data_ptr, data_len) else {
+                return Ok(-1);
+            };
+
+            let desc: ImageDesc = bytemuck::pod_read_unaligned(&desc_bytes);
+            Ok(match images.lock().unwrap().insert(desc, data) {
+                Some(id) => id as i32,
+                None => -1,
+            })
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use super::headless::{self, InputScript, ScriptInput};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// `--record-input file` writes the input of every tick as a headless input script whose ticks
+// carry the time they happened at (`<tick>@<ms> <key>`, ms since the first tick).
+// `--replay-input file` feeds such a script back to the TUI: each input at its tick, once as
+// much time has passed as when it was recorded. Headless runs take the same file as `--input`.
+//
+// With --deterministic on both runs, the replay ends in the same state as the recording.
+
+pub struct InputRecorder {
+    out: BufWriter<File>,
+    start: Instant,
+    tick: u32,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create input recording '{}'", path.display()))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "# Recorded input: <tick>@<ms> <key>. Replay with --replay-input, or headless with --input")?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+            tick: 0,
+        })
+    }
+
+    /// Notes the input the focused driver was ticked with, once per tick.
+    pub fn push(&mut self, input: &ScriptInput) -> Result<()> {
+        self.tick += 1;
+        let Some(key) = headless::format_input(input) else {
+            return Ok(());
+        };
+        writeln!(
+            self.out,
+            "{}@{} {}",
+            self.tick,
+            self.start.elapsed().as_millis(),
+            key
+        )?;
+        // A trap may end the run any moment, and the recording is what reproduces it
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub struct InputReplay {
+    script: InputScript,
+    start: Instant,
+    tick: u32,
+}
+
+impl InputReplay {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            script: InputScript::load(path)?,
+            start: Instant::now(),
+            tick: 0,
+        })
+    }
+
+    /// Whether the next tick should happen now. Ticks without input before the next recorded
+    /// input are due right away; that input once its time has come.
+    pub fn due(&self) -> bool {
+        match self.script.next_event(self.tick) {
+            Some(next) if next > self.tick + 1 => true,
+            Some(next) => self
+                .script
+                .time_at(next)
+                .is_none_or(|ms| self.start.elapsed().as_millis() >= ms as u128),
+            None => false,
+        }
+    }
+
+    /// The input for the next tick.
+    pub fn take(&mut self) -> ScriptInput {
+        self.tick += 1;
+        self.script.input_at(self.tick)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.script.next_event(self.tick).is_none()
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Looked up in the working directory when no `--keys` is given
+pub const DEFAULT_KEYMAP_PATH: &str = "keys.toml";
+
+// Rewrites keys before they reach the driver, and decides which keys the host keeps for itself.
+//
+// keys.toml:
+//   [remap]                  # pressed key = key the driver sees
+//   w = "Up"
+//   a = "Left"
+//   f = "Space"
+//   [host]                   # host command = key (unset commands keep their default)
+//   quit = "Ctrl+q"          # default Esc
+//   export = "F12"
+//   focus_next = "F10"
+//   profiler = "F11"         # show plugin call timings over the panes
+//   log = "F9"               # show plugin logs under the panes (the `pane` log sink)
+//   heap = "F8"              # show host heap usage and fragmentation over time
+//   pause = "F7"             # stop/resume ticking
+//   chat = "F6"              # type a chat line, in split play (embedder/chat.rs)
+//
+// Keys are written like in headless input scripts: a single character, `Space`, Enter, Esc,
+// Backspace, Tab, Up, Down, Left, Right, Delete or F1..F12, optionally prefixed with
+// `Ctrl+`, `Shift+` and/or `Alt+`.
+pub struct Keymap {
+    remap: HashMap<KeySpec, KeySpec>,
+    host: HashMap<KeySpec, HostAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostAction {
+    // Still forwarded to the driver, so it can react before the host exits
+    Quit,
+    Export,
+    FocusNext,
+    ToggleProfiler,
+    ToggleLog,
+    ToggleHeap,
+    // Stops and resumes ticking; failed host_asserts pause too with `--on-assert pause`
+    Pause,
+    // Opens the chat input; only in split play, elsewhere the key goes to the driver
+    Chat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeymapFile {
+    remap: HashMap<String, String>,
+    host: HostBindings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct HostBindings {
+    quit: Option<String>,
+    export: Option<String>,
+    focus_next: Option<String>,
+    profiler: Option<String>,
+    log: Option<String>,
+    heap: Option<String>,
+    pause: Option<String>,
+    chat: Option<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bind = |code| KeySpec {
+            code,
+            modifiers: KeyModifiers::NONE,
+        };
+        Self {
+            remap: HashMap::new(),
+            host: HashMap::from([
+                (bind(KeyCode::Esc), HostAction::Quit),
+                (bind(KeyCode::F(12)), HostAction::Export),
+                (bind(KeyCode::F(10)), HostAction::FocusNext),
+                (bind(KeyCode::F(11)), HostAction::ToggleProfiler),
+                (bind(KeyCode::F(9)), HostAction::ToggleLog),
+                (bind(KeyCode::F(8)), HostAction::ToggleHeap),
+                (bind(KeyCode::F(7)), HostAction::Pause),
+                (bind(KeyCode::F(6)), HostAction::Chat),
+            ]),
+        }
+    }
+}
+
+impl Keymap {
+    /// Uses `path` if given, otherwise `keys.toml` if it exists, otherwise the default bindings.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_KEYMAP_PATH).exists() => {
+                Self::load(Path::new(DEFAULT_KEYMAP_PATH))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keymap '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid keymap '{}'", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: KeymapFile = toml::from_str(text)?;
+        let mut keymap = Self::default();
+
+        for (from, to) in &file.remap {
+            keymap.remap.insert(parse_spec(from)?, parse_spec(to)?);
+        }
+
+        for (spec, action) in [
+            (&file.host.quit, HostAction::Quit),
+            (&file.host.export, HostAction::Export),
+            (&file.host.focus_next, HostAction::FocusNext),
+            (&file.host.profiler, HostAction::ToggleProfiler),
+            (&file.host.log, HostAction::ToggleLog),
+            (&file.host.heap, HostAction::ToggleHeap),
+            (&file.host.pause, HostAction::Pause),
+            (&file.host.chat, HostAction::Chat),
+        ] {
+            if let Some(spec) = spec {
+                keymap.host.retain(|_, bound| *bound != action);
+                keymap.host.insert(parse_spec(spec)?, action);
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// The host command bound to `key`, if any.
+    pub fn action(&self, key: &KeyEvent) -> Option<HostAction> {
+        self.host.get(&KeySpec::of(key)).copied()
+    }
+
+    /// `key` as the driver should see it.
+    pub fn remap(&self, key: KeyEvent) -> KeyEvent {
+        match self.remap.get(&KeySpec::of(&key)) {
+            Some(to) => KeyEvent::new(to.code, to.modifiers),
+            None => key,
+        }
+    }
+}
+
+impl KeySpec {
+    fn of(key: &KeyEvent) -> Self {
+        let mut modifiers =
+            key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::SHIFT | KeyModifiers::ALT);
+        // Shift is already part of the character ('W' vs 'w')
+        if matches!(key.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: key.code,
+            modifiers,
+        }
+    }
+}
+
+fn parse_spec(spec: &str) -> Result<KeySpec> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    loop {
+        if let Some(r) = rest.strip_prefix("Ctrl+") {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("Shift+") {
+            modifiers |= KeyModifiers::SHIFT;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("Alt+") {
+            modifiers |= KeyModifiers::ALT;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+
+    let code = match rest {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Tab" => KeyCode::Tab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Delete" => KeyCode::Delete,
+        "Space" => KeyCode::Char(' '),
+        other => match other.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => {
+                let mut chars = other.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(anyhow!("Unknown key '{}'", spec)),
+                }
+            }
+        },
+    };
+
+    let mut key = KeySpec { code, modifiers };
+    if matches!(code, KeyCode::Char(_)) {
+        key.modifiers.remove(KeyModifiers::SHIFT);
+    }
+    Ok(key)
+}
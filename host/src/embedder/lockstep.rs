@@ -0,0 +1,131 @@
+use super::driver::DriverHandle;
+use super::netplay::{self, Advance, InputExchange};
+use crate::host::host_object::BlindHost;
+use anyhow::{anyhow, Result};
+use grid_protocol::GridInput;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+
+// Lockstep netplay, with `--lockstep addr,addr,...` (netplay.rs has what all netplay shares):
+// no frame runs before every player's input for it is in, so nothing is predicted or rolled
+// back, and everyone plays at the pace of the slowest link. The input delay hides the round
+// trip; past it, frames wait. Meant for turn-based and slow-paced games, where that goes
+// unnoticed.
+//
+// Desyncs (a plugin that isn't as deterministic as it has to be) are caught by comparing
+// world hashes (BlindHost::state_hash) after every HASH_INTERVAL-th frame: each player sends
+// their latest with every packet, and one that differs from ours for the same frame ends the
+// game with an error naming the player and the frame, rather than play on in two worlds.
+
+pub const HASH_INTERVAL: u32 = 60;
+
+// Our hashes kept for reports that arrive late
+const KEPT_HASHES: usize = 8;
+
+pub struct LockstepSession {
+    exchange: InputExchange,
+    // The next frame to run
+    frame: u32,
+    // Keyboard input waiting for a frame, one frame each
+    local_queue: VecDeque<GridInput>,
+    // Our world hash after recent hashed frames
+    hashes: BTreeMap<u32, u64>,
+}
+
+impl LockstepSession {
+    /// Binds to `peers[local]` and plays with everyone else in `peers`.
+    pub fn bind(peers: &[String], local: u8, delay: u32) -> Result<Self> {
+        Self::new(InputExchange::bind(peers, local, delay)?)
+    }
+
+    /// Plays over `exchange`, e.g. one through a relay (InputExchange::relayed).
+    pub fn new(exchange: InputExchange) -> Result<Self> {
+        let (local, players) = (exchange.local(), exchange.players());
+        eprintln!(
+            "🔒 [HOST] Lockstep as player {} of {} on {}",
+            local,
+            players,
+            exchange.local_addr()?
+        );
+        Ok(Self {
+            exchange,
+            frame: 0,
+            local_queue: VecDeque::new(),
+            hashes: BTreeMap::new(),
+        })
+    }
+
+    /// Where this player listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.exchange.local_addr()
+    }
+
+    /// Points player `player` at `addr`, for peers whose port wasn't known up front.
+    pub fn set_peer(&mut self, player: u8, addr: SocketAddr) {
+        self.exchange.set_peer(player, addr);
+    }
+
+    /// The next frame to run.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Queues keyboard input for this player's next frame without one.
+    pub fn push_local(&mut self, input: GridInput) {
+        self.local_queue.push_back(input);
+    }
+
+    /// Takes in the others' input and runs the next frame on `driver` if everyone's input for
+    /// it is in. Fails on a desync. Call it once per FRAME_DELTA.
+    pub fn advance(&mut self, host: &mut BlindHost, driver: &DriverHandle) -> Result<Advance> {
+        crate::scope!("lockstep_advance");
+        netplay::check_driver(driver)?;
+        self.exchange.receive()?;
+        if !self.exchange.recorded(self.frame) {
+            let input = self.local_queue.pop_front().unwrap_or_default();
+            self.exchange.record_local(self.frame, input);
+        }
+
+        let ran = self.frame < self.exchange.confirmed();
+        if ran {
+            let inputs: Vec<GridInput> = (0..self.exchange.players())
+                .map(|player| self.exchange.input(player, self.frame).unwrap_or_default())
+                .collect();
+            netplay::run_frame(host, driver, &inputs)?;
+            if self.frame.is_multiple_of(HASH_INTERVAL) {
+                let hash = host.state_hash();
+                self.hashes.insert(self.frame, hash);
+                if self.hashes.len() > KEPT_HASHES {
+                    self.hashes.pop_first();
+                }
+                self.exchange.report_hash(self.frame, hash);
+            }
+            self.frame += 1;
+        }
+        self.check_hashes()?;
+        self.exchange.send()?;
+        self.exchange.forget(self.frame);
+        Ok(Advance {
+            ran,
+            rolled_back: 0,
+        })
+    }
+
+    fn check_hashes(&self) -> Result<()> {
+        for (player, frame, theirs) in self.exchange.reported_hashes() {
+            if let Some(&ours) = self.hashes.get(&frame) {
+                if ours != theirs {
+                    return Err(anyhow!(
+                        "Desync: player {}'s world after frame {} isn't ours (hash {:016x}, ours {:016x}); \
+                         a plugin isn't deterministic",
+                        player,
+                        frame,
+                        theirs,
+                        ours
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
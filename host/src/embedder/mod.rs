@@ -0,0 +1,29 @@
+// The Grid Embedder: everything the terminal front-end in `main.rs` layers on top of
+// the generic `BlindHost` (its own host calls, rendering helpers, ...).
+pub mod args;
+pub mod autosave;
+pub mod chat;
+pub mod compositor;
+pub mod config;
+pub mod crash;
+pub mod debug_server;
+pub mod driver;
+pub mod export;
+pub mod golden;
+pub mod headless;
+pub mod images;
+pub mod input_record;
+pub mod keymap;
+pub mod lockstep;
+pub mod narrator;
+pub mod netplay;
+pub mod players;
+pub mod record;
+pub mod recovery;
+pub mod rollback;
+pub mod simulate;
+pub mod spectate;
+pub mod split;
+pub mod theme;
+pub mod transport;
+pub mod widgets;
@@ -0,0 +1,122 @@
+use super::export::{self, FrameRef};
+use super::images::ImageStore;
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+// Past this share of changed rows a diff is harder to follow than a fresh read of the screen
+const FULL_READ_RATIO: f32 = 0.5;
+
+// Describes the grid in words for screen readers: the whole screen once, then only the rows
+// that changed. Announcements go to a file/pipe (one line each) and/or a TTS command.
+pub struct Narrator {
+    out: Option<File>,
+    // Run as `<program> <args...> <text>`, e.g. "espeak" or "spd-say -w"
+    speak: Option<Vec<String>>,
+    speaking: Option<Child>,
+    last_rows: Vec<String>,
+}
+
+impl Narrator {
+    /// `out` may be a regular file or a FIFO; opening a FIFO waits until a reader attaches.
+    pub fn new(out: Option<&Path>, speak: Option<&str>) -> Result<Self> {
+        let out = match out {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open '{}'", path.display()))?,
+            ),
+            None => None,
+        };
+        let speak = speak.map(|cmd| cmd.split_whitespace().map(String::from).collect::<Vec<_>>());
+
+        Ok(Self {
+            out,
+            speak: speak.filter(|cmd| !cmd.is_empty()),
+            speaking: None,
+            last_rows: Vec::new(),
+        })
+    }
+
+    /// Announces what changed since the previous call. Says nothing if nothing did.
+    pub fn update(&mut self, frame: &FrameRef, images: &ImageStore) -> Result<()> {
+        let rows: Vec<String> = export::to_text(frame, images)
+            .lines()
+            .map(|row| row.trim_end().to_string())
+            .collect();
+
+        let changed: Vec<usize> = (0..rows.len())
+            .filter(|&y| self.last_rows.get(y) != Some(&rows[y]))
+            .collect();
+        if changed.is_empty() && rows.len() == self.last_rows.len() {
+            return Ok(());
+        }
+
+        let full_read = self.last_rows.len() != rows.len()
+            || changed.len() as f32 > rows.len() as f32 * FULL_READ_RATIO;
+        let lines: Vec<String> = if full_read {
+            let mut lines = vec!["Screen:".to_string()];
+            lines.extend(describe_rows(&rows, 0..rows.len(), false));
+            lines
+        } else {
+            describe_rows(&rows, changed.into_iter(), true).collect()
+        };
+
+        self.last_rows = rows;
+        self.announce(&lines)
+    }
+
+    fn announce(&mut self, lines: &[String]) -> Result<()> {
+        if let Some(out) = self.out.as_mut() {
+            for line in lines {
+                writeln!(out, "{}", line)?;
+            }
+            out.flush()?;
+        }
+
+        if let Some(cmd) = &self.speak {
+            // Newer news wins: cut off whatever is still being read
+            if let Some(mut child) = self.speaking.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            let child = Command::new(&cmd[0])
+                .args(&cmd[1..])
+                .arg(lines.join(". "))
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("Failed to run '{}'", cmd[0]))?;
+            self.speaking = Some(child);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Narrator {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.speaking.take() {
+            let _ = child.wait();
+        }
+    }
+}
+
+// "Row 3: Input: a". Blank rows are skipped, or reported as "Row 3 cleared" when `cleared` is set.
+fn describe_rows<'a>(
+    rows: &'a [String],
+    which: impl Iterator<Item = usize> + 'a,
+    cleared: bool,
+) -> impl Iterator<Item = String> + 'a {
+    which.filter_map(move |y| {
+        let text = rows[y].trim();
+        if text.is_empty() {
+            return cleared.then(|| format!("Row {} cleared", y + 1));
+        }
+        Some(format!("Row {}: {}", y + 1, text))
+    })
+}
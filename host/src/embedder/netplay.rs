@@ -0,0 +1,397 @@
+use super::driver::DriverHandle;
+use super::lockstep::LockstepSession;
+use super::rollback::RollbackSession;
+use crate::host::host_object::BlindHost;
+use crate::host_calls::bus;
+use anyhow::{anyhow, Context, Result};
+use grid_protocol::{GridInput, GRID_EXT_PLAYERS};
+use relay::Room;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+// Netplay: every player runs the same plugins and only inputs go over the network, so the
+// plugins have to be deterministic (the host runs with `--deterministic`). Two ways to play:
+//
+//   --rollback addr,addr,...   never wait: predict the others' input, roll back when wrong
+//                              (rollback.rs), for action games
+//   --lockstep addr,addr,...   wait for everyone's input before each frame, and compare
+//                              world hashes now and then (lockstep.rs), for slower games
+//
+// Player ids are positions in the address list, which every player passes the same, along
+// with `--player` for their own. Local input is played `--input-delay` frames late
+// (DEFAULT_INPUT_DELAY), so a peer usually has it before the frame it's for.
+//
+// A frame is one tick of the first pane's driver per player, in id order, each with that
+// player's input tagged with their id (GRID_EXT_PLAYERS, which the driver has to take), the
+// first with FRAME_DELTA and the rest with 0. Bus messages are delivered before each frame.
+// Only keys are exchanged: pasted text stays on the machine it was pasted on.
+//
+// Inputs go over UDP, in packets carrying the sender's inputs the receiver hasn't
+// acknowledged, so a lost packet only delays them. Little-endian:
+//
+//   player u8, input delay u8   who sent it, and the delay they play with (must match ours)
+//   ack u32                     how many of the receiver's frames the sender has
+//   first u32, count u8         the frames that follow
+//   count × GridInput           12 bytes each
+//   [frame u32, hash u64]       optional: the sender's world hash after that frame
+//
+// Players who can't reach each other play through a relay instead (`--relay addr/room`, the
+// relay crate), and pass how many they are rather than their addresses: `--rollback 2`.
+// Packets then go to the relay, wrapped in its header, and come back from it as they were.
+//
+// Packets are only taken from the address listed for the player they say they're from, but
+// nothing is authenticated or encrypted: only play with peers you trust.
+
+// Frame length in seconds
+pub const FRAME_DELTA: f32 = 1.0 / 60.0;
+pub const DEFAULT_INPUT_DELAY: u32 = 2;
+
+// Inputs one packet carries at most
+const MAX_PACKET_INPUTS: usize = 64;
+const HEADER_SIZE: usize = 11;
+const INPUT_SIZE: usize = std::mem::size_of::<GridInput>();
+const HASH_SIZE: usize = 12;
+
+// What one `advance` did
+pub struct Advance {
+    // Whether a new frame ran; not while waiting on the others' input
+    pub ran: bool,
+    // Old frames re-run first, on corrected input
+    pub rolled_back: u32,
+}
+
+pub enum Session {
+    Rollback(RollbackSession),
+    Lockstep(LockstepSession),
+}
+
+impl Session {
+    /// Queues keyboard input for this player's next frame without one.
+    pub fn push_local(&mut self, input: GridInput) {
+        match self {
+            Session::Rollback(session) => session.push_local(input),
+            Session::Lockstep(session) => session.push_local(input),
+        }
+    }
+
+    /// Runs the next frame on `driver` if it can. Call it once per FRAME_DELTA.
+    pub fn advance(&mut self, host: &mut BlindHost, driver: &DriverHandle) -> Result<Advance> {
+        match self {
+            Session::Rollback(session) => session.advance(host, driver),
+            Session::Lockstep(session) => session.advance(host, driver),
+        }
+    }
+}
+
+// Input to and from the other players, by frame
+pub struct InputExchange {
+    socket: UdpSocket,
+    // Each player's address, by id; ours too, since it's what we're bound to
+    peers: Vec<SocketAddr>,
+    // The relay room everyone plays through, if they do: then every peer is the relay
+    relay: Option<Room>,
+    local: u8,
+    delay: u32,
+    // Each player's known inputs by frame; frames before `confirmed` are all known
+    inputs: Vec<BTreeMap<u32, GridInput>>,
+    confirmed: Vec<u32>,
+    // How many of our frames each player has acknowledged
+    acked: Vec<u32>,
+    // The latest world hash each player sent, and ours, as (frame, hash)
+    hashes: Vec<Option<(u32, u64)>>,
+}
+
+impl InputExchange {
+    /// Binds to `peers[local]` and plays with everyone else in `peers`.
+    pub fn bind(peers: &[String], local: u8, delay: u32) -> Result<Self> {
+        let peers: Vec<SocketAddr> = peers
+            .iter()
+            .map(|peer| {
+                peer.parse()
+                    .with_context(|| format!("'{}' is not an address (host:port)", peer))
+            })
+            .collect::<Result<_>>()?;
+        let Some(own) = peers.get(local as usize) else {
+            return Err(anyhow!(
+                "Player {} isn't one of the {} players",
+                local,
+                peers.len()
+            ));
+        };
+        let socket = UdpSocket::bind(own)
+            .with_context(|| format!("Failed to bind player {}'s address {}", local, own))?;
+        Self::new(socket, peers, local, delay, None)
+    }
+
+    /// Plays as player `local` of `players` through the relay's `room`.
+    pub fn relayed(room: &Room, players: usize, local: u8, delay: u32) -> Result<Self> {
+        let relay = room.socket_addr()?;
+        let any: SocketAddr = match relay {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(any)?;
+        Self::new(
+            socket,
+            vec![relay; players],
+            local,
+            delay,
+            Some(room.clone()),
+        )
+    }
+
+    fn new(
+        socket: UdpSocket,
+        peers: Vec<SocketAddr>,
+        local: u8,
+        delay: u32,
+        relay: Option<Room>,
+    ) -> Result<Self> {
+        if peers.len() < 2 || peers.len() > u8::MAX as usize {
+            return Err(anyhow!(
+                "Netplay needs between 2 and {} players, got {}",
+                u8::MAX,
+                peers.len()
+            ));
+        }
+        if local as usize >= peers.len() {
+            return Err(anyhow!(
+                "Player {} isn't one of the {} players",
+                local,
+                peers.len()
+            ));
+        }
+        if delay > u8::MAX as u32 {
+            return Err(anyhow!(
+                "An input delay of {} frames is over the most, {}",
+                delay,
+                u8::MAX
+            ));
+        }
+        socket.set_nonblocking(true)?;
+
+        // Nobody has input for the frames before the delay: those are empty for everyone
+        let players = peers.len();
+        let empty: BTreeMap<u32, GridInput> = (0..delay)
+            .map(|frame| (frame, GridInput::default()))
+            .collect();
+        Ok(Self {
+            socket,
+            peers,
+            relay,
+            local,
+            delay,
+            inputs: vec![empty; players],
+            confirmed: vec![delay; players],
+            acked: vec![delay; players],
+            hashes: vec![None; players],
+        })
+    }
+
+    /// Where this player listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Points player `player` at `addr`, for peers whose port wasn't known up front.
+    pub fn set_peer(&mut self, player: u8, addr: SocketAddr) {
+        self.peers[player as usize] = addr;
+    }
+
+    pub fn players(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn local(&self) -> u8 {
+        self.local
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    /// Records our input for `frame`, which is played input-delay frames later. Once per frame.
+    pub fn record_local(&mut self, frame: u32, input: GridInput) {
+        let local = self.local as usize;
+        self.inputs[local].insert(frame + self.delay, input);
+        self.confirmed[local] = frame + self.delay + 1;
+    }
+
+    /// Whether our input for `frame` was recorded yet.
+    pub fn recorded(&self, frame: u32) -> bool {
+        frame + self.delay < self.confirmed[self.local as usize]
+    }
+
+    /// Player `player`'s input for `frame`, if it has come in.
+    pub fn input(&self, player: usize, frame: u32) -> Option<GridInput> {
+        self.inputs[player].get(&frame).copied()
+    }
+
+    /// Frames everyone's input is known for, from the first.
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed.iter().copied().min().unwrap_or(0)
+    }
+
+    /// Sends our world hash after `frame` with every packet from now on.
+    pub fn report_hash(&mut self, frame: u32, hash: u64) {
+        self.hashes[self.local as usize] = Some((frame, hash));
+    }
+
+    /// The latest world hash each other player sent, as (player, frame, hash).
+    pub fn reported_hashes(&self) -> impl Iterator<Item = (usize, u32, u64)> + '_ {
+        let local = self.local as usize;
+        self.hashes
+            .iter()
+            .enumerate()
+            .filter(move |&(player, _)| player != local)
+            .filter_map(|(player, hash)| hash.map(|(frame, hash)| (player, frame, hash)))
+    }
+
+    /// Takes in every packet that has arrived.
+    pub fn receive(&mut self) -> Result<()> {
+        let mut packet = [0u8; HEADER_SIZE + MAX_PACKET_INPUTS * INPUT_SIZE + HASH_SIZE];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut packet) {
+                Ok((len, from)) => (len, from),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+                // A peer that isn't up yet: ICMP says so, and there's nothing to do but wait
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => return Err(e.into()),
+            };
+            self.take_packet(&packet[..len], from)?;
+        }
+    }
+
+    // Packets from anywhere but the player's address are dropped (relayed ones all come from the
+    // relay), and so are ones whose frames would run past u32::MAX
+    fn take_packet(&mut self, packet: &[u8], from: SocketAddr) -> Result<()> {
+        if packet.len() < HEADER_SIZE {
+            return Ok(());
+        }
+        let (player, delay) = (packet[0] as usize, packet[1] as u32);
+        let ack = u32::from_le_bytes(packet[2..6].try_into().unwrap());
+        let first = u32::from_le_bytes(packet[6..10].try_into().unwrap());
+        let inputs_end = HEADER_SIZE + packet[10] as usize * INPUT_SIZE;
+        let hashed = packet.len() == inputs_end + HASH_SIZE;
+        if player >= self.peers.len()
+            || player == self.local as usize
+            || (packet.len() != inputs_end && !hashed)
+        {
+            return Ok(());
+        }
+        if self.relay.is_none() && from != self.peers[player] {
+            return Ok(());
+        }
+        if delay != self.delay {
+            return Err(anyhow!(
+                "Player {} plays with an input delay of {}, we play with {}",
+                player,
+                delay,
+                self.delay
+            ));
+        }
+
+        for (idx, bytes) in packet[HEADER_SIZE..inputs_end]
+            .chunks_exact(INPUT_SIZE)
+            .enumerate()
+        {
+            let Some(frame) = first.checked_add(idx as u32) else {
+                return Ok(());
+            };
+            if frame >= self.confirmed[player] {
+                self.inputs[player].insert(frame, bytemuck::pod_read_unaligned(bytes));
+            }
+        }
+        self.acked[player] = self.acked[player].max(ack);
+        while self.inputs[player].contains_key(&self.confirmed[player]) {
+            self.confirmed[player] += 1;
+        }
+        if hashed {
+            let frame = u32::from_le_bytes(packet[inputs_end..inputs_end + 4].try_into().unwrap());
+            let hash = u64::from_le_bytes(packet[inputs_end + 4..].try_into().unwrap());
+            // Packets can arrive out of order
+            if self.hashes[player].is_none_or(|(latest, _)| frame >= latest) {
+                self.hashes[player] = Some((frame, hash));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends every other player the inputs of ours they haven't acknowledged.
+    pub fn send(&self) -> Result<()> {
+        let local = self.local as usize;
+        for (player, peer) in self.peers.iter().enumerate() {
+            if player == local {
+                continue;
+            }
+            let first = self.acked[player].min(self.confirmed[local]);
+            let last = self.confirmed[local].min(first + MAX_PACKET_INPUTS as u32);
+            let mut packet = vec![self.local, self.delay as u8];
+            packet.extend_from_slice(&self.confirmed[player].to_le_bytes());
+            packet.extend_from_slice(&first.to_le_bytes());
+            packet.push((last - first) as u8);
+            for frame in first..last {
+                packet.extend_from_slice(bytemuck::bytes_of(&self.inputs[local][&frame]));
+            }
+            if let Some((frame, hash)) = self.hashes[local] {
+                packet.extend_from_slice(&frame.to_le_bytes());
+                packet.extend_from_slice(&hash.to_le_bytes());
+            }
+            if let Some(room) = &self.relay {
+                packet = room.datagram(self.local, player as u8, &packet);
+            }
+            match self.socket.send_to(&packet, peer) {
+                Ok(_) => {}
+                // Lost like any other packet; the next one carries the same inputs
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::ConnectionRefused
+                    ) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the inputs of frames before `frame`, but keeps ours until everyone has them.
+    pub fn forget(&mut self, frame: u32) {
+        let local = self.local as usize;
+        let acked = self
+            .acked
+            .iter()
+            .enumerate()
+            .filter(|&(player, _)| player != local);
+        let resend = acked.map(|(_, &ack)| ack).min().unwrap_or(frame);
+        for (player, inputs) in self.inputs.iter_mut().enumerate() {
+            let keep = if player == local {
+                frame.min(resend)
+            } else {
+                frame
+            };
+            *inputs = inputs.split_off(&keep);
+        }
+    }
+}
+
+/// Fails for drivers that can't tell players apart.
+pub fn check_driver(driver: &DriverHandle) -> Result<()> {
+    if driver.extensions & GRID_EXT_PLAYERS == 0 {
+        return Err(anyhow!(
+            "Driver '{}' doesn't take GRID_EXT_PLAYERS, so it can't tell players apart",
+            driver.name
+        ));
+    }
+    Ok(())
+}
+
+/// Runs one frame: the messages sent last frame, then a tick per player with their input.
+pub fn run_frame(host: &mut BlindHost, driver: &DriverHandle, inputs: &[GridInput]) -> Result<()> {
+    // Delivered here rather than between loops, so every player's machine delivers alike
+    bus::deliver(host)?;
+    for (player, input) in inputs.iter().enumerate() {
+        let delta = if player == 0 { FRAME_DELTA } else { 0.0 };
+        driver.tick(host, &input.with_player(player as u8), delta)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use super::headless::{parse_input, ScriptInput};
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+// Remote players, served with `--players addr`: each connection is one more player, whose
+// input goes to the focused pane alongside the keyboard's, tagged with their id (see
+// GRID_EXT_PLAYERS). One line per input each way:
+//
+//   player 2                 sent on connecting: the id this connection's input carries
+//   a, Up, Ctrl+c            keys, as input scripts spell them (headless.rs)
+//   Commit:text              committed text; Preedit:text for a composition
+//   error: unknown key 'x'   the answer to a line that's none of those
+//
+// The keyboard is always player LOCAL_PLAYER; remote ids count up from 1 and aren't reused, so
+// connections after the 254th are turned away. Like the debug server, nothing is authenticated:
+// anyone who can connect can play.
+
+pub const LOCAL_PLAYER: u8 = 0;
+
+pub struct PlayerServer {
+    inputs: Receiver<(u8, ScriptInput)>,
+    addr: SocketAddr,
+}
+
+impl PlayerServer {
+    pub fn bind(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to start the player server on {}", addr))?;
+        let addr = listener.local_addr()?;
+        let (sender, inputs) = mpsc::channel();
+        let next_id = Arc::new(AtomicU8::new(LOCAL_PLAYER + 1));
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (sender, next_id) = (sender.clone(), next_id.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &next_id, sender) {
+                        eprintln!("⚠️ [PLAYERS] {:#}", e);
+                    }
+                });
+            }
+        });
+        eprintln!("🎮 [HOST] Players can join on {}", addr);
+
+        Ok(Self { inputs, addr })
+    }
+
+    /// Where the server listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The oldest input no tick has taken yet, and whose it is.
+    pub fn next(&self) -> Option<(u8, ScriptInput)> {
+        self.inputs.try_recv().ok()
+    }
+
+    /// Drops every input that's come in, for while remote play is on hold (paused, replaying).
+    pub fn discard(&self) -> usize {
+        self.inputs.try_iter().count()
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    next_id: &AtomicU8,
+    inputs: Sender<(u8, ScriptInput)>,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let Ok(player) =
+        next_id.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| id.checked_add(1))
+    else {
+        writeln!(writer, "error: no player ids left")?;
+        return Ok(());
+    };
+    writeln!(writer, "player {}", player)?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let spec = line.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        let Some(input) = parse_input(spec) else {
+            writeln!(writer, "error: unknown key '{}'", spec)?;
+            continue;
+        };
+        // The host went away
+        if inputs.send((player, input)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
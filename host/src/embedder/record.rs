@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use grid_protocol::{
+    GridInput, INPUT_KEY, KEY_BACKSPACE, KEY_DELETE, KEY_DOWN, KEY_ENTER, KEY_ESC, KEY_LEFT,
+    KEY_RIGHT, KEY_TAB, KEY_UP,
+};
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// Writes an asciinema v2 cast: a JSON header line, then one `[time, code, data]`
+// line per event ("o" = output written to the terminal, "i" = input read from it).
+pub struct CastRecorder {
+    out: BufWriter<File>,
+    start: Instant,
+    last_frame: Option<String>,
+}
+
+impl CastRecorder {
+    pub fn create(path: &Path, width: u16, height: u16) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording '{}'", path.display()))?;
+        let mut out = BufWriter::new(file);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": { "TERM": std::env::var("TERM").unwrap_or_default() },
+        });
+        writeln!(out, "{}", header)?;
+
+        Ok(Self {
+            out,
+            start: Instant::now(),
+            last_frame: None,
+        })
+    }
+
+    /// Records a rendered screen. Identical consecutive frames are skipped, since a
+    /// tick doesn't always change what's on screen.
+    pub fn frame(&mut self, screen: String) -> Result<()> {
+        if self.last_frame.as_ref() == Some(&screen) {
+            return Ok(());
+        }
+        self.event("o", &screen)?;
+        self.last_frame = Some(screen);
+        Ok(())
+    }
+
+    pub fn input(&mut self, input: &GridInput) -> Result<()> {
+        match input_text(input) {
+            Some(text) => self.event("i", &text),
+            None => Ok(()),
+        }
+    }
+
+    /// Records committed text (an IME commit or a paste) as typed input.
+    pub fn text(&mut self, text: &str) -> Result<()> {
+        self.event("i", text)
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(self.out, "{}", json!([time, code, data]))?;
+        Ok(())
+    }
+}
+
+// The bytes a terminal would have sent for this key
+fn input_text(input: &GridInput) -> Option<String> {
+    if input.input_type != INPUT_KEY {
+        return None;
+    }
+    let text = match input.key_code {
+        KEY_ENTER => "\r",
+        KEY_ESC => "\x1b",
+        KEY_BACKSPACE => "\x7f",
+        KEY_TAB => "\t",
+        KEY_UP => "\x1b[A",
+        KEY_DOWN => "\x1b[B",
+        KEY_RIGHT => "\x1b[C",
+        KEY_LEFT => "\x1b[D",
+        KEY_DELETE => "\x1b[3~",
+        code => return char::from_u32(code).map(String::from),
+    };
+    Some(text.to_string())
+}
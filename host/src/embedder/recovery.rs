@@ -0,0 +1,89 @@
+use super::compositor::Compositor;
+use super::crash::{self, InputLog};
+use crate::host::backtrace::Trapped;
+use crate::host::host_object::BlindHost;
+use crate::host::interfaces;
+use crate::host::logger::Level;
+use anyhow::{anyhow, Error, Result};
+use std::path::{Path, PathBuf};
+
+// Drivers that trap mid-tick, without ending the run. The main loop stops ticking, writes the
+// crash bundle it would have written on the way out (crash.rs), and shows the trap over the
+// panes (widgets::render_crash) until the user restarts the driver or quits. A restart is a
+// fresh instance from the driver's file, in the slot the trapped one gives back
+// (BlindHost::unload_plugin), primed like the panes are on startup.
+
+// What the crash screen shows
+pub struct Crashed {
+    // The driver whose tick trapped, and the plugin the trap was in, if another one
+    pub driver: String,
+    pub plugin: Option<String>,
+    pub message: String,
+    pub bundle: Option<PathBuf>,
+}
+
+impl Crashed {
+    /// The trap `error` from `driver`'s tick. Writes the crash bundle under `dir`.
+    pub fn new(
+        dir: &Path,
+        driver: &str,
+        error: &Error,
+        host: &BlindHost,
+        inputs: &InputLog,
+        config: &str,
+    ) -> Self {
+        let plugin = error
+            .downcast_ref::<Trapped>()
+            .map(|trapped| trapped.plugin.clone())
+            .filter(|plugin| plugin != driver);
+        let bundle = match crash::write_bundle(dir, error, host, inputs, config) {
+            Ok(bundle) => Some(bundle),
+            Err(e) => {
+                let text = format!("Failed to write a crash bundle: {:#}", e);
+                host.store
+                    .data()
+                    .logger
+                    .lock()
+                    .unwrap()
+                    .log("host", Level::Warn, &text);
+                None
+            }
+        };
+        Self {
+            driver: driver.to_string(),
+            plugin,
+            message: format!("{:#}", error),
+            bundle,
+        }
+    }
+}
+
+/// Replaces the pane's `driver` with a fresh instance from where it was loaded (`modules`).
+pub fn restart(
+    host: &mut BlindHost,
+    compositor: &mut Compositor,
+    driver: &str,
+    modules: &[(String, PathBuf)],
+    tick_rate: f32,
+) -> Result<()> {
+    let (_, path) = modules
+        .iter()
+        .find(|(name, _)| name == driver)
+        .ok_or(anyhow!("'{}' wasn't loaded from a file", driver))?;
+    // Plugins importing its exports keep the old instance, so it takes a new slot
+    if let Err(e) = host.unload_plugin(driver) {
+        let text = format!("Restarting '{}' in a new slot: {:#}", driver, e);
+        host.store
+            .data()
+            .logger
+            .lock()
+            .unwrap()
+            .log("host", Level::Warn, &text);
+    }
+    host.load_plugin_file(driver, path)?;
+    interfaces::check_exports(host, driver)?;
+    if !compositor.rebind(host, driver, tick_rate)? {
+        return Err(anyhow!("'{}' isn't a pane's driver", driver));
+    }
+    Ok(())
+}
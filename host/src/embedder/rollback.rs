@@ -0,0 +1,184 @@
+use super::driver::DriverHandle;
+use super::netplay::{self, Advance, InputExchange};
+use crate::host::host_object::BlindHost;
+use crate::host::snapshot::Snapshot;
+use anyhow::{anyhow, Result};
+use grid_protocol::GridInput;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+// Rollback netplay, with `--rollback addr,addr,...` (netplay.rs has what all netplay shares):
+// nobody waits for anyone. Frames run on the inputs known so far, with the missing ones
+// predicted; when a late input turns out different from its prediction, the world is rolled
+// back to a snapshot (snapshot.rs) from before that frame and simulated again up to now. This
+// is GGPO's model, and all a plugin has to do for it is be deterministic.
+//
+// Missing input is predicted as no input: key presses are events, and repeating the last
+// one would be wrong more often than not.
+//
+// A player can run at most MAX_PREDICTION frames ahead of the inputs they have from
+// everyone; past that they stall until the others catch up, which also keeps a faster
+// machine from running away from a slower one.
+
+pub const MAX_PREDICTION: u32 = 8;
+
+// A frame simulated with predicted input: the world before it, and the inputs it got
+struct Predicted {
+    frame: u32,
+    snapshot: Snapshot,
+    inputs: Vec<GridInput>,
+}
+
+pub struct RollbackSession {
+    exchange: InputExchange,
+    // The next frame to run
+    frame: u32,
+    // Keyboard input waiting for a frame, one frame each
+    local_queue: VecDeque<GridInput>,
+    predicted: VecDeque<Predicted>,
+    rollbacks: u64,
+}
+
+impl RollbackSession {
+    /// Binds to `peers[local]` and plays with everyone else in `peers`.
+    pub fn bind(peers: &[String], local: u8, delay: u32) -> Result<Self> {
+        Self::new(InputExchange::bind(peers, local, delay)?)
+    }
+
+    /// Plays over `exchange`, e.g. one through a relay (InputExchange::relayed).
+    pub fn new(exchange: InputExchange) -> Result<Self> {
+        if exchange.delay() > MAX_PREDICTION {
+            return Err(anyhow!(
+                "An input delay of {} frames is over the most, {}",
+                exchange.delay(),
+                MAX_PREDICTION
+            ));
+        }
+        let (local, players) = (exchange.local(), exchange.players());
+        eprintln!(
+            "🔁 [HOST] Rollback as player {} of {} on {}",
+            local,
+            players,
+            exchange.local_addr()?
+        );
+        Ok(Self {
+            exchange,
+            frame: 0,
+            local_queue: VecDeque::new(),
+            predicted: VecDeque::new(),
+            rollbacks: 0,
+        })
+    }
+
+    /// Where this player listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.exchange.local_addr()
+    }
+
+    /// Points player `player` at `addr`, for peers whose port wasn't known up front.
+    pub fn set_peer(&mut self, player: u8, addr: SocketAddr) {
+        self.exchange.set_peer(player, addr);
+    }
+
+    /// The next frame to run.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Frames re-run on corrected input so far.
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    /// Queues keyboard input for this player's next frame without one.
+    pub fn push_local(&mut self, input: GridInput) {
+        self.local_queue.push_back(input);
+    }
+
+    /// Takes in the others' input, rolls back if any of it contradicts a prediction, then runs
+    /// the next frame on `driver` unless that would predict too far ahead. Call it once per
+    /// FRAME_DELTA.
+    pub fn advance(&mut self, host: &mut BlindHost, driver: &DriverHandle) -> Result<Advance> {
+        crate::scope!("rollback_advance");
+        netplay::check_driver(driver)?;
+        self.exchange.receive()?;
+        let rolled_back = self.correct(host, driver)?;
+
+        let stalled = self.frame >= self.exchange.confirmed() + MAX_PREDICTION;
+        if !stalled {
+            let input = self.local_queue.pop_front().unwrap_or_default();
+            self.exchange.record_local(self.frame, input);
+            self.run_frame(host, driver)?;
+        }
+        self.exchange.send()?;
+        self.forget();
+        Ok(Advance {
+            ran: !stalled,
+            rolled_back,
+        })
+    }
+
+    fn run_frame(&mut self, host: &mut BlindHost, driver: &DriverHandle) -> Result<()> {
+        let known: Vec<Option<GridInput>> = (0..self.exchange.players())
+            .map(|player| self.exchange.input(player, self.frame))
+            .collect();
+        let inputs: Vec<GridInput> = known
+            .iter()
+            .map(|input| input.unwrap_or_default())
+            .collect();
+        if known.iter().any(Option::is_none) {
+            self.predicted.push_back(Predicted {
+                frame: self.frame,
+                snapshot: Snapshot::take(host)?,
+                inputs: inputs.clone(),
+            });
+        }
+        netplay::run_frame(host, driver, &inputs)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    // Re-runs from the first frame whose prediction was wrong, answering how many frames that was
+    fn correct(&mut self, host: &mut BlindHost, driver: &DriverHandle) -> Result<u32> {
+        let exchange = &self.exchange;
+        let wrong = self.predicted.iter().position(|predicted| {
+            predicted.inputs.iter().enumerate().any(|(player, used)| {
+                exchange
+                    .input(player, predicted.frame)
+                    .is_some_and(|known| bytemuck::bytes_of(&known) != bytemuck::bytes_of(used))
+            })
+        });
+        let Some(wrong) = wrong else {
+            return Ok(0);
+        };
+
+        let now = self.frame;
+        let from = self.predicted[wrong].frame;
+        self.predicted[wrong].snapshot.restore(host)?;
+        self.predicted.truncate(wrong);
+        self.frame = from;
+        while self.frame < now {
+            self.run_frame(host, driver)?;
+        }
+        self.rollbacks += (now - from) as u64;
+        Ok(now - from)
+    }
+
+    // Drops what no rollback can need anymore: predictions that turned out right, and the
+    // inputs of frames that ran on known input
+    fn forget(&mut self) {
+        let everyone = self.exchange.confirmed();
+        while self
+            .predicted
+            .front()
+            .is_some_and(|predicted| predicted.frame < everyone)
+        {
+            self.predicted.pop_front();
+        }
+        let oldest = self
+            .predicted
+            .front()
+            .map_or(self.frame, |predicted| predicted.frame);
+        self.exchange.forget(oldest);
+    }
+}
@@ -0,0 +1,128 @@
+use super::driver::DriverHandle;
+use super::headless::{self, HeadlessOptions, InputScript};
+use super::images::{self, ImageStore};
+use crate::host::host_object::{BlindHost, BlindHostConfig};
+use crate::host_calls::files::{self, FileAccess};
+use crate::host_calls::storage::{self, FileStorage};
+use anyhow::Result;
+use ecs_protocol::{CAPABILITY_EVENTS, CAPABILITY_TUI};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Deterministic headless runs for regression tests on game logic: the embedder's whole stack
+// (its host calls, scripted input, HEADLESS_DELTA ticks) under BlindHostConfig::deterministic,
+// answering with hashes of how the run ended. Same plugin, input and seed, same hashes.
+//
+//   let hash = simulate(Path::new("grid_driver.wasm"), 600, &InputScript::load(script)?)?;
+//   assert_eq!(hash.grid, 0x1c3f_...);
+//
+// From the command line: `--simulate` with --driver, --plugin, --ticks, --input and --seed
+// prints the hashes instead of starting the TUI.
+//
+// Storage and user files start out empty in a scratch directory each run, so nothing left
+// over from earlier runs (or the user's own saves) leaks in.
+
+// Scratch directories of runs still going, numbered per process
+static NEXT_RUN: AtomicU32 = AtomicU32::new(0);
+
+// How a simulation ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WorldHash {
+    // FNV-1a of the driver's final grid: its dimensions, then every cell's bytes
+    pub grid: u64,
+    // BlindHost::state_hash: all of shared memory, so every plugin's state too
+    pub state: u64,
+}
+
+impl fmt::Display for WorldHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "grid {:016x} state {:016x}", self.grid, self.state)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SimulationOptions {
+    // Loaded before the driver, as with --plugin
+    pub plugins: Vec<(String, PathBuf)>,
+    // host_random's seed
+    pub seed: u64,
+}
+
+/// Runs the driver `plugin` for `ticks` ticks of `input` and hashes how it ended.
+pub fn simulate(plugin: &Path, ticks: u32, input: &InputScript) -> Result<WorldHash> {
+    simulate_with(plugin, ticks, input, &SimulationOptions::default())
+}
+
+pub fn simulate_with(
+    plugin: &Path,
+    ticks: u32,
+    input: &InputScript,
+    options: &SimulationOptions,
+) -> Result<WorldHash> {
+    let scratch = std::env::temp_dir().join(format!(
+        "simulate-{}-{}",
+        std::process::id(),
+        NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = run(plugin, ticks, input, options, &scratch);
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+fn run(
+    plugin: &Path,
+    ticks: u32,
+    input: &InputScript,
+    options: &SimulationOptions,
+    scratch: &Path,
+) -> Result<WorldHash> {
+    let config = BlindHostConfig {
+        capabilities: CAPABILITY_TUI | CAPABILITY_EVENTS,
+        random_seed: Some(options.seed),
+        deterministic: true,
+        ..Default::default()
+    };
+    let images = Arc::new(Mutex::new(ImageStore::default()));
+    let storage = Arc::new(FileStorage::new(scratch.join("storage")));
+    let file_access = Arc::new(FileAccess::new(scratch.join("files")));
+    let mut host = BlindHost::new(config, move |linker, plugin| {
+        images::register_host_calls(linker, plugin.to_string(), images.clone())?;
+        files::register_host_calls(linker, plugin.to_string(), file_access.clone())?;
+        storage::register_host_calls(linker, plugin.to_string(), storage.clone())
+    })?;
+    host.init_heap();
+
+    for (name, path) in &options.plugins {
+        host.load_plugin_file(name, path)?;
+    }
+    let name = plugin
+        .file_stem()
+        .map_or("driver".into(), |stem| stem.to_string_lossy().into_owned());
+    host.load_plugin_file(&name, plugin)?;
+    let driver = DriverHandle::bind(&mut host, &name)?;
+
+    let headless = HeadlessOptions {
+        ticks,
+        ..Default::default()
+    };
+    headless::play(&mut host, &driver, input, &headless, |_, _| Ok(()))?;
+
+    let frame = driver.grid_view(&mut host)?;
+    let mut grid = fnv1a(FNV_OFFSET, &frame.width.to_le_bytes());
+    grid = fnv1a(grid, &frame.height.to_le_bytes());
+    grid = fnv1a(grid, bytemuck::cast_slice(frame.cells));
+    Ok(WorldHash {
+        grid,
+        state: host.state_hash(),
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
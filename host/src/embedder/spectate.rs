@@ -0,0 +1,330 @@
+use super::export::FrameRef;
+use super::images::ImageStore;
+use super::theme::Theme;
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+// Remote spectators, served with `--spectate addr`: a browser opening http://addr/ gets a small
+// viewer page, which connects back over WebSocket and is sent every frame the TUI draws.
+//
+// Server to viewer, one JSON text message each, never anything the other way:
+//
+//   {"palette": ["#rrggbb", ... 256]}          once, first: the theme's colors by ANSI index
+//   {"w": 80, "h": 24, "key": true, "runs": [[start, "text", [fg, bg, style, ...]], ...]}
+//
+// A run is consecutive cells from index `start` (row-major): their glyphs, then three numbers
+// per cell. A key frame covers every cell; the others only the cells that changed since the
+// frame before (and aren't sent if none did), which keeps a mostly-still game cheap to watch. Viewers get a key frame
+// to start with, after a resize, and after falling behind: each has a few frames of backlog
+// before the ones it can't take are dropped for a fresh key frame.
+//
+// Image anchors are sent as their fallback glyphs. Like the debug server, nothing is
+// authenticated: anyone who can connect can watch.
+
+// Frames a viewer may fall behind by before it's skipped ahead to a key frame
+const BACKLOG: usize = 8;
+
+// RFC 6455's key suffix for the handshake's Sec-WebSocket-Accept
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const VIEWER_PAGE: &str = include_str!("spectate.html");
+
+struct Viewer {
+    frames: SyncSender<Arc<str>>,
+    needs_key: bool,
+}
+
+// A cell as viewers see it
+#[derive(Clone, Copy, PartialEq)]
+struct Shown {
+    glyph: char,
+    fg: u8,
+    bg: u8,
+    style: u16,
+}
+
+pub struct SpectatorServer {
+    viewers: Arc<Mutex<Vec<Viewer>>>,
+    addr: SocketAddr,
+    // What viewers were last sent, and its width and height
+    last: Vec<Shown>,
+    size: (i32, i32),
+}
+
+impl SpectatorServer {
+    pub fn bind(addr: &str, theme: &Theme) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to start the spectator server on {}", addr))?;
+        let addr = listener.local_addr()?;
+        let viewers = Arc::new(Mutex::new(Vec::new()));
+        let palette: Vec<String> = (0..=255u8)
+            .map(|index| {
+                let (r, g, b) = theme.rgb(index);
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            })
+            .collect();
+        let palette: Arc<str> = json!({ "palette": palette }).to_string().into();
+
+        let registry = viewers.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (viewers, palette) = (registry.clone(), palette.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &viewers, &palette) {
+                        eprintln!("⚠️ [SPECTATE] {:#}", e);
+                    }
+                });
+            }
+        });
+        eprintln!("👀 [HOST] Spectators can watch at http://{}/", addr);
+
+        Ok(Self {
+            viewers,
+            addr,
+            last: Vec::new(),
+            size: (0, 0),
+        })
+    }
+
+    /// Where the server listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Viewers connected now.
+    pub fn viewers(&self) -> usize {
+        self.viewers.lock().unwrap().len()
+    }
+
+    /// Sends `frame` to every viewer: a key frame to those who need one, the cells that changed
+    /// to the rest. Never waits on a viewer.
+    pub fn broadcast(&mut self, frame: &FrameRef, images: &ImageStore) {
+        let mut viewers = self.viewers.lock().unwrap();
+        if viewers.is_empty() {
+            return;
+        }
+        let cells: Vec<Shown> = frame
+            .cells
+            .iter()
+            .map(|cell| Shown {
+                glyph: images.glyph(cell).unwrap_or(' '),
+                fg: cell.fg_color,
+                bg: cell.bg_color,
+                style: cell.style,
+            })
+            .collect();
+        let size = (frame.width, frame.height);
+        if size != self.size || cells.len() != self.last.len() {
+            viewers
+                .iter_mut()
+                .for_each(|viewer| viewer.needs_key = true);
+        }
+
+        let (mut key, mut diff) = (None, None);
+        viewers.retain_mut(|viewer| {
+            let message = if viewer.needs_key {
+                key.get_or_insert_with(|| message(size, true, &runs(&cells, None)))
+                    .clone()
+            } else {
+                let diff = diff.get_or_insert_with(|| {
+                    let runs = runs(&cells, Some(&self.last));
+                    (!runs.is_empty()).then(|| message(size, false, &runs))
+                });
+                // Nothing changed, so nothing to send
+                let Some(diff) = diff else {
+                    return true;
+                };
+                diff.clone()
+            };
+            match viewer.frames.try_send(message) {
+                Ok(()) => {
+                    viewer.needs_key = false;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    viewer.needs_key = true;
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+        self.last = cells;
+        self.size = size;
+    }
+}
+
+// Runs of the cells that differ from `last`, or of all of them
+fn runs<'a>(cells: &'a [Shown], last: Option<&[Shown]>) -> Vec<(usize, &'a [Shown])> {
+    let changed = |idx: usize| last.is_none_or(|last| last.get(idx) != Some(&cells[idx]));
+    let mut runs = Vec::new();
+    let mut idx = 0;
+    while idx < cells.len() {
+        if !changed(idx) {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < cells.len() && changed(idx) {
+            idx += 1;
+        }
+        runs.push((start, &cells[start..idx]));
+    }
+    runs
+}
+
+fn message((width, height): (i32, i32), key: bool, runs: &[(usize, &[Shown])]) -> Arc<str> {
+    let runs: Vec<_> = runs
+        .iter()
+        .map(|&(start, cells)| {
+            let text: String = cells.iter().map(|cell| cell.glyph).collect();
+            let attributes: Vec<u16> = cells
+                .iter()
+                .flat_map(|cell| [cell.fg as u16, cell.bg as u16, cell.style])
+                .collect();
+            json!([start, text, attributes])
+        })
+        .collect();
+    json!({ "w": width, "h": height, "key": key, "runs": runs })
+        .to_string()
+        .into()
+}
+
+// Serves the viewer page, or upgrades to a WebSocket and writes frames until the viewer leaves
+fn handle_connection(stream: TcpStream, viewers: &Mutex<Vec<Viewer>>, palette: &str) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let Some(key) = key else {
+        let found =
+            request_line.starts_with("GET / ") || request_line.starts_with("GET /index.html ");
+        let (status, body) = if found {
+            ("200 OK", VIEWER_PAGE)
+        } else {
+            ("404 Not Found", "Not found\n")
+        };
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        return Ok(());
+    };
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    write_text(&mut writer, palette)?;
+
+    let (frames, received): (_, Receiver<Arc<str>>) = mpsc::sync_channel(BACKLOG);
+    viewers.lock().unwrap().push(Viewer {
+        frames,
+        needs_key: true,
+    });
+    for frame in received {
+        // Gone: the failed send drops `received`, and the next broadcast forgets the viewer
+        if write_text(&mut writer, &frame).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The Sec-WebSocket-Accept answering a handshake's Sec-WebSocket-Key.
+pub fn accept_key(key: &str) -> String {
+    base64::engine::general_purpose::STANDARD
+        .encode(sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+// One unmasked, unfragmented text frame, as servers send them
+fn write_text(out: &mut impl Write, text: &str) -> std::io::Result<()> {
+    let len = text.len();
+    let mut header = vec![0x81];
+    match len {
+        0..=125 => header.push(len as u8),
+        126..=0xffff => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.write_all(&header)?;
+    out.write_all(text.as_bytes())?;
+    out.flush()
+}
+
+// SHA-1 (RFC 3174), which the WebSocket handshake needs and nothing else here does
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,732 @@
+use super::transport::{
+    self, read_frame, write_frame, Incoming, QuicEndpoint, QuicIncoming, QuicTransport,
+    TcpTransport, Transport, TransportKind, UdpTransport,
+};
+use crate::delta;
+use crate::host::host_object::BlindHost;
+use crate::host_calls::bus;
+use crate::host_calls::replicate::Replicas;
+use anyhow::{anyhow, Context, Result};
+use bus_protocol::chat::ChatLine;
+use bus_protocol::lobby::LOBBY;
+use bus_protocol::Envelope;
+use relay::Room;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Split play: the game's core plugins run on one headless server (`--serve addr`, with only
+// `--plugin`s), and each player's machine runs the client plugins that draw it (`--connect
+// addr`, with its `--driver`s), the way tasksapp-tui draws what tasksapp-core keeps. The host
+// carries two things between them:
+//
+//   - state: what the server's plugins designate with host_replicate (host_calls/replicate.rs)
+//     goes to every client after each frame it changed in, into the client plugins' own copy
+//   - messages: a client plugin's bus_send to a plugin loaded on the server, or to its lobby
+//     (bus_protocol::lobby), is delivered there, sent by `name@client`; what the server sends
+//     to that name goes back to the client's `name`
+//
+// A server frame delivers bus messages, calls every plugin's `plugin_update` (what ecs-client's
+// register_plugin! exports), then sends what changed. Clients tick at the same rate.
+//
+// Frames go over host.toml's [net] transport (transport.rs), state on its `latest` channel:
+//
+//   UDP      client to server, before its hello: the port it takes datagrams on u16, for the
+//            "udp" transport; a "tcp" server sends it everything over TCP anyway
+//
+// A "udp" or "quic" server still takes TCP clients, who get everything over TCP. "quic" clients
+// only reach "quic" servers.
+//
+//   HELLO    both ways, first: the plugin names loaded at that end, one per line
+//   STATE    server to client: name (u16 len + UTF-8), then
+//              KEY    u8 0, key u32, the state's bytes
+//              DELTA  u8 1, key u32, what changed since that key frame (delta.rs)
+//   MESSAGE  both ways: to (u16 len + UTF-8), then an encoded bus Envelope
+//   CHAT     both ways: a chat line (bus_protocol::chat), which the server shows and passes on
+//            to every other client
+//
+// Each client gets a key frame of a state first, then deltas against it, and a new key frame
+// once KEYFRAME_FRAMES have gone by or a delta would be over half the state. Deltas are against
+// the key frame rather than the last delta, so a lost one costs nothing; a client that lost the
+// key frame drops the deltas for it until the next one.
+//
+// A server can also take clients through a relay (`--relay addr/room`, the relay crate), for
+// players who can't reach it directly: clients go there when connecting to it fails. Those
+// get everything over TCP, since the relay only pipes their connection.
+//
+// Client ids count up from 1. Like the debug server, nothing is authenticated: anyone who can
+// connect can play.
+
+const KIND_HELLO: u8 = 0;
+const KIND_STATE: u8 = 1;
+const KIND_MESSAGE: u8 = 2;
+const KIND_UDP: u8 = 3;
+const KIND_CHAT: u8 = 4;
+
+const STATE_KEY: u8 = 0;
+const STATE_DELTA: u8 = 1;
+
+// The most frames between two key frames of a state
+const KEYFRAME_FRAMES: u64 = 60;
+
+// How often clients on unreliable transports get unchanged state again, in frames
+const RESEND_FRAMES: u64 = 30;
+
+// A client that doesn't take a frame in this long is dropped rather than stall the server
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum Event {
+    Joined(u32, Box<dyn Transport>, Vec<String>),
+    Frame(u32, u8, Vec<u8>),
+    Left(u32),
+}
+
+struct Client {
+    transport: Box<dyn Transport>,
+    plugins: Vec<String>,
+    // The key frame each state's deltas go against
+    keys: BTreeMap<String, Keyframe>,
+}
+
+struct Keyframe {
+    key: u32,
+    bytes: Vec<u8>,
+    frame: u64,
+}
+
+pub struct SplitServer {
+    events: Receiver<Event>,
+    addr: SocketAddr,
+    clients: BTreeMap<u32, Client>,
+    replicas: Arc<Replicas>,
+    // The state every client has, as of the last frame
+    sent: BTreeMap<String, Vec<u8>>,
+    frame: u64,
+    next_key: u32,
+    accept: Acceptor,
+    // Chat lines from clients for the others, by who sent them
+    chat: Vec<(u32, ChatLine)>,
+}
+
+// What takes a client's connection in, from the listener or a relay
+#[derive(Clone)]
+struct Acceptor {
+    hello: Vec<u8>,
+    events: Sender<Event>,
+    next_id: Arc<AtomicU32>,
+    // Where state datagrams go out from, with the "udp" transport
+    udp: Option<Arc<UdpSocket>>,
+}
+
+impl SplitServer {
+    /// Serves the plugins loaded in `host` to clients connecting on `addr`.
+    pub fn bind(
+        addr: &str,
+        host: &BlindHost,
+        replicas: Arc<Replicas>,
+        transport: TransportKind,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to start the split server on {}", addr))?;
+        let addr = listener.local_addr()?;
+        let udp = match transport {
+            TransportKind::Udp => {
+                let socket = UdpSocket::bind(addr)
+                    .with_context(|| format!("Failed to bind UDP {}", addr))?;
+                socket.set_nonblocking(true)?;
+                Some(Arc::new(socket))
+            }
+            _ => None,
+        };
+        let (sender, events) = mpsc::channel();
+        let accept = Acceptor {
+            // Clients use the server's lobby rather than their own
+            hello: hello(host, &[LOBBY]),
+            events: sender,
+            next_id: Arc::new(AtomicU32::new(1)),
+            udp,
+        };
+
+        let listening = accept.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                listening.spawn(move |id, accept| handle_client(stream, id, true, accept));
+            }
+        });
+        if transport == TransportKind::Quic {
+            let endpoint = QuicEndpoint::bind(addr)?;
+            let listening = accept.clone();
+            std::thread::spawn(move || {
+                while let Some(incoming) = endpoint.accept() {
+                    listening.spawn(move |id, accept| handle_quic_client(incoming, id, accept));
+                }
+            });
+        }
+        eprintln!("🛰️ [HOST] Split server on {} ({:?})", addr, transport);
+
+        Ok(Self {
+            events,
+            addr,
+            clients: BTreeMap::new(),
+            replicas,
+            sent: BTreeMap::new(),
+            frame: 0,
+            next_key: 0,
+            accept,
+            chat: Vec::new(),
+        })
+    }
+
+    /// Takes clients through the relay's `room` too, for as long as the relay is up.
+    pub fn serve_relay(&self, room: &Room) -> Result<()> {
+        let mut announcer = room.host()?;
+        eprintln!(
+            "🛰️ [HOST] Split server in room '{}' on the relay at {}",
+            room.name, room.addr
+        );
+        let (room, accept) = (room.clone(), self.accept.clone());
+        std::thread::spawn(move || loop {
+            let client = match relay::next_client(&mut announcer) {
+                Ok(client) => client,
+                Err(e) => return eprintln!("⚠️ [SPLIT] Relay {}: {:#}", room.addr, e),
+            };
+            match room.accept(client) {
+                Ok(stream) => {
+                    accept.spawn(move |id, accept| handle_client(stream, id, false, accept))
+                }
+                Err(e) => eprintln!("⚠️ [SPLIT] Relay {}: {:#}", room.addr, e),
+            }
+        });
+        Ok(())
+    }
+
+    /// Where the server listens, with the port resolved if it was bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Runs one server frame: takes in clients and their messages, delivers the bus, updates
+    /// the plugins, and sends clients their messages and the state that changed.
+    pub fn step(&mut self, host: &mut BlindHost) -> Result<()> {
+        crate::scope!("split_step");
+        self.receive(host)?;
+        run_frame(host)?;
+        self.publish(host)
+    }
+
+    fn receive(&mut self, host: &mut BlindHost) -> Result<()> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Joined(id, transport, plugins) => {
+                    let mut client = Client {
+                        transport,
+                        plugins,
+                        keys: BTreeMap::new(),
+                    };
+                    // A client joining late starts from the state everyone else has
+                    let caught_up = self.sent.iter().all(|(name, bytes)| {
+                        client
+                            .send_state(name, bytes, self.frame, &mut self.next_key)
+                            .is_ok()
+                    });
+                    if !caught_up {
+                        client.transport.shutdown();
+                        continue;
+                    }
+                    let mut bus = host.store.data().bus.lock().unwrap();
+                    for plugin in &client.plugins {
+                        bus.add_remote(format!("{}@{}", plugin, id));
+                    }
+                    eprintln!(
+                        "🛰️ [SPLIT] Client {} joined with {}",
+                        id,
+                        client.plugins.join(", ")
+                    );
+                    self.clients.insert(id, client);
+                }
+                Event::Frame(id, KIND_MESSAGE, payload) => {
+                    let Some((to, mut envelope)) = message(&payload) else {
+                        continue;
+                    };
+                    if !host.store.data().instances.contains_key(&to) && to != LOBBY {
+                        eprintln!(
+                            "⚠️ [SPLIT] Client {} sent to '{}', which isn't loaded here",
+                            id, to
+                        );
+                        continue;
+                    }
+                    envelope.sender = format!("{}@{}", envelope.sender, id);
+                    envelope.reply_to = envelope
+                        .reply_to
+                        .map(|reply_to| format!("{}@{}", reply_to, id));
+                    host.store.data().bus.lock().unwrap().push(to, envelope);
+                }
+                Event::Frame(id, KIND_CHAT, payload) => {
+                    let Some(line) = ChatLine::decode(&payload) else {
+                        continue;
+                    };
+                    eprintln!("💬 [SPLIT] {}: {}", line.from, line.text);
+                    host.store.data().chat.lock().unwrap().receive(line.clone());
+                    self.chat.push((id, line));
+                }
+                Event::Frame(..) => {}
+                Event::Left(id) => {
+                    if self.clients.contains_key(&id) {
+                        self.drop_client(host, id);
+                        eprintln!("🛰️ [SPLIT] Client {} left", id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self, host: &mut BlindHost) -> Result<()> {
+        let mut gone = Vec::new();
+        let messages = host.store.data().bus.lock().unwrap().take_remote();
+        for (to, envelope) in messages {
+            let Some((name, id)) = to
+                .rsplit_once('@')
+                .and_then(|(name, id)| Some((name, id.parse().ok()?)))
+            else {
+                continue;
+            };
+            if let Some(client) = self.clients.get_mut(&id) {
+                if client
+                    .transport
+                    .send_reliable(KIND_MESSAGE, &named(name, &envelope.encode()))
+                    .is_err()
+                {
+                    gone.push(id);
+                }
+            }
+        }
+
+        // Lines posted here come from no client, so everyone gets them
+        let ours = host.store.data().chat.lock().unwrap().take_outgoing();
+        let lines: Vec<(u32, ChatLine)> = self
+            .chat
+            .drain(..)
+            .chain(ours.into_iter().map(|line| (0, line)))
+            .collect();
+        for (from, line) in lines {
+            let payload = line.encode();
+            for (&id, client) in self.clients.iter_mut().filter(|(&id, _)| id != from) {
+                if client.transport.send_reliable(KIND_CHAT, &payload).is_err() {
+                    gone.push(id);
+                }
+            }
+        }
+
+        // Unreliable transports lose some state, so now and then it all goes out again
+        self.frame += 1;
+        let resend = self.frame.is_multiple_of(RESEND_FRAMES);
+        let state = self.replicas.read(host)?;
+        for (name, bytes) in &state {
+            let changed = self.sent.get(name) != Some(bytes);
+            for (&id, client) in &mut self.clients {
+                let lossy_resend = resend && !client.transport.reliable();
+                if !changed && !lossy_resend {
+                    continue;
+                }
+                if client
+                    .send_state(name, bytes, self.frame, &mut self.next_key)
+                    .is_err()
+                {
+                    gone.push(id);
+                }
+            }
+        }
+        self.sent = state;
+
+        for id in gone {
+            if self.clients.contains_key(&id) {
+                self.drop_client(host, id);
+                eprintln!("⚠️ [SPLIT] Client {} stopped taking frames, dropped", id);
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_client(&mut self, host: &BlindHost, id: u32) {
+        let Some(client) = self.clients.remove(&id) else {
+            return;
+        };
+        client.transport.shutdown();
+        let mut bus = host.store.data().bus.lock().unwrap();
+        for plugin in &client.plugins {
+            bus.remove_remote(&format!("{}@{}", plugin, id));
+        }
+    }
+}
+
+impl Client {
+    // A delta against the client's key frame of the state, or a new key frame
+    fn send_state(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+        frame: u64,
+        next_key: &mut u32,
+    ) -> Result<()> {
+        let delta = self
+            .keys
+            .get(name)
+            .filter(|keyframe| frame - keyframe.frame < KEYFRAME_FRAMES)
+            .and_then(|keyframe| Some((keyframe.key, delta::encode(&keyframe.bytes, bytes)?)))
+            .filter(|(_, delta)| delta.len() <= bytes.len() / 2);
+        let mut payload = named(name, &[]);
+        match delta {
+            Some((key, delta)) => {
+                payload.push(STATE_DELTA);
+                payload.extend_from_slice(&key.to_le_bytes());
+                payload.extend_from_slice(&delta);
+            }
+            None => {
+                *next_key = next_key.wrapping_add(1);
+                payload.push(STATE_KEY);
+                payload.extend_from_slice(&next_key.to_le_bytes());
+                payload.extend_from_slice(bytes);
+                let keyframe = Keyframe {
+                    key: *next_key,
+                    bytes: bytes.to_vec(),
+                    frame,
+                };
+                self.keys.insert(name.to_string(), keyframe);
+            }
+        }
+        self.transport.send_latest(name, KIND_STATE, &payload)
+    }
+}
+
+impl Acceptor {
+    // A client on its own thread, from `handle` to when it leaves
+    fn spawn(&self, handle: impl FnOnce(u32, &Acceptor) -> Result<()> + Send + 'static) {
+        let accept = self.clone();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        std::thread::spawn(move || {
+            if let Err(e) = handle(id, &accept) {
+                eprintln!("⚠️ [SPLIT] Client {}: {:#}", id, e);
+            }
+            let _ = accept.events.send(Event::Left(id));
+        });
+    }
+}
+
+// Datagrams can't reach a client that isn't `direct`
+fn handle_client(mut stream: TcpStream, id: u32, direct: bool, accept: &Acceptor) -> Result<()> {
+    write_frame(&mut stream, KIND_HELLO, &accept.hello)?;
+    let (plugins, udp_port) = read_hello(&mut stream, direct)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let transport: Box<dyn Transport> = match (&accept.udp, udp_port) {
+        (Some(socket), Some(port)) => {
+            let peer = SocketAddr::new(stream.peer_addr()?.ip(), port);
+            Box::new(UdpTransport::new(stream.try_clone()?, socket.clone(), peer))
+        }
+        _ => Box::new(TcpTransport::new(stream.try_clone()?)),
+    };
+    take_frames(stream, id, plugins, transport, &accept.events)
+}
+
+fn handle_quic_client(incoming: QuicIncoming, id: u32, accept: &Acceptor) -> Result<()> {
+    let (mut transport, mut stream) = incoming.establish()?;
+    transport.set_write_timeout(WRITE_TIMEOUT);
+    transport.send_reliable(KIND_HELLO, &accept.hello)?;
+    let (plugins, _) = read_hello(&mut stream, false)?;
+    take_frames(stream, id, plugins, Box::new(transport), &accept.events)
+}
+
+// The plugins in a client's hello, and the port it takes datagrams on if it sent one and may
+fn read_hello(stream: &mut impl Read, datagrams: bool) -> Result<(Vec<String>, Option<u16>)> {
+    let mut udp_port = None;
+    let payload = loop {
+        match read_frame(stream)? {
+            (KIND_HELLO, payload) => break payload,
+            (KIND_UDP, port) if port.len() == 2 && datagrams => {
+                udp_port = Some(u16::from_le_bytes([port[0], port[1]]))
+            }
+            (KIND_UDP, _) => {}
+            (kind, _) => return Err(anyhow!("Expected a hello, got a frame of kind {}", kind)),
+        }
+    };
+    Ok((
+        String::from_utf8_lossy(&payload)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        udp_port,
+    ))
+}
+
+// Joins the client, then hands the server its frames until it's gone
+fn take_frames(
+    mut stream: impl Read,
+    id: u32,
+    plugins: Vec<String>,
+    transport: Box<dyn Transport>,
+    events: &Sender<Event>,
+) -> Result<()> {
+    if events.send(Event::Joined(id, transport, plugins)).is_err() {
+        return Ok(());
+    }
+    loop {
+        let (kind, payload) = read_frame(&mut stream)?;
+        if events.send(Event::Frame(id, kind, payload)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+pub struct SplitClient {
+    transport: Box<dyn Transport>,
+    // What the server sent, oldest first; None once it's gone
+    frames: Receiver<Incoming>,
+    replicas: Arc<Replicas>,
+    server_plugins: Vec<String>,
+    // The latest key frame of each state, and its key
+    keys: BTreeMap<String, (u32, Vec<u8>)>,
+}
+
+impl SplitClient {
+    /// Connects the plugins loaded in `host` to the split server at `addr`.
+    pub fn connect(
+        addr: &str,
+        host: &BlindHost,
+        replicas: Arc<Replicas>,
+        transport: TransportKind,
+    ) -> Result<Self> {
+        let failed = || format!("Failed to connect to the split server at {}", addr);
+        let server = format!("at {}", addr);
+        let (sender, frames) = mpsc::channel();
+        if transport == TransportKind::Quic {
+            let resolved = addr
+                .to_socket_addrs()
+                .with_context(failed)?
+                .next()
+                .ok_or(anyhow!("'{}' has no address", addr))?;
+            let (quic, stream) = QuicTransport::connect(resolved).with_context(failed)?;
+            quic.spawn_datagram_reader(sender.clone());
+            return Self::start(
+                stream,
+                Box::new(quic),
+                &server,
+                host,
+                replicas,
+                (sender, frames),
+            );
+        }
+
+        let stream = TcpStream::connect(addr).with_context(failed)?;
+        let mut tcp = TcpTransport::new(stream.try_clone()?);
+        if transport == TransportKind::Udp {
+            let socket = UdpSocket::bind((stream.local_addr()?.ip(), 0))?;
+            tcp.send_reliable(KIND_UDP, &socket.local_addr()?.port().to_le_bytes())?;
+            transport::spawn_udp_reader(socket, sender.clone());
+        }
+        Self::start(
+            stream,
+            Box::new(tcp),
+            &server,
+            host,
+            replicas,
+            (sender, frames),
+        )
+    }
+
+    /// Connects to the split server in the relay's `room`. Everything goes over TCP.
+    pub fn join_relay(room: &Room, host: &BlindHost, replicas: Arc<Replicas>) -> Result<Self> {
+        let stream = room.join()?;
+        let server = format!("in room '{}' on the relay at {}", room.name, room.addr);
+        let tcp = Box::new(TcpTransport::new(stream.try_clone()?));
+        Self::start(stream, tcp, &server, host, replicas, mpsc::channel())
+    }
+
+    // Over `transport`, reading what comes back from `stream`; `server` names it for logs,
+    // e.g. "at addr"
+    fn start(
+        mut stream: impl Read + Send + 'static,
+        mut transport: Box<dyn Transport>,
+        server: &str,
+        host: &BlindHost,
+        replicas: Arc<Replicas>,
+        (sender, frames): (Sender<Incoming>, Receiver<Incoming>),
+    ) -> Result<Self> {
+        transport.send_reliable(KIND_HELLO, &hello(host, &[]))?;
+        let (kind, payload) = read_frame(&mut stream)?;
+        if kind != KIND_HELLO {
+            return Err(anyhow!(
+                "The server {} answered with a frame of kind {}, it isn't a split server",
+                server,
+                kind
+            ));
+        }
+        // Plugins loaded here too are sent to here
+        let server_plugins: Vec<String> = String::from_utf8_lossy(&payload)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        {
+            let state = host.store.data();
+            let mut bus = state.bus.lock().unwrap();
+            for plugin in server_plugins
+                .iter()
+                .filter(|plugin| !state.instances.contains_key(*plugin))
+            {
+                bus.add_remote(plugin.clone());
+            }
+        }
+
+        transport::spawn_reader(stream, sender);
+        eprintln!(
+            "🛰️ [HOST] Connected to the split server {} ({})",
+            server,
+            server_plugins.join(", ")
+        );
+
+        Ok(Self {
+            transport,
+            frames,
+            replicas,
+            server_plugins,
+            keys: BTreeMap::new(),
+        })
+    }
+
+    /// The plugins loaded on the server, and its lobby.
+    pub fn server_plugins(&self) -> &[String] {
+        &self.server_plugins
+    }
+
+    /// Sends the server the messages for its plugins and our chat lines, then takes in what it
+    /// sent: its messages go on the bus, its state into the plugins' copies, its chat lines into
+    /// the host's chat. Answers the states written. Fails
+    /// once the server is gone. Call it once per frame, before delivering the bus.
+    pub fn exchange(&mut self, host: &mut BlindHost) -> Result<Vec<String>> {
+        crate::scope!("split_exchange");
+        let messages = host.store.data().bus.lock().unwrap().take_remote();
+        for (to, envelope) in messages {
+            self.transport
+                .send_reliable(KIND_MESSAGE, &named(&to, &envelope.encode()))
+                .context("Lost the split server")?;
+        }
+        let lines = host.store.data().chat.lock().unwrap().take_outgoing();
+        for line in lines {
+            self.transport
+                .send_reliable(KIND_CHAT, &line.encode())
+                .context("Lost the split server")?;
+        }
+
+        while let Ok(frame) = self.frames.try_recv() {
+            match frame {
+                Some((KIND_STATE, payload)) => {
+                    if let Some((name, bytes)) = self.take_state(&payload) {
+                        self.replicas.receive(name, bytes);
+                    }
+                }
+                Some((KIND_MESSAGE, payload)) => {
+                    let Some((to, envelope)) = message(&payload) else {
+                        continue;
+                    };
+                    if !host.store.data().instances.contains_key(&to) {
+                        eprintln!(
+                            "⚠️ [SPLIT] The server sent to '{}', which isn't loaded here",
+                            to
+                        );
+                        continue;
+                    }
+                    host.store.data().bus.lock().unwrap().push(to, envelope);
+                }
+                Some((KIND_CHAT, payload)) => {
+                    if let Some(line) = ChatLine::decode(&payload) {
+                        host.store.data().chat.lock().unwrap().receive(line);
+                    }
+                }
+                Some(_) => {}
+                None => return Err(anyhow!("Lost the split server")),
+            }
+        }
+        self.replicas.apply(host)
+    }
+}
+
+impl SplitClient {
+    // The state a STATE frame brings, if the key frame it needs is here
+    fn take_state(&mut self, payload: &[u8]) -> Option<(String, Vec<u8>)> {
+        let (name, rest) = split_named(payload)?;
+        let key = u32::from_le_bytes(rest.get(1..5)?.try_into().ok()?);
+        let bytes = match *rest.first()? {
+            STATE_KEY => {
+                let bytes = rest[5..].to_vec();
+                self.keys.insert(name.clone(), (key, bytes.clone()));
+                bytes
+            }
+            STATE_DELTA => {
+                let (base_key, base) = self.keys.get(&name)?;
+                if *base_key != key {
+                    return None;
+                }
+                delta::decode(base, &rest[5..])?
+            }
+            _ => return None,
+        };
+        Some((name, bytes))
+    }
+}
+
+/// One server frame's plugin work: the bus, then every `plugin_update`, in name order.
+pub fn run_frame(host: &mut BlindHost) -> Result<()> {
+    bus::deliver(host)?;
+    let mut names: Vec<String> = host.store.data().instances.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        let Ok(func) = host.get_func(&name, "plugin_update") else {
+            continue;
+        };
+        host.profiled(&name, "plugin_update", |store| {
+            func.call(store, &[], &mut [])
+        })
+        .with_context(|| format!("'{}' failed to update", name))?;
+    }
+    Ok(())
+}
+
+// The plugins loaded in `host`, and `also`
+fn hello(host: &BlindHost, also: &[&str]) -> Vec<u8> {
+    let mut names: Vec<&str> = host
+        .store
+        .data()
+        .instances
+        .keys()
+        .map(String::as_str)
+        .collect();
+    names.extend_from_slice(also);
+    names.sort();
+    names.join("\n").into_bytes()
+}
+
+fn message(payload: &[u8]) -> Option<(String, Envelope)> {
+    let (to, bytes) = split_named(payload)?;
+    Some((to, Envelope::decode(bytes)?))
+}
+
+fn named(name: &str, rest: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(2 + name.len() + rest.len());
+    payload.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    payload.extend_from_slice(name.as_bytes());
+    payload.extend_from_slice(rest);
+    payload
+}
+
+fn split_named(payload: &[u8]) -> Option<(String, &[u8])> {
+    let len = u16::from_le_bytes(payload.get(..2)?.try_into().ok()?) as usize;
+    let name = String::from_utf8(payload.get(2..2 + len)?.to_vec()).ok()?;
+    Some((name, &payload[2 + len..]))
+}
@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Looked up in the working directory when no `--theme` is given
+pub const DEFAULT_THEME_PATH: &str = "theme.toml";
+
+// Remaps the ANSI 256 indices drivers write into `GridCell`s. Applied only at render
+// time, so plugins never know a theme is active.
+//
+// theme.toml:
+//   preset = "colorblind"    # optional base palette ("default" or "colorblind")
+//   foreground = "#d0d0d0"   # optional, paints the screen around the grid
+//   background = 235
+//   [colors]
+//   196 = "#d55e00"          # hex, or another palette index
+#[derive(Clone)]
+pub struct Theme {
+    palette: [Color; 256],
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ThemeFile {
+    preset: Option<String>,
+    foreground: Option<ColorSpec>,
+    background: Option<ColorSpec>,
+    colors: HashMap<String, ColorSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorSpec {
+    Index(u8),
+    Hex(String),
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            palette: std::array::from_fn(|i| Color::Indexed(i as u8)),
+            foreground: None,
+            background: None,
+        }
+    }
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "colorblind" => Some(Self::colorblind()),
+            _ => None,
+        }
+    }
+
+    /// Uses `path` if given, otherwise `theme.toml` if it exists, otherwise the identity palette.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_THEME_PATH).exists() => {
+                Self::load(Path::new(DEFAULT_THEME_PATH))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid theme '{}'", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(text)?;
+
+        let mut theme = match file.preset.as_deref() {
+            Some(name) => Self::preset(name).ok_or(anyhow!("Unknown preset '{}'", name))?,
+            None => Self::default(),
+        };
+
+        for (index, spec) in &file.colors {
+            let index: u8 = index
+                .parse()
+                .map_err(|_| anyhow!("Color key '{}' is not an index in 0..=255", index))?;
+            theme.palette[index as usize] = spec.to_color()?;
+        }
+        if let Some(spec) = &file.foreground {
+            theme.foreground = Some(spec.to_color()?);
+        }
+        if let Some(spec) = &file.background {
+            theme.background = Some(spec.to_color()?);
+        }
+        Ok(theme)
+    }
+
+    pub fn color(&self, index: u8) -> Color {
+        self.palette[index as usize]
+    }
+
+    /// Same as `color`, resolved to RGB with the stock xterm palette where needed.
+    pub fn rgb(&self, index: u8) -> (u8, u8, u8) {
+        match self.color(index) {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i) => xterm_rgb(i),
+            _ => xterm_rgb(index),
+        }
+    }
+
+    // Okabe-Ito colors for the 16 ANSI slots, and the color cube pushed from a
+    // red/green axis onto an orange/blue one so e.g. 196 and 46 stay apart.
+    fn colorblind() -> Self {
+        const ANSI: [(u8, u8, u8); 16] = [
+            (0x00, 0x00, 0x00), // black
+            (0xD5, 0x5E, 0x00), // red -> vermillion
+            (0x00, 0x9E, 0x73), // green -> bluish green
+            (0xF0, 0xE4, 0x42), // yellow
+            (0x00, 0x72, 0xB2), // blue
+            (0xCC, 0x79, 0xA7), // magenta -> reddish purple
+            (0x56, 0xB4, 0xE9), // cyan -> sky blue
+            (0xC0, 0xC0, 0xC0), // white
+            (0x60, 0x60, 0x60), // bright black
+            (0xE6, 0x9F, 0x00), // bright red -> orange
+            (0x35, 0xD0, 0xA8), // bright green
+            (0xFF, 0xF0, 0x80), // bright yellow
+            (0x3C, 0x9C, 0xE0), // bright blue
+            (0xE8, 0xA8, 0xCC), // bright magenta
+            (0x9A, 0xD8, 0xF8), // bright cyan
+            (0xFF, 0xFF, 0xFF), // bright white
+        ];
+
+        let mut theme = Self::default();
+        for (i, &(r, g, b)) in ANSI.iter().enumerate() {
+            theme.palette[i] = Color::Rgb(r, g, b);
+        }
+
+        const CUBE_LEVELS: [u16; 6] = [0, 95, 135, 175, 215, 255];
+        for i in 0..216 {
+            let r = CUBE_LEVELS[i / 36];
+            let g = CUBE_LEVELS[i / 6 % 6];
+            let b = CUBE_LEVELS[i % 6];
+            let new_g = (r + g) / 2;
+            let new_b = b.max(g - r.min(g));
+            theme.palette[16 + i] = Color::Rgb(r as u8, new_g as u8, new_b as u8);
+        }
+        // 232..=255 is the grayscale ramp, which needs no remapping
+        theme
+    }
+}
+
+impl ColorSpec {
+    fn to_color(&self) -> Result<Color> {
+        match self {
+            ColorSpec::Index(i) => Ok(Color::Indexed(*i)),
+            ColorSpec::Hex(hex) => {
+                let digits = hex.strip_prefix('#').unwrap_or(hex);
+                if digits.len() != 6 {
+                    return Err(anyhow!("Color '{}' is not #rrggbb", hex));
+                }
+                let value = u32::from_str_radix(digits, 16)
+                    .map_err(|_| anyhow!("Color '{}' is not #rrggbb", hex))?;
+                Ok(Color::Rgb(
+                    (value >> 16) as u8,
+                    (value >> 8) as u8,
+                    value as u8,
+                ))
+            }
+        }
+    }
+}
+
+pub fn xterm_rgb(index: u8) -> (u8, u8, u8) {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match index {
+        0..=15 => ANSI[index as usize],
+        16..=231 => {
+            let i = (index - 16) as usize;
+            (
+                CUBE_LEVELS[i / 36],
+                CUBE_LEVELS[i / 6 % 6],
+                CUBE_LEVELS[i % 6],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use anyhow::{anyhow, Context, Result};
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use quinn::rustls::crypto::{self, CryptoProvider};
+use quinn::rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use quinn::rustls::{self, DigitallySignedStruct, SignatureScheme};
+use quinn::{
+    ClientConfig, Connection, Endpoint, IdleTimeout, RecvStream, SendDatagramError, SendStream,
+    ServerConfig, TransportConfig,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+// How split play's server and clients talk (split.rs), picked with host.toml's
+//
+//   [net]
+//   transport = "tcp"      # or "udp", "quic"
+//
+// Either way there are two kinds of channel:
+//
+//   reliable   everything arrives, in order: the handshake, bus messages
+//   latest     for state the next send replaces: a send may be lost, but never arrives after
+//              a newer one for the same key
+//
+// "tcp" (the default) carries both on the one connection, so a lost packet holds up everything
+// behind it. "udp" sends `latest` frames as datagrams instead, so state keeps flowing past a
+// lost one; the server then sends unchanged state again now and then to make up for losses.
+// Datagrams carry seq u32, key (u16 len + UTF-8), then the frame; frames that wouldn't fit in
+// MAX_DATAGRAM go over the reliable channel.
+//
+// "quic" carries both on one QUIC connection (quinn), on the server's port: reliable frames on
+// a stream the client opens, `latest` ones as QUIC datagrams laid out like udp's, so state
+// keeps flowing past losses without a second socket. QUIC won't go without TLS, so the server
+// makes up a certificate when it starts and clients take whatever one they're shown: this
+// encrypts, but like the rest of split play it authenticates nothing.
+//
+// Frames are kind u8, len u32 (little-endian), then len bytes.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    #[default]
+    Tcp,
+    Udp,
+    Quic,
+}
+
+// What fits in one packet on about any path, so datagrams are never fragmented
+pub const MAX_DATAGRAM: usize = 1200;
+
+// Frames past this are a broken or hostile peer, not state
+const MAX_FRAME_LEN: usize = 16 << 20;
+
+// QUIC peers that go quiet for this long are gone; keep-alives go out well before
+const QUIC_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const QUIC_KEEP_ALIVE: Duration = Duration::from_secs(2);
+
+// What the server's made-up certificate is for, and what clients ask for
+const QUIC_SERVER_NAME: &str = "localhost";
+
+// A frame that came in, or None once the peer is gone
+pub type Incoming = Option<(u8, Vec<u8>)>;
+
+pub trait Transport: Send {
+    /// Sends a frame that has to arrive, after everything sent before it.
+    fn send_reliable(&mut self, kind: u8, payload: &[u8]) -> Result<()>;
+
+    /// Sends a frame that replaces the last one sent for `key`; it may get lost.
+    fn send_latest(&mut self, key: &str, kind: u8, payload: &[u8]) -> Result<()>;
+
+    /// Whether `send_latest` frames always arrive, so there's never a reason to resend one.
+    fn reliable(&self) -> bool;
+
+    fn shutdown(&self);
+}
+
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send_reliable(&mut self, kind: u8, payload: &[u8]) -> Result<()> {
+        write_frame(&mut self.stream, kind, payload)?;
+        Ok(())
+    }
+
+    fn send_latest(&mut self, _key: &str, kind: u8, payload: &[u8]) -> Result<()> {
+        self.send_reliable(kind, payload)
+    }
+
+    fn reliable(&self) -> bool {
+        true
+    }
+
+    fn shutdown(&self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+// `latest` frames as datagrams to `peer`, the rest over TCP
+pub struct UdpTransport {
+    tcp: TcpTransport,
+    socket: Arc<UdpSocket>,
+    peer: SocketAddr,
+    seq: u32,
+}
+
+impl UdpTransport {
+    pub fn new(stream: TcpStream, socket: Arc<UdpSocket>, peer: SocketAddr) -> Self {
+        Self {
+            tcp: TcpTransport::new(stream),
+            socket,
+            peer,
+            seq: 0,
+        }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_reliable(&mut self, kind: u8, payload: &[u8]) -> Result<()> {
+        self.tcp.send_reliable(kind, payload)
+    }
+
+    fn send_latest(&mut self, key: &str, kind: u8, payload: &[u8]) -> Result<()> {
+        if datagram_len(key, payload) > MAX_DATAGRAM {
+            return self.send_reliable(kind, payload);
+        }
+        self.seq = self.seq.wrapping_add(1);
+        match self
+            .socket
+            .send_to(&datagram(self.seq, key, kind, payload), self.peer)
+        {
+            Ok(_) => Ok(()),
+            // Lost like any other datagram; the next send for the key makes up for it
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn reliable(&self) -> bool {
+        false
+    }
+
+    fn shutdown(&self) {
+        self.tcp.shutdown();
+    }
+}
+
+// `latest` frames as datagrams on a QUIC connection, the rest on a stream of it. quinn runs on
+// a runtime of the transport's own, which the split threads block on
+pub struct QuicTransport {
+    runtime: Arc<Runtime>,
+    connection: Connection,
+    send: SendStream,
+    seq: u32,
+    write_timeout: Option<Duration>,
+}
+
+// The reading half of a QuicTransport's stream, for spawn_reader
+pub struct QuicStream {
+    runtime: Arc<Runtime>,
+    recv: RecvStream,
+}
+
+// Takes QUIC connections for a server
+pub struct QuicEndpoint {
+    runtime: Arc<Runtime>,
+    endpoint: Endpoint,
+}
+
+// A client connecting to a QuicEndpoint, short of its handshake
+pub struct QuicIncoming {
+    runtime: Arc<Runtime>,
+    incoming: quinn::Incoming,
+}
+
+impl QuicTransport {
+    /// Connects to the QuicEndpoint on `addr` and opens the stream.
+    pub fn connect(addr: SocketAddr) -> Result<(Self, QuicStream)> {
+        let runtime = Arc::new(quic_runtime()?);
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let mut endpoint = {
+            let _entered = runtime.enter();
+            Endpoint::client(local)?
+        };
+        endpoint.set_default_client_config(client_config()?);
+        // On the runtime, which quinn spawns the connection's driver on
+        let (connection, send, recv) = runtime.block_on(async {
+            let connection = endpoint.connect(addr, QUIC_SERVER_NAME)?.await?;
+            let (send, recv) = connection.open_bi().await?;
+            anyhow::Ok((connection, send, recv))
+        })?;
+        Ok(Self::open(runtime, connection, send, recv))
+    }
+
+    fn open(
+        runtime: Arc<Runtime>,
+        connection: Connection,
+        send: SendStream,
+        recv: RecvStream,
+    ) -> (Self, QuicStream) {
+        let stream = QuicStream {
+            runtime: runtime.clone(),
+            recv,
+        };
+        let transport = Self {
+            runtime,
+            connection,
+            send,
+            seq: 0,
+            write_timeout: None,
+        };
+        (transport, stream)
+    }
+
+    /// Fails reliable sends the peer doesn't take within `timeout`, like a TcpStream's.
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        self.write_timeout = Some(timeout);
+    }
+
+    /// Hands the frames of datagrams arriving on the connection to `frames`, like
+    /// spawn_udp_reader.
+    pub fn spawn_datagram_reader(&self, frames: Sender<Incoming>) {
+        let (runtime, connection) = (self.runtime.clone(), self.connection.clone());
+        std::thread::spawn(move || {
+            let mut latest = Latest::default();
+            while let Ok(datagram) = runtime.block_on(connection.read_datagram()) {
+                let Some(frame) = latest.take(&datagram) else {
+                    continue;
+                };
+                if frames.send(Some(frame)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+impl Transport for QuicTransport {
+    fn send_reliable(&mut self, kind: u8, payload: &[u8]) -> Result<()> {
+        let frame = frame(kind, payload);
+        let (runtime, send) = (&self.runtime, &mut self.send);
+        match self.write_timeout {
+            Some(timeout) => runtime.block_on(async {
+                tokio::time::timeout(timeout, send.write_all(&frame)).await
+            })??,
+            None => runtime.block_on(send.write_all(&frame))?,
+        }
+        Ok(())
+    }
+
+    fn send_latest(&mut self, key: &str, kind: u8, payload: &[u8]) -> Result<()> {
+        let max = self
+            .connection
+            .max_datagram_size()
+            .unwrap_or(0)
+            .min(MAX_DATAGRAM);
+        if datagram_len(key, payload) > max {
+            return self.send_reliable(kind, payload);
+        }
+        self.seq = self.seq.wrapping_add(1);
+        match self
+            .connection
+            .send_datagram(datagram(self.seq, key, kind, payload).into())
+        {
+            Ok(()) => Ok(()),
+            Err(SendDatagramError::ConnectionLost(e)) => Err(e.into()),
+            // The path shrank, or the peer took datagrams back
+            Err(_) => self.send_reliable(kind, payload),
+        }
+    }
+
+    fn reliable(&self) -> bool {
+        false
+    }
+
+    fn shutdown(&self) {
+        self.connection.close(0u32.into(), b"");
+    }
+}
+
+impl Read for QuicStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let (runtime, recv) = (&self.runtime, &mut self.recv);
+        // None is the end of the stream
+        Ok(runtime.block_on(recv.read(buf))?.unwrap_or(0))
+    }
+}
+
+impl QuicEndpoint {
+    /// Takes QUIC connections on `addr`, with a certificate made up for it.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let runtime = Arc::new(quic_runtime()?);
+        let cert = rcgen::generate_simple_self_signed(vec![QUIC_SERVER_NAME.to_string()])?;
+        let key = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+        let mut config = ServerConfig::with_single_cert(vec![cert.cert.der().clone()], key.into())?;
+        config.transport_config(transport_config());
+        let endpoint = {
+            let _entered = runtime.enter();
+            Endpoint::server(config, addr)
+                .with_context(|| format!("Failed to bind QUIC {}", addr))?
+        };
+        Ok(Self { runtime, endpoint })
+    }
+
+    /// The next client to connect, or None once the endpoint is closed.
+    pub fn accept(&self) -> Option<QuicIncoming> {
+        let incoming = self.runtime.block_on(self.endpoint.accept())?;
+        Some(QuicIncoming {
+            runtime: self.runtime.clone(),
+            incoming,
+        })
+    }
+}
+
+impl QuicIncoming {
+    /// Finishes the handshake and waits for the client's stream.
+    pub fn establish(self) -> Result<(QuicTransport, QuicStream)> {
+        let (connection, send, recv) = self.runtime.block_on(async {
+            let connection = self.incoming.accept()?.await?;
+            let (send, recv) = connection.accept_bi().await?;
+            anyhow::Ok((connection, send, recv))
+        })?;
+        Ok(QuicTransport::open(self.runtime, connection, send, recv))
+    }
+}
+
+// One thread's enough for quinn's timers and sockets: the split threads do the waiting
+fn quic_runtime() -> Result<Runtime> {
+    Ok(tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()?)
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config.max_idle_timeout(Some(IdleTimeout::try_from(QUIC_IDLE_TIMEOUT).unwrap()));
+    config.keep_alive_interval(Some(QUIC_KEEP_ALIVE));
+    Arc::new(config)
+}
+
+fn client_config() -> Result<ClientConfig> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let tls = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+        .with_no_client_auth();
+    let mut config = ClientConfig::new(Arc::new(QuicClientConfig::try_from(tls)?));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+// Takes the server's certificate, whatever it is (see the file's header), but still checks the
+// handshake was signed with its key
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Hands every frame arriving on `stream` to `frames`, then None once it closes.
+pub fn spawn_reader(mut stream: impl Read + Send + 'static, frames: Sender<Incoming>) {
+    std::thread::spawn(move || loop {
+        match read_frame(&mut stream) {
+            Ok(frame) => {
+                if frames.send(Some(frame)).is_err() {
+                    break;
+                }
+            }
+            Err(_) => {
+                let _ = frames.send(None);
+                break;
+            }
+        }
+    });
+}
+
+/// Hands the frames of datagrams arriving on `socket` to `frames`, dropping any older than one
+/// already handed on for the same key.
+pub fn spawn_udp_reader(socket: UdpSocket, frames: Sender<Incoming>) {
+    std::thread::spawn(move || {
+        let mut latest = Latest::default();
+        let mut datagram = vec![0u8; MAX_DATAGRAM];
+        while let Ok(len) = socket.recv(&mut datagram) {
+            let Some(frame) = latest.take(&datagram[..len]) else {
+                continue;
+            };
+            if frames.send(Some(frame)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// The seq of the newest datagram taken for each key
+#[derive(Default)]
+struct Latest(HashMap<String, u32>);
+
+impl Latest {
+    // The datagram's frame, unless it's broken or older than one already taken for its key
+    fn take(&mut self, datagram: &[u8]) -> Option<(u8, Vec<u8>)> {
+        let (seq, key, frame) = parse_datagram(datagram)?;
+        // Wrapping compare, so the sender's seq can roll over
+        if self
+            .0
+            .get(&key)
+            .is_some_and(|&last| (seq.wrapping_sub(last) as i32) <= 0)
+        {
+            return None;
+        }
+        self.0.insert(key, seq);
+        Some(frame)
+    }
+}
+
+fn datagram_len(key: &str, payload: &[u8]) -> usize {
+    4 + 2 + key.len() + 5 + payload.len()
+}
+
+fn datagram(seq: u32, key: &str, kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(datagram_len(key, payload));
+    datagram.extend_from_slice(&seq.to_le_bytes());
+    datagram.extend_from_slice(&(key.len() as u16).to_le_bytes());
+    datagram.extend_from_slice(key.as_bytes());
+    datagram.extend_from_slice(&frame(kind, payload));
+    datagram
+}
+
+fn parse_datagram(datagram: &[u8]) -> Option<(u32, String, (u8, Vec<u8>))> {
+    let seq = u32::from_le_bytes(datagram.get(..4)?.try_into().ok()?);
+    let key_len = u16::from_le_bytes(datagram.get(4..6)?.try_into().ok()?) as usize;
+    let key = String::from_utf8(datagram.get(6..6 + key_len)?.to_vec()).ok()?;
+    let rest = datagram.get(6 + key_len..)?;
+    let len = u32::from_le_bytes(rest.get(1..5)?.try_into().ok()?) as usize;
+    let payload = rest.get(5..)?;
+    if payload.len() != len {
+        return None;
+    }
+    Some((seq, key, (rest[0], payload.to_vec())))
+}
+
+fn frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+pub fn write_frame(stream: &mut impl Write, kind: u8, payload: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&frame(kind, payload))
+}
+
+pub fn read_frame(stream: &mut impl Read) -> Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!(
+            "A {} byte frame is over the most, {}",
+            len,
+            MAX_FRAME_LEN
+        ));
+    }
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
@@ -0,0 +1,396 @@
+use super::export::FrameRef;
+use super::images::ImageStore;
+use super::recovery::Crashed;
+use super::theme::Theme;
+use crate::host::heap_timeline::HeapSample;
+use crate::host::logger::LogLine;
+use crate::host::profiler::ProfileReport;
+use bus_protocol::chat::ChatLine;
+use grid_protocol::widgets::{Widget, WidgetNode, WidgetRect};
+use grid_protocol::{STYLE_BOLD, STYLE_DIM, STYLE_ITALIC, STYLE_REVERSE, STYLE_UNDERLINE};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{
+    Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Sparkline, Wrap,
+};
+use ratatui::Frame;
+
+/// Draws a composed screen's cells into `buf`, clipped to `area`, over the theme's colors.
+/// Image anchors get their fallback glyph; the images themselves are drawn after the flush.
+pub fn render_grid(
+    buf: &mut Buffer,
+    area: Rect,
+    frame: &FrameRef,
+    theme: &Theme,
+    images: &ImageStore,
+) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+    buf.set_style(area, base);
+
+    let width = frame.width.max(0) as usize;
+    for (idx, cell) in frame
+        .cells
+        .iter()
+        .enumerate()
+        .take(width * frame.height.max(0) as usize)
+    {
+        let (x, y) = ((idx % width) as u16, (idx / width) as u16);
+        if x >= area.width || y >= area.height {
+            continue;
+        }
+        // Cells without a valid char are left to the theme
+        if let Some(ch) = images.glyph(cell) {
+            // ANSI 256 indices, remapped by the theme
+            buf[(area.x + x, area.y + y)]
+                .set_char(ch)
+                .set_fg(theme.color(cell.fg_color))
+                .set_bg(theme.color(cell.bg_color))
+                .set_style(Style::default().add_modifier(cell_modifier(cell.style)));
+        }
+    }
+}
+
+// GridCell::style bits; the compositor already cleared them for drivers without GRID_EXT_STYLES
+fn cell_modifier(style: u16) -> Modifier {
+    [
+        (STYLE_BOLD, Modifier::BOLD),
+        (STYLE_DIM, Modifier::DIM),
+        (STYLE_ITALIC, Modifier::ITALIC),
+        (STYLE_UNDERLINE, Modifier::UNDERLINED),
+        (STYLE_REVERSE, Modifier::REVERSED),
+    ]
+    .into_iter()
+    .filter(|(bit, _)| style & bit != 0)
+    .fold(Modifier::empty(), |all, (_, modifier)| all | modifier)
+}
+
+/// Draws a driver's widgets over its pane. `pane` is where the driver's grid sits on screen;
+/// widget areas are relative to it and clipped to it.
+pub fn render(f: &mut Frame, pane: Rect, nodes: &[WidgetNode], theme: &Theme) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+    let highlight = base.add_modifier(Modifier::REVERSED);
+
+    for node in nodes {
+        let area = to_screen(pane, node.area);
+        if area.is_empty() {
+            continue;
+        }
+
+        match &node.widget {
+            Widget::Text { text } => {
+                f.render_widget(
+                    Paragraph::new(text.as_str())
+                        .style(base)
+                        .wrap(Wrap { trim: false }),
+                    area,
+                );
+            }
+            Widget::TextInput {
+                label,
+                value,
+                cursor,
+                focused,
+            } => {
+                let block = Block::default().borders(Borders::ALL).title(label.as_str());
+                let inner = block.inner(area);
+                f.render_widget(
+                    Paragraph::new(value.as_str()).style(base).block(block),
+                    area,
+                );
+                if *focused && !inner.is_empty() {
+                    let col = (*cursor as u16).min(inner.width - 1);
+                    f.set_cursor_position(Position::new(inner.x + col, inner.y));
+                }
+            }
+            Widget::List {
+                title,
+                items,
+                selected,
+            } => {
+                let items: Vec<ListItem> =
+                    items.iter().map(|i| ListItem::new(i.as_str())).collect();
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(title.as_str()))
+                    .style(base)
+                    .highlight_style(highlight)
+                    .highlight_symbol("> ");
+                let mut state = ListState::default().with_selected(selected.map(|s| s as usize));
+                f.render_stateful_widget(list, area, &mut state);
+            }
+            Widget::Menu { items, selected } => {
+                let spans: Vec<Span> = items
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, item)| {
+                        let style = if *selected == Some(idx as u32) {
+                            highlight
+                        } else {
+                            base
+                        };
+                        Span::styled(format!(" {} ", item), style)
+                    })
+                    .collect();
+                f.render_widget(Paragraph::new(Line::from(spans)).style(base), area);
+            }
+            Widget::Progress { label, ratio } => {
+                let gauge = Gauge::default()
+                    .style(base)
+                    .gauge_style(highlight)
+                    .label(label.as_str())
+                    .ratio(ratio.clamp(0.0, 1.0) as f64);
+                f.render_widget(gauge, area);
+            }
+        }
+    }
+}
+
+/// The profiler overlay: `report` in a box in the top right corner of `screen`, over the panes.
+pub fn render_profile(f: &mut Frame, screen: Rect, report: &ProfileReport, theme: &Theme) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+
+    let text = report.to_string();
+    let width = (text.lines().map(str::len).max().unwrap_or(0) as u16 + 2).min(screen.width);
+    let height = (text.lines().count() as u16 + 2).min(screen.height);
+    let area = Rect::new(screen.x + screen.width - width, screen.y, width, height);
+    let block = Block::default().borders(Borders::ALL).title("Profile");
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(text).style(base).block(block), area);
+}
+
+/// The log pane: the latest of `lines` in a box along the bottom of `screen`, over the panes.
+pub fn render_log(f: &mut Frame, screen: Rect, lines: &[LogLine], theme: &Theme) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+
+    let height = (screen.height / 3).max(3).min(screen.height);
+    let area = Rect::new(
+        screen.x,
+        screen.y + screen.height - height,
+        screen.width,
+        height,
+    );
+    let shown = lines
+        .len()
+        .saturating_sub(height.saturating_sub(2) as usize);
+    let text: Vec<Line> = lines[shown..]
+        .iter()
+        .map(|line| {
+            Line::from(format!(
+                "[{}] {:<5} {}",
+                line.plugin,
+                line.level.name(),
+                line.text
+            ))
+        })
+        .collect();
+    let block = Block::default().borders(Borders::ALL).title("Log");
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(text).style(base).block(block), area);
+}
+
+/// The crash screen: the trapped driver and what trapped it, in a box in the middle of `screen`,
+/// over the panes, with the keys that restart it (r) or quit.
+pub fn render_crash(f: &mut Frame, screen: Rect, crashed: &Crashed, theme: &Theme) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+
+    let mut text = vec![Line::from(match &crashed.plugin {
+        Some(plugin) => format!("'{}' trapped in '{}':", crashed.driver, plugin),
+        None => format!("'{}' trapped:", crashed.driver),
+    })];
+    text.extend(
+        crashed
+            .message
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    );
+    text.push(Line::from(""));
+    if let Some(bundle) = &crashed.bundle {
+        text.push(
+            Line::from(format!("Crash bundle: {}", bundle.display()))
+                .style(base.add_modifier(Modifier::DIM)),
+        );
+    }
+    text.push(
+        Line::from("r restarts it, the quit key quits").style(base.add_modifier(Modifier::BOLD)),
+    );
+
+    let width = (screen.width * 2 / 3).max(40).min(screen.width);
+    // Room for the wrapped message, as far as the screen goes
+    let inner = width.saturating_sub(2).max(1) as usize;
+    let rows: usize = text
+        .iter()
+        .map(|line| line.width().max(1).div_ceil(inner))
+        .sum();
+    let height = (rows as u16 + 2).min(screen.height);
+    let area = Rect::new(
+        screen.x + (screen.width - width) / 2,
+        screen.y + (screen.height - height) / 2,
+        width,
+        height,
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Plugin trapped");
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .style(base)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// The chat overlay: `lines` (oldest first) in a box in the bottom left corner of `screen`, over
+/// the panes, with the line being typed under them while the chat input is open.
+pub fn render_chat(
+    f: &mut Frame,
+    screen: Rect,
+    lines: &[ChatLine],
+    input: Option<&str>,
+    theme: &Theme,
+) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+
+    let mut text: Vec<Line> = lines
+        .iter()
+        .map(|line| {
+            let from = Span::styled(
+                format!("{}: ", line.from),
+                base.add_modifier(Modifier::BOLD),
+            );
+            Line::from(vec![from, Span::raw(line.text.as_str())])
+        })
+        .collect();
+    if let Some(input) = input {
+        text.push(Line::from(format!("> {}_", input)));
+    }
+    if text.is_empty() {
+        return;
+    }
+    let width = (screen.width / 2).max(30).min(screen.width);
+    let height = (text.len() as u16 + 2).min(screen.height);
+    let area = Rect::new(screen.x, screen.y + screen.height - height, width, height);
+    // The newest lines, and the input, when they don't all fit
+    let scroll = (text.len() as u16).saturating_sub(height.saturating_sub(2));
+    let block = Block::default().borders(Borders::ALL).title("Chat");
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .style(base)
+            .block(block)
+            .scroll((scroll, 0)),
+        area,
+    );
+}
+
+/// The heap pane: host heap usage and fragmentation over the latest `samples` (oldest first),
+/// in a box in the top left corner of `screen`, over the panes.
+pub fn render_heap(f: &mut Frame, screen: Rect, samples: &[HeapSample], theme: &Theme) {
+    let mut base = Style::default();
+    if let Some(fg) = theme.foreground {
+        base = base.fg(fg);
+    }
+    if let Some(bg) = theme.background {
+        base = base.bg(bg);
+    }
+
+    let width = (screen.width * 2 / 3).max(24).min(screen.width);
+    let area = Rect::new(screen.x, screen.y, width, 9.min(screen.height));
+    let block = Block::default().borders(Borders::ALL).title("Heap");
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let status = match samples.last() {
+        Some(last) => format!(
+            "{} KiB of {} KiB in use, {} free blocks, largest {} KiB, {:.0}% fragmented",
+            last.in_use / 1024,
+            last.size() / 1024,
+            last.free_blocks,
+            last.largest_free / 1024,
+            last.fragmentation() * 100.0
+        ),
+        None => "No samples yet".to_string(),
+    };
+    // One sparkline column per sample, the newest on the right
+    let shown = &samples[samples.len().saturating_sub(inner.width as usize)..];
+    let usage: Vec<u64> = shown
+        .iter()
+        .map(|sample| sample.in_use * 100 / sample.size().max(1))
+        .collect();
+    let fragmentation: Vec<u64> = shown
+        .iter()
+        .map(|sample| (sample.fragmentation() * 100.0) as u64)
+        .collect();
+
+    let rows = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(2),
+        Constraint::Length(1),
+        Constraint::Length(2),
+    ])
+    .split(inner);
+    f.render_widget(Paragraph::new(status).style(base), rows[0]);
+    f.render_widget(Paragraph::new("in use %").style(base), rows[1]);
+    f.render_widget(
+        Sparkline::default().data(&usage).max(100).style(base),
+        rows[2],
+    );
+    f.render_widget(Paragraph::new("fragmentation %").style(base), rows[3]);
+    f.render_widget(
+        Sparkline::default()
+            .data(&fragmentation)
+            .max(100)
+            .style(base),
+        rows[4],
+    );
+}
+
+fn to_screen(pane: Rect, area: WidgetRect) -> Rect {
+    Rect::new(
+        pane.x.saturating_add(area.x),
+        pane.y.saturating_add(area.y),
+        area.width,
+        area.height,
+    )
+    .intersection(pane)
+}
@@ -0,0 +1,110 @@
+use super::caller_state::HostState;
+use super::logger::Level;
+use anyhow::{Context, Result};
+use fat_ptr::envelope;
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+
+// What plugins did to each other, for hosts running plugins their operator didn't write. With
+// BlindHostConfig::audit_log (`--audit-log path`) every link and every cross-plugin call is
+// appended to the file as it happens, one JSON object per line, and never rewritten:
+//
+//   {"at":1718000000123,"event":"call","caller":"ui","callee":"game","export":"get_state",
+//    "payload":12,"status":"ok"}
+//
+//   at        unix time, in milliseconds
+//   event     link / link_checked (host_link_call / host_link_call_checked), linked (a call
+//             through a linked table entry), call, fire_and_forget, or message (a bus message
+//             handed to the callee's on_message; the caller can be a host endpoint like LOBBY)
+//   payload   the (ptr, len) argument's length in bytes; absent for other signatures and links
+//   status    ok, error (it answered an error envelope), trapped, or refused (the provider or
+//             export doesn't exist or doesn't fit, or the callee couldn't take the message)
+//
+// Auditing routes linked exports through the host on every call, like trace_calls does. Lines
+// are written out one at a time, so a crash loses none already recorded. A file that can't be
+// written to is logged once and the host goes on without it.
+
+pub const OK: &str = "ok";
+pub const ERROR: &str = "error";
+pub const TRAPPED: &str = "trapped";
+pub const REFUSED: &str = "refused";
+
+pub struct AuditEntry<'a> {
+    pub event: &'a str,
+    pub caller: &'a str,
+    pub callee: &'a str,
+    pub export: &'a str,
+    pub payload: Option<u32>,
+    pub status: &'a str,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    file: LineWriter<File>,
+    failed: bool,
+}
+
+impl AuditLog {
+    /// Appends to `path`, starting it if there's none.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open the audit log '{}'", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: LineWriter::new(file),
+            failed: false,
+        })
+    }
+
+    pub fn record(&mut self, entry: &AuditEntry) -> std::io::Result<()> {
+        let at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut line = json!({
+            "at": at,
+            "event": entry.event,
+            "caller": entry.caller,
+            "callee": entry.callee,
+            "export": entry.export,
+            "status": entry.status,
+        });
+        if let Some(payload) = entry.payload {
+            line["payload"] = json!(payload);
+        }
+        writeln!(self.file, "{}", line)
+    }
+}
+
+/// Records `entry` if the host keeps an audit log.
+pub fn audit(state: &HostState, entry: AuditEntry) {
+    let Some(log) = &state.audit else {
+        return;
+    };
+    let mut log = log.lock().unwrap();
+    if let Err(e) = log.record(&entry) {
+        if !log.failed {
+            log.failed = true;
+            let text = format!(
+                "Failed to write the audit log '{}': {}",
+                log.path.display(),
+                e
+            );
+            state.logger.lock().unwrap().log("host", Level::Warn, &text);
+        }
+    }
+}
+
+/// The status of a call answering `result`, for exports answering an i64 envelope if `packed`.
+pub fn status<T>(result: &Result<T>, packed: Option<i64>) -> &'static str {
+    match (result, packed) {
+        (Err(_), _) => TRAPPED,
+        (Ok(_), Some(packed)) if envelope::is_error(packed) => ERROR,
+        _ => OK,
+    }
+}
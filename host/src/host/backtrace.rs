@@ -0,0 +1,105 @@
+use anyhow::Error;
+use std::fmt::{self, Write};
+use wasmtime::{FrameInfo, Module, Trap, WasmBacktrace};
+
+// Rewrites the wasm backtrace wasmtime attaches to traps into something plugin authors can
+// read: which plugin each frame is in (wasmtime only knows "<unknown>"), demangled Rust names,
+// and file:line where the plugin carries DWARF (a `debug = true` build; the engine keeps it).
+//
+//   'tasksapp_core' trapped (a panic, unless the plugin hit `unreachable` itself):
+//       0: tasksapp_core!tasksapp_core::new_task
+//              at .archived/tasksapp-core/src/lib.rs:450:13
+//       1: tasksapp-tui!<tasksapp_tui::TasksDriver as GridDriver>::tick
+//
+// Caused by:
+//     wasm trap: wasm `unreachable` instruction executed
+
+/// The context `symbolicate` puts on a trap: the plugin whose code trapped, and the text above.
+#[derive(Debug)]
+pub struct Trapped {
+    pub plugin: String,
+    pub backtrace: String,
+}
+
+impl fmt::Display for Trapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.backtrace)
+    }
+}
+
+/// `error` with its wasm backtrace symbolicated against `modules` (plugin name, module).
+/// Errors that aren't traps, or carry no backtrace, come back untouched. The Trap stays in
+/// the chain, so callers can still downcast to it (and to `Trapped`).
+pub fn symbolicate(error: Error, modules: &[(String, Module)]) -> Error {
+    let Some(trace) = error.downcast_ref::<WasmBacktrace>() else {
+        return error;
+    };
+    let Some(first) = trace.frames().first() else {
+        return error;
+    };
+
+    let plugin = plugin_of(first, modules).to_string();
+    let mut text = format!("'{}' trapped", plugin);
+    if error.downcast_ref::<Trap>() == Some(&Trap::UnreachableCodeReached) {
+        text.push_str(" (a panic, unless the plugin hit `unreachable` itself)");
+    }
+    text.push(':');
+    for (idx, frame) in trace.frames().iter().enumerate() {
+        write_frame(&mut text, idx, frame, modules);
+    }
+
+    match error.downcast::<Trap>() {
+        Ok(trap) => Error::new(trap).context(Trapped {
+            plugin,
+            backtrace: text,
+        }),
+        Err(error) => error,
+    }
+}
+
+fn write_frame(out: &mut String, idx: usize, frame: &FrameInfo, modules: &[(String, Module)]) {
+    let plugin = plugin_of(frame, modules);
+    let raw_name = match frame.func_name() {
+        Some(name) => demangle(name),
+        None => format!("<function {}>", frame.func_index()),
+    };
+    if frame.symbols().is_empty() {
+        let _ = write!(out, "\n    {:>3}: {}!{}", idx, plugin, raw_name);
+        return;
+    }
+    // Several symbols per frame when functions were inlined into it, innermost first
+    for (inlined, symbol) in frame.symbols().iter().enumerate() {
+        let name = symbol
+            .name()
+            .map(demangle)
+            .unwrap_or_else(|| raw_name.clone());
+        if inlined == 0 {
+            let _ = write!(out, "\n    {:>3}: {}!{}", idx, plugin, name);
+        } else {
+            let _ = write!(out, "\n         inlined into {}", name);
+        }
+        if let Some(file) = symbol.file() {
+            let _ = write!(out, "\n           at {}", file);
+            if let Some(line) = symbol.line() {
+                let _ = write!(out, ":{}", line);
+                if let Some(column) = symbol.column() {
+                    let _ = write!(out, ":{}", column);
+                }
+            }
+        }
+    }
+}
+
+// Modules are compared by their compiled image, the one thing a frame points back to
+fn plugin_of<'a>(frame: &FrameInfo, modules: &'a [(String, Module)]) -> &'a str {
+    let image = frame.module().image_range().start;
+    modules
+        .iter()
+        .find(|(_, module)| module.image_range().start == image)
+        .map_or("<unknown>", |(name, _)| name.as_str())
+}
+
+// Without the hash suffix, which only tells builds apart
+fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
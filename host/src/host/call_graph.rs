@@ -0,0 +1,169 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::time::Duration;
+
+// How many calls the graph remembers, newest kept
+const CALL_HISTORY: usize = 4096;
+
+// (caller, callee) -> export -> (calls, total time)
+type Edges<'a> = BTreeMap<(&'a str, &'a str), BTreeMap<&'a str, (u64, Duration)>>;
+
+// Who links against and calls whom, as it happens. Links (host_link_call) are always recorded;
+// calls only with BlindHostConfig::trace_calls, which routes linked exports through the host
+// (they're direct table calls otherwise) and records every `call` / `fire_and_forget`.
+#[derive(Default)]
+pub struct CallGraph {
+    pub enabled: bool,
+    links: Vec<Link>,
+    calls: VecDeque<CallRecord>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Link {
+    pub caller: String,
+    pub provider: String,
+    pub export: String,
+    pub checked: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    // Through a host_link_call table entry
+    Linked,
+    Call,
+    FireAndForget,
+}
+
+#[derive(Clone, Debug)]
+pub struct CallRecord {
+    pub caller: String,
+    pub callee: String,
+    pub export: String,
+    pub kind: CallKind,
+    // The payload's length, for exports taking (ptr, len)
+    pub payload: Option<u32>,
+    pub took: Duration,
+}
+
+impl CallKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CallKind::Linked => "linked",
+            CallKind::Call => "call",
+            CallKind::FireAndForget => "fire_and_forget",
+        }
+    }
+}
+
+impl CallGraph {
+    pub fn new(enabled: bool) -> Self {
+        CallGraph {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    pub fn linked(&mut self, link: Link) {
+        self.links.push(link);
+    }
+
+    pub fn called(&mut self, record: CallRecord) {
+        if self.calls.len() == CALL_HISTORY {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(record);
+    }
+
+    // Over the links and remembered calls
+    fn edges(&self) -> Edges<'_> {
+        let mut edges = Edges::new();
+        for link in &self.links {
+            edges
+                .entry((link.caller.as_str(), link.provider.as_str()))
+                .or_default()
+                .entry(link.export.as_str())
+                .or_default();
+        }
+        for call in &self.calls {
+            let stats = edges
+                .entry((call.caller.as_str(), call.callee.as_str()))
+                .or_default()
+                .entry(call.export.as_str())
+                .or_default();
+            stats.0 += 1;
+            stats.1 += call.took;
+        }
+        edges
+    }
+
+    /// Graphviz: one edge per caller -> callee, labelled with each export's call count and
+    /// average time. Exports only linked and never called show without numbers.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph plugins {\n    node [shape=box];\n");
+        for ((caller, callee), exports) in self.edges() {
+            let label: Vec<String> = exports
+                .iter()
+                .map(|(export, (calls, total))| match calls {
+                    0 => export.to_string(),
+                    n => format!("{} ×{} ({:.2?} avg)", export, n, *total / *n as u32),
+                })
+                .collect();
+            let _ = writeln!(
+                dot,
+                "    {:?} -> {:?} [label={:?}];",
+                caller,
+                callee,
+                label.join("\n")
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Every link and remembered call, plus the edges `to_dot` draws.
+    pub fn to_json(&self) -> Value {
+        let links: Vec<Value> = self
+            .links
+            .iter()
+            .map(|link| {
+                json!({
+                    "caller": link.caller,
+                    "provider": link.provider,
+                    "export": link.export,
+                    "checked": link.checked,
+                })
+            })
+            .collect();
+        let calls: Vec<Value> = self
+            .calls
+            .iter()
+            .map(|call| {
+                json!({
+                    "caller": call.caller,
+                    "callee": call.callee,
+                    "export": call.export,
+                    "kind": call.kind.name(),
+                    "payload": call.payload,
+                    "us": call.took.as_micros() as u64,
+                })
+            })
+            .collect();
+        let edges: Vec<Value> = self
+            .edges()
+            .into_iter()
+            .flat_map(|((caller, callee), exports)| {
+                exports.into_iter().map(move |(export, (calls, total))| {
+                    json!({
+                        "caller": caller,
+                        "callee": callee,
+                        "export": export,
+                        "calls": calls,
+                        "total_us": total.as_micros() as u64,
+                    })
+                })
+            })
+            .collect();
+        json!({ "links": links, "calls": calls, "edges": edges })
+    }
+}
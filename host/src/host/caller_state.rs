@@ -1,17 +1,81 @@
+use super::audit::AuditLog;
+use super::call_graph::CallGraph;
+use super::heap_timeline::HeapTimeline;
+use super::logger::Logger;
+use super::manifest::PluginManifest;
+use super::metrics::Metrics;
+use super::profiler::Profiler;
+use super::quotas::Quotas;
 use crate::allocator::HostHeap;
+use crate::host_calls::assert::AssertFailure;
+use crate::host_calls::bus::MessageBus;
+use crate::host_calls::chat::Chat;
+use crate::host_calls::ids::IdRegistry;
+use crate::host_calls::lobby::Lobby;
+use crate::host_calls::random::Random;
+use crate::host_calls::saves::{SaveStore, SaveVersion};
+use crate::host_calls::strings::StringTable;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use wasmtime::{Instance, SharedMemory, Table};
+use wasmtime::{Instance, Module, SharedMemory, Table};
 
 #[derive(Clone)]
 pub struct HostState {
     pub instances: HashMap<String, Instance>,
+    // Every loaded plugin's module, to tell whose code a trap's frames are in
+    pub modules: Vec<(String, Module)>,
     pub tables: HashMap<String, Table>,
     pub shared_memory: SharedMemory,
     pub next_memory_offset: i32,
+    // Each plugin's slot base, in load order
+    pub slots: Vec<(String, i32)>,
+    // Slots unloaded plugins gave back, taken before new ones (BlindHost::unload_plugin)
+    pub free_slots: Vec<i32>,
+    // Which plugin each `env` export was last defined by, and what each plugin imported from
+    // others when it was instantiated: plugin -> (provider, export)
+    pub env_exports: HashMap<String, String>,
+    pub imported: HashMap<String, Vec<(String, String)>>,
+    // Entries in callers' tables linked to a provider's export: (provider, caller, index)
+    pub linked_entries: Vec<(String, String, u32)>,
     pub next_stack_offset: i32,
     pub heap: Arc<Mutex<HostHeap>>,
     pub slot_size: i32,
     pub data_size: i32,
     pub heap_start_address: i32,
+    // CAPABILITY_* bits host_capabilities answers with
+    pub capabilities: u64,
+    // What each plugin's get_capabilities reported, if it has one
+    pub plugin_capabilities: HashMap<String, u64>,
+    pub ids: Arc<Mutex<IdRegistry>>,
+    pub strings: Arc<Mutex<StringTable>>,
+    // Plugin messages waiting for bus::deliver
+    pub bus: Arc<Mutex<MessageBus>>,
+    // Multiplayer sessions, reached over the bus (lobby.rs)
+    pub lobby: Arc<Mutex<Lobby>>,
+    // Chat lines, posted over the bus or typed in the overlay (chat.rs)
+    pub chat: Arc<Mutex<Chat>>,
+    // Plugins' save slots, None when saves are off (saves.rs)
+    pub saves: Option<Arc<SaveStore>>,
+    // What each plugin saves as, from its __abi_version and __save_schema
+    pub save_versions: HashMap<String, SaveVersion>,
+    // What each plugin loaded with a manifest was loaded with (manifest.rs)
+    pub manifests: HashMap<String, PluginManifest>,
+    // Timings of host-made plugin calls, see BlindHost::profiled
+    pub profiler: Arc<Mutex<Profiler>>,
+    // Plugin logs, filtered and sent to the configured sinks
+    pub logger: Arc<Mutex<Logger>>,
+    // Allocations per plugin, ticks, frames and links, see metrics.rs
+    pub metrics: Arc<Mutex<Metrics>>,
+    // host_random's generator
+    pub random: Arc<Mutex<Random>>,
+    // Cross-plugin links and (with trace_calls) calls
+    pub call_graph: Arc<Mutex<CallGraph>>,
+    // Host heap usage over time, see heap_timeline.rs
+    pub heap_timeline: Arc<Mutex<HeapTimeline>>,
+    // Failed host_asserts the embedder hasn't looked at yet
+    pub assert_failures: Arc<Mutex<Vec<AssertFailure>>>,
+    // Per-plugin budgets and who's over them, see quotas.rs
+    pub quotas: Arc<Mutex<Quotas>>,
+    // Where links and cross-plugin calls are recorded, if anywhere (audit.rs)
+    pub audit: Option<Arc<Mutex<AuditLog>>>,
 }
@@ -0,0 +1,118 @@
+use crate::allocator::HostHeap;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// How many samples the timeline keeps, newest kept: a minute at the default interval
+const SAMPLE_HISTORY: usize = 600;
+
+// Default for BlindHostConfig::heap_sample_interval
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+// The host heap's usage and fragmentation over time, sampled by the embedder after ticks
+// (`BlindHost::end_tick`) and shown in the heap pane or written as CSV (`--heap-timeline`):
+//
+//   tick,ms,in_use,free,free_blocks,largest_free,fragmentation
+//
+// Fragmentation is the share of free bytes outside the largest free block: 0 when all free
+// memory is one block, close to 1 when it's scattered in slivers no large allocation fits in.
+pub struct HeapTimeline {
+    interval: Duration,
+    start: Instant,
+    last: Option<Instant>,
+    ticks: u64,
+    samples: VecDeque<HeapSample>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HeapSample {
+    // How many ticks in, counting ticks between samples too
+    pub tick: u64,
+    // Since the timeline started
+    pub at: Duration,
+    pub in_use: u64,
+    pub free: u64,
+    pub free_blocks: usize,
+    pub largest_free: u64,
+}
+
+impl HeapSample {
+    pub fn fragmentation(&self) -> f64 {
+        match self.free {
+            0 => 0.0,
+            free => 1.0 - self.largest_free as f64 / free as f64,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.in_use + self.free
+    }
+}
+
+impl HeapTimeline {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            start: Instant::now(),
+            last: None,
+            ticks: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Called once per tick: samples `heap`, which manages `heap_size` bytes, unless the last
+    /// sample is more recent than the interval.
+    pub fn sample(&mut self, heap: &HostHeap, heap_size: u64) {
+        self.ticks += 1;
+        let now = Instant::now();
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return;
+        }
+        self.last = Some(now);
+
+        let free: u64 = heap.free_blocks.iter().map(|block| block.size as u64).sum();
+        let largest_free = heap
+            .free_blocks
+            .iter()
+            .map(|block| block.size as u64)
+            .max()
+            .unwrap_or(0);
+        if self.samples.len() == SAMPLE_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(HeapSample {
+            tick: self.ticks,
+            at: now.duration_since(self.start),
+            in_use: heap_size.saturating_sub(free),
+            free,
+            free_blocks: heap.free_blocks.len(),
+            largest_free,
+        });
+    }
+
+    /// Oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &HeapSample> {
+        self.samples.iter()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("tick,ms,in_use,free,free_blocks,largest_free,fragmentation\n");
+        for sample in &self.samples {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.4}",
+                sample.tick,
+                sample.at.as_millis(),
+                sample.in_use,
+                sample.free,
+                sample.free_blocks,
+                sample.largest_free,
+                sample.fragmentation()
+            );
+        }
+        csv
+    }
+}
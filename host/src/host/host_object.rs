@@ -1,35 +1,144 @@
+use super::audit::{self, audit, AuditEntry, AuditLog};
+use super::backtrace;
+use super::call_graph::{CallGraph, CallKind, CallRecord, Link};
 use super::caller_state::HostState;
+use super::heap_timeline::{HeapTimeline, DEFAULT_SAMPLE_INTERVAL};
+use super::layouts;
+use super::logger::{Level, LogConfig, LogLine, Logger};
+use super::manifest::{PluginManifest, ABI_IMPORTS};
+use super::metrics::Metrics;
+use super::module_cache::ModuleCache;
+use super::profiler::{ProfileReport, Profiler};
+use super::quotas::{QuotaConfig, Quotas};
 use crate::allocator::HostHeap;
-use crate::host_calls::allocator::{host_alloc, host_dealloc};
-use crate::host_calls::print::host_print;
-use anyhow::{anyhow, Result};
+use crate::host_calls::allocator::{self, alloc_owned};
+use crate::host_calls::assert::{self, AssertFailure};
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::bus::{register_bus_send, MessageBus};
+use crate::host_calls::call;
+use crate::host_calls::chat::Chat;
+use crate::host_calls::ids::{self, IdRegistry};
+use crate::host_calls::lobby::Lobby;
+use crate::host_calls::print;
+use crate::host_calls::random::{host_random, Random};
+use crate::host_calls::save_backend::SaveBackend;
+use crate::host_calls::saves::{self, SaveStore, SaveVersion};
+use crate::host_calls::storage::{read_guest, write_guest};
+use crate::host_calls::strings::{self, StringTable};
+use crate::host_calls::wasi::{self, WasiOptions, WASI_MODULE};
+use anyhow::{anyhow, Context, Result};
+use bus_protocol::chat::CHAT;
+use bus_protocol::lobby::LOBBY;
+use bus_protocol::quotas::{QuotaEvent, QUOTAS, RESOURCE_FUEL, RESOURCE_TIME};
+use bus_protocol::Envelope;
+use ecs_protocol::{
+    CAPABILITY_AUDIO, CAPABILITY_ECS_KERNEL, CAPABILITY_EVENTS, CAPABILITY_MOUSE, CAPABILITY_TUI,
+};
+use fat_ptr::envelope::{self, CallError, STATUS_TRAPPED};
+use fat_ptr::shared::SharedSlice;
+use fat_ptr::FatPtr;
+use layout_fingerprint::LayoutEntry;
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use wasmtime::{
     Caller, Config, Engine, Extern, Func, Global, GlobalType, Instance, Linker, MemoryType, Module,
-    Mutability, Ref, RefType, SharedMemory, Store, Table, TableType, Val, ValType,
+    Mutability, Ref, RefType, SharedMemory, Store, Table, TableType, Trap, Val, ValType,
+    WasmBacktraceDetails,
 };
 
-const DATA_REGION_START: i32 = 1024;
-const STACK_REGION_START: i32 = 16 * 1024 * 1024;
-const MODULE_DATA_ALLOWANCE: i32 = 1 * 1024 * 1024;
-const MODULE_STACK_SIZE: i32 = 1 * 1024 * 1024;
-
 pub struct BlindHostConfig {
     pub max_plugins: u32,
     pub data_allowance: i32,
     pub stack_size: i32,
+    // `__abi_version`s load_plugin accepts
+    pub abi_versions: RangeInclusive<i32>,
+    // CAPABILITY_* bits the embedder's frontend provides. CAPABILITY_ECS_KERNEL is added by
+    // load_plugin once a kernel is loaded.
+    pub capabilities: u64,
+    // Also time every call into a plugin's sys_* exports (the ECS kernel's syscalls). Off by
+    // default: those are called many times per tick and go through the host once timed.
+    pub profile_syscalls: bool,
+    // Levels, per-plugin filters and sinks of plugin logs (host.toml's [log])
+    pub log: LogConfig,
+    // host_random's seed; from the clock when unset
+    pub random_seed: Option<u64>,
+    // Runs repeat exactly given the same input: host calls whose answers depend on the outside
+    // world (NONDETERMINISTIC_CALLS) trap, and NaNs are canonicalized. Embedders also have to
+    // tick with a fixed delta and set random_seed.
+    pub deterministic: bool,
+    // Record every cross-plugin call in the call graph, not just links. Linked exports then go
+    // through the host on every call, like checked ones.
+    pub trace_calls: bool,
+    // Least time between two heap timeline samples (heap_timeline.rs)
+    pub heap_sample_interval: Duration,
+    // Ticks taking longer are logged with the exports that ran in them (BlindHost::end_tick)
+    pub tick_budget: Option<Duration>,
+    // Where plugins' save slots are kept (host_calls/saves.rs); saves are off when unset, and in
+    // deterministic hosts
+    pub save_backend: Option<Arc<dyn SaveBackend>>,
+    // Per-plugin cpu, heap, fuel and time budgets (quotas.rs); none by default
+    pub quotas: QuotaConfig,
+    // Append every link and cross-plugin call to this file (audit.rs); off when unset
+    pub audit_log: Option<PathBuf>,
+    // Longest one call to a plugin's `tick` may run, by plugin name, with "*" for the others.
+    // Past it the tick traps with "tick budget exceeded", so a plugin looping forever can't
+    // freeze the host. Unlike the quotas' time budget it isn't a strike, and deterministic
+    // hosts keep it: the run ends either way.
+    pub tick_deadlines: HashMap<String, Duration>,
+    // Link wasi_snapshot_preview1 into every plugin (host_calls/wasi.rs); off by default
+    pub enable_wasi: bool,
+    // The directory WASI plugins see as `/`, each in a subdirectory of its own; none when unset,
+    // and in deterministic hosts
+    pub wasi_dir: Option<PathBuf>,
+    // Keep compiled plugins in this directory between runs (module_cache.rs); every load
+    // compiles when unset
+    pub module_cache: Option<PathBuf>,
 }
 
-impl BlindHostConfig {
-    pub fn default() -> Self {
+// How many of a slow tick's costliest exports are logged
+const SLOW_TICK_EXPORTS: usize = 5;
+
+// How often the epoch moves on, for the per-call time budget (quotas.rs)
+const EPOCH_TICK: Duration = Duration::from_millis(1);
+// Fuel and epochs outside calls the quotas cover: as good as none
+const UNLIMITED: u64 = u64::MAX / 2;
+// The host's own names on the bus (bus.rs), which no plugin can be loaded as
+const RESERVED: &[&str] = &[LOBBY, CHAT, QUOTAS];
+
+// Host calls deterministic hosts refuse: the network, user files, and data saved by earlier runs
+pub const NONDETERMINISTIC_CALLS: &[&str] =
+    &["send_to_server", "host_file_read", "host_storage_get"];
+
+impl Default for BlindHostConfig {
+    fn default() -> Self {
         Self {
             max_plugins: 16,
             data_allowance: 128 * 1024,
-            stack_size: 1 * 1024 * 1024,
+            stack_size: 1024 * 1024,
+            abi_versions: fat_ptr::ABI_VERSION..=fat_ptr::ABI_VERSION,
+            capabilities: 0,
+            profile_syscalls: false,
+            log: LogConfig::default(),
+            random_seed: None,
+            deterministic: false,
+            trace_calls: false,
+            heap_sample_interval: DEFAULT_SAMPLE_INTERVAL,
+            tick_budget: None,
+            save_backend: None,
+            quotas: QuotaConfig::default(),
+            audit_log: None,
+            tick_deadlines: HashMap::new(),
+            enable_wasi: false,
+            wasi_dir: None,
+            module_cache: None,
         }
     }
+}
 
+impl BlindHostConfig {
     pub fn slot_size(&self) -> i32 {
         let size = self.data_allowance + self.stack_size + 16;
 
@@ -41,16 +150,58 @@ pub struct BlindHost {
     pub engine: Engine,
     pub store: Store<HostState>,
     pub linker: Linker<HostState>,
+    abi_versions: RangeInclusive<i32>,
+    profile_syscalls: bool,
+    tick_budget: Option<Duration>,
+    // Copied out of the quotas, since every profiled call needs them
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    tick_deadlines: HashMap<String, Duration>,
+    wasi: Option<WasiOptions>,
+    module_cache: Option<ModuleCache>,
+    deterministic: bool,
+    // The embedder's host calls, registered in each plugin's own linker with its name
+    plugin_calls: Box<PluginCalls>,
 }
 
+type PluginCalls = dyn Fn(&mut Linker<HostState>, &str) -> Result<()> + Send + Sync;
+
 impl BlindHost {
-    pub fn new<F>(config: BlindHostConfig, setup_linker: F) -> Result<Self>
+    /// `plugin_calls` registers the embedder's host calls for the plugin it's given the name
+    /// of, each time one is loaded.
+    pub fn new<F>(config: BlindHostConfig, plugin_calls: F) -> Result<Self>
     where
-        F: FnOnce(&mut Linker<HostState>, &mut Store<HostState>) -> Result<()>,
+        F: Fn(&mut Linker<HostState>, &str) -> Result<()> + Send + Sync + 'static,
     {
         let mut wasm_config = Config::new();
         wasm_config.wasm_threads(true);
+        // Keep plugins' DWARF, so traps can name source lines (see backtrace.rs)
+        wasm_config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+        // NaN bit patterns are otherwise up to the CPU
+        wasm_config.cranelift_nan_canonicalization(config.deterministic);
+        let quotas = if config.deterministic {
+            config.quotas.clone().deterministic()
+        } else {
+            config.quotas.clone()
+        };
+        // Both cost on every call, so they're only on with a budget to enforce
+        let epochs = quotas.timeout.is_some() || !config.tick_deadlines.is_empty();
+        wasm_config.consume_fuel(quotas.fuel.is_some());
+        wasm_config.epoch_interruption(epochs);
         let engine = Engine::new(&wasm_config)?;
+        // Everything above that changes what plugins compile to
+        let engine_key = format!(
+            "{}-{} threads backtraces nan_canonicalization={} fuel={} epochs={}",
+            std::env::consts::ARCH,
+            std::env::consts::OS,
+            config.deterministic,
+            quotas.fuel.is_some(),
+            epochs
+        );
+        let module_cache = config
+            .module_cache
+            .as_ref()
+            .map(|dir| ModuleCache::new(dir, &engine_key));
 
         // --- 1. EXACT CALCULATION ---
         let slot_size = config.slot_size();
@@ -89,76 +240,823 @@ impl BlindHost {
         let memory = SharedMemory::new(&engine, MemoryType::shared(initial_pages as u32, 16384))?;
 
         // --- 4. STATE SETUP (Same as before) ---
+        let logger = Arc::new(Mutex::new(Logger::new(config.log)?));
         let initial_state = HostState {
             instances: HashMap::new(),
+            modules: Vec::new(),
             tables: HashMap::new(),
             shared_memory: memory.clone(),
             next_memory_offset: 1024,
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            env_exports: HashMap::new(),
+            imported: HashMap::new(),
+            linked_entries: Vec::new(),
             next_stack_offset: 0,
             slot_size,
             heap_start_address,
             data_size: config.data_allowance,
             heap: Arc::new(Mutex::new(HostHeap::new())),
+            capabilities: config.capabilities,
+            plugin_capabilities: HashMap::new(),
+            ids: Arc::new(Mutex::new(IdRegistry::default())),
+            strings: Arc::new(Mutex::new(StringTable::default())),
+            bus: Arc::new(Mutex::new(MessageBus::default())),
+            lobby: Arc::new(Mutex::new(Lobby::default())),
+            chat: Arc::new(Mutex::new(Chat::default())),
+            saves: config
+                .save_backend
+                .filter(|_| !config.deterministic)
+                .map(|backend| {
+                    backend.set_logger(logger.clone());
+                    Arc::new(SaveStore::with_backend(backend))
+                }),
+            save_versions: HashMap::new(),
+            manifests: HashMap::new(),
+            profiler: Arc::new(Mutex::new(Profiler::default())),
+            logger,
+            metrics: Arc::new(Mutex::new(Metrics::default())),
+            random: Arc::new(Mutex::new(Random::new(
+                config.random_seed.unwrap_or_else(clock_seed),
+            ))),
+            call_graph: Arc::new(Mutex::new(CallGraph::new(config.trace_calls))),
+            heap_timeline: Arc::new(Mutex::new(HeapTimeline::new(config.heap_sample_interval))),
+            assert_failures: Arc::new(Mutex::new(Vec::new())),
+            quotas: Arc::new(Mutex::new(Quotas::new(quotas.clone()))),
+            audit: match &config.audit_log {
+                Some(path) => Some(Arc::new(Mutex::new(AuditLog::open(path)?))),
+                None => None,
+            },
         };
 
         let mut store = Store::new(&engine, initial_state);
+        if quotas.fuel.is_some() {
+            store.set_fuel(UNLIMITED)?;
+        }
+        if epochs {
+            store.set_epoch_deadline(UNLIMITED);
+            // Until the host (and with it the engine) is gone
+            let engine = engine.weak();
+            std::thread::spawn(move || {
+                while let Some(engine) = engine.upgrade() {
+                    engine.increment_epoch();
+                    drop(engine);
+                    std::thread::sleep(EPOCH_TICK);
+                }
+            });
+        }
         let mut linker = Linker::new(&engine);
         linker.allow_shadowing(true);
 
         linker.define(&store, "env", "memory", memory)?;
-        linker.func_wrap("env", "host_print", host_print)?;
-        linker.func_wrap("env", "host_alloc", host_alloc)?;
-        linker.func_wrap("env", "host_dealloc", host_dealloc)?;
-
-        setup_linker(&mut linker, &mut store)?;
+        linker.func_wrap(
+            "env",
+            "host_capabilities",
+            |c: Caller<'_, HostState>| -> i64 { c.data().capabilities as i64 },
+        )?;
+        linker.func_wrap("env", "host_random", host_random)?;
 
         Ok(Self {
             engine,
             store,
             linker,
+            abi_versions: config.abi_versions,
+            profile_syscalls: config.profile_syscalls,
+            tick_budget: config.tick_budget,
+            fuel: quotas.fuel,
+            timeout: quotas.timeout,
+            tick_deadlines: config.tick_deadlines,
+            wasi: config.enable_wasi.then(|| WasiOptions {
+                dir: config.wasi_dir.filter(|_| !config.deterministic),
+                deterministic: config.deterministic,
+            }),
+            module_cache,
+            deterministic: config.deterministic,
+            plugin_calls: Box::new(plugin_calls),
         })
     }
 
-    // load_plugin remains exactly the same as your working version
+    /// The HostHeap starts empty: hands it the shared memory past the plugins' slots to manage.
+    pub fn init_heap(&self) {
+        let data = self.store.data();
+        let heap_start = data.heap_start_address as u32;
+        // SharedMemory len is in bytes
+        let mem_size = data.shared_memory.data().len() as u32;
+
+        let mut heap = data.heap.lock().unwrap();
+        if heap.free_blocks.is_empty() {
+            heap.dealloc(heap_start, mem_size - heap_start);
+        }
+    }
+
+    /// Loads `wasm_bytes` as plugin `name`. The IDs it gets from host_register_id come from the
+    /// registry every plugin shares (host_calls/ids.rs), so they match other plugins' for a name.
     pub fn load_plugin(&mut self, name: &str, wasm_bytes: &[u8]) -> Result<Instance> {
+        self.load(name, wasm_bytes, None)
+    }
+
+    /// load_plugin for a plugin that may only import what `manifest` allows (host/manifest.rs).
+    pub fn load_plugin_with_manifest(
+        &mut self,
+        name: &str,
+        wasm_bytes: &[u8],
+        manifest: &PluginManifest,
+    ) -> Result<Instance> {
+        self.load(name, wasm_bytes, Some(manifest))
+    }
+
+    /// Loads the plugin at `path`, with the manifest beside it if there is one. `path` may
+    /// also be a plugin directory (load_plugin_from_dir), loaded as `name`.
+    pub fn load_plugin_file(&mut self, name: &str, path: &Path) -> Result<Instance> {
+        if path.is_dir() {
+            let (manifest, wasm) = PluginManifest::in_dir(path)?;
+            let wasm_bytes = std::fs::read(&wasm)
+                .with_context(|| format!("Failed to read '{}'", wasm.display()))?;
+            return self.load_plugin_with_manifest(name, &wasm_bytes, &manifest);
+        }
+        let wasm_bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+        match PluginManifest::beside(path)? {
+            Some(manifest) => self.load_plugin_with_manifest(name, &wasm_bytes, &manifest),
+            None => self.load_plugin(name, &wasm_bytes),
+        }
+    }
+
+    /// Loads the plugin in `dir`, as its plugin.toml describes it (host/manifest.rs). Answers
+    /// the name it was loaded as, the manifest's.
+    pub fn load_plugin_from_dir(&mut self, dir: &Path) -> Result<String> {
+        let (manifest, wasm) = PluginManifest::in_dir(dir)?;
+        let name = manifest.name.clone().unwrap_or_default();
+        let wasm_bytes =
+            std::fs::read(&wasm).with_context(|| format!("Failed to read '{}'", wasm.display()))?;
+        self.load_plugin_with_manifest(&name, &wasm_bytes, &manifest)?;
+        Ok(name)
+    }
+
+    /// The manifest `plugin` was loaded with, if any.
+    pub fn manifest(&self, plugin: &str) -> Option<&PluginManifest> {
+        self.store.data().manifests.get(plugin)
+    }
+
+    /// Unloads `name`: its instance and table are forgotten, every host heap block it took with
+    /// host_alloc is freed, and its slot is zeroed and handed to the next plugin loaded. Its
+    /// exports trap from then on, in the linker and in the tables other plugins linked them
+    /// into. Plugins that imported its exports when they were instantiated hold them directly,
+    /// so they have to be unloaded first. (The instance itself stays in the store, which can't
+    /// drop one, but nothing reaches it.)
+    pub fn unload_plugin(&mut self, name: &str) -> Result<()> {
+        crate::scope!("unload_plugin", name);
+        let state = self.store.data();
+        if !state.instances.contains_key(name) {
+            return Err(anyhow!("'{}' isn't loaded", name));
+        }
+        let mut importers: Vec<String> = state
+            .imported
+            .iter()
+            .filter(|(plugin, imports)| {
+                *plugin != name && imports.iter().any(|(provider, _)| provider == name)
+            })
+            .map(|(plugin, _)| format!("'{}'", plugin))
+            .collect();
+        if !importers.is_empty() {
+            importers.sort();
+            return Err(anyhow!(
+                "'{}' is imported by {}, unload those first",
+                name,
+                importers.join(", ")
+            ));
+        }
+        self.teardown(name)
+    }
+
+    // Everything of `name`'s, loaded or partly (load undoes a failed load with it)
+    fn teardown(&mut self, name: &str) -> Result<()> {
+        self.stub_exports(name)?;
+        let (gone, kept): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.store.data_mut().linked_entries)
+                .into_iter()
+                .partition(|(provider, caller, _)| provider == name || caller == name);
+        self.store.data_mut().linked_entries = kept;
+        for (_, caller, idx) in gone.into_iter().filter(|(provider, _, _)| provider == name) {
+            let Some(table) = self.store.data().tables.get(&caller).copied() else {
+                continue;
+            };
+            if let Some(Ref::Func(Some(func))) = table.get(&mut self.store, idx) {
+                let stub = unloaded_stub(&mut self.store, func, name);
+                table.set(&mut self.store, idx, Ref::Func(Some(stub)))?;
+            }
+        }
+        let instance = self.store.data().instances.get(name).copied();
+        let was_kernel = instance.is_some_and(|i| {
+            i.get_func(&mut self.store, "sys_register_component")
+                .is_some()
+        });
+
+        let state = self.store.data_mut();
+        state.instances.remove(name);
+        state.tables.remove(name);
+        state.modules.retain(|(plugin, _)| plugin != name);
+        state.imported.remove(name);
+        state.plugin_capabilities.remove(name);
+        state.save_versions.remove(name);
+        state.manifests.remove(name);
+        state.bus.lock().unwrap().drop_to(name);
+        let instances: Vec<Instance> = state.instances.values().copied().collect();
+        if was_kernel
+            && !instances.iter().any(|i| {
+                i.get_func(&mut self.store, "sys_register_component")
+                    .is_some()
+            })
+        {
+            self.store.data_mut().capabilities &= !CAPABILITY_ECS_KERNEL;
+        }
+
+        let state = self.store.data_mut();
+        let blocks = state.metrics.lock().unwrap().release(name);
+        let mut heap = state.heap.lock().unwrap();
+        for (ptr, size) in blocks {
+            heap.dealloc(ptr, size);
+        }
+        drop(heap);
+        // A reloaded plugin holds several slots, all of them its
+        let slots: Vec<i32> = state
+            .slots
+            .iter()
+            .filter(|(plugin, _)| plugin == name)
+            .map(|(_, base)| *base)
+            .collect();
+        state.slots.retain(|(plugin, _)| plugin != name);
+        self.free_slots(slots)
+    }
+
+    // What `name` put in the global linker traps from now on, and isn't its any more
+    fn stub_exports(&mut self, name: &str) -> Result<()> {
+        let exports: Vec<String> = self
+            .store
+            .data()
+            .env_exports
+            .iter()
+            .filter(|(_, plugin)| *plugin == name)
+            .map(|(export, _)| export.clone())
+            .collect();
+        for export in exports {
+            self.store.data_mut().env_exports.remove(&export);
+            if let Some(Extern::Func(func)) = self.linker.get(&mut self.store, "env", &export) {
+                let stub = unloaded_stub(&mut self.store, func, name);
+                self.linker.define(&self.store, "env", &export, stub)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Zeroed, as the next plugin's static data past its data segments is expected to be
+    fn free_slots(&mut self, slots: Vec<i32>) -> Result<()> {
+        let zeros = vec![0; self.store.data().slot_size as usize];
+        for &base in &slots {
+            self.write_mem(base, &zeros)?;
+        }
+        self.store.data_mut().free_slots.extend(slots);
+        Ok(())
+    }
+
+    fn load(
+        &mut self,
+        name: &str,
+        wasm_bytes: &[u8],
+        manifest: Option<&PluginManifest>,
+    ) -> Result<Instance> {
+        crate::scope!("load_plugin", name);
+        if RESERVED.contains(&name) {
+            return Err(anyhow!(
+                "'{}' is the host's, load the plugin under another name",
+                name
+            ));
+        }
         // println!("📦 [HOST] Loading Plugin: {}", name);
-        let module = Module::new(&self.engine, wasm_bytes)?;
-        let instance_linker = self.prepare_env(name)?;
-        let instance = instance_linker.instantiate(&mut self.store, &module)?;
+        let module = self.compile(name, wasm_bytes)?;
+        if let Some(manifest) = manifest {
+            manifest.check(name, &module)?;
+            self.check_manifest(name, wasm_bytes, manifest)?;
+        }
+        // Nothing of a plugin that fails from here on is left behind, its slot included: a new one
+        // goes the way unloaded ones do, a reload falls back to the instance it was to replace
+        let replaced = Replaced::of(self.store.data(), name);
+        let loaded = self.instantiate(name, &module, manifest);
+        if loaded.is_err() {
+            let undone = match replaced.instance {
+                Some(_) => self.restore(name, replaced),
+                None => self.teardown(name),
+            };
+            if let Err(e) = undone {
+                let text = format!(
+                    "Failed to clean up after '{}' failed to load: {:#}",
+                    name, e
+                );
+                self.store
+                    .data()
+                    .logger
+                    .lock()
+                    .unwrap()
+                    .log("host", Level::Warn, &text);
+            }
+        }
+        loaded
+    }
+
+    fn instantiate(
+        &mut self,
+        name: &str,
+        module: &Module,
+        manifest: Option<&PluginManifest>,
+    ) -> Result<Instance> {
+        let instance_linker = self.prepare_env(name, module, manifest)?;
+        self.store
+            .data_mut()
+            .modules
+            .push((name.to_string(), module.clone()));
+        let instance = instance_linker.instantiate(&mut self.store, module)?;
+        let state = self.store.data_mut();
+        let imported = module
+            .imports()
+            .filter(|import| import.module() == "env")
+            .filter_map(|import| {
+                Some((
+                    state.env_exports.get(import.name())?.clone(),
+                    import.name().to_string(),
+                ))
+            })
+            .filter(|(provider, _)| provider != name)
+            .collect();
+        state.imported.insert(name.to_string(), imported);
+        let abi = self.check_abi(name, instance)?;
+        self.check_layouts(name, instance)?;
+        // What its saves are written as (host_calls/saves.rs); constant, like __abi_version
+        let schema = match instance.get_typed_func::<(), i32>(&mut self.store, "__save_schema") {
+            Ok(func) => func.call(&mut self.store, ())? as u32,
+            Err(_) => 0,
+        };
+        self.store
+            .data_mut()
+            .save_versions
+            .insert(name.to_string(), SaveVersion { abi, schema });
+        if let Some(manifest) = manifest {
+            self.store
+                .data_mut()
+                .manifests
+                .insert(name.to_string(), manifest.clone());
+        }
 
         self.store
             .data_mut()
             .instances
-            .insert(name.to_string(), instance.clone());
+            .insert(name.to_string(), instance);
+
+        self.export_all(name, instance);
 
-        // Auto-Export
+        // Init
+        if let Some(func) = instance.get_func(&mut self.store, "__wasm_call_ctors") {
+            let func = func.typed::<(), ()>(&mut self.store)?;
+            self.profiled(name, "__wasm_call_ctors", |store| func.call(store, ()))?;
+        }
+        self.negotiate_capabilities(name, instance)?;
+        if let Some(func) = instance.get_func(&mut self.store, "init") {
+            let func = func.typed::<(), ()>(&mut self.store)?;
+            self.profiled(name, "init", |store| func.call(store, ()))?;
+        }
+
+        Ok(instance)
+    }
+
+    // Auto-Export
+    fn export_all(&mut self, name: &str, instance: Instance) {
         let exports: Vec<(String, Extern)> = instance
             .exports(&mut self.store)
             .map(|e| (e.name().to_string(), e.into_extern()))
             .collect();
 
         for (export_name, export_val) in exports {
-            let _ = self
+            let export_val = match export_val {
+                Extern::Func(func) if self.profile_syscalls && export_name.starts_with("sys_") => {
+                    Extern::Func(self.timed_func(name, &export_name, func))
+                }
+                other => other,
+            };
+            if self
                 .linker
-                .define(&self.store, "env", &export_name, export_val);
+                .define(&self.store, "env", &export_name, export_val)
+                .is_ok()
+            {
+                self.store
+                    .data_mut()
+                    .env_exports
+                    .insert(export_name, name.to_string());
+            }
         }
+    }
 
-        // Init
-        if let Some(func) = instance.get_func(&mut self.store, "__wasm_call_ctors") {
-            func.typed::<(), ()>(&mut self.store)?
-                .call(&mut self.store, ())?;
+    // A failed reload: what the new instance added goes, and what it replaced comes back
+    fn restore(&mut self, name: &str, replaced: Replaced) -> Result<()> {
+        self.stub_exports(name)?;
+        let state = self.store.data_mut();
+        state.modules.truncate(replaced.modules);
+        state.linked_entries.truncate(replaced.linked_entries);
+        state.capabilities = replaced.capabilities;
+        put_back(&mut state.instances, name, replaced.instance);
+        put_back(&mut state.tables, name, replaced.table);
+        put_back(&mut state.imported, name, replaced.imported);
+        put_back(
+            &mut state.plugin_capabilities,
+            name,
+            replaced.plugin_capabilities,
+        );
+        put_back(&mut state.save_versions, name, replaced.save_version);
+        put_back(&mut state.manifests, name, replaced.manifest);
+        let live = state.metrics.lock().unwrap().live_allocations();
+        for (ptr, (owner, _)) in live {
+            if owner == name && !replaced.blocks.contains(&ptr) {
+                allocator::free_owned(state, name, ptr as i32)?;
+            }
         }
-        if let Some(func) = instance.get_func(&mut self.store, "init") {
-            func.typed::<(), ()>(&mut self.store)?
-                .call(&mut self.store, ())?;
+        let slots = state
+            .slots
+            .drain(replaced.slots..)
+            .map(|(_, base)| base)
+            .collect();
+        self.free_slots(slots)?;
+        if let Some(instance) = replaced.instance {
+            self.export_all(name, instance);
         }
+        Ok(())
+    }
 
-        Ok(instance)
+    // From the module cache if it has it; what's compiled goes in, or is logged when it can't
+    fn compile(&self, name: &str, wasm_bytes: &[u8]) -> Result<Module> {
+        let Some(cache) = &self.module_cache else {
+            return Module::new(&self.engine, wasm_bytes);
+        };
+        if let Some(module) = cache.get(&self.engine, wasm_bytes) {
+            return Ok(module);
+        }
+        let module = Module::new(&self.engine, wasm_bytes)?;
+        if let Err(e) = cache.put(wasm_bytes, &module) {
+            let text = format!("Failed to cache '{}' compiled: {:#}", name, e);
+            self.store
+                .data()
+                .logger
+                .lock()
+                .unwrap()
+                .log("host", Level::Warn, &text);
+        }
+        Ok(module)
+    }
+
+    // What the manifest asks of the host, before the plugin takes a slot: room in it, the
+    // imports it requires, and the exports it links already loaded
+    fn check_manifest(
+        &mut self,
+        name: &str,
+        wasm_bytes: &[u8],
+        manifest: &PluginManifest,
+    ) -> Result<()> {
+        let state = self.store.data();
+        let stack_room = state.slot_size - state.data_size - 16;
+        manifest.check_memory(name, wasm_bytes, state.data_size as u32, stack_room as u32)?;
+
+        let own = self.own_calls(name)?;
+        let missing: Vec<String> = manifest
+            .required()
+            .filter(|&(module, import)| match module {
+                "env" => {
+                    !ABI_IMPORTS.contains(&import)
+                        && self.linker.get(&mut self.store, module, import).is_none()
+                        && own.get(&mut self.store, module, import).is_none()
+                }
+                WASI_MODULE => self.wasi.is_none(),
+                _ => true,
+            })
+            .map(|(module, import)| format!("{}::{}", module, import))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "'{}' requires what this host doesn't have: {}",
+                name,
+                missing.join(", ")
+            ));
+        }
+
+        let unlinkable: Vec<String> = manifest
+            .links()
+            .filter(|&(provider, export)| {
+                let instance = self.store.data().instances.get(provider).copied();
+                instance
+                    .and_then(|instance| instance.get_func(&mut self.store, export))
+                    .is_none()
+            })
+            .map(|(provider, export)| format!("{}::{}", provider, export))
+            .collect();
+        if !unlinkable.is_empty() {
+            return Err(anyhow!(
+                "'{}' links what isn't loaded: {}",
+                name,
+                unlinkable.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    // Before any plugin code runs: one built against other layouts would scribble over memory.
+    // Answers the plugin's ABI version.
+    fn check_abi(&mut self, name: &str, instance: Instance) -> Result<i32> {
+        let (min, max) = (*self.abi_versions.start(), *self.abi_versions.end());
+        let supported = if min == max {
+            format!("ABI v{}", min)
+        } else {
+            format!("ABI v{} to v{}", min, max)
+        };
+
+        let version = instance
+            .get_typed_func::<(), i32>(&mut self.store, "__abi_version")
+            .map_err(|_| {
+                anyhow!(
+                    "❌ Plugin '{}' has no `__abi_version() -> i32` export. Rebuild it with \
+                     fat_ptr::export_abi_version!() (this host supports {})",
+                    name,
+                    supported
+                )
+            })?
+            .call(&mut self.store, ())?;
+        if !self.abi_versions.contains(&version) {
+            return Err(anyhow!(
+                "❌ Plugin '{}' was built for ABI v{}, this host supports {}. Rebuild it against this host's crates",
+                name,
+                version,
+                supported
+            ));
+        }
+        Ok(version)
+    }
+
+    // Optional export, but a plugin that has it and disagrees never runs
+    fn check_layouts(&mut self, name: &str, instance: Instance) -> Result<()> {
+        let Ok(get_layouts) =
+            instance.get_typed_func::<(), i64>(&mut self.store, "__layout_fingerprints")
+        else {
+            return Ok(());
+        };
+        let FatPtr { ptr, len } = FatPtr::unpack(get_layouts.call(&mut self.store, ())?);
+        let bytes = self.read_mem(ptr, len * LayoutEntry::SIZE as i32)?;
+        let drifted = layouts::mismatches(&LayoutEntry::parse_all(&bytes));
+        if !drifted.is_empty() {
+            return Err(anyhow!(
+                "❌ Plugin '{}' was built with a different layout for {}. Rebuild it against this host's crates",
+                name,
+                drifted.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn negotiate_capabilities(&mut self, name: &str, instance: Instance) -> Result<()> {
+        if instance
+            .get_func(&mut self.store, "sys_register_component")
+            .is_some()
+        {
+            self.store.data_mut().capabilities |= CAPABILITY_ECS_KERNEL;
+        }
+
+        let Ok(get_capabilities) =
+            instance.get_typed_func::<(), i64>(&mut self.store, "get_capabilities")
+        else {
+            return Ok(());
+        };
+        let wanted = get_capabilities.call(&mut self.store, ())? as u64;
+        let state = self.store.data_mut();
+        state.plugin_capabilities.insert(name.to_string(), wanted);
+
+        // Not fatal: plugins are expected to check host_capabilities and make do
+        let missing = wanted & !state.capabilities;
+        if missing != 0 {
+            let text = format!(
+                "Plugin '{}' uses capabilities this host lacks: {}",
+                name,
+                capability_names(missing).join(", ")
+            );
+            state.logger.lock().unwrap().log("host", Level::Warn, &text);
+        }
+        Ok(())
+    }
+
+    /// Same IDs plugins get from host_register_id, for host code reading their data.
+    pub fn register_id(&self, namespace: &str, name: &str) -> i32 {
+        self.store
+            .data()
+            .ids
+            .lock()
+            .unwrap()
+            .register(namespace, name)
+    }
+
+    /// Same handles plugins get from host_intern.
+    pub fn intern(&self, s: &str) -> u32 {
+        self.store.data().strings.lock().unwrap().intern(s)
+    }
+
+    pub fn resolve(&self, id: u32) -> Option<String> {
+        self.store
+            .data()
+            .strings
+            .lock()
+            .unwrap()
+            .resolve(id)
+            .map(str::to_string)
+    }
+
+    /// Runs `call`, a call into `plugin`'s `export`, and records how long it took (failed or not).
+    /// Embedders calling plugins themselves go through this so their calls show up in
+    /// `profile_report`, and traps come back with a symbolicated backtrace (see backtrace.rs).
+    pub fn profiled<R>(
+        &mut self,
+        plugin: &str,
+        export: &str,
+        call: impl FnOnce(&mut Store<HostState>) -> Result<R>,
+    ) -> Result<R> {
+        if let Some(fuel) = self.fuel {
+            self.store.set_fuel(fuel)?;
+        }
+        // The tick deadline, when it's the tighter of the two
+        let deadline = self
+            .tick_deadline(plugin, export)
+            .filter(|deadline| self.timeout.is_none_or(|t| *deadline < t));
+        let limit = deadline.or(self.timeout);
+        if let Some(limit) = limit {
+            self.store
+                .set_epoch_deadline((limit.as_nanos() / EPOCH_TICK.as_nanos()).max(1) as u64);
+        }
+        let start = Instant::now();
+        let result = call(&mut self.store);
+        let took = start.elapsed();
+        self.store
+            .data()
+            .profiler
+            .lock()
+            .unwrap()
+            .record(plugin, export, took);
+
+        if self.fuel.is_some() {
+            self.store.set_fuel(UNLIMITED)?;
+        }
+        if limit.is_some() {
+            self.store.set_epoch_deadline(UNLIMITED);
+        }
+        let mut past_deadline = false;
+        if let Err(e) = &result {
+            let over = match e.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => self.fuel.map(|fuel| (RESOURCE_FUEL, fuel)),
+                Some(Trap::Interrupt) if deadline.is_some() => {
+                    past_deadline = true;
+                    None
+                }
+                Some(Trap::Interrupt) => Some((RESOURCE_TIME, took.as_micros() as u64)),
+                _ => None,
+            };
+            if let Some((resource, used)) = over {
+                self.store
+                    .data()
+                    .quotas
+                    .lock()
+                    .unwrap()
+                    .went_over(plugin, resource, used);
+            }
+        }
+        result.map_err(|e| {
+            let e = backtrace::symbolicate(e, &self.store.data().modules);
+            match deadline.filter(|_| past_deadline) {
+                Some(deadline) => e.context(format!(
+                    "'{}' tick budget exceeded: still running after {:.2?}",
+                    plugin, deadline
+                )),
+                None => e,
+            }
+        })
+    }
+
+    /// How long a call to `plugin`'s `export` may run before it's interrupted as a runaway
+    /// tick, if it's a `tick` (BlindHostConfig::tick_deadlines).
+    pub fn tick_deadline(&self, plugin: &str, export: &str) -> Option<Duration> {
+        if export != "tick" {
+            return None;
+        }
+        self.tick_deadlines
+            .get(plugin)
+            .or_else(|| self.tick_deadlines.get("*"))
+            .copied()
+    }
+
+    /// Whether `plugin` is to be ticked: neither throttled nor suspended by its quotas
+    /// (quotas.rs). Embedders ticking plugins themselves skip their tick when it isn't.
+    pub fn may_tick(&self, plugin: &str) -> bool {
+        self.store.data().quotas.lock().unwrap().may_tick(plugin)
+    }
+
+    /// Lets a plugin its quotas suspended run again. Answers whether it was suspended.
+    pub fn resume(&mut self, plugin: &str) -> bool {
+        let event = self.store.data().quotas.lock().unwrap().resume(plugin);
+        let Some(event) = event else {
+            return false;
+        };
+        self.quota_event(&event);
+        true
+    }
+
+    // Logs a quota event as the host's and tells every other plugin that listens
+    fn quota_event(&mut self, event: &QuotaEvent) {
+        let text = self.store.data().quotas.lock().unwrap().describe(event);
+        self.store
+            .data()
+            .logger
+            .lock()
+            .unwrap()
+            .log("host", Level::Warn, &text);
+        let plugins: Vec<(String, Instance)> = self
+            .store
+            .data()
+            .instances
+            .iter()
+            .filter(|(name, _)| **name != event.plugin)
+            .map(|(name, instance)| (name.clone(), *instance))
+            .collect();
+        for (name, instance) in plugins {
+            if instance.get_func(&mut self.store, "on_message").is_none() {
+                continue;
+            }
+            let envelope = Envelope {
+                sender: QUOTAS.to_string(),
+                ..Envelope::event(event.encode())
+            };
+            self.store.data().bus.lock().unwrap().push(name, envelope);
+        }
+    }
+
+    /// Rolling averages, p99 and max of every plugin call timed so far, slowest first.
+    pub fn profile_report(&self) -> ProfileReport {
+        self.store.data().profiler.lock().unwrap().report()
+    }
+
+    /// Who links against and calls whom, as Graphviz (see call_graph.rs).
+    pub fn call_graph_dot(&self) -> String {
+        self.store.data().call_graph.lock().unwrap().to_dot()
+    }
+
+    /// The same as JSON, with every link and remembered call.
+    pub fn call_graph_json(&self) -> serde_json::Value {
+        self.store.data().call_graph.lock().unwrap().to_json()
+    }
+
+    /// The last `count` plugin log lines that went to the pane sink, oldest first.
+    pub fn recent_logs(&self, count: usize) -> Vec<LogLine> {
+        let logger = self.store.data().logger.lock().unwrap();
+        let recent = logger.recent();
+        let skip = recent.len().saturating_sub(count);
+        recent.skip(skip).cloned().collect()
     }
 
-    fn prepare_env(&mut self, name: &str) -> Result<Linker<HostState>> {
+    // `func`, timed as plugin::export on every call
+    fn timed_func(&mut self, plugin: &str, export: &str, func: Func) -> Func {
+        let ty = func.ty(&self.store);
+        let (plugin, export) = (plugin.to_string(), export.to_string());
+        Func::new(&mut self.store, ty, move |mut caller, params, results| {
+            let start = Instant::now();
+            let result = func.call(&mut caller, params, results);
+            caller
+                .data()
+                .profiler
+                .lock()
+                .unwrap()
+                .record(&plugin, &export, start.elapsed());
+            result
+        })
+    }
+
+    /// CAPABILITY_* bits the host currently provides.
+    pub fn capabilities(&self) -> u64 {
+        self.store.data().capabilities
+    }
+
+    /// What `name` reported through `get_capabilities`, `None` if it doesn't export it.
+    pub fn plugin_capabilities(&self, name: &str) -> Option<u64> {
+        self.store.data().plugin_capabilities.get(name).copied()
+    }
+
+    fn prepare_env(
+        &mut self,
+        name: &str,
+        module: &Module,
+        manifest: Option<&PluginManifest>,
+    ) -> Result<Linker<HostState>> {
         let state = self.store.data();
-        let slot_base = state.next_memory_offset;
+        // An unloaded plugin's slot, lowest first, before a new one
+        let reused = state.free_slots.iter().copied().min();
+        let slot_base = reused.unwrap_or(state.next_memory_offset);
         let slot_size = state.slot_size;
         let heap_limit = state.heap_start_address;
 
@@ -171,12 +1069,25 @@ impl BlindHost {
         let my_stack_top = slot_base + slot_size - 16;
 
         // Advance Pointers
-        self.store.data_mut().next_memory_offset += slot_size;
+        match reused {
+            Some(base) => self
+                .store
+                .data_mut()
+                .free_slots
+                .retain(|free| *free != base),
+            None => self.store.data_mut().next_memory_offset += slot_size,
+        }
+        self.store
+            .data_mut()
+            .slots
+            .push((name.to_string(), slot_base));
 
         // println!("       ├── Slot Base:  {:#X}", slot_base);
         // println!("       └── Stack Top:  {:#X}", my_stack_top);
 
-        let mut linker = self.linker.clone();
+        // What's this plugin's own goes in a linker of its own, then into the one it's instantiated
+        // with: a copy of the global one, or for plugins with a manifest, only what it allows
+        let mut linker = self.own_calls(name)?;
 
         // 1. Table
         let table = Table::new(
@@ -209,70 +1120,32 @@ impl BlindHost {
         )?;
         linker.define(&self.store, "env", "__table_base", g_tbl)?;
 
-        // 3. Host Link Call
-        let caller_name = name.to_string();
-
-        linker.func_wrap(
-            "env",
-            "host_link_call",
-            move |mut c: Caller<'_, HostState>,
-                  provider_mod_ptr: i32,
-                  provider_mod_len: i32,
-                  provider_fn_ptr: i32,
-                  provider_fn_len: i32|
-                  -> Result<i32> {
-                // --- SAFE STRING READ ---
-                // We access memory directly to replicate your working logic,
-                // but we do it safely inside the closure.
-                let (provider_mod, provider_func) = {
-                    let mem = c.data().shared_memory.data();
-                    let base = mem.as_ptr() as *const u8;
-                    unsafe {
-                        (
-                            String::from_utf8_lossy(std::slice::from_raw_parts(
-                                base.add(provider_mod_ptr as usize),
-                                provider_mod_len as usize,
-                            ))
-                            .to_string(),
-                            String::from_utf8_lossy(std::slice::from_raw_parts(
-                                base.add(provider_fn_ptr as usize),
-                                provider_fn_len as usize,
-                            ))
-                            .to_string(),
-                        )
-                    }
-                };
-
-                // Logic to find instance and function
-                let provider_instance = c
-                    .data()
-                    .instances
-                    .get(&provider_mod)
-                    .ok_or(anyhow!("Provider '{}' not found", provider_mod))?
-                    .clone();
-
-                let func = provider_instance
-                    .get_func(&mut c, &provider_func)
-                    .ok_or(anyhow!("Export '{}' not found", provider_func))?;
-
-                let caller_table = c
-                    .data()
-                    .tables
-                    .get(&caller_name)
-                    .ok_or(anyhow!("Table for '{}' not found", caller_name))?
-                    .clone();
-
-                let new_idx = caller_table.size(&mut c);
-                caller_table.grow(&mut c, 1, Ref::Func(Some(func)))?;
-
-                // println!(
-                //     "🔗 [HOST] Linked {}::{} -> {}::Table[{}]",
-                //     provider_mod, provider_func, caller_name, new_idx
-                // );
-                Ok(new_idx as i32)
-            },
-        )?;
+        let mut instance_linker = match manifest {
+            Some(_) => {
+                let mut allowed = Linker::new(&self.engine);
+                allowed.allow_shadowing(true);
+                copy_linked(&mut self.store, &self.linker, &mut allowed, manifest)?;
+                allowed
+            }
+            None => self.linker.clone(),
+        };
+        copy_linked(&mut self.store, &linker, &mut instance_linker, manifest)?;
+        // Straight into the one it's instantiated with: manifests have had their say in check
+        if let Some(options) = &self.wasi {
+            wasi::register_host_calls(&mut instance_linker, name.to_string(), options, module)?;
+        }
+        Ok(instance_linker)
+    }
 
+    // The host calls stamped with `name`: the host's own and the embedder's
+    fn own_calls(&mut self, name: &str) -> Result<Linker<HostState>> {
+        let mut linker = Linker::new(&self.engine);
+        linker.allow_shadowing(true);
+        register_plugin_calls(&mut linker, name)?;
+        (self.plugin_calls)(&mut linker, name)?;
+        if self.deterministic {
+            forbid_nondeterministic(&mut linker, &mut self.store);
+        }
         Ok(linker)
     }
 
@@ -282,55 +1155,579 @@ impl BlindHost {
             .data()
             .instances
             .get(module_name)
-            .ok_or(anyhow!("Instance not found"))?
-            .clone();
+            .copied()
+            .ok_or(anyhow!("Instance not found"))?;
         instance
             .get_func(&mut self.store, func_name)
             .ok_or(anyhow!("Function not found"))
     }
 
     pub fn read_mem(&mut self, ptr: i32, len: i32) -> Result<Vec<u8>> {
-        // 1. Get the shared memory handle from the store data
-        let memory = &self.store.data().shared_memory;
+        crate::scope!("read_mem");
+        Ok(self.view_mem(ptr, len)?.to_vec())
+    }
 
-        // 2. Get the raw data (In Wasmtime 21+, this returns &[UnsafeCell<u8>])
-        let data_cells = memory.data();
+    /// Borrows `len` bytes of shared memory at `ptr` without copying.
+    /// The borrow keeps `&mut self` (and so any guest call) out until the view is dropped.
+    pub fn view_mem(&self, ptr: i32, len: i32) -> Result<&[u8]> {
+        let memory = SharedSlice::of(&self.store.data().shared_memory);
+        if ptr < 0 || len < 0 {
+            anyhow::bail!(
+                "Memory access with negative pointer or length: {} / {}",
+                ptr,
+                len
+            );
+        }
+        // Safety: plugins only run through &mut self, which the borrow keeps out
+        unsafe { memory.view(ptr, len) }.ok_or_else(|| {
+            anyhow!(
+                "Memory access out of bounds: {} > {}",
+                ptr as usize + len as usize,
+                memory.len()
+            )
+        })
+    }
 
-        // 3. SAFETY: Cast UnsafeCell<u8> to u8.
-        // This is safe because our Host is single-threaded relative to the WASM execution.
-        let data: &[u8] = unsafe {
-            std::slice::from_raw_parts(data_cells.as_ptr() as *const u8, data_cells.len())
-        };
+    /// `view_mem` for `count` values of `T`. Fails if `ptr` isn't aligned for `T`.
+    pub fn view_slice<T: bytemuck::Pod>(&self, ptr: i32, count: usize) -> Result<&[T]> {
+        let bytes = count.checked_mul(std::mem::size_of::<T>()).ok_or(anyhow!(
+            "Cannot view {} values at {}",
+            count,
+            ptr
+        ))?;
+        let len = i32::try_from(bytes)?;
+        bytemuck::try_cast_slice(self.view_mem(ptr, len)?).map_err(|e| {
+            anyhow!(
+                "Cannot view memory at {} as {}: {}",
+                ptr,
+                std::any::type_name::<T>(),
+                e
+            )
+        })
+    }
 
-        // 4. Perform bounds checking
-        let start = ptr as usize;
-        let end = start + len as usize;
+    pub fn write_mem(&mut self, ptr: i32, data: &[u8]) -> Result<()> {
+        if ptr < 0 {
+            anyhow::bail!("Memory write with negative pointer: {}", ptr);
+        }
+        if !SharedSlice::of(&self.store.data().shared_memory).write(ptr, data) {
+            anyhow::bail!("Memory write out of bounds");
+        }
+        Ok(())
+    }
 
-        if end > data.len() {
-            anyhow::bail!("Memory access out of bounds: {} > {}", end, data.len());
+    /// FNV-1a of all of shared memory: every plugin's data and stack and the host heap. Two
+    /// deterministic runs fed the same input end with the same hash, on any machine.
+    pub fn state_hash(&self) -> u64 {
+        let memory = SharedSlice::of(&self.store.data().shared_memory);
+        memory.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+
+    /// Books a tick that `took` this long in the metrics and the heap timeline. Answers whether
+    /// the tick went over the tick budget, in which case the exports that ran in it are logged
+    /// as the host's, costliest first. Embedders call it once per tick.
+    pub fn end_tick(&mut self, took: Duration) -> bool {
+        let state = self.store.data();
+        state.metrics.lock().unwrap().record_tick(took);
+        self.sample_heap();
+        let exports = state.profiler.lock().unwrap().take_tick();
+
+        let mut cpu: HashMap<String, Duration> = HashMap::new();
+        for tick in &exports {
+            *cpu.entry(tick.entry.plugin.clone()).or_default() += tick.total;
+        }
+        let events = state.quotas.lock().unwrap().end_tick(&cpu);
+        for event in &events {
+            self.quota_event(event);
         }
 
-        // 5. Copy the data
-        Ok(data[start..end].to_vec())
+        let state = self.store.data();
+        let Some(budget) = self.tick_budget.filter(|budget| took > *budget) else {
+            return false;
+        };
+
+        state.metrics.lock().unwrap().slow_tick();
+        let mut logger = state.logger.lock().unwrap();
+        logger.log(
+            "host",
+            Level::Warn,
+            &format!("Tick took {:.2?}, over the {:.2?} budget", took, budget),
+        );
+        for tick in exports.iter().take(SLOW_TICK_EXPORTS) {
+            logger.log(
+                "host",
+                Level::Warn,
+                &format!(
+                    "  {}::{} {:.2?} in {} calls (recently {:.2?} avg, {:.2?} p99)",
+                    tick.entry.plugin,
+                    tick.entry.export,
+                    tick.total,
+                    tick.calls,
+                    tick.entry.avg,
+                    tick.entry.p99
+                ),
+            );
+        }
+        true
     }
 
-    pub fn write_mem(&mut self, ptr: i32, data: &[u8]) -> Result<()> {
-        let memory = &self.store.data().shared_memory;
-        let mem_cells = memory.data();
+    /// The host_asserts that failed since the last call, oldest first.
+    pub fn take_assert_failures(&self) -> Vec<AssertFailure> {
+        std::mem::take(&mut *self.store.data().assert_failures.lock().unwrap())
+    }
+
+    /// Adds the host heap's current state to the heap timeline, if the interval has passed.
+    /// end_tick does, once per tick.
+    pub fn sample_heap(&self) {
+        let state = self.store.data();
+        let heap_size = state
+            .shared_memory
+            .data()
+            .len()
+            .saturating_sub(state.heap_start_address as usize);
+        let heap = state.heap.lock().unwrap();
+        state
+            .heap_timeline
+            .lock()
+            .unwrap()
+            .sample(&heap, heap_size as u64);
+    }
+
+    /// Copies out a packed (len << 32 | ptr) response from `module`, then hands the buffer back
+    /// through the module's `free_response(ptr, len)` export if it has one.
+    pub fn take_response(&mut self, module_name: &str, packed: i64) -> Result<Vec<u8>> {
+        let FatPtr { ptr, len } = FatPtr::unpack(packed);
+        let bytes = self.read_mem(ptr, len)?;
 
-        // Safety: Cast to mutable u8 slice
-        let mem_slice: &mut [u8] = unsafe {
-            std::slice::from_raw_parts_mut(mem_cells.as_ptr() as *mut u8, mem_cells.len())
+        if let Ok(free) = self.get_func(module_name, "free_response") {
+            self.profiled(module_name, "free_response", |store| {
+                free.call(store, &[Val::I32(ptr), Val::I32(len)], &mut [])
+            })?;
+        }
+        Ok(bytes)
+    }
+}
+
+// Resolves provider::export and appends it to the caller's table, answering the index
+fn link_export(
+    c: &mut Caller<'_, HostState>,
+    caller_name: &str,
+    (provider_mod_ptr, provider_mod_len, provider_fn_ptr, provider_fn_len): (i32, i32, i32, i32),
+    checked: bool,
+) -> Result<i32> {
+    crate::scope!("host_link_call", caller_name);
+    let read = |c: &Caller<'_, HostState>, ptr, len| {
+        validate_guest_range(c, caller_name, "host_link_call", ptr, len)?;
+        read_guest(c, ptr, len)
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .ok_or(anyhow!(
+                "'{}' passed a name outside shared memory",
+                caller_name
+            ))
+    };
+    let provider_mod = read(c, provider_mod_ptr, provider_mod_len)?;
+    let provider_func = read(c, provider_fn_ptr, provider_fn_len)?;
+
+    let linked = link_resolved(
+        c,
+        caller_name,
+        provider_mod.clone(),
+        provider_func.clone(),
+        checked,
+    );
+    let entry = AuditEntry {
+        event: if checked { "link_checked" } else { "link" },
+        caller: caller_name,
+        callee: &provider_mod,
+        export: &provider_func,
+        payload: None,
+        status: if linked.is_ok() {
+            audit::OK
+        } else {
+            audit::REFUSED
+        },
+    };
+    audit(c.data(), entry);
+    linked
+}
+
+fn link_resolved(
+    c: &mut Caller<'_, HostState>,
+    caller_name: &str,
+    provider_mod: String,
+    provider_func: String,
+    checked: bool,
+) -> Result<i32> {
+    let provider_instance = c
+        .data()
+        .instances
+        .get(&provider_mod)
+        .copied()
+        .ok_or(anyhow!("Provider '{}' not found", provider_mod))?;
+
+    let func = provider_instance
+        .get_func(&mut *c, &provider_func)
+        .ok_or(anyhow!("Export '{}' not found", provider_func))?;
+
+    let caller_table = c
+        .data()
+        .tables
+        .get(caller_name)
+        .copied()
+        .ok_or(anyhow!("Table for '{}' not found", caller_name))?;
+
+    c.data()
+        .metrics
+        .lock()
+        .unwrap()
+        .linked(caller_name, &provider_mod);
+    let tracing = {
+        let mut graph = c.data().call_graph.lock().unwrap();
+        graph.linked(Link {
+            caller: caller_name.to_string(),
+            provider: provider_mod.clone(),
+            export: provider_func.clone(),
+            checked,
+        });
+        graph.enabled || c.data().audit.is_some()
+    };
+    let linked_to = provider_mod.clone();
+
+    let func = if checked {
+        trap_guard(&mut *c, func, caller_name, provider_mod, provider_func)?
+    } else if tracing {
+        traced(&mut *c, func, caller_name, provider_mod, provider_func)
+    } else {
+        func
+    };
+
+    let new_idx = caller_table.size(&mut *c);
+    caller_table.grow(&mut *c, 1, Ref::Func(Some(func)))?;
+    c.data_mut()
+        .linked_entries
+        .push((linked_to, caller_name.to_string(), new_idx));
+
+    // println!(
+    //     "🔗 [HOST] Linked {}::{} -> {}::Table[{}]",
+    //     provider_mod, provider_func, caller_name, new_idx
+    // );
+    Ok(new_idx as i32)
+}
+
+// Wraps an export answering i64 so a trap inside it becomes a STATUS_TRAPPED error envelope.
+// The call passes through the host anyway, so it's timed in the profile too.
+fn trap_guard(
+    c: &mut Caller<'_, HostState>,
+    func: Func,
+    caller_name: &str,
+    provider: String,
+    export: String,
+) -> Result<Func> {
+    let label = format!("{}::{}", provider, export);
+    let ty = func.ty(&*c);
+    if !matches!(ty.results().collect::<Vec<_>>()[..], [ValType::I64]) {
+        return Err(anyhow!("'{}' must answer i64 to be linked checked", label));
+    }
+    let caller_name = caller_name.to_string();
+    Ok(Func::new(
+        &mut *c,
+        ty,
+        move |mut caller, params, results| {
+            let start = Instant::now();
+            let result = func.call(&mut caller, params, results);
+            let status = audit::status(&result, results[0].i64());
+            record_linked_call(
+                &caller,
+                (&caller_name, &provider, &export),
+                params,
+                start.elapsed(),
+                status,
+            );
+            let Err(trap) = result else {
+                // The caller frees error envelopes (fat_ptr::envelope::open), so they're its from here on
+                if let Some(packed) = results[0]
+                    .i64()
+                    .filter(|&packed| envelope::is_error(packed))
+                {
+                    let envelope = envelope::unpack_error(packed);
+                    caller
+                        .data()
+                        .metrics
+                        .lock()
+                        .unwrap()
+                        .transferred(envelope.ptr as u32, &caller_name);
+                }
+                return Ok(());
+            };
+            let trap = backtrace::symbolicate(trap, &caller.data().modules);
+            let text = format!("'{}' trapped: {:?}", label, trap);
+            caller
+                .data()
+                .logger
+                .lock()
+                .unwrap()
+                .log("host", Level::Warn, &text);
+            let error = CallError {
+                status: STATUS_TRAPPED,
+                message: format!("{}: {:#}", label, trap),
+            }
+            .encode();
+            let ptr = alloc_owned(caller.data(), &caller_name, error.len() as i32);
+            results[0] = if ptr != 0 && write_guest(&caller, ptr, &error) {
+                Val::I64(envelope::pack_error(FatPtr::new(ptr, error.len() as i32)))
+            } else {
+                Val::I64(envelope::ERROR_BIT)
+            };
+            Ok(())
+        },
+    ))
+}
+
+// Replaces whichever NONDETERMINISTIC_CALLS the embedder registered with stubs that trap, so
+// plugins importing them still load but can't call them
+fn forbid_nondeterministic(linker: &mut Linker<HostState>, store: &mut Store<HostState>) {
+    for &name in NONDETERMINISTIC_CALLS {
+        let Some(Extern::Func(func)) = linker.get(&mut *store, "env", name) else {
+            continue;
         };
+        let ty = func.ty(&*store);
+        let stub = Func::new(&mut *store, ty, move |_, _, _| {
+            Err(anyhow!("'{}' isn't allowed in deterministic mode", name))
+        });
+        let _ = linker.define(&*store, "env", name, stub);
+    }
+}
 
-        let start = ptr as usize;
-        let end = start + data.len();
+// The host calls stamped with `name`, each plugin's own
+fn register_plugin_calls(linker: &mut Linker<HostState>, name: &str) -> Result<()> {
+    // Host Link Call
+    let caller_name = name.to_string();
 
-        if end > mem_slice.len() {
-            anyhow::bail!("Memory write out of bounds");
+    linker.func_wrap(
+        "env",
+        "host_link_call",
+        move |mut c: Caller<'_, HostState>,
+              provider_mod_ptr: i32,
+              provider_mod_len: i32,
+              provider_fn_ptr: i32,
+              provider_fn_len: i32|
+              -> Result<i32> {
+            let provider = (
+                provider_mod_ptr,
+                provider_mod_len,
+                provider_fn_ptr,
+                provider_fn_len,
+            );
+            link_export(&mut c, &caller_name, provider, false)
+        },
+    )?;
+
+    // Same, but traps in the export come back as error envelopes (fat_ptr::envelope)
+    let caller_name = name.to_string();
+    linker.func_wrap(
+        "env",
+        "host_link_call_checked",
+        move |mut c: Caller<'_, HostState>,
+              provider_mod_ptr: i32,
+              provider_mod_len: i32,
+              provider_fn_ptr: i32,
+              provider_fn_len: i32|
+              -> Result<i32> {
+            let provider = (
+                provider_mod_ptr,
+                provider_mod_len,
+                provider_fn_ptr,
+                provider_fn_len,
+            );
+            link_export(&mut c, &caller_name, provider, true)
+        },
+    )?;
+
+    // IDs, strings, message bus, logging, assertions and allocation, stamped with this plugin's name
+    ids::register_host_calls(linker, name.to_string())?;
+    strings::register_host_calls(linker, name.to_string())?;
+    register_bus_send(linker, name.to_string())?;
+    print::register_host_calls(linker, name.to_string())?;
+    assert::register_host_calls(linker, name.to_string())?;
+    allocator::register_host_calls(linker, name.to_string())?;
+    call::register_host_calls(linker, name.to_string())?;
+    saves::register_host_calls(linker, name.to_string())?;
+    Ok(())
+}
+
+// What loading `name` again replaces, for BlindHost::restore to put back if the load fails
+struct Replaced {
+    instance: Option<Instance>,
+    table: Option<Table>,
+    imported: Option<Vec<(String, String)>>,
+    plugin_capabilities: Option<u64>,
+    save_version: Option<SaveVersion>,
+    manifest: Option<PluginManifest>,
+    capabilities: u64,
+    // Heap blocks it held, and how many modules, slots and linked entries there were
+    blocks: Vec<u32>,
+    modules: usize,
+    slots: usize,
+    linked_entries: usize,
+}
+
+impl Replaced {
+    fn of(state: &HostState, name: &str) -> Self {
+        let live = state.metrics.lock().unwrap().live_allocations();
+        Self {
+            instance: state.instances.get(name).copied(),
+            table: state.tables.get(name).copied(),
+            imported: state.imported.get(name).cloned(),
+            plugin_capabilities: state.plugin_capabilities.get(name).copied(),
+            save_version: state.save_versions.get(name).copied(),
+            manifest: state.manifests.get(name).cloned(),
+            capabilities: state.capabilities,
+            blocks: live
+                .into_iter()
+                .filter(|(_, (owner, _))| owner == name)
+                .map(|(ptr, _)| ptr)
+                .collect(),
+            modules: state.modules.len(),
+            slots: state.slots.len(),
+            linked_entries: state.linked_entries.len(),
         }
+    }
+}
 
-        mem_slice[start..end].copy_from_slice(data);
-        Ok(())
+fn put_back<V>(map: &mut HashMap<String, V>, name: &str, value: Option<V>) {
+    match value {
+        Some(value) => map.insert(name.to_string(), value),
+        None => map.remove(name),
+    };
+}
+
+// Stands in for an unloaded plugin's export: same signature, but it traps
+fn unloaded_stub(store: &mut Store<HostState>, func: Func, plugin: &str) -> Func {
+    let ty = func.ty(&*store);
+    let plugin = plugin.to_string();
+    Func::new(&mut *store, ty, move |_, _, _| {
+        Err(anyhow!("'{}' was unloaded", plugin))
+    })
+}
+
+// Defines what `from` has in `into`, only what `manifest` allows if there is one
+fn copy_linked(
+    store: &mut Store<HostState>,
+    from: &Linker<HostState>,
+    into: &mut Linker<HostState>,
+    manifest: Option<&PluginManifest>,
+) -> Result<()> {
+    let linked: Vec<(String, String, Extern)> = from
+        .iter(&mut *store)
+        .filter(|&(module, name, _)| {
+            manifest.is_none_or(|manifest| module == "env" && manifest.allows(name))
+        })
+        .map(|(module, name, item)| (module.to_string(), name.to_string(), item))
+        .collect();
+    for (module, name, item) in linked {
+        into.define(&*store, &module, &name, item)?;
+    }
+    Ok(())
+}
+
+fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+// `func` for a call graph that records every call, or an audit log; only used with
+// BlindHostConfig::trace_calls or audit_log
+fn traced(
+    c: &mut Caller<'_, HostState>,
+    func: Func,
+    caller_name: &str,
+    provider: String,
+    export: String,
+) -> Func {
+    let ty = func.ty(&*c);
+    let caller_name = caller_name.to_string();
+    Func::new(&mut *c, ty, move |mut caller, params, results| {
+        let start = Instant::now();
+        let result = func.call(&mut caller, params, results);
+        let packed = match results {
+            [Val::I64(packed)] => Some(*packed),
+            _ => None,
+        };
+        let status = audit::status(&result, packed);
+        record_linked_call(
+            &caller,
+            (&caller_name, &provider, &export),
+            params,
+            start.elapsed(),
+            status,
+        );
+        result
+    })
+}
+
+// Profile, (when tracing) call graph and (when auditing) audit log entries for a call through a
+// linked table entry
+fn record_linked_call(
+    caller: &Caller<'_, HostState>,
+    (caller_name, provider, export): (&str, &str, &str),
+    params: &[Val],
+    took: Duration,
+    status: &str,
+) {
+    caller
+        .data()
+        .profiler
+        .lock()
+        .unwrap()
+        .record(provider, export, took);
+    // The usual (ptr, len) payload; other signatures have no payload to speak of
+    let payload = match params {
+        [Val::I32(_), Val::I32(len)] => Some((*len).max(0) as u32),
+        _ => None,
+    };
+    let entry = AuditEntry {
+        event: CallKind::Linked.name(),
+        caller: caller_name,
+        callee: provider,
+        export,
+        payload,
+        status,
+    };
+    audit(caller.data(), entry);
+    let mut graph = caller.data().call_graph.lock().unwrap();
+    if !graph.enabled {
+        return;
+    }
+    graph.called(CallRecord {
+        caller: caller_name.to_string(),
+        callee: provider.to_string(),
+        export: export.to_string(),
+        kind: CallKind::Linked,
+        payload,
+        took,
+    });
+}
+
+fn capability_names(bits: u64) -> Vec<String> {
+    let known = [
+        (CAPABILITY_TUI, "TUI"),
+        (CAPABILITY_AUDIO, "audio"),
+        (CAPABILITY_MOUSE, "mouse"),
+        (CAPABILITY_EVENTS, "events"),
+        (CAPABILITY_ECS_KERNEL, "ECS kernel"),
+    ];
+    let mut names: Vec<String> = known
+        .iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+    let unknown = known.iter().fold(bits, |rest, (bit, _)| rest & !bit);
+    if unknown != 0 {
+        names.push(format!("{:#x}", unknown));
     }
+    names
 }
@@ -0,0 +1,170 @@
+use super::host_object::BlindHost;
+use anyhow::Result;
+use std::fmt::{self, Write};
+
+// What each part of shared memory is for, and hex dumps annotated with it. For debugging
+// pointer-packing mistakes: a FatPtr pointing into the wrong plugin's stack is obvious here.
+//
+//   [0, 1024)                      reserved by the host
+//   per plugin, by address:        its data (static data, then its own allocator's heap),
+//                                  then its stack (growing down from the slot's top)
+//   up to heap_start_address:      slots no plugin took yet, or that unloaded plugins gave back
+//   the rest:                      the host heap (host_alloc), in use or free
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    Reserved,
+    Data(String),
+    Stack(String),
+    UnusedSlots,
+    HeapInUse,
+    HeapFree,
+}
+
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub start: u32,
+    pub end: u32,
+    pub kind: RegionKind,
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionKind::Reserved => write!(f, "reserved"),
+            RegionKind::Data(plugin) => write!(f, "data of '{}'", plugin),
+            RegionKind::Stack(plugin) => write!(f, "stack of '{}'", plugin),
+            RegionKind::UnusedSlots => write!(f, "unused plugin slots"),
+            RegionKind::HeapInUse => write!(f, "host heap, in use"),
+            RegionKind::HeapFree => write!(f, "host heap, free"),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}..{:#010x} {}", self.start, self.end, self.kind)
+    }
+}
+
+/// Every region of shared memory, in address order and without gaps.
+pub fn region_map(host: &BlindHost) -> Vec<Region> {
+    let state = host.store.data();
+    let memory_end = state.shared_memory.data().len() as u32;
+    let heap_start = state.heap_start_address as u32;
+    let mut regions = vec![Region {
+        start: 0,
+        end: 1024,
+        kind: RegionKind::Reserved,
+    }];
+
+    let mut slots: Vec<(Option<&String>, i32)> = state
+        .slots
+        .iter()
+        .map(|(plugin, base)| (Some(plugin), *base))
+        .collect();
+    slots.extend(state.free_slots.iter().map(|base| (None, *base)));
+    slots.sort_by_key(|(_, base)| *base);
+    for (plugin, base) in slots {
+        let base = base as u32;
+        let Some(plugin) = plugin else {
+            regions.push(Region {
+                start: base,
+                end: base + state.slot_size as u32,
+                kind: RegionKind::UnusedSlots,
+            });
+            continue;
+        };
+        let stack = base + state.data_size as u32;
+        regions.push(Region {
+            start: base,
+            end: stack,
+            kind: RegionKind::Data(plugin.clone()),
+        });
+        regions.push(Region {
+            start: stack,
+            end: base + state.slot_size as u32,
+            kind: RegionKind::Stack(plugin.clone()),
+        });
+    }
+    let slots_end = state.next_memory_offset as u32;
+    if slots_end < heap_start {
+        regions.push(Region {
+            start: slots_end,
+            end: heap_start,
+            kind: RegionKind::UnusedSlots,
+        });
+    }
+
+    // The heap only knows its free blocks; whatever lies between them is in use
+    let mut free = state.heap.lock().unwrap().free_blocks.clone();
+    free.sort_by_key(|block| block.addr);
+    let mut at = heap_start;
+    for block in free.iter().filter(|block| block.addr >= heap_start) {
+        if block.addr > at {
+            regions.push(Region {
+                start: at,
+                end: block.addr,
+                kind: RegionKind::HeapInUse,
+            });
+        }
+        regions.push(Region {
+            start: block.addr,
+            end: block.addr + block.size,
+            kind: RegionKind::HeapFree,
+        });
+        at = block.addr + block.size;
+    }
+    if at < memory_end {
+        regions.push(Region {
+            start: at,
+            end: memory_end,
+            kind: RegionKind::HeapInUse,
+        });
+    }
+    regions
+}
+
+/// `len` bytes at `ptr` as hex and ASCII, 16 per line, with a header wherever the range
+/// crosses into another region.
+pub fn hex_dump(host: &BlindHost, ptr: i32, len: i32) -> Result<String> {
+    let bytes = host.view_mem(ptr, len)?;
+    let (start, end) = (ptr as u32, ptr as u32 + len as u32);
+    let mut out = String::new();
+
+    for region in region_map(host)
+        .iter()
+        .filter(|r| r.start < end && r.end > start)
+    {
+        let _ = writeln!(out, "── {} ──", region);
+        let from = region.start.max(start);
+        let to = region.end.min(end);
+        let part = &bytes[(from - start) as usize..(to - start) as usize];
+        for (line, chunk) in part.chunks(16).enumerate() {
+            let _ = write!(out, "{:#010x}  ", from as usize + line * 16);
+            for idx in 0..16 {
+                match chunk.get(idx) {
+                    Some(byte) => {
+                        let _ = write!(out, "{:02x} ", byte);
+                    }
+                    None => out.push_str("   "),
+                }
+                if idx == 7 {
+                    out.push(' ');
+                }
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            let _ = writeln!(out, " |{}|", ascii);
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,66 @@
+use super::host_object::BlindHost;
+use anyhow::{anyhow, Result};
+use wasmtime::ValType;
+
+// What the files in /idl promise each module exports, lowered to wasm types
+include!(concat!(env!("OUT_DIR"), "/interfaces.rs"));
+
+pub struct InterfaceSpec {
+    pub module: &'static str,
+    pub exports: &'static [ExportSpec],
+}
+
+pub struct ExportSpec {
+    pub name: &'static str,
+    pub params: &'static [WasmType],
+    pub results: &'static [WasmType],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WasmType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl WasmType {
+    fn of(ty: &ValType) -> Option<Self> {
+        match ty {
+            ValType::I32 => Some(WasmType::I32),
+            ValType::I64 => Some(WasmType::I64),
+            ValType::F32 => Some(WasmType::F32),
+            ValType::F64 => Some(WasmType::F64),
+            _ => None,
+        }
+    }
+}
+
+/// Fails if `module` is one an IDL file describes but doesn't export what it promises, so a
+/// stale build is caught at load time rather than as a trap on the first call. Modules without
+/// an interface always pass.
+pub fn check_exports(host: &mut BlindHost, module: &str) -> Result<()> {
+    for spec in INTERFACES.iter().filter(|spec| spec.module == module) {
+        for export in spec.exports {
+            let func = host
+                .get_func(module, export.name)
+                .map_err(|_| anyhow!("'{}' is missing the '{}' export", module, export.name))?;
+            let ty = func.ty(&host.store);
+            let params: Vec<_> = ty.params().map(|t| WasmType::of(&t)).collect();
+            let results: Vec<_> = ty.results().map(|t| WasmType::of(&t)).collect();
+            let expected = |types: &[WasmType]| types.iter().copied().map(Some).collect::<Vec<_>>();
+            if params != expected(export.params) || results != expected(export.results) {
+                return Err(anyhow!(
+                    "'{}::{}' is {:?} -> {:?}, the interface expects {:?} -> {:?}",
+                    module,
+                    export.name,
+                    params,
+                    results,
+                    export.params,
+                    export.results
+                ));
+            }
+        }
+    }
+    Ok(())
+}
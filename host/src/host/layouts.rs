@@ -0,0 +1,32 @@
+use ecs_protocol::{Cell, GameConfig, GameGrid, GameState, Position, Tile};
+use grid_protocol::{DirtyRect, GridCell, GridInput};
+use layout_fingerprint::{Fingerprint, LayoutEntry};
+
+// The host's copies of the shared structs, compared with what plugins report in
+// `__layout_fingerprints` at load time
+pub const KNOWN: &[(&str, LayoutEntry)] = &[
+    known::<GridCell>(),
+    known::<GridInput>(),
+    known::<DirtyRect>(),
+    known::<Position>(),
+    known::<Tile>(),
+    known::<GameConfig>(),
+    known::<GameState>(),
+    known::<Cell>(),
+    known::<GameGrid>(),
+];
+
+const fn known<T: Fingerprint>() -> (&'static str, LayoutEntry) {
+    (T::NAME, LayoutEntry::of::<T>())
+}
+
+/// Names of the structs whose layout differs from the host's. Unknown names are skipped.
+pub fn mismatches(reported: &[LayoutEntry]) -> Vec<&'static str> {
+    reported
+        .iter()
+        .filter_map(|entry| {
+            let (name, ours) = KNOWN.iter().find(|(_, ours)| ours.name == entry.name)?;
+            (ours.fingerprint != entry.fingerprint).then_some(*name)
+        })
+        .collect()
+}
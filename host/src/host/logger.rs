@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+// How many lines the log pane can scroll back through
+const RECENT_LINES: usize = 500;
+
+// Where plugin logs (host_print / host_log) go, after each plugin's filter.
+//
+// host.toml:
+//   [log]
+//   level = "info"                 # error, warn, info, debug or trace; default info
+//   sinks = ["pane", "file"]       # any of stderr, file, pane; default pane, stderr headless
+//   file = "host.log"              # appended to, for the file sink
+//   [log.plugins.tasksapp_core]
+//   level = "debug"                # this plugin's own level
+//   allow = ["sync"]               # only lines containing one of these
+//   deny = ["heartbeat"]           # never lines containing one of these
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    /// host_log's `level` argument; out of range levels are clamped.
+    pub fn from_abi(level: i32) -> Self {
+        match level {
+            i32::MIN..=1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sink {
+    Stderr,
+    File,
+    // Kept for the embedder to show, see Logger::recent
+    Pane,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: Level,
+    // Unset leaves the choice to the embedder; stderr if it doesn't choose either
+    pub sinks: Option<Vec<Sink>>,
+    pub file: PathBuf,
+    pub plugins: HashMap<String, PluginFilter>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            sinks: None,
+            file: "host.log".into(),
+            plugins: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PluginFilter {
+    pub level: Option<Level>,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub plugin: String,
+    pub level: Level,
+    pub text: String,
+}
+
+pub struct Logger {
+    config: LogConfig,
+    sinks: Vec<Sink>,
+    file: Option<File>,
+    recent: VecDeque<LogLine>,
+}
+
+impl Logger {
+    pub fn new(config: LogConfig) -> Result<Self> {
+        let sinks = config.sinks.clone().unwrap_or(vec![Sink::Stderr]);
+        let file = if sinks.contains(&Sink::File) {
+            let file = File::options()
+                .create(true)
+                .append(true)
+                .open(&config.file)
+                .with_context(|| format!("Failed to open log file '{}'", config.file.display()))?;
+            Some(file)
+        } else {
+            None
+        };
+        Ok(Self {
+            config,
+            sinks,
+            file,
+            recent: VecDeque::new(),
+        })
+    }
+
+    pub fn log(&mut self, plugin: &str, level: Level, text: &str) {
+        if !self.wants(plugin, level, text) {
+            return;
+        }
+        for sink in &self.sinks {
+            match sink {
+                Sink::Stderr => eprintln!("[{}] {:<5} {}", plugin, level.name(), text),
+                Sink::File => {
+                    if let Some(file) = self.file.as_mut() {
+                        let _ = writeln!(file, "[{}] {:<5} {}", plugin, level.name(), text);
+                    }
+                }
+                Sink::Pane => {
+                    if self.recent.len() == RECENT_LINES {
+                        self.recent.pop_front();
+                    }
+                    self.recent.push_back(LogLine {
+                        plugin: plugin.to_string(),
+                        level,
+                        text: text.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Lines that went to the pane sink, oldest first.
+    pub fn recent(&self) -> impl DoubleEndedIterator<Item = &LogLine> + ExactSizeIterator {
+        self.recent.iter()
+    }
+
+    fn wants(&self, plugin: &str, level: Level, text: &str) -> bool {
+        let Some(filter) = self.config.plugins.get(plugin) else {
+            return level <= self.config.level;
+        };
+        level <= filter.level.unwrap_or(self.config.level)
+            && (filter.allow.is_empty()
+                || filter
+                    .allow
+                    .iter()
+                    .any(|pattern| text.contains(pattern.as_str())))
+            && !filter
+                .deny
+                .iter()
+                .any(|pattern| text.contains(pattern.as_str()))
+    }
+}
@@ -0,0 +1,248 @@
+use crate::host_calls::wasi::WASI_MODULE;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use wasmparser::{DataKind, Dylink0Subsection, KnownCustom, Parser, Payload};
+use wasmtime::Module;
+
+// A plugin's manifest: the `env` imports it may link against, and so all it can reach of the
+// host and of other plugins. A plugin loaded with one (BlindHost::load_plugin_with_manifest,
+// or load_plugin_file finding one beside the .wasm) gets a linker built from that list alone,
+// plus the ABI's own imports, instead of a copy of everything the host and earlier plugins
+// export. An empty manifest is the ABI and nothing else.
+//
+// <plugin>.toml, beside <plugin>.wasm:
+//   imports = [
+//       "host_print",        # a host call or another plugin's export, by name
+//       "sys_*",             # a trailing * allows every name starting with what comes before
+//       "wasi_snapshot_preview1::*",  # WASI (host_calls/wasi.rs), on hosts that enable it
+//   ]
+//
+// Imports it doesn't allow fail the load before any plugin code runs, naming each of them, so
+// the manifest is an exact account of what the plugin can do. Plugins loaded without one
+// (BlindHost::load_plugin) still link against everything.
+//
+// A plugin kept in a directory of its own (BlindHost::load_plugin_from_dir) has its manifest
+// there as plugin.toml, saying more about it:
+//   name = "game"                 # it's game.wasm beside plugin.toml, loaded as `game`
+//   version = "0.3.0"
+//   imports = ["host_print"]      # as above; the names without a * must be there to link against
+//   exports = ["tick"]            # what it has to export
+//   links = ["tasksapp_core::get_tasks"]  # what it host_link_calls, from plugins loaded before it
+//   data_size = 65536             # the static data and stack it needs in its slot, in bytes
+//   stack_size = 262144
+//
+// Every key also works in a manifest beside a .wasm, and is checked against the module and the
+// host before it's instantiated, so a missing link fails the load rather than host_link_call
+// mid-tick. Declaring links allows host_link_call and host_link_call_checked.
+
+// What every plugin imports to be a plugin at all: its memory, its slot and its table
+pub const ABI_IMPORTS: &[&str] = &[
+    "memory",
+    "__memory_base",
+    "__stack_pointer",
+    "__table_base",
+    "__indirect_function_table",
+];
+
+// The manifest in a plugin's directory
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+// What declaring links allows
+const LINK_CALLS: &[&str] = &["host_link_call", "host_link_call_checked"];
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginManifest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub imports: Vec<String>,
+    pub exports: Vec<String>,
+    // `plugin::export`
+    pub links: Vec<String>,
+    pub data_size: Option<u32>,
+    pub stack_size: Option<u32>,
+}
+
+impl PluginManifest {
+    pub fn parse(text: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(text)?;
+        if let Some(link) = manifest
+            .links
+            .iter()
+            .find(|link| link.split_once("::").is_none())
+        {
+            return Err(anyhow!("links are `plugin::export`, got '{}'", link));
+        }
+        Ok(manifest)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to parse '{}'", path.display()))
+    }
+
+    /// The manifest kept beside `wasm` (`game.toml` for `game.wasm`), if there is one.
+    pub fn beside(wasm: &Path) -> Result<Option<Self>> {
+        let path = wasm.with_extension("toml");
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load(&path).map(Some)
+    }
+
+    /// The manifest in the plugin directory `dir`, and the .wasm it names there.
+    pub fn in_dir(dir: &Path) -> Result<(Self, PathBuf)> {
+        let path = dir.join(MANIFEST_FILE);
+        let manifest = Self::load(&path)?;
+        let name = manifest
+            .name
+            .as_ref()
+            .ok_or(anyhow!("'{}' doesn't name its plugin", path.display()))?;
+        let wasm = dir.join(name).with_extension("wasm");
+        Ok((manifest, wasm))
+    }
+
+    /// The imports it requires, `module::name`, leaving out the ones with a *.
+    pub fn required(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.imports
+            .iter()
+            .filter(|import| !import.ends_with('*'))
+            .map(|import| import.split_once("::").unwrap_or(("env", import)))
+    }
+
+    /// What it expects to host_link_call: (plugin, export).
+    pub fn links(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.links.iter().filter_map(|link| link.split_once("::"))
+    }
+
+    /// Whether a plugin with this manifest may import `env::name`.
+    pub fn allows(&self, name: &str) -> bool {
+        ABI_IMPORTS.contains(&name)
+            || (!self.links.is_empty() && LINK_CALLS.contains(&name))
+            || self
+                .imports
+                .iter()
+                .any(|allowed| match allowed.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => allowed == name,
+                })
+    }
+
+    /// Whether a plugin with this manifest may import `module::name`: `env` names as they are,
+    /// WASI ones as `wasi_snapshot_preview1::name`.
+    pub fn allows_import(&self, module: &str, name: &str) -> bool {
+        match module {
+            "env" => self.allows(name),
+            WASI_MODULE => self.allows(&format!("{}::{}", module, name)),
+            _ => false,
+        }
+    }
+
+    /// Fails, naming them, if `module` imports anything this manifest doesn't allow, or doesn't
+    /// export what it declares. Plugins loaded under another name than theirs fail too.
+    pub fn check(&self, plugin: &str, module: &Module) -> Result<()> {
+        if let Some(name) = self.name.as_ref().filter(|name| *name != plugin) {
+            return Err(anyhow!(
+                "'{}' is named '{}' in its manifest, load it as that",
+                plugin,
+                name
+            ));
+        }
+        let missing: Vec<&str> = self
+            .exports
+            .iter()
+            .filter(|export| module.get_export(export).is_none())
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "'{}' doesn't export what its manifest declares: {}",
+                plugin,
+                missing.join(", ")
+            ));
+        }
+        let refused: Vec<String> = module
+            .imports()
+            .filter(|import| !self.allows_import(import.module(), import.name()))
+            .map(|import| format!("{}::{}", import.module(), import.name()))
+            .collect();
+        if refused.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "'{}' imports what its manifest doesn't allow: {}",
+            plugin,
+            refused.join(", ")
+        ))
+    }
+
+    /// Fails if the sizes it asks for don't fit the host's slots, `data_room` bytes of data then
+    /// `stack_room` of stack, or the static data in `wasm` doesn't fit the data it asks for.
+    pub fn check_memory(
+        &self,
+        plugin: &str,
+        wasm: &[u8],
+        data_room: u32,
+        stack_room: u32,
+    ) -> Result<()> {
+        let data_size = self.data_size.unwrap_or(data_room);
+        if data_size > data_room {
+            return Err(anyhow!(
+                "'{}' asks for {} bytes of data, slots have {}",
+                plugin,
+                data_size,
+                data_room
+            ));
+        }
+        if let Some(stack_size) = self.stack_size.filter(|size| *size > stack_room) {
+            return Err(anyhow!(
+                "'{}' asks for {} bytes of stack, slots have {}",
+                plugin,
+                stack_size,
+                stack_room
+            ));
+        }
+        let used = static_data_size(wasm)?;
+        if used > data_size {
+            return Err(anyhow!(
+                "'{}' has {} bytes of static data, more than the {} it asks for",
+                plugin,
+                used,
+                data_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The memory the module needs at its __memory_base: what its dylink.0 section says, as the
+// linker wrote it, else all its active data segments together
+fn static_data_size(wasm: &[u8]) -> Result<u32> {
+    let wasm = wat::parse_bytes(wasm)?;
+    let mut segments = 0;
+    for payload in Parser::new(0).parse_all(&wasm) {
+        match payload? {
+            Payload::CustomSection(section) => {
+                if let KnownCustom::Dylink0(reader) = section.as_known() {
+                    for subsection in reader {
+                        if let Dylink0Subsection::MemInfo(info) = subsection? {
+                            return Ok(info.memory_size);
+                        }
+                    }
+                }
+            }
+            Payload::DataSection(reader) => {
+                for data in reader {
+                    let data = data?;
+                    if matches!(data.kind, DataKind::Active { .. }) {
+                        segments += data.data.len() as u32;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(segments)
+}
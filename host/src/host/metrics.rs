@@ -0,0 +1,354 @@
+use super::host_object::BlindHost;
+use super::profiler::Profiler;
+use crate::allocator::HostHeap;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmtime::SharedMemory;
+
+// Owner of buffers host calls allocate for plugins (bus messages, storage values, ...)
+pub const HOST_OWNER: &str = "host";
+
+// Upper bounds of the tick duration histogram, in seconds
+const TICK_BUCKETS: [f64; 10] = [0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+// Counters for monitoring long-running hosts, served in the Prometheus text format with
+// `--metrics addr` (GET /metrics):
+//
+//   host_tick_duration_seconds            histogram of embedder ticks (all panes)
+//   host_slow_ticks_total                 ticks over BlindHostConfig::tick_budget
+//   host_frames_rendered_total            frames drawn to the terminal
+//   host_frames_skipped_total             frames not drawn after a slow tick
+//   host_allocations_total{plugin}        host_alloc calls (rate() gives allocations/sec)
+//   host_heap_bytes{plugin}               live host heap bytes per owner (HOST_OWNER for host calls)
+//   host_heap_free_bytes / _in_use_bytes  the whole host heap
+//   host_plugin_calls_total{plugin,export}    calls the host made or relayed (profiler.rs)
+//   host_plugin_links_total{caller,provider}  exports linked through host_link_call
+//
+// Calls between plugins through linked tables never pass through the host, so only
+// checked links (host_link_call_checked) are counted per call, or every link with
+// BlindHostConfig::trace_calls.
+#[derive(Default)]
+pub struct Metrics {
+    ticks: Vec<u64>,
+    tick_count: u64,
+    tick_seconds: f64,
+    slow_ticks: u64,
+    frames: u64,
+    frames_skipped: u64,
+    owners: HashMap<String, Owner>,
+    // Live allocations: ptr -> (owner, size), so a free is charged to whoever allocated, and
+    // in order, so the bounds check (host_calls/bounds.rs) finds the one a pointer is in
+    live: BTreeMap<u32, (String, u32)>,
+    links: HashMap<(String, String), u64>,
+}
+
+#[derive(Default)]
+struct Owner {
+    allocations: u64,
+    bytes: u64,
+}
+
+impl Metrics {
+    pub fn record_tick(&mut self, took: Duration) {
+        if self.ticks.is_empty() {
+            self.ticks = vec![0; TICK_BUCKETS.len()];
+        }
+        let seconds = took.as_secs_f64();
+        for (bucket, bound) in self.ticks.iter_mut().zip(TICK_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.tick_count += 1;
+        self.tick_seconds += seconds;
+    }
+
+    /// Ticks booked so far.
+    pub fn ticks(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn slow_tick(&mut self) {
+        self.slow_ticks += 1;
+    }
+
+    pub fn frame_rendered(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn frame_skipped(&mut self) {
+        self.frames_skipped += 1;
+    }
+
+    pub fn allocated(&mut self, owner: &str, ptr: u32, size: u32) {
+        if !self.owners.contains_key(owner) {
+            self.owners.insert(owner.to_string(), Owner::default());
+        }
+        let stats = self.owners.get_mut(owner).unwrap();
+        stats.allocations += 1;
+        stats.bytes += size as u64;
+        self.live.insert(ptr, (owner.to_string(), size));
+    }
+
+    /// Live host heap bytes `owner` holds.
+    pub fn live_bytes(&self, owner: &str) -> u64 {
+        self.owners.get(owner).map_or(0, |stats| stats.bytes)
+    }
+
+    /// Forgets the allocation at `ptr`, returning its size. `None` if nothing lives there.
+    pub fn freed(&mut self, ptr: u32) -> Option<u32> {
+        let (owner, size) = self.live.remove(&ptr)?;
+        if let Some(stats) = self.owners.get_mut(&owner) {
+            stats.bytes -= size as u64;
+        }
+        Some(size)
+    }
+
+    /// Who holds the allocation starting at `ptr`.
+    pub fn owner(&self, ptr: u32) -> Option<&str> {
+        self.live.get(&ptr).map(|(owner, _)| owner.as_str())
+    }
+
+    /// Charges the allocation at `ptr` to `owner` from now on, for buffers handed from one
+    /// plugin to another. Nothing happens if nothing lives there.
+    pub fn transferred(&mut self, ptr: u32, owner: &str) {
+        let Some((from, size)) = self.live.get(&ptr).cloned() else {
+            return;
+        };
+        if let Some(stats) = self.owners.get_mut(&from) {
+            stats.bytes -= size as u64;
+        }
+        self.owners.entry(owner.to_string()).or_default().bytes += size as u64;
+        self.live.insert(ptr, (owner.to_string(), size));
+    }
+
+    /// Forgets every allocation `owner` holds, returning them as (ptr, size).
+    pub fn release(&mut self, owner: &str) -> Vec<(u32, u32)> {
+        let blocks: Vec<(u32, u32)> = self
+            .live
+            .iter()
+            .filter(|(_, (of, _))| of == owner)
+            .map(|(&ptr, &(_, size))| (ptr, size))
+            .collect();
+        for (ptr, _) in &blocks {
+            self.live.remove(ptr);
+        }
+        if let Some(stats) = self.owners.get_mut(owner) {
+            stats.bytes = 0;
+        }
+        blocks
+    }
+
+    /// The live allocation `addr` falls in, as (ptr, size).
+    pub fn live_block(&self, addr: u32) -> Option<(u32, u32)> {
+        // Blocks don't overlap, so it's the last one starting at or before `addr`
+        let (&ptr, &(_, size)) = self.live.range(..=addr).next_back()?;
+        ((addr as u64) < ptr as u64 + size as u64).then_some((ptr, size))
+    }
+
+    /// Every live allocation, as ptr -> (owner, size), for snapshots.
+    pub fn live_allocations(&self) -> HashMap<u32, (String, u32)> {
+        self.live
+            .iter()
+            .map(|(&ptr, block)| (ptr, block.clone()))
+            .collect()
+    }
+
+    /// Puts back what `live_allocations` answered, with each owner's live bytes to match.
+    /// Allocation counts aren't rolled back: those allocations did happen.
+    pub fn restore_live_allocations(&mut self, live: HashMap<u32, (String, u32)>) {
+        self.owners.values_mut().for_each(|stats| stats.bytes = 0);
+        for (owner, size) in live.values() {
+            self.owners.entry(owner.clone()).or_default().bytes += *size as u64;
+        }
+        self.live = live.into_iter().collect();
+    }
+
+    pub fn linked(&mut self, caller: &str, provider: &str) {
+        *self
+            .links
+            .entry((caller.to_string(), provider.to_string()))
+            .or_default() += 1;
+    }
+}
+
+// What the server thread reads, shared with the host
+struct Sources {
+    metrics: Arc<Mutex<Metrics>>,
+    profiler: Arc<Mutex<Profiler>>,
+    heap: Arc<Mutex<HostHeap>>,
+    memory: SharedMemory,
+    heap_start: usize,
+}
+
+/// Serves `host`'s metrics on `addr` from a thread of its own, for as long as the process runs.
+pub fn serve(addr: &str, host: &BlindHost) -> Result<()> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to serve metrics on {}", addr))?;
+    let state = host.store.data();
+    let sources = Sources {
+        metrics: state.metrics.clone(),
+        profiler: state.profiler.clone(),
+        heap: state.heap.clone(),
+        memory: state.shared_memory.clone(),
+        heap_start: state.heap_start_address as usize,
+    };
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = answer(stream, &sources) {
+                eprintln!("⚠️ [METRICS] {:#}", e);
+            }
+        }
+    });
+    eprintln!("📈 [HOST] Metrics at http://{}/metrics", addr);
+    Ok(())
+}
+
+fn answer(stream: TcpStream, sources: &Sources) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The rest of the request doesn't matter, but has to be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut stream = reader.into_inner();
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if request.starts_with("GET ") && path == "/metrics" {
+        ("200 OK", render(sources))
+    } else {
+        ("404 Not Found", "Metrics are at /metrics\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+fn render(sources: &Sources) -> String {
+    let mut out = String::new();
+    {
+        let metrics = sources.metrics.lock().unwrap();
+        let _ = writeln!(out, "# TYPE host_tick_duration_seconds histogram");
+        for (count, bound) in metrics.ticks.iter().zip(TICK_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "host_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "host_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            metrics.tick_count
+        );
+        let _ = writeln!(
+            out,
+            "host_tick_duration_seconds_sum {}",
+            metrics.tick_seconds
+        );
+        let _ = writeln!(
+            out,
+            "host_tick_duration_seconds_count {}",
+            metrics.tick_count
+        );
+
+        let _ = writeln!(out, "# TYPE host_slow_ticks_total counter");
+        let _ = writeln!(out, "host_slow_ticks_total {}", metrics.slow_ticks);
+
+        let _ = writeln!(out, "# TYPE host_frames_rendered_total counter");
+        let _ = writeln!(out, "host_frames_rendered_total {}", metrics.frames);
+        let _ = writeln!(out, "# TYPE host_frames_skipped_total counter");
+        let _ = writeln!(out, "host_frames_skipped_total {}", metrics.frames_skipped);
+
+        let mut owners: Vec<_> = metrics.owners.iter().collect();
+        owners.sort_by_key(|(name, _)| name.as_str());
+        let _ = writeln!(out, "# TYPE host_allocations_total counter");
+        for (name, stats) in &owners {
+            let _ = writeln!(
+                out,
+                "host_allocations_total{{plugin=\"{}\"}} {}",
+                escape(name),
+                stats.allocations
+            );
+        }
+        let _ = writeln!(out, "# TYPE host_heap_bytes gauge");
+        for (name, stats) in &owners {
+            let _ = writeln!(
+                out,
+                "host_heap_bytes{{plugin=\"{}\"}} {}",
+                escape(name),
+                stats.bytes
+            );
+        }
+
+        let mut links: Vec<_> = metrics.links.iter().collect();
+        links.sort();
+        let _ = writeln!(out, "# TYPE host_plugin_links_total counter");
+        for ((caller, provider), count) in links {
+            let _ = writeln!(
+                out,
+                "host_plugin_links_total{{caller=\"{}\",provider=\"{}\"}} {}",
+                escape(caller),
+                escape(provider),
+                count
+            );
+        }
+    }
+
+    let free: u64 = sources
+        .heap
+        .lock()
+        .unwrap()
+        .free_blocks
+        .iter()
+        .map(|block| block.size as u64)
+        .sum();
+    let heap_size = sources
+        .memory
+        .data()
+        .len()
+        .saturating_sub(sources.heap_start) as u64;
+    let _ = writeln!(out, "# TYPE host_heap_free_bytes gauge");
+    let _ = writeln!(out, "host_heap_free_bytes {}", free);
+    let _ = writeln!(out, "# TYPE host_heap_in_use_bytes gauge");
+    let _ = writeln!(
+        out,
+        "host_heap_in_use_bytes {}",
+        heap_size.saturating_sub(free)
+    );
+
+    let report = sources.profiler.lock().unwrap().report();
+    let _ = writeln!(out, "# TYPE host_plugin_calls_total counter");
+    for entry in &report.entries {
+        let _ = writeln!(
+            out,
+            "host_plugin_calls_total{{plugin=\"{}\",export=\"{}\"}} {}",
+            escape(&entry.plugin),
+            escape(&entry.export),
+            entry.calls
+        );
+    }
+    out
+}
+
+// Label values are quoted; plugin and export names could hold anything
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,2 +1,16 @@
+pub mod audit;
+pub mod backtrace;
+pub mod call_graph;
 pub mod caller_state;
+pub mod heap_timeline;
 pub mod host_object;
+pub mod inspect;
+pub mod interfaces;
+pub mod layouts;
+pub mod logger;
+pub mod manifest;
+pub mod metrics;
+pub mod module_cache;
+pub mod profiler;
+pub mod quotas;
+pub mod snapshot;
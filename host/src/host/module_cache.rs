@@ -0,0 +1,71 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use wasmtime::{Engine, Module};
+
+// Where compiled plugins are kept, unless host.toml's [module_cache] says otherwise
+pub const DEFAULT_MODULE_CACHE_DIR: &str = "plugin-cache";
+
+// Compiled plugins, kept between runs so they're compiled once rather than on every start
+// (seconds, for the bevy_ecs kernel). With BlindHostConfig::module_cache, load_plugin looks a
+// plugin's bytes up here before compiling them, and keeps what it compiles:
+//
+//   <dir>/<sha256 of the engine key and the plugin's bytes>.cwasm    Module::serialize's output
+//
+// The engine key is what the host configured its engine with, so hosts set up differently
+// (deterministic ones, ones counting fuel) keep entries of their own. Entries wasmtime won't
+// take back, from another wasmtime version say, or that don't read, are compiled again and
+// replaced. Nothing is ever removed; delete the directory to start over.
+
+pub struct ModuleCache {
+    dir: PathBuf,
+    engine_key: String,
+}
+
+impl ModuleCache {
+    pub fn new(dir: impl Into<PathBuf>, engine_key: &str) -> Self {
+        Self {
+            dir: dir.into(),
+            engine_key: engine_key.to_string(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the module compiled from `wasm` is kept.
+    pub fn path_of(&self, wasm: &[u8]) -> PathBuf {
+        let mut hash = Sha256::new();
+        hash.update(self.engine_key.as_bytes());
+        hash.update([0]);
+        hash.update(wasm);
+        let name: String = hash
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        self.dir.join(name).with_extension("cwasm")
+    }
+
+    /// The module compiled from `wasm`, if it's kept here and wasmtime takes it back.
+    pub fn get(&self, engine: &Engine, wasm: &[u8]) -> Option<Module> {
+        let bytes = std::fs::read(self.path_of(wasm)).ok()?;
+        // Safety: entries are only ever Module::serialize's output, written by put under the hash
+        // of what they were compiled from; wasmtime still checks the header and engine settings
+        unsafe { Module::deserialize(engine, bytes) }.ok()
+    }
+
+    /// Keeps `module`, compiled from `wasm`. Written aside and renamed into place, so other
+    /// hosts never read half an entry.
+    pub fn put(&self, wasm: &[u8], module: &Module) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create '{}'", self.dir.display()))?;
+        let path = self.path_of(wasm);
+        let partial = path.with_extension(format!("cwasm.{}", std::process::id()));
+        std::fs::write(&partial, module.serialize()?)
+            .with_context(|| format!("Failed to write '{}'", partial.display()))?;
+        std::fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+}
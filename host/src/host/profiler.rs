@@ -0,0 +1,189 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+// How many of the latest calls each export's average and p99 are taken over
+const WINDOW: usize = 256;
+
+// Timings of the plugin calls the host makes (and, with BlindHostConfig::profile_syscalls, of
+// calls into the kernel's sys_* exports), by plugin and export. Always on: the host only
+// calls exports a handful of times per tick, so two Instant::now()s per call are noise.
+#[derive(Default)]
+pub struct Profiler {
+    plugins: HashMap<String, HashMap<String, Samples>>,
+}
+
+#[derive(Default)]
+struct Samples {
+    recent: VecDeque<Duration>,
+    calls: u64,
+    max: Duration,
+    // Since the last take_tick
+    tick_calls: u32,
+    tick_total: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileEntry {
+    pub plugin: String,
+    pub export: String,
+    // Since the plugin was loaded; avg and p99 only cover the last WINDOW calls
+    pub calls: u64,
+    pub avg: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// An export called during the tick `take_tick` ended, with its recent timings for comparison.
+#[derive(Clone, Debug)]
+pub struct TickEntry {
+    pub entry: ProfileEntry,
+    pub calls: u32,
+    pub total: Duration,
+}
+
+/// `profile_report()`'s answer, costliest export (by recent average) first.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileReport {
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl Profiler {
+    pub fn record(&mut self, plugin: &str, export: &str, took: Duration) {
+        // Only the first call of each export allocates
+        if !self.plugins.contains_key(plugin) {
+            self.plugins.insert(plugin.to_string(), HashMap::new());
+        }
+        let exports = self.plugins.get_mut(plugin).unwrap();
+        if !exports.contains_key(export) {
+            exports.insert(export.to_string(), Samples::default());
+        }
+        let samples = exports.get_mut(export).unwrap();
+
+        if samples.recent.len() == WINDOW {
+            samples.recent.pop_front();
+        }
+        samples.recent.push_back(took);
+        samples.calls += 1;
+        samples.max = samples.max.max(took);
+        samples.tick_calls += 1;
+        samples.tick_total += took;
+    }
+
+    pub fn report(&self) -> ProfileReport {
+        let mut entries: Vec<ProfileEntry> = self
+            .plugins
+            .iter()
+            .flat_map(|(plugin, exports)| {
+                exports
+                    .iter()
+                    .map(move |(export, samples)| samples.entry(plugin, export))
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.avg
+                .cmp(&a.avg)
+                .then_with(|| (&a.plugin, &a.export).cmp(&(&b.plugin, &b.export)))
+        });
+        ProfileReport { entries }
+    }
+
+    /// The exports called since the last call, costliest first, and starts counting afresh.
+    /// The embedder calls it at the end of every tick (BlindHost::end_tick).
+    pub fn take_tick(&mut self) -> Vec<TickEntry> {
+        let mut entries = Vec::new();
+        for (plugin, exports) in &mut self.plugins {
+            for (export, samples) in exports {
+                if samples.tick_calls == 0 {
+                    continue;
+                }
+                entries.push(TickEntry {
+                    entry: samples.entry(plugin, export),
+                    calls: samples.tick_calls,
+                    total: samples.tick_total,
+                });
+                samples.tick_calls = 0;
+                samples.tick_total = Duration::ZERO;
+            }
+        }
+        entries.sort_by_key(|tick| std::cmp::Reverse(tick.total));
+        entries
+    }
+}
+
+impl Samples {
+    fn entry(&self, plugin: &str, export: &str) -> ProfileEntry {
+        let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+        sorted.sort();
+        let total: Duration = sorted.iter().sum();
+        ProfileEntry {
+            plugin: plugin.to_string(),
+            export: export.to_string(),
+            calls: self.calls,
+            avg: total / sorted.len().max(1) as u32,
+            p99: sorted
+                .get(sorted.len() * 99 / 100)
+                .copied()
+                .unwrap_or_default(),
+            max: self.max,
+        }
+    }
+}
+
+// One export per line, for stderr or the TUI overlay
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<32} {:>8} {:>10} {:>10} {:>10}",
+            "export", "calls", "avg", "p99", "max"
+        )?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:<32} {:>8} {:>10} {:>10} {:>10}",
+                format!("{}::{}", entry.plugin, entry.export),
+                entry.calls,
+                format!("{:.2?}", entry.avg),
+                format!("{:.2?}", entry.p99),
+                format!("{:.2?}", entry.max),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// --- PUFFIN ---
+// With the `puffin` feature, `scope!`s in the host loop show up as flame graphs in
+// puffin_viewer. `serve_puffin` turns them on, for as long as the server it returns lives.
+
+#[cfg(feature = "puffin")]
+pub type PuffinServer = puffin_http::Server;
+
+#[cfg(not(feature = "puffin"))]
+pub struct PuffinServer;
+
+#[cfg(feature = "puffin")]
+pub fn serve_puffin() -> anyhow::Result<PuffinServer> {
+    let addr = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
+    let server = puffin_http::Server::new(&addr)?;
+    puffin::set_scopes_on(true);
+    eprintln!(
+        "🔥 [HOST] Profiling, connect with `puffin_viewer --url {}`",
+        addr
+    );
+    Ok(server)
+}
+
+#[cfg(not(feature = "puffin"))]
+pub fn serve_puffin() -> anyhow::Result<PuffinServer> {
+    Err(anyhow::anyhow!(
+        "This host was built without puffin, rebuild it with `--features puffin`"
+    ))
+}
+
+/// Closes the current puffin frame; the embedder calls it once per trip around its loop.
+pub fn puffin_frame() {
+    #[cfg(feature = "puffin")]
+    puffin::GlobalProfiler::lock().new_frame();
+}
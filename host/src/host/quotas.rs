@@ -0,0 +1,219 @@
+use bus_protocol::quotas::{
+    QuotaEvent, EVENT_RESUMED, EVENT_SUSPENDED, EVENT_THROTTLED, RESOURCE_CPU, RESOURCE_FUEL,
+    RESOURCE_HEAP, RESOURCE_TIME,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+// Per-plugin budgets, so one plugin gone wrong costs itself rather than the whole client:
+//
+//   cpu       time in its exports per tick, as the profiler booked it (BlindHost::end_tick)
+//   heap      live host heap bytes it holds; host_alloc past it answers 0
+//   fuel      wasm instructions per call into it (wasmtime fuel); the call traps when it runs out
+//   timeout   wall-clock time per call (wasmtime epochs); the call traps when it's up
+//
+// Going over one is a strike. A plugin with a strike is throttled: its next `throttle` ticks
+// are skipped (BlindHost::may_tick, which DriverHandle checks). After `suspend_after` strikes in
+// a row it's suspended instead, with no ticks and no messages until BlindHost::resume. Each is
+// logged as the host's and sent to the other plugins from QUOTAS (bus_protocol::quotas). A tick
+// it runs within its budgets clears its strikes.
+//
+// Calls from one plugin into another count against the one the host called. Deterministic hosts
+// only keep the heap and fuel budgets: time differs from run to run.
+
+#[derive(Clone, Debug)]
+pub struct QuotaConfig {
+    pub cpu: Option<Duration>,
+    pub heap: Option<u64>,
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub throttle: u32,
+    pub suspend_after: u32,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            cpu: None,
+            heap: None,
+            fuel: None,
+            timeout: None,
+            throttle: 30,
+            suspend_after: 3,
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// The budgets a deterministic host keeps: those that don't depend on the clock.
+    pub fn deterministic(self) -> Self {
+        Self {
+            cpu: None,
+            timeout: None,
+            ..self
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Quotas {
+    config: QuotaConfig,
+    tick: u64,
+    plugins: HashMap<String, Standing>,
+    // Heap, fuel and time gone over since the last end_tick: plugin -> (resource, used)
+    over: BTreeMap<String, (u8, u64)>,
+}
+
+#[derive(Default)]
+struct Standing {
+    strikes: u32,
+    throttled_until: u64,
+    suspended: bool,
+}
+
+impl Quotas {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    /// Neither throttled nor suspended.
+    pub fn may_tick(&self, plugin: &str) -> bool {
+        self.plugins
+            .get(plugin)
+            .is_none_or(|standing| !standing.suspended && self.tick >= standing.throttled_until)
+    }
+
+    pub fn is_suspended(&self, plugin: &str) -> bool {
+        self.plugins
+            .get(plugin)
+            .is_some_and(|standing| standing.suspended)
+    }
+
+    /// Whether `plugin`, holding `live` heap bytes, may take `size` more. Books it if not.
+    pub fn heap_allows(&mut self, plugin: &str, live: u64, size: u64) -> bool {
+        match self.config.heap {
+            Some(budget) if live + size > budget => {
+                self.went_over(plugin, RESOURCE_HEAP, live + size);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Books `plugin` going over its budget of `resource`, charged at the next end_tick (once
+    /// per tick, however often it happens).
+    pub fn went_over(&mut self, plugin: &str, resource: u8, used: u64) {
+        self.over
+            .entry(plugin.to_string())
+            .or_insert((resource, used));
+    }
+
+    /// Ends a tick in which plugins spent `cpu` in their exports, striking those over a budget.
+    /// Answers what changed, for the log and the other plugins.
+    pub fn end_tick(&mut self, cpu: &HashMap<String, Duration>) -> Vec<QuotaEvent> {
+        self.tick += 1;
+        let mut over = std::mem::take(&mut self.over);
+        if let Some(budget) = self.config.cpu {
+            for (plugin, spent) in cpu.iter().filter(|(_, spent)| **spent > budget) {
+                over.entry(plugin.clone())
+                    .or_insert((RESOURCE_CPU, spent.as_micros() as u64));
+            }
+        }
+        for plugin in cpu.keys().filter(|plugin| !over.contains_key(*plugin)) {
+            if let Some(standing) = self.plugins.get_mut(plugin) {
+                standing.strikes = 0;
+            }
+        }
+
+        let mut events = Vec::new();
+        for (plugin, (resource, used)) in over {
+            let budget = self.budget(resource);
+            let standing = self.plugins.entry(plugin.clone()).or_default();
+            if standing.suspended {
+                continue;
+            }
+            standing.strikes += 1;
+            let event = if standing.strikes >= self.config.suspend_after {
+                standing.suspended = true;
+                EVENT_SUSPENDED
+            } else {
+                standing.throttled_until = self.tick + self.config.throttle as u64;
+                EVENT_THROTTLED
+            };
+            events.push(QuotaEvent {
+                event,
+                resource,
+                plugin,
+                used,
+                budget,
+            });
+        }
+        events
+    }
+
+    /// Lets a suspended plugin run again, with a clean slate. `None` if it wasn't suspended.
+    pub fn resume(&mut self, plugin: &str) -> Option<QuotaEvent> {
+        let standing = self
+            .plugins
+            .get_mut(plugin)
+            .filter(|standing| standing.suspended)?;
+        *standing = Standing::default();
+        Some(QuotaEvent {
+            event: EVENT_RESUMED,
+            resource: RESOURCE_CPU,
+            plugin: plugin.to_string(),
+            used: 0,
+            budget: 0,
+        })
+    }
+
+    // In the units of bus_protocol::quotas
+    fn budget(&self, resource: u8) -> u64 {
+        let micros =
+            |budget: Option<Duration>| budget.map_or(0, |budget| budget.as_micros() as u64);
+        match resource {
+            RESOURCE_CPU => micros(self.config.cpu),
+            RESOURCE_HEAP => self.config.heap.unwrap_or(0),
+            RESOURCE_FUEL => self.config.fuel.unwrap_or(0),
+            RESOURCE_TIME => micros(self.config.timeout),
+            _ => 0,
+        }
+    }
+
+    /// `event` as a log line.
+    pub fn describe(&self, event: &QuotaEvent) -> String {
+        let amount = |value: u64| match event.resource {
+            RESOURCE_CPU | RESOURCE_TIME => format!("{:.2?}", Duration::from_micros(value)),
+            RESOURCE_HEAP => format!("{} bytes", value),
+            _ => format!("{} fuel", value),
+        };
+        let budget = match event.resource {
+            RESOURCE_CPU => "cpu budget per tick",
+            RESOURCE_HEAP => "heap budget",
+            RESOURCE_FUEL => "fuel budget per call",
+            _ => "time budget per call",
+        };
+        let (used, of) = (amount(event.used), amount(event.budget));
+        let over = format!(
+            "'{}' went over its {} ({} of {})",
+            event.plugin, budget, used, of
+        );
+        match event.event {
+            EVENT_THROTTLED => {
+                format!("{}: skipping its next {} ticks", over, self.config.throttle)
+            }
+            EVENT_SUSPENDED => format!(
+                "{} {} times in a row: suspended",
+                over, self.config.suspend_after
+            ),
+            _ => format!("'{}' was resumed", event.plugin),
+        }
+    }
+}
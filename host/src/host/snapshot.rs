@@ -0,0 +1,116 @@
+use super::host_object::BlindHost;
+use crate::allocator::HostHeap;
+use crate::host_calls::bus::MessageBus;
+use crate::host_calls::ids::IdRegistry;
+use crate::host_calls::random::Random;
+use crate::host_calls::strings::StringTable;
+use anyhow::{anyhow, Result};
+use fat_ptr::shared::SharedSlice;
+use std::collections::HashMap;
+use wasmtime::{Extern, Global, Mutability, Val};
+
+// The whole simulated world at one moment, to go back to (rollback netcode, rollback.rs):
+//
+//   - all of shared memory: every plugin's data and stack, the host heap and what's in it
+//   - the host heap's free list and live allocations
+//   - the id and string tables, host_random's generator, and bus messages not yet delivered
+//   - plugins' exported mutable globals
+//
+// That covers what a plugin can reach between ticks, as long as it keeps its state in memory
+// (Rust plugins do). Unexported mutable globals are out of the host's reach. Logs, metrics
+// counters, profiles and the outside world (files, storage, the network) aren't rolled back.
+//
+// Snapshots are taken and restored between ticks, never during one: a restore under a running
+// plugin would pull its memory out from under it.
+
+pub struct Snapshot {
+    memory: Vec<u8>,
+    heap: HostHeap,
+    live: HashMap<u32, (String, u32)>,
+    ids: IdRegistry,
+    strings: StringTable,
+    random: Random,
+    bus: MessageBus,
+    globals: Vec<(Global, Val)>,
+}
+
+impl Snapshot {
+    pub fn take(host: &mut BlindHost) -> Result<Self> {
+        crate::scope!("snapshot_take");
+        let len = host.store.data().shared_memory.data().len();
+        let memory = host.view_mem(0, i32::try_from(len)?)?.to_vec();
+
+        let instances: Vec<_> = host.store.data().instances.values().copied().collect();
+        let mut globals = Vec::new();
+        for instance in instances {
+            let exports: Vec<Extern> = instance
+                .exports(&mut host.store)
+                .map(|e| e.into_extern())
+                .collect();
+            for export in exports {
+                if let Extern::Global(global) = export {
+                    if global.ty(&host.store).mutability() == Mutability::Var {
+                        globals.push((global, global.get(&mut host.store)));
+                    }
+                }
+            }
+        }
+
+        let state = host.store.data();
+        Ok(Self {
+            memory,
+            heap: state.heap.lock().unwrap().clone(),
+            live: state.metrics.lock().unwrap().live_allocations(),
+            ids: state.ids.lock().unwrap().clone(),
+            strings: state.strings.lock().unwrap().clone(),
+            random: state.random.lock().unwrap().clone(),
+            bus: state.bus.lock().unwrap().clone(),
+            globals,
+        })
+    }
+
+    /// Puts the world back as it was when the snapshot was taken. Memory can't shrink, so what it
+    /// grew by since is zeroed and handed back to the heap as free.
+    pub fn restore(&self, host: &mut BlindHost) -> Result<()> {
+        crate::scope!("snapshot_restore");
+        let state = host.store.data();
+        let memory = SharedSlice::of(&state.shared_memory);
+        let grown = memory.len() - self.memory.len();
+        if !memory.write(0, &self.memory)
+            || !memory.write(self.memory.len() as i32, &vec![0; grown])
+        {
+            return Err(anyhow!(
+                "Snapshot of {} bytes doesn't fit in memory",
+                self.memory.len()
+            ));
+        }
+
+        let mut heap = self.heap.clone();
+        if grown > 0 {
+            heap.dealloc(self.memory.len() as u32, grown as u32);
+        }
+        *state.heap.lock().unwrap() = heap;
+        state
+            .metrics
+            .lock()
+            .unwrap()
+            .restore_live_allocations(self.live.clone());
+        *state.ids.lock().unwrap() = self.ids.clone();
+        *state.strings.lock().unwrap() = self.strings.clone();
+        *state.random.lock().unwrap() = self.random.clone();
+        *state.bus.lock().unwrap() = self.bus.clone();
+        for (global, value) in &self.globals {
+            global.set(&mut host.store, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Bytes of memory held.
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+}
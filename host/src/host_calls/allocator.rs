@@ -1,20 +1,78 @@
 use crate::host::caller_state::HostState;
-use wasmtime::Caller;
+use crate::host::metrics::HOST_OWNER;
+use anyhow::{anyhow, Result};
+use wasmtime::{Caller, Linker};
 
 const WASM_PAGE_SIZE: u64 = 65536;
 const GROWTH_CHUNK_SIZE: u64 = 80;
 const HEAP_START_ADDR: u32 = 32 * 1024 * 1024;
 
-pub fn host_alloc(caller: Caller<'_, HostState>, size: i32) -> i32 {
-    let size = (size as u32 + 7) & !7;
-    let memory = caller.data().shared_memory.clone();
-    let mut heap = caller.data().heap.lock().unwrap();
+// host_alloc / host_dealloc, registered per plugin so the metrics know whose memory it is:
+// host_alloc answers 0 past the plugin's heap quota (host/quotas.rs), and host_dealloc traps on
+// another plugin's block
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    let owner = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_alloc",
+        move |caller: Caller<'_, HostState>, size: i32| -> i32 {
+            crate::scope!("host_alloc");
+            alloc_owned(caller.data(), &owner, size)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "host_dealloc",
+        move |caller: Caller<'_, HostState>, ptr: i32, _size: i32| {
+            free_owned(caller.data(), &plugin, ptr)
+        },
+    )?;
+    Ok(())
+}
+
+// Also used by host calls that hand buffers to plugins. Returns 0 when out of memory.
+pub fn alloc_shared(state: &HostState, size: i32) -> i32 {
+    alloc_owned(state, HOST_OWNER, size)
+}
+
+// Charged to `owner`, so unloading it gives the memory back; 0 past its heap quota too
+pub fn alloc_owned(state: &HostState, owner: &str, size: i32) -> i32 {
+    if size < 0 {
+        return 0;
+    }
+    // Empty allocations still take a block, or the next one would share their address
+    let size = (size.max(1) as u32 + 7) & !7;
+    if owner != HOST_OWNER {
+        let live = state.metrics.lock().unwrap().live_bytes(owner);
+        if !state
+            .quotas
+            .lock()
+            .unwrap()
+            .heap_allows(owner, live, size as u64)
+        {
+            return 0;
+        }
+    }
+    let ptr = alloc_block(state, size);
+    if ptr != 0 {
+        state
+            .metrics
+            .lock()
+            .unwrap()
+            .allocated(owner, ptr as u32, size);
+    }
+    ptr
+}
+
+fn alloc_block(state: &HostState, size: u32) -> i32 {
+    let memory = state.shared_memory.clone();
+    let mut heap = state.heap.lock().unwrap();
 
     if let Some(addr) = heap.alloc(size) {
         return addr as i32;
     }
 
-    let current_mem_size = (memory.size() * WASM_PAGE_SIZE) as u64;
+    let current_mem_size = memory.size() * WASM_PAGE_SIZE;
     let growth_start_addr =
         if heap.free_blocks.is_empty() && current_mem_size < HEAP_START_ADDR as u64 {
             HEAP_START_ADDR
@@ -22,10 +80,7 @@ pub fn host_alloc(caller: Caller<'_, HostState>, size: i32) -> i32 {
             current_mem_size as u32
         };
 
-    let required_growth = std::cmp::max(
-        GROWTH_CHUNK_SIZE,
-        (size as u64 + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE,
-    );
+    let required_growth = std::cmp::max(GROWTH_CHUNK_SIZE, (size as u64).div_ceil(WASM_PAGE_SIZE));
 
     if memory.grow(required_growth).is_err() {
         return 0;
@@ -37,11 +92,35 @@ pub fn host_alloc(caller: Caller<'_, HostState>, size: i32) -> i32 {
     heap.alloc(size).unwrap_or(0) as i32
 }
 
-pub fn host_dealloc(caller: Caller<'_, HostState>, ptr: i32, size: i32) {
+// Gives back what alloc_shared handed out, as large as it was handed out
+pub fn free_shared(state: &HostState, ptr: i32, _size: i32) {
+    let _ = free_owned(state, HOST_OWNER, ptr);
+}
+
+// Gives back a block `owner` holds. Double frees and made-up pointers are ignored: freeing them
+// would let the heap hand out memory that's still in use. Another owner's block is an error.
+pub fn free_owned(state: &HostState, owner: &str, ptr: i32) -> Result<()> {
     if ptr == 0 {
-        return;
+        return Ok(());
     }
     let ptr = ptr as u32;
-    let size = (size as u32 + 7) & !7;
-    caller.data().heap.lock().unwrap().dealloc(ptr, size);
+    let mut metrics = state.metrics.lock().unwrap();
+    match metrics.owner(ptr) {
+        None => return Ok(()),
+        Some(of) if of != owner => {
+            return Err(anyhow!(
+                "'{}' tried to free {}, which is '{}''s",
+                owner,
+                ptr,
+                of
+            ));
+        }
+        Some(_) => {}
+    }
+    let Some(size) = metrics.freed(ptr) else {
+        return Ok(());
+    };
+    drop(metrics);
+    state.heap.lock().unwrap().dealloc(ptr, size);
+    Ok(())
 }
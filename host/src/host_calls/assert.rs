@@ -0,0 +1,56 @@
+use crate::host::caller_state::HostState;
+use crate::host::logger::Level;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::Result;
+use wasmtime::{Caller, Linker};
+
+// Guest-side invariants the host knows about.
+//
+//   host_assert(cond, msg_ptr, msg_len)    nothing when cond != 0; otherwise the message is
+//                                          logged at error level with the plugin and tick,
+//                                          and kept for the embedder (BlindHost::take_assert_failures)
+//
+// Plugins keep running either way: whether a failed assertion pauses the TUI or fails a
+// headless run is the embedder's call (`--on-assert`).
+
+/// A failed host_assert.
+#[derive(Clone, Debug)]
+pub struct AssertFailure {
+    pub plugin: String,
+    // The tick it failed in, counting from 1; 0 before the first
+    pub tick: u64,
+    pub message: String,
+}
+
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "host_assert",
+        move |caller: Caller<'_, HostState>, cond: i32, ptr: i32, len: i32| -> Result<()> {
+            if cond != 0 {
+                return Ok(());
+            }
+            validate_guest_range(&caller, &plugin, "host_assert", ptr, len)?;
+            let message = match read_guest(&caller, ptr, len) {
+                Some(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                None => "(message outside shared memory)".to_string(),
+            };
+            let state = caller.data();
+            // end_tick books ticks once they're over, so this one is the next
+            let tick = state.metrics.lock().unwrap().ticks() + 1;
+            state.logger.lock().unwrap().log(
+                &plugin,
+                Level::Error,
+                &format!("Assertion failed at tick {}: {}", tick, message),
+            );
+            state.assert_failures.lock().unwrap().push(AssertFailure {
+                plugin: plugin.clone(),
+                tick,
+                message,
+            });
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
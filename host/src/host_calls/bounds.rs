@@ -0,0 +1,78 @@
+use crate::host::caller_state::HostState;
+use anyhow::{anyhow, Result};
+use wasmtime::Caller;
+
+// The check on every (ptr, len) a plugin hands a host call, before the host reads or writes it:
+// the range must lie in shared memory and in the caller's own slot (its data and stack) or in
+// one live host heap allocation. Anything else is a plugin bug that would otherwise read
+// someone else's bytes, or be answered with a quiet error code, so the call traps instead, in
+// every build.
+//
+// Every host call taking guest pointers is registered per plugin (the host's in
+// register_plugin_calls, the embedder's through BlindHost::new), so the caller is always known.
+//
+// Empty ranges are fine anywhere: an empty Rust slice's pointer is dangling by design.
+
+/// Fails if `ptr..ptr + len`, passed to `call`, lies outside `plugin`'s slot and outside every
+/// live heap allocation, or past the end of shared memory.
+pub fn validate_guest_range(
+    caller: &Caller<'_, HostState>,
+    plugin: &str,
+    call: &str,
+    ptr: i32,
+    len: i32,
+) -> Result<()> {
+    let state = caller.data();
+    if len == 0 {
+        return Ok(());
+    }
+    if ptr < 0 || len < 0 {
+        return Err(anyhow!(
+            "{} was passed {}+{} by '{}': negative",
+            call,
+            ptr,
+            len,
+            plugin
+        ));
+    }
+    let (start, end) = (ptr as u64, ptr as u64 + len as u64);
+    if end > state.shared_memory.data_size() as u64 {
+        return Err(anyhow!(
+            "{} was passed {}+{} by '{}': past the end of memory",
+            call,
+            ptr,
+            len,
+            plugin
+        ));
+    }
+
+    let in_slot = state
+        .slots
+        .iter()
+        .filter(|(name, _)| name == plugin)
+        .any(|&(_, base)| base as u64 <= start && end <= base as u64 + state.slot_size as u64);
+    if in_slot {
+        return Ok(());
+    }
+    if let Some((block, size)) = state.metrics.lock().unwrap().live_block(ptr as u32) {
+        if end <= block as u64 + size as u64 {
+            return Ok(());
+        }
+        return Err(anyhow!(
+            "{} was passed {}+{} by '{}': past the end of the allocation {}+{}",
+            call,
+            ptr,
+            len,
+            plugin,
+            block,
+            size
+        ));
+    }
+    Err(anyhow!(
+        "{} was passed {}+{} by '{}': outside its slot and every live allocation",
+        call,
+        ptr,
+        len,
+        plugin
+    ))
+}
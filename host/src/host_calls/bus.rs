@@ -0,0 +1,210 @@
+use crate::host::audit::{self, audit, AuditEntry};
+use crate::host::caller_state::HostState;
+use crate::host::host_object::BlindHost;
+use crate::host::logger::Level;
+use crate::host_calls::allocator::{alloc_shared, free_shared};
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::{anyhow, Context, Result};
+use bus_protocol::chat::CHAT;
+use bus_protocol::lobby::LOBBY;
+use bus_protocol::Envelope;
+use std::collections::{BTreeSet, VecDeque};
+use wasmtime::{Caller, Linker, Val};
+
+// Queued plugin-to-plugin messages; see bus_protocol for the envelope and the ABI.
+// Messages sit here until `deliver` runs between ticks, so a send never re-enters a plugin.
+// Those to LOBBY go to the host's lobby (lobby.rs) instead of a plugin, and those to CHAT to
+// its chat (chat.rs). Plugins suspended by their quotas (host/quotas.rs) miss theirs. Messages
+// handed to a plugin, or that it couldn't take, go in the audit log (host/audit.rs).
+// Plugins can also send to `remote` ones, loaded on another host (split play, embedder/split.rs),
+// whose messages the embedder takes out with `take_remote` and carries over.
+#[derive(Default, Clone)]
+pub struct MessageBus {
+    queue: VecDeque<(String, Envelope)>,
+    remote: BTreeSet<String>,
+}
+
+impl MessageBus {
+    pub fn push(&mut self, to: String, envelope: Envelope) {
+        self.queue.push_back((to, envelope));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Lets plugins send to `name` though it isn't loaded here.
+    pub fn add_remote(&mut self, name: String) {
+        self.remote.insert(name);
+    }
+
+    pub fn remove_remote(&mut self, name: &str) {
+        self.remote.remove(name);
+    }
+
+    pub fn is_remote(&self, name: &str) -> bool {
+        self.remote.contains(name)
+    }
+
+    /// Takes out the queued messages for remote plugins, oldest first.
+    pub fn take_remote(&mut self) -> Vec<(String, Envelope)> {
+        let (remote, local) = self
+            .queue
+            .drain(..)
+            .partition(|(to, _)| self.remote.contains(to));
+        self.queue = local;
+        remote.into()
+    }
+
+    /// Drops the queued messages to `name`, which is gone.
+    pub fn drop_to(&mut self, name: &str) {
+        self.queue.retain(|(to, _)| to != name);
+    }
+
+    fn take(&mut self) -> Vec<(String, Envelope)> {
+        self.queue.drain(..).collect()
+    }
+
+    // Puts messages that weren't delivered back in front, ahead of those sent meanwhile
+    fn requeue(&mut self, messages: impl IntoIterator<Item = (String, Envelope)>) {
+        let mut queue: VecDeque<_> = messages.into_iter().collect();
+        queue.append(&mut self.queue);
+        self.queue = queue;
+    }
+}
+
+// Registered per plugin by prepare_env, since the host stamps each envelope with its sender
+pub(crate) fn register_bus_send(linker: &mut Linker<HostState>, caller_name: String) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "bus_send",
+        move |caller: Caller<'_, HostState>,
+              to_ptr: i32,
+              to_len: i32,
+              message_ptr: i32,
+              message_len: i32|
+              -> Result<i32> {
+            validate_guest_range(&caller, &caller_name, "bus_send", to_ptr, to_len)?;
+            validate_guest_range(&caller, &caller_name, "bus_send", message_ptr, message_len)?;
+            let Some(to) =
+                read_guest(&caller, to_ptr, to_len).and_then(|b| String::from_utf8(b).ok())
+            else {
+                return Ok(-2);
+            };
+            let Some(mut envelope) =
+                read_guest(&caller, message_ptr, message_len).and_then(|b| Envelope::decode(&b))
+            else {
+                return Ok(-2);
+            };
+            let mut bus = caller.data().bus.lock().unwrap();
+            if !caller.data().instances.contains_key(&to)
+                && !bus.is_remote(&to)
+                && to != LOBBY
+                && to != CHAT
+            {
+                return Ok(-1);
+            }
+            envelope.sender = caller_name.clone();
+            bus.push(to, envelope);
+            Ok(0)
+        },
+    )?;
+    Ok(())
+}
+
+/// Hands every queued message to its target's `on_message`. Messages sent while delivering wait
+/// for the next call, so two plugins answering each other can't spin forever.
+/// Call it from the embedder's main loop. Returns how many messages were delivered; a plugin
+/// failing to handle one doesn't keep the rest from theirs, and the failures come back together.
+pub fn deliver(host: &mut BlindHost) -> Result<usize> {
+    let mut messages = host.store.data().bus.lock().unwrap().take().into_iter();
+
+    let mut delivered = 0;
+    let mut failures = Vec::new();
+    while let Some((to, envelope)) = messages.next() {
+        if to == LOBBY {
+            let answers = host.store.data().lobby.lock().unwrap().handle(&envelope);
+            let mut bus = host.store.data().bus.lock().unwrap();
+            for (to, answer) in answers {
+                bus.push(to, answer);
+            }
+            delivered += 1;
+            continue;
+        }
+        if to == CHAT {
+            if let Err(e) = host.store.data().chat.lock().unwrap().handle(&envelope) {
+                host.store.data().logger.lock().unwrap().log(
+                    "host",
+                    Level::Warn,
+                    &format!("{:#}", e),
+                );
+            }
+            delivered += 1;
+            continue;
+        }
+        let bytes = envelope.encode();
+        let entry = |status| AuditEntry {
+            event: "message",
+            caller: &envelope.sender,
+            callee: &to,
+            export: "on_message",
+            payload: Some(bytes.len() as u32),
+            status,
+        };
+        // Suspended by its quotas (host/quotas.rs): it hears nothing until it's resumed
+        if host.store.data().quotas.lock().unwrap().is_suspended(&to) {
+            audit(host.store.data(), entry(audit::REFUSED));
+            continue;
+        }
+        let Ok(func) = host.get_func(&to, "on_message") else {
+            audit(host.store.data(), entry(audit::REFUSED));
+            let text = format!(
+                "'{}' has no on_message, dropped a message from '{}'",
+                to, envelope.sender
+            );
+            host.store
+                .data()
+                .logger
+                .lock()
+                .unwrap()
+                .log("host", Level::Warn, &text);
+            continue;
+        };
+        let ptr = alloc_shared(host.store.data(), bytes.len() as i32);
+        if ptr == 0 {
+            // Nothing more gets through this call; they're still queued for the next
+            let len = bytes.len();
+            host.store
+                .data()
+                .bus
+                .lock()
+                .unwrap()
+                .requeue(std::iter::once((to, envelope)).chain(messages));
+            return Err(anyhow!("Out of shared memory for a {} byte message", len));
+        }
+        host.write_mem(ptr, &bytes)?;
+        let args = [Val::I32(ptr), Val::I32(bytes.len() as i32)];
+        let result = host.profiled(&to, "on_message", |store| func.call(store, &args, &mut []));
+        free_shared(host.store.data(), ptr, bytes.len() as i32);
+        audit(host.store.data(), entry(audit::status(&result, None)));
+        match result.with_context(|| {
+            format!(
+                "'{}' failed to handle a message from '{}'",
+                to, envelope.sender
+            )
+        }) {
+            Ok(()) => delivered += 1,
+            Err(e) => failures.push(format!("{:#}", e)),
+        }
+    }
+    if failures.is_empty() {
+        Ok(delivered)
+    } else {
+        Err(anyhow!("{}", failures.join("; ")))
+    }
+}
@@ -0,0 +1,153 @@
+use crate::host::audit::{self, audit, AuditEntry};
+use crate::host::backtrace;
+use crate::host::call_graph::{CallKind, CallRecord};
+use crate::host::caller_state::HostState;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::{anyhow, Result};
+use fat_ptr::envelope;
+use fat_ptr::FatPtr;
+use std::time::Instant;
+use wasmtime::{Caller, Linker};
+
+// Calls into another plugin by name, resolved on every call (host_link_call resolves once).
+//
+//   call(module_ptr, module_len, func_ptr, func_len, arg0, arg1) -> i64
+//       module::func(arg0, arg1) -> i64, usually a (ptr, len) payload and a packed response
+//   fire_and_forget(module_ptr, module_len, func_ptr, func_len, arg0, arg1)
+//       the same, for when the caller doesn't want the answer: a packed response is handed
+//       back through the module's free_response
+//
+// Both are timed in the profile and, with BlindHostConfig::trace_calls, in the call graph. With
+// an audit log, each is recorded there too, with what came of it (host/audit.rs).
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    let caller_name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "call",
+        move |mut c: Caller<'_, HostState>,
+              module_ptr: i32,
+              module_len: i32,
+              func_ptr: i32,
+              func_len: i32,
+              arg0: i32,
+              arg1: i32|
+              -> Result<i64> {
+            let target = (module_ptr, module_len, func_ptr, func_len);
+            call_by_name(&mut c, &caller_name, target, (arg0, arg1), CallKind::Call)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "fire_and_forget",
+        move |mut c: Caller<'_, HostState>,
+              module_ptr: i32,
+              module_len: i32,
+              func_ptr: i32,
+              func_len: i32,
+              arg0: i32,
+              arg1: i32|
+              -> Result<()> {
+            let target = (module_ptr, module_len, func_ptr, func_len);
+            call_by_name(
+                &mut c,
+                &plugin,
+                target,
+                (arg0, arg1),
+                CallKind::FireAndForget,
+            )
+            .map(|_| ())
+        },
+    )?;
+    Ok(())
+}
+
+fn call_by_name(
+    c: &mut Caller<'_, HostState>,
+    caller_name: &str,
+    (module_ptr, module_len, func_ptr, func_len): (i32, i32, i32, i32),
+    args: (i32, i32),
+    kind: CallKind,
+) -> Result<i64> {
+    let read = |c: &Caller<'_, HostState>, ptr, len| {
+        validate_guest_range(c, caller_name, "call", ptr, len)?;
+        read_guest(c, ptr, len)
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .ok_or(anyhow!(
+                "'{}' passed a name outside shared memory",
+                caller_name
+            ))
+    };
+    let module = read(c, module_ptr, module_len)?;
+    let export = read(c, func_ptr, func_len)?;
+
+    let payload = Some(args.1.max(0) as u32);
+    let entry = |status| AuditEntry {
+        event: kind.name(),
+        caller: caller_name,
+        callee: &module,
+        export: &export,
+        payload,
+        status,
+    };
+
+    let Some(instance) = c.data().instances.get(&module).copied() else {
+        audit(c.data(), entry(audit::REFUSED));
+        return Err(anyhow!("Provider '{}' not found", module));
+    };
+    let Ok(func) = instance.get_typed_func::<(i32, i32), i64>(&mut *c, &export) else {
+        audit(c.data(), entry(audit::REFUSED));
+        return Err(anyhow!(
+            "'{}::{}' isn't an (i32, i32) -> i64 export",
+            module,
+            export
+        ));
+    };
+
+    let start = Instant::now();
+    let result = func.call(&mut *c, args);
+    let took = start.elapsed();
+    c.data()
+        .profiler
+        .lock()
+        .unwrap()
+        .record(&module, &export, took);
+    audit(
+        c.data(),
+        entry(audit::status(&result, result.as_ref().ok().copied())),
+    );
+    {
+        let mut graph = c.data().call_graph.lock().unwrap();
+        if graph.enabled {
+            graph.called(CallRecord {
+                caller: caller_name.to_string(),
+                callee: module.clone(),
+                export: export.clone(),
+                kind,
+                payload,
+                took,
+            });
+        }
+    }
+    let packed = result.map_err(|e| backtrace::symbolicate(e, &c.data().modules))?;
+
+    // Error envelopes are the callee's buffers too
+    let buffer = match envelope::is_error(packed) {
+        true => envelope::unpack_error(packed),
+        false => FatPtr::unpack(packed),
+    };
+    if kind == CallKind::FireAndForget && !buffer.is_null() {
+        if let Ok(free) = instance.get_typed_func::<(i32, i32), ()>(&mut *c, "free_response") {
+            free.call(&mut *c, (buffer.ptr, buffer.len))?;
+        }
+    }
+    // Otherwise the caller frees what it's answered, so it's the caller's from here on
+    if kind == CallKind::Call && !buffer.is_null() {
+        c.data()
+            .metrics
+            .lock()
+            .unwrap()
+            .transferred(buffer.ptr as u32, caller_name);
+    }
+    Ok(packed)
+}
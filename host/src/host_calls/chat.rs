@@ -0,0 +1,65 @@
+use anyhow::{anyhow, Result};
+use bus_protocol::chat::ChatLine;
+use bus_protocol::Envelope;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Lines kept for the overlay
+const KEPT_LINES: usize = 200;
+
+// The chat plugins post to over the bus as bus_protocol::chat::CHAT, which has the protocol,
+// and players type into (embedder/chat.rs). What's posted here waits in `outgoing` for split
+// play to send it to the other players; what they sent comes in with `receive`.
+#[derive(Default)]
+pub struct Chat {
+    lines: VecDeque<(Instant, ChatLine)>,
+    outgoing: Vec<ChatLine>,
+    // Lines ever shown, so the embedder can tell new ones came in
+    count: u64,
+}
+
+impl Chat {
+    /// A line written here: shown, and sent on to the other players.
+    pub fn post(&mut self, line: ChatLine) {
+        self.outgoing.push(line.clone());
+        self.receive(line);
+    }
+
+    /// A line from another player, only shown.
+    pub fn receive(&mut self, line: ChatLine) {
+        if self.lines.len() == KEPT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((Instant::now(), line));
+        self.count += 1;
+    }
+
+    /// A plugin's message to CHAT, posted under its name; one that isn't UTF-8 is refused.
+    pub fn handle(&mut self, envelope: &Envelope) -> Result<()> {
+        let text = std::str::from_utf8(&envelope.payload)
+            .map_err(|_| anyhow!("'{}' posted a line that isn't UTF-8", envelope.sender))?;
+        self.post(ChatLine::new(&envelope.sender, text));
+        Ok(())
+    }
+
+    pub fn take_outgoing(&mut self) -> Vec<ChatLine> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The last `most` lines, oldest first; with `within`, only those that came in that recently.
+    pub fn recent(&self, most: usize, within: Option<Duration>) -> Vec<ChatLine> {
+        let shown: Vec<ChatLine> = self
+            .lines
+            .iter()
+            .rev()
+            .take(most)
+            .take_while(|(at, _)| within.is_none_or(|within| at.elapsed() < within))
+            .map(|(_, line)| line.clone())
+            .collect();
+        shown.into_iter().rev().collect()
+    }
+}
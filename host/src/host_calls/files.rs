@@ -0,0 +1,117 @@
+use crate::host::caller_state::HostState;
+use crate::host_calls::allocator::alloc_owned;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::{read_guest, write_guest};
+use anyhow::{anyhow, Context, Result};
+use fat_ptr::FatPtr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wasmtime::{Caller, Linker};
+
+// Where user-visible plugin files (exports, imports) go unless embedders pick another directory
+pub const DEFAULT_FILES_DIR: &str = "plugin-files";
+
+// Whole-file access to one directory the user can see, for data plugins exchange with other
+// programs. Unlike storage, names are kept as given. Opt-in: embedders call `register_host_calls`.
+//
+//   host_file_read(name_ptr, name_len) -> i64
+//       contents as (len << 32 | ptr) in a host_alloc'd buffer the plugin frees with
+//       host_dealloc; -1 if there's no such file, 0 if it can't be read
+//   host_file_write(name_ptr, name_len, data_ptr, data_len) -> i32    0 ok, -1 failed
+pub struct FileAccess {
+    root: PathBuf,
+}
+
+impl FileAccess {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(name)?;
+        match std::fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+        }
+    }
+
+    pub fn write(&self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(name)?;
+        std::fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create '{}'", self.root.display()))?;
+        std::fs::write(&path, data).with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+
+    // Plain file names only: no directories, nothing hidden
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '));
+        if !valid {
+            return Err(anyhow!("Invalid file name '{}'", name));
+        }
+        Ok(self.root.join(name))
+    }
+}
+
+pub fn register_host_calls(
+    linker: &mut Linker<HostState>,
+    plugin: String,
+    files: Arc<FileAccess>,
+) -> Result<()> {
+    let (caller_name, access) = (plugin.clone(), files.clone());
+    linker.func_wrap(
+        "env",
+        "host_file_read",
+        move |caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32| -> Result<i64> {
+            validate_guest_range(&caller, &caller_name, "host_file_read", name_ptr, name_len)?;
+            let Some(name) = read_name(&caller, name_ptr, name_len) else {
+                return Ok(0);
+            };
+            let data = match access.read(&name) {
+                Ok(Some(data)) => data,
+                Ok(None) => return Ok(-1),
+                Err(_) => return Ok(0),
+            };
+
+            let ptr = alloc_owned(caller.data(), &caller_name, data.len() as i32);
+            if ptr == 0 || !write_guest(&caller, ptr, &data) {
+                return Ok(0);
+            }
+            Ok(FatPtr::new(ptr, data.len() as i32).pack())
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "host_file_write",
+        move |caller: Caller<'_, HostState>,
+              name_ptr: i32,
+              name_len: i32,
+              data_ptr: i32,
+              data_len: i32|
+              -> Result<i32> {
+            validate_guest_range(&caller, &plugin, "host_file_write", name_ptr, name_len)?;
+            validate_guest_range(&caller, &plugin, "host_file_write", data_ptr, data_len)?;
+            let (Some(name), Some(data)) = (
+                read_name(&caller, name_ptr, name_len),
+                read_guest(&caller, data_ptr, data_len),
+            ) else {
+                return Ok(-1);
+            };
+            Ok(if files.write(&name, &data).is_ok() {
+                0
+            } else {
+                -1
+            })
+        },
+    )?;
+    Ok(())
+}
+
+fn read_name(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_guest(caller, ptr, len)?).ok()
+}
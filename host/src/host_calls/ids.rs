@@ -0,0 +1,98 @@
+use crate::host::caller_state::HostState;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmtime::{Caller, Linker};
+
+// Names to small integer IDs, shared by every plugin, so nobody has to agree on magic numbers.
+//
+//   host_register_id(namespace_ptr, namespace_len, name_ptr, name_len) -> i32
+//       the ID for `name` within `namespace`, handed out the first time anyone asks and the
+//       same for everyone after that; -1 if either string isn't valid UTF-8 or is empty
+//
+// IDs start at 1 in each namespace ("component", "resource", ...) and only live as long as
+// the host, so don't persist them.
+#[derive(Default, Clone)]
+pub struct IdRegistry {
+    namespaces: HashMap<String, Namespace>,
+}
+
+#[derive(Default, Clone)]
+struct Namespace {
+    ids: HashMap<String, i32>,
+    next: i32,
+}
+
+impl IdRegistry {
+    pub fn register(&mut self, namespace: &str, name: &str) -> i32 {
+        let namespace = self.namespaces.entry(namespace.to_string()).or_default();
+        if let Some(&id) = namespace.ids.get(name) {
+            return id;
+        }
+        namespace.next += 1;
+        namespace.ids.insert(name.to_string(), namespace.next);
+        namespace.next
+    }
+
+    /// The ID `name` already has, without handing out a new one.
+    pub fn get(&self, namespace: &str, name: &str) -> Option<i32> {
+        self.namespaces.get(namespace)?.ids.get(name).copied()
+    }
+}
+
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "host_register_id",
+        move |caller: Caller<'_, HostState>,
+              namespace_ptr: i32,
+              namespace_len: i32,
+              name_ptr: i32,
+              name_len: i32| {
+            host_register_id(
+                caller,
+                &plugin,
+                namespace_ptr,
+                namespace_len,
+                name_ptr,
+                name_len,
+            )
+        },
+    )?;
+    Ok(())
+}
+
+fn host_register_id(
+    caller: Caller<'_, HostState>,
+    plugin: &str,
+    namespace_ptr: i32,
+    namespace_len: i32,
+    name_ptr: i32,
+    name_len: i32,
+) -> Result<i32> {
+    validate_guest_range(
+        &caller,
+        plugin,
+        "host_register_id",
+        namespace_ptr,
+        namespace_len,
+    )?;
+    validate_guest_range(&caller, plugin, "host_register_id", name_ptr, name_len)?;
+    let read = |ptr, len| {
+        read_guest(&caller, ptr, len)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .filter(|s| !s.is_empty())
+    };
+    let (Some(namespace), Some(name)) =
+        (read(namespace_ptr, namespace_len), read(name_ptr, name_len))
+    else {
+        return Ok(-1);
+    };
+    Ok(caller
+        .data()
+        .ids
+        .lock()
+        .unwrap()
+        .register(&namespace, &name))
+}
@@ -0,0 +1,118 @@
+use bus_protocol::lobby::{
+    Event, Reply, Request, Session, Slot, EVENT_CHANGED, EVENT_CLOSED, EVENT_READY, LOBBY,
+    STATUS_FULL, STATUS_MALFORMED, STATUS_NOT_MEMBER, STATUS_NO_SESSION, STATUS_OK,
+};
+use bus_protocol::{Envelope, KIND_REQUEST};
+use std::collections::BTreeMap;
+
+// The lobby plugins reach over the bus as bus_protocol::lobby::LOBBY, which has the protocol.
+// bus::deliver hands it the requests sent its way and queues what it answers, so replies and
+// events arrive on the next delivery like any plugin's would.
+#[derive(Default)]
+pub struct Lobby {
+    sessions: BTreeMap<u32, Session>,
+    next_id: u32,
+}
+
+impl Lobby {
+    /// Answers one request: the reply to its sender, and events for the session's other members.
+    pub fn handle(&mut self, envelope: &Envelope) -> Vec<(String, Envelope)> {
+        if envelope.kind != KIND_REQUEST {
+            return Vec::new();
+        }
+        let member = envelope.sender.as_str();
+        let (reply, event) = match Request::decode(&envelope.payload) {
+            Some(request) => self.apply(member, request),
+            None => (Reply::status(STATUS_MALFORMED), None),
+        };
+
+        let mut answers = vec![envelope.reply(reply.encode())];
+        if let Some(event) = event {
+            let payload = event.encode();
+            for other in event.session.members().filter(|other| *other != member) {
+                answers.push((other.to_string(), Envelope::event(payload.clone())));
+            }
+        }
+        for (_, answer) in &mut answers {
+            answer.sender = LOBBY.to_string();
+        }
+        answers
+    }
+
+    fn apply(&mut self, member: &str, request: Request) -> (Reply, Option<Event>) {
+        let session_id = match request {
+            Request::List => {
+                let reply = Reply {
+                    status: STATUS_OK,
+                    sessions: self.sessions.values().cloned().collect(),
+                };
+                return (reply, None);
+            }
+            Request::Create { slots, name } => {
+                if slots == 0 {
+                    return (Reply::status(STATUS_MALFORMED), None);
+                }
+                self.next_id += 1;
+                let mut session = Session {
+                    id: self.next_id,
+                    name,
+                    slots: vec![Slot::default(); slots as usize],
+                };
+                session.slots[0].member = Some(member.to_string());
+                self.sessions.insert(session.id, session.clone());
+                return (ok(session), None);
+            }
+            Request::Join(id) | Request::Leave(id) | Request::Ready(id, _) => id,
+        };
+        let Some(session) = self.sessions.get_mut(&session_id) else {
+            return (Reply::status(STATUS_NO_SESSION), None);
+        };
+
+        match request {
+            Request::Join(_) => {
+                if session.slot_of(member).is_some() {
+                    return (ok(session.clone()), None);
+                }
+                let Some(free) = session.slots.iter_mut().find(|slot| slot.member.is_none()) else {
+                    return (Reply::status(STATUS_FULL), None);
+                };
+                free.member = Some(member.to_string());
+            }
+            Request::Leave(_) => {
+                let Some(slot) = session.slot_of(member) else {
+                    return (Reply::status(STATUS_NOT_MEMBER), None);
+                };
+                // The creator leaving ends it for everyone
+                if slot == 0 {
+                    let session = self.sessions.remove(&session_id).unwrap();
+                    return (Reply::status(STATUS_OK), Some(event(EVENT_CLOSED, session)));
+                }
+                session.slots[slot] = Slot::default();
+            }
+            Request::Ready(_, ready) => {
+                let Some(slot) = session.slot_of(member) else {
+                    return (Reply::status(STATUS_NOT_MEMBER), None);
+                };
+                session.slots[slot].ready = ready;
+            }
+            Request::List | Request::Create { .. } => unreachable!(),
+        }
+        let kind = if session.is_ready() {
+            EVENT_READY
+        } else {
+            EVENT_CHANGED
+        };
+        (ok(session.clone()), Some(event(kind, session.clone())))
+    }
+}
+
+fn ok(session: Session) -> Reply {
+    Reply {
+        status: STATUS_OK,
+        sessions: vec![session],
+    }
+}
+
+fn event(event: u8, session: Session) -> Event {
+    Event { event, session }
+}
@@ -1,2 +1,18 @@
 pub mod allocator;
+pub mod assert;
+pub mod bounds;
+pub mod bus;
+pub mod call;
+pub mod chat;
+pub mod files;
+pub mod ids;
+pub mod lobby;
 pub mod print;
+pub mod random;
+pub mod replicate;
+pub mod save_backend;
+pub mod saves;
+pub mod storage;
+pub mod strings;
+pub mod sync;
+pub mod wasi;
@@ -1,21 +1,60 @@
 use crate::host::caller_state::HostState;
+use crate::host::logger::Level;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
 use anyhow::Result;
-use wasmtime::Caller;
+use wasmtime::{Caller, Linker};
 
-pub fn host_print(caller: Caller<'_, HostState>, ptr: i32, len: i32) -> Result<()> {
-    let mem = caller.data().shared_memory.data();
-    if ptr < 0 || (ptr as usize + len as usize) > mem.len() {
-        return Ok(());
-    }
-
-    let base_ptr = mem.as_ptr() as *const u8;
+// Plugin logging, through the host's Logger (levels, per-plugin filters, sinks; logger.rs).
+//
+//   host_print(ptr, len)           a line at info level
+//   host_log(level, ptr, len)      a line at `level`: 1 error, 2 warn, 3 info, 4 debug, 5 trace
+//
+// Registered per plugin, so every line is stamped with the plugin that wrote it.
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    let name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_print",
+        move |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            log(&caller, &name, "host_print", Level::Info, ptr, len)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "host_log",
+        move |caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+            log(
+                &caller,
+                &plugin,
+                "host_log",
+                Level::from_abi(level),
+                ptr,
+                len,
+            )
+        },
+    )?;
+    Ok(())
+}
 
-    let s = unsafe {
-        std::str::from_utf8_unchecked(std::slice::from_raw_parts(
-            base_ptr.add(ptr as usize),
-            len as usize,
-        ))
+fn log(
+    caller: &Caller<'_, HostState>,
+    plugin: &str,
+    call: &str,
+    level: Level,
+    ptr: i32,
+    len: i32,
+) -> Result<()> {
+    validate_guest_range(caller, plugin, call, ptr, len)?;
+    let Some(bytes) = read_guest(caller, ptr, len) else {
+        return Ok(());
     };
-    println!("{}", s);
+    let text = String::from_utf8_lossy(&bytes);
+    caller
+        .data()
+        .logger
+        .lock()
+        .unwrap()
+        .log(plugin, level, &text);
     Ok(())
 }
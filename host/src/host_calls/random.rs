@@ -0,0 +1,33 @@
+use crate::host::caller_state::HostState;
+use wasmtime::Caller;
+
+// Random numbers for plugins, from one generator the whole host shares.
+//
+//   host_random() -> i64
+//       64 random bits
+//
+// Seeded from BlindHostConfig::random_seed, or the clock without one; with the same seed (and
+// the same calls in the same order) every run draws the same numbers. Not for cryptography.
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // splitmix64: tiny, and any seed (0 included) is a good one
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+pub fn host_random(caller: Caller<'_, HostState>) -> i64 {
+    caller.data().random.lock().unwrap().next_u64() as i64
+}
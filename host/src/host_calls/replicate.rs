@@ -0,0 +1,105 @@
+use crate::host::caller_state::HostState;
+use crate::host::host_object::BlindHost;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::read_guest;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use wasmtime::{Caller, Linker};
+
+// State the server replicates to its clients in split play (embedder/split.rs). Opt-in:
+// embedders call `register_host_calls`.
+//
+//   host_replicate(name_ptr, name_len, ptr, len) -> i32
+//       designates the len bytes at ptr as the state called `name`: a resource, a component
+//       column. On the server they're sent to every client after each frame they changed in;
+//       on a client, what the server sent for `name` is written there. Call it again when the
+//       state moves (a column after its table grew); len 0 stops replicating it.
+//       0 ok, -1 malformed name
+//
+// The name is the contract: both ends lay the bytes out alike (ecs_protocol's structs), and a
+// client whose length differs from the server's gets nothing for that name.
+#[derive(Default)]
+pub struct Replicas {
+    regions: Mutex<BTreeMap<String, (i32, i32)>>,
+    // Client side: the server's latest for each name, until it's written
+    pending: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl Replicas {
+    /// Each designated state's current bytes, by name.
+    pub fn read(&self, host: &BlindHost) -> Result<BTreeMap<String, Vec<u8>>> {
+        let regions = self.regions.lock().unwrap().clone();
+        regions
+            .into_iter()
+            .map(|(name, (ptr, len))| Ok((name, host.view_mem(ptr, len)?.to_vec())))
+            .collect()
+    }
+
+    /// Holds the server's bytes for `name` until `apply` can write them.
+    pub fn receive(&self, name: String, bytes: Vec<u8>) {
+        self.pending.lock().unwrap().insert(name, bytes);
+    }
+
+    /// Writes what the server sent into the states designated here. Answers the names written;
+    /// names nobody here designated yet stay pending.
+    pub fn apply(&self, host: &mut BlindHost) -> Result<Vec<String>> {
+        let regions = self.regions.lock().unwrap().clone();
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        let mut written = Vec::new();
+        for (name, bytes) in pending {
+            let Some(&(ptr, len)) = regions.get(&name) else {
+                self.pending.lock().unwrap().entry(name).or_insert(bytes);
+                continue;
+            };
+            if bytes.len() != len as usize {
+                eprintln!(
+                    "⚠️ [SPLIT] '{}' is {} bytes on the server, {} here; not written",
+                    name,
+                    bytes.len(),
+                    len
+                );
+                continue;
+            }
+            host.write_mem(ptr, &bytes)?;
+            written.push(name);
+        }
+        Ok(written)
+    }
+}
+
+pub fn register_host_calls(
+    linker: &mut Linker<HostState>,
+    plugin: String,
+    replicas: Arc<Replicas>,
+) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "host_replicate",
+        move |caller: Caller<'_, HostState>,
+              name_ptr: i32,
+              name_len: i32,
+              ptr: i32,
+              len: i32|
+              -> Result<i32> {
+            validate_guest_range(&caller, &plugin, "host_replicate", name_ptr, name_len)?;
+            validate_guest_range(&caller, &plugin, "host_replicate", ptr, len)?;
+            let Some(name) =
+                read_guest(&caller, name_ptr, name_len).and_then(|b| String::from_utf8(b).ok())
+            else {
+                return Ok(-1);
+            };
+            if name.is_empty() || len < 0 {
+                return Ok(-1);
+            }
+            let mut regions = replicas.regions.lock().unwrap();
+            if len == 0 {
+                regions.remove(&name);
+            } else {
+                regions.insert(name, (ptr, len));
+            }
+            Ok(0)
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use crate::host::logger::{Level, LogConfig, Logger};
+use crate::host_calls::sync::Endpoint;
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+
+// Where save files (saves.rs) are kept. SaveStore does the format and checks names; backends
+// only keep each plugin's files by slot, as opaque bytes:
+//
+//   LocalDir      <dir>/<plugin>/<slot>.sav, the default
+//   HttpBackend   <url>/<plugin>/<slot>.sav over plain HTTP: GET, PUT and DELETE, so WebDAV
+//                 servers and custom ones both work. Listing is `GET <url>/<plugin>/`, answered
+//                 with a file name per line; S3 buckets go through a gateway or signing proxy.
+//   Mirrored      the async variant, for remote backends: a local copy answers right away and
+//                 a worker thread passes changes on, in order, so a slow or down server
+//                 never stalls a tick. Slots only the remote has (saved on another machine)
+//                 are fetched in the background the first time they're read, and listed from
+//                 the remote's last listing, which each listing refreshes.
+//
+// Embedders pick one with BlindHostConfig::save_backend; the grid embedder's is host.toml's
+// [saves] backend.
+
+pub trait SaveBackend: Send + Sync {
+    fn read(&self, plugin: &str, slot: &str) -> Result<Option<Vec<u8>>>;
+    fn write(&self, plugin: &str, slot: &str, file: &[u8]) -> Result<()>;
+    // Deleting a slot that has no file is fine
+    fn delete(&self, plugin: &str, slot: &str) -> Result<()>;
+    // The plugin's slots, in no particular order
+    fn slots(&self, plugin: &str) -> Result<Vec<String>>;
+    // Adds `bytes` to the end of the slot's file, starting it if there's none (save journals).
+    // Backends that can't append in place rewrite the file.
+    fn append(&self, plugin: &str, slot: &str, bytes: &[u8]) -> Result<()> {
+        let mut file = self.read(plugin, slot)?.unwrap_or_default();
+        file.extend_from_slice(bytes);
+        self.write(plugin, slot, &file)
+    }
+    // Waits for writes still on their way, for backends that write in the background
+    fn flush(&self) {}
+    // Where backends that work in the background report what fails there
+    fn set_logger(&self, _logger: Arc<Mutex<Logger>>) {}
+}
+
+#[derive(Clone)]
+pub struct LocalDir {
+    root: PathBuf,
+}
+
+impl LocalDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, plugin: &str, slot: &str) -> PathBuf {
+        self.root.join(plugin).join(format!("{}.sav", slot))
+    }
+}
+
+impl SaveBackend for LocalDir {
+    fn read(&self, plugin: &str, slot: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(plugin, slot);
+        match std::fs::read(&path) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+        }
+    }
+
+    fn write(&self, plugin: &str, slot: &str, file: &[u8]) -> Result<()> {
+        let path = self.path(plugin, slot);
+        let dir = self.root.join(plugin);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        // Write then rename, so a crash mid-save never leaves half a save behind
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, file)
+            .with_context(|| format!("Failed to write '{}'", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to replace '{}'", path.display()))
+    }
+
+    fn append(&self, plugin: &str, slot: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.path(plugin, slot);
+        let dir = self.root.join(plugin);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open '{}'", path.display()))?;
+        file.write_all(bytes)
+            .with_context(|| format!("Failed to append to '{}'", path.display()))
+    }
+
+    fn delete(&self, plugin: &str, slot: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(plugin, slot)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn slots(&self, plugin: &str) -> Result<Vec<String>> {
+        let dir = self.root.join(plugin);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to list '{}'", dir.display())),
+        };
+        let mut slots = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(slot) = name.to_str().and_then(|name| name.strip_suffix(".sav")) {
+                slots.push(slot.to_string());
+            }
+        }
+        Ok(slots)
+    }
+}
+
+pub struct HttpBackend {
+    endpoint: Endpoint,
+}
+
+impl HttpBackend {
+    /// `url` is a plain `http://host[:port]/path` URL, the saves' root.
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            endpoint: Endpoint::parse(url)?,
+        })
+    }
+
+    fn path(&self, plugin: &str, slot: Option<&str>) -> String {
+        let root = self.endpoint.path.trim_end_matches('/');
+        match slot {
+            Some(slot) => format!("{}/{}/{}.sav", root, plugin, slot),
+            None => format!("{}/{}/", root, plugin),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<&[u8]>) -> Result<(u16, Vec<u8>)> {
+        let (status, reply) = self.endpoint.request(method, path, body)?;
+        match status {
+            200..=299 | 404 => Ok((status, reply)),
+            _ => Err(anyhow!(
+                "Save server answered {} to {} {}",
+                status,
+                method,
+                path
+            )),
+        }
+    }
+}
+
+impl SaveBackend for HttpBackend {
+    fn read(&self, plugin: &str, slot: &str) -> Result<Option<Vec<u8>>> {
+        match self.request("GET", &self.path(plugin, Some(slot)), None)? {
+            (404, _) => Ok(None),
+            (_, file) => Ok(Some(file)),
+        }
+    }
+
+    fn write(&self, plugin: &str, slot: &str, file: &[u8]) -> Result<()> {
+        match self.request("PUT", &self.path(plugin, Some(slot)), Some(file))? {
+            (404, _) => Err(anyhow!("Save server has no '{}'", self.path(plugin, None))),
+            _ => Ok(()),
+        }
+    }
+
+    fn delete(&self, plugin: &str, slot: &str) -> Result<()> {
+        self.request("DELETE", &self.path(plugin, Some(slot)), None)
+            .map(|_| ())
+    }
+
+    fn slots(&self, plugin: &str) -> Result<Vec<String>> {
+        let (status, listing) = self.request("GET", &self.path(plugin, None), None)?;
+        if status == 404 {
+            return Ok(Vec::new());
+        }
+        let listing =
+            String::from_utf8(listing).map_err(|_| anyhow!("Save server's listing isn't UTF-8"))?;
+        Ok(listing
+            .lines()
+            .filter_map(|line| line.trim().strip_suffix(".sav"))
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+enum Job {
+    Write(String, String, Vec<u8>),
+    Append(String, String, Vec<u8>),
+    Delete(String, String),
+    // A slot that isn't here, copied here if the remote has it
+    Fetch(String, String),
+    // The plugin's remote slots, for the listing cache
+    List(String),
+    // Answered once every job before it is done
+    Flush(Sender<()>),
+}
+
+// What the tick thread and the worker both use
+struct Mirror {
+    // Held around every local change, so a fetch never overwrites a newer save
+    local: Mutex<LocalDir>,
+    logger: Mutex<Arc<Mutex<Logger>>>,
+    // The remote's slots by plugin, as last listed
+    listings: Mutex<HashMap<String, Vec<String>>>,
+    // Fetches (plugin and slot) and listings (plugin and no slot) queued and not done yet
+    pending: Mutex<HashSet<(String, Option<String>)>>,
+}
+
+impl Mirror {
+    fn warn(&self, text: &str) {
+        let logger = self.logger.lock().unwrap().clone();
+        logger.lock().unwrap().log("host", Level::Warn, text);
+    }
+
+    fn run(&self, remote: &dyn SaveBackend, job: Job) -> Result<()> {
+        match job {
+            Job::Write(plugin, slot, file) => remote.write(&plugin, &slot, &file),
+            Job::Append(plugin, slot, bytes) => remote.append(&plugin, &slot, &bytes),
+            Job::Delete(plugin, slot) => remote.delete(&plugin, &slot),
+            Job::Fetch(plugin, slot) => {
+                let fetched = remote.read(&plugin, &slot);
+                self.pending
+                    .lock()
+                    .unwrap()
+                    .remove(&(plugin.clone(), Some(slot.clone())));
+                let Some(file) = fetched? else {
+                    return Ok(());
+                };
+                let local = self.local.lock().unwrap();
+                // Saved here while it was on its way
+                if local.read(&plugin, &slot)?.is_some() {
+                    return Ok(());
+                }
+                local.write(&plugin, &slot, &file)
+            }
+            Job::List(plugin) => {
+                let listed = remote.slots(&plugin);
+                self.pending.lock().unwrap().remove(&(plugin.clone(), None));
+                self.listings.lock().unwrap().insert(plugin, listed?);
+                Ok(())
+            }
+            Job::Flush(done) => {
+                let _ = done.send(());
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct Mirrored {
+    mirror: Arc<Mirror>,
+    jobs: Mutex<Sender<Job>>,
+}
+
+impl Mirrored {
+    /// Keeps the saves in `local` and passes every change on to `remote` in the background.
+    pub fn new(local: LocalDir, remote: Arc<dyn SaveBackend>) -> Self {
+        let logger = Logger::new(LogConfig::default()).expect("the default logger opens no file");
+        let mirror = Arc::new(Mirror {
+            local: Mutex::new(local),
+            logger: Mutex::new(Arc::new(Mutex::new(logger))),
+            listings: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashSet::new()),
+        });
+        let (jobs, queued) = mpsc::channel::<Job>();
+        let worker = mirror.clone();
+        std::thread::spawn(move || {
+            for job in queued {
+                if let Err(e) = worker.run(remote.as_ref(), job) {
+                    worker.warn(&format!("Remote save failed: {:#}", e));
+                }
+            }
+        });
+        Self {
+            mirror,
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    fn send(&self, job: Job) -> Result<()> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(job)
+            .map_err(|_| anyhow!("The remote save worker stopped"))
+    }
+
+    // Queues the fetch or listing unless one is already on its way
+    fn request(&self, plugin: &str, slot: Option<&str>, job: Job) -> Result<()> {
+        let key = (plugin.to_string(), slot.map(str::to_string));
+        if !self.mirror.pending.lock().unwrap().insert(key) {
+            return Ok(());
+        }
+        self.send(job)
+    }
+}
+
+impl SaveBackend for Mirrored {
+    fn read(&self, plugin: &str, slot: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(file) = self.mirror.local.lock().unwrap().read(plugin, slot)? {
+            return Ok(Some(file));
+        }
+        // Saved on another machine, or not at all: it's here on a later read if the remote has
+        // it. Changes still on their way out go first.
+        self.request(
+            plugin,
+            Some(slot),
+            Job::Fetch(plugin.to_string(), slot.to_string()),
+        )?;
+        Ok(None)
+    }
+
+    fn write(&self, plugin: &str, slot: &str, file: &[u8]) -> Result<()> {
+        self.mirror
+            .local
+            .lock()
+            .unwrap()
+            .write(plugin, slot, file)?;
+        self.send(Job::Write(
+            plugin.to_string(),
+            slot.to_string(),
+            file.to_vec(),
+        ))
+    }
+
+    fn append(&self, plugin: &str, slot: &str, bytes: &[u8]) -> Result<()> {
+        self.mirror
+            .local
+            .lock()
+            .unwrap()
+            .append(plugin, slot, bytes)?;
+        self.send(Job::Append(
+            plugin.to_string(),
+            slot.to_string(),
+            bytes.to_vec(),
+        ))
+    }
+
+    fn delete(&self, plugin: &str, slot: &str) -> Result<()> {
+        self.mirror.local.lock().unwrap().delete(plugin, slot)?;
+        if let Some(listed) = self.mirror.listings.lock().unwrap().get_mut(plugin) {
+            listed.retain(|other| other != slot);
+        }
+        self.send(Job::Delete(plugin.to_string(), slot.to_string()))
+    }
+
+    // What's here and what the remote last listed; offline, that's as of the last listing
+    fn slots(&self, plugin: &str) -> Result<Vec<String>> {
+        let mut slots = self.mirror.local.lock().unwrap().slots(plugin)?;
+        if let Some(listed) = self.mirror.listings.lock().unwrap().get(plugin) {
+            slots.extend(listed.iter().cloned());
+        }
+        self.request(plugin, None, Job::List(plugin.to_string()))?;
+        slots.sort();
+        slots.dedup();
+        Ok(slots)
+    }
+
+    // The changes made so far reach the remote (or fail to), say before quitting
+    fn flush(&self) {
+        let (done, flushed) = mpsc::channel();
+        if self.send(Job::Flush(done)).is_ok() {
+            let _ = flushed.recv();
+        }
+    }
+
+    fn set_logger(&self, logger: Arc<Mutex<Logger>>) {
+        *self.mirror.logger.lock().unwrap() = logger;
+    }
+}
@@ -0,0 +1,618 @@
+use crate::host::caller_state::HostState;
+use crate::host::logger::Level;
+use crate::host_calls::allocator::alloc_owned;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::save_backend::{LocalDir, SaveBackend};
+use crate::host_calls::storage::{read_guest, write_guest};
+use anyhow::{anyhow, Context, Result};
+use fat_ptr::FatPtr;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmtime::{Caller, Linker};
+
+// Where embedders keep save slots unless they pick another directory
+pub const DEFAULT_SAVES_DIR: &str = "saves";
+
+// Named save slots, so game plugins can offer several saves. Unlike storage's keys, slots are
+// each plugin's own: they're kept by plugin in BlindHostConfig::save_backend (save_backend.rs),
+// and these calls are registered per plugin. Saves are off (every call fails) without a
+// backend, and in deterministic hosts, whose runs mustn't depend on earlier ones.
+//
+//   host_save(slot_ptr, slot_len, data_ptr, data_len) -> i32     the slot's new version, -1 failed
+//   host_load(slot_ptr, slot_len) -> i64
+//       the data as (len << 32 | ptr) in a host_alloc'd buffer the plugin frees with
+//       host_dealloc; -1 if the slot is empty, 0 if it can't be read
+//   host_list_saves() -> i64
+//       the plugin's slots, the same way: count u32, then per slot, by name, little-endian:
+//       slot (u16 len + UTF-8), version u32, saved_at u64 (Unix seconds), len u32
+//   host_delete_save(slot_ptr, slot_len) -> i32                  0 ok, -1 failed
+//
+// Slot names are like storage keys: ASCII letters, digits, '-', '_' and '.', not leading.
+// Each save of a slot bumps its version, from 1. A save file is, little-endian:
+//
+//   magic "USAV", format u16 (SAVE_FORMAT), flags u16 (FLAG_*), abi i32, schema u32,
+//   version u32, saved_at u64, len u32, crc32 u32, plugin (u16 len + UTF-8), then the data
+//
+// where len and crc32 are the data's before compression. `schema` is what the plugin's optional
+// `__save_schema() -> i32` export answered when it was loaded (0 without one), bumped by the
+// plugin whenever what it saves changes shape. Loading a slot saved under another schema goes
+// through the migration the embedder registered for the plugin (SaveStore::set_migration), and
+// fails with what's wrong otherwise, like files of other plugins, newer hosts or torn writes.
+//
+// Journaled saves (SaveStore::append; autosaves with host.toml's [saves] journal) cost what
+// changed rather than the whole state, for games with large worlds: a slot keeps its last full
+// save (flagged FLAG_JOURNALED) and a journal of the states after it. The journal is the
+// `<slot>~journal` file (a name plugins can't use), little-endian:
+//
+//   magic "USJL", base u32 (the version of the save it follows), then entries of
+//     kind u8 (JOURNAL_DELTA or JOURNAL_WHOLE), len u32, crc32 u32 (of kind and bytes), bytes
+//
+// A delta is crate::delta's from the state before; states that change length go in whole.
+// Loading replays the journal onto the save, up to an entry a crash cut short. Once the journal
+// outgrows the state or has COMPACT_ENTRIES entries it's compacted: the state is saved in full
+// and a new journal follows that save. A journal with another base was left by a crash during
+// compaction, or by a full save since, and is ignored. Listings show the last full save.
+
+pub const SAVE_FORMAT: u16 = 1;
+// The data is zstd-compressed; it's stored as is when that wouldn't make it smaller
+pub const FLAG_ZSTD: u16 = 1;
+// A journal may follow the save
+pub const FLAG_JOURNALED: u16 = 2;
+
+const MAGIC: &[u8; 4] = b"USAV";
+const HEADER_SIZE: usize = 36;
+const ZSTD_LEVEL: i32 = 3;
+
+pub const JOURNAL_DELTA: u8 = 0;
+pub const JOURNAL_WHOLE: u8 = 1;
+pub const COMPACT_ENTRIES: u32 = 1024;
+const JOURNAL_MAGIC: &[u8; 4] = b"USJL";
+const ENTRY_HEADER_SIZE: usize = 9;
+
+// What a plugin saves as: its ABI and its save schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveVersion {
+    pub abi: i32,
+    pub schema: u32,
+}
+
+impl Default for SaveVersion {
+    fn default() -> Self {
+        Self {
+            abi: fat_ptr::ABI_VERSION,
+            schema: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveInfo {
+    pub slot: String,
+    pub version: u32,
+    pub saved_at: u64,
+    pub len: u32,
+}
+
+// A save file's header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveHeader {
+    pub format: u16,
+    pub flags: u16,
+    pub plugin: String,
+    pub saved_with: SaveVersion,
+    pub info: SaveInfo,
+    pub crc32: u32,
+}
+
+/// Turns data saved under `header.saved_with` into what the plugin reads as `to`, or says why it
+/// can't.
+pub type Migration = dyn Fn(&SaveHeader, Vec<u8>, SaveVersion) -> Result<Vec<u8>> + Send + Sync;
+
+pub struct SaveStore {
+    backend: Arc<dyn SaveBackend>,
+    migrations: Mutex<HashMap<String, Box<Migration>>>,
+    // Journaled slots by plugin and slot, with the state their journal ends at
+    journals: Mutex<HashMap<(String, String), Journal>>,
+}
+
+struct Journal {
+    state: Vec<u8>,
+    entries: u32,
+    // Bytes in the journal past its header
+    len: usize,
+}
+
+impl SaveStore {
+    /// Saves in a local directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_backend(Arc::new(LocalDir::new(root)))
+    }
+
+    pub fn with_backend(backend: Arc<dyn SaveBackend>) -> Self {
+        Self {
+            backend,
+            migrations: Mutex::new(HashMap::new()),
+            journals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for saves the backend is still writing in the background.
+    pub fn flush(&self) {
+        self.backend.flush();
+    }
+
+    /// What `load` runs `plugin`'s saves from other schemas through.
+    pub fn set_migration(&self, plugin: &str, migration: Box<Migration>) {
+        self.migrations
+            .lock()
+            .unwrap()
+            .insert(plugin.to_string(), migration);
+    }
+
+    /// Saves `data` in `plugin`'s `slot`, answering what it saved.
+    pub fn save(
+        &self,
+        plugin: &str,
+        slot: &str,
+        saved_with: SaveVersion,
+        data: &[u8],
+    ) -> Result<SaveInfo> {
+        valid_names(plugin, slot)?;
+        // The slot's journal now follows an older save; the next journaled one starts over
+        self.journals
+            .lock()
+            .unwrap()
+            .remove(&(plugin.to_string(), slot.to_string()));
+        self.write_save(plugin, slot, saved_with, data, 0)
+    }
+
+    /// Saves `data` in `plugin`'s `slot` through its journal, answering whether that took a full
+    /// save. The first journaled save of a slot since the store was made is a full one, so a
+    /// journal only ever follows a save in the plugin's current schema.
+    pub fn append(
+        &self,
+        plugin: &str,
+        slot: &str,
+        saved_with: SaveVersion,
+        data: &[u8],
+    ) -> Result<bool> {
+        valid_names(plugin, slot)?;
+        let key = (plugin.to_string(), slot.to_string());
+        let mut journals = self.journals.lock().unwrap();
+        let journal = match journals.get_mut(&key) {
+            Some(journal) if journal.entries < COMPACT_ENTRIES && journal.len < data.len() => {
+                journal
+            }
+            _ => {
+                journals.remove(&key);
+                let info = self.write_save(plugin, slot, saved_with, data, FLAG_JOURNALED)?;
+                let mut header = JOURNAL_MAGIC.to_vec();
+                header.extend_from_slice(&info.version.to_le_bytes());
+                self.backend.write(plugin, &journal_slot(slot), &header)?;
+                let journal = Journal {
+                    state: data.to_vec(),
+                    entries: 0,
+                    len: 0,
+                };
+                journals.insert(key, journal);
+                return Ok(true);
+            }
+        };
+
+        let (kind, bytes) = match crate::delta::encode(&journal.state, data) {
+            Some(delta) if delta.is_empty() => return Ok(false),
+            Some(delta) => (JOURNAL_DELTA, delta),
+            None => (JOURNAL_WHOLE, data.to_vec()),
+        };
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&[kind]);
+        crc.update(&bytes);
+        let mut entry = vec![kind];
+        entry.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        entry.extend_from_slice(&crc.finalize().to_le_bytes());
+        entry.extend_from_slice(&bytes);
+        if let Err(e) = self.backend.append(plugin, &journal_slot(slot), &entry) {
+            // Whatever made it into the journal, the next journaled save is a full one
+            journals.remove(&key);
+            return Err(e);
+        }
+        journal.state = data.to_vec();
+        journal.entries += 1;
+        journal.len += entry.len();
+        Ok(false)
+    }
+
+    fn write_save(
+        &self,
+        plugin: &str,
+        slot: &str,
+        saved_with: SaveVersion,
+        data: &[u8],
+        flags: u16,
+    ) -> Result<SaveInfo> {
+        let version = self.previous_version(plugin, slot).wrapping_add(1);
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let compressed =
+            zstd::bulk::compress(data, ZSTD_LEVEL).context("Failed to compress the save")?;
+        let (flags, payload) = if compressed.len() < data.len() {
+            (flags | FLAG_ZSTD, &compressed[..])
+        } else {
+            (flags, data)
+        };
+
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&SAVE_FORMAT.to_le_bytes());
+        file.extend_from_slice(&flags.to_le_bytes());
+        file.extend_from_slice(&saved_with.abi.to_le_bytes());
+        file.extend_from_slice(&saved_with.schema.to_le_bytes());
+        file.extend_from_slice(&version.to_le_bytes());
+        file.extend_from_slice(&saved_at.to_le_bytes());
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        file.extend_from_slice(&(plugin.len() as u16).to_le_bytes());
+        file.extend_from_slice(plugin.as_bytes());
+        file.extend_from_slice(payload);
+
+        self.backend.write(plugin, slot, &file)?;
+        Ok(SaveInfo {
+            slot: slot.to_string(),
+            version,
+            saved_at,
+            len: data.len() as u32,
+        })
+    }
+
+    /// The data in `plugin`'s `slot` if it has any, migrated to `reads_as` if it was saved under
+    /// another schema.
+    pub fn load(&self, plugin: &str, slot: &str, reads_as: SaveVersion) -> Result<Option<Vec<u8>>> {
+        let Some((header, payload)) = self.read(plugin, slot)? else {
+            return Ok(None);
+        };
+        let data = if header.flags & FLAG_ZSTD != 0 {
+            zstd::bulk::decompress(&payload, header.info.len as usize)
+                .map_err(|_| anyhow!("'{}' is corrupt: its data doesn't decompress", slot))?
+        } else {
+            payload
+        };
+        if data.len() != header.info.len as usize || crc32fast::hash(&data) != header.crc32 {
+            return Err(anyhow!("'{}' is corrupt: its checksum doesn't match", slot));
+        }
+        let data = match header.flags & FLAG_JOURNALED {
+            0 => data,
+            _ => self.replay(plugin, slot, header.info.version, data)?,
+        };
+        if header.saved_with.schema == reads_as.schema {
+            return Ok(Some(data));
+        }
+
+        let migrations = self.migrations.lock().unwrap();
+        let Some(migrate) = migrations.get(plugin) else {
+            return Err(anyhow!(
+                "'{}' was saved with schema v{}, '{}' reads v{} and has no migration for it",
+                slot,
+                header.saved_with.schema,
+                plugin,
+                reads_as.schema
+            ));
+        };
+        let migrated = migrate(&header, data, reads_as).with_context(|| {
+            format!(
+                "'{}' couldn't be migrated from schema v{} to v{}",
+                slot, header.saved_with.schema, reads_as.schema
+            )
+        })?;
+        Ok(Some(migrated))
+    }
+
+    pub fn info(&self, plugin: &str, slot: &str) -> Result<Option<SaveInfo>> {
+        Ok(self.header(plugin, slot)?.map(|header| header.info))
+    }
+
+    pub fn header(&self, plugin: &str, slot: &str) -> Result<Option<SaveHeader>> {
+        Ok(self.read(plugin, slot)?.map(|(header, _)| header))
+    }
+
+    /// `plugin`'s slots, by name. Slots that can't be read are left out.
+    pub fn list(&self, plugin: &str) -> Result<Vec<SaveInfo>> {
+        valid(plugin)?;
+        let mut saves: Vec<SaveInfo> = self
+            .backend
+            .slots(plugin)?
+            .iter()
+            .filter(|slot| valid(slot).is_ok())
+            .filter_map(|slot| self.info(plugin, slot).ok().flatten())
+            .collect();
+        saves.sort_by(|a, b| a.slot.cmp(&b.slot));
+        Ok(saves)
+    }
+
+    pub fn delete(&self, plugin: &str, slot: &str) -> Result<()> {
+        valid_names(plugin, slot)?;
+        self.journals
+            .lock()
+            .unwrap()
+            .remove(&(plugin.to_string(), slot.to_string()));
+        self.backend.delete(plugin, &journal_slot(slot))?;
+        self.backend.delete(plugin, slot)
+    }
+
+    // `state`, the slot's save of `version`, with its journal's entries applied
+    fn replay(
+        &self,
+        plugin: &str,
+        slot: &str,
+        version: u32,
+        mut state: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let Some(journal) = self.backend.read(plugin, &journal_slot(slot))? else {
+            return Ok(state);
+        };
+        if journal.len() < 8
+            || &journal[..4] != JOURNAL_MAGIC
+            || journal[4..8] != version.to_le_bytes()
+        {
+            return Ok(state);
+        }
+        let mut rest = &journal[8..];
+        while rest.len() >= ENTRY_HEADER_SIZE {
+            let kind = rest[0];
+            let len = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+            let Some(bytes) = rest.get(ENTRY_HEADER_SIZE..ENTRY_HEADER_SIZE + len) else {
+                break;
+            };
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(&[kind]);
+            crc.update(bytes);
+            if crc.finalize().to_le_bytes() != rest[5..9] {
+                break;
+            }
+            state = match kind {
+                JOURNAL_DELTA => crate::delta::decode(&state, bytes)
+                    .ok_or_else(|| anyhow!("'{}' is corrupt: its journal doesn't apply", slot))?,
+                JOURNAL_WHOLE => bytes.to_vec(),
+                _ => break,
+            };
+            rest = &rest[ENTRY_HEADER_SIZE + len..];
+        }
+        Ok(state)
+    }
+
+    // The header and the data as stored, compressed or not
+    fn read(&self, plugin: &str, slot: &str) -> Result<Option<(SaveHeader, Vec<u8>)>> {
+        valid_names(plugin, slot)?;
+        let Some(mut file) = self.backend.read(plugin, slot)? else {
+            return Ok(None);
+        };
+        if file.len() < HEADER_SIZE + 2 || &file[..4] != MAGIC {
+            return Err(anyhow!("'{}' isn't a save", slot));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes(file[at..at + 2].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+        let format = u16_at(4);
+        if format > SAVE_FORMAT {
+            return Err(anyhow!(
+                "'{}' was saved by a newer host (save format v{}, this one reads up to v{})",
+                slot,
+                format,
+                SAVE_FORMAT
+            ));
+        }
+        let plugin_len = u16_at(HEADER_SIZE) as usize;
+        let saved_by = file
+            .get(HEADER_SIZE + 2..HEADER_SIZE + 2 + plugin_len)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| anyhow!("'{}' isn't a save", slot))?;
+        if saved_by != plugin {
+            return Err(anyhow!(
+                "'{}' is a save of '{}', not of '{}'",
+                slot,
+                saved_by,
+                plugin
+            ));
+        }
+        let header = SaveHeader {
+            format,
+            flags: u16_at(6),
+            plugin: saved_by.to_string(),
+            saved_with: SaveVersion {
+                abi: u32_at(8) as i32,
+                schema: u32_at(12),
+            },
+            info: SaveInfo {
+                slot: slot.to_string(),
+                version: u32_at(16),
+                saved_at: u64::from_le_bytes(file[20..28].try_into().unwrap()),
+                len: u32_at(28),
+            },
+            crc32: u32_at(32),
+        };
+        Ok(Some((header, file.split_off(HEADER_SIZE + 2 + plugin_len))))
+    }
+
+    // The version of the slot's last save, 0 if it has none. Read straight from the header, so
+    // a save that can't be loaded anymore is still replaced by a later version.
+    fn previous_version(&self, plugin: &str, slot: &str) -> u32 {
+        match self.backend.read(plugin, slot) {
+            Ok(Some(file)) if file.len() >= HEADER_SIZE && &file[..4] == MAGIC => {
+                u32::from_le_bytes(file[16..20].try_into().unwrap())
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// A host_list_saves listing.
+pub fn encode_list(saves: &[SaveInfo]) -> Vec<u8> {
+    let mut bytes = (saves.len() as u32).to_le_bytes().to_vec();
+    for save in saves {
+        bytes.extend_from_slice(&(save.slot.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(save.slot.as_bytes());
+        bytes.extend_from_slice(&save.version.to_le_bytes());
+        bytes.extend_from_slice(&save.saved_at.to_le_bytes());
+        bytes.extend_from_slice(&save.len.to_le_bytes());
+    }
+    bytes
+}
+
+// Where a slot's journal is kept, under a name valid() refuses
+fn journal_slot(slot: &str) -> String {
+    format!("{}~journal", slot)
+}
+
+fn valid_names(plugin: &str, slot: &str) -> Result<()> {
+    valid(plugin)?;
+    valid(slot)
+}
+
+// Plugin and slot names become paths, so only allow characters that can't escape the root
+fn valid(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(anyhow!("Invalid save name '{}'", name));
+    }
+    Ok(())
+}
+
+/// What `plugin` saves as, from its `__abi_version` and `__save_schema` exports.
+pub fn save_version(state: &HostState, plugin: &str) -> SaveVersion {
+    state.save_versions.get(plugin).copied().unwrap_or_default()
+}
+
+pub(crate) fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    let caller_name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_save",
+        move |caller: Caller<'_, HostState>,
+              slot_ptr: i32,
+              slot_len: i32,
+              data_ptr: i32,
+              data_len: i32|
+              -> Result<i32> {
+            validate_guest_range(&caller, &caller_name, "host_save", slot_ptr, slot_len)?;
+            validate_guest_range(&caller, &caller_name, "host_save", data_ptr, data_len)?;
+            let (Some(saves), Some(slot), Some(data)) = (
+                caller.data().saves.clone(),
+                read_name(&caller, slot_ptr, slot_len),
+                read_guest(&caller, data_ptr, data_len),
+            ) else {
+                return Ok(-1);
+            };
+            match saves.save(
+                &caller_name,
+                &slot,
+                save_version(caller.data(), &caller_name),
+                &data,
+            ) {
+                Ok(info) => Ok(info.version as i32),
+                Err(e) => {
+                    let text = format!("'{}' couldn't save '{}': {:#}", caller_name, slot, e);
+                    caller
+                        .data()
+                        .logger
+                        .lock()
+                        .unwrap()
+                        .log("host", Level::Warn, &text);
+                    Ok(-1)
+                }
+            }
+        },
+    )?;
+
+    let caller_name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_load",
+        move |caller: Caller<'_, HostState>, slot_ptr: i32, slot_len: i32| -> Result<i64> {
+            validate_guest_range(&caller, &caller_name, "host_load", slot_ptr, slot_len)?;
+            let (Some(saves), Some(slot)) = (
+                caller.data().saves.clone(),
+                read_name(&caller, slot_ptr, slot_len),
+            ) else {
+                return Ok(-1);
+            };
+            match saves.load(
+                &caller_name,
+                &slot,
+                save_version(caller.data(), &caller_name),
+            ) {
+                Ok(Some(data)) => Ok(hand_over(&caller, &caller_name, &data)),
+                Ok(None) => Ok(-1),
+                Err(e) => {
+                    let text = format!("'{}' couldn't load '{}': {:#}", caller_name, slot, e);
+                    caller
+                        .data()
+                        .logger
+                        .lock()
+                        .unwrap()
+                        .log("host", Level::Warn, &text);
+                    Ok(0)
+                }
+            }
+        },
+    )?;
+
+    let caller_name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_list_saves",
+        move |caller: Caller<'_, HostState>| -> Result<i64> {
+            let list = match caller.data().saves.clone() {
+                Some(saves) => saves.list(&caller_name),
+                None => Ok(Vec::new()),
+            };
+            match list {
+                Ok(list) => Ok(hand_over(&caller, &caller_name, &encode_list(&list))),
+                Err(_) => Ok(0),
+            }
+        },
+    )?;
+
+    let caller_name = plugin;
+    linker.func_wrap(
+        "env",
+        "host_delete_save",
+        move |caller: Caller<'_, HostState>, slot_ptr: i32, slot_len: i32| -> Result<i32> {
+            validate_guest_range(
+                &caller,
+                &caller_name,
+                "host_delete_save",
+                slot_ptr,
+                slot_len,
+            )?;
+            let (Some(saves), Some(slot)) = (
+                caller.data().saves.clone(),
+                read_name(&caller, slot_ptr, slot_len),
+            ) else {
+                return Ok(-1);
+            };
+            Ok(if saves.delete(&caller_name, &slot).is_ok() {
+                0
+            } else {
+                -1
+            })
+        },
+    )?;
+    Ok(())
+}
+
+fn read_name(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_guest(caller, ptr, len)?).ok()
+}
+
+// `data` in a fresh buffer of `plugin`'s, as a packed FatPtr; 0 if it doesn't fit
+fn hand_over(caller: &Caller<'_, HostState>, plugin: &str, data: &[u8]) -> i64 {
+    let ptr = alloc_owned(caller.data(), plugin, data.len() as i32);
+    if ptr == 0 || !write_guest(caller, ptr, data) {
+        return 0;
+    }
+    FatPtr::new(ptr, data.len() as i32).pack()
+}
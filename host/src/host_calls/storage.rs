@@ -0,0 +1,159 @@
+use crate::host::caller_state::HostState;
+use crate::host_calls::allocator::alloc_owned;
+use crate::host_calls::bounds::validate_guest_range;
+use anyhow::{anyhow, Context, Result};
+use fat_ptr::shared::SharedSlice;
+use fat_ptr::FatPtr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use wasmtime::{Caller, Linker};
+
+// Where embedders keep plugin data unless they pick another directory
+pub const DEFAULT_STORAGE_DIR: &str = "plugin-data";
+
+// Key-value storage for plugins, one file per key. Opt-in: embedders call `register_host_calls`.
+//
+//   host_storage_get(key_ptr, key_len) -> i64
+//       value as (len << 32 | ptr) in a host_alloc'd buffer the plugin frees with
+//       host_dealloc; -1 if the key is unset, 0 if it can't be read
+//   host_storage_set(key_ptr, key_len, value_ptr, value_len) -> i32    0 ok, -1 failed
+//   host_storage_delete(key_ptr, key_len) -> i32                       0 ok, -1 failed
+//
+// Keys are shared by all plugins, so prefix them with the plugin name.
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key)?;
+        match std::fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path.display())),
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        std::fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create '{}'", self.root.display()))?;
+        // Write then rename, so a crash mid-save never leaves half a value behind
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, value)
+            .with_context(|| format!("Failed to write '{}'", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to replace '{}'", path.display()))
+    }
+
+    pub fn delete(&self, key: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(key)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Keys become file names, so only allow characters that can't escape `root`
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let valid = !key.is_empty()
+            && !key.starts_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(anyhow!("Invalid storage key '{}'", key));
+        }
+        Ok(self.root.join(format!("{}.bin", key)))
+    }
+}
+
+pub fn register_host_calls(
+    linker: &mut Linker<HostState>,
+    plugin: String,
+    storage: Arc<FileStorage>,
+) -> Result<()> {
+    let (caller_name, store) = (plugin.clone(), storage.clone());
+    linker.func_wrap(
+        "env",
+        "host_storage_get",
+        move |caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32| -> Result<i64> {
+            validate_guest_range(&caller, &caller_name, "host_storage_get", key_ptr, key_len)?;
+            let Some(key) = read_key(&caller, key_ptr, key_len) else {
+                return Ok(0);
+            };
+            let value = match store.get(&key) {
+                Ok(Some(value)) => value,
+                Ok(None) => return Ok(-1),
+                Err(_) => return Ok(0),
+            };
+
+            let ptr = alloc_owned(caller.data(), &caller_name, value.len() as i32);
+            if ptr == 0 || !write_guest(&caller, ptr, &value) {
+                return Ok(0);
+            }
+            Ok(FatPtr::new(ptr, value.len() as i32).pack())
+        },
+    )?;
+
+    let (caller_name, store) = (plugin.clone(), storage.clone());
+    linker.func_wrap(
+        "env",
+        "host_storage_set",
+        move |caller: Caller<'_, HostState>,
+              key_ptr: i32,
+              key_len: i32,
+              value_ptr: i32,
+              value_len: i32|
+              -> Result<i32> {
+            validate_guest_range(&caller, &caller_name, "host_storage_set", key_ptr, key_len)?;
+            validate_guest_range(
+                &caller,
+                &caller_name,
+                "host_storage_set",
+                value_ptr,
+                value_len,
+            )?;
+            let (Some(key), Some(value)) = (
+                read_key(&caller, key_ptr, key_len),
+                read_guest(&caller, value_ptr, value_len),
+            ) else {
+                return Ok(-1);
+            };
+            Ok(if store.set(&key, &value).is_ok() {
+                0
+            } else {
+                -1
+            })
+        },
+    )?;
+
+    linker.func_wrap(
+        "env",
+        "host_storage_delete",
+        move |caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32| -> Result<i32> {
+            validate_guest_range(&caller, &plugin, "host_storage_delete", key_ptr, key_len)?;
+            let Some(key) = read_key(&caller, key_ptr, key_len) else {
+                return Ok(-1);
+            };
+            Ok(if storage.delete(&key).is_ok() { 0 } else { -1 })
+        },
+    )?;
+    Ok(())
+}
+
+fn read_key(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    String::from_utf8(read_guest(caller, ptr, len)?).ok()
+}
+
+pub(crate) fn read_guest(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    FatPtr::new(ptr, len).read(&caller.data().shared_memory)
+}
+
+pub(crate) fn write_guest(caller: &Caller<'_, HostState>, ptr: i32, data: &[u8]) -> bool {
+    SharedSlice::of(&caller.data().shared_memory).write(ptr, data)
+}
@@ -0,0 +1,89 @@
+use crate::host::caller_state::HostState;
+use crate::host_calls::allocator::alloc_owned;
+use crate::host_calls::bounds::validate_guest_range;
+use crate::host_calls::storage::{read_guest, write_guest};
+use anyhow::Result;
+use fat_ptr::FatPtr;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmtime::{Caller, Linker};
+
+// Strings to u32 handles, shared by every plugin, so names that cross the boundary over and
+// over (components, systems, topics) are copied and checked once instead of on every call.
+//
+//   host_intern(ptr, len) -> i32
+//       the handle for the UTF-8 string, the same for everyone who interns it; 0 if it
+//       isn't valid UTF-8
+//   host_resolve(id) -> i64
+//       the string as (len << 32 | ptr) in a host_alloc'd buffer the plugin frees with
+//       host_dealloc; -1 if nothing was interned as `id`, 0 if it can't be copied out
+//
+// Handles start at 1 and only live as long as the host, so don't persist them.
+#[derive(Default, Clone)]
+pub struct StringTable {
+    ids: HashMap<Arc<str>, u32>,
+    strings: Vec<Arc<str>>,
+}
+
+impl StringTable {
+    pub fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&id) = self.ids.get(s) {
+            return id;
+        }
+        let s: Arc<str> = s.into();
+        self.strings.push(s.clone());
+        let id = self.strings.len() as u32;
+        self.ids.insert(s, id);
+        id
+    }
+
+    pub fn resolve(&self, id: u32) -> Option<&str> {
+        let idx = id.checked_sub(1)?;
+        self.strings.get(idx as usize).map(|s| &**s)
+    }
+}
+
+pub fn register_host_calls(linker: &mut Linker<HostState>, plugin: String) -> Result<()> {
+    let caller_name = plugin.clone();
+    linker.func_wrap(
+        "env",
+        "host_intern",
+        move |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            host_intern(caller, &caller_name, ptr, len)
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "host_resolve",
+        move |caller: Caller<'_, HostState>, id: i32| host_resolve(caller, &plugin, id),
+    )?;
+    Ok(())
+}
+
+fn host_intern(caller: Caller<'_, HostState>, plugin: &str, ptr: i32, len: i32) -> Result<i32> {
+    validate_guest_range(&caller, plugin, "host_intern", ptr, len)?;
+    let Some(s) = read_guest(&caller, ptr, len).and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return Ok(0);
+    };
+    Ok(caller.data().strings.lock().unwrap().intern(&s) as i32)
+}
+
+fn host_resolve(caller: Caller<'_, HostState>, plugin: &str, id: i32) -> i64 {
+    // Copied out so the lock isn't held across the allocation
+    let Some(s) = caller
+        .data()
+        .strings
+        .lock()
+        .unwrap()
+        .resolve(id as u32)
+        .map(str::to_string)
+    else {
+        return -1;
+    };
+    let ptr = alloc_owned(caller.data(), plugin, s.len() as i32);
+    if ptr == 0 || !write_guest(&caller, ptr, s.as_bytes()) {
+        return 0;
+    }
+    FatPtr::new(ptr, s.len() as i32).pack()
+}
@@ -0,0 +1,229 @@
+use crate::host::caller_state::HostState;
+use crate::host::host_object::BlindHost;
+use crate::host::logger::Level;
+use crate::host_calls::allocator::{alloc_shared, free_shared};
+use crate::host_calls::bounds::validate_guest_range;
+use anyhow::{anyhow, Context, Result};
+use fat_ptr::FatPtr;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmtime::{Caller, Linker, Val};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Relays plugin messages to a sync server over HTTP. Opt-in: embedders call `register_host_calls`.
+//
+//   send_to_server(message_ptr, message_len)
+//       queues the bytes as the body of a POST to the endpoint and returns right away
+//   receive_from_server(message_ptr, message_len)      plugin export, optional
+//       called by `deliver` with each non-empty response body; the buffer is freed afterwards
+//
+// Requests go out one at a time from a worker thread, so a slow server never stalls a tick.
+// Those that fail are logged by the next `deliver`.
+pub struct SyncClient {
+    outbox: Mutex<Sender<Vec<u8>>>,
+    inbox: Mutex<Receiver<Vec<u8>>>,
+    failures: Mutex<Receiver<String>>,
+}
+
+// A plain http:// URL, requested one connection per request (also saves over HTTP, save_backend.rs)
+pub(crate) struct Endpoint {
+    host: String,
+    port: u16,
+    pub path: String,
+}
+
+impl SyncClient {
+    /// `endpoint` is a plain `http://host[:port]/path` URL.
+    pub fn new(endpoint: &str) -> Result<Self> {
+        let endpoint = Endpoint::parse(endpoint)?;
+        let (outbox, outgoing) = mpsc::channel::<Vec<u8>>();
+        let (incoming, inbox) = mpsc::channel();
+        let (failed, failures) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            for message in outgoing {
+                match endpoint.post(&message) {
+                    Ok(reply) if !reply.is_empty() => {
+                        if incoming.send(reply).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        let _ = failed.send(format!("Sync request failed: {:#}", e));
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            outbox: Mutex::new(outbox),
+            inbox: Mutex::new(inbox),
+            failures: Mutex::new(failures),
+        })
+    }
+
+    pub fn send(&self, message: Vec<u8>) {
+        let _ = self.outbox.lock().unwrap().send(message);
+    }
+
+    /// Replies that arrived since the last call, oldest first.
+    pub fn take_received(&self) -> Vec<Vec<u8>> {
+        self.inbox.lock().unwrap().try_iter().collect()
+    }
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or(anyhow!("Endpoint '{}' must start with http://", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| anyhow!("Invalid port in endpoint '{}'", url))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(anyhow!("Missing host in endpoint '{}'", url));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn post(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let (status, reply) = self.request("POST", &self.path, Some(body))?;
+        if !(200..300).contains(&status) {
+            return Err(anyhow!("Sync server answered {}", status));
+        }
+        Ok(reply)
+    }
+
+    /// `method path` with `body`, answering the status code and the response body.
+    pub fn request(&self, method: &str, path: &str, body: Option<&[u8]>) -> Result<(u16, Vec<u8>)> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))
+            .with_context(|| format!("Failed to connect to {}:{}", self.host, self.port))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let body = body.unwrap_or_default();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/octet-stream\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            path,
+            self.host,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        let code = status
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| anyhow!("{}:{} answered '{}'", self.host, self.port, status.trim()))?;
+
+        // Headers: only the length matters, the connection closes after the body anyway
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let mut reply = Vec::new();
+        match length {
+            Some(len) => reader.take(len as u64).read_to_end(&mut reply)?,
+            None => reader.read_to_end(&mut reply)?,
+        };
+        Ok((code, reply))
+    }
+}
+
+pub fn register_host_calls(
+    linker: &mut Linker<HostState>,
+    plugin: String,
+    client: Arc<SyncClient>,
+) -> Result<()> {
+    linker.func_wrap(
+        "env",
+        "send_to_server",
+        move |caller: Caller<'_, HostState>, message_ptr: i32, message_len: i32| -> Result<()> {
+            validate_guest_range(&caller, &plugin, "send_to_server", message_ptr, message_len)?;
+            if let Some(message) =
+                FatPtr::new(message_ptr, message_len).read(&caller.data().shared_memory)
+            {
+                client.send(message);
+            }
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+/// Hands every reply received so far to each plugin exporting `receive_from_server`, and logs
+/// the requests that failed.
+/// Call it from the embedder's main loop. Returns how many replies were delivered.
+pub fn deliver(host: &mut BlindHost, client: &SyncClient) -> Result<usize> {
+    for failure in client.failures.lock().unwrap().try_iter() {
+        host.store
+            .data()
+            .logger
+            .lock()
+            .unwrap()
+            .log("host", Level::Warn, &failure);
+    }
+    let replies = client.take_received();
+    if replies.is_empty() {
+        return Ok(0);
+    }
+
+    let mut receivers: Vec<String> = host.store.data().instances.keys().cloned().collect();
+    receivers.sort();
+    receivers.retain(|name| host.get_func(name, "receive_from_server").is_ok());
+
+    for reply in &replies {
+        for name in &receivers {
+            let func = host.get_func(name, "receive_from_server")?;
+            let ptr = alloc_shared(host.store.data(), reply.len() as i32);
+            if ptr == 0 {
+                return Err(anyhow!(
+                    "Out of shared memory for a {} byte sync reply",
+                    reply.len()
+                ));
+            }
+            host.write_mem(ptr, reply)?;
+            let args = [Val::I32(ptr), Val::I32(reply.len() as i32)];
+            let result = host.profiled(name, "receive_from_server", |store| {
+                func.call(store, &args, &mut [])
+            });
+            free_shared(host.store.data(), ptr, reply.len() as i32);
+            result.with_context(|| format!("'{}' failed to receive a sync reply", name))?;
+        }
+    }
+    Ok(replies.len())
+}
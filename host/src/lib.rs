@@ -1,3 +1,18 @@
 pub mod allocator;
+pub mod delta;
+pub mod embedder;
 pub mod host;
 pub mod host_calls;
+
+#[cfg(feature = "puffin")]
+pub use puffin;
+
+/// A puffin scope until the end of the enclosing block, e.g. `scope!("tick", &name)`.
+/// Compiles to nothing without the `puffin` feature.
+#[macro_export]
+macro_rules! scope {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "puffin")]
+        $crate::puffin::profile_scope!($($arg)*);
+    };
+}
@@ -1,26 +1,62 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{prelude::*, widgets::*};
+use ecs_protocol::kernel::KERNEL_MODULE;
+use ratatui::prelude::*;
 use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use wasmtime::TypedFunc;
 
-// Internal crate imports
-pub mod allocator;
-pub mod host;
-pub mod host_calls;
-
-use host::host_object::{BlindHost, BlindHostConfig};
+use bus_protocol::chat::ChatLine;
+use ecs_protocol::{CAPABILITY_EVENTS, CAPABILITY_TUI};
 use grid_protocol::{
-    GridCell, GridInput, 
-    INPUT_KEY, INPUT_NONE, 
-    KEY_ENTER, KEY_ESC, KEY_BACKSPACE, KEY_LEFT, KEY_RIGHT, KEY_UP, KEY_DOWN, KEY_DELETE, KEY_TAB,
-    MOD_SHIFT, MOD_CTRL, MOD_ALT
+    GridInput, INPUT_COMMIT, INPUT_KEY, INPUT_NONE, KEY_BACKSPACE, KEY_DELETE, KEY_DOWN, KEY_ENTER,
+    KEY_ESC, KEY_LEFT, KEY_RIGHT, KEY_TAB, KEY_UP, MOD_ALT, MOD_CTRL, MOD_SHIFT,
 };
+use host::embedder::args::{Args, AssertAction};
+use host::embedder::autosave::Autosave;
+use host::embedder::chat::{ChatInput, ChatKey, CHAT_FADE, CHAT_LINES};
+use host::embedder::compositor::Compositor;
+use host::embedder::config::HostConfig;
+use host::embedder::crash::{self, InputLog, DEFAULT_CRASH_DIR};
+use host::embedder::debug_server::DebugServer;
+use host::embedder::driver::{DriverHandle, Frame, DEFAULT_DRIVER};
+use host::embedder::export;
+use host::embedder::headless::{self, HeadlessOptions, InputScript, ScriptInput, HEADLESS_DELTA};
+use host::embedder::images::{self, GraphicsProtocol, ImageStore};
+use host::embedder::input_record::{InputRecorder, InputReplay};
+use host::embedder::keymap::{HostAction, Keymap};
+use host::embedder::lockstep::LockstepSession;
+use host::embedder::narrator::Narrator;
+use host::embedder::netplay::{InputExchange, Session, DEFAULT_INPUT_DELAY, FRAME_DELTA};
+use host::embedder::players::{PlayerServer, LOCAL_PLAYER};
+use host::embedder::record::CastRecorder;
+use host::embedder::recovery::{self, Crashed};
+use host::embedder::rollback::RollbackSession;
+use host::embedder::simulate::{self, SimulationOptions};
+use host::embedder::spectate::SpectatorServer;
+use host::embedder::split::{SplitClient, SplitServer};
+use host::embedder::theme::Theme;
+use host::embedder::transport::TransportKind;
+use host::embedder::widgets;
+use host::host::heap_timeline::DEFAULT_SAMPLE_INTERVAL;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::inspect;
+use host::host::interfaces;
+use host::host::logger::{Level, Sink};
+use host::host::metrics;
+use host::host::profiler;
+use host::host_calls::bus;
+use host::host_calls::files::{self, FileAccess, DEFAULT_FILES_DIR};
+use host::host_calls::replicate::{self, Replicas};
+use host::host_calls::storage::{self, FileStorage, DEFAULT_STORAGE_DIR};
+use host::host_calls::sync::{self, SyncClient};
 
 // Helper to map keys from Crossterm to GridInput
 fn map_key(event: KeyEvent) -> GridInput {
@@ -32,9 +68,15 @@ fn map_key(event: KeyEvent) -> GridInput {
     };
 
     // Map Modifiers
-    if event.modifiers.contains(KeyModifiers::SHIFT) { input.modifiers |= MOD_SHIFT; }
-    if event.modifiers.contains(KeyModifiers::CONTROL) { input.modifiers |= MOD_CTRL; }
-    if event.modifiers.contains(KeyModifiers::ALT) { input.modifiers |= MOD_ALT; }
+    if event.modifiers.contains(KeyModifiers::SHIFT) {
+        input.modifiers |= MOD_SHIFT;
+    }
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        input.modifiers |= MOD_CTRL;
+    }
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        input.modifiers |= MOD_ALT;
+    }
 
     // Map Code
     match event.code {
@@ -55,178 +97,930 @@ fn map_key(event: KeyEvent) -> GridInput {
     input
 }
 
+// Plugin traps that end the run leave a bundle behind to reproduce them with
+fn write_crash_bundle(error: &anyhow::Error, host: &BlindHost, inputs: &InputLog, config: &str) {
+    if !crash::is_trap(error) {
+        return;
+    }
+    match crash::write_bundle(Path::new(DEFAULT_CRASH_DIR), error, host, inputs, config) {
+        Ok(bundle) => eprintln!("💥 [HOST] Crash bundle written to '{}'", bundle.display()),
+        Err(e) => eprintln!("⚠️ [HOST] Failed to write a crash bundle: {:#}", e),
+    }
+}
+
+// `--heap-timeline`
+fn write_heap_timeline(path: &Path, host: &BlindHost) {
+    let csv = host.store.data().heap_timeline.lock().unwrap().to_csv();
+    match std::fs::write(path, csv) {
+        Ok(()) => eprintln!("📊 [HOST] Heap timeline written to '{}'", path.display()),
+        Err(e) => eprintln!(
+            "⚠️ [HOST] Failed to write the heap timeline to '{}': {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+// `--trace-calls`: Graphviz for .dot paths, JSON otherwise
+fn write_call_graph(path: &Path, host: &BlindHost) {
+    let graph = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("dot") => host.call_graph_dot(),
+        _ => format!("{:#}\n", host.call_graph_json()),
+    };
+    match std::fs::write(path, graph) {
+        Ok(()) => eprintln!("🕸️ [HOST] Call graph written to '{}'", path.display()),
+        Err(e) => eprintln!(
+            "⚠️ [HOST] Failed to write the call graph to '{}': {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+// `--simulate`: the first driver's hashes after a deterministic headless run
+fn run_simulation(args: &Args) -> Result<()> {
+    let driver = args
+        .drivers
+        .first()
+        .map_or(PathBuf::from(DEFAULT_DRIVER.1), |(_, path)| path.clone());
+    let input = match &args.input_script {
+        Some(path) => InputScript::load(path)?,
+        None => InputScript::default(),
+    };
+    let options = SimulationOptions {
+        plugins: args.plugins.clone(),
+        seed: args.seed.unwrap_or(0),
+    };
+    println!(
+        "{}",
+        simulate::simulate_with(&driver, args.ticks, &input, &options)?
+    );
+    Ok(())
+}
+
+// `--serve`: core plugin frames at the netplay frame rate, until the process is stopped
+fn run_split_server(
+    host: &mut BlindHost,
+    addr: &str,
+    args: &Args,
+    replicas: Arc<Replicas>,
+    transport: TransportKind,
+    mut autosave: Option<Autosave>,
+) -> Result<()> {
+    let mut server = SplitServer::bind(addr, host, replicas, transport)?;
+    if let Some(room) = &args.relay {
+        server.serve_relay(room)?;
+    }
+    let frame = Duration::from_secs_f32(FRAME_DELTA);
+    loop {
+        let start = Instant::now();
+        server.step(host)?;
+        host.end_tick(start.elapsed());
+        if let Some(autosave) = autosave.as_mut() {
+            autosave.tick(host);
+        }
+        std::thread::sleep(frame.saturating_sub(start.elapsed()));
+    }
+}
+
 fn main() -> Result<()> {
     // 1. Config & Host Setup
-    let config = BlindHostConfig::default();
-    
-    // We don't need any special host calls for this MVP, but we must pass a linker setup closure
-    let mut host = BlindHost::new(config, |_, _| Ok(()))?;
+    let args = Args::parse()?;
+    if args.simulate {
+        return run_simulation(&args);
+    }
+    let theme = Theme::load_or_default(args.theme.as_deref())?;
+    let keymap = Keymap::load_or_default(args.keys.as_deref())?;
+    let mut host_config = HostConfig::load_or_default(args.config.as_deref())?;
+    // Plugin logs would scribble over the TUI on stderr, and over frames on stdout headless
+    host_config.log.sinks.get_or_insert(vec![if args.headless {
+        Sink::Stderr
+    } else {
+        Sink::Pane
+    }]);
+    let config_text = format!("{:#?}\n\n{:#?}\n", args, host_config);
+    let _puffin = if args.profile {
+        Some(profiler::serve_puffin()?)
+    } else {
+        None
+    };
+    // Netplay only works if every player's machine computes the same frames
+    let deterministic = args.deterministic || args.rollback.is_some() || args.lockstep.is_some();
+    let tick_deadlines = host_config.tick_deadlines();
+    let config = BlindHostConfig {
+        // Mouse events are dropped by the main loop for now
+        capabilities: CAPABILITY_TUI | CAPABILITY_EVENTS,
+        profile_syscalls: args.profile_syscalls,
+        log: host_config.log,
+        random_seed: args.seed.or(deterministic.then_some(0)),
+        deterministic,
+        trace_calls: args.trace_calls.is_some(),
+        // Headless ticks take microseconds, the timeline would skip nearly all of them
+        heap_sample_interval: if args.headless {
+            Duration::ZERO
+        } else {
+            DEFAULT_SAMPLE_INTERVAL
+        },
+        tick_budget: args.tick_budget,
+        save_backend: Some(host_config.saves.backend()?),
+        quotas: host_config.quotas.quotas(),
+        audit_log: args.audit_log.clone(),
+        tick_deadlines,
+        enable_wasi: host_config.wasi.enabled,
+        wasi_dir: Some(host_config.wasi.dir()),
+        module_cache: host_config.module_cache.dir(),
+        ..Default::default()
+    };
+
+    // Embedder host calls: image uploads for sprite cells, key-value storage, user files
+    let image_store = Arc::new(Mutex::new(ImageStore::default()));
+    let graphics = GraphicsProtocol::detect();
+    let storage = Arc::new(FileStorage::new(DEFAULT_STORAGE_DIR));
+    let file_access = Arc::new(FileAccess::new(DEFAULT_FILES_DIR));
+    let sync_client = match &args.sync_url {
+        Some(url) => Some(Arc::new(SyncClient::new(url)?)),
+        None => None,
+    };
+    let replicas = Arc::new(Replicas::default());
+    let split = args.serve.is_some() || args.connect.is_some();
+    let (images_for, sync_for, replicas_for) =
+        (image_store.clone(), sync_client.clone(), replicas.clone());
+    let mut host = BlindHost::new(config, move |linker, plugin| {
+        images::register_host_calls(linker, plugin.to_string(), images_for.clone())?;
+        // Server sync, only when an endpoint is configured
+        if let Some(client) = &sync_for {
+            sync::register_host_calls(linker, plugin.to_string(), client.clone())?;
+        }
+        // State replicated from the split server, at either end
+        if split {
+            replicate::register_host_calls(linker, plugin.to_string(), replicas_for.clone())?;
+        }
+        // Files the user exchanges with plugins (task exports, ...)
+        files::register_host_calls(linker, plugin.to_string(), file_access.clone())?;
+        // Plugin save data, kept between runs
+        storage::register_host_calls(linker, plugin.to_string(), storage.clone())
+    })?;
+
+    // Monitoring for long-running hosts, answered from the metrics' own thread
+    if let Some(addr) = &args.metrics {
+        metrics::serve(addr, &host)?;
+    }
 
     // 2. Initialize Shared Heap
-    // The HostHeap starts empty. We must give it the free memory region to manage.
-    {
-        let data = host.store.data();
-        let heap_start = data.heap_start_address as u32;
-        // SharedMemory len is in bytes
-        let mem_size = data.shared_memory.data().len() as u32;
-        
-        let mut heap = data.heap.lock().unwrap();
-        // Initialize the heap with the remaining free memory block
-        if heap.free_blocks.is_empty() {
-            heap.dealloc(heap_start, mem_size - heap_start);
-        }
-    }
-
-    // 3. Load the Driver Plugin
-    // We expect the WASM to be built in the target directory
-    let wasm_path = "target/wasm32-unknown-unknown/release/grid_driver.wasm";
-    if !std::path::Path::new(wasm_path).exists() {
-        // Fallback or Error
-        eprintln!("❌ Error: WASM driver not found at '{}'", wasm_path);
-        eprintln!("   Please run: cargo build -p grid-driver --target wasm32-unknown-unknown --release");
+    host.init_heap();
+
+    // 3. Load the Plugins
+    // Non-driver modules first (e.g. tasksapp_core), so drivers can link against them
+    // Each with the manifest beside it or in its directory, if it has one (host/manifest.rs)
+    // The ECS kernel host.toml picks goes first, so every plugin links against it
+    if let Some(kernel_path) = host_config.ecs.kernel_path() {
+        if args.plugins.iter().any(|(name, _)| name == KERNEL_MODULE) {
+            return Err(anyhow!(
+                "host.toml's [ecs] kernel is '{}' already; drop the --plugin",
+                KERNEL_MODULE
+            ));
+        }
+        host.load_plugin_file(KERNEL_MODULE, &kernel_path)?;
+        interfaces::check_exports(&mut host, KERNEL_MODULE)?;
+    }
+    for (name, wasm_path) in &args.plugins {
+        host.load_plugin_file(name, wasm_path)?;
+        interfaces::check_exports(&mut host, name)?;
+    }
+
+    // Split servers run the plugins without a terminal or drivers, for as long as they're up
+    if let Some(addr) = &args.serve {
+        if !args.drivers.is_empty()
+            || args.connect.is_some()
+            || args.headless
+            || args.rollback.is_some()
+            || args.lockstep.is_some()
+        {
+            return Err(anyhow!(
+                "--serve runs the --plugins on their own; drivers go on the clients (--connect)"
+            ));
+        }
+        let autosave = host_config.saves.autosave();
+        return run_split_server(
+            &mut host,
+            addr,
+            &args,
+            replicas,
+            host_config.net.transport,
+            autosave,
+        );
+    }
+
+    // We expect the WASM to be built in the target directory unless --driver says otherwise
+    let mut drivers = args.drivers.clone();
+    if drivers.is_empty() {
+        drivers.push((DEFAULT_DRIVER.0.to_string(), DEFAULT_DRIVER.1.into()));
+    }
+
+    // 4. Bind Exports, one pane per driver
+    let mut compositor = Compositor::new(args.layout);
+    for (name, wasm_path) in &drivers {
+        if !wasm_path.exists() {
+            // Fallback or Error
+            eprintln!(
+                "❌ Error: WASM driver not found at '{}'",
+                wasm_path.display()
+            );
+            eprintln!("   Please run: cargo build -p grid-driver --target wasm32-unknown-unknown --release");
+            return Ok(());
+        }
+
+        host.load_plugin_file(name, wasm_path)?;
+        compositor.add(DriverHandle::bind(&mut host, name)?);
+    }
+
+    // 5. Headless runs stop here: no terminal, scripted input, frames to stdout/files.
+    // Only the first driver runs headless.
+    if args.headless {
+        if args.record_input.is_some() {
+            return Err(anyhow!("--record-input records the terminal's input, headless runs already have theirs in --input"));
+        }
+        if args.rollback.is_some() || args.lockstep.is_some() || args.connect.is_some() {
+            return Err(anyhow!(
+                "Netplay and split play are played in the terminal, not headless"
+            ));
+        }
+        // A replay is an input script, played to its end
+        let mut ticks = args.ticks;
+        if let Some(path) = &args.replay_input {
+            ticks = ticks.max(InputScript::load(path)?.last_tick());
+        }
+        let options = HeadlessOptions {
+            ticks,
+            input_script: args.replay_input.clone().or(args.input_script.clone()),
+            every: args.every,
+            out_dir: args.out_dir.clone(),
+            fail_on_assert: args.on_assert == AssertAction::Fail,
+            golden: args.golden.clone(),
+            update_golden: args.update_golden,
+        };
+        let driver = &compositor.focused().driver;
+        let result = headless::run(&mut host, driver, &image_store.lock().unwrap(), &options);
+        if let Some(path) = &args.trace_calls {
+            write_call_graph(path, &host);
+        }
+        if let Some(path) = &args.heap_timeline {
+            write_heap_timeline(path, &host);
+        }
+        if let Err(e) = result {
+            // The input script already is the input
+            write_crash_bundle(&e, &host, &InputLog::default(), &config_text);
+            return Err(e);
+        }
+        if let Some((ptr, len)) = args.inspect {
+            print!("{}", inspect::hex_dump(&host, ptr, len)?);
+        }
+        // What another run with the same input has to end with
+        if args.deterministic {
+            eprintln!("🎲 [HOST] State hash {:016x}", host.state_hash());
+        }
         return Ok(());
     }
-    
-    let wasm_bytes = std::fs::read(wasm_path).context("Failed to read grid_driver.wasm")?;
-    host.load_plugin("grid-driver", &wasm_bytes)?;
 
-    // 4. Bind Exports
-    // Typed functions for performance and type safety
-    let tick_fn: TypedFunc<(f32,), ()> = host.get_func("grid-driver", "tick")?.typed(&host.store)?;
-    let set_input_fn: TypedFunc<(i32,), ()> = host.get_func("grid-driver", "set_input")?.typed(&host.store)?;
-    let set_tickrate_fn: TypedFunc<(f32,), ()> = host.get_func("grid-driver", "set_tickrate")?.typed(&host.store)?;
-    let get_dims_fn: TypedFunc<(), i64> = host.get_func("grid-driver", "get_grid_dimensions")?.typed(&host.store)?;
-    let get_ptr_fn: TypedFunc<(), i32> = host.get_func("grid-driver", "get_grid_ptr")?.typed(&host.store)?;
+    // Memory inspection only: the first frame, then the dump instead of the TUI
+    if let Some((ptr, len)) = args.inspect {
+        compositor.prime(&mut host, 0.0)?;
+        print!("{}", inspect::hex_dump(&host, ptr, len)?);
+        return Ok(());
+    }
 
-    // 5. Allocate Input Buffer in Shared Memory
-    // The driver reads from this pointer. We write to it.
-    let input_layout = std::alloc::Layout::new::<GridInput>();
-    let input_ptr = {
-        let mut heap = host.store.data().heap.lock().unwrap();
-        // alloc returns Option<u32>
-        heap.alloc(input_layout.size() as u32)
-            .ok_or(anyhow::anyhow!("Failed to allocate input buffer in SharedMemory"))? as i32
+    // Debug commands from external tools; `reload` looks plugins up here
+    let debug_server = match &args.debug_server {
+        Some(addr) => Some(DebugServer::bind(addr)?),
+        None => None,
+    };
+    let modules: Vec<(String, std::path::PathBuf)> =
+        args.plugins.iter().chain(&drivers).cloned().collect();
+    let mut spectators = match &args.spectate {
+        Some(addr) => Some(SpectatorServer::bind(addr, &theme)?),
+        None => None,
+    };
+    // Remote players, whose input goes to the focused pane like the keyboard's
+    let players = match &args.players {
+        Some(addr) => Some(PlayerServer::bind(addr)?),
+        None => None,
+    };
+    // Netplay: the first pane, ticked on every peer's input at a fixed frame rate
+    let mut netplay = if args.rollback.is_some() || args.lockstep.is_some() {
+        if args.replay_input.is_some() || players.is_some() {
+            return Err(anyhow!(
+                "Netplay takes its input from the keyboard and its peers only"
+            ));
+        }
+        let player = u8::try_from(args.player)
+            .map_err(|_| anyhow!("--player {} is too large", args.player))?;
+        let delay = args.input_delay.unwrap_or(DEFAULT_INPUT_DELAY);
+        let (peers, rollback) = match (&args.rollback, &args.lockstep) {
+            (Some(peers), None) => (peers, true),
+            (None, Some(peers)) => (peers, false),
+            _ => {
+                return Err(anyhow!(
+                    "--rollback and --lockstep are two ways to play, pick one"
+                ))
+            }
+        };
+        let exchange = match &args.relay {
+            // Through the relay, the players are counted rather than listed
+            Some(room) => {
+                let players = match peers.as_slice() {
+                    [players] => players.parse().ok(),
+                    _ => None,
+                };
+                let players = players.ok_or(anyhow!(
+                    "With --relay, netplay takes the number of players, e.g. '2'"
+                ))?;
+                InputExchange::relayed(room, players, player, delay)?
+            }
+            None => InputExchange::bind(peers, player, delay)?,
+        };
+        if rollback {
+            Some(Session::Rollback(RollbackSession::new(exchange)?))
+        } else {
+            Some(Session::Lockstep(LockstepSession::new(exchange)?))
+        }
+    } else {
+        None
     };
+    // Split play: the drivers here, the plugins they draw on the server
+    let mut split_client = match &args.connect {
+        Some(_) if netplay.is_some() => {
+            return Err(anyhow!(
+                "Netplay and split play are two ways to play, pick one"
+            ))
+        }
+        Some(addr) => match (
+            SplitClient::connect(addr, &host, replicas.clone(), host_config.net.transport),
+            &args.relay,
+        ) {
+            (Ok(client), _) => Some(client),
+            (Err(e), Some(room)) => {
+                eprintln!("⚠️ [HOST] {:#}; trying the relay", e);
+                Some(SplitClient::join_relay(room, &host, replicas.clone())?)
+            }
+            (Err(e), None) => return Err(e),
+        },
+        None => None,
+    };
+    if args.relay.is_some() && netplay.is_none() && split_client.is_none() {
+        return Err(anyhow!(
+            "--relay is for playing with others: --serve, --connect, --rollback or --lockstep"
+        ));
+    }
 
     // 6. TUI Initialization
     enable_raw_mode()?;
     let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    // Pasted text (and whatever the terminal's IME commits as a burst) arrives as one event
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    let mut recorder = match &args.record {
+        Some(path) => {
+            let size = terminal.size()?;
+            Some(CastRecorder::create(path, size.width, size.height)?)
+        }
+        None => None,
+    };
+
+    // Input recordings to write, or to play back instead of the keyboard (input_record.rs)
+    let mut input_recorder = match &args.record_input {
+        Some(path) => Some(InputRecorder::create(path)?),
+        None => None,
+    };
+    let mut replay = match &args.replay_input {
+        Some(path) => Some(InputReplay::load(path)?),
+        None => None,
+    };
+
+    // Accessibility: describe the focused pane in words as it changes
+    let mut narrator = if args.narrate.is_some() || args.speak.is_some() {
+        Some(Narrator::new(
+            args.narrate.as_deref(),
+            args.speak.as_deref(),
+        )?)
+    } else {
+        None
+    };
+
     // 7. Main Loop
-    let mut tick_rate = 0.0; // Hz. 0.0 means "input driven"
-    
-    // Notify driver of initial tickrate
-    set_tickrate_fn.call(&mut host.store, (tick_rate,))?;
+    let tick_rate = if netplay.is_some() || split_client.is_some() {
+        1.0 / FRAME_DELTA
+    } else {
+        0.0
+    }; // Hz. 0.0 means "input driven"
 
     let mut last_tick = Instant::now();
     let mut should_quit = false;
+    let mut export_requested = false;
+    let mut show_profile = false;
+    let mut show_log = false;
+    let mut show_heap = false;
+    // No ticks while paused (the pause key, or a failed host_assert with `--on-assert pause`)
+    let mut paused = false;
+    // The chat line being typed, while the chat key has the keyboard (split play only)
+    let mut chat_input: Option<ChatInput> = None;
+    let mut chat_seen = 0;
+    // Whether the last draw had the chat overlay, which has to go once its lines fade
+    let mut chat_shown = false;
+    let player_name = host_config.net.player_name();
+    // Plugins' autosave exports, every so often (off unless host.toml's [saves] asks for it)
+    let mut autosave = host_config.saves.autosave();
+    // For the crash bundle, should a plugin trap
+    let mut inputs = InputLog::default();
+    // A driver that trapped mid-tick, shown instead of ticking until it's restarted
+    let mut crashed: Option<Crashed> = None;
 
-    // Initial tick to render something
-    tick_fn.call(&mut host.store, (0.0,))?;
+    // Errors end the loop here rather than in main, so the terminal is restored first
+    let result = (|| -> Result<()> {
+        // Notify drivers of the initial tickrate and tick once to render something.
+        // Each pane keeps a host-side copy of its grid, refreshed after ticks from the driver's dirty rects.
+        compositor.prime(&mut host, tick_rate)?;
+        let mut needs_draw = true;
+        // `--skip-slow-frames` never skips two frames in a row, so the screen can't freeze
+        let mut skipped_frame = false;
 
-    loop {
-        if should_quit { break; }
+        loop {
+            if should_quit {
+                break;
+            }
+            profiler::puffin_frame();
 
-        let mut input_val = GridInput::default();
-        let mut input_received = false;
+            let mut input_val = GridInput::default();
+            let mut input_text: Option<(u32, String)> = None;
+            let mut input_received = false;
+            let mut input_player = LOCAL_PLAYER;
+            let mut slow_tick = false;
+            if replay.as_ref().is_some_and(|replay| replay.finished()) {
+                replay = None;
+                host.store.data().logger.lock().unwrap().log(
+                    "host",
+                    Level::Info,
+                    "Replay finished",
+                );
+            }
 
-        // --- Event Polling ---
-        // If tick_rate is 0, we block (wait) for input to save CPU.
-        // If tick_rate > 0, we poll with a short timeout to maintain frame rate.
-        let poll_timeout = if tick_rate == 0.0 {
-            Duration::from_millis(100) // Small timeout to allow check of other conditions if needed
-        } else {
-            Duration::from_millis(1) // Fast poll
-        };
+            // --- Event Polling ---
+            // If tick_rate is 0, we block (wait) for input to save CPU.
+            // If tick_rate > 0, we poll with a short timeout to maintain frame rate.
+            // Replays keep their own time, and remote players shouldn't wait on the keyboard,
+            // so they're polled for quickly too.
+            let poll_timeout = if tick_rate == 0.0 && replay.is_none() && players.is_none() {
+                Duration::from_millis(100) // Small timeout to allow check of other conditions if needed
+            } else {
+                Duration::from_millis(1) // Fast poll
+            };
 
-        if event::poll(poll_timeout)? {
-            let evt = event::read()?;
-            match evt {
-                Event::Key(key) => {
-                    if key.code == KeyCode::Esc {
-                        should_quit = true;
+            if event::poll(poll_timeout)? {
+                // Ignore mouse for MVP
+                match event::read()? {
+                    // The crash screen only restarts the trapped driver or quits
+                    Event::Key(key) if crashed.is_some() => {
+                        if key.code == KeyCode::Char('r') {
+                            let driver = crashed
+                                .as_ref()
+                                .map(|crashed| crashed.driver.clone())
+                                .unwrap_or_default();
+                            match recovery::restart(
+                                &mut host,
+                                &mut compositor,
+                                &driver,
+                                &modules,
+                                tick_rate,
+                            ) {
+                                Ok(()) => {
+                                    crashed = None;
+                                    last_tick = Instant::now();
+                                    let text = format!("Restarted '{}'", driver);
+                                    host.store.data().logger.lock().unwrap().log(
+                                        "host",
+                                        Level::Info,
+                                        &text,
+                                    );
+                                }
+                                Err(e) => {
+                                    if let Some(crashed) = crashed.as_mut() {
+                                        crashed.message = format!("Restart failed: {:#}", e);
+                                    }
+                                }
+                            }
+                        } else if keymap.action(&key) == Some(HostAction::Quit) {
+                            should_quit = true;
+                        }
+                        needs_draw = true;
                     }
-                    input_val = map_key(key);
-                    input_received = true;
+                    Event::Key(key) if chat_input.is_some() => {
+                        match chat_input.as_mut().map(|input| input.key(&key)) {
+                            Some(ChatKey::Send(text)) => {
+                                host.store
+                                    .data()
+                                    .chat
+                                    .lock()
+                                    .unwrap()
+                                    .post(ChatLine::new(&player_name, &text));
+                                chat_input = None;
+                            }
+                            Some(ChatKey::Close) => chat_input = None,
+                            _ => {}
+                        }
+                        needs_draw = true;
+                    }
+                    Event::Key(key) => match keymap.action(&key) {
+                        // Host commands, never forwarded to the driver:
+                        // dump the focused pane's frame, move focus to the next pane
+                        Some(HostAction::Export) => export_requested = true,
+                        Some(HostAction::FocusNext) => {
+                            compositor.focus_next();
+                            needs_draw = true;
+                        }
+                        Some(HostAction::ToggleProfiler) => {
+                            show_profile = !show_profile;
+                            needs_draw = true;
+                        }
+                        Some(HostAction::ToggleLog) => {
+                            show_log = !show_log;
+                            needs_draw = true;
+                        }
+                        Some(HostAction::ToggleHeap) => {
+                            show_heap = !show_heap;
+                            needs_draw = true;
+                        }
+                        Some(HostAction::Pause) => paused = !paused,
+                        Some(HostAction::Chat) if split_client.is_some() => {
+                            chat_input = Some(ChatInput::default());
+                            needs_draw = true;
+                        }
+                        // The keyboard only quits a replay or a pause, the rest of its input is dropped
+                        action
+                            if (replay.is_some() || paused) && action != Some(HostAction::Quit) => {
+                        }
+                        action => {
+                            if action == Some(HostAction::Quit) {
+                                should_quit = true;
+                            }
+                            // User remaps apply only to what the driver sees
+                            input_val = map_key(keymap.remap(key));
+                            input_received = true;
+                        }
+                    },
+                    Event::Paste(text) if chat_input.is_some() => {
+                        if let Some(input) = chat_input.as_mut() {
+                            input.paste(&text);
+                        }
+                        needs_draw = true;
+                    }
+                    // The terminal owns the preedit, we only ever see the committed text
+                    Event::Paste(_) if replay.is_some() || paused => {}
+                    Event::Paste(text) => {
+                        input_text = Some((INPUT_COMMIT, text));
+                        input_received = true;
+                    }
+                    // The terminal lost its contents, draw everything again
+                    Event::Resize(_, _) => needs_draw = true,
+                    _ => {}
                 }
-                _ => {} // Ignore mouse/resize for MVP
             }
-        }
 
-        // --- Ticking Logic ---
-        let should_tick = if tick_rate == 0.0 {
-            // Tick only if we got input
-            input_received
-        } else {
-            // Tick if enough time passed
-            last_tick.elapsed().as_secs_f32() >= (1.0 / tick_rate)
-        };
+            // Replayed input arrives like typed input, at the tick and time it was recorded at
+            if let Some(replay) = replay.as_mut().filter(|replay| !paused && replay.due()) {
+                match replay.take() {
+                    ScriptInput::Key(input) => input_val = input,
+                    ScriptInput::Text(kind, text) => input_text = Some((kind, text)),
+                }
+                input_received = true;
+            }
+
+            // Remote players get the ticks the keyboard and replay leave over, one input each;
+            // what they send while paused or during a replay is dropped, like the keyboard's
+            if let Some(players) = &players {
+                if paused || replay.is_some() {
+                    players.discard();
+                } else if !input_received {
+                    if let Some((player, input)) = players.next() {
+                        match input {
+                            ScriptInput::Key(input) => input_val = input,
+                            ScriptInput::Text(kind, text) => input_text = Some((kind, text)),
+                        }
+                        input_player = player;
+                        input_received = true;
+                    }
+                }
+            }
+
+            // The server's state and messages, and ours for it, before the bus takes over
+            if let Some(client) = split_client.as_mut() {
+                client.exchange(&mut host)?;
+            }
+            // New chat lines, to show
+            let chat_count = host.store.data().chat.lock().unwrap().count();
+            if chat_count != chat_seen {
+                chat_seen = chat_count;
+                needs_draw = true;
+            }
+
+            // Plugin-to-plugin messages queued during the last tick; netplay delivers its own
+            if netplay.is_none() {
+                bus::deliver(&mut host)?;
+            }
+
+            // Server replies go straight to the plugins, they'll show up on the next tick
+            if let Some(client) = &sync_client {
+                sync::deliver(&mut host, client)?;
+            }
+
+            // Calls and reloads may have changed what the panes show
+            if let Some(server) = &debug_server {
+                if server.serve(&mut host, &mut compositor, &modules, tick_rate) > 0 {
+                    for pane in compositor.panes_mut() {
+                        needs_draw |= pane.refresh(&mut host)?;
+                    }
+                }
+            }
+
+            // --- Ticking Logic ---
+            let should_tick = if crashed.is_some() || (paused && !should_quit) {
+                false
+            } else if tick_rate == 0.0 {
+                // Tick only if we got input
+                input_received
+            } else {
+                // Tick if enough time passed
+                last_tick.elapsed().as_secs_f32() >= (1.0 / tick_rate)
+            };
+
+            if input_received {
+                if let Some(rec) = recorder.as_mut() {
+                    match &input_text {
+                        Some((_, text)) => rec.text(text)?,
+                        None => rec.input(&input_val)?,
+                    }
+                }
+            }
+
+            // Netplay frames take one key each, typed ones wait their turn; only keys go to peers
+            if let Some(session) = netplay.as_mut() {
+                if input_received && input_text.is_none() && input_val.input_type != INPUT_NONE {
+                    session.push_local(input_val);
+                }
+            }
 
-        if should_tick {
-             // 1. Update Input in WASM Memory
-             let bytes = bytemuck::bytes_of(&input_val);
-             host.write_mem(input_ptr, bytes)?;
-             
-             // 2. Notify Driver of Input Pointer
-             set_input_fn.call(&mut host.store, (input_ptr,))?;
-
-             // 3. Call Tick
-             // Calculate delta if needed, for now fixed or actual elapsed
-             let delta = last_tick.elapsed().as_secs_f32();
-             tick_fn.call(&mut host.store, (delta,))?;
-             
-             last_tick = Instant::now();
-        }
-
-        // --- Rendering ---
-        // We render every loop iteration to keep UI responsive (e.g. if we add UI outside the grid)
-        // Retrieve Grid Info
-        let dims = get_dims_fn.call(&mut host.store, ())?;
-        let width = (dims >> 32) as i32;
-        let height = (dims & 0xFFFFFFFF) as i32;
-        let grid_ptr = get_ptr_fn.call(&mut host.store, ())?;
-
-        // Read Grid Data
-        let grid_byte_len = width * height * std::mem::size_of::<GridCell>() as i32;
-        let grid_data = host.read_mem(grid_ptr, grid_byte_len)?;
-        let cells: &[GridCell] = bytemuck::cast_slice(&grid_data);
-
-        terminal.draw(|f| {
-            let area = f.area();
-            let buf = f.buffer_mut();
-            
-            // Render the Grid
-            for y in 0..height {
-                for x in 0..width {
-                    // Bounds check against screen size
-                    if (x as u16) < area.width && (y as u16) < area.height {
-                        let idx = (y * width + x) as usize;
-                        if idx < cells.len() {
-                            let cell = &cells[idx];
-                            // Only draw if char is valid
-                            if let Some(ch) = std::char::from_u32(cell.character) {
-                                // Basic Color Mapping (ANSI 256)
-                                let fg = Color::Indexed(cell.fg_color);
-                                let bg = Color::Indexed(cell.bg_color);
-                                
-                                buf.get_mut(x as u16, y as u16)
-                                   .set_char(ch)
-                                   .set_fg(fg)
-                                   .set_bg(bg);
+            if should_tick {
+                host::scope!("tick");
+                let tick_start = Instant::now();
+                // Calculate delta if needed, for now fixed or actual elapsed
+                let delta = if args.deterministic {
+                    HEADLESS_DELTA
+                } else {
+                    last_tick.elapsed().as_secs_f32()
+                };
+                let focus = compositor.focus_index();
+                let tick_input = match &input_text {
+                    Some((kind, text)) => ScriptInput::Text(*kind, text.clone()),
+                    None => ScriptInput::Key(input_val),
+                };
+                if let Some(rec) = input_recorder.as_mut() {
+                    rec.push(&tick_input)?;
+                }
+                inputs.push(tick_input);
+                if let Some(session) = netplay.as_mut() {
+                    let pane = &mut compositor.panes_mut()[0];
+                    let advance = session.advance(&mut host, &pane.driver)?;
+                    // After re-run frames the driver's dirty rects only cover the last one
+                    if advance.rolled_back > 0 {
+                        pane.frame = Frame::default();
+                    }
+                    needs_draw |= pane.refresh(&mut host)?;
+                } else {
+                    for (idx, pane) in compositor.panes_mut().iter_mut().enumerate() {
+                        // Input goes to the focused pane; the others only tick when the clock says so
+                        let ticked = if idx == focus {
+                            match &input_text {
+                                Some((kind, text)) => pane.driver.tick_text(
+                                    &mut host,
+                                    input_player,
+                                    *kind,
+                                    text,
+                                    delta,
+                                ),
+                                None => pane.driver.tick(
+                                    &mut host,
+                                    &input_val.with_player(input_player),
+                                    delta,
+                                ),
                             }
+                        } else if tick_rate > 0.0 {
+                            pane.driver.tick(&mut host, &GridInput::default(), delta)
+                        } else {
+                            continue;
+                        };
+
+                        // A trap stops the ticks for the crash screen, anything else ends the run
+                        match ticked.and_then(|()| pane.refresh(&mut host)) {
+                            Ok(changed) => needs_draw |= changed,
+                            Err(e) if crash::is_trap(&e) => {
+                                let dir = Path::new(DEFAULT_CRASH_DIR);
+                                crashed = Some(Crashed::new(
+                                    dir,
+                                    &pane.driver.name,
+                                    &e,
+                                    &host,
+                                    &inputs,
+                                    &config_text,
+                                ));
+                                needs_draw = true;
+                                break;
+                            }
+                            Err(e) => return Err(e),
                         }
                     }
                 }
+                slow_tick = host.end_tick(tick_start.elapsed());
+                if let Some(failure) = host.take_assert_failures().into_iter().next() {
+                    match args.on_assert {
+                        AssertAction::Log => {}
+                        AssertAction::Pause => {
+                            paused = true;
+                            show_log = true;
+                            needs_draw = true;
+                            host.store.data().logger.lock().unwrap().log(
+                                "host",
+                                Level::Warn,
+                                "Paused, the pause key resumes",
+                            );
+                        }
+                        AssertAction::Fail => {
+                            return Err(anyhow!(
+                                "'{}' failed an assertion at tick {}: {}",
+                                failure.plugin,
+                                failure.tick,
+                                failure.message
+                            ));
+                        }
+                    }
+                }
+
+                last_tick = Instant::now();
             }
-        })?;
-    }
+
+            // Between ticks, so plugins are saved as they stand after one
+            if let Some(autosave) = autosave.as_mut() {
+                autosave.tick(&mut host);
+            }
+
+            if export_requested {
+                export_requested = false;
+                let frame = &compositor.focused().frame;
+                export::save_frame(
+                    &frame.as_ref(),
+                    args.export_format,
+                    &theme,
+                    &image_store.lock().unwrap(),
+                )?;
+            }
+
+            // --- Rendering ---
+            // Nothing changed on either side, so the terminal already shows this frame.
+            // The profiler, log and heap overlays keep moving, so they're redrawn every time around,
+            // and so is chat until its lines fade.
+            let chat_lines = match (&split_client, &chat_input) {
+                (None, _) => Vec::new(),
+                (Some(_), Some(_)) => host
+                    .store
+                    .data()
+                    .chat
+                    .lock()
+                    .unwrap()
+                    .recent(CHAT_LINES, None),
+                (Some(_), None) => host
+                    .store
+                    .data()
+                    .chat
+                    .lock()
+                    .unwrap()
+                    .recent(CHAT_LINES, Some(CHAT_FADE)),
+            };
+            let show_chat = !chat_lines.is_empty() || chat_input.is_some();
+            needs_draw |= chat_shown && !show_chat;
+            if !needs_draw && !show_profile && !show_log && !show_heap && !show_chat {
+                continue;
+            }
+            // Catch up with the input that piled up during a slow tick before drawing again
+            if slow_tick && args.skip_slow_frames && !skipped_frame {
+                skipped_frame = true;
+                host.store.data().metrics.lock().unwrap().frame_skipped();
+                continue;
+            }
+            needs_draw = false;
+            skipped_frame = false;
+            chat_shown = show_chat;
+
+            let size = terminal.size()?;
+            let frame = compositor.compose(size.width, size.height);
+            let image_store_guard = image_store.lock().unwrap();
+
+            host::scope!("draw");
+            terminal.draw(|f| {
+                let area = f.area();
+                widgets::render_grid(
+                    f.buffer_mut(),
+                    area,
+                    &frame.as_ref(),
+                    &theme,
+                    &image_store_guard,
+                );
+
+                // Driver widgets go on top of their pane's cells
+                for (idx, pane_area) in compositor.areas(area.width, area.height) {
+                    widgets::render(f, pane_area, &compositor.panes()[idx].widgets, &theme);
+                }
+                if show_log {
+                    widgets::render_log(f, area, &host.recent_logs(area.height as usize), &theme);
+                }
+                if show_heap {
+                    let samples: Vec<_> = host
+                        .store
+                        .data()
+                        .heap_timeline
+                        .lock()
+                        .unwrap()
+                        .samples()
+                        .copied()
+                        .collect();
+                    widgets::render_heap(f, area, &samples, &theme);
+                }
+                if show_profile {
+                    widgets::render_profile(f, area, &host.profile_report(), &theme);
+                }
+                if show_chat {
+                    widgets::render_chat(
+                        f,
+                        area,
+                        &chat_lines,
+                        chat_input.as_ref().map(ChatInput::text),
+                        &theme,
+                    );
+                }
+                if let Some(crashed) = &crashed {
+                    widgets::render_crash(f, area, crashed, &theme);
+                }
+            })?;
+            host.store.data().metrics.lock().unwrap().frame_rendered();
+            if let Some(rec) = recorder.as_mut() {
+                rec.frame(export::to_ansi_screen(
+                    &frame.as_ref(),
+                    &theme,
+                    &image_store_guard,
+                ))?;
+            }
+            if let Some(spectators) = spectators.as_mut() {
+                spectators.broadcast(&frame.as_ref(), &image_store_guard);
+            }
+            if let Some(narrator) = narrator.as_mut() {
+                narrator.update(&compositor.focused().frame.as_ref(), &image_store_guard)?;
+            }
+            drop(image_store_guard);
+
+            // --- Inline Images ---
+            let area = terminal.size()?;
+            let placements =
+                images::collect_placements(&frame.cells, frame.width, area.width, area.height);
+            image_store
+                .lock()
+                .unwrap()
+                .render(terminal.backend_mut(), graphics, placements)?;
+        }
+        Ok(())
+    })();
 
     // --- Cleanup ---
+    // The terminal comes back first, so a failure below still leaves a usable shell
     disable_raw_mode()?;
-    execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    execute!(
+        std::io::stdout(),
+        DisableBracketedPaste,
+        LeaveAlternateScreen
+    )?;
+    let recorded = recorder.map_or(Ok(()), |rec| rec.finish());
+    if let Some(path) = &args.trace_calls {
+        write_call_graph(path, &host);
+    }
+    if let Some(path) = &args.heap_timeline {
+        write_heap_timeline(path, &host);
+    }
+    // Saves still on their way to a remote backend
+    if let Some(saves) = &host.store.data().saves {
+        saves.flush();
+    }
+    if let Err(e) = result {
+        write_crash_bundle(&e, &host, &inputs, &config_text);
+        return Err(e);
+    }
+    recorded?;
     println!("👋 GridEmbedder Exited.");
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,162 @@
+// Audit logs (host/audit.rs): links and cross-plugin calls, appended as JSON lines.
+
+use bus_protocol::Envelope;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::bus::deliver;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+const GAME: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "get") (param i32 i32) (result i64) i64.const 0)
+  (func (export "fail") (param i32 i32) (result i64) unreachable)
+  (func (export "deny") (param i32 i32) (result i64) i64.const 0x8000000000000000)
+  (func (export "on_message") (param i32 i32)))
+"#;
+
+// Calls game::<the name at `at`, `len` bytes into its slot> with a 5 byte payload, or links
+// game::get and calls it through its table with a 7 byte one
+const UI: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "__indirect_function_table" (table 1024 funcref))
+  (import "env" "host_link_call" (func $link (param i32 i32 i32 i32) (result i32)))
+  (import "env" "call" (func $call (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (type $export (func (param i32 i32) (result i64)))
+  (data (global.get $base) "gamegetfaildenynope")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "call") (param $at i32) (param $len i32) (result i64)
+    (call $call
+      (global.get $base) (i32.const 4)
+      (i32.add (global.get $base) (local.get $at)) (local.get $len)
+      (global.get $base) (i32.const 5)))
+  (func (export "link") (param $at i32) (param $len i32) (result i64)
+    (call_indirect (type $export)
+      (global.get $base) (i32.const 7)
+      (call $link
+        (global.get $base) (i32.const 4)
+        (i32.add (global.get $base) (local.get $at)) (local.get $len)))))
+"#;
+
+fn host(audit_log: Option<PathBuf>) -> BlindHost {
+    let config = BlindHostConfig {
+        audit_log,
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    host.load_plugin("ui", UI.as_bytes()).unwrap();
+    host
+}
+
+fn ui(host: &mut BlindHost, export: &str, name: (i32, i32)) -> anyhow::Result<i64> {
+    let func = host.get_func("ui", export).unwrap();
+    host.profiled("ui", export, |store| {
+        func.typed::<(i32, i32), i64>(&*store)?.call(store, name)
+    })
+}
+
+fn entries(path: &Path) -> Vec<Value> {
+    let log = std::fs::read_to_string(path).unwrap();
+    log.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+// event, callee::export, payload, status
+fn summary(entry: &Value) -> (String, String, Option<u64>, String) {
+    let text = |key: &str| entry[key].as_str().unwrap().to_string();
+    let export = format!("{}::{}", text("callee"), text("export"));
+    (
+        text("event"),
+        export,
+        entry["payload"].as_u64(),
+        text("status"),
+    )
+}
+
+#[test]
+fn links_calls_and_messages_are_appended_with_what_came_of_them() {
+    let path = std::env::temp_dir().join(format!("audit-test-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut host = host(Some(path.clone()));
+
+    assert_eq!(ui(&mut host, "call", (4, 3)).unwrap(), 0);
+    assert!(ui(&mut host, "call", (7, 4)).is_err());
+    assert!(ui(&mut host, "call", (11, 4)).unwrap() < 0);
+    assert!(
+        ui(&mut host, "call", (15, 4)).is_err(),
+        "there's no game::nope"
+    );
+    assert_eq!(ui(&mut host, "link", (4, 3)).unwrap(), 0);
+    assert!(ui(&mut host, "link", (15, 4)).is_err());
+    let message = Envelope {
+        sender: "ui".into(),
+        ..Envelope::event(vec![1, 2, 3])
+    };
+    let message_len = message.encode().len() as u64;
+    host.store
+        .data()
+        .bus
+        .lock()
+        .unwrap()
+        .push("game".into(), message);
+    assert_eq!(deliver(&mut host).unwrap(), 1);
+
+    let entries = entries(&path);
+    let expected = [
+        ("call", "game::get", Some(5), "ok"),
+        ("call", "game::fail", Some(5), "trapped"),
+        ("call", "game::deny", Some(5), "error"),
+        ("call", "game::nope", Some(5), "refused"),
+        ("link", "game::get", None, "ok"),
+        ("linked", "game::get", Some(7), "ok"),
+        ("link", "game::nope", None, "refused"),
+        ("message", "game::on_message", Some(message_len), "ok"),
+    ];
+    let got: Vec<_> = entries.iter().map(summary).collect();
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(event, export, payload, status)| {
+            (
+                event.to_string(),
+                export.to_string(),
+                *payload,
+                status.to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(got, expected);
+    assert!(entries
+        .iter()
+        .all(|entry| entry["caller"] == "ui" && entry["at"].as_u64().unwrap() > 0));
+
+    // A later host appends to what's there
+    drop(host);
+    let mut host = self::host(Some(path.clone()));
+    ui(&mut host, "call", (4, 3)).unwrap();
+    assert_eq!(self::entries(&path).len(), expected.len() + 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn hosts_without_an_audit_log_write_none() {
+    let mut host = host(None);
+    assert!(host.store.data().audit.is_none());
+    assert_eq!(ui(&mut host, "call", (4, 3)).unwrap(), 0);
+    assert!(
+        BlindHost::new(
+            BlindHostConfig {
+                audit_log: Some(std::env::temp_dir().join("no-such-dir").join("audit.jsonl")),
+                ..Default::default()
+            },
+            |_, _| Ok(())
+        )
+        .is_err(),
+        "an audit log that can't be opened fails the host"
+    );
+}
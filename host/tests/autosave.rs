@@ -0,0 +1,137 @@
+// Autosaves (embedder/autosave.rs): plugins' autosave exports, written to their autosave slot.
+
+use host::embedder::autosave::{autosave, Autosave, AUTOSAVE_SLOT};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::logger::{Level, LogConfig, Sink};
+use host::host_calls::save_backend::{LocalDir, SaveBackend};
+use host::host_calls::saves::{SaveStore, SaveVersion};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Answers the 5 bytes at the start of its slot
+const SAVER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (data (global.get $base) "lvl 3")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "autosave") (result i64)
+    global.get $base i64.extend_i32_u i64.const 0x500000000 i64.or))
+"#;
+
+const NOTHING_YET: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "autosave") (result i64) i64.const 0))
+"#;
+
+const TRAPS: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "autosave") (result i64) unreachable))
+"#;
+
+const NO_EXPORT: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1))
+"#;
+
+fn host(saves_dir: Option<PathBuf>) -> BlindHost {
+    let config = BlindHostConfig {
+        max_plugins: 4,
+        save_backend: saves_dir.map(|dir| Arc::new(LocalDir::new(dir)) as Arc<dyn SaveBackend>),
+        log: LogConfig {
+            sinks: Some(vec![Sink::Pane]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("traps", TRAPS.as_bytes()).unwrap();
+    host.load_plugin("saver", SAVER.as_bytes()).unwrap();
+    host.load_plugin("nothing-yet", NOTHING_YET.as_bytes())
+        .unwrap();
+    host.load_plugin("no-export", NO_EXPORT.as_bytes()).unwrap();
+    host
+}
+
+#[test]
+fn plugins_with_something_to_save_are_saved_past_one_that_traps() {
+    let dir = std::env::temp_dir().join(format!("autosave-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut host = host(Some(dir.clone()));
+    assert_eq!(autosave(&mut host, false), 1);
+
+    let store = SaveStore::new(&dir);
+    assert_eq!(
+        store
+            .load("saver", AUTOSAVE_SLOT, SaveVersion::default())
+            .unwrap(),
+        Some(b"lvl 3".to_vec())
+    );
+    for plugin in ["traps", "nothing-yet", "no-export"] {
+        assert_eq!(
+            store
+                .load(plugin, AUTOSAVE_SLOT, SaveVersion::default())
+                .unwrap(),
+            None
+        );
+    }
+    let warnings: Vec<_> = host
+        .recent_logs(10)
+        .into_iter()
+        .filter(|line| line.level == Level::Warn)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].text.contains("'traps'"), "{}", warnings[0].text);
+
+    // Each autosave is the slot's next version
+    assert_eq!(autosave(&mut host, false), 1);
+    assert_eq!(
+        store.info("saver", AUTOSAVE_SLOT).unwrap().unwrap().version,
+        2
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn autosaves_wait_their_turn_and_need_saves() {
+    let mut host = host(None);
+    assert_eq!(Autosave::new(Duration::ZERO).tick(&mut host), 0);
+    assert!(
+        host.recent_logs(10).is_empty(),
+        "plugins aren't asked while saves are off"
+    );
+
+    let mut autosave = Autosave::new(Duration::from_secs(3600));
+    assert!(!autosave.due());
+    assert_eq!(autosave.tick(&mut host), 0);
+}
+
+#[test]
+fn journaled_autosaves_go_through_the_journal() {
+    let dir = std::env::temp_dir().join(format!("autosave-journal-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut host = host(Some(dir.clone()));
+    assert_eq!(autosave(&mut host, true), 1);
+    assert_eq!(autosave(&mut host, true), 1);
+
+    let store = SaveStore::new(&dir);
+    assert_eq!(
+        store
+            .load("saver", AUTOSAVE_SLOT, SaveVersion::default())
+            .unwrap(),
+        Some(b"lvl 3".to_vec())
+    );
+    // Nothing changed, so the second one didn't need a full save
+    assert_eq!(
+        store.info("saver", AUTOSAVE_SLOT).unwrap().unwrap().version,
+        1
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
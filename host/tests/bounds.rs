@@ -0,0 +1,237 @@
+// The bounds check on guest pointers (host_calls/bounds.rs), through host_assert: a failing
+// assertion's message is read from whatever range the plugin passes. Then through every other
+// host call taking pointers, and host_dealloc's check on whose block it is.
+
+mod common;
+
+use common::slot_of;
+use host::embedder::images::{self, ImageStore};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::allocator::{alloc_shared, free_shared};
+use host::host_calls::files::{self, FileAccess};
+use host::host_calls::replicate::{self, Replicas};
+use host::host_calls::storage::{self, FileStorage};
+use host::host_calls::sync::{self, SyncClient};
+use std::sync::{Arc, Mutex};
+
+const ASSERTS: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_assert" (func $assert (param i32 i32 i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "fail") (param i32 i32)
+    i32.const 0 local.get 0 local.get 1 call $assert))
+"#;
+
+fn host() -> BlindHost {
+    common::host(&[("a", ASSERTS), ("b", ASSERTS)])
+}
+
+// Writes `message` at `ptr`, then has `plugin` fail an assertion with it
+fn fail(host: &mut BlindHost, plugin: &str, ptr: i32, message: &[u8]) -> anyhow::Result<()> {
+    host.write_mem(ptr, message)?;
+    let fail = host
+        .get_func(plugin, "fail")?
+        .typed::<(i32, i32), ()>(&host.store)?;
+    fail.call(&mut host.store, (ptr, message.len() as i32))
+}
+
+#[test]
+fn ranges_in_the_callers_slot_or_a_live_allocation_pass() {
+    let mut host = host();
+    let ptr = slot_of(&host, "a") + 64;
+    fail(&mut host, "a", ptr, b"in my slot").unwrap();
+
+    let ptr = alloc_shared(host.store.data(), 16);
+    fail(&mut host, "a", ptr, b"on the heap").unwrap();
+    // Anywhere inside the allocation, not just at its start
+    fail(&mut host, "a", ptr + 8, b"in it").unwrap();
+
+    let messages: Vec<String> = host
+        .take_assert_failures()
+        .into_iter()
+        .map(|f| f.message)
+        .collect();
+    assert_eq!(messages, ["in my slot", "on the heap", "in it"]);
+}
+
+#[test]
+fn ranges_anywhere_else_trap_in_every_build() {
+    let mut host = host();
+    let theirs = slot_of(&host, "b") + 64;
+    let allocation = alloc_shared(host.store.data(), 8);
+    let freed = alloc_shared(host.store.data(), 8);
+    free_shared(host.store.data(), freed, 8);
+
+    let cases = [
+        (theirs, &b"another plugin's slot"[..], "outside its slot"),
+        (
+            allocation,
+            &b"past the allocation"[..],
+            "past the end of the allocation",
+        ),
+        (freed, &b"freed"[..], "outside its slot"),
+    ];
+    for (ptr, message, error) in cases {
+        let trap = format!("{:?}", fail(&mut host, "a", ptr, message).expect_err(error));
+        assert!(trap.contains(error), "{}", trap);
+    }
+
+    let fail = host
+        .get_func("a", "fail")
+        .unwrap()
+        .typed::<(i32, i32), ()>(&host.store)
+        .unwrap();
+    let memory = host.store.data().shared_memory.data_size() as i32;
+    for (ptr, len, error) in [
+        (-4, 4, "negative"),
+        (memory - 2, 4, "past the end of memory"),
+        (0, 4, "outside"),
+    ] {
+        let trap = format!(
+            "{:?}",
+            fail.call(&mut host.store, (ptr, len)).expect_err(error)
+        );
+        assert!(trap.contains(error), "{}", trap);
+    }
+    // Empty ranges carry no bytes, so they pass wherever they point
+    fail.call(&mut host.store, (-4, 0)).unwrap();
+}
+
+// Every host call taking pointers, each through an export passing it (ptr, len)
+const CALLS: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_register_id" (func $register_id (param i32 i32 i32 i32) (result i32)))
+  (import "env" "host_intern" (func $intern (param i32 i32) (result i32)))
+  (import "env" "host_storage_set" (func $storage_set (param i32 i32 i32 i32) (result i32)))
+  (import "env" "host_file_write" (func $file_write (param i32 i32 i32 i32) (result i32)))
+  (import "env" "host_upload_image" (func $upload_image (param i32 i32 i32) (result i32)))
+  (import "env" "send_to_server" (func $send (param i32 i32)))
+  (import "env" "host_replicate" (func $replicate (param i32 i32 i32 i32) (result i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "ids") (param i32 i32)
+    (drop (call $register_id (local.get 0) (local.get 1) (local.get 0) (local.get 1))))
+  (func (export "strings") (param i32 i32)
+    (drop (call $intern (local.get 0) (local.get 1))))
+  (func (export "storage") (param i32 i32)
+    (drop (call $storage_set (local.get 0) (local.get 1) (local.get 0) (local.get 1))))
+  (func (export "files") (param i32 i32)
+    (drop (call $file_write (local.get 0) (local.get 1) (local.get 0) (local.get 1))))
+  (func (export "images") (param i32 i32)
+    (drop (call $upload_image (local.get 0) (local.get 0) (local.get 1))))
+  (func (export "sync") (param i32 i32)
+    (call $send (local.get 0) (local.get 1)))
+  (func (export "replicate") (param i32 i32)
+    (drop (call $replicate (local.get 0) (local.get 1) (local.get 0) (local.get 1)))))
+"#;
+
+const GROUPS: &[&str] = &[
+    "ids",
+    "strings",
+    "storage",
+    "files",
+    "images",
+    "sync",
+    "replicate",
+];
+
+#[test]
+fn every_host_call_group_traps_on_another_plugins_slot() {
+    let dir = common::scratch("bounds", "groups");
+    let images = Arc::new(Mutex::new(ImageStore::default()));
+    let storage = Arc::new(FileStorage::new(dir.join("storage")));
+    let files = Arc::new(FileAccess::new(dir.join("files")));
+    // Nothing listens there; the calls under test trap before anything is sent
+    let sync = Arc::new(SyncClient::new("http://127.0.0.1:9").unwrap());
+    let replicas = Arc::new(Replicas::default());
+    let mut host = BlindHost::new(BlindHostConfig::default(), move |linker, plugin| {
+        images::register_host_calls(linker, plugin.to_string(), images.clone())?;
+        storage::register_host_calls(linker, plugin.to_string(), storage.clone())?;
+        files::register_host_calls(linker, plugin.to_string(), files.clone())?;
+        sync::register_host_calls(linker, plugin.to_string(), sync.clone())?;
+        replicate::register_host_calls(linker, plugin.to_string(), replicas.clone())
+    })
+    .unwrap();
+    host.init_heap();
+    host.load_plugin("a", CALLS.as_bytes()).unwrap();
+    host.load_plugin("b", CALLS.as_bytes()).unwrap();
+
+    let theirs = slot_of(&host, "b") + 64;
+    for group in GROUPS {
+        let call = host
+            .get_func("a", group)
+            .unwrap()
+            .typed::<(i32, i32), ()>(&host.store)
+            .unwrap();
+        let trap = format!(
+            "{:?}",
+            call.call(&mut host.store, (theirs, 32)).expect_err(group)
+        );
+        assert!(
+            trap.contains("by 'a': outside its slot"),
+            "{}: {}",
+            group,
+            trap
+        );
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Allocates through host_alloc and frees through host_dealloc, as the plugin loading it
+const HEAP: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (import "env" "host_dealloc" (func $dealloc (param i32 i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "alloc") (param i32) (result i32) (call $alloc (local.get 0)))
+  (func (export "free") (param i32 i32) (call $dealloc (local.get 0) (local.get 1))))
+"#;
+
+#[test]
+fn host_dealloc_traps_on_another_plugins_block() {
+    let mut host = common::host(&[("a", HEAP), ("b", HEAP)]);
+    let alloc = host
+        .get_func("b", "alloc")
+        .unwrap()
+        .typed::<i32, i32>(&host.store)
+        .unwrap();
+    let block = alloc.call(&mut host.store, 16).unwrap();
+
+    let free = host
+        .get_func("a", "free")
+        .unwrap()
+        .typed::<(i32, i32), ()>(&host.store)
+        .unwrap();
+    let trap = format!(
+        "{:?}",
+        free.call(&mut host.store, (block, 16))
+            .expect_err("b's block")
+    );
+    assert!(trap.contains("which is 'b''s"), "{}", trap);
+    assert!(host
+        .store
+        .data()
+        .metrics
+        .lock()
+        .unwrap()
+        .live_block(block as u32)
+        .is_some());
+
+    // Its owner still can
+    let free = host
+        .get_func("b", "free")
+        .unwrap()
+        .typed::<(i32, i32), ()>(&host.store)
+        .unwrap();
+    free.call(&mut host.store, (block, 16)).unwrap();
+    assert!(host
+        .store
+        .data()
+        .metrics
+        .lock()
+        .unwrap()
+        .live_block(block as u32)
+        .is_none());
+}
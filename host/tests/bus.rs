@@ -0,0 +1,94 @@
+// The message bus (host_calls/bus.rs): a plugin failing on one message still gets the ones
+// after it, and messages to a plugin without on_message are logged.
+
+mod common;
+
+use bus_protocol::Envelope;
+use common::{host_with, slot_of};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::logger::{LogConfig, Sink};
+use host::host_calls::bus::deliver;
+
+// Counts its messages at the start of its slot, and traps on the second
+const PICKY: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "on_message") (param i32 i32)
+    (i32.store (global.get $base) (i32.add (i32.load (global.get $base)) (i32.const 1)))
+    (if (i32.eq (i32.load (global.get $base)) (i32.const 2)) (then unreachable))))
+"#;
+
+const DEAF: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1))
+"#;
+
+fn host() -> BlindHost {
+    let config = BlindHostConfig {
+        log: LogConfig {
+            sinks: Some(vec![Sink::Pane]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    host_with(config, &[("picky", PICKY), ("deaf", DEAF)])
+}
+
+fn send(host: &mut BlindHost, to: &str, payload: &[u8]) {
+    let envelope = Envelope {
+        correlation_id: 0,
+        kind: 0,
+        sender: "game".to_string(),
+        reply_to: None,
+        payload: payload.to_vec(),
+    };
+    host.store
+        .data()
+        .bus
+        .lock()
+        .unwrap()
+        .push(to.to_string(), envelope);
+}
+
+fn received(host: &mut BlindHost) -> u32 {
+    let base = slot_of(host, "picky");
+    u32::from_le_bytes(host.read_mem(base, 4).unwrap().try_into().unwrap())
+}
+
+#[test]
+fn a_trap_on_one_message_still_delivers_the_rest() {
+    let mut host = host();
+    for payload in [b"one", b"two", b"six"] {
+        send(&mut host, "picky", payload);
+    }
+    let error = deliver(&mut host).unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("'picky' failed to handle a message from 'game'"),
+        "{:#}",
+        error
+    );
+    assert_eq!(received(&mut host), 3);
+    assert!(host.store.data().bus.lock().unwrap().is_empty());
+
+    send(&mut host, "picky", b"ten");
+    assert_eq!(deliver(&mut host).unwrap(), 1);
+    assert_eq!(received(&mut host), 4);
+}
+
+#[test]
+fn a_message_to_a_plugin_without_on_message_is_logged() {
+    let mut host = host();
+    send(&mut host, "deaf", b"hello");
+    send(&mut host, "picky", b"one");
+    assert_eq!(deliver(&mut host).unwrap(), 1);
+    let logger = host.store.data().logger.lock().unwrap();
+    let line = logger.recent().last().expect("a logged line");
+    assert_eq!(line.plugin, "host");
+    assert_eq!(
+        line.text,
+        "'deaf' has no on_message, dropped a message from 'game'"
+    );
+}
@@ -0,0 +1,83 @@
+// The host's chat (host_calls/chat.rs): lines plugins post over the bus, the overlay's input
+// line (embedder/chat.rs), and the wire format (bus_protocol::chat). Split play carrying it is
+// in split.rs.
+
+use bus_protocol::chat::{ChatLine, CHAT, MAX_TEXT};
+use bus_protocol::Envelope;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use host::embedder::chat::{ChatInput, ChatKey};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::bus;
+use std::time::Duration;
+
+const PLUGIN: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1))
+"#;
+
+fn press(input: &mut ChatInput, code: KeyCode) -> ChatKey {
+    input.key(&KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+#[test]
+fn the_input_line_types_sends_and_closes() {
+    let mut input = ChatInput::default();
+    assert_eq!(
+        press(&mut input, KeyCode::Enter),
+        ChatKey::Close,
+        "nothing to send"
+    );
+    for c in "gg wpx".chars() {
+        assert_eq!(press(&mut input, KeyCode::Char(c)), ChatKey::Typing);
+    }
+    press(&mut input, KeyCode::Backspace);
+    input.key(&KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+    input.paste("\nplease");
+    assert_eq!(input.text(), "gg wp please");
+    assert_eq!(
+        press(&mut input, KeyCode::Enter),
+        ChatKey::Send("gg wp please".into())
+    );
+    assert_eq!(input.text(), "");
+
+    press(&mut input, KeyCode::Char('x'));
+    assert_eq!(press(&mut input, KeyCode::Esc), ChatKey::Close);
+}
+
+#[test]
+fn plugins_post_over_the_bus_under_their_name() {
+    let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("game", PLUGIN.as_bytes()).unwrap();
+    assert!(host.load_plugin(CHAT, PLUGIN.as_bytes()).is_err());
+
+    let mut envelope = Envelope::event(b"round\ttwo".to_vec());
+    envelope.sender = "game".into();
+    host.store
+        .data()
+        .bus
+        .lock()
+        .unwrap()
+        .push(CHAT.into(), envelope);
+    bus::deliver(&mut host).unwrap();
+
+    let mut chat = host.store.data().chat.lock().unwrap();
+    let posted = ChatLine::new("game", "round two");
+    assert_eq!(chat.recent(10, None), std::slice::from_ref(&posted));
+    assert_eq!(chat.recent(10, Some(Duration::ZERO)), []);
+    // Posted lines go to the other players, received ones don't go back out
+    assert_eq!(chat.take_outgoing(), [posted]);
+    chat.receive(ChatLine::new("ada", "hi"));
+    assert!(chat.take_outgoing().is_empty());
+    assert_eq!(chat.count(), 2);
+}
+
+#[test]
+fn lines_are_one_line_and_cut_short() {
+    let line = ChatLine::new("ada", &"é".repeat(MAX_TEXT));
+    assert_eq!(line.text.len(), MAX_TEXT);
+    assert_eq!(ChatLine::decode(&line.encode()), Some(line));
+    assert_eq!(ChatLine::new("ada", "a\r\nb").text, "a  b");
+    assert_eq!(ChatLine::decode(&[5, 0, b'a']), None);
+}
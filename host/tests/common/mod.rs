@@ -0,0 +1,39 @@
+// What the host's integration tests share. Each test file is a crate of its own and uses only
+// some of it, hence the allow.
+#![allow(dead_code)]
+
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use std::path::PathBuf;
+
+/// A host set up with `config` and its heap, with `plugins` (name, .wat) loaded in order.
+pub fn host_with(config: BlindHostConfig, plugins: &[(&str, &str)]) -> BlindHost {
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    for (name, wat) in plugins {
+        host.load_plugin(name, wat.as_bytes()).unwrap();
+    }
+    host
+}
+
+/// `host_with` the default config.
+pub fn host(plugins: &[(&str, &str)]) -> BlindHost {
+    host_with(BlindHostConfig::default(), plugins)
+}
+
+/// Where `plugin`'s slot starts; its first slot, for a reloaded one.
+pub fn slot_of(host: &BlindHost, plugin: &str) -> i32 {
+    host.store
+        .data()
+        .slots
+        .iter()
+        .find(|(name, _)| name == plugin)
+        .unwrap()
+        .1
+}
+
+/// A directory of `suite`'s own for `test`, gone until the test makes it.
+pub fn scratch(suite: &str, test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}-test-{}-{}", suite, test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
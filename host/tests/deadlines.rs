@@ -0,0 +1,91 @@
+// Tick deadlines (BlindHostConfig::tick_deadlines): a plugin whose tick runs away traps with
+// "tick budget exceeded" instead of freezing the host.
+
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::quotas::QuotaConfig;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Ticks forever when `delta` is negative
+const PLUGIN: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "tick") (param $delta f32)
+    (if (f32.lt (local.get $delta) (f32.const 0)) (then (loop $again br $again)))))
+"#;
+
+fn host(deadlines: &[(&str, u64)], quotas: QuotaConfig) -> BlindHost {
+    let config = BlindHostConfig {
+        tick_deadlines: deadlines
+            .iter()
+            .map(|(plugin, ms)| (plugin.to_string(), Duration::from_millis(*ms)))
+            .collect::<HashMap<_, _>>(),
+        quotas,
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("game", PLUGIN.as_bytes()).unwrap();
+    host.load_plugin("ui", PLUGIN.as_bytes()).unwrap();
+    host
+}
+
+fn tick(host: &mut BlindHost, plugin: &str, delta: f32) -> anyhow::Result<()> {
+    let func = host.get_func(plugin, "tick").unwrap();
+    host.profiled(plugin, "tick", |store| {
+        func.typed::<f32, ()>(&*store)?.call(store, delta)
+    })
+}
+
+#[test]
+fn runaway_ticks_trap_past_their_plugins_deadline() {
+    let mut host = host(&[("*", 500), ("game", 20)], QuotaConfig::default());
+    assert_eq!(
+        host.tick_deadline("game", "tick"),
+        Some(Duration::from_millis(20))
+    );
+    assert_eq!(
+        host.tick_deadline("ui", "tick"),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(
+        host.tick_deadline("game", "update"),
+        None,
+        "only ticks have one"
+    );
+    tick(&mut host, "game", 0.1).unwrap();
+
+    let start = Instant::now();
+    let error = tick(&mut host, "game", -1.0).unwrap_err();
+    assert!(
+        start.elapsed() < Duration::from_millis(400),
+        "{:?}",
+        start.elapsed()
+    );
+    let error = format!("{:#}", error);
+    assert!(
+        error.starts_with("'game' tick budget exceeded: still running after 20"),
+        "{}",
+        error
+    );
+
+    // The host goes on, and the plugin with it
+    tick(&mut host, "game", 0.1).unwrap();
+    tick(&mut host, "ui", 0.1).unwrap();
+    assert!(host.may_tick("game"), "a deadline isn't a quota strike");
+}
+
+#[test]
+fn a_tighter_time_quota_still_counts_as_a_strike() {
+    let quotas = QuotaConfig {
+        timeout: Some(Duration::from_millis(10)),
+        throttle: 5,
+        ..Default::default()
+    };
+    let mut host = host(&[("*", 1000)], quotas);
+    let error = format!("{:#}", tick(&mut host, "ui", -1.0).unwrap_err());
+    assert!(!error.contains("tick budget exceeded"), "{}", error);
+    host.end_tick(Duration::ZERO);
+    assert!(!host.may_tick("ui"));
+}
@@ -0,0 +1,34 @@
+// The delta codec split play sends replicated state with (delta.rs).
+
+use host::delta;
+
+#[test]
+fn deltas_carry_only_what_changed() {
+    let old = vec![0u8; 4096];
+    let mut new = old.clone();
+    new[10] = 1;
+    new[11] = 2;
+    new[4000] = 3;
+    let encoded = delta::encode(&old, &new).unwrap();
+    // Two runs: 4 bytes of header each, and the changed bytes
+    assert_eq!(encoded.len(), 4 + 2 + 4 + 1);
+    assert_eq!(delta::decode(&old, &encoded).unwrap(), new);
+
+    assert!(delta::encode(&old, &old).unwrap().is_empty());
+    assert_eq!(delta::decode(&old, &[]).unwrap(), old);
+}
+
+#[test]
+fn long_runs_and_mismatches() {
+    // Past what one run's u16 skip and length cover
+    let old = vec![7u8; 200_000];
+    let mut new = old.clone();
+    new[70_000..140_000].fill(9);
+    new[199_999] = 0;
+    let encoded = delta::encode(&old, &new).unwrap();
+    assert_eq!(delta::decode(&old, &encoded).unwrap(), new);
+
+    assert!(delta::encode(&old, &new[1..]).is_none());
+    assert!(delta::decode(&old[..10], &encoded).is_none());
+    assert!(delta::decode(&old, &encoded[..3]).is_none());
+}
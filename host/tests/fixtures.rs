@@ -0,0 +1,189 @@
+// End to end: the plugins in tests/fixtures, built for wasm32-unknown-unknown the first time a
+// test needs them (`cargo +nightly build`, as the Makefile builds plugins), loaded through
+// BlindHost like any other.
+//
+// SKIP_FIXTURES=1 skips these tests on machines without the nightly toolchain and rust-src.
+
+use fat_ptr::envelope::{self, STATUS_TRAPPED};
+use fat_ptr::FatPtr;
+use host::embedder::headless::InputScript;
+use host::embedder::simulate::{simulate, simulate_with, SimulationOptions};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+// Builds every fixture once per test binary and returns where the .wasm files are
+fn build_fixtures() -> &'static Path {
+    static BUILT: OnceLock<PathBuf> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixtures");
+        let mut cargo = Command::new("cargo");
+        cargo
+            .args([
+                "+nightly",
+                "build",
+                "--release",
+                "--target",
+                "wasm32-unknown-unknown",
+            ])
+            .current_dir(fixtures_dir())
+            .env("CARGO_TARGET_DIR", &target_dir);
+        // What the outer cargo set for the host's own build
+        for var in [
+            "RUSTC",
+            "RUSTC_WRAPPER",
+            "RUSTFLAGS",
+            "CARGO_ENCODED_RUSTFLAGS",
+            "RUSTUP_TOOLCHAIN",
+        ] {
+            cargo.env_remove(var);
+        }
+        let output = cargo
+            .output()
+            .expect("Failed to run cargo for the fixtures");
+        assert!(
+            output.status.success(),
+            "Fixtures didn't build (SKIP_FIXTURES=1 skips these tests):\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        target_dir.join("wasm32-unknown-unknown/release")
+    })
+}
+
+fn skip() -> bool {
+    std::env::var_os("SKIP_FIXTURES").is_some()
+}
+
+/// A host with the fixtures loaded under these names, provider first.
+fn host_with(plugins: &[(&str, &str)]) -> BlindHost {
+    let dir = build_fixtures();
+    let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    for (name, fixture) in plugins {
+        let wasm = std::fs::read(dir.join(format!("fixture_{}.wasm", fixture))).unwrap();
+        host.load_plugin(name, &wasm).unwrap();
+    }
+    host
+}
+
+fn call<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(
+    host: &mut BlindHost,
+    plugin: &str,
+    export: &str,
+    args: P,
+) -> R {
+    let func = host
+        .get_func(plugin, export)
+        .unwrap()
+        .typed::<P, R>(&host.store)
+        .unwrap();
+    func.call(&mut host.store, args).unwrap()
+}
+
+#[test]
+fn call_by_name_returns_the_providers_response() {
+    if skip() {
+        return;
+    }
+    let mut host = host_with(&[("provider", "provider"), ("caller", "caller")]);
+    let packed: i64 = call(&mut host, "caller", "call_double", ());
+    let response = FatPtr::unpack(packed);
+    assert_eq!(
+        host.read_mem(response.ptr, response.len).unwrap(),
+        b"hheelllloo"
+    );
+}
+
+#[test]
+fn linked_exports_run_through_the_table() {
+    if skip() {
+        return;
+    }
+    let mut host = host_with(&[("provider", "provider"), ("caller", "caller")]);
+    let sum: i64 = call(&mut host, "caller", "linked_add", (40, 2));
+    assert_eq!(sum, 42);
+    let sum: i64 = call(&mut host, "caller", "linked_add", (-1, 1));
+    assert_eq!(sum, 0);
+}
+
+#[test]
+fn checked_links_turn_traps_into_error_envelopes() {
+    if skip() {
+        return;
+    }
+    let mut host = host_with(&[("provider", "provider"), ("caller", "caller")]);
+    let packed: i64 = call(&mut host, "caller", "linked_fail", ());
+    assert!(envelope::is_error(packed));
+    let error = FatPtr::unpack(packed & !envelope::ERROR_BIT);
+    let bytes = host.read_mem(error.ptr, error.len).unwrap();
+    assert_eq!(envelope::CallError::decode(&bytes).status, STATUS_TRAPPED);
+}
+
+#[test]
+fn every_plugin_has_statics_of_its_own() {
+    if skip() {
+        return;
+    }
+    let mut host = host_with(&[
+        ("provider", "provider"),
+        ("first", "caller"),
+        ("second", "caller"),
+    ]);
+    assert_eq!(call::<(), i32>(&mut host, "first", "bump", ()), 1);
+    assert_eq!(call::<(), i32>(&mut host, "first", "bump", ()), 2);
+    assert_eq!(call::<(), i32>(&mut host, "second", "bump", ()), 1);
+}
+
+#[test]
+fn host_alloc_hands_out_heap_memory_that_can_be_reused() {
+    if skip() {
+        return;
+    }
+    let mut host = host_with(&[("provider", "provider"), ("caller", "caller")]);
+    let heap_start = host.store.data().heap_start_address;
+
+    let first: i32 = call(&mut host, "caller", "alloc_filled", (64, 0xAB));
+    let second: i32 = call(&mut host, "caller", "alloc_filled", (64, 0xCD));
+    assert!(first >= heap_start && second >= heap_start);
+    assert!(
+        first + 64 <= second || second + 64 <= first,
+        "{} and {} overlap",
+        first,
+        second
+    );
+    assert_eq!(host.read_mem(first, 64).unwrap(), [0xAB; 64]);
+    assert_eq!(host.read_mem(second, 64).unwrap(), [0xCD; 64]);
+
+    call::<(i32, i32), ()>(&mut host, "caller", "free", (first, 64));
+    let third: i32 = call(&mut host, "caller", "alloc_filled", (64, 0xEF));
+    assert_eq!(third, first);
+    assert_eq!(host.read_mem(second, 64).unwrap(), [0xCD; 64]);
+}
+
+#[test]
+fn simulations_repeat_exactly_given_the_same_input_and_seed() {
+    if skip() {
+        return;
+    }
+    let driver = build_fixtures().join("fixture_driver.wasm");
+    let input = InputScript::parse("1 h\n2 i\n5 Shift+!").unwrap();
+
+    let first = simulate(&driver, 8, &input).unwrap();
+    assert_eq!(simulate(&driver, 8, &input).unwrap(), first);
+
+    let other_input = InputScript::parse("1 h\n2 o\n5 Shift+!").unwrap();
+    assert_ne!(simulate(&driver, 8, &other_input).unwrap().grid, first.grid);
+    let reseeded = SimulationOptions {
+        seed: 7,
+        ..Default::default()
+    };
+    assert_ne!(
+        simulate_with(&driver, 8, &input, &reseeded).unwrap().grid,
+        first.grid
+    );
+}
@@ -0,0 +1,82 @@
+#![no_std]
+
+// Drives the host calls tests/fixtures.rs checks, against the provider fixture.
+
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn host_alloc(size: i32) -> i32;
+    fn host_dealloc(ptr: i32, size: i32);
+    fn host_link_call(module_ptr: i32, module_len: i32, func_ptr: i32, func_len: i32) -> i32;
+    fn host_link_call_checked(module_ptr: i32, module_len: i32, func_ptr: i32, func_len: i32) -> i32;
+    fn call(module_ptr: i32, module_len: i32, func_ptr: i32, func_len: i32, arg0: i32, arg1: i32) -> i64;
+}
+
+const PROVIDER: &str = "provider";
+
+static mut COUNTER: i32 = 0;
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[no_mangle]
+pub extern "C" fn __abi_version() -> i32 {
+    1
+}
+
+// A table index is the function pointer
+unsafe fn link(export: &str, checked: bool) -> extern "C" fn(i32, i32) -> i64 {
+    let link = if checked { host_link_call_checked } else { host_link_call };
+    let idx = link(PROVIDER.as_ptr() as i32, PROVIDER.len() as i32, export.as_ptr() as i32, export.len() as i32);
+    core::mem::transmute(idx as usize)
+}
+
+/// provider::double(b"hello") through `call`: the provider's packed response.
+#[no_mangle]
+pub unsafe extern "C" fn call_double() -> i64 {
+    let export = "double";
+    let payload = b"hello";
+    call(
+        PROVIDER.as_ptr() as i32,
+        PROVIDER.len() as i32,
+        export.as_ptr() as i32,
+        export.len() as i32,
+        payload.as_ptr() as i32,
+        payload.len() as i32,
+    )
+}
+
+/// provider::add(a, b), linked into this plugin's table.
+#[no_mangle]
+pub unsafe extern "C" fn linked_add(a: i32, b: i32) -> i64 {
+    link("add", false)(a, b)
+}
+
+/// provider::fail, linked checked: the host's error envelope instead of a trap.
+#[no_mangle]
+pub unsafe extern "C" fn linked_fail() -> i64 {
+    link("fail", true)(0, 0)
+}
+
+/// Counts calls in a static, which every loaded copy of this plugin has its own of.
+#[no_mangle]
+pub unsafe extern "C" fn bump() -> i32 {
+    COUNTER += 1;
+    COUNTER
+}
+
+/// `size` bytes of `byte` from host_alloc.
+#[no_mangle]
+pub unsafe extern "C" fn alloc_filled(size: i32, byte: i32) -> i32 {
+    let ptr = host_alloc(size);
+    if ptr != 0 {
+        core::ptr::write_bytes(ptr as usize as *mut u8, byte as u8, size as usize);
+    }
+    ptr
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn free(ptr: i32, size: i32) {
+    host_dealloc(ptr, size)
+}
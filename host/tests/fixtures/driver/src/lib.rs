@@ -0,0 +1,60 @@
+#![no_std]
+
+// A grid driver for simulate(): types keys into an 8x2 grid, each in a color host_random
+// picks, so runs only repeat when input and seed do.
+
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn host_random() -> i64;
+}
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 2;
+
+// GridCell: character, fg, bg, style
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cell(u32, u8, u8, u16);
+
+static mut GRID: [Cell; WIDTH * HEIGHT] = [Cell(' ' as u32, 7, 0, 0); WIDTH * HEIGHT];
+static mut CURSOR: usize = 0;
+static mut INPUT: *const u32 = core::ptr::null();
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[no_mangle]
+pub extern "C" fn __abi_version() -> i32 {
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_input(ptr: i32) {
+    INPUT = ptr as usize as *const u32;
+}
+
+#[no_mangle]
+pub extern "C" fn set_tickrate(_: f32) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn tick(_: f32) {
+    // GridInput: input_type, key_code, ...; 1 is a key
+    if INPUT.is_null() || *INPUT != 1 {
+        return;
+    }
+    let color = (host_random() as u64 % 256) as u8;
+    GRID[CURSOR] = Cell(*INPUT.add(1), color, 0, 0);
+    CURSOR = (CURSOR + 1) % (WIDTH * HEIGHT);
+}
+
+#[no_mangle]
+pub extern "C" fn get_grid_dimensions() -> i64 {
+    ((WIDTH as i64) << 32) | HEIGHT as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_grid_ptr() -> i32 {
+    core::ptr::addr_of!(GRID) as i32
+}
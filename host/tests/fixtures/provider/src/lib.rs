@@ -0,0 +1,41 @@
+#![no_std]
+
+// What the caller fixture reaches: through `call` by name, and linked into its table.
+
+#[link(wasm_import_module = "env")]
+extern "C" {
+    fn host_alloc(size: i32) -> i32;
+}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    core::arch::wasm32::unreachable()
+}
+
+#[no_mangle]
+pub extern "C" fn __abi_version() -> i32 {
+    1
+}
+
+/// The payload with every byte twice (b"ab" -> b"aabb"), in a host_alloc'd response.
+#[no_mangle]
+pub unsafe extern "C" fn double(ptr: i32, len: i32) -> i64 {
+    let out = host_alloc(len * 2);
+    let payload = core::slice::from_raw_parts(ptr as usize as *const u8, len as usize);
+    let response = core::slice::from_raw_parts_mut(out as usize as *mut u8, len as usize * 2);
+    for (idx, byte) in payload.iter().enumerate() {
+        response[idx * 2] = *byte;
+        response[idx * 2 + 1] = *byte;
+    }
+    ((len as i64 * 2) << 32) | out as u32 as i64
+}
+
+#[no_mangle]
+pub extern "C" fn add(a: i32, b: i32) -> i64 {
+    a as i64 + b as i64
+}
+
+#[no_mangle]
+pub extern "C" fn fail(_: i32, _: i32) -> i64 {
+    core::arch::wasm32::unreachable()
+}
@@ -0,0 +1,139 @@
+use grid_protocol::GridCell;
+use host::embedder::driver::DriverHandle;
+use host::embedder::export::FrameRef;
+use host::embedder::golden::{self, GoldenFrames};
+use host::embedder::headless::{self, HeadlessOptions, InputScript};
+use host::embedder::images::ImageStore;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use std::path::Path;
+
+// A 4x1 driver typing each key into the next cell, colored by position
+const ECHO_DRIVER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (global $input (mut i32) (i32.const 0))
+  (global $pos (mut i32) (i32.const 0))
+  (data (i32.const 3000) "\20\00\00\00\07\00\00\00\20\00\00\00\07\00\00\00\20\00\00\00\07\00\00\00\20\00\00\00\07\00\00\00")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "set_input") (param i32) local.get 0 global.set $input)
+  (func (export "set_tickrate") (param f32))
+  (func (export "get_grid_dimensions") (result i64) i64.const 0x400000001)
+  (func (export "get_grid_ptr") (result i32) i32.const 3000)
+  (func (export "tick") (param f32) (local $cell i32)
+    global.get $input
+    i32.eqz
+    if return end
+    global.get $input
+    i32.load
+    i32.const 1
+    i32.ne
+    if return end
+    i32.const 3000
+    global.get $pos
+    i32.const 8
+    i32.mul
+    i32.add
+    local.set $cell
+    local.get $cell
+    global.get $input
+    i32.load offset=4
+    i32.store
+    local.get $cell
+    global.get $pos
+    i32.const 1
+    i32.add
+    i32.store8 offset=4
+    global.get $pos
+    i32.const 1
+    i32.add
+    i32.const 4
+    i32.rem_u
+    global.set $pos))
+"#;
+
+fn echo_driver() -> (BlindHost, DriverHandle) {
+    let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("echo", ECHO_DRIVER.as_bytes()).unwrap();
+    let driver = DriverHandle::bind(&mut host, "echo").unwrap();
+    (host, driver)
+}
+
+#[test]
+fn echo_driver_matches_its_golden_frames() {
+    let (mut host, driver) = echo_driver();
+    let script = InputScript::parse("1 H\n2 i\n4 Shift+!").unwrap();
+    let options = HeadlessOptions {
+        ticks: 4,
+        every: Some(2),
+        ..Default::default()
+    };
+
+    let images = ImageStore::default();
+    let mut golden =
+        GoldenFrames::from_env(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/echo"));
+    headless::play(&mut host, &driver, &script, &options, |tick, frame| {
+        golden.check(tick, &golden::snapshot(&frame, &images))
+    })
+    .unwrap();
+    golden.finish().unwrap();
+}
+
+#[test]
+fn snapshots_name_each_attribute_combination() {
+    let cell = |character: char, fg_color, style| GridCell {
+        character: character as u32,
+        fg_color,
+        bg_color: 0,
+        style,
+    };
+    let cells = [
+        cell('o', 7, 0),
+        cell('k', 7, 0),
+        cell('!', 1, 1),
+        cell(' ', 7, 0),
+    ];
+    let frame = FrameRef {
+        cells: &cells,
+        width: 2,
+        height: 2,
+    };
+
+    let snapshot = golden::snapshot(&frame, &ImageStore::default());
+    assert_eq!(
+        snapshot,
+        "ok\n! \n--- attributes ---\naa\nba\n--- legend ---\na fg 7 bg 0 style 0\nb fg 1 bg 0 style 1\n"
+    );
+}
+
+#[test]
+fn diffs_point_at_changed_columns() {
+    assert_eq!(golden::diff("ab\ncd\n", "ab\ncd\n"), None);
+    assert_eq!(
+        golden::diff("ab\ncd\n", "ab\nxd\nef\n").unwrap(),
+        "line 2:\n  - |cd|\n  + |xd|\n     ^\nline 3:\n  + |ef|\n"
+    );
+}
+
+#[test]
+fn mismatches_fail_unless_updating() {
+    let dir = std::env::temp_dir().join(format!("golden-test-{}", std::process::id()));
+    let mut golden = GoldenFrames::new(&dir, false);
+    // Missing snapshots are written
+    golden.check(1, "old\n").unwrap();
+    golden.finish().unwrap();
+
+    let mut golden = GoldenFrames::new(&dir, false);
+    golden.check(1, "new\n").unwrap();
+    let error = golden.finish().unwrap_err().to_string();
+    assert!(error.contains("  - |old|\n  + |new|"), "{}", error);
+
+    let mut golden = GoldenFrames::new(&dir, true);
+    golden.check(1, "new\n").unwrap();
+    golden.finish().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("frame-1.txt")).unwrap(),
+        "new\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
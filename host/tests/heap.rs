@@ -0,0 +1,163 @@
+// Properties of the host heap under random alloc/free sequences, checked after every step:
+// live blocks never overlap, free blocks are sorted and fully coalesced, nothing is handed
+// out below the heap's start, and no byte goes missing. proptest shrinks a failing sequence
+// before reporting it.
+
+use host::allocator::HostHeap;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::allocator::{alloc_shared, free_shared};
+use proptest::prelude::*;
+
+const CASES: u32 = 256;
+const STEPS: usize = 200;
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Alloc(u32),
+    // Index into the live blocks, modulo their count
+    Free(usize),
+}
+
+fn steps() -> impl Strategy<Value = Vec<Step>> {
+    // Mostly small, now and then larger than a free block may be
+    let size = prop_oneof![7 => 1..=512u32, 1 => 1..=(1u32 << 20)];
+    let step =
+        prop_oneof![2 => size.prop_map(Step::Alloc), 1 => any::<usize>().prop_map(Step::Free)];
+    prop::collection::vec(step, 0..=STEPS)
+}
+
+// Live blocks as (addr, size)
+fn check_blocks(heap: &HostHeap, live: &[(u32, u32)], start: u32, end: u32) -> Result<(), String> {
+    let mut blocks: Vec<(u32, u32, bool)> = live
+        .iter()
+        .map(|&(addr, size)| (addr, size, true))
+        .collect();
+    blocks.extend(
+        heap.free_blocks
+            .iter()
+            .map(|block| (block.addr, block.size, false)),
+    );
+    blocks.sort();
+
+    for pair in heap.free_blocks.windows(2) {
+        if pair[0].addr >= pair[1].addr {
+            return Err(format!("free blocks out of order: {:?}", heap.free_blocks));
+        }
+        if pair[0].addr + pair[0].size == pair[1].addr {
+            return Err(format!(
+                "free blocks {:?} and {:?} weren't coalesced",
+                pair[0], pair[1]
+            ));
+        }
+    }
+    for &(addr, size, _) in &blocks {
+        if addr < start || addr as u64 + size as u64 > end as u64 {
+            return Err(format!(
+                "block {}+{} outside the heap {}..{}",
+                addr, size, start, end
+            ));
+        }
+    }
+    for pair in blocks.windows(2) {
+        let ((addr, size, a_live), (next, _, b_live)) = (pair[0], pair[1]);
+        if addr + size > next {
+            let kind = |live| if live { "live" } else { "free" };
+            return Err(format!(
+                "{} {}+{} overlaps {} {}",
+                kind(a_live),
+                addr,
+                size,
+                kind(b_live),
+                next
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn heap_walk(steps: &[Step]) -> Result<(), String> {
+    const START: u32 = 65536;
+    const SIZE: u32 = 4 << 20;
+
+    let mut heap = HostHeap::new();
+    heap.dealloc(START, SIZE);
+    let mut live: Vec<(u32, u32)> = Vec::new();
+
+    for step in steps {
+        match *step {
+            Step::Alloc(size) => {
+                // Rounded as host_alloc rounds
+                let size = (size + 7) & !7;
+                if let Some(addr) = heap.alloc(size) {
+                    live.push((addr, size));
+                }
+            }
+            Step::Free(idx) if !live.is_empty() => {
+                let (addr, size) = live.swap_remove(idx % live.len());
+                heap.dealloc(addr, size);
+            }
+            Step::Free(_) => {}
+        }
+        check_blocks(&heap, &live, START, START + SIZE)?;
+
+        let free: u64 = heap.free_blocks.iter().map(|block| block.size as u64).sum();
+        let used: u64 = live.iter().map(|&(_, size)| size as u64).sum();
+        if free + used != SIZE as u64 {
+            return Err(format!(
+                "{} free + {} live bytes in a heap of {}",
+                free, used, SIZE
+            ));
+        }
+    }
+
+    // Everything back: one block again
+    for (addr, size) in live.drain(..) {
+        heap.dealloc(addr, size);
+    }
+    match heap.free_blocks.as_slice() {
+        [block] if block.addr == START && block.size == SIZE => Ok(()),
+        blocks => Err(format!("all freed, but the heap is {:?}", blocks)),
+    }
+}
+
+fn host_walk(steps: &[Step]) -> Result<(), String> {
+    let host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    let state = host.store.data();
+    let start = state.heap_start_address as u32;
+    let mut live: Vec<(u32, u32)> = Vec::new();
+
+    for step in steps {
+        match *step {
+            Step::Alloc(size) => {
+                let ptr = alloc_shared(state, size as i32);
+                if ptr != 0 {
+                    live.push((ptr as u32, (size + 7) & !7));
+                }
+            }
+            Step::Free(idx) if !live.is_empty() => {
+                let (ptr, size) = live.swap_remove(idx % live.len());
+                free_shared(state, ptr as i32, size as i32);
+            }
+            Step::Free(_) => {}
+        }
+        // The heap grows with memory, so its end moves
+        let end = state.shared_memory.data().len() as u32;
+        check_blocks(&state.heap.lock().unwrap(), &live, start, end)?;
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn heap_blocks_never_overlap_and_stay_coalesced(steps in steps()) {
+        heap_walk(&steps).map_err(TestCaseError::fail)?;
+    }
+
+    #[test]
+    fn host_allocations_stay_above_the_heap_start(steps in steps()) {
+        host_walk(&steps).map_err(TestCaseError::fail)?;
+    }
+}
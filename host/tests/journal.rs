@@ -0,0 +1,112 @@
+// Journaled saves (SaveStore::append in host_calls/saves.rs): deltas after a full save,
+// replayed on load and compacted into a full save once the journal outgrows the state.
+
+mod common;
+
+use common::scratch;
+use host::host_calls::saves::{SaveStore, SaveVersion, COMPACT_ENTRIES};
+use std::path::Path;
+
+const VERSION: SaveVersion = SaveVersion { abi: 1, schema: 0 };
+
+fn journal_len(dir: &Path) -> u64 {
+    std::fs::metadata(dir.join("game").join("world~journal.sav"))
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn saves_after_the_first_only_append_what_changed() {
+    let dir = scratch("journal", "deltas");
+    let store = SaveStore::new(&dir);
+    let mut world = vec![0u8; 64 * 1024];
+    assert!(
+        store.append("game", "world", VERSION, &world).unwrap(),
+        "the first one is in full"
+    );
+    for tick in 1..=10u8 {
+        world[tick as usize * 100] = tick;
+        assert!(!store.append("game", "world", VERSION, &world).unwrap());
+    }
+    assert!(journal_len(&dir) < 200, "{} bytes", journal_len(&dir));
+    assert_eq!(
+        store.load("game", "world", VERSION).unwrap(),
+        Some(world.clone())
+    );
+    assert_eq!(store.info("game", "world").unwrap().unwrap().version, 1);
+
+    // A world that grew goes in whole, and a restarted host reads the lot
+    world.extend_from_slice(b"new area");
+    store.append("game", "world", VERSION, &world).unwrap();
+    assert_eq!(
+        SaveStore::new(&dir).load("game", "world", VERSION).unwrap(),
+        Some(world)
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn journals_are_compacted_once_they_outgrow_the_state() {
+    let dir = scratch("journal", "compact");
+    let store = SaveStore::new(&dir);
+    store.append("game", "world", VERSION, &[0; 256]).unwrap();
+    // Every byte changes every time, so a few entries add up to more than the state
+    let mut compacted_at = None;
+    for tick in 1..COMPACT_ENTRIES {
+        if store
+            .append("game", "world", VERSION, &[tick as u8; 256])
+            .unwrap()
+        {
+            compacted_at = Some(tick);
+            break;
+        }
+    }
+    assert!(
+        compacted_at.is_some_and(|tick| tick < 5),
+        "{:?}",
+        compacted_at
+    );
+    assert_eq!(store.info("game", "world").unwrap().unwrap().version, 2);
+    assert_eq!(journal_len(&dir), 8, "a new journal, only its header");
+    let last = compacted_at.unwrap() as u8;
+    assert_eq!(
+        store.load("game", "world", VERSION).unwrap(),
+        Some(vec![last; 256])
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn torn_entries_and_stale_journals_are_left_out() {
+    let dir = scratch("journal", "torn");
+    let store = SaveStore::new(&dir);
+    let mut world = vec![0u8; 1024];
+    for tick in 1..=3 {
+        world[0] = tick;
+        store.append("game", "world", VERSION, &world).unwrap();
+    }
+
+    // A crash halfway through the last entry loses that one only
+    let path = dir.join("game").join("world~journal.sav");
+    let journal = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &journal[..journal.len() - 1]).unwrap();
+    world[0] = 2;
+    assert_eq!(store.load("game", "world", VERSION).unwrap(), Some(world));
+
+    // A full save since makes the journal stale, and the next journaled save starts over
+    store.save("game", "world", VERSION, b"menu").unwrap();
+    assert_eq!(
+        store.load("game", "world", VERSION).unwrap(),
+        Some(b"menu".to_vec())
+    );
+    assert!(store.append("game", "world", VERSION, b"tick 4").unwrap());
+    assert_eq!(
+        store.load("game", "world", VERSION).unwrap(),
+        Some(b"tick 4".to_vec())
+    );
+
+    store.delete("game", "world").unwrap();
+    assert!(!path.exists());
+    assert_eq!(store.load("game", "world", VERSION).unwrap(), None);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,121 @@
+// The host's lobby (host_calls/lobby.rs), asked over the bus by two plugins that keep the last
+// message they got.
+
+mod common;
+
+use bus_protocol::lobby::{
+    Event, Reply, Request, EVENT_CHANGED, EVENT_READY, LOBBY, STATUS_FULL, STATUS_NOT_MEMBER,
+    STATUS_NO_SESSION, STATUS_OK,
+};
+use bus_protocol::{Envelope, KIND_EVENT, KIND_REPLY};
+use common::slot_of;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::bus;
+
+// Keeps the length of the last message at base, and the message at base+8
+const KEEPER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "on_message") (param $ptr i32) (param $len i32)
+    global.get $base local.get $len i32.store
+    global.get $base i32.const 8 i32.add local.get $ptr local.get $len memory.copy))
+"#;
+
+fn host() -> BlindHost {
+    let config = BlindHostConfig {
+        max_plugins: 2,
+        ..Default::default()
+    };
+    common::host_with(config, &[("a", KEEPER), ("b", KEEPER)])
+}
+
+// `from`'s request, answered and delivered
+fn ask(host: &mut BlindHost, from: &str, request: Request) -> Reply {
+    let mut envelope = Envelope::request(1, request.encode());
+    envelope.sender = from.to_string();
+    host.store
+        .data()
+        .bus
+        .lock()
+        .unwrap()
+        .push(LOBBY.to_string(), envelope);
+    bus::deliver(host).unwrap();
+    bus::deliver(host).unwrap();
+    let reply = last(host, from);
+    assert_eq!(reply.kind, KIND_REPLY);
+    Reply::decode(&reply.payload).unwrap()
+}
+
+fn last(host: &mut BlindHost, plugin: &str) -> Envelope {
+    let base = slot_of(host, plugin);
+    let len = u32::from_le_bytes(host.read_mem(base, 4).unwrap().try_into().unwrap());
+    Envelope::decode(&host.read_mem(base + 8, len as i32).unwrap()).unwrap()
+}
+
+fn event(host: &mut BlindHost, plugin: &str) -> Event {
+    let event = last(host, plugin);
+    assert_eq!((event.kind, event.sender.as_str()), (KIND_EVENT, LOBBY));
+    Event::decode(&event.payload).unwrap()
+}
+
+#[test]
+fn players_create_join_and_ready_up() {
+    let mut host = host();
+    let created = ask(
+        &mut host,
+        "a",
+        Request::Create {
+            slots: 2,
+            name: "duel".into(),
+        },
+    );
+    assert_eq!(created.status, STATUS_OK);
+    let id = created.sessions[0].id;
+
+    let listed = ask(&mut host, "b", Request::List);
+    assert_eq!(listed.sessions.len(), 1);
+    assert_eq!(listed.sessions[0].name, "duel");
+    assert_eq!(listed.sessions[0].members().collect::<Vec<_>>(), ["a"]);
+
+    let joined = ask(&mut host, "b", Request::Join(id));
+    assert_eq!(joined.sessions[0].slot_of("b"), Some(1));
+    let changed = event(&mut host, "a");
+    assert_eq!(changed.event, EVENT_CHANGED);
+    assert_eq!(changed.session.members().collect::<Vec<_>>(), ["a", "b"]);
+
+    ask(&mut host, "a", Request::Ready(id, true));
+    assert_eq!(event(&mut host, "b").event, EVENT_CHANGED);
+    let ready = ask(&mut host, "b", Request::Ready(id, true));
+    assert!(ready.sessions[0].is_ready());
+    assert_eq!(event(&mut host, "a").event, EVENT_READY);
+}
+
+#[test]
+fn full_sessions_strangers_and_closing() {
+    let mut host = host();
+    let id = ask(
+        &mut host,
+        "a",
+        Request::Create {
+            slots: 1,
+            name: "solo".into(),
+        },
+    )
+    .sessions[0]
+        .id;
+    assert_eq!(ask(&mut host, "b", Request::Join(id)).status, STATUS_FULL);
+    assert_eq!(
+        ask(&mut host, "b", Request::Join(id + 1)).status,
+        STATUS_NO_SESSION
+    );
+    assert_eq!(
+        ask(&mut host, "b", Request::Leave(id)).status,
+        STATUS_NOT_MEMBER
+    );
+
+    assert_eq!(ask(&mut host, "a", Request::Leave(id)).status, STATUS_OK);
+    assert!(ask(&mut host, "b", Request::List).sessions.is_empty());
+    assert!(host.load_plugin(LOBBY, KEEPER.as_bytes()).is_err());
+}
@@ -0,0 +1,223 @@
+// Plugin manifests (host/manifest.rs): a plugin loaded with one links against what it allows.
+
+mod common;
+
+use host::host::host_object::BlindHost;
+use host::host::manifest::PluginManifest;
+
+const PROVIDER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "greet") (result i32) i32.const 42))
+"#;
+
+// Calls the provider and prints, so it needs both allowed
+const CONSUMER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "greet" (func $greet (result i32)))
+  (import "env" "host_print" (func $print (param i32 i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "run") (result i32)
+    global.get $base i32.const 0 call $print
+    call $greet))
+"#;
+
+fn host() -> BlindHost {
+    common::host(&[("provider", PROVIDER)])
+}
+
+fn manifest(text: &str) -> PluginManifest {
+    PluginManifest::parse(text).unwrap()
+}
+
+#[test]
+fn plugins_link_against_what_their_manifest_allows() {
+    let mut host = host();
+    let allowed = manifest(r#"imports = ["greet", "host_print"]"#);
+    host.load_plugin_with_manifest("consumer", CONSUMER.as_bytes(), &allowed)
+        .unwrap();
+    let run = host
+        .get_func("consumer", "run")
+        .unwrap()
+        .typed::<(), i32>(&host.store)
+        .unwrap();
+    assert_eq!(run.call(&mut host.store, ()).unwrap(), 42);
+
+    // A trailing * allows a whole family of names
+    let wildcards = manifest(r#"imports = ["gr*", "host_*"]"#);
+    host.load_plugin_with_manifest("consumer-2", CONSUMER.as_bytes(), &wildcards)
+        .unwrap();
+}
+
+#[test]
+fn imports_the_manifest_leaves_out_fail_the_load_naming_them() {
+    let mut host = host();
+    for (text, refused) in [
+        (r#"imports = ["host_print"]"#, "env::greet"),
+        ("", "env::greet, env::host_print"),
+        (r#"imports = ["greeting*"]"#, "env::greet, env::host_print"),
+    ] {
+        let error = host
+            .load_plugin_with_manifest("consumer", CONSUMER.as_bytes(), &manifest(text))
+            .unwrap_err();
+        let error = format!("{:#}", error);
+        assert!(
+            error.contains(&format!("doesn't allow: {}", refused)),
+            "{}",
+            error
+        );
+    }
+    assert!(!host.store.data().instances.contains_key("consumer"));
+    // Without one, everything's there as before
+    host.load_plugin("consumer", CONSUMER.as_bytes()).unwrap();
+}
+
+#[test]
+fn manifests_are_found_beside_the_wasm_and_checked_for_typos() {
+    let dir = std::env::temp_dir().join(format!("manifest-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let wasm = dir.join("consumer.wasm");
+    // wasmtime takes the text format too
+    std::fs::write(&wasm, CONSUMER).unwrap();
+
+    let mut host = host();
+    std::fs::write(dir.join("consumer.toml"), r#"imports = ["host_print"]"#).unwrap();
+    assert!(
+        host.load_plugin_file("consumer", &wasm).is_err(),
+        "greet isn't allowed"
+    );
+    std::fs::write(
+        dir.join("consumer.toml"),
+        r#"imports = ["host_print", "greet"]"#,
+    )
+    .unwrap();
+    host.load_plugin_file("consumer", &wasm).unwrap();
+
+    std::fs::write(dir.join("consumer.toml"), r#"import = ["greet"]"#).unwrap();
+    let error = format!("{:#}", PluginManifest::beside(&wasm).unwrap_err());
+    assert!(error.contains("unknown field `import`"), "{}", error);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Links provider::greet and calls it, with 19 bytes of static data
+const LINKER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "__indirect_function_table" (table 1024 funcref))
+  (import "env" "host_link_call" (func $link (param i32 i32 i32 i32) (result i32)))
+  (type $greet (func (result i32)))
+  (data (global.get $base) "hello!providergreet")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "run") (result i32)
+    (call_indirect (type $greet)
+      (call $link
+        (i32.add (global.get $base) (i32.const 6)) (i32.const 8)
+        (i32.add (global.get $base) (i32.const 14)) (i32.const 5)))))
+"#;
+
+fn plugin_dir(name: &str, manifest: &str) -> std::path::PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("manifest-dir-test-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("linker.wasm"), LINKER).unwrap();
+    std::fs::write(dir.join("plugin.toml"), manifest).unwrap();
+    dir
+}
+
+#[test]
+fn plugin_directories_load_as_their_manifest_names_them() {
+    let mut host = host();
+    let dir = plugin_dir(
+        "load",
+        r#"
+        name = "linker"
+        version = "0.3.0"
+        exports = ["run"]
+        links = ["provider::greet"]
+        data_size = 64
+        stack_size = 4096
+        "#,
+    );
+    assert_eq!(host.load_plugin_from_dir(&dir).unwrap(), "linker");
+    assert_eq!(
+        host.manifest("linker").unwrap().version.as_deref(),
+        Some("0.3.0")
+    );
+    assert!(host.manifest("provider").is_none());
+    // Declaring links allows host_link_call
+    let run = host
+        .get_func("linker", "run")
+        .unwrap()
+        .typed::<(), i32>(&host.store)
+        .unwrap();
+    assert_eq!(run.call(&mut host.store, ()).unwrap(), 42);
+    // The directory loads under its manifest's name only
+    let error = format!("{:#}", host.load_plugin_file("other", &dir).unwrap_err());
+    assert!(
+        error.contains("is named 'linker' in its manifest"),
+        "{}",
+        error
+    );
+
+    std::fs::write(dir.join("plugin.toml"), r#"version = "1""#).unwrap();
+    let error = format!("{:#}", host.load_plugin_from_dir(&dir).unwrap_err());
+    assert!(error.contains("doesn't name its plugin"), "{}", error);
+    std::fs::write(dir.join("plugin.toml"), r#"links = ["greet"]"#).unwrap();
+    let error = format!("{:#}", host.load_plugin_from_dir(&dir).unwrap_err());
+    assert!(error.contains("links are `plugin::export`"), "{}", error);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn what_the_manifest_declares_is_checked_before_instantiation() {
+    let mut host = host();
+    for (declared, expected) in [
+        (
+            r#"links = ["provider::farewell"]"#,
+            "links what isn't loaded: provider::farewell",
+        ),
+        (
+            r#"links = ["nobody::greet"]"#,
+            "links what isn't loaded: nobody::greet",
+        ),
+        (
+            r#"imports = ["host_link_call", "host_teleport"]"#,
+            "doesn't have: env::host_teleport",
+        ),
+        (
+            r#"imports = ["host_link_call", "wasi_snapshot_preview1::fd_write"]"#,
+            "doesn't have: wasi_snapshot_preview1::fd_write",
+        ),
+        (
+            r#"imports = ["host_link_call"]
+            exports = ["run", "tick"]"#,
+            "doesn't export what its manifest declares: tick",
+        ),
+        (
+            r#"links = ["provider::greet"]
+            data_size = 4"#,
+            "19 bytes of static data, more than the 4",
+        ),
+        (
+            r#"links = ["provider::greet"]
+            data_size = 1000000000"#,
+            "asks for 1000000000 bytes of data",
+        ),
+        (
+            r#"links = ["provider::greet"]
+            stack_size = 1000000000"#,
+            "asks for 1000000000 bytes of stack",
+        ),
+    ] {
+        let dir = plugin_dir("checks", &format!("name = \"linker\"\n{}", declared));
+        let error = format!("{:#}", host.load_plugin_from_dir(&dir).unwrap_err());
+        assert!(error.contains(expected), "{}: {}", declared, error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    assert!(!host.store.data().instances.contains_key("linker"));
+    assert_eq!(host.store.data().slots.len(), 1, "none of them took a slot");
+}
@@ -0,0 +1,77 @@
+// The module cache (host/module_cache.rs): plugins compiled once, loaded back by later hosts.
+
+mod common;
+
+use common::scratch;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use std::path::{Path, PathBuf};
+
+const GAME: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "get") (result i32) i32.const 7))
+"#;
+
+fn host(cache: &Path, deterministic: bool) -> BlindHost {
+    let config = BlindHostConfig {
+        module_cache: Some(cache.to_path_buf()),
+        deterministic,
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    let get = host
+        .get_func("game", "get")
+        .unwrap()
+        .typed::<(), i32>(&host.store)
+        .unwrap();
+    assert_eq!(get.call(&mut host.store, ()).unwrap(), 7);
+    host
+}
+
+fn entries(cache: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(cache)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+#[test]
+fn a_compiled_plugin_is_kept_and_loaded_back() {
+    let cache = scratch("module-cache", "kept");
+    host(&cache, false);
+    let kept = entries(&cache);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].extension().unwrap(), "cwasm");
+    let written = std::fs::metadata(&kept[0]).unwrap().modified().unwrap();
+
+    // Loaded back rather than compiled and written again
+    host(&cache, false);
+    assert_eq!(entries(&cache), kept);
+    assert_eq!(
+        std::fs::metadata(&kept[0]).unwrap().modified().unwrap(),
+        written
+    );
+
+    // Engines set up differently keep their own
+    host(&cache, true);
+    assert_eq!(entries(&cache).len(), 2);
+    let _ = std::fs::remove_dir_all(&cache);
+}
+
+#[test]
+fn entries_that_dont_load_are_compiled_again() {
+    let cache = scratch("module-cache", "broken");
+    host(&cache, false);
+    let entry = entries(&cache).remove(0);
+    std::fs::write(&entry, b"not a module").unwrap();
+
+    host(&cache, false);
+    assert_ne!(std::fs::read(&entry).unwrap(), b"not a module");
+    assert_eq!(entries(&cache), [entry]);
+    let _ = std::fs::remove_dir_all(&cache);
+}
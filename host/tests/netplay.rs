@@ -0,0 +1,246 @@
+// World snapshots (host/snapshot.rs), and rollback and lockstep netplay between two hosts on
+// loopback (embedder/netplay.rs), directly and through a relay, with a driver that folds every input it's handed into one
+// number.
+
+use host::embedder::driver::DriverHandle;
+use host::embedder::lockstep::{LockstepSession, HASH_INTERVAL};
+use host::embedder::netplay::InputExchange;
+use host::embedder::rollback::{RollbackSession, MAX_PREDICTION};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::snapshot::Snapshot;
+use host::host_calls::allocator::alloc_shared;
+use std::time::{Duration, Instant};
+
+// A 1x1 grid at its memory base, and state = state * 31 + key + 1000 * player after it
+const FOLDER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "host_random" (func $random (result i64)))
+  (global $input (mut i32) (i32.const 0))
+  (global $ticks (export "ticks") (mut i32) (i32.const 0))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "grid_negotiate") (param i32 i64) (result i64) local.get 1)
+  (func (export "set_input") (param i32) local.get 0 global.set $input)
+  (func (export "set_tickrate") (param f32))
+  (func (export "get_grid_dimensions") (result i64) i64.const 0x100000001)
+  (func (export "get_grid_ptr") (result i32) global.get $base)
+  (func (export "draw") (result i64) call $random)
+  (func (export "tick") (param f32)
+    global.get $base
+    global.get $base i32.load offset=8 i32.const 31 i32.mul
+    global.get $input i32.load offset=4
+    i32.add
+    global.get $input i32.load8_u offset=9 i32.const 1000 i32.mul
+    i32.add
+    i32.store offset=8
+    global.get $ticks i32.const 1 i32.add global.set $ticks))
+"#;
+
+fn host() -> (BlindHost, DriverHandle) {
+    let config = BlindHostConfig {
+        deterministic: true,
+        random_seed: Some(0),
+        // Snapshots and hashes cover all of memory, which is mostly plugin slots
+        max_plugins: 1,
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("folder", FOLDER.as_bytes()).unwrap();
+    let driver = DriverHandle::bind(&mut host, "folder").unwrap();
+    (host, driver)
+}
+
+fn state(host: &mut BlindHost) -> u32 {
+    let base = host.store.data().slots[0].1;
+    u32::from_le_bytes(host.read_mem(base + 8, 4).unwrap().try_into().unwrap())
+}
+
+fn key(c: char) -> grid_protocol::GridInput {
+    host::embedder::headless::parse_key(&c.to_string()).unwrap()
+}
+
+fn peers() -> [String; 2] {
+    ["127.0.0.1:0".to_string(), "127.0.0.1:0".to_string()]
+}
+
+fn pair() -> (RollbackSession, RollbackSession) {
+    let mut a = RollbackSession::bind(&peers(), 0, 0).unwrap();
+    let mut b = RollbackSession::bind(&peers(), 1, 0).unwrap();
+    a.set_peer(1, b.local_addr().unwrap());
+    b.set_peer(0, a.local_addr().unwrap());
+    (a, b)
+}
+
+// Long enough for a loopback datagram to land
+fn settle() {
+    std::thread::sleep(Duration::from_millis(50));
+}
+
+#[test]
+fn a_restored_snapshot_puts_the_world_back() {
+    let (mut host, driver) = host();
+    let draw = host
+        .get_func("folder", "draw")
+        .unwrap()
+        .typed::<(), i64>(&host.store)
+        .unwrap();
+    let snapshot = Snapshot::take(&mut host).unwrap();
+    let before = (host.state_hash(), draw.call(&mut host.store, ()).unwrap());
+    snapshot.restore(&mut host).unwrap();
+
+    driver.tick(&mut host, &key('x'), 0.0).unwrap();
+    let block = alloc_shared(host.store.data(), 1 << 20);
+    assert!(block > 0);
+    assert_ne!(host.state_hash(), before.0);
+
+    snapshot.restore(&mut host).unwrap();
+    assert_eq!(host.state_hash(), before.0);
+    assert_eq!(draw.call(&mut host.store, ()).unwrap(), before.1);
+    let instance = host.store.data().instances["folder"];
+    let ticks = instance.get_global(&mut host.store, "ticks").unwrap();
+    assert_eq!(ticks.get(&mut host.store).i32(), Some(0));
+    // The block is free again, so the same allocation lands in the same place
+    assert_eq!(alloc_shared(host.store.data(), 1 << 20), block);
+}
+
+#[test]
+fn late_input_rolls_back_and_both_players_agree() {
+    let (mut host_a, driver_a) = host();
+    let (mut host_b, driver_b) = host();
+    let (mut a, mut b) = pair();
+
+    // A runs ahead on B's input predicted as none, then hears B pressed a key in frame 0
+    a.push_local(key('a'));
+    for _ in 0..3 {
+        assert!(a.advance(&mut host_a, &driver_a).unwrap().ran);
+    }
+    settle();
+    b.push_local(key('b'));
+    for _ in 0..3 {
+        assert_eq!(b.advance(&mut host_b, &driver_b).unwrap().rolled_back, 0);
+    }
+    settle();
+    assert_eq!(a.advance(&mut host_a, &driver_a).unwrap().rolled_back, 3);
+    settle();
+    b.advance(&mut host_b, &driver_b).unwrap();
+
+    assert_eq!((a.frame(), b.frame()), (4, 4));
+    assert_eq!((a.rollbacks(), b.rollbacks()), (3, 0));
+    assert_eq!(state(&mut host_a), state(&mut host_b));
+    assert_ne!(state(&mut host_a), 0);
+}
+
+#[test]
+fn a_player_stalls_rather_than_predict_too_far() {
+    let (mut host, driver) = host();
+    let (mut a, _b) = pair();
+    for _ in 0..MAX_PREDICTION {
+        assert!(a.advance(&mut host, &driver).unwrap().ran);
+    }
+    assert!(!a.advance(&mut host, &driver).unwrap().ran);
+    assert_eq!(a.frame(), MAX_PREDICTION);
+}
+
+fn lockstep_pair(delay: u32) -> (LockstepSession, LockstepSession) {
+    let mut a = LockstepSession::bind(&peers(), 0, delay).unwrap();
+    let mut b = LockstepSession::bind(&peers(), 1, delay).unwrap();
+    a.set_peer(1, b.local_addr().unwrap());
+    b.set_peer(0, a.local_addr().unwrap());
+    (a, b)
+}
+
+// Advances both players until they're both past `frames`, or one of them fails
+fn play(
+    (a, host_a, driver_a): (&mut LockstepSession, &mut BlindHost, &DriverHandle),
+    (b, host_b, driver_b): (&mut LockstepSession, &mut BlindHost, &DriverHandle),
+    frames: u32,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    while a.frame() < frames || b.frame() < frames {
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "stuck at frames {} and {}",
+            a.frame(),
+            b.frame()
+        );
+        a.advance(host_a, driver_a)?;
+        b.advance(host_b, driver_b)?;
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+}
+
+#[test]
+fn lockstep_plays_through_a_relay() {
+    let relay = relay::Relay::bind("127.0.0.1:0").unwrap();
+    let room = relay::Room::parse(&format!("{}/netplay", relay.local_addr().unwrap())).unwrap();
+    std::thread::spawn(move || relay.run());
+
+    let (mut host_a, driver_a) = host();
+    let (mut host_b, driver_b) = host();
+    let mut a = LockstepSession::new(InputExchange::relayed(&room, 2, 0, 2).unwrap()).unwrap();
+    let mut b = LockstepSession::new(InputExchange::relayed(&room, 2, 1, 2).unwrap()).unwrap();
+    a.push_local(key('a'));
+    b.push_local(key('b'));
+    play(
+        (&mut a, &mut host_a, &driver_a),
+        (&mut b, &mut host_b, &driver_b),
+        10,
+    )
+    .unwrap();
+    assert_eq!(state(&mut host_a), state(&mut host_b));
+    assert_ne!(state(&mut host_a), 0);
+}
+
+#[test]
+fn lockstep_waits_for_everyones_input() {
+    let (mut host_a, driver_a) = host();
+    let (mut host_b, driver_b) = host();
+    let (mut a, mut b) = lockstep_pair(0);
+
+    a.push_local(key('a'));
+    assert!(!a.advance(&mut host_a, &driver_a).unwrap().ran);
+    settle();
+    b.push_local(key('b'));
+    assert!(b.advance(&mut host_b, &driver_b).unwrap().ran);
+    settle();
+    assert!(a.advance(&mut host_a, &driver_a).unwrap().ran);
+    assert_eq!(state(&mut host_a), state(&mut host_b));
+    assert_ne!(state(&mut host_a), 0);
+}
+
+#[test]
+fn lockstep_plays_on_while_hashes_agree() {
+    let (mut host_a, driver_a) = host();
+    let (mut host_b, driver_b) = host();
+    let (mut a, mut b) = lockstep_pair(2);
+    a.push_local(key('a'));
+    b.push_local(key('b'));
+    play(
+        (&mut a, &mut host_a, &driver_a),
+        (&mut b, &mut host_b, &driver_b),
+        HASH_INTERVAL + 2,
+    )
+    .unwrap();
+    assert_eq!(host_a.state_hash(), host_b.state_hash());
+}
+
+#[test]
+fn lockstep_stops_on_a_desync() {
+    let (mut host_a, driver_a) = host();
+    let (mut host_b, driver_b) = host();
+    // What a plugin reading the clock would do: B's world drifts from A's
+    let base = host_b.store.data().slots[0].1;
+    host_b.write_mem(base + 64, &[1]).unwrap();
+
+    let (mut a, mut b) = lockstep_pair(2);
+    let error = play(
+        (&mut a, &mut host_a, &driver_a),
+        (&mut b, &mut host_b, &driver_b),
+        10,
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", error).contains("Desync"), "{:#}", error);
+}
@@ -0,0 +1,64 @@
+use grid_protocol::{INPUT_COMMIT, INPUT_KEY};
+use host::embedder::headless::ScriptInput;
+use host::embedder::players::PlayerServer;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+fn join(server: &PlayerServer) -> (TcpStream, BufReader<TcpStream>) {
+    let stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let reader = BufReader::new(stream.try_clone().unwrap());
+    (stream, reader)
+}
+
+fn read_line(reader: &mut impl BufRead) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line.trim().to_string()
+}
+
+fn wait_for(server: &PlayerServer) -> (u8, ScriptInput) {
+    let start = Instant::now();
+    loop {
+        if let Some(input) = server.next() {
+            return input;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "no input arrived");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn each_connection_is_a_new_player() {
+    let server = PlayerServer::bind("127.0.0.1:0").unwrap();
+    let (_first, mut first) = join(&server);
+    assert_eq!(read_line(&mut first), "player 1");
+    let (_second, mut second) = join(&server);
+    assert_eq!(read_line(&mut second), "player 2");
+}
+
+#[test]
+fn inputs_arrive_tagged_with_their_player() {
+    let server = PlayerServer::bind("127.0.0.1:0").unwrap();
+    let (mut stream, mut reader) = join(&server);
+    assert_eq!(read_line(&mut reader), "player 1");
+
+    writeln!(stream, "Ctrl+c\nnot a key\nCommit:héllo").unwrap();
+    assert_eq!(read_line(&mut reader), "error: unknown key 'not a key'");
+
+    match wait_for(&server) {
+        (1, ScriptInput::Key(input)) => {
+            assert_eq!((input.input_type, input.key_code), (INPUT_KEY, 'c' as u32));
+            assert_ne!(input.modifiers, 0);
+        }
+        _ => panic!("expected player 1's key"),
+    }
+    match wait_for(&server) {
+        (1, ScriptInput::Text(INPUT_COMMIT, text)) => assert_eq!(text, "héllo"),
+        _ => panic!("expected player 1's text"),
+    }
+    assert!(server.next().is_none());
+}
@@ -0,0 +1,203 @@
+// Quotas (host/quotas.rs): plugins over their heap, fuel, time or cpu budgets are throttled or
+// suspended, and the other plugins hear about it.
+
+mod common;
+
+use bus_protocol::quotas::{
+    QuotaEvent, EVENT_RESUMED, EVENT_SUSPENDED, EVENT_THROTTLED, RESOURCE_CPU, RESOURCE_FUEL,
+    RESOURCE_HEAP, RESOURCE_TIME,
+};
+use common::slot_of;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::logger::{LogConfig, Sink};
+use host::host::quotas::QuotaConfig;
+use host::host_calls::bus::deliver;
+use std::time::{Duration, Instant};
+
+const HOG: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "grab") (param i32) (result i32) local.get 0 call $alloc)
+  (func (export "spin") (param $n i32)
+    (loop $again
+      local.get $n i32.const 1 i32.sub local.tee $n
+      i32.const 0 i32.gt_s br_if $again))
+  (func (export "forever") (loop $again br $again)))
+"#;
+
+// Keeps the last message it got at the start of its slot: len u32, then the message
+const LISTENER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "on_message") (param i32 i32)
+    global.get $base local.get 1 i32.store
+    global.get $base i32.const 4 i32.add local.get 0 local.get 1 memory.copy))
+"#;
+
+fn host(quotas: QuotaConfig, deterministic: bool) -> BlindHost {
+    let config = BlindHostConfig {
+        quotas,
+        deterministic,
+        log: LogConfig {
+            sinks: Some(vec![Sink::Pane]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("hog", HOG.as_bytes()).unwrap();
+    host.load_plugin("listener", LISTENER.as_bytes()).unwrap();
+    host
+}
+
+fn call(host: &mut BlindHost, export: &str, arg: Option<i32>) -> anyhow::Result<i32> {
+    let func = host.get_func("hog", export).unwrap();
+    host.profiled("hog", export, |store| match arg {
+        Some(arg) if export == "grab" => func.typed::<i32, i32>(&*store)?.call(store, arg),
+        Some(arg) => func.typed::<i32, ()>(&*store)?.call(store, arg).map(|()| 0),
+        None => func.typed::<(), ()>(&*store)?.call(store, ()).map(|()| 0),
+    })
+}
+
+// What the listener heard last, delivering what's queued first
+fn heard(host: &mut BlindHost) -> Option<QuotaEvent> {
+    deliver(host).unwrap();
+    let base = slot_of(host, "listener");
+    let len = u32::from_le_bytes(host.read_mem(base, 4).unwrap().try_into().unwrap()) as i32;
+    let message = bus_protocol::Envelope::decode(&host.read_mem(base + 4, len).ok()?)?;
+    assert_eq!(message.sender, "quotas");
+    QuotaEvent::decode(&message.payload)
+}
+
+fn tick(host: &mut BlindHost) {
+    host.end_tick(Duration::ZERO);
+}
+
+#[test]
+fn allocations_past_the_heap_budget_fail_and_throttle_the_plugin() {
+    let quotas = QuotaConfig {
+        heap: Some(1024),
+        throttle: 2,
+        suspend_after: 2,
+        ..Default::default()
+    };
+    let mut host = host(quotas, false);
+    assert_ne!(call(&mut host, "grab", Some(512)).unwrap(), 0);
+    assert_eq!(call(&mut host, "grab", Some(1024)).unwrap(), 0);
+    assert!(host.may_tick("hog"), "throttling starts with the next tick");
+    tick(&mut host);
+
+    let event = heard(&mut host).unwrap();
+    assert_eq!(
+        (event.event, event.resource, event.plugin.as_str()),
+        (EVENT_THROTTLED, RESOURCE_HEAP, "hog")
+    );
+    assert_eq!((event.used, event.budget), (1536, 1024));
+    let warning = &host.recent_logs(1)[0].text;
+    assert!(
+        warning.contains("'hog' went over its heap budget") && warning.contains("next 2 ticks"),
+        "{}",
+        warning
+    );
+
+    assert!(!host.may_tick("hog"));
+    assert!(host.may_tick("listener"), "others are left alone");
+    tick(&mut host);
+    assert!(!host.may_tick("hog"));
+    tick(&mut host);
+    assert!(host.may_tick("hog"));
+
+    // A tick within budget wipes the slate, so going over again only throttles it again
+    call(&mut host, "spin", Some(1)).unwrap();
+    tick(&mut host);
+    call(&mut host, "grab", Some(1024)).unwrap();
+    tick(&mut host);
+    assert_eq!(heard(&mut host).unwrap().event, EVENT_THROTTLED);
+}
+
+#[test]
+fn runaway_calls_run_out_of_fuel_until_suspended_then_resume() {
+    let quotas = QuotaConfig {
+        fuel: Some(100_000),
+        suspend_after: 2,
+        ..Default::default()
+    };
+    let mut host = host(quotas, true);
+    call(&mut host, "spin", Some(100)).unwrap();
+    let trap = format!("{:?}", call(&mut host, "forever", None).unwrap_err());
+    assert!(trap.contains("fuel"), "{}", trap);
+    tick(&mut host);
+    let event = heard(&mut host).unwrap();
+    assert_eq!(
+        (event.event, event.resource, event.budget),
+        (EVENT_THROTTLED, RESOURCE_FUEL, 100_000)
+    );
+
+    call(&mut host, "forever", None).unwrap_err();
+    tick(&mut host);
+    assert_eq!(heard(&mut host).unwrap().event, EVENT_SUSPENDED);
+    for _ in 0..100 {
+        tick(&mut host);
+    }
+    assert!(!host.may_tick("hog"), "suspended until resumed");
+
+    // Calls the quotas don't cover (not through profiled) aren't budgeted
+    let spin = host
+        .get_func("hog", "spin")
+        .unwrap()
+        .typed::<i32, ()>(&host.store)
+        .unwrap();
+    spin.call(&mut host.store, 1_000_000).unwrap();
+
+    assert!(host.resume("hog"));
+    assert!(!host.resume("hog"));
+    assert!(host.may_tick("hog"));
+    assert_eq!(heard(&mut host).unwrap().event, EVENT_RESUMED);
+}
+
+#[test]
+fn calls_past_their_time_are_interrupted() {
+    let quotas = QuotaConfig {
+        timeout: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    let mut host = host(quotas, false);
+    let start = Instant::now();
+    call(&mut host, "forever", None).unwrap_err();
+    assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "{:?}",
+        start.elapsed()
+    );
+    call(&mut host, "spin", Some(100)).unwrap();
+    tick(&mut host);
+    let event = heard(&mut host).unwrap();
+    assert_eq!(
+        (event.event, event.resource, event.budget),
+        (EVENT_THROTTLED, RESOURCE_TIME, 20_000)
+    );
+    assert!(event.used >= 20_000, "{}", event.used);
+}
+
+#[test]
+fn ticks_over_the_cpu_budget_throttle_except_in_deterministic_hosts() {
+    let quotas = QuotaConfig {
+        cpu: Some(Duration::from_micros(1)),
+        ..Default::default()
+    };
+    for deterministic in [false, true] {
+        let mut host = host(quotas.clone(), deterministic);
+        call(&mut host, "spin", Some(1_000_000)).unwrap();
+        tick(&mut host);
+        assert_eq!(host.may_tick("hog"), deterministic);
+        match heard(&mut host) {
+            Some(event) => assert_eq!((event.resource, event.budget), (RESOURCE_CPU, 1)),
+            None => assert!(deterministic),
+        }
+    }
+}
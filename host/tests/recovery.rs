@@ -0,0 +1,115 @@
+// Drivers trapping mid-tick (embedder/recovery.rs): what the crash screen shows, and a restart
+// into a fresh instance in the slot the trapped one gave back.
+
+mod common;
+
+use common::slot_of;
+use grid_protocol::GridInput;
+use host::embedder::compositor::{Compositor, Layout};
+use host::embedder::crash::{self, InputLog};
+use host::embedder::driver::DriverHandle;
+use host::embedder::recovery::{self, Crashed};
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use std::path::PathBuf;
+
+// A 1x1 grid at its memory base that counts its ticks, and traps on a negative delta
+const DRIVER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (global $ticks (export "ticks") (mut i32) (i32.const 0))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "grid_negotiate") (param i32 i64) (result i64) local.get 1)
+  (func (export "set_input") (param i32))
+  (func (export "set_tickrate") (param f32))
+  (func (export "get_grid_dimensions") (result i64) i64.const 0x100000001)
+  (func (export "get_grid_ptr") (result i32) global.get $base)
+  (func (export "tick") (param f32)
+    (if (f32.lt (local.get 0) (f32.const 0)) (then unreachable))
+    global.get $ticks i32.const 1 i32.add global.set $ticks))
+"#;
+
+fn setup(name: &str) -> (BlindHost, Compositor, Vec<(String, PathBuf)>, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("recovery-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("game.wat");
+    std::fs::write(&path, DRIVER).unwrap();
+
+    let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin_file("game", &path).unwrap();
+    let mut compositor = Compositor::new(Layout::Tabs);
+    compositor.add(DriverHandle::bind(&mut host, "game").unwrap());
+    compositor.prime(&mut host, 0.0).unwrap();
+    (host, compositor, vec![("game".to_string(), path)], dir)
+}
+
+fn tick(host: &mut BlindHost, compositor: &Compositor, delta: f32) -> anyhow::Result<()> {
+    compositor.panes()[0]
+        .driver
+        .tick(host, &GridInput::default(), delta)
+}
+
+fn ticks(host: &mut BlindHost) -> i32 {
+    let instance = host.store.data().instances["game"];
+    let ticks = instance.get_global(&mut host.store, "ticks").unwrap();
+    ticks.get(&mut host.store).i32().unwrap()
+}
+
+#[test]
+fn a_trap_names_the_driver_and_leaves_a_bundle() {
+    let (mut host, compositor, _, dir) = setup("crash");
+    let error = tick(&mut host, &compositor, -1.0).unwrap_err();
+    assert!(crash::is_trap(&error));
+
+    let crashed = Crashed::new(
+        &dir.join("crashes"),
+        "game",
+        &error,
+        &host,
+        &InputLog::default(),
+        "",
+    );
+    assert_eq!(crashed.driver, "game");
+    assert_eq!(crashed.plugin, None, "the trap was in the driver itself");
+    assert!(
+        crashed.message.contains("unreachable"),
+        "{}",
+        crashed.message
+    );
+    let bundle = crashed.bundle.unwrap();
+    assert!(bundle.join("trap.txt").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_restart_ticks_a_fresh_instance_in_the_same_slot() {
+    let (mut host, mut compositor, modules, dir) = setup("restart");
+    tick(&mut host, &compositor, 0.1).unwrap();
+    assert_eq!(ticks(&mut host), 2, "primed, then ticked");
+    let slot = slot_of(&host, "game");
+    let live = host.store.data().metrics.lock().unwrap().live_bytes("game");
+    assert!(live > 0, "the input buffer is the driver's");
+    assert!(tick(&mut host, &compositor, -1.0).is_err());
+
+    recovery::restart(&mut host, &mut compositor, "game", &modules, 0.0).unwrap();
+    assert_eq!(ticks(&mut host), 1, "a new instance, primed again");
+    tick(&mut host, &compositor, 0.1).unwrap();
+    assert_eq!(ticks(&mut host), 2);
+    let slots = &host.store.data().slots;
+    let bases: Vec<_> = slots
+        .iter()
+        .filter(|(name, _)| name == "game")
+        .map(|(_, base)| *base)
+        .collect();
+    assert_eq!(bases, [slot]);
+    assert_eq!(
+        host.store.data().metrics.lock().unwrap().live_bytes("game"),
+        live,
+        "the old buffer went with it"
+    );
+
+    assert!(recovery::restart(&mut host, &mut compositor, "other", &modules, 0.0).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,236 @@
+// Save backends (host_calls/save_backend.rs) against a small in-memory HTTP save server: plain
+// HTTP, two machines mirroring their saves through it, and mirroring from a server that stalls.
+
+mod common;
+
+use common::scratch;
+use host::host_calls::save_backend::{HttpBackend, LocalDir, Mirrored, SaveBackend};
+use host::host_calls::saves::{SaveStore, SaveVersion};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type Files = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+// GET, PUT and DELETE of paths, and `GET <dir>/` listing the names under it
+fn serve() -> (String, Files) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/saves", listener.local_addr().unwrap());
+    let files = Files::default();
+    let served = files.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let _ = answer(stream.unwrap(), &served);
+        }
+    });
+    (url, files)
+}
+
+fn answer(stream: TcpStream, files: &Files) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut parts = request.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or("").to_string(),
+        parts.next().unwrap_or("").to_string(),
+    );
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let mut files = files.lock().unwrap();
+    let (status, reply) = match method.as_str() {
+        "GET" if path.ends_with('/') => {
+            let names: Vec<&str> = files
+                .keys()
+                .filter_map(|key| key.strip_prefix(&path))
+                .collect();
+            (200, names.join("\n").into_bytes())
+        }
+        "GET" => match files.get(&path) {
+            Some(file) => (200, file.clone()),
+            None => (404, Vec::new()),
+        },
+        "PUT" => {
+            files.insert(path, body);
+            (201, Vec::new())
+        }
+        "DELETE" => match files.remove(&path) {
+            Some(_) => (204, Vec::new()),
+            None => (404, Vec::new()),
+        },
+        _ => (405, Vec::new()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reply.len()
+    )?;
+    stream.write_all(&reply)
+}
+
+#[test]
+fn http_keeps_each_plugins_files_by_slot() {
+    let (url, files) = serve();
+    let http = HttpBackend::new(&url).unwrap();
+    assert_eq!(http.read("game", "quick").unwrap(), None);
+    http.write("game", "quick", b"one").unwrap();
+    http.write("game", "boss", b"two").unwrap();
+    assert!(files.lock().unwrap().contains_key("/saves/game/quick.sav"));
+    assert_eq!(http.read("game", "quick").unwrap(), Some(b"one".to_vec()));
+
+    let mut slots = http.slots("game").unwrap();
+    slots.sort();
+    assert_eq!(slots, ["boss", "quick"]);
+    assert!(http.slots("other").unwrap().is_empty());
+
+    http.delete("game", "quick").unwrap();
+    http.delete("game", "quick").unwrap();
+    assert_eq!(http.read("game", "quick").unwrap(), None);
+}
+
+#[test]
+fn mirrored_saves_reach_another_machine() {
+    let (url, _files) = serve();
+    let (here, there) = (
+        scratch("save-backend", "here"),
+        scratch("save-backend", "there"),
+    );
+    let mirrored = |dir: &PathBuf| -> Arc<dyn SaveBackend> {
+        Arc::new(Mirrored::new(
+            LocalDir::new(dir),
+            Arc::new(HttpBackend::new(&url).unwrap()),
+        ))
+    };
+    let version = SaveVersion::default();
+
+    let home = SaveStore::with_backend(mirrored(&here));
+    home.save("game", "quick", version, b"level 4").unwrap();
+    home.flush();
+
+    // The other machine has nothing locally, so it lists and fetches from the server, in the
+    // background: the first asks only queue that
+    let away = SaveStore::with_backend(mirrored(&there));
+    assert!(away.list("game").unwrap().is_empty());
+    assert_eq!(away.load("game", "quick", version).unwrap(), None);
+    away.flush();
+    let listed: Vec<String> = away
+        .list("game")
+        .unwrap()
+        .into_iter()
+        .map(|info| info.slot)
+        .collect();
+    assert_eq!(listed, ["quick"]);
+    assert_eq!(
+        away.load("game", "quick", version).unwrap(),
+        Some(b"level 4".to_vec())
+    );
+    assert!(
+        there.join("game").join("quick.sav").exists(),
+        "fetched saves are kept locally"
+    );
+
+    // Later versions follow, and so do deletes
+    assert_eq!(
+        away.save("game", "quick", version, b"level 5")
+            .unwrap()
+            .version,
+        2
+    );
+    away.flush();
+    std::fs::remove_dir_all(&here).unwrap();
+    assert_eq!(home.load("game", "quick", version).unwrap(), None);
+    home.flush();
+    assert_eq!(
+        home.load("game", "quick", version).unwrap(),
+        Some(b"level 5".to_vec())
+    );
+    away.delete("game", "quick").unwrap();
+    assert!(
+        away.list("game").unwrap().is_empty(),
+        "deletes leave the cached listing too"
+    );
+    away.flush();
+    std::fs::remove_dir_all(&here).unwrap();
+    assert_eq!(home.load("game", "quick", version).unwrap(), None);
+    home.flush();
+    assert_eq!(home.load("game", "quick", version).unwrap(), None);
+    let _ = std::fs::remove_dir_all(&there);
+}
+
+#[test]
+fn mirrored_saves_work_offline() {
+    let dir = scratch("save-backend", "offline");
+    // Nothing listens on a port just given back
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let remote = Arc::new(HttpBackend::new(&format!("http://127.0.0.1:{}/saves", port)).unwrap());
+    let store = SaveStore::with_backend(Arc::new(Mirrored::new(LocalDir::new(&dir), remote)));
+    let version = SaveVersion::default();
+
+    store.save("game", "quick", version, b"level 1").unwrap();
+    store.flush();
+    assert_eq!(
+        store.load("game", "quick", version).unwrap(),
+        Some(b"level 1".to_vec())
+    );
+    assert_eq!(store.list("game").unwrap().len(), 1);
+    // A slot that isn't here stays missing, the failed fetch only logged
+    assert_eq!(store.load("game", "boss", version).unwrap(), None);
+    store.flush();
+    assert_eq!(store.load("game", "boss", version).unwrap(), None);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn mirrored_reads_never_wait_on_the_server() {
+    let dir = scratch("save-backend", "stalled");
+    // Takes connections and never answers them
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/saves", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let held: Vec<TcpStream> = listener.incoming().map_while(Result::ok).collect();
+        drop(held);
+    });
+    let mirrored = Mirrored::new(
+        LocalDir::new(&dir),
+        Arc::new(HttpBackend::new(&url).unwrap()),
+    );
+
+    let started = Instant::now();
+    assert_eq!(mirrored.read("game", "quick").unwrap(), None);
+    assert!(mirrored.slots("game").unwrap().is_empty());
+    mirrored.write("game", "boss", b"level 2").unwrap();
+    assert_eq!(
+        mirrored.read("game", "boss").unwrap(),
+        Some(b"level 2".to_vec())
+    );
+    assert_eq!(mirrored.read("game", "quick").unwrap(), None);
+    assert_eq!(mirrored.slots("game").unwrap(), ["boss"]);
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "took {:?}",
+        started.elapsed()
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,267 @@
+// Save slots (host_calls/saves.rs), through a plugin that passes the host calls straight through,
+// and the save files they leave behind: their format, checks and migrations.
+
+mod common;
+
+use common::{scratch, slot_of};
+use fat_ptr::FatPtr;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::save_backend::{LocalDir, SaveBackend};
+use host::host_calls::saves::{
+    encode_list, SaveHeader, SaveInfo, SaveStore, SaveVersion, FLAG_ZSTD, SAVE_FORMAT,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const SAVER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_save" (func $save (param i32 i32 i32 i32) (result i32)))
+  (import "env" "host_load" (func $load (param i32 i32) (result i64)))
+  (import "env" "host_list_saves" (func $list (result i64)))
+  (import "env" "host_delete_save" (func $delete (param i32 i32) (result i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (export "save" (func $save))
+  (export "load" (func $load))
+  (export "list" (func $list))
+  (export "delete" (func $delete)))
+"#;
+
+fn host(saves_dir: Option<PathBuf>, deterministic: bool) -> BlindHost {
+    let config = BlindHostConfig {
+        max_plugins: 2,
+        save_backend: saves_dir.map(|dir| Arc::new(LocalDir::new(dir)) as Arc<dyn SaveBackend>),
+        deterministic,
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("a", SAVER.as_bytes()).unwrap();
+    host.load_plugin("b", SAVER.as_bytes()).unwrap();
+    host
+}
+
+// Somewhere in `plugin`'s slot to put arguments
+fn scratch_ptr(host: &BlindHost, plugin: &str, offset: i32) -> i32 {
+    slot_of(host, plugin) + 64 + offset
+}
+
+fn save(host: &mut BlindHost, plugin: &str, slot: &str, data: &[u8]) -> i32 {
+    let (slot_ptr, data_ptr) = (scratch_ptr(host, plugin, 0), scratch_ptr(host, plugin, 64));
+    host.write_mem(slot_ptr, slot.as_bytes()).unwrap();
+    host.write_mem(data_ptr, data).unwrap();
+    let save = host
+        .get_func(plugin, "save")
+        .unwrap()
+        .typed::<(i32, i32, i32, i32), i32>(&host.store)
+        .unwrap();
+    save.call(
+        &mut host.store,
+        (slot_ptr, slot.len() as i32, data_ptr, data.len() as i32),
+    )
+    .unwrap()
+}
+
+fn load(host: &mut BlindHost, plugin: &str, slot: &str) -> Result<Vec<u8>, i64> {
+    let slot_ptr = scratch_ptr(host, plugin, 0);
+    host.write_mem(slot_ptr, slot.as_bytes()).unwrap();
+    let load = host
+        .get_func(plugin, "load")
+        .unwrap()
+        .typed::<(i32, i32), i64>(&host.store)
+        .unwrap();
+    match load
+        .call(&mut host.store, (slot_ptr, slot.len() as i32))
+        .unwrap()
+    {
+        packed @ (-1 | 0) => Err(packed),
+        packed => Ok(host.take_response(plugin, packed).unwrap()),
+    }
+}
+
+fn list(host: &mut BlindHost, plugin: &str) -> Vec<u8> {
+    let list = host
+        .get_func(plugin, "list")
+        .unwrap()
+        .typed::<(), i64>(&host.store)
+        .unwrap();
+    let packed = list.call(&mut host.store, ()).unwrap();
+    assert_ne!(FatPtr::unpack(packed).ptr, 0);
+    host.take_response(plugin, packed).unwrap()
+}
+
+fn delete(host: &mut BlindHost, plugin: &str, slot: &str) -> i32 {
+    let slot_ptr = scratch_ptr(host, plugin, 0);
+    host.write_mem(slot_ptr, slot.as_bytes()).unwrap();
+    let delete = host
+        .get_func(plugin, "delete")
+        .unwrap()
+        .typed::<(i32, i32), i32>(&host.store)
+        .unwrap();
+    delete
+        .call(&mut host.store, (slot_ptr, slot.len() as i32))
+        .unwrap()
+}
+
+#[test]
+fn plugins_keep_versioned_slots_of_their_own() {
+    let dir = scratch("saves", "slots");
+    let mut host = host(Some(dir.clone()), false);
+    assert_eq!(load(&mut host, "a", "quick"), Err(-1));
+    assert_eq!(save(&mut host, "a", "quick", b"level 1"), 1);
+    assert_eq!(save(&mut host, "a", "quick", b"level 2"), 2);
+    assert_eq!(save(&mut host, "a", "boss", b"level 9"), 1);
+    assert_eq!(load(&mut host, "a", "quick"), Ok(b"level 2".to_vec()));
+    // The same slot name is another plugin's own slot
+    assert_eq!(load(&mut host, "b", "quick"), Err(-1));
+    assert_eq!(list(&mut host, "b"), encode_list(&[]));
+
+    let store = SaveStore::new(&dir);
+    let saves = store.list("a").unwrap();
+    let slots: Vec<_> = saves
+        .iter()
+        .map(|s| (s.slot.as_str(), s.version, s.len))
+        .collect();
+    assert_eq!(slots, [("boss", 1, 7), ("quick", 2, 7)]);
+    assert_eq!(list(&mut host, "a"), encode_list(&saves));
+
+    assert_eq!(delete(&mut host, "a", "quick"), 0);
+    assert_eq!(load(&mut host, "a", "quick"), Err(-1));
+    assert_eq!(store.list("a").unwrap().len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bad_names_and_broken_files_fail_without_trapping() {
+    let dir = scratch("saves", "broken");
+    let mut host = host(Some(dir.clone()), false);
+    assert_eq!(save(&mut host, "a", "../b/quick", b"sneaky"), -1);
+    assert_eq!(save(&mut host, "a", ".hidden", b"sneaky"), -1);
+    assert_eq!(delete(&mut host, "a", ""), -1);
+
+    std::fs::create_dir_all(dir.join("a")).unwrap();
+    std::fs::write(dir.join("a").join("torn.sav"), b"USA").unwrap();
+    assert_eq!(load(&mut host, "a", "torn"), Err(0));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn saves_are_off_without_a_directory_and_in_deterministic_hosts() {
+    let dir = scratch("saves", "off");
+    for mut host in [host(None, false), host(Some(dir.clone()), true)] {
+        assert_eq!(save(&mut host, "a", "quick", b"level 1"), -1);
+        assert_eq!(load(&mut host, "a", "quick"), Err(-1));
+        assert_eq!(list(&mut host, "a"), encode_list(&[]));
+    }
+    assert!(!dir.exists());
+    assert_eq!(
+        SaveStore::new(&dir).list("a").unwrap(),
+        Vec::<SaveInfo>::new()
+    );
+}
+
+// Schema 2 of SAVER, which has its saves migrated
+const SAVER_V2: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_save" (func $save (param i32 i32 i32 i32) (result i32)))
+  (import "env" "host_load" (func $load (param i32 i32) (result i64)))
+  (import "env" "host_list_saves" (func $list (result i64)))
+  (import "env" "host_delete_save" (func $delete (param i32 i32) (result i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "__save_schema") (result i32) i32.const 2)
+  (export "save" (func $save))
+  (export "load" (func $load))
+  (export "list" (func $list))
+  (export "delete" (func $delete)))
+"#;
+
+#[test]
+fn saves_are_compressed_checksummed_and_carry_who_wrote_them() {
+    let dir = scratch("saves", "format");
+    let store = SaveStore::new(&dir);
+    let data = vec![7; 4096];
+    let saved_with = SaveVersion { abi: 1, schema: 3 };
+    store.save("a", "big", saved_with, &data).unwrap();
+    let file = std::fs::read(dir.join("a").join("big.sav")).unwrap();
+    assert!(file.len() < 200, "{} bytes", file.len());
+
+    let header = store.header("a", "big").unwrap().unwrap();
+    assert_eq!(
+        (header.format, header.flags, header.plugin.as_str()),
+        (SAVE_FORMAT, FLAG_ZSTD, "a")
+    );
+    assert_eq!(header.saved_with, saved_with);
+    assert_eq!((header.info.version, header.info.len), (1, 4096));
+    assert_eq!(store.load("a", "big", saved_with).unwrap(), Some(data));
+
+    // Flipping a byte of the data is caught rather than handed to the plugin
+    let mut torn = file.clone();
+    *torn.last_mut().unwrap() ^= 0xff;
+    std::fs::write(dir.join("a").join("big.sav"), &torn).unwrap();
+    let error = store.load("a", "big", saved_with).unwrap_err().to_string();
+    assert!(error.contains("corrupt"), "{}", error);
+
+    // So are other plugins' saves and those of newer hosts
+    std::fs::create_dir_all(dir.join("b")).unwrap();
+    std::fs::write(dir.join("b").join("big.sav"), &file).unwrap();
+    let error = store.load("b", "big", saved_with).unwrap_err().to_string();
+    assert!(error.contains("of 'a', not of 'b'"), "{}", error);
+    let mut newer = file;
+    newer[4..6].copy_from_slice(&(SAVE_FORMAT + 1).to_le_bytes());
+    std::fs::write(dir.join("a").join("big.sav"), &newer).unwrap();
+    let error = store.load("a", "big", saved_with).unwrap_err().to_string();
+    assert!(error.contains("newer host"), "{}", error);
+    // which are still replaced by later versions
+    assert_eq!(
+        store
+            .save("a", "big", saved_with, b"small")
+            .unwrap()
+            .version,
+        2
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn saves_of_older_schemas_go_through_the_migration() {
+    let dir = scratch("saves", "migrate");
+    let mut old = host(Some(dir.clone()), false);
+    assert_eq!(save(&mut old, "a", "quick", b"level 1"), 1);
+    drop(old);
+
+    let config = BlindHostConfig {
+        save_backend: Some(Arc::new(LocalDir::new(&dir))),
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("a", SAVER_V2.as_bytes()).unwrap();
+    assert_eq!(load(&mut host, "a", "quick"), Err(0), "no migration yet");
+
+    let saves = host.store.data().saves.clone().unwrap();
+    saves.set_migration(
+        "a",
+        Box::new(|header: &SaveHeader, mut data: Vec<u8>, to: SaveVersion| {
+            if (header.saved_with.schema, to.schema) != (0, 2) {
+                return Err(anyhow::anyhow!("unknown schema"));
+            }
+            data.extend_from_slice(b" (v2)");
+            Ok(data)
+        }),
+    );
+    assert_eq!(load(&mut host, "a", "quick"), Ok(b"level 1 (v2)".to_vec()));
+    // Saved again, it needs no migration anymore
+    assert_eq!(save(&mut host, "a", "quick", b"level 2"), 2);
+    assert_eq!(
+        saves
+            .header("a", "quick")
+            .unwrap()
+            .unwrap()
+            .saved_with
+            .schema,
+        2
+    );
+    assert_eq!(load(&mut host, "a", "quick"), Ok(b"level 2".to_vec()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
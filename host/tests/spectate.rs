@@ -0,0 +1,139 @@
+use grid_protocol::GridCell;
+use host::embedder::export::FrameRef;
+use host::embedder::images::ImageStore;
+use host::embedder::spectate::{accept_key, SpectatorServer};
+use host::embedder::theme::Theme;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+fn cell(character: char, fg_color: u8) -> GridCell {
+    GridCell {
+        character: character as u32,
+        fg_color,
+        ..Default::default()
+    }
+}
+
+fn frame(cells: &[GridCell]) -> FrameRef<'_> {
+    FrameRef {
+        cells,
+        width: 2,
+        height: 2,
+    }
+}
+
+// Reads one unmasked text frame, as the server sends them
+fn read_text(stream: &mut impl Read) -> Value {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(header[0], 0x81, "not a final text frame");
+    let len = match header[1] {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len).unwrap();
+            u64::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    let mut text = vec![0; len];
+    stream.read_exact(&mut text).unwrap();
+    serde_json::from_slice(&text).unwrap()
+}
+
+#[test]
+fn the_handshake_answers_the_rfc_example() {
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn viewers_get_a_key_frame_then_only_what_changed() {
+    let mut server = SpectatorServer::bind("127.0.0.1:0", &Theme::default()).unwrap();
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut reader = BufReader::new(stream);
+    let mut response = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        response.push(line.trim().to_string());
+    }
+    assert_eq!(response[0], "HTTP/1.1 101 Switching Protocols");
+    assert!(response.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string()));
+    assert_eq!(
+        read_text(&mut reader)["palette"].as_array().unwrap().len(),
+        256
+    );
+
+    let start = Instant::now();
+    while server.viewers() == 0 {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "the viewer never registered"
+        );
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let images = ImageStore::default();
+    let mut cells = vec![cell('a', 7), cell('b', 7), cell('c', 7), cell('d', 7)];
+    server.broadcast(&frame(&cells), &images);
+    let key = read_text(&mut reader);
+    assert_eq!(
+        (key["w"].as_i64(), key["h"].as_i64(), key["key"].as_bool()),
+        (Some(2), Some(2), Some(true))
+    );
+    assert_eq!(key["runs"][0][0], 0);
+    assert_eq!(key["runs"][0][1], "abcd");
+    assert_eq!(key["runs"][0][2].as_array().unwrap().len(), 12);
+
+    cells[1] = cell('B', 1);
+    cells[2] = cell('C', 7);
+    server.broadcast(&frame(&cells), &images);
+    let diff = read_text(&mut reader);
+    assert_eq!(diff["key"], false);
+    assert_eq!(
+        diff["runs"],
+        serde_json::json!([[1, "BC", [1, 0, 0, 7, 0, 0]]])
+    );
+
+    // Nothing changed, nothing sent: the next message is the next change
+    server.broadcast(&frame(&cells), &images);
+    cells[3] = cell('D', 7);
+    server.broadcast(&frame(&cells), &images);
+    assert_eq!(
+        read_text(&mut reader)["runs"],
+        serde_json::json!([[3, "D", [7, 0, 0]]])
+    );
+}
+
+#[test]
+fn browsers_get_the_viewer_page() {
+    let server = SpectatorServer::bind("127.0.0.1:0", &Theme::default()).unwrap();
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut page = String::new();
+    stream.read_to_string(&mut page).unwrap();
+    assert!(page.starts_with("HTTP/1.1 200 OK"));
+    assert!(page.contains("new WebSocket"));
+}
@@ -0,0 +1,286 @@
+// Split play (embedder/split.rs): a server host running a core plugin and a client host running
+// a plugin that shows its state, on loopback, over either transport (embedder/transport.rs) or
+// through a relay, and chat between clients.
+
+use bus_protocol::chat::ChatLine;
+use host::embedder::split::{SplitClient, SplitServer};
+use host::embedder::transport::TransportKind;
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host_calls::bus;
+use host::host_calls::replicate::{self, Replicas};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Replicates "counter", 16 bytes at base+16 so deltas of it are worth sending, counts frames
+// into it and 100 per message, and answers each message's sender with an empty reply
+const CORE: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "host_replicate" (func $replicate (param i32 i32 i32 i32) (result i32)))
+  (import "env" "bus_send" (func $send (param i32 i32 i32 i32) (result i32)))
+  (data (global.get $base)
+    "counter\00\00\00\00\00\00\00\00\00" "\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00"
+    "\00\00\00\00\00\00\00\00\01\00\00\00\00")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "init")
+    global.get $base i32.const 7 global.get $base i32.const 16 i32.add i32.const 16 call $replicate drop)
+  (func (export "plugin_update")
+    global.get $base global.get $base i32.load offset=16 i32.const 1 i32.add i32.store offset=16)
+  (func (export "on_message") (param $ptr i32) (param $len i32)
+    global.get $base global.get $base i32.load offset=16 i32.const 100 i32.add i32.store offset=16
+    local.get $ptr i32.const 11 i32.add
+    local.get $ptr i32.load16_u offset=9
+    global.get $base i32.const 32 i32.add
+    i32.const 13
+    call $send drop))
+"#;
+
+// Its own "counter" at base+16 (16 bytes), a request to "core" at base+32, replies counted at base+64
+const VIEW: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "host_replicate" (func $replicate (param i32 i32 i32 i32) (result i32)))
+  (import "env" "bus_send" (func $send (param i32 i32 i32 i32) (result i32)))
+  (data (global.get $base)
+    "counter\00\00\00\00\00\00\00\00\00" "\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00"
+    "\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00" "core")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "init")
+    global.get $base i32.const 7 global.get $base i32.const 16 i32.add i32.const 16 call $replicate drop)
+  (func (export "poke") (result i32)
+    global.get $base i32.const 48 i32.add i32.const 4 global.get $base i32.const 32 i32.add i32.const 13 call $send)
+  (func (export "on_message") (param i32 i32)
+    global.get $base global.get $base i32.load offset=64 i32.const 1 i32.add i32.store offset=64))
+"#;
+
+fn host(name: &str, wat: &str) -> (BlindHost, Arc<Replicas>) {
+    let replicas = Arc::new(Replicas::default());
+    let config = BlindHostConfig {
+        max_plugins: 1,
+        ..Default::default()
+    };
+    let shared = replicas.clone();
+    let mut host = BlindHost::new(config, move |linker, plugin| {
+        replicate::register_host_calls(linker, plugin.to_string(), shared.clone())
+    })
+    .unwrap();
+    host.init_heap();
+    host.load_plugin(name, wat.as_bytes()).unwrap();
+    (host, replicas)
+}
+
+fn read(host: &mut BlindHost, offset: i32) -> u32 {
+    let base = host.store.data().slots[0].1;
+    u32::from_le_bytes(host.read_mem(base + offset, 4).unwrap().try_into().unwrap())
+}
+
+fn wait_until(mut done: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn connect(transport: TransportKind) -> (BlindHost, SplitServer, BlindHost, SplitClient) {
+    let (mut core, core_replicas) = host("core", CORE);
+    let mut server = SplitServer::bind("127.0.0.1:0", &core, core_replicas, transport).unwrap();
+    let (view, view_replicas) = host("view", VIEW);
+    let client = SplitClient::connect(
+        &server.local_addr().to_string(),
+        &view,
+        view_replicas,
+        transport,
+    )
+    .unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        server.clients() == 1
+    });
+    (core, server, view, client)
+}
+
+#[test]
+fn clients_get_the_state_the_server_designates() {
+    let (mut core, mut server, mut view, mut client) = connect(TransportKind::Tcp);
+    assert_eq!(client.server_plugins(), ["core", "lobby"]);
+    for _ in 0..3 {
+        server.step(&mut core).unwrap();
+    }
+    let counter = read(&mut core, 16);
+    assert!(counter >= 4);
+
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        read(&mut view, 16) == counter
+    });
+}
+
+#[test]
+fn client_messages_reach_the_server_and_replies_come_back() {
+    let (mut core, mut server, mut view, mut client) = connect(TransportKind::Tcp);
+    let poke = view
+        .get_func("view", "poke")
+        .unwrap()
+        .typed::<(), i32>(&view.store)
+        .unwrap();
+    // "core" isn't loaded on the client, but it's on the server
+    assert_eq!(poke.call(&mut view.store, ()).unwrap(), 0);
+    client.exchange(&mut view).unwrap();
+
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        read(&mut core, 16) >= 100
+    });
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        bus::deliver(&mut view).unwrap();
+        read(&mut view, 64) == 1 && read(&mut view, 16) >= 100
+    });
+}
+
+#[test]
+fn clients_that_cant_reach_the_server_join_through_a_relay() {
+    let relay = relay::Relay::bind("127.0.0.1:0").unwrap();
+    let room = relay::Room::parse(&format!("{}/split", relay.local_addr().unwrap())).unwrap();
+    std::thread::spawn(move || relay.run());
+
+    let (mut core, core_replicas) = host("core", CORE);
+    let mut server =
+        SplitServer::bind("127.0.0.1:0", &core, core_replicas, TransportKind::Udp).unwrap();
+    server.serve_relay(&room).unwrap();
+    let (mut view, view_replicas) = host("view", VIEW);
+    let mut client = SplitClient::join_relay(&room, &view, view_replicas).unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        server.clients() == 1
+    });
+
+    let poke = view
+        .get_func("view", "poke")
+        .unwrap()
+        .typed::<(), i32>(&view.store)
+        .unwrap();
+    assert_eq!(poke.call(&mut view.store, ()).unwrap(), 0);
+    client.exchange(&mut view).unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        read(&mut core, 16) >= 100
+    });
+    let counter = read(&mut core, 16);
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        bus::deliver(&mut view).unwrap();
+        read(&mut view, 64) == 1 && read(&mut view, 16) == counter
+    });
+}
+
+#[test]
+fn udp_carries_state_in_datagrams_and_messages_over_tcp() {
+    let (mut core, mut server, mut view, mut client) = connect(TransportKind::Udp);
+    let poke = view
+        .get_func("view", "poke")
+        .unwrap()
+        .typed::<(), i32>(&view.store)
+        .unwrap();
+    assert_eq!(poke.call(&mut view.store, ()).unwrap(), 0);
+    client.exchange(&mut view).unwrap();
+
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        read(&mut core, 16) >= 100
+    });
+    let counter = read(&mut core, 16);
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        bus::deliver(&mut view).unwrap();
+        read(&mut view, 64) == 1 && read(&mut view, 16) == counter
+    });
+}
+
+#[test]
+fn quic_carries_state_in_datagrams_and_messages_on_a_stream() {
+    let (mut core, mut server, mut view, mut client) = connect(TransportKind::Quic);
+    assert_eq!(client.server_plugins(), ["core", "lobby"]);
+    let poke = view
+        .get_func("view", "poke")
+        .unwrap()
+        .typed::<(), i32>(&view.store)
+        .unwrap();
+    assert_eq!(poke.call(&mut view.store, ()).unwrap(), 0);
+    client.exchange(&mut view).unwrap();
+
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        read(&mut core, 16) >= 100
+    });
+    let counter = read(&mut core, 16);
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        bus::deliver(&mut view).unwrap();
+        read(&mut view, 64) == 1 && read(&mut view, 16) == counter
+    });
+
+    // TCP clients still get in
+    let (other, other_replicas) = host("view", VIEW);
+    let addr = server.local_addr().to_string();
+    let _other_client =
+        SplitClient::connect(&addr, &other, other_replicas, TransportKind::Tcp).unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        server.clients() == 2
+    });
+}
+
+#[test]
+fn chat_lines_reach_every_other_player() {
+    let (mut core, mut server, mut view, mut client) = connect(TransportKind::Tcp);
+    let (mut other, other_replicas) = host("view", VIEW);
+    let addr = server.local_addr().to_string();
+    let mut other_client =
+        SplitClient::connect(&addr, &other, other_replicas, TransportKind::Tcp).unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        server.clients() == 2
+    });
+
+    let hi = ChatLine::new("ada", "hi");
+    view.store.data().chat.lock().unwrap().post(hi.clone());
+    client.exchange(&mut view).unwrap();
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        core.store.data().chat.lock().unwrap().count() == 1
+    });
+    core.store
+        .data()
+        .chat
+        .lock()
+        .unwrap()
+        .post(ChatLine::new("core", "round one"));
+    wait_until(|| {
+        server.step(&mut core).unwrap();
+        other_client.exchange(&mut other).unwrap();
+        other.store.data().chat.lock().unwrap().count() == 2
+    });
+    let round_one = ChatLine::new("core", "round one");
+    assert_eq!(
+        other.store.data().chat.lock().unwrap().recent(10, None),
+        [hi.clone(), round_one.clone()]
+    );
+    assert_eq!(
+        core.store.data().chat.lock().unwrap().recent(10, None),
+        [hi.clone(), round_one.clone()]
+    );
+
+    // Its own line doesn't come back to the one who sent it
+    wait_until(|| {
+        client.exchange(&mut view).unwrap();
+        view.store.data().chat.lock().unwrap().count() == 2
+    });
+    assert_eq!(
+        view.store.data().chat.lock().unwrap().recent(10, None),
+        [hi, round_one]
+    );
+}
@@ -0,0 +1,215 @@
+// Unloading plugins (BlindHost::unload_plugin): their heap blocks and slot come back, and
+// what other plugins linked of theirs traps.
+
+mod common;
+
+use common::slot_of;
+use host::host::host_object::BlindHost;
+use wasmtime::Extern;
+
+// Takes host heap blocks, and keeps some static data in its slot
+const GAME: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (data (global.get $base) "saved")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "alloc") (param i32) (result i32) (call $alloc (local.get 0)))
+  (func (export "get") (param i32 i32) (result i64) i64.const 7))
+"#;
+
+// Links game::get and calls it through its table
+const UI: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "env" "__indirect_function_table" (table 1024 funcref))
+  (import "env" "host_link_call" (func $link (param i32 i32 i32 i32) (result i32)))
+  (type $export (func (param i32 i32) (result i64)))
+  (data (global.get $base) "gameget")
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "link") (result i32)
+    (call $link (global.get $base) (i32.const 4) (i32.add (global.get $base) (i32.const 4)) (i32.const 3)))
+  (func (export "call") (param $idx i32) (result i64)
+    (call_indirect (type $export) (i32.const 0) (i32.const 0) (local.get $idx))))
+"#;
+
+// Imports game::get when it's instantiated
+const IMPORTER: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "get" (func (param i32 i32) (result i64)))
+  (func (export "__abi_version") (result i32) i32.const 1))
+"#;
+
+const EMPTY: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (func (export "__abi_version") (result i32) i32.const 1))
+"#;
+
+fn host() -> BlindHost {
+    common::host(&[])
+}
+
+fn heap_free(host: &BlindHost) -> u64 {
+    host.store
+        .data()
+        .heap
+        .lock()
+        .unwrap()
+        .free_blocks
+        .iter()
+        .map(|block| block.size as u64)
+        .sum()
+}
+
+fn call<P: wasmtime::WasmParams, R: wasmtime::WasmResults>(
+    host: &mut BlindHost,
+    plugin: &str,
+    export: &str,
+    params: P,
+) -> anyhow::Result<R> {
+    let func = host.get_func(plugin, export).unwrap();
+    host.profiled(plugin, export, |store| {
+        func.typed::<P, R>(&*store)?.call(store, params)
+    })
+}
+
+#[test]
+fn unloading_frees_the_plugins_heap_blocks_and_slot() {
+    let mut host = host();
+    let free = heap_free(&host);
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    host.load_plugin("other", EMPTY.as_bytes()).unwrap();
+    let slot = slot_of(&host, "game");
+    for size in [64, 4096, 100] {
+        assert_ne!(
+            call::<i32, i32>(&mut host, "game", "alloc", size).unwrap(),
+            0
+        );
+    }
+    assert!(heap_free(&host) < free);
+    assert_eq!(host.view_mem(slot, 5).unwrap(), b"saved");
+
+    host.unload_plugin("game").unwrap();
+    assert_eq!(heap_free(&host), free);
+    assert_eq!(
+        host.store.data().metrics.lock().unwrap().live_bytes("game"),
+        0
+    );
+    assert!(host.get_func("game", "alloc").is_err());
+    assert_eq!(
+        host.view_mem(slot, 5).unwrap(),
+        [0; 5],
+        "the slot is zeroed"
+    );
+    assert!(host.unload_plugin("game").is_err());
+
+    // The next plugin takes the slot back instead of a new one
+    let next_offset = host.store.data().next_memory_offset;
+    host.load_plugin("later", EMPTY.as_bytes()).unwrap();
+    assert_eq!(slot_of(&host, "later"), slot);
+    assert_eq!(host.store.data().next_memory_offset, next_offset);
+    assert!(host.store.data().free_slots.is_empty());
+}
+
+#[test]
+fn what_others_linked_of_an_unloaded_plugin_traps() {
+    let mut host = host();
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    host.load_plugin("ui", UI.as_bytes()).unwrap();
+    let idx = call::<(), i32>(&mut host, "ui", "link", ()).unwrap();
+    assert_eq!(call::<i32, i64>(&mut host, "ui", "call", idx).unwrap(), 7);
+
+    host.unload_plugin("game").unwrap();
+    let trap = call::<i32, i64>(&mut host, "ui", "call", idx).unwrap_err();
+    assert!(
+        format!("{:?}", trap).contains("'game' was unloaded"),
+        "{:?}",
+        trap
+    );
+    assert!(
+        call::<(), i32>(&mut host, "ui", "link", ()).is_err(),
+        "there's no game to link against"
+    );
+    // Plugins loaded later import a stub in its place
+    let Some(Extern::Func(get)) = host.linker.get(&mut host.store, "env", "get") else {
+        panic!("env::get is gone");
+    };
+    assert!(get
+        .typed::<(i32, i32), i64>(&host.store)
+        .unwrap()
+        .call(&mut host.store, (0, 0))
+        .is_err());
+}
+
+#[test]
+fn plugins_importing_its_exports_are_unloaded_first() {
+    let mut host = host();
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    host.load_plugin("importer", IMPORTER.as_bytes()).unwrap();
+
+    let refused = host.unload_plugin("game").unwrap_err();
+    assert!(
+        refused.to_string().contains("imported by 'importer'"),
+        "{}",
+        refused
+    );
+    assert!(
+        host.get_func("game", "get").is_ok(),
+        "a refused unload leaves it loaded"
+    );
+
+    host.unload_plugin("importer").unwrap();
+    host.unload_plugin("game").unwrap();
+    assert_eq!(host.store.data().free_slots.len(), 2);
+}
+
+// Takes a heap block, then traps in init
+const BROKEN: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "host_alloc" (func $alloc (param i32) (result i32)))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "get") (param i32 i32) (result i64) i64.const 9)
+  (func (export "init") (drop (call $alloc (i32.const 64))) unreachable))
+"#;
+
+#[test]
+fn a_plugin_that_fails_to_load_leaves_nothing_behind() {
+    let mut host = host();
+    let free = heap_free(&host);
+    assert!(host.load_plugin("broken", BROKEN.as_bytes()).is_err());
+    let state = host.store.data();
+    assert!(state.slots.is_empty() && state.modules.is_empty() && state.tables.is_empty());
+    assert!(!state.instances.contains_key("broken") && !state.imported.contains_key("broken"));
+    assert!(!state.env_exports.contains_key("get"));
+    assert_eq!(heap_free(&host), free);
+
+    // Into the slot it gave back
+    let slot = state.free_slots[0];
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    assert_eq!(slot_of(&host, "game"), slot);
+}
+
+#[test]
+fn a_reload_that_fails_keeps_the_instance_it_was_to_replace() {
+    let mut host = host();
+    host.load_plugin("game", GAME.as_bytes()).unwrap();
+    let slot = slot_of(&host, "game");
+    let free = heap_free(&host);
+    assert!(host.load_plugin("game", BROKEN.as_bytes()).is_err());
+
+    let state = host.store.data();
+    assert_eq!(state.slots, [("game".to_string(), slot)]);
+    assert_eq!(state.modules.len(), 1);
+    assert_eq!(heap_free(&host), free);
+    let Some(Extern::Func(get)) = host.linker.get(&mut host.store, "env", "get") else {
+        panic!("game::get isn't linked");
+    };
+    let get = get.typed::<(i32, i32), i64>(&host.store).unwrap();
+    assert_eq!(get.call(&mut host.store, (0, 0)).unwrap(), 7);
+    host.unload_plugin("game").unwrap();
+}
@@ -0,0 +1,165 @@
+// WASI Preview 1 for plugins (host_calls/wasi.rs): stdout to the log, a sandboxed directory,
+// the host's random numbers, and ENOSYS for the rest.
+
+use host::host::host_object::{BlindHost, BlindHostConfig};
+use host::host::logger::{Level, LogConfig, Sink};
+use host::host::manifest::PluginManifest;
+use std::path::PathBuf;
+
+// At its base: a line for stdout at 0, a file name at 16, one out of its directory at 32, the
+// file's contents at 48, then an iovec at 64 and answers from 72 on
+const PLUGIN: &str = r#"
+(module
+  (import "env" "memory" (memory 1 16384 shared))
+  (import "env" "__memory_base" (global $base i32))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+  (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "clock_time_get" (func $clock (param i32 i64 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+  (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll (param i32 i32 i32 i32) (result i32)))
+  (data (global.get $base)
+    "hi there\n\00\00\00\00\00\00\00" "notes.txt\00\00\00\00\00\00\00"
+    "../x\00\00\00\00\00\00\00\00\00\00\00\00" "saved")
+  (func $at (param $off i32) (result i32) (i32.add (global.get $base) (local.get $off)))
+  (func $iov (param $off i32) (param $len i32) (result i32)
+    (i32.store (call $at (i32.const 64)) (call $at (local.get $off)))
+    (i32.store (call $at (i32.const 68)) (local.get $len))
+    (call $at (i32.const 64)))
+  ;; Creates (and truncates) the file named at `off`, for writing; its fd goes at 72
+  (func $open (param $off i32) (param $len i32) (result i32)
+    (call $path_open (i32.const 3) (i32.const 0) (call $at (local.get $off)) (local.get $len)
+      (i32.const 9) (i64.const 64) (i64.const 0) (i32.const 0) (call $at (i32.const 72))))
+  (func (export "__abi_version") (result i32) i32.const 1)
+  (func (export "hello") (result i32)
+    (call $fd_write (i32.const 1) (call $iov (i32.const 0) (i32.const 9)) (i32.const 1) (call $at (i32.const 72))))
+  (func (export "save") (result i32)
+    (local $errno i32)
+    (local.set $errno (call $open (i32.const 16) (i32.const 9)))
+    (if (local.get $errno) (then (return (local.get $errno))))
+    (local.set $errno
+      (call $fd_write
+        (i32.load (call $at (i32.const 72))) (call $iov (i32.const 48) (i32.const 5)) (i32.const 1)
+        (call $at (i32.const 76))))
+    (if (local.get $errno) (then (return (local.get $errno))))
+    (call $fd_close (i32.load (call $at (i32.const 72)))))
+  (func (export "escape") (result i32) (call $open (i32.const 32) (i32.const 4)))
+  (func (export "random") (result i64)
+    (drop (call $random_get (call $at (i32.const 80)) (i32.const 8)))
+    (i64.load (call $at (i32.const 80))))
+  (func (export "clock") (result i32) (call $clock (i32.const 1) (i64.const 0) (call $at (i32.const 80))))
+  (func (export "poll") (result i32) (call $poll (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+  (func (export "exit") (call $exit (i32.const 3))))
+"#;
+
+fn host(wasi_dir: Option<PathBuf>, deterministic: bool) -> BlindHost {
+    let config = BlindHostConfig {
+        enable_wasi: true,
+        wasi_dir,
+        deterministic,
+        random_seed: Some(7),
+        log: LogConfig {
+            sinks: Some(vec![Sink::Pane]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut host = BlindHost::new(config, |_, _| Ok(())).unwrap();
+    host.init_heap();
+    host.load_plugin("tool", PLUGIN.as_bytes()).unwrap();
+    host
+}
+
+fn call<R: wasmtime::WasmResults>(host: &mut BlindHost, export: &str) -> anyhow::Result<R> {
+    let func = host.get_func("tool", export).unwrap();
+    host.profiled("tool", export, |store| {
+        func.typed::<(), R>(&*store)?.call(store, ())
+    })
+}
+
+#[test]
+fn stdout_is_logged_and_files_stay_in_the_plugins_directory() {
+    let dir = std::env::temp_dir().join(format!("wasi-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut host = host(Some(dir.clone()), false);
+
+    assert_eq!(call::<i32>(&mut host, "hello").unwrap(), 0);
+    let line = host.recent_logs(1).pop().unwrap();
+    assert_eq!(
+        (line.plugin.as_str(), line.level, line.text.as_str()),
+        ("tool", Level::Info, "hi there")
+    );
+
+    assert_eq!(call::<i32>(&mut host, "save").unwrap(), 0);
+    assert_eq!(
+        std::fs::read(dir.join("tool").join("notes.txt")).unwrap(),
+        b"saved"
+    );
+    assert_eq!(call::<i32>(&mut host, "escape").unwrap(), 76, "ENOTCAPABLE");
+
+    assert_eq!(call::<i32>(&mut host, "clock").unwrap(), 0);
+    assert_eq!(call::<i32>(&mut host, "poll").unwrap(), 52, "ENOSYS");
+    let exit = call::<()>(&mut host, "exit").unwrap_err();
+    assert!(
+        format!("{:?}", exit).contains("'tool' exited with code 3"),
+        "{:?}",
+        exit
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn files_arent_created_through_links_out_of_the_plugins_directory() {
+    let dir = std::env::temp_dir().join(format!("wasi-link-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("tool")).unwrap();
+    // Dangling, so there's nothing behind it to canonicalize
+    std::os::unix::fs::symlink(dir.join("outside.txt"), dir.join("tool").join("notes.txt"))
+        .unwrap();
+    let mut host = host(Some(dir.clone()), false);
+
+    assert_eq!(call::<i32>(&mut host, "save").unwrap(), 76, "ENOTCAPABLE");
+    assert!(!dir.join("outside.txt").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn deterministic_hosts_draw_the_same_bytes_and_have_no_clock_or_directory() {
+    let mut first = host(Some(std::env::temp_dir()), true);
+    let mut second = host(None, true);
+    let drawn = call::<i64>(&mut first, "random").unwrap();
+    assert_eq!(call::<i64>(&mut second, "random").unwrap(), drawn);
+    assert!(call::<i32>(&mut first, "clock").is_err());
+    assert_eq!(
+        call::<i32>(&mut first, "save").unwrap(),
+        8,
+        "EBADF: nothing is preopened"
+    );
+}
+
+#[test]
+fn hosts_without_wasi_refuse_wasi_plugins() {
+    let mut host = BlindHost::new(BlindHostConfig::default(), |_, _| Ok(())).unwrap();
+    host.init_heap();
+    assert!(host.load_plugin("tool", PLUGIN.as_bytes()).is_err());
+}
+
+#[test]
+fn manifests_list_wasi_imports_by_module() {
+    let mut host = host(None, false);
+    let env_only = PluginManifest::parse(r#"imports = ["host_*"]"#).unwrap();
+    let error = host
+        .load_plugin_with_manifest("strict", PLUGIN.as_bytes(), &env_only)
+        .unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("wasi_snapshot_preview1::fd_write"),
+        "{:#}",
+        error
+    );
+    let wasi = PluginManifest::parse(r#"imports = ["wasi_snapshot_preview1::*"]"#).unwrap();
+    host.load_plugin_with_manifest("listed", PLUGIN.as_bytes(), &wasi)
+        .unwrap();
+}
@@ -1,5 +1,7 @@
 use ecs_client::{export_grid, register_plugin, App, Res, ResMut, Resource, Schedule};
 
+fat_ptr::export_abi_version!();
+
 // shared-structs/src/lib.rs
 // (Or put this at the top of my-game/src/lib.rs)
 
@@ -39,9 +41,8 @@ pub struct GameGrid {
 }
 
 // --- IDs ---
-// Host and Guest must agree on these manual IDs
-pub const GRID_RES_ID: i32 = 100;
-pub const INPUT_RES_ID: i32 = 101;
+// Names the host's ID registry maps to resource IDs, shared with anyone reading them
+pub const INPUT_RES_NAME: &str = "input_state";
 
 #[repr(C)]
 pub struct InputState {
@@ -50,22 +51,20 @@ pub struct InputState {
     pub reveal: bool, // Spacebar pressed?
     pub flag: bool,   // 'F' pressed?
 }
-// use shared_structs::{GameGrid, InputState, Cell, GRID_RES_ID, INPUT_RES_ID, MAX_WIDTH, MAX_HEIGHT};
+// use shared_structs::{GameGrid, InputState, Cell, MAX_WIDTH, MAX_HEIGHT};
 // (Pasting the structs here for a self-contained example if needed, but assuming import)
 
 // --- 1. RESOURCE WIRING ---
 
-// Tell ECS that GameGrid lives at ID 100
 impl Resource for GameGrid {
-    fn resource_id() -> i32 {
-        GRID_RES_ID
+    fn resource_name() -> &'static str {
+        ecs_protocol::RESOURCE_GRID
     }
 }
 
-// Tell ECS that InputState lives at ID 101
 impl Resource for InputState {
-    fn resource_id() -> i32 {
-        INPUT_RES_ID
+    fn resource_name() -> &'static str {
+        INPUT_RES_NAME
     }
 }
 
@@ -0,0 +1,7 @@
+use std::path::PathBuf;
+
+// Stubs for tasksapp-core's exports, see idl/tasksapp.idl
+fn main() {
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("tasksapp.rs");
+    idl::write_guest_bindings(&["../../idl/tasksapp.idl"], out).unwrap();
+}
@@ -0,0 +1,5 @@
+// `TasksappCore`: one method per tasksapp-core export, generated from idl/tasksapp.idl.
+// The host must load core as `tasksapp_core` (`--plugin tasksapp_core=...`).
+use tasksapp_net::*;
+
+include!(concat!(env!("OUT_DIR"), "/tasksapp.rs"));
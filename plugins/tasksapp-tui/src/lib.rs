@@ -0,0 +1,362 @@
+mod core_link;
+
+use crate::core_link::TasksappCore;
+use grid_protocol::driver::{granted_extensions, DoubleBuffer, GridDriver};
+use grid_protocol::{
+    export_grid_driver, GridCell, GridInput, GRID_EXT_STYLES, INPUT_KEY, KEY_BACKSPACE, KEY_DELETE,
+    KEY_DOWN, KEY_ENTER, KEY_ESC, KEY_UP, STYLE_BOLD,
+};
+use tasksapp_net::{
+    ListTasksRequest, NewTaskRequest, Priority, Task, TaskFilter, TaskPatch, LOCAL_SESSION,
+};
+
+// Colors (ANSI 256)
+const FG: u8 = 15;
+const BG: u8 = 0;
+const DIM: u8 = 8;
+const ACCENT: u8 = 14;
+const URGENT: u8 = 196;
+const SELECTED_BG: u8 = 4;
+
+// A task list on top of tasksapp-core. The host must load core as `tasksapp_core` before
+// the first tick (`--plugin tasksapp_core=... --driver tasksapp-tui=...`).
+//
+//   Up/Down, j/k    move            a            add a task (Enter saves, Esc cancels)
+//   Space/Enter     toggle done     p            cycle priority
+//   d/Delete        delete          r            reload from core
+struct TasksDriver {
+    width: i32,
+    height: i32,
+    cells: DoubleBuffer,
+    core: TasksappCore,
+    // In display order, with their depth in the subtask tree
+    rows: Vec<(usize, Task)>,
+    selected: usize,
+    scroll: usize,
+    mode: Mode,
+    status: String,
+    loaded: bool,
+    pending: Vec<GridInput>,
+}
+
+enum Mode {
+    Browse,
+    Adding(String),
+}
+
+impl GridDriver for TasksDriver {
+    fn new() -> Self {
+        let width = 80;
+        let height = 24;
+        Self {
+            width,
+            height,
+            cells: DoubleBuffer::new((width * height) as usize),
+            core: TasksappCore::default(),
+            rows: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            mode: Mode::Browse,
+            status: String::new(),
+            loaded: false,
+            pending: Vec::new(),
+        }
+    }
+
+    fn dimensions(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn grid(&mut self) -> &mut [GridCell] {
+        self.cells.front()
+    }
+
+    fn frame_counter(&self) -> Option<u64> {
+        Some(self.cells.counter())
+    }
+
+    // Core may not be loaded yet, so keys wait for the tick
+    fn handle_input(&mut self, input: &GridInput) {
+        if input.input_type == INPUT_KEY {
+            self.pending.push(*input);
+        }
+    }
+
+    // Bold header where the host can draw it
+    fn extensions(&self) -> u64 {
+        GRID_EXT_STYLES
+    }
+
+    fn tick(&mut self, _delta: f32) {
+        if !self.loaded {
+            self.reload();
+            self.loaded = true;
+        }
+        for input in std::mem::take(&mut self.pending) {
+            self.on_key(input.key_code);
+        }
+        self.draw();
+    }
+}
+
+impl TasksDriver {
+    fn on_key(&mut self, key: u32) {
+        self.status.clear();
+        match &mut self.mode {
+            Mode::Adding(title) => match key {
+                KEY_ENTER => {
+                    let title = std::mem::take(title);
+                    self.mode = Mode::Browse;
+                    self.add(title);
+                }
+                KEY_ESC => self.mode = Mode::Browse,
+                KEY_BACKSPACE => {
+                    title.pop();
+                }
+                c => {
+                    if let Some(c) = char::from_u32(c).filter(|c| !c.is_control()) {
+                        title.push(c);
+                    }
+                }
+            },
+            Mode::Browse => match key {
+                KEY_UP => self.selected = self.selected.saturating_sub(1),
+                KEY_DOWN => {
+                    self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+                }
+                KEY_ENTER => self.toggle(),
+                KEY_DELETE => self.delete(),
+                c => match char::from_u32(c) {
+                    Some('k') => self.selected = self.selected.saturating_sub(1),
+                    Some('j') => {
+                        self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1))
+                    }
+                    Some(' ') => self.toggle(),
+                    Some('d') => self.delete(),
+                    Some('p') => self.cycle_priority(),
+                    Some('a') => self.mode = Mode::Adding(String::new()),
+                    Some('r') => {
+                        self.reload();
+                        self.status = "Reloaded".to_string();
+                    }
+                    _ => {}
+                },
+            },
+        }
+    }
+
+    // Fetches every page and orders it as a tree: each task followed by its subtasks
+    fn reload(&mut self) {
+        let mut tasks = Vec::new();
+        let mut cursor = None;
+        loop {
+            let request = ListTasksRequest {
+                cursor,
+                limit: 0,
+                filter: TaskFilter::All,
+                session: None,
+            };
+            let Some(page) = self.core.list_tasks(&request) else {
+                self.status = format!("Could not list tasks: {}", self.core_failure());
+                break;
+            };
+            tasks.extend(page.tasks);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        self.rows.clear();
+        let ids: Vec<i32> = tasks.iter().map(|t| t.id).collect();
+        let mut stack: Vec<(usize, Task)> = tasks
+            .iter()
+            // Orphans (parent outside the list) show up at the top level
+            .filter(|t| t.parent_id.is_none_or(|parent| !ids.contains(&parent)))
+            .rev()
+            .map(|t| (0, t.clone()))
+            .collect();
+        while let Some((depth, task)) = stack.pop() {
+            stack.extend(
+                tasks
+                    .iter()
+                    .filter(|t| t.parent_id == Some(task.id))
+                    .rev()
+                    .map(|t| (depth + 1, t.clone())),
+            );
+            self.rows.push((depth, task));
+        }
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    // Why core's last answer was missing; a trap in core lands here instead of taking us down
+    fn core_failure(&mut self) -> String {
+        match self.core.take_error() {
+            Some(error) => error.to_string(),
+            None => "Core did not answer".to_string(),
+        }
+    }
+
+    fn selected_task(&self) -> Option<&Task> {
+        self.rows.get(self.selected).map(|(_, task)| task)
+    }
+
+    fn add(&mut self, title: String) {
+        if title.trim().is_empty() {
+            return;
+        }
+        let request = NewTaskRequest {
+            title,
+            priority: Priority::Regular,
+            completed: false,
+            due_at: None,
+            tags: Vec::new(),
+            session: LOCAL_SESSION,
+            parent_id: None,
+        };
+        self.status = match self.core.new_task(&request) {
+            Some(Ok(task)) => format!("Added #{}", task.id),
+            Some(Err(e)) => format!("Not added: {:?}", e),
+            None => self.core_failure(),
+        };
+        self.reload();
+        self.selected = self.rows.len().saturating_sub(1);
+    }
+
+    fn toggle(&mut self) {
+        let Some(task) = self.selected_task() else {
+            return;
+        };
+        let patch = TaskPatch {
+            id: task.id,
+            completed: Some(!task.completed),
+            ..Default::default()
+        };
+        self.update(patch);
+    }
+
+    fn cycle_priority(&mut self) {
+        let Some(task) = self.selected_task() else {
+            return;
+        };
+        let priority = match task.priority {
+            Priority::Low => Priority::Regular,
+            Priority::Regular => Priority::Urgent,
+            Priority::Urgent => Priority::Low,
+        };
+        let patch = TaskPatch {
+            id: task.id,
+            priority: Some(priority),
+            ..Default::default()
+        };
+        self.update(patch);
+    }
+
+    fn update(&mut self, patch: TaskPatch) {
+        self.status = match self.core.update_task(&patch) {
+            Some(Ok(task)) => format!("Updated #{}", task.id),
+            Some(Err(e)) => format!("Not updated: {:?}", e),
+            None => self.core_failure(),
+        };
+        // Parents may have been completed along with it
+        self.reload();
+    }
+
+    fn delete(&mut self) {
+        let Some(id) = self.selected_task().map(|t| t.id) else {
+            return;
+        };
+        self.status = match self.core.delete_task(id) {
+            Some(Ok(task)) => format!("Deleted \"{}\"", task.title),
+            Some(Err(e)) => format!("Not deleted: {:?}", e),
+            None => self.core_failure(),
+        };
+        self.reload();
+    }
+
+    fn draw(&mut self) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let list_height = height.saturating_sub(2);
+
+        // Keep the selection on screen
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+
+        let done = self.rows.iter().filter(|(_, t)| t.completed).count();
+        let header = format!(" Tasks  {}/{} done", done, self.rows.len());
+        let footer = match &self.mode {
+            Mode::Adding(title) => format!(" New task: {}_", title),
+            Mode::Browse if !self.status.is_empty() => format!(" {}", self.status),
+            Mode::Browse => " a add  space done  p priority  d delete  r reload".to_string(),
+        };
+
+        let cells = self.cells.back();
+        for cell in cells.iter_mut() {
+            *cell = GridCell {
+                character: ' ' as u32,
+                fg_color: FG,
+                bg_color: BG,
+                style: 0,
+            };
+        }
+
+        put(cells, width, 0, &header, ACCENT, BG);
+        if granted_extensions() & GRID_EXT_STYLES != 0 {
+            for cell in &mut cells[..width] {
+                cell.style = STYLE_BOLD;
+            }
+        }
+        for (line, (depth, task)) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_height)
+        {
+            let y = line - self.scroll + 1;
+            let fg = if task.completed {
+                DIM
+            } else if task.priority == Priority::Urgent {
+                URGENT
+            } else {
+                FG
+            };
+            let bg = if line == self.selected {
+                SELECTED_BG
+            } else {
+                BG
+            };
+            let mark = if task.completed { 'x' } else { ' ' };
+            let flag = match task.priority {
+                Priority::Urgent => "!",
+                Priority::Regular => "",
+                Priority::Low => "~",
+            };
+            let text = format!(" {}[{}] {}{}", "  ".repeat(*depth), mark, task.title, flag);
+            if bg != BG {
+                for cell in &mut cells[y * width..(y + 1) * width] {
+                    cell.bg_color = bg;
+                }
+            }
+            put(cells, width, y, &text, fg, bg);
+        }
+        put(cells, width, height - 1, &footer, DIM, BG);
+
+        self.cells.swap();
+    }
+}
+
+// Writes `text` at the start of row `y`, cut off at the grid edge
+fn put(cells: &mut [GridCell], width: usize, y: usize, text: &str, fg: u8, bg: u8) {
+    for (x, c) in text.chars().take(width).enumerate() {
+        let cell = &mut cells[y * width + x];
+        cell.character = c as u32;
+        cell.fg_color = fg;
+        cell.bg_color = bg;
+    }
+}
+
+export_grid_driver!(TasksDriver);